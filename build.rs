@@ -0,0 +1,43 @@
+//! Embeds the git commit, build profile and rustc version of the build, which the client
+//! records in every export (see `client::build_info`).
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // Builds from a source tarball have no .git; CI can pass the commit in instead
+    println!("cargo:rerun-if-env-changed=SYNAPSE_GIT_SHA");
+    let git_sha = env::var("SYNAPSE_GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| output("git", &["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    // A path that does not exist would rerun the script on every build
+    for path in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=SYNAPSE_GIT_SHA={}", git_sha);
+    println!(
+        "cargo:rustc-env=SYNAPSE_BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string())
+    );
+    println!("cargo:rustc-env=SYNAPSE_RUSTC_VERSION={}", rustc_version);
+}
+
+/// Trimmed standard output of `program args`, if it ran successfully
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
@@ -1,15 +1,54 @@
 use anyhow::{Context, Result};
-use clap::Parser;
 use colored::*;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "charts")]
+use synapse::client::charts;
 use synapse::client::{
-    init_logging_with_config, measurement_phase, warmup_phase, Config, NetworkSocket, Reporter,
-    Statistics, TcpNetworkSocket,
+    bundle, clock_sync_phase, connections, drilldown, endpoints, families, flows, impair,
+    init_logging_with_config, matrix, measurement_phase_with_options, output, pacing, pcap,
+    periodicity, pipeline, port_sweep, reflectors, retry, trend, warmup_phase, AnalyzeArgs,
+    BandwidthBudget, Baseline, BaselineOutcome, BudgetedSocket, Checkpointer, ClientError, Clock,
+    ClockSource, Command, CompareArgs, Comparison, Config, ConnectionStats, CpuThrottling,
+    Decomposition, DepthSweepArgs, DepthSweepSettings, DissectorArgs, EndpointResult, EnergyMeter,
+    Environment, ExitStatus, FamilyResult, Flow, FlowSocket, IdleSplit, ImpairSweepArgs,
+    IrqAffinity, MatrixArgs, MatrixRow, MeasurementOptions, MeasurementResult, NetworkSocket,
+    NumaPlacement, PcapCapture, PortRange, PortResult, ProgressStream, RawOutput, ReflectorPool,
+    ReflectorStats, Reporter, RunRecord, RunSummary, SloOutcome, Statistics, SweepSettings,
+    SystemMonitor, TcpNetworkSocket, UdpNetworkSocket, WatchdogSocket,
 };
-use tracing::{error, info};
+use synapse::protocol::{lua_dissector, Transport};
+use tracing::{error, info, warn};
+
+/// Context of an error that kept the client from reaching the server, so it exits with
+/// [`ExitStatus::Connection`]
+#[derive(Debug)]
+struct ConnectionFailed(String);
+
+impl std::fmt::Display for ConnectionFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConnectionFailed {}
+
+/// Results of a run broken down by connection, flow or reflector, for the runs that use
+/// several
+#[derive(Default)]
+struct Breakdown {
+    /// Per connection of --connections, or per flow of --flows
+    streams: Vec<ConnectionStats>,
+    /// Per reflector of --reflector
+    reflectors: Vec<ReflectorStats>,
+}
 
 fn main() {
     // Parse CLI arguments first
-    let config = Config::parse();
+    let config = Config::load();
 
     // Initialize structured logging with config options
     init_logging_with_config(&config.log_level, config.is_json_format());
@@ -18,17 +57,63 @@ fn main() {
     if let Err(e) = config.validate() {
         error!(error = %e, "Invalid configuration");
         eprintln!("Configuration error: {}", e);
-        std::process::exit(1);
+        std::process::exit(ExitStatus::Config.code());
     }
 
-    if let Err(e) = run(config) {
+    let result = match config.command.clone() {
+        Some(Command::Analyze(args)) => analyze(&config, &args),
+        Some(Command::Compare(args)) => compare(&config, &args).map(exit_unless_passed),
+        Some(Command::Matrix(args)) => run_matrix(&config, &args),
+        Some(Command::ImpairSweep(args)) => impair_sweep(&config, &args),
+        Some(Command::DepthSweep(args)) => depth_sweep(&config, &args),
+        Some(Command::Dissector(args)) => dissector(&config, &args),
+        None => {
+            log_traffic_estimate(&config);
+            match config.port_sweep {
+                Some(range) => port_sweep(&config, range),
+                None if config.compare_families => compare_families(&config),
+                None if config.each_address => each_address(&config),
+                None => run(config).map(exit_unless_passed),
+            }
+        }
+    };
+
+    if let Err(e) = result {
         error!(error = %e, "Application failed");
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(error_status(&e).code());
     }
 }
 
-fn run(config: Config) -> Result<()> {
+/// Exit with the code of `status`, unless it is a pass
+fn exit_unless_passed(status: ExitStatus) {
+    if status != ExitStatus::Pass {
+        info!(exit_code = status.code(), status = ?status, "Run did not pass");
+        std::process::exit(status.code());
+    }
+}
+
+/// Exit status of a client that failed with `error`
+fn error_status(error: &anyhow::Error) -> ExitStatus {
+    if error.is::<ConnectionFailed>() {
+        ExitStatus::Connection
+    } else if is_strict_loss(error) {
+        ExitStatus::Loss
+    } else {
+        ExitStatus::Error
+    }
+}
+
+/// Whether `error` is a run aborted by --strict-loss
+fn is_strict_loss(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<ClientError>(),
+        Some(ClientError::StrictLoss(_))
+    )
+}
+
+/// Measure and report a single run, returning how it fared against its thresholds
+fn run(config: Config) -> Result<ExitStatus> {
     info!(
         server = %config.server,
         packets = config.packets,
@@ -36,59 +121,1476 @@ fn run(config: Config) -> Result<()> {
         "Starting Synapse client"
     );
 
-    // Create and configure the TCP socket
-    let mut socket = TcpNetworkSocket::connect(&config.server)
-        .with_context(|| format!("Failed to connect to server at {}", config.server))?;
+    let throttling_at_start = CpuThrottling::current();
+    let energy_meter = if config.energy {
+        EnergyMeter::start()
+    } else {
+        None
+    };
+    let (result, breakdown) = execute(&config)?;
+    let energy = energy_meter.and_then(EnergyMeter::finish);
+    let throttling = CpuThrottling::current()
+        .zip(throttling_at_start)
+        .map(|(end, start)| end.since(&start));
+
+    // Analysis and reporting
+    info!("Calculating statistics");
+    let stats = Statistics::new(&result.latencies).with_context(|| {
+        format!(
+            "Failed to calculate statistics from {} latency measurements",
+            result.latencies.len()
+        )
+    })?;
+    let reporter = reporter(&config);
+
+    reporter
+        .print_results(
+            &stats,
+            result.lost_packets,
+            result.total_packets,
+            result.elapsed,
+            &result.latencies,
+        )
+        .context("Failed to print results")?;
+    if config.flows > 1 {
+        reporter
+            .print_flows(&breakdown.streams)
+            .context("Failed to print flow breakdown")?;
+    } else {
+        reporter
+            .print_connections(&breakdown.streams)
+            .context("Failed to print connection breakdown")?;
+    }
+    reporter
+        .print_reflectors(&breakdown.reflectors, &config.reflector_weights())
+        .context("Failed to print reflector breakdown")?;
+    if stats.count() > 0 {
+        reporter
+            .print_bucket_examples(
+                &result.latencies,
+                &result.sequences,
+                &result.send_offsets,
+                result.started_at,
+            )
+            .context("Failed to print example packets")?;
+        let slowest = drilldown::slowest(
+            result
+                .sequences
+                .iter()
+                .zip(&result.send_offsets)
+                .zip(&result.latencies)
+                .map(|((&sequence, &offset_ns), &latency_ns)| {
+                    (sequence, offset_ns, Some(latency_ns))
+                }),
+            result.started_at,
+            config.slowest,
+        );
+        reporter
+            .print_slowest_probes(&slowest, result.elapsed)
+            .context("Failed to print slowest probes")?;
+        let send_stats = Statistics::new(&result.send_durations)
+            .context("Failed to calculate send-complete statistics")?;
+        let recv_wait_stats = Statistics::new(&result.recv_wait_durations)
+            .context("Failed to calculate receive-wait statistics")?;
+        info!(
+            send_mean_ns = send_stats.mean(),
+            send_p99_ns = send_stats.percentile(0.99),
+            recv_wait_mean_ns = recv_wait_stats.mean(),
+            recv_wait_p99_ns = recv_wait_stats.percentile(0.99),
+            "RTT components"
+        );
+
+        println!();
+        reporter
+            .print_rtt_breakdown(&send_stats, &recv_wait_stats)
+            .context("Failed to print RTT breakdown")?;
+        reporter
+            .print_path_breakdown(&result.path_timings, result.clock_offset.as_ref())
+            .context("Failed to print path breakdown")?;
+        reporter
+            .print_server_processing(&result.path_timings, &stats)
+            .context("Failed to print server processing")?;
+        reporter
+            .print_kernel_breakdown(&result.kernel_timings)
+            .context("Failed to print kernel timestamp breakdown")?;
+        reporter
+            .print_send_path(&result.send_durations)
+            .context("Failed to print send path statistics")?;
+        if let Some(decomposition) = Decomposition::new(
+            stats.mean(),
+            &result.send_durations,
+            &result.kernel_timings,
+            &result.path_timings,
+        ) {
+            reporter
+                .print_latency_decomposition(&decomposition)
+                .context("Failed to print latency decomposition")?;
+        }
+    }
+
+    if stats.count() > 0 {
+        let buckets = trend::latency_over_time(
+            &result.latencies,
+            &result.send_offsets,
+            result.elapsed,
+            trend::TREND_COLUMNS,
+        );
+        reporter
+            .print_latency_over_time(&buckets)
+            .context("Failed to print latency over time")?;
+        let periods = periodicity::spike_periods(&result.latencies, &result.send_offsets);
+        reporter
+            .print_spike_periods(&periods)
+            .context("Failed to print latency periodicity")?;
+    }
+    if result.lost_packets >= periodicity::MIN_EVENTS {
+        let periods = periodicity::loss_periods(&result.lost_sequences, result.total_packets);
+        let packet_interval =
+            Duration::from_secs_f64(result.elapsed.as_secs_f64() / result.total_packets as f64);
+        reporter
+            .print_loss_pattern(&periods, packet_interval)
+            .context("Failed to print loss pattern")?;
+    }
+    if let Some(pacing) = &result.pacing {
+        reporter
+            .print_pacing(pacing, result.elapsed, result.total_packets)
+            .context("Failed to print pacing")?;
+        if stats.count() > 0 {
+            // Each connection paces at --rate, a share of the merged target
+            let threshold = config
+                .cold_gap()
+                .unwrap_or_else(|| pacing.interval() * config.connections as u32);
+            reporter
+                .print_idle_split(&IdleSplit::new(
+                    &result.latencies,
+                    &result.idle_gaps,
+                    threshold,
+                ))
+                .context("Failed to print idle gap split")?;
+        }
+    }
+    if let Some(dscp) = config.dscp {
+        reporter
+            .print_dscp(dscp, &result.reply_dscp, config.transport)
+            .context("Failed to print DSCP verification")?;
+    }
+    if let Some(backing) = result.sample_backing {
+        reporter
+            .print_sample_backing(backing)
+            .context("Failed to print sample buffer backing")?;
+    }
+    if let Some(usage) = &result.thread_usage {
+        reporter
+            .print_thread_usage(usage, result.elapsed, result.total_packets)
+            .context("Failed to print thread usage")?;
+    }
+    if let Some(throttling) = &throttling {
+        reporter
+            .print_cpu_throttling(throttling)
+            .context("Failed to print CPU throttling")?;
+    }
+    if let Some(energy) = &energy {
+        reporter
+            .print_energy(energy, config.carbon_intensity)
+            .context("Failed to print energy")?;
+    }
+    if let Some(timeline) = &result.system_timeline {
+        reporter
+            .print_system_events(timeline, &stats)
+            .context("Failed to print system events")?;
+    }
+    let caveats = Environment::detect().caveats(config.connections);
+    reporter
+        .print_caveats(&caveats)
+        .context("Failed to print environment caveats")?;
+    if let Some(target) = config.until_ci() {
+        reporter
+            .print_confidence(&stats, target, result.total_packets, config.packets)
+            .context("Failed to print confidence interval")?;
+    }
+    if let Some(policy) = config.retry_policy {
+        let probes = result
+            .sequences
+            .iter()
+            .zip(&result.latencies)
+            .map(|(&sequence, &latency_ns)| (sequence, Some(latency_ns)))
+            .chain(
+                result
+                    .lost_sequences
+                    .iter()
+                    .map(|&sequence| (sequence, None)),
+            );
+        let outcome = retry::replay(policy, probes, result.seed);
+        reporter
+            .print_retry_outcome(&outcome, &stats)
+            .context("Failed to print retry policy")?;
+    }
+
+    let mut objectives_met = true;
+    if !config.slo.is_empty() {
+        let outcomes: Vec<SloOutcome> = config
+            .slo
+            .iter()
+            .map(|&slo| SloOutcome::evaluate(slo, &result.latencies, result.lost_packets))
+            .collect();
+        reporter
+            .print_slo_report(&outcomes, result.elapsed)
+            .context("Failed to print SLO report card")?;
+        objectives_met &= outcomes.iter().all(SloOutcome::met);
+    }
+    if let Some(sla) = &config.sla {
+        let outcomes = sla.evaluate(&stats, result.lost_packets, result.total_packets);
+        reporter
+            .print_sla_report(&outcomes)
+            .context("Failed to print SLA policy")?;
+        objectives_met &= outcomes.iter().all(|outcome| outcome.met);
+    }
+    if let Some(dir) = &config.baseline_dir {
+        let outcome = Baseline::load(
+            dir,
+            &config.exported_server(),
+            config.baseline_window(),
+            result.started_at,
+        )?
+        .filter(|_| stats.count() > 0)
+        .map(|baseline| BaselineOutcome {
+            baseline,
+            p99_ns: stats.percentile(0.99),
+            max_change_pct: config.max_p99_change,
+        });
+        reporter
+            .print_baseline(outcome.as_ref(), config.baseline_hours)
+            .context("Failed to print baseline comparison")?;
+        objectives_met &= outcome.as_ref().is_none_or(BaselineOutcome::within);
+    }
+
+    println!(
+        "Seed: {} (reproduce this probe schedule with --seed {})",
+        result.seed, result.seed
+    );
+
+    let summary = RunSummary::new(
+        &stats,
+        result.lost_packets,
+        result.total_packets,
+        &result.send_durations,
+    )
+    .with_verdict(config.verdict())
+    .with_stages(output::stage_latencies(
+        &result.path_timings,
+        &result.kernel_timings,
+    ))
+    .with_caveats(&caveats)
+    .with_energy(energy.as_ref());
+    for line in output::render(config.output, &summary) {
+        println!("{}", line);
+    }
+    if let Some(path) = &config.raw_output {
+        println!("Raw samples written to {}", path.display());
+    }
+    if let Some(path) = &config.hgrm {
+        bundle::write_hgrm(path, &stats)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Percentile distribution written to {}", path.display());
+    }
+    #[cfg(feature = "charts")]
+    if let Some(dir) = &config.chart_dir {
+        let written = charts::write_charts(dir, &result)
+            .with_context(|| format!("Failed to write charts to {}", dir.display()))?;
+        if written.is_empty() {
+            println!("Charts: no probe was answered; nothing to chart");
+        } else {
+            println!("Charts written to {}:", dir.display());
+            for path in &written {
+                println!("  {}", path.display());
+            }
+        }
+    }
+    if let Some(dir) = &config.report_bundle {
+        let (record, summary) = summarize(&config, &result)?;
+        let manifest = bundle::write_bundle(dir, &record, &summary, &result)
+            .with_context(|| format!("Failed to write report bundle to {}", dir.display()))?;
+        reporter
+            .print_report_bundle(dir, &manifest)
+            .context("Failed to print report bundle")?;
+    }
+    if let Some(gateway) = config.pushgateway() {
+        gateway
+            .push(&summary)
+            .context("Failed to push metrics to the Pushgateway")?;
+        println!("Metrics pushed to {}", gateway.group_path());
+    }
+    #[cfg(feature = "otlp")]
+    if let Some(exporter) = config.otlp_exporter() {
+        exporter
+            .export(&result)
+            .context("Failed to export telemetry over OTLP")?;
+        println!("Trace and metrics exported over OTLP");
+    }
+
+    info!("Results reported successfully");
+    Ok(ExitStatus::of_run(
+        &summary,
+        config.max_loss,
+        objectives_met,
+    ))
+}
+
+/// Connect, warm up and run the measurement phase described by `config`
+///
+/// Also returns the breakdown per connection of a run over several connections, per flow
+/// of a run over several flows, or per reflector of a run over several reflectors.
+fn execute(config: &Config) -> Result<(MeasurementResult, Breakdown)> {
+    if config.connections > 1 {
+        return execute_concurrent(config);
+    }
+
+    // Pin before anything is allocated or spawned so buffers and helper threads follow
+    let numa = config.numa_node.map(NumaPlacement::apply).transpose()?;
+    let reporter = reporter(config);
+
+    // The seed is settled first, as the payload template draws its filler from it
+    let mut options = MeasurementOptions::new(config.packets, config.update, !config.live_view());
+    if let Some(seed) = config.seed {
+        options.seed = seed;
+    }
+    // With --flows or --reflector, the source address of every socket, to break the
+    // results down by; with --reflector, where each probe went as well
+    let (socket, flow, sources, pool) = if config.flows > 1 {
+        let (socket, flow) = connect_flows(config, options.seed)?;
+        let sources = socket
+            .local_addrs()
+            .context("Failed to read the source addresses of the flows")?;
+        (
+            Box::new(socket) as Box<dyn NetworkSocket>,
+            flow,
+            sources,
+            None,
+        )
+    } else if !config.reflectors.is_empty() {
+        let (pool, flow) = connect_reflectors(config, options.seed)?;
+        let sources = pool
+            .local_addrs()
+            .context("Failed to read the source addresses of the reflector sockets")?;
+        let routing = (pool.reflector_addrs().to_vec(), pool.routes());
+        (
+            Box::new(pool) as Box<dyn NetworkSocket>,
+            flow,
+            sources,
+            Some(routing),
+        )
+    } else {
+        let (socket, flow) = connect(config, options.seed)?;
+        (socket, flow, Vec::new(), None)
+    };
     socket
         .set_timeout(config.timeout())
         .with_context(|| format!("Failed to set socket timeout to {}ms", config.timeout_ms))?;
+    let budget = config.bandwidth_budget();
+    let socket = with_budget(socket, budget.as_ref(), config.transport);
+    let affinity = flow.and_then(|flow| IrqAffinity::detect(flow.local.ip()));
+    let affinity_warnings = affinity
+        .as_ref()
+        .map(IrqAffinity::warnings)
+        .unwrap_or_default();
+    if let Some(affinity) = &affinity {
+        info!(
+            interface = %affinity.interface,
+            nic_numa_node = ?affinity.nic_numa_node,
+            irq_cpus = ?affinity.irq_cpus,
+            "NIC locality"
+        );
+    }
+
+    let mut socket: Box<dyn NetworkSocket> = match config.watchdog() {
+        Some(stall_timeout) => Box::new(
+            WatchdogSocket::new(socket, stall_timeout).context("Failed to start watchdog")?,
+        ),
+        None => socket,
+    };
+
+    options.huge_pages = config.huge_pages;
+    options.strict_loss = config.strict_loss;
+    options.payload_size = config.probe_size();
+    options.theme = config.theme;
+    #[cfg(feature = "dashboard")]
+    {
+        options.dashboard = config.dashboard();
+    }
+    options.rate = config.rate;
+    options.traffic = config.traffic;
+    options.window = config.window;
+    options.clock = Clock::new(config.clock).context("Failed to set up the --clock source")?;
+    options.until_ci = config.until_ci();
+    options.transaction = config.transaction;
+    options.heartbeat = config.heartbeat();
+    options.progress_stream = config
+        .progress_json
+        .as_ref()
+        .map(|target| ProgressStream::open(target, config.update, config.packets))
+        .transpose()?;
+    info!(seed = options.seed, "Run seed");
 
     // Print header only if not in quiet mode
     if !config.quiet {
         println!("{}", "Synapse Application Diagnostic Tool".bold());
-        println!("Server: {}\n", config.server);
+        println!(
+            "Server: {} ({}, {}-byte packets)",
+            config.server,
+            config.transport,
+            config.probe_size()
+        );
+        if let Some(template) = config.payload_template {
+            println!("Payload: {} message", template.kind);
+        }
+        if config.window > 1 {
+            println!("Window: {} probes in flight (open loop)", config.window);
+        }
+        if let Some(transaction) = config.transaction {
+            println!(
+                "Transaction: {} requests, {} responses (completion time of each)",
+                transaction.requests, transaction.responses
+            );
+        }
+        if let Some(target) = options.until_ci {
+            println!(
+                "Until: 95% confidence interval of the {} within ±{}% (at most {} probes)",
+                target.metric, target.half_width_pct, config.packets
+            );
+        }
+        if config.flows > 1 {
+            println!("Flows: {} UDP source ports, taking turns", config.flows);
+        }
+        if config.clock != ClockSource::Monotonic {
+            println!("Clock: {}", options.clock);
+        }
+        if !config.reflectors.is_empty() {
+            println!(
+                "Reflectors: {}, {} (taking turns, failing over from any that is down)",
+                config.server,
+                config.reflectors.join(", ")
+            );
+        }
+        print_bandwidth_cap(config);
+        if is_lone_run(config) {
+            print_traffic_estimate(config);
+        }
+        // A name with several addresses is measured at one of them only
+        if let Ok(addresses) = endpoints::resolve_all(&config.server) {
+            if addresses.len() > 1 {
+                println!(
+                    "Note: {} resolves to {} addresses and only one is measured; --each-address measures them all",
+                    config.server,
+                    addresses.len()
+                );
+            }
+        }
+        reporter
+            .print_numa_locality(numa.as_ref(), affinity.as_ref())
+            .context("Failed to print NUMA locality")?;
+        println!();
+        reporter
+            .print_affinity_warnings(&affinity_warnings)
+            .context("Failed to print affinity warnings")?;
+    } else {
+        for warning in &affinity_warnings {
+            warn!(warning = %warning, "Measuring thread placement distorts results");
+        }
     }
 
     // Warmup phase
-    info!(warmup_count = config.warmup, "Starting warmup phase");
-    warmup_phase(&mut socket, config.warmup, config.quiet).context("Warmup phase failed")?;
+    info!(warmup = %config.warmup, "Starting warmup phase");
+    let warmup_started_at = SystemTime::now();
+    let warmup_start = Instant::now();
+    let warmup_probes = warmup_phase(
+        &mut socket,
+        config.warmup,
+        config.warmup_tolerance_pct(),
+        config.probe_size(),
+        !config.live_view(),
+    )
+    .context("Warmup phase failed")?;
+    let warmup = (warmup_probes > 0).then(|| (warmup_started_at, warmup_start.elapsed()));
     info!("Warmup phase completed");
 
+    if config.clock_sync > 0 {
+        info!(
+            exchanges = config.clock_sync,
+            "Estimating server clock offset"
+        );
+        options.clock_offset =
+            clock_sync_phase(&mut socket, config.clock_sync, config.probe_size())
+                .context("Clock sync phase failed")?;
+    }
+
     // Measurement phase
     info!(
         packet_count = config.packets,
         update_interval = config.update,
         "Starting measurement phase"
     );
-    let result = measurement_phase(&mut socket, config.packets, config.update, config.quiet)
-        .context("Measurement phase failed")?;
+    if let Some(path) = &config.checkpoint {
+        let checkpointer = Checkpointer::new(
+            path,
+            config.checkpoint_interval(),
+            &config.exported_server(),
+            config.packets,
+        )
+        .with_context(|| format!("Failed to write checkpoint to {}", path.display()))?
+        .with_tags(config.tags());
+        options.checkpointer = Some(if config.record_packets {
+            checkpointer.with_packet_timestamps(config.probe_size())
+        } else {
+            checkpointer
+        });
+    }
+    if let Some(path) = &config.raw_output {
+        options.raw_output = Some(
+            RawOutput::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?,
+        );
+    }
+    if config.watch_system {
+        // Started here, on the measuring thread, so its counters are the ones sampled
+        options.system_monitor = SystemMonitor::start();
+    }
+    if config.pcap.is_some() && config.redact {
+        warn!("A packet capture holds the raw addresses; --redact does not apply to it");
+    }
+    // Captured after warmup so every sequence number in the file is unique
+    let capture = match (&config.pcap, flow) {
+        (Some(path), Some(flow)) => Some(PcapCapture::start(path, flow)?),
+        (Some(_), None) => {
+            warn!("Connection addresses unavailable; not capturing");
+            None
+        }
+        (None, _) => None,
+    };
+    let result = measurement_phase_with_options(&mut socket, options);
+    // Finish the capture even when the measurement failed; that is when it is needed most
+    if let Some(capture) = capture {
+        let path = capture.path().to_path_buf();
+        let stats = capture.finish()?;
+        if !config.quiet {
+            reporter
+                .print_capture(&path, &stats)
+                .context("Failed to print capture summary")?;
+        }
+    }
+    let mut result = result.context("Measurement phase failed")?;
+    result.warmup = warmup;
+    info!(
+        packets_received = result.latencies.len(),
+        packets_lost = result.lost_packets,
+        elapsed_secs = result.elapsed.as_secs_f64(),
+        "Measurement phase completed"
+    );
+    log_budget(budget.as_ref());
+    let breakdown = match pool {
+        Some((reflectors, routes)) => Breakdown {
+            reflectors: reflectors::reflector_stats(&result, &routes, &reflectors, &sources)
+                .context("Failed to break results down by reflector")?,
+            ..Breakdown::default()
+        },
+        None if sources.is_empty() => Breakdown::default(),
+        None => Breakdown {
+            streams: flows::flow_stats(&result, &sources)
+                .context("Failed to break results down by flow")?,
+            ..Breakdown::default()
+        },
+    };
+    Ok((result, breakdown))
+}
+
+/// Measure `--connections` connections at once, each from a thread of its own
+///
+/// Every connection warms up on its own; their measurement phases start together.
+fn execute_concurrent(config: &Config) -> Result<(MeasurementResult, Breakdown)> {
+    // Threads inherit the placement of the thread that spawns them
+    let _numa = config.numa_node.map(NumaPlacement::apply).transpose()?;
+    let seed = config
+        .seed
+        .unwrap_or_else(|| MeasurementOptions::new(0, 0, true).seed);
+    info!(connections = config.connections, seed = seed, "Run seed");
+    let clock = Clock::new(config.clock).context("Failed to set up the --clock source")?;
+    if !config.quiet {
+        println!("{}", "Synapse Application Diagnostic Tool".bold());
+        println!(
+            "Server: {} ({}, {}-byte packets, {} connections)",
+            config.server,
+            config.transport,
+            config.probe_size(),
+            config.connections
+        );
+        if let Some(template) = config.payload_template {
+            println!("Payload: {} message", template.kind);
+        }
+        if config.window > 1 {
+            println!("Window: {} probes in flight (open loop)", config.window);
+        }
+        if config.clock != ClockSource::Monotonic {
+            println!("Clock: {}", clock);
+        }
+        print_bandwidth_cap(config);
+        if is_lone_run(config) {
+            print_traffic_estimate(config);
+        }
+        println!(
+            "Warming up and measuring {} connections of {} packets each...\n",
+            config.connections, config.packets
+        );
+    }
+
+    // One budget for all connections, so the cap holds for their sum
+    let budget = config.bandwidth_budget();
+    let start = Barrier::new(config.connections);
+    let outcomes = thread::scope(|scope| {
+        let workers: Vec<_> = (0..config.connections)
+            .map(|index| {
+                let (start, budget) = (&start, budget.as_ref());
+                thread::Builder::new()
+                    .name(format!("synapse-conn-{}", index))
+                    .spawn_scoped(scope, move || {
+                        measure_connection(config, index, seed, clock, start, budget)
+                    })
+            })
+            .collect::<std::io::Result<_>>()
+            .context("Failed to start connection threads")?;
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Connection thread panicked")))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let connections = outcomes
+        .iter()
+        .enumerate()
+        .map(|(index, (local, result))| ConnectionStats::new(index, *local, result))
+        .collect::<synapse::client::Result<Vec<_>>>()?;
+    let result = connections::merge(outcomes.into_iter().map(|(_, result)| result).collect());
     info!(
+        connections = connections.len(),
         packets_received = result.latencies.len(),
         packets_lost = result.lost_packets,
         elapsed_secs = result.elapsed.as_secs_f64(),
         "Measurement phase completed"
     );
+    log_budget(budget.as_ref());
+    Ok((
+        result,
+        Breakdown {
+            streams: connections,
+            ..Breakdown::default()
+        },
+    ))
+}
 
-    // Analysis and reporting
-    info!("Calculating statistics");
-    let stats = Statistics::new(&result.latencies).with_context(|| {
-        format!(
-            "Failed to calculate statistics from {} latency measurements",
-            result.latencies.len()
+/// Connect, warm up and measure connection `index` of a concurrent run
+///
+/// Waits at `start` for every other connection to be ready, even if its own setup
+/// failed, so one failure cannot leave the others waiting. Connection `index` draws
+/// its randomized choices from `seed + index`, times its round trips with `clock` and
+/// sends its probes within `budget`.
+fn measure_connection(
+    config: &Config,
+    index: usize,
+    seed: u64,
+    clock: Clock,
+    start: &Barrier,
+    budget: Option<&BandwidthBudget>,
+) -> Result<(Option<SocketAddr>, MeasurementResult)> {
+    let seed = seed.wrapping_add(index as u64);
+    let setup = (|| {
+        let (socket, flow) = connect(config, seed)?;
+        socket
+            .set_timeout(config.timeout())
+            .with_context(|| format!("Failed to set socket timeout to {}ms", config.timeout_ms))?;
+        let socket = with_budget(socket, budget, config.transport);
+        let mut socket: Box<dyn NetworkSocket> = match config.watchdog() {
+            Some(stall_timeout) => Box::new(
+                WatchdogSocket::new(socket, stall_timeout).context("Failed to start watchdog")?,
+            ),
+            None => socket,
+        };
+        warmup_phase(
+            &mut socket,
+            config.warmup,
+            config.warmup_tolerance_pct(),
+            config.probe_size(),
+            true,
         )
+        .context("Warmup phase failed")?;
+        let clock_offset = if config.clock_sync > 0 {
+            clock_sync_phase(&mut socket, config.clock_sync, config.probe_size())
+                .context("Clock sync phase failed")?
+        } else {
+            None
+        };
+        anyhow::Ok((socket, flow, clock_offset))
+    })();
+    start.wait();
+    // The cause goes into the message, as only the outermost context is printed
+    let (mut socket, flow, clock_offset) = setup.map_err(|e| {
+        let message = format!("Connection {}: {:#}", index, e);
+        if e.is::<ConnectionFailed>() {
+            anyhow::Error::new(ConnectionFailed(message))
+        } else {
+            anyhow::anyhow!(message)
+        }
     })?;
-    let reporter = Reporter;
+
+    let mut options = MeasurementOptions::new(config.packets, config.update, true);
+    options.seed = seed;
+    options.huge_pages = config.huge_pages;
+    options.strict_loss = config.strict_loss;
+    options.payload_size = config.probe_size();
+    options.clock_offset = clock_offset;
+    options.theme = config.theme;
+    options.rate = config.rate;
+    options.traffic = config.traffic;
+    options.window = config.window;
+    options.clock = clock;
+    options.transaction = config.transaction;
+    let result = measurement_phase_with_options(&mut socket, options).map_err(|e| {
+        let message = format!("Connection {}: Measurement phase failed: {}", index, e);
+        match e {
+            // Kept as the cause, so the client exits with the status of a loss
+            ClientError::StrictLoss(_) => anyhow::Error::new(e).context(message),
+            _ => anyhow::anyhow!(message),
+        }
+    })?;
+    Ok((flow.map(|flow| flow.local), result))
+}
+
+fn analyze(config: &Config, args: &AnalyzeArgs) -> Result<()> {
+    let reporter = reporter(config);
+    info!(path = %args.path.display(), "Analyzing checkpoint");
+
+    let record = RunRecord::load(&args.path)
+        .with_context(|| format!("Failed to read checkpoint {}", args.path.display()))?;
+    let stats = record
+        .statistics()
+        .context("Failed to rebuild statistics from checkpoint")?;
+
+    println!("{}", "Synapse Checkpoint Analysis".bold());
+    println!("File:   {}", args.path.display());
+    println!("Server: {}", record.server);
+    if let Some(seed) = record.seed {
+        println!("Seed:   {}", seed);
+    }
+    if record.complete {
+        println!("Status: complete ({} packets)", record.sent_packets);
+    } else {
+        println!(
+            "Status: {} (stopped after {} of {} packets)",
+            config.theme.warning("incomplete").bold(),
+            record.sent_packets,
+            record.planned_packets
+        );
+    }
 
     reporter
-        .print_results(
+        .print_results_from_histogram(
             &stats,
-            result.lost_packets,
-            result.total_packets,
-            result.elapsed,
-            &result.latencies,
+            record.lost_packets,
+            record.sent_packets,
+            record.elapsed(),
         )
         .context("Failed to print results")?;
 
-    info!("Results reported successfully");
+    if args.slowest > 0 {
+        match &record.packets {
+            Some(packets) => {
+                let started_at = UNIX_EPOCH + Duration::from_nanos(packets.started_at_unix_ns);
+                let slowest = drilldown::slowest(
+                    packets
+                        .sequences
+                        .iter()
+                        .zip(&packets.send_offsets_ns)
+                        .zip(&packets.rtts_ns)
+                        .map(|((&sequence, &offset_ns), &rtt_ns)| (sequence, offset_ns, rtt_ns)),
+                    started_at,
+                    args.slowest,
+                );
+                reporter
+                    .print_slowest_probes(&slowest, record.elapsed())
+                    .context("Failed to print slowest probes")?;
+            }
+            None => println!(
+                "\nSlowest probes: not recorded; write the checkpoint with --record-packets"
+            ),
+        }
+    }
+
+    // Records written before the RTT was split carry no component histograms
+    if stats.count() > 0 {
+        let send_stats = record
+            .send_statistics()
+            .context("Failed to rebuild send-complete statistics from checkpoint")?;
+        let recv_wait_stats = record
+            .recv_wait_statistics()
+            .context("Failed to rebuild receive-wait statistics from checkpoint")?;
+        println!();
+        reporter
+            .print_rtt_breakdown(&send_stats, &recv_wait_stats)
+            .context("Failed to print RTT breakdown")?;
+    }
+
+    if let Some(pcap_path) = &args.pcap {
+        let packets = record.packet_timestamps()?;
+        let server_port = record
+            .server_port()
+            .with_context(|| format!("Cannot tell the server port from '{}'", record.server))?;
+        let frames = pcap::read_capture(pcap_path)?;
+        info!(
+            path = %pcap_path.display(),
+            frames = frames.len(),
+            packets = packets.len(),
+            "Correlating capture with run"
+        );
+        let timings = pcap::correlate(packets, &frames, server_port);
+        reporter
+            .print_wire_timings(&timings)
+            .context("Failed to print wire timings")?;
+        if let Some(csv_path) = &args.packets_csv {
+            pcap::write_timings_csv(csv_path, &timings)
+                .with_context(|| format!("Failed to write {}", csv_path.display()))?;
+            println!("Per-packet timestamps written to {}", csv_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare two saved runs; a regression beyond the tolerance is a threshold breach
+fn compare(config: &Config, args: &CompareArgs) -> Result<ExitStatus> {
+    args.validate().context("Invalid compare arguments")?;
+    let load = |path: &std::path::Path| {
+        RunRecord::load(path)
+            .with_context(|| format!("Failed to read run record {}", path.display()))
+    };
+    let (baseline, current) = (load(&args.baseline)?, load(&args.current)?);
+    for (path, record) in [(&args.baseline, &baseline), (&args.current, &current)] {
+        if !record.complete {
+            warn!(path = %path.display(), "Comparing an incomplete run");
+        }
+    }
+    let comparison = Comparison::new(
+        &baseline,
+        &current,
+        &config.percentiles,
+        config.buckets.as_ref(),
+    )
+    .context("Failed to rebuild statistics from the run records")?;
+
+    reporter(config)
+        .print_comparison(
+            &comparison,
+            args.tolerance,
+            (
+                &args.baseline.display().to_string(),
+                &args.current.display().to_string(),
+            ),
+        )
+        .context("Failed to print run comparison")?;
+    Ok(if comparison.regressions(args.tolerance).is_empty() {
+        ExitStatus::Pass
+    } else {
+        ExitStatus::Breach
+    })
+}
+
+fn dissector(config: &Config, args: &DissectorArgs) -> Result<()> {
+    let port = match args.port {
+        Some(port) => port,
+        None => config
+            .server
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .with_context(|| format!("Cannot tell the server port from '{}'", config.server))?,
+    };
+    let lua = lua_dissector(port);
+    match &args.out {
+        Some(path) => {
+            fs::write(path, lua).with_context(|| format!("Failed to write {}", path.display()))?;
+            info!(path = %path.display(), port = port, "Dissector written");
+            println!(
+                "Dissector for port {} written to {}; load it with `wireshark -X lua_script:{}`",
+                port,
+                path.display(),
+                path.display()
+            );
+        }
+        None => print!("{}", lua),
+    }
+    Ok(())
+}
+
+fn impair_sweep(config: &Config, args: &ImpairSweepArgs) -> Result<()> {
+    args.validate().context("Invalid impair-sweep arguments")?;
+    let reporter = reporter(config);
+    info!(
+        interface = %args.interface,
+        profiles = args.profiles.len(),
+        "Starting impairment sweep"
+    );
+
+    println!("{}", "Synapse Impairment Sweep".bold());
+    println!("Server:    {}", config.server);
+    println!("Interface: {}", args.interface);
+    println!(
+        "Profiles:  {} (+ baseline), {} packets each\n",
+        args.profiles.len(),
+        args.packets
+    );
+
+    let settings = SweepSettings {
+        server: config.server.clone(),
+        interface: args.interface.clone(),
+        timeout: config.timeout(),
+        warmup: args.warmup,
+        packets: args.packets,
+        transport: config.transport,
+        payload_size: config.probe_size(),
+    };
+    // The tc error (missing privileges, no netem module) is the useful part; keep it visible
+    let results = impair::run_sweep(&settings, &args.profiles)?;
+
+    reporter
+        .print_impair_sweep(&results, args.passes(), config.transport)
+        .context("Failed to print sweep results")?;
     Ok(())
 }
+
+fn depth_sweep(config: &Config, args: &DepthSweepArgs) -> Result<()> {
+    // The reason (UDP, depth too large for the payload) is the useful part of the error
+    args.validate(config.transport, config.probe_size())?;
+    let reporter = reporter(config);
+    let depths = pipeline::depths(args.max_depth);
+    info!(
+        max_depth = args.max_depth,
+        depths = depths.len(),
+        "Starting depth sweep"
+    );
+
+    println!("{}", "Synapse Depth Sweep".bold());
+    println!("Server: {}", config.server);
+    println!(
+        "Depths: {}, {} packets each\n",
+        depths
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        args.packets
+    );
+
+    let settings = DepthSweepSettings {
+        server: config.server.clone(),
+        timeout: config.timeout(),
+        warmup: args.warmup,
+        packets: args.packets,
+        payload_size: config.probe_size(),
+    };
+    let results = pipeline::run_sweep(&settings, &depths)?;
+
+    reporter
+        .print_depth_sweep(&results)
+        .context("Failed to print sweep results")?;
+    Ok(())
+}
+
+fn port_sweep(config: &Config, range: PortRange) -> Result<()> {
+    let reporter = reporter(config);
+    info!(
+        server = %config.server,
+        ports = %range,
+        "Starting port sweep"
+    );
+
+    println!("{}", "Synapse Port Sweep".bold());
+    println!("Server: {}", config.server);
+    println!(
+        "Ports:  {} ({} ports), {} packets each",
+        range,
+        range.count(),
+        config.packets
+    );
+    print_traffic_estimate(config);
+    println!();
+
+    let total = range.count();
+    let mut results = Vec::with_capacity(total);
+    for (i, port) in range.ports().enumerate() {
+        println!("[{}/{}] port {}", i + 1, total, port);
+        info!(port = port, "Measuring port");
+
+        let mut port_config = config.clone();
+        port_config.server = port_sweep::with_port(&config.server, port)?;
+        let outcome = execute_summary(&port_config, ("port", &port.to_string()));
+
+        if let Err(e) = &outcome {
+            warn!(port = port, error = %e, "Port measurement failed");
+        }
+        results.push(PortResult {
+            port,
+            outcome: outcome.map_err(|e| format!("{:#}", e)),
+        });
+    }
+
+    reporter
+        .print_port_sweep(&results)
+        .context("Failed to print sweep results")?;
+    Ok(())
+}
+
+/// Run one measurement of a sweep and summarize it
+///
+/// With a Pushgateway, the summary is pushed under the extra grouping `label`, so the
+/// runs of a sweep do not overwrite one another.
+fn execute_summary(config: &Config, (label, value): (&str, &str)) -> Result<RunSummary> {
+    let (result, _) = execute(config)?;
+    let stats = Statistics::new(&result.latencies)?;
+    let summary = RunSummary::new(
+        &stats,
+        result.lost_packets,
+        result.total_packets,
+        &result.send_durations,
+    )
+    .with_verdict(config.verdict())
+    .with_stages(output::stage_latencies(
+        &result.path_timings,
+        &result.kernel_timings,
+    ));
+    if let Some(gateway) = config.pushgateway() {
+        gateway
+            .with_label(label, value)
+            .push(&summary)
+            .context("Failed to push metrics to the Pushgateway")?;
+    }
+    Ok(summary)
+}
+
+fn compare_families(config: &Config) -> Result<()> {
+    let reporter = reporter(config);
+    info!(server = %config.server, "Starting IPv4/IPv6 comparison");
+
+    let endpoints = families::resolve(&config.server)?;
+    println!("{}", "Synapse IPv4 vs IPv6".bold());
+    println!("Server: {}", config.server);
+    println!("Packets: {} per family", config.packets);
+    print_traffic_estimate(config);
+    println!();
+
+    let mut results = Vec::with_capacity(endpoints.len());
+    for (family, address) in endpoints {
+        let outcome = match address {
+            Some(address) => {
+                println!("[{}] {}", family, address);
+                info!(family = %family, address = %address, "Measuring family");
+                let mut family_config = config.clone();
+                family_config.server = address.to_string();
+                execute_summary(&family_config, ("family", &family.to_string()))
+                    .map_err(|e| format!("{:#}", e))
+            }
+            None => {
+                println!("[{}] no {} record", family, family.record());
+                Err(format!(
+                    "{} has no {} record",
+                    config.server,
+                    family.record()
+                ))
+            }
+        };
+
+        if let Err(e) = &outcome {
+            warn!(family = %family, error = %e, "Family measurement failed");
+        }
+        results.push(FamilyResult {
+            family,
+            address,
+            outcome,
+        });
+    }
+
+    reporter
+        .print_family_comparison(&results)
+        .context("Failed to print family comparison")?;
+    Ok(())
+}
+
+fn each_address(config: &Config) -> Result<()> {
+    let reporter = reporter(config);
+    info!(server = %config.server, "Starting per-address measurement");
+
+    let addresses = endpoints::resolve_all(&config.server)?;
+    println!("{}", "Synapse DNS Endpoints".bold());
+    println!("Server: {}", config.server);
+    println!(
+        "Addresses: {}, {} packets each",
+        addresses.len(),
+        config.packets
+    );
+    print_traffic_estimate(config);
+    println!();
+
+    let total = addresses.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, address) in addresses.into_iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, total, address);
+        info!(address = %address, "Measuring address");
+
+        let mut address_config = config.clone();
+        address_config.server = address.to_string();
+        let outcome = execute_summary(&address_config, ("address", &address.to_string()));
+
+        if let Err(e) = &outcome {
+            warn!(address = %address, error = %e, "Address measurement failed");
+        }
+        results.push(EndpointResult {
+            address,
+            outcome: outcome.map_err(|e| format!("{:#}", e)),
+        });
+    }
+
+    reporter
+        .print_endpoints(&results)
+        .context("Failed to print per-address results")?;
+    Ok(())
+}
+
+fn run_matrix(config: &Config, args: &MatrixArgs) -> Result<()> {
+    let reporter = reporter(config);
+    let base_args = matrix::base_args(std::env::args().skip(1));
+    let combinations = matrix::combinations(&args.vary);
+
+    // Reject an unknown flag or invalid value before the first run starts
+    let configs = combinations
+        .iter()
+        .map(|combination| matrix::run_config(&base_args, combination))
+        .collect::<synapse::client::Result<Vec<_>>>()?;
+    fs::create_dir_all(&args.out_dir)
+        .with_context(|| format!("Failed to create {}", args.out_dir.display()))?;
+
+    println!("{}", "Synapse Experiment Matrix".bold());
+    println!("Runs:   {}", combinations.len());
+    println!("Output: {}\n", args.out_dir.display());
+    info!(runs = combinations.len(), "Starting matrix");
+
+    let total = combinations.len();
+    let mut rows = Vec::with_capacity(total);
+    for (i, (combination, config)) in combinations.into_iter().zip(configs).enumerate() {
+        let label = matrix::label(&combination);
+        println!("[{}/{}] {}", i + 1, total, label);
+        info!(run = i + 1, parameters = %label, "Starting matrix run");
+
+        let redactor = config.redactor();
+        let combination = match &redactor {
+            Some(redactor) => matrix::redacted(&combination, redactor),
+            None => combination,
+        };
+        let record_path = args
+            .out_dir
+            .join(matrix::record_file_name(i + 1, &combination));
+        let outcome = execute(&config).and_then(|(result, _)| {
+            let (record, summary) = summarize(&config, &result)?;
+            record
+                .save(&record_path)
+                .with_context(|| format!("Failed to write {}", record_path.display()))?;
+            if let Some(gateway) = config.pushgateway() {
+                // Each run gets its own group, so runs do not overwrite one another
+                combination
+                    .iter()
+                    .fold(gateway, |gateway, (name, value)| {
+                        gateway.with_label(name, value)
+                    })
+                    .push(&summary)
+                    .context("Failed to push metrics to the Pushgateway")?;
+            }
+            Ok(summary)
+        });
+
+        if let Err(e) = &outcome {
+            warn!(run = i + 1, error = %e, "Matrix run failed");
+        }
+        rows.push(MatrixRow {
+            combination,
+            record_path: outcome.is_ok().then_some(record_path),
+            outcome: outcome.map_err(|e| {
+                let error = format!("{:#}", e);
+                match &redactor {
+                    Some(redactor) => redactor.scrub(&error, &config.server),
+                    None => error,
+                }
+            }),
+        });
+    }
+
+    reporter
+        .print_matrix(&rows)
+        .context("Failed to print matrix results")?;
+    let csv_path = args.out_dir.join("matrix.csv");
+    matrix::write_csv(&csv_path, &args.vary, &rows)
+        .with_context(|| format!("Failed to write {}", csv_path.display()))?;
+    println!("Combined results written to {}", csv_path.display());
+    Ok(())
+}
+
+/// Reporter with the number format, theme and plain mode of `config`
+fn reporter(config: &Config) -> Reporter {
+    Reporter::new(config.number_format())
+        .with_theme(config.theme)
+        .with_plain(config.plain_verdict)
+        .with_percentiles(&config.percentiles)
+        .with_verdict(config.verdict())
+        .with_buckets(config.buckets.clone())
+}
+
+/// Connect to the server over the configured transport
+///
+/// Also returns the endpoints of the flow, where they are known. A payload template
+/// draws its filler from `seed`.
+fn connect(config: &Config, seed: u64) -> Result<(Box<dyn NetworkSocket>, Option<Flow>)> {
+    let payload = config
+        .payload_template
+        .map(|template| template.payload(seed));
+    let connected = match config.transport {
+        Transport::Tcp => TcpNetworkSocket::connect(&config.server).and_then(|mut socket| {
+            if config.kernel_timestamps {
+                socket.enable_kernel_timestamps()?;
+            }
+            // Back-to-back requests and a window of probes in flight would wait on Nagle
+            if config.transaction.is_some() || config.window > 1 {
+                socket.disable_nagle()?;
+            }
+            if let Some(dscp) = config.dscp {
+                socket.set_dscp(dscp)?;
+            }
+            if let Some(payload) = &payload {
+                socket.set_payload(payload.clone());
+            }
+            let flow = socket.local_addr().and_then(|local| {
+                Ok(Flow {
+                    local,
+                    peer: socket.peer_addr()?,
+                })
+            });
+            Ok((Box::new(socket) as Box<dyn NetworkSocket>, flow.ok()))
+        }),
+        Transport::Udp => UdpNetworkSocket::connect(&config.server).and_then(|mut socket| {
+            if config.kernel_timestamps {
+                socket.enable_kernel_timestamps()?;
+            }
+            if let Some(dscp) = config.dscp {
+                socket.set_dscp(dscp)?;
+            }
+            if let Some(payload) = &payload {
+                socket.set_payload(payload.clone());
+            }
+            let flow = socket.local_addr().and_then(|local| {
+                Ok(Flow {
+                    local,
+                    peer: socket.peer_addr()?,
+                })
+            });
+            Ok((Box::new(socket) as Box<dyn NetworkSocket>, flow.ok()))
+        }),
+    };
+    connected.with_context(|| {
+        ConnectionFailed(format!("Failed to connect to server at {}", config.server))
+    })
+}
+
+/// Open the UDP sockets of `--flows`, configured as [`connect`] configures one
+///
+/// Also returns the endpoints of the first flow.
+fn connect_flows(config: &Config, seed: u64) -> Result<(FlowSocket, Option<Flow>)> {
+    let payload = config
+        .payload_template
+        .map(|template| template.payload(seed));
+    let connected = FlowSocket::connect(&config.server, config.flows).and_then(|mut socket| {
+        for flow in socket.sockets_mut() {
+            if config.kernel_timestamps {
+                flow.enable_kernel_timestamps()?;
+            }
+            if let Some(dscp) = config.dscp {
+                flow.set_dscp(dscp)?;
+            }
+            if let Some(payload) = &payload {
+                flow.set_payload(payload.clone());
+            }
+        }
+        let flow = socket.sockets_mut().next().and_then(|first| {
+            Some(Flow {
+                local: first.local_addr().ok()?,
+                peer: first.peer_addr().ok()?,
+            })
+        });
+        Ok((socket, flow))
+    });
+    connected.with_context(|| {
+        ConnectionFailed(format!("Failed to connect to server at {}", config.server))
+    })
+}
+
+/// Open the UDP sockets to --server and every --reflector, configured as [`connect`]
+/// configures one
+///
+/// Also returns the endpoints of the socket to --server.
+fn connect_reflectors(config: &Config, seed: u64) -> Result<(ReflectorPool, Option<Flow>)> {
+    let payload = config
+        .payload_template
+        .map(|template| template.payload(seed));
+    let targets: Vec<String> = std::iter::once(&config.server)
+        .chain(&config.reflectors)
+        .cloned()
+        .collect();
+    let connected = ReflectorPool::connect(&targets).and_then(|mut pool| {
+        for socket in pool.sockets_mut() {
+            if config.kernel_timestamps {
+                socket.enable_kernel_timestamps()?;
+            }
+            if let Some(dscp) = config.dscp {
+                socket.set_dscp(dscp)?;
+            }
+            if let Some(payload) = &payload {
+                socket.set_payload(payload.clone());
+            }
+        }
+        let flow = pool.sockets_mut().next().and_then(|first| {
+            Some(Flow {
+                local: first.local_addr().ok()?,
+                peer: first.peer_addr().ok()?,
+            })
+        });
+        Ok((pool, flow))
+    });
+    connected.with_context(|| {
+        ConnectionFailed(format!(
+            "Failed to connect to reflectors {}",
+            targets.join(", ")
+        ))
+    })
+}
+
+/// Wrap `socket` to send its probes within `budget`, if the probe traffic is capped
+fn with_budget(
+    socket: Box<dyn NetworkSocket>,
+    budget: Option<&BandwidthBudget>,
+    transport: Transport,
+) -> Box<dyn NetworkSocket> {
+    match budget {
+        Some(budget) => Box::new(BudgetedSocket::new(
+            socket,
+            budget.clone(),
+            pacing::header_bytes(transport),
+        )),
+        None => socket,
+    }
+}
+
+/// Print the probe traffic cap and the probe rate it allows, if capped
+fn print_bandwidth_cap(config: &Config) {
+    if let Some(bandwidth) = config.max_bandwidth {
+        let bytes = config.probe_size() + pacing::header_bytes(config.transport);
+        println!(
+            "Bandwidth cap: {} (at most {:.0} probes/s of {} bytes on the wire)",
+            bandwidth,
+            bandwidth.packet_rate(bytes),
+            bytes
+        );
+    }
+}
+
+/// Print the bytes the run will put on the wire, for metered links
+fn print_traffic_estimate(config: &Config) {
+    let estimate = config.traffic_estimate();
+    let numbers = config.number_format();
+    let scope = if config.each_address {
+        " per address"
+    } else {
+        ""
+    };
+    println!(
+        "Traffic: {}{} on the wire ({} probes of {} bytes and their echoes)",
+        numbers.bytes(estimate.total_bytes()),
+        scope,
+        numbers.count(estimate.probes),
+        estimate.probe_bytes
+    );
+    if let Some((duration, bandwidth)) = estimate.paced_load(config.connections) {
+        println!(
+            "Pacing: {:.1}s of measurement at {} each way",
+            duration.as_secs_f64(),
+            bandwidth
+        );
+    }
+}
+
+/// Log the bytes the invocation will put on the wire
+fn log_traffic_estimate(config: &Config) {
+    let estimate = config.traffic_estimate();
+    info!(
+        bytes_on_wire = estimate.total_bytes(),
+        probes = estimate.probes,
+        probe_bytes = estimate.probe_bytes,
+        "Estimated traffic"
+    );
+}
+
+/// Whether the run is the whole invocation rather than one run of a sweep, whose header
+/// prints the traffic of every run
+fn is_lone_run(config: &Config) -> bool {
+    config.port_sweep.is_none() && !config.compare_families && !config.each_address
+}
+
+/// Log how much the probe traffic cap held probing back, if capped
+fn log_budget(budget: Option<&BandwidthBudget>) {
+    let Some(stats) = budget.and_then(|budget| budget.stats().ok()) else {
+        return;
+    };
+    info!(
+        bandwidth = %stats.bandwidth,
+        bytes_sent = stats.bytes,
+        held_sends = stats.held_sends,
+        held_secs = stats.held.as_secs_f64(),
+        "Probe traffic kept within the bandwidth cap"
+    );
+}
+
+/// Build the run record and summary of a finished measurement
+fn summarize(config: &Config, result: &MeasurementResult) -> Result<(RunRecord, RunSummary)> {
+    let stats = Statistics::new(&result.latencies)?;
+    let send_stats = Statistics::new(&result.send_durations)?;
+    let recv_wait_stats = Statistics::new(&result.recv_wait_durations)?;
+
+    let record = RunRecord::new(
+        &config.exported_server(),
+        &stats,
+        config.packets,
+        result.total_packets,
+        result.lost_packets,
+        result.elapsed,
+        true,
+    )
+    .with_components(&send_stats, &recv_wait_stats)
+    .with_seed(result.seed)
+    .with_tags(config.tags());
+    let summary = RunSummary::new(
+        &stats,
+        result.lost_packets,
+        result.total_packets,
+        &result.send_durations,
+    )
+    .with_verdict(config.verdict())
+    .with_stages(output::stage_latencies(
+        &result.path_timings,
+        &result.kernel_timings,
+    ))
+    .with_caveats(&Environment::detect().caveats(config.connections));
+    Ok((record, summary))
+}
@@ -1,16 +1,35 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use std::io::ErrorKind;
 use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use synapse::client::dscp::{self, Dscp};
 use synapse::client::init_logging_with_config;
-use synapse::protocol::PACKET_SIZE;
-use synapse::server::{ServerConfig, ServerMonitor};
-use tracing::{debug, error, info};
+use synapse::protocol::{unix_time_ns, Packet, Transport, PACKET_SIZE};
+use synapse::server::{
+    activated_socket, bind_udp_group, start_admin, start_metrics, ActivatedSocket, FrameLimits,
+    ServerConfig, ServerCounters, ServerMonitor, SourceCookies, SourceLimiter, Violation,
+    STATUS_LOG_INTERVAL_SECS,
+};
+#[cfg(feature = "tokio")]
+use synapse::server::{async_engine, Engine};
+use tracing::{debug, error, info, warn};
+
+/// Interval at which the UDP receive loop checks whether the server is draining, and a
+/// draining TCP server whether its connections closed.
+///
+/// The TCP accept loop blocks instead, woken by the drain request; no echo waits on it.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time without a datagram after which a draining UDP server takes its clients' runs
+/// to be over.
+const UDP_DRAIN_IDLE: Duration = Duration::from_secs(2);
 
 fn main() {
     // Parse CLI arguments
-    let config = ServerConfig::parse();
+    let config = ServerConfig::load();
 
     // Initialize structured logging with config options
     init_logging_with_config(&config.log_level, config.is_json_format());
@@ -31,9 +50,7 @@ fn main() {
 
 fn run(config: ServerConfig) -> Result<()> {
     let addr = config.address();
-
-    // Bind the TCP listener
-    let listener = TcpListener::bind(&addr).map_err(|e| {
+    let bind_error = |e: std::io::Error| {
         if e.kind() == std::io::ErrorKind::AddrInUse {
             anyhow::anyhow!(
                 "Failed to bind to {}: Address already in use. Try a different port or ensure no other process is using it.",
@@ -42,72 +59,137 @@ fn run(config: ServerConfig) -> Result<()> {
         } else {
             anyhow::Error::new(e).context(format!("Failed to bind to {}", addr))
         }
-    })?;
+    };
+
+    // Bind before starting the monitor so a bind failure leaves the terminal untouched;
+    // under socket activation systemd has bound the echo socket already
+    enum Listener {
+        Tcp(TcpListener),
+        Udp(Vec<UdpSocket>),
+    }
+    let activated = activated_socket().context("Failed to take the socket passed by systemd")?;
+    let listener = match (activated, config.transport) {
+        (Some(ActivatedSocket::Tcp(listener)), Transport::Tcp) => Listener::Tcp(listener),
+        (Some(ActivatedSocket::Udp(_)), Transport::Udp) if config.udp_sockets > 1 => {
+            anyhow::bail!(
+                "systemd passes one UDP socket; --udp-sockets needs the server to bind them"
+            )
+        }
+        (Some(ActivatedSocket::Udp(socket)), Transport::Udp) => Listener::Udp(vec![socket]),
+        (Some(socket), transport) => anyhow::bail!(
+            "systemd passed a {} socket, but the server is set to --transport {}",
+            socket.transport(),
+            transport
+        ),
+        (None, Transport::Tcp) => Listener::Tcp(TcpListener::bind(&addr).map_err(bind_error)?),
+        (None, Transport::Udp) if config.udp_sockets > 1 => {
+            Listener::Udp(bind_udp_group(&addr, config.udp_sockets).map_err(bind_error)?)
+        }
+        (None, Transport::Udp) => Listener::Udp(vec![UdpSocket::bind(&addr).map_err(bind_error)?]),
+    };
+    let local_addr = match &listener {
+        Listener::Tcp(listener) => listener.local_addr(),
+        Listener::Udp(sockets) => sockets[0].local_addr(),
+    }
+    .map_or_else(|_| addr.clone(), |local| local.to_string());
+    let metrics_listener = match config.metrics_address() {
+        Some(metrics_addr) => Some(TcpListener::bind(&metrics_addr).map_err(|e| {
+            anyhow::Error::new(e).context(format!(
+                "Failed to bind the metrics endpoint to {}",
+                metrics_addr
+            ))
+        })?),
+        None => None,
+    };
+    let admin_listener = match config.admin_address() {
+        Some(admin_addr) => Some(TcpListener::bind(&admin_addr).map_err(|e| {
+            anyhow::Error::new(e).context(format!(
+                "Failed to bind the admin endpoint to {}",
+                admin_addr
+            ))
+        })?),
+        None => None,
+    };
 
     info!(
-        address = %addr,
+        address = %local_addr,
+        transport = %config.transport,
         update_interval_ms = config.update_interval,
+        max_payload_size = config.max_payload_size,
         quiet_mode = config.quiet,
-        "Synapse TCP server listening"
+        "Synapse {} server listening",
+        config.transport.to_string().to_uppercase()
     );
 
     // Initialize server monitor with configured update interval
-    let monitor = ServerMonitor::new(config.update_interval);
+    let monitor = Arc::new(ServerMonitor::new(config.update_interval));
     let counters = Arc::new(monitor.counters());
 
-    // Start background display thread only if not in quiet mode
-    if !config.quiet {
+    if let Some(metrics_listener) = metrics_listener {
+        start_metrics(metrics_listener, Arc::clone(&monitor));
+    }
+    if let Some(admin_listener) = admin_listener {
+        // A TCP server blocks in accept, so the drain request wakes it up
+        let echo_listener = match &listener {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            Listener::Udp(_) => None,
+        };
+        start_admin(admin_listener, Arc::clone(&monitor), echo_listener);
+    }
+
+    // Start background display thread only if not in quiet mode; off a terminal, the
+    // status goes to the log instead
+    if config.live_view() {
         monitor.start_display();
+    } else if !config.quiet {
+        info!("Stdout is not a terminal: logging status instead of the terminal UI");
+        monitor.start_log(Duration::from_secs(STATUS_LOG_INTERVAL_SECS));
     } else {
         info!("Running in quiet mode (terminal UI disabled)");
     }
 
+    let limits = FrameLimits::new(config.max_payload_size);
+    match listener {
+        #[cfg(feature = "tokio")]
+        Listener::Tcp(listener) if config.engine == Engine::Async => {
+            async_engine::serve_tcp(listener, counters, limits, DRAIN_POLL_INTERVAL)
+                .context("Async engine failed")
+        }
+        Listener::Tcp(listener) => serve_tcp(listener, counters, limits),
+        Listener::Udp(sockets) => serve_udp_group(sockets, counters, limits, config.udp_rate_limit),
+    }
+}
+
+/// Echo packets on every accepted connection, each handled in its own thread
+fn serve_tcp(
+    listener: TcpListener,
+    counters: Arc<ServerCounters>,
+    limits: FrameLimits,
+) -> Result<()> {
     info!("Ready to accept connections and echo packets...");
 
-    // Accept connections and handle each in a separate thread
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut stream) => {
-                let peer_addr = stream.peer_addr().ok();
-                info!(peer = ?peer_addr, "New client connected");
+    // Block in accept: only the admin endpoint drains the server, and it connects to the
+    // listener to wake this loop up. A socket passed by systemd may come non-blocking
+    listener.set_nonblocking(false)?;
+    loop {
+        match listener.accept() {
+            Ok((stream, peer_addr)) => {
+                if counters.is_draining() {
+                    break;
+                }
+                info!(peer = %peer_addr, "New client connected");
+                // The echoes of a transaction or of pipelined probes go out back to back,
+                // which Nagle would hold back
+                if let Err(e) = stream.set_nodelay(true) {
+                    counters.increment_error();
+                    error!(error = %e, peer = %peer_addr, "Failed to set up connection");
+                    continue;
+                }
 
                 let counters = Arc::clone(&counters);
 
                 // Spawn a thread to handle this client
-                std::thread::spawn(move || {
-                    let mut buf = [0u8; PACKET_SIZE];
-
-                    loop {
-                        // TCP is stream-based, so we must use read_exact to read exactly PACKET_SIZE bytes
-                        match stream.read_exact(&mut buf) {
-                            Ok(_) => {
-                                counters.increment_received();
-
-                                // Echo back the exact same payload
-                                match stream.write_all(&buf) {
-                                    Ok(_) => {
-                                        counters.increment_sent();
-                                    }
-                                    Err(e) => {
-                                        counters.increment_error();
-                                        error!(error = %e, peer = ?peer_addr, "Failed to send packet");
-                                        break;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                // Check if it's a connection closed error
-                                if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                                    debug!(peer = ?peer_addr, "Client disconnected");
-                                } else {
-                                    counters.increment_error();
-                                    error!(error = %e, peer = ?peer_addr, "Failed to receive packet");
-                                }
-                                break;
-                            }
-                        }
-                    }
-                });
+                thread::spawn(move || serve_connection(stream, Some(peer_addr), &counters, limits));
             }
             Err(e) => {
                 counters.increment_error();
@@ -116,5 +198,260 @@ fn run(config: ServerConfig) -> Result<()> {
         }
     }
 
+    // Closing the listener refuses new clients while the open connections finish; a
+    // socket passed by systemd stays open there, holding them for the next start
+    drop(listener);
+    info!(
+        active_connections = counters.active_connections(),
+        "Draining: waiting for open connections to close"
+    );
+    while counters.active_connections() > 0 {
+        thread::sleep(DRAIN_POLL_INTERVAL);
+    }
+    info!("Drained; exiting");
     Ok(())
 }
+
+/// Echo the packets of one TCP connection until the client disconnects
+fn serve_connection(
+    mut stream: TcpStream,
+    peer_addr: Option<SocketAddr>,
+    counters: &ServerCounters,
+    limits: FrameLimits,
+) {
+    counters.connection_opened();
+
+    // Probes may be padded to any size up to the limit; the header says how long each
+    // one is, and the first sets the size of all that follow
+    let mut buf = vec![0u8; limits.max_size()];
+    let mut payload_size = None;
+    // Read to written of the last echo, sent back with the next one
+    let mut previous_processing = None;
+
+    'connection: loop {
+        // TCP is stream-based, so we must use read_exact to read the header, then the rest
+        if let Err(e) = stream.read_exact(&mut buf[..PACKET_SIZE]) {
+            // Check if it's a connection closed error
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                debug!(peer = ?peer_addr, "Client disconnected");
+            } else {
+                counters.increment_error();
+                error!(error = %e, peer = ?peer_addr, "Failed to receive packet");
+            }
+            break;
+        }
+        let size = match limits.check(&buf[..PACKET_SIZE], payload_size) {
+            Ok(size) => size,
+            Err(violation) => {
+                // The stream cannot be resynchronized after a frame it does not accept
+                counters.reject_frame(violation);
+                warn!(
+                    reason = %violation,
+                    peer = ?peer_addr,
+                    "Rejected frame; closing connection"
+                );
+                break;
+            }
+        };
+        payload_size = Some(size);
+        if let Err(e) = stream.read_exact(&mut buf[PACKET_SIZE..size]) {
+            counters.increment_error();
+            error!(error = %e, peer = ?peer_addr, "Failed to receive packet");
+            break;
+        }
+        let received_ns = unix_time_ns();
+        let received = Instant::now();
+        counters.increment_received();
+
+        // Echo back the same packet with the server's timestamps filled in, as many
+        // times as it asks for
+        for index in 0..Packet::replies_requested(&buf[..size]) {
+            Packet::stamp_reply_index(&mut buf[..size], index);
+            Packet::stamp_server_processing(&mut buf[..size], previous_processing);
+            Packet::stamp_server_times(&mut buf[..size], received_ns, unix_time_ns());
+            match stream.write_all(&buf[..size]) {
+                Ok(_) => {
+                    previous_processing = Some(received.elapsed());
+                    counters.increment_sent();
+                    counters.add_bytes_sent(size as u64);
+                }
+                Err(e) => {
+                    counters.increment_error();
+                    error!(error = %e, peer = ?peer_addr, "Failed to send packet");
+                    break 'connection;
+                }
+            }
+        }
+    }
+
+    counters.connection_closed();
+}
+
+/// Serve every socket of a `--udp-sockets` group, each from a thread of its own
+///
+/// A lone socket is served from the calling thread. Each socket keeps its own rate limit
+/// buckets, as a source can reach several sockets of the group from different ports: one
+/// table for the echoes of validated sources, and one for challenges, so spoofed sources
+/// filling the latter never crowd out clients in the former.
+fn serve_udp_group(
+    mut sockets: Vec<UdpSocket>,
+    counters: Arc<ServerCounters>,
+    limits: FrameLimits,
+    rate_limit: Option<u32>,
+) -> Result<()> {
+    // One key for the whole group, so a client is valid whichever socket it reaches
+    let cookies = SourceCookies::new();
+    if sockets.len() == 1 {
+        let socket = sockets.remove(0);
+        let rate_limits =
+            rate_limit.map(|rate| (SourceLimiter::new(rate), SourceLimiter::new(rate)));
+        return serve_udp(socket, counters, limits, cookies, rate_limits);
+    }
+
+    info!(
+        sockets = sockets.len(),
+        "Serving UDP from a SO_REUSEPORT group"
+    );
+    let workers = sockets
+        .into_iter()
+        .enumerate()
+        .map(|(i, socket)| {
+            let counters = Arc::clone(&counters);
+            let cookies = cookies.clone();
+            thread::Builder::new()
+                .name(format!("synapse-udp-{}", i))
+                .spawn(move || {
+                    let rate_limits =
+                        rate_limit.map(|rate| (SourceLimiter::new(rate), SourceLimiter::new(rate)));
+                    serve_udp(socket, counters, limits, cookies, rate_limits)
+                })
+        })
+        .collect::<std::io::Result<Vec<_>>>()
+        .context("Failed to start UDP workers")?;
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| anyhow::anyhow!("UDP worker panicked"))??;
+    }
+    Ok(())
+}
+
+/// Echo every datagram back to its sender
+///
+/// All clients share the one socket; each probe is answered on its own, so no
+/// per-client state is kept beyond the token buckets of `rate_limits`, those of echoes
+/// and those of challenges. A datagram without its source's cookie gets a challenge
+/// instead of its echoes (see [`synapse::server::cookie`]), which costs its source one
+/// token of the challenge limit. Every reply carries the DSCP its probe arrived with,
+/// so a client can tell whether its marking survived the round trip.
+fn serve_udp(
+    socket: UdpSocket,
+    counters: Arc<ServerCounters>,
+    limits: FrameLimits,
+    cookies: SourceCookies,
+    mut rate_limits: Option<(SourceLimiter, SourceLimiter)>,
+) -> Result<()> {
+    info!("Ready to echo datagrams...");
+
+    // Wake up now and then to notice a drain request
+    socket.set_read_timeout(Some(DRAIN_POLL_INTERVAL))?;
+    let ipv6 = socket.local_addr()?.is_ipv6();
+    if let Err(e) = dscp::report_markings(&socket, ipv6) {
+        warn!(error = %e, "Cannot read probe markings; replies will not reflect DSCP");
+    }
+    // Marking of the socket, changed only when a probe arrives with another one
+    let mut marking = Dscp::from_tos(0);
+    let mut last_received = Instant::now();
+    // Read to sent of the last echo, sent back with the next one
+    let mut previous_processing = None;
+
+    // Room for a longer datagram, so one is rejected rather than silently truncated
+    let mut buf = vec![0u8; limits.max_size() + 1];
+    loop {
+        let (len, peer_addr, tos) = match dscp::recv_from(&socket, &mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                // Datagrams carry no session, so a drain waits for clients to go quiet
+                if counters.is_draining() && last_received.elapsed() >= UDP_DRAIN_IDLE {
+                    info!("Drained; exiting");
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(e) => {
+                // An ICMP error from an earlier reply (e.g. a client that went away)
+                counters.increment_error();
+                debug!(error = %e, "Failed to receive datagram");
+                continue;
+            }
+        };
+        let received_ns = unix_time_ns();
+        last_received = Instant::now();
+        // Datagrams are independent, so there is no payload size to keep to
+        let checked = match limits.check(&buf[..len], None) {
+            Ok(size) if size != len => Err(Violation::Invalid),
+            checked => checked,
+        };
+        if let Err(violation) = checked {
+            counters.reject_frame(violation);
+            debug!(peer = %peer_addr, len = len, reason = %violation, "Ignoring rejected datagram");
+            continue;
+        }
+        counters.increment_received();
+        if !cookies.is_valid(peer_addr, Packet::cookie_of(&buf[..len])) {
+            if let Some((_, challenges)) = &mut rate_limits {
+                if !challenges.allow(peer_addr.ip(), 1, Instant::now()) {
+                    counters.increment_rate_limited();
+                    debug!(
+                        peer = %peer_addr,
+                        "Dropping datagram over the source's challenge limit"
+                    );
+                    continue;
+                }
+            }
+            Packet::make_challenge(&mut buf[..len], cookies.cookie(peer_addr));
+            match socket.send_to(&buf[..PACKET_SIZE], peer_addr) {
+                Ok(_) => counters.increment_challenges(),
+                Err(e) => {
+                    counters.increment_error();
+                    debug!(error = %e, peer = %peer_addr, "Failed to send challenge");
+                }
+            }
+            continue;
+        }
+        if let Some((echoes, _)) = &mut rate_limits {
+            let replies = Packet::replies_requested(&buf[..len]);
+            if !echoes.allow(peer_addr.ip(), replies, Instant::now()) {
+                counters.increment_rate_limited();
+                debug!(peer = %peer_addr, "Dropping datagram over the source's rate limit");
+                continue;
+            }
+        }
+
+        if let Some(dscp) = tos.map(Dscp::from_tos).filter(|dscp| *dscp != marking) {
+            match dscp::mark(&socket, ipv6, dscp) {
+                Ok(()) => marking = dscp,
+                Err(e) => debug!(error = %e, dscp = %dscp, "Failed to reflect DSCP"),
+            }
+        }
+
+        // Echo back the same packet with the server's timestamps filled in, as many
+        // times as it asks for
+        for index in 0..Packet::replies_requested(&buf[..len]) {
+            Packet::stamp_reply_index(&mut buf[..len], index);
+            Packet::stamp_server_processing(&mut buf[..len], previous_processing);
+            Packet::stamp_server_times(&mut buf[..len], received_ns, unix_time_ns());
+            match socket.send_to(&buf[..len], peer_addr) {
+                Ok(_) => {
+                    previous_processing = Some(last_received.elapsed());
+                    counters.increment_sent();
+                    counters.add_bytes_sent(len as u64);
+                }
+                Err(e) => {
+                    counters.increment_error();
+                    error!(error = %e, peer = %peer_addr, "Failed to send packet");
+                }
+            }
+        }
+    }
+}
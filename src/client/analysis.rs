@@ -0,0 +1,219 @@
+//! Significance tests between two runs
+//!
+//! Two runs of the same setup never report the same percentiles: a P99 moves by a few
+//! percent from one run to the next. These tests tell a real change from that noise.
+//! Both work on the latency histograms of run records, so they need no raw samples:
+//!
+//! - [`mann_whitney`] tests whether one run's latencies are generally higher than the
+//!   other's (a shift of the whole distribution), with the Mann-Whitney U test. Equal
+//!   histogram values are ties and ranked as such.
+//! - [`exceedance`] tests a single percentile: whether more of the current run's
+//!   probes exceed the baseline's value of it than the baseline's did, with a
+//!   two-proportion z-test. A tail can regress while the median stays put.
+//!
+//! Both use the normal approximation, sound for the thousands of probes of a run.
+
+/// Level below which a p-value is significant
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Outcome of a test comparing a current run with its baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestResult {
+    /// Standard score; positive when the current run is slower
+    pub z: f64,
+    /// Two-sided p-value
+    pub p_value: f64,
+}
+
+impl TestResult {
+    fn from_z(z: f64) -> Self {
+        Self {
+            z,
+            p_value: erfc(z.abs() / std::f64::consts::SQRT_2),
+        }
+    }
+
+    /// Whether the difference is significant at [`SIGNIFICANCE_LEVEL`]
+    pub fn is_significant(&self) -> bool {
+        self.p_value < SIGNIFICANCE_LEVEL
+    }
+
+    /// Whether the current run is significantly slower than the baseline
+    pub fn is_significantly_slower(&self) -> bool {
+        self.is_significant() && self.z > 0.0
+    }
+}
+
+/// Mann-Whitney U test of a shift between two latency distributions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShiftTest {
+    /// Probability that a probe of the current run is slower than one of the baseline,
+    /// ties counting half (0.5 means no shift)
+    pub probability_slower: f64,
+    pub test: TestResult,
+}
+
+/// Test whether `current` is shifted from `baseline`, both histograms of
+/// `[value_ns, count]` pairs in ascending order of value
+///
+/// Returns `None` if either is empty or every value is the same.
+pub fn mann_whitney(baseline: &[(u64, u64)], current: &[(u64, u64)]) -> Option<ShiftTest> {
+    let n1 = baseline.iter().map(|&(_, count)| count).sum::<u64>() as f64;
+    let n2 = current.iter().map(|&(_, count)| count).sum::<u64>() as f64;
+    if n1 == 0.0 || n2 == 0.0 {
+        return None;
+    }
+
+    // Walk both histograms in value order; every value is one group of tied ranks
+    let (mut i, mut j) = (0, 0);
+    let (mut ranked, mut current_rank_sum, mut tie_term) = (0.0, 0.0, 0.0);
+    while i < baseline.len() || j < current.len() {
+        let value = match (baseline.get(i), current.get(j)) {
+            (Some(&(a, _)), Some(&(b, _))) => a.min(b),
+            (Some(&(a, _)), None) => a,
+            (None, Some(&(b, _))) => b,
+            (None, None) => unreachable!(),
+        };
+        let take = |histogram: &[(u64, u64)], index: &mut usize| match histogram.get(*index) {
+            Some(&(v, count)) if v == value => {
+                *index += 1;
+                count as f64
+            }
+            _ => 0.0,
+        };
+        let (a, b) = (take(baseline, &mut i), take(current, &mut j));
+        let tied = a + b;
+        current_rank_sum += b * (ranked + (tied + 1.0) / 2.0);
+        tie_term += tied * tied * tied - tied;
+        ranked += tied;
+    }
+
+    let n = n1 + n2;
+    let u = current_rank_sum - n2 * (n2 + 1.0) / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return None;
+    }
+    Some(ShiftTest {
+        probability_slower: u / (n1 * n2),
+        test: TestResult::from_z((u - n1 * n2 / 2.0) / variance.sqrt()),
+    })
+}
+
+/// Test whether a larger share of `current` than of `baseline` exceeds `threshold_ns`
+///
+/// With the baseline's value of a percentile as the threshold, this tests whether the
+/// percentile moved. Returns `None` if either histogram is empty, or if no probe or
+/// every probe of both exceeds the threshold.
+pub fn exceedance(
+    baseline: &[(u64, u64)],
+    current: &[(u64, u64)],
+    threshold_ns: u64,
+) -> Option<TestResult> {
+    let share = |histogram: &[(u64, u64)]| {
+        let total: u64 = histogram.iter().map(|&(_, count)| count).sum();
+        let above: u64 = histogram
+            .iter()
+            .filter(|&&(value, _)| value > threshold_ns)
+            .map(|&(_, count)| count)
+            .sum();
+        (above as f64, total as f64)
+    };
+    let ((x1, n1), (x2, n2)) = (share(baseline), share(current));
+    if n1 == 0.0 || n2 == 0.0 {
+        return None;
+    }
+    let pooled = (x1 + x2) / (n1 + n2);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        return None;
+    }
+    Some(TestResult::from_z((x2 / n2 - x1 / n1) / se))
+}
+
+/// Format `p_value` as "p = 0.012", or "p < 0.001" below that
+pub fn format_p_value(p_value: f64) -> String {
+    if p_value < 0.001 {
+        "p < 0.001".into()
+    } else {
+        format!("p = {:.3}", p_value)
+    }
+}
+
+/// Complementary error function, with a fractional error below 1.2e-7 (Numerical
+/// Recipes' `erfcc`)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erfc() {
+        for (x, expected) in [(0.0, 1.0), (1.0, 0.157_299_2), (-1.0, 1.842_700_8)] {
+            assert!((erfc(x) - expected).abs() < 1e-6, "erfc({})", x);
+        }
+        // Two-sided p-value of z = 1.96
+        assert!((erfc(1.96 / std::f64::consts::SQRT_2) - 0.05).abs() < 1e-3);
+        assert_eq!(format_p_value(0.0123), "p = 0.012");
+        assert_eq!(format_p_value(1e-9), "p < 0.001");
+    }
+
+    #[test]
+    fn test_mann_whitney() {
+        // 3 vs 4 samples without ties: the current run ranks 2, 4 and 6, so U = 6 of 12
+        let baseline = [(10, 1), (30, 1), (50, 1), (70, 1)];
+        let current = [(20, 1), (40, 1), (60, 1)];
+        let shift = mann_whitney(&baseline, &current).unwrap();
+        assert!((shift.probability_slower - 6.0 / 12.0).abs() < 1e-9);
+        assert!(!shift.test.is_significant());
+
+        // A clear shift of large runs
+        let baseline = [(50_000, 900), (60_000, 100)];
+        let current = [(50_000, 500), (60_000, 400), (70_000, 100)];
+        let shift = mann_whitney(&baseline, &current).unwrap();
+        assert!(shift.probability_slower > 0.6);
+        assert!(shift.test.is_significantly_slower());
+        let reverse = mann_whitney(&current, &baseline).unwrap();
+        assert!(reverse.test.is_significant() && !reverse.test.is_significantly_slower());
+
+        // Identical runs, and nothing to rank
+        let same = mann_whitney(&baseline, &baseline).unwrap();
+        assert!(same.test.z.abs() < 1e-9 && same.test.p_value > 0.99);
+        assert_eq!(mann_whitney(&[], &current), None);
+        assert_eq!(mann_whitney(&[(5, 10)], &[(5, 20)]), None);
+    }
+
+    #[test]
+    fn test_exceedance() {
+        // 1% above 80 µs in the baseline, 5% in the current run
+        let baseline = [(50_000, 990), (90_000, 10)];
+        let current = [(50_000, 950), (140_000, 50)];
+        let test = exceedance(&baseline, &current, 80_000).unwrap();
+        assert!(test.is_significantly_slower(), "{:?}", test);
+
+        // 1% vs 1.2% of a thousand probes is noise
+        let current = [(50_000, 988), (140_000, 12)];
+        assert!(!exceedance(&baseline, &current, 80_000)
+            .unwrap()
+            .is_significant());
+        assert_eq!(exceedance(&baseline, &current, 200_000), None);
+    }
+}
@@ -0,0 +1,521 @@
+//! Async client for embedding in tokio applications
+//!
+//! The blocking client measures one stream per thread, which does not fit an async
+//! application that wants to probe many servers at once. [`AsyncNetworkSocket`] and
+//! [`measurement_phase_async`] mirror [`NetworkSocket`](crate::client::NetworkSocket) and
+//! [`measurement_phase`](crate::client::measurement_phase) on tokio sockets: every
+//! stream is a future, so any number of them can run concurrently on a single thread.
+//!
+//! Probes use the same wire format and fill the same [`MeasurementResult`], so results
+//! go through the usual statistics and reporting. The async phase only measures: it has
+//! no terminal UI, checkpoints, pacing or kernel timestamps. Latencies include the
+//! runtime's scheduling delay, which grows with the work sharing the thread; keep other
+//! CPU-bound tasks off the runtime that measures.
+
+use crate::client::constants::HELLO_TIMEOUT_MS;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::{MeasurementResult, PathTiming};
+use crate::client::rng::random_seed;
+use crate::protocol::{unix_time_ns, Packet, SequenceNumber, PACKET_SIZE};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tracing::{debug, warn};
+
+/// Reply timeout of [`AsyncMeasurementOptions::new`], as for the blocking client
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Async counterpart of [`NetworkSocket`](crate::client::NetworkSocket)
+///
+/// There is no `set_timeout`: the measurement bounds each receive with
+/// [`tokio::time::timeout`] instead.
+pub trait AsyncNetworkSocket: Send {
+    /// Send a packet to the server
+    fn send_packet(&mut self, packet: &Packet) -> impl Future<Output = Result<usize>> + Send;
+
+    /// Receive the reply to the last packet sent
+    fn recv_packet(&mut self) -> impl Future<Output = Result<Packet>> + Send;
+}
+
+/// TCP implementation of [`AsyncNetworkSocket`]
+///
+/// Replies are as long as the probes they echo, so each receive reads as many bytes as
+/// the last packet sent.
+pub struct AsyncTcpNetworkSocket {
+    stream: TcpStream,
+    send_buffer: Vec<u8>,
+    recv_buffer: Vec<u8>,
+}
+
+impl AsyncTcpNetworkSocket {
+    /// Connect to a remote address
+    pub async fn connect(addr: &str) -> Result<Self> {
+        debug!(addr = addr, "Connecting TCP stream");
+        let stream = TcpStream::connect(addr).await.map_err(|e| {
+            warn!(error = %e, "Failed to connect stream");
+            ClientError::Socket(format!("Failed to connect to {}: {}", addr, e))
+        })?;
+        debug!("TCP stream connected successfully");
+        Ok(Self {
+            stream,
+            send_buffer: Vec::new(),
+            recv_buffer: vec![0; PACKET_SIZE],
+        })
+    }
+
+    /// Local address the connection is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.stream
+            .local_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read local address: {}", e)))
+    }
+}
+
+impl AsyncNetworkSocket for AsyncTcpNetworkSocket {
+    async fn send_packet(&mut self, packet: &Packet) -> Result<usize> {
+        packet.encode_into(&mut self.send_buffer);
+        self.recv_buffer.resize(self.send_buffer.len(), 0);
+        self.stream
+            .write_all(&self.send_buffer)
+            .await
+            .map_err(|e| {
+                warn!(error = %e, "Failed to send packet");
+                ClientError::Io(e)
+            })?;
+        debug!(
+            bytes_sent = self.send_buffer.len(),
+            sequence = packet.sequence.0,
+            "Packet sent"
+        );
+        Ok(self.send_buffer.len())
+    }
+
+    async fn recv_packet(&mut self) -> Result<Packet> {
+        self.stream
+            .read_exact(&mut self.recv_buffer)
+            .await
+            .map_err(|e| {
+                debug!(error = %e, "Failed to receive packet");
+                ClientError::Io(e)
+            })?;
+        let packet = Packet::decode(&self.recv_buffer)?;
+        debug!(
+            sequence = packet.sequence.0,
+            bytes_received = self.recv_buffer.len(),
+            "Packet received"
+        );
+        Ok(packet)
+    }
+}
+
+/// UDP implementation of [`AsyncNetworkSocket`]
+///
+/// As with [`UdpNetworkSocket`](crate::client::UdpNetworkSocket), replies not carrying
+/// the last sent sequence number arrived after their probe timed out and are discarded,
+/// the cookie is asked for with a hello on connecting, and a probe the server challenges
+/// all the same is sent again once with the new cookie.
+pub struct AsyncUdpNetworkSocket {
+    socket: UdpSocket,
+    /// Sequence number of the last probe sent
+    last_sent: u64,
+    /// Cookie of the last challenge from the server, 0 before the first
+    cookie: u64,
+    /// Sequence number of the last probe sent again after a challenge
+    resent: Option<u64>,
+    send_buffer: Vec<u8>,
+    recv_buffer: Vec<u8>,
+}
+
+impl AsyncUdpNetworkSocket {
+    /// Create a socket sending to and receiving only from `addr`, and exchange a hello
+    /// for the server's cookie
+    pub async fn connect(addr: &str) -> Result<Self> {
+        debug!(addr = addr, "Connecting UDP socket");
+        let remote = tokio::net::lookup_host(addr)
+            .await
+            .map_err(|e| ClientError::Socket(format!("Failed to resolve {}: {}", addr, e)))?
+            .next()
+            .ok_or_else(|| ClientError::Socket(format!("{} resolved to no address", addr)))?;
+        let local: SocketAddr = if remote.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(remote).await.map_err(|e| {
+            warn!(error = %e, "Failed to connect socket");
+            ClientError::Socket(format!("Failed to connect to {}: {}", addr, e))
+        })?;
+        debug!("UDP socket connected successfully");
+        let mut socket = Self {
+            socket,
+            last_sent: 0,
+            cookie: 0,
+            resent: None,
+            send_buffer: Vec::new(),
+            recv_buffer: Vec::new(),
+        };
+        socket.exchange_cookie().await;
+        Ok(socket)
+    }
+
+    /// Send a hello and keep the cookie of the server's challenge to it
+    ///
+    /// As for the blocking socket, a peer that echoes the hello or stays silent does
+    /// not fail the connection.
+    async fn exchange_cookie(&mut self) {
+        let mut buf = [0u8; PACKET_SIZE];
+        let exchange = async {
+            self.socket.send(&Packet::hello().encode()).await?;
+            self.socket.recv(&mut buf).await
+        };
+        let timeout = Duration::from_millis(HELLO_TIMEOUT_MS);
+        match tokio::time::timeout(timeout, exchange).await {
+            Ok(Ok(len)) => match Packet::decode(&buf[..len]) {
+                Ok(challenge) if challenge.is_challenge() => {
+                    debug!("Server challenged the hello");
+                    self.cookie = challenge.cookie;
+                }
+                _ => debug!("Server answered the hello without a challenge"),
+            },
+            Ok(Err(e)) => debug!(error = %e, "No challenge to the hello"),
+            Err(_) => debug!("No challenge to the hello"),
+        }
+    }
+
+    /// Local address the socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket
+            .local_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read local address: {}", e)))
+    }
+}
+
+impl AsyncNetworkSocket for AsyncUdpNetworkSocket {
+    async fn send_packet(&mut self, packet: &Packet) -> Result<usize> {
+        packet.encode_into(&mut self.send_buffer);
+        Packet::stamp_cookie(&mut self.send_buffer, self.cookie);
+        self.last_sent = packet.sequence.0;
+        let sent = self.socket.send(&self.send_buffer).await.map_err(|e| {
+            warn!(error = %e, "Failed to send packet");
+            ClientError::Io(e)
+        })?;
+        debug!(
+            bytes_sent = sent,
+            sequence = packet.sequence.0,
+            "Packet sent"
+        );
+        Ok(sent)
+    }
+
+    async fn recv_packet(&mut self) -> Result<Packet> {
+        let size = self.send_buffer.len();
+        // Room for a longer datagram, so one is rejected rather than silently truncated
+        self.recv_buffer.resize(size + 1, 0);
+        loop {
+            let len = self.socket.recv(&mut self.recv_buffer).await.map_err(|e| {
+                debug!(error = %e, "Failed to receive packet");
+                ClientError::Io(e)
+            })?;
+            // A challenge, or a late echo of the hello, is the header alone and asks
+            // for no echo, whatever the size of the probe
+            let header = (len == PACKET_SIZE)
+                .then(|| Packet::decode(&self.recv_buffer[..len]).ok())
+                .flatten()
+                .filter(|header| header.replies == 0);
+            if let Some(header) = header {
+                if header.is_challenge() {
+                    debug!(sequence = header.sequence.0, "Server challenged the probe");
+                    self.cookie = header.cookie;
+                    // The server dropped the probe; once more with the cookie, it answers
+                    if header.sequence.0 == self.last_sent && self.resent != Some(self.last_sent) {
+                        self.resent = Some(self.last_sent);
+                        Packet::stamp_cookie(&mut self.send_buffer, self.cookie);
+                        self.socket.send(&self.send_buffer).await.map_err(|e| {
+                            warn!(error = %e, "Failed to send packet");
+                            ClientError::Io(e)
+                        })?;
+                    }
+                }
+                continue;
+            }
+            if len != size {
+                return Err(ClientError::Socket(format!(
+                    "Received a {}-byte datagram, expected {}",
+                    len, size
+                )));
+            }
+            let packet = Packet::decode(&self.recv_buffer[..len])?;
+            if packet.sequence.0 == self.last_sent {
+                debug!(
+                    sequence = packet.sequence.0,
+                    bytes_received = len,
+                    "Packet received"
+                );
+                return Ok(packet);
+            }
+            debug!(
+                expected = self.last_sent,
+                received = packet.sequence.0,
+                "Discarding stale reply"
+            );
+        }
+    }
+}
+
+/// Options controlling an async measurement phase
+#[derive(Debug, Clone)]
+pub struct AsyncMeasurementOptions {
+    /// Number of packets to send
+    pub packet_count: usize,
+    /// Time to wait for each reply before counting the packet as lost
+    pub timeout: Duration,
+    /// Size of every probe in bytes, sequence header included
+    pub payload_size: usize,
+    /// Seed recorded with the result (see [`crate::client::rng`])
+    pub seed: u64,
+}
+
+impl AsyncMeasurementOptions {
+    /// Create options for `packet_count` probes of the default size, a 100ms timeout
+    /// and a fresh random seed
+    pub fn new(packet_count: usize) -> Self {
+        Self {
+            packet_count,
+            timeout: DEFAULT_TIMEOUT,
+            payload_size: PACKET_SIZE,
+            seed: random_seed(),
+        }
+    }
+}
+
+/// Perform a measurement phase on an async socket and collect latency statistics
+///
+/// Probing is lockstep, as in [`measurement_phase`](crate::client::measurement_phase):
+/// each probe is sent once the previous one was answered or timed out. Over TCP a reply
+/// that times out half-read leaves the stream out of step, and the following probes
+/// count as lost.
+pub async fn measurement_phase_async<S: AsyncNetworkSocket>(
+    socket: &mut S,
+    options: AsyncMeasurementOptions,
+) -> Result<MeasurementResult> {
+    let AsyncMeasurementOptions {
+        packet_count,
+        timeout,
+        payload_size,
+        seed,
+    } = options;
+
+    let mut latencies = Vec::with_capacity(packet_count);
+    let mut sequences = Vec::with_capacity(packet_count);
+    let mut send_offsets = Vec::with_capacity(packet_count);
+    let mut idle_gaps = Vec::with_capacity(packet_count);
+    let mut send_durations = Vec::with_capacity(packet_count);
+    let mut recv_wait_durations = Vec::with_capacity(packet_count);
+    let mut path_timings = Vec::with_capacity(packet_count);
+    let mut lost_sequences = Vec::new();
+
+    let start_time = Instant::now();
+    let started_at = SystemTime::now();
+    let mut idle_since = start_time;
+
+    for i in 0..packet_count {
+        let sequence = SequenceNumber(i as u64);
+        let mut packet = Packet::with_size(sequence, payload_size);
+        let send_offset_ns = start_time.elapsed().as_nanos() as u64;
+        let t1 = Instant::now();
+        let idle_ns = (t1 - idle_since).as_nanos() as u64;
+        let client_send_ns = unix_time_ns();
+        packet.client_send_ns = Some(client_send_ns);
+
+        let interrupted = |e: ClientError, measured: usize, lost: usize| {
+            ClientError::Measurement(format!(
+                "Measurement phase interrupted after {} packets ({} successful, {} lost): {}",
+                measured + lost,
+                measured,
+                lost,
+                e
+            ))
+        };
+        if let Err(e) = socket.send_packet(&packet).await {
+            return Err(interrupted(e, latencies.len(), lost_sequences.len()));
+        }
+        let t_send_done = Instant::now();
+        send_durations.push((t_send_done - t1).as_nanos() as u64);
+
+        match tokio::time::timeout(timeout, socket.recv_packet()).await {
+            Ok(Ok(reply)) if reply.sequence == sequence => {
+                let t2 = Instant::now();
+                let latency_ns = (t2 - t1).as_nanos() as u64;
+                latencies.push(latency_ns);
+                recv_wait_durations.push((t2 - t_send_done).as_nanos() as u64);
+                sequences.push(sequence.0);
+                send_offsets.push(send_offset_ns);
+                idle_gaps.push(idle_ns);
+                path_timings.extend(PathTiming::from_reply(
+                    client_send_ns,
+                    unix_time_ns(),
+                    &reply,
+                ));
+                debug!(
+                    packet_num = i + 1,
+                    latency_ns = latency_ns,
+                    "Measurement packet completed"
+                );
+            }
+            Ok(Ok(reply)) => {
+                warn!(
+                    expected = sequence.0,
+                    received = reply.sequence.0,
+                    "Sequence mismatch"
+                );
+                lost_sequences.push(sequence.0);
+            }
+            Ok(Err(e)) => {
+                warn!(error = %e, "Error receiving packet");
+                return Err(interrupted(e, latencies.len(), lost_sequences.len()));
+            }
+            Err(_) => {
+                warn!(packet_num = i + 1, "Measurement packet lost or timed out");
+                lost_sequences.push(sequence.0);
+            }
+        }
+        idle_since = Instant::now();
+    }
+
+    debug!(
+        packets_received = latencies.len(),
+        packets_lost = lost_sequences.len(),
+        "Measurement phase completed"
+    );
+
+    Ok(MeasurementResult {
+        latencies,
+        sequences,
+        send_offsets,
+        idle_gaps,
+        started_at,
+        warmup: None,
+        send_durations,
+        recv_wait_durations,
+        lost_packets: lost_sequences.len(),
+        total_packets: packet_count,
+        elapsed: start_time.elapsed(),
+        seed,
+        system_timeline: None,
+        lost_sequences,
+        thread_usage: None,
+        sample_backing: None,
+        path_timings,
+        clock_offset: None,
+        kernel_timings: Vec::new(),
+        pacing: None,
+        reply_dscp: BTreeMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Echo every datagram on a blocking UDP socket, dropping the ones numbered in `drop`
+    fn udp_echo(drop: &'static [u64]) -> std::io::Result<SocketAddr> {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        let addr = socket.local_addr()?;
+        thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            while let Ok((len, source)) = socket.recv_from(&mut buf) {
+                let sequence = Packet::decode(&buf[..len]).map(|p| p.sequence.0);
+                if sequence.is_ok_and(|s| drop.contains(&s)) {
+                    continue;
+                }
+                let _ = socket.send_to(&buf[..len], source);
+            }
+        });
+        Ok(addr)
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime")
+    }
+
+    #[test]
+    fn test_concurrent_streams_on_one_thread() -> Result<()> {
+        let addr = udp_echo(&[3])?.to_string();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let tcp_addr = listener.local_addr()?.to_string();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut reader = stream.try_clone().expect("clone");
+                let _ = std::io::copy(&mut reader, &mut stream);
+            }
+        });
+
+        let options = |count| AsyncMeasurementOptions {
+            payload_size: 64,
+            ..AsyncMeasurementOptions::new(count)
+        };
+        let (udp, tcp) = runtime().block_on(async {
+            let mut udp = AsyncUdpNetworkSocket::connect(&addr).await?;
+            let mut tcp = AsyncTcpNetworkSocket::connect(&tcp_addr).await?;
+            tokio::try_join!(
+                measurement_phase_async(&mut udp, options(10)),
+                measurement_phase_async(&mut tcp, options(10)),
+            )
+        })?;
+
+        assert_eq!(udp.total_packets, 10);
+        assert_eq!(udp.lost_sequences, vec![3]);
+        assert_eq!(udp.latencies.len(), 9);
+        assert_eq!(udp.sequences.len(), 9);
+        assert_eq!(udp.send_durations.len(), 10);
+
+        assert_eq!(tcp.lost_packets, 0);
+        assert_eq!(tcp.sequences, (0..10).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_udp_challenge() -> Result<()> {
+        // Challenge every datagram without the cookie, echo the others; the cookie
+        // changes after three echoes, as on a server restart
+        let server = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        let addr = server.local_addr()?.to_string();
+        thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            let mut echoes = 0;
+            while let Ok((len, source)) = server.recv_from(&mut buf) {
+                let cookie = if echoes < 3 { 9 } else { 10 };
+                if Packet::cookie_of(&buf[..len]) == cookie {
+                    echoes += 1;
+                    let _ = server.send_to(&buf[..len], source);
+                } else {
+                    Packet::make_challenge(&mut buf[..len], cookie);
+                    let _ = server.send_to(&buf[..PACKET_SIZE], source);
+                }
+            }
+        });
+
+        let result = runtime().block_on(async {
+            let mut udp = AsyncUdpNetworkSocket::connect(&addr).await?;
+            let options = AsyncMeasurementOptions {
+                payload_size: 100,
+                ..AsyncMeasurementOptions::new(5)
+            };
+            measurement_phase_async(&mut udp, options).await
+        })?;
+        // The hello got the first cookie, and the probe challenged for the second went
+        // out again, so no probe was lost to a challenge
+        assert_eq!(result.lost_packets, 0);
+        assert_eq!(result.sequences, vec![0, 1, 2, 3, 4]);
+        Ok(())
+    }
+}
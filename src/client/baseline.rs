@@ -0,0 +1,210 @@
+//! Alerts relative to a rolling baseline of earlier runs
+//!
+//! One absolute threshold cannot serve every link: a P99 of 2 ms is an outage on a LAN
+//! and a good day over a satellite hop. `--baseline-dir <DIR>` holds the run records of
+//! earlier runs, typically written by a cron job or systemd timer with
+//! `--checkpoint DIR/run-$(date +%s).json`. The records of the same server written over
+//! the trailing `--baseline-hours` (24 by default) are merged into one baseline, and the
+//! run fails when its P99 deviates from the baseline P99 by more than
+//! `--max-p99-change` percent, either way: a link that got faster may have changed paths.
+//!
+//! Only complete records written before the run started count, so the run's own
+//! checkpoint in the same directory is left out. Files that are not run records are
+//! skipped.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::record::RunRecord;
+use crate::client::statistics::Statistics;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Hours of earlier runs the baseline covers unless --baseline-hours says otherwise
+pub const DEFAULT_BASELINE_HOURS: u64 = 24;
+
+/// Deviation of the P99 from the baseline, in percent, allowed unless --max-p99-change
+/// says otherwise
+pub const DEFAULT_MAX_P99_CHANGE_PCT: f64 = 20.0;
+
+/// Latency of the earlier runs of a server over a trailing window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Baseline {
+    /// Runs merged into the baseline
+    pub runs: usize,
+    /// Probes answered over those runs
+    pub samples: u64,
+    /// Median RTT of the merged runs in nanoseconds
+    pub p50_ns: u64,
+    /// 99th percentile RTT of the merged runs in nanoseconds
+    pub p99_ns: u64,
+}
+
+impl Baseline {
+    /// Merge the complete records of `server` in `dir` written over the `window` before
+    /// `started_at`; `None` if there is none
+    pub fn load(
+        dir: &Path,
+        server: &str,
+        window: Duration,
+        started_at: SystemTime,
+    ) -> Result<Option<Self>> {
+        let unix_ms = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        };
+        let before = unix_ms(started_at);
+        let since = unix_ms(started_at.checked_sub(window).unwrap_or(UNIX_EPOCH));
+
+        let entries = fs::read_dir(dir).map_err(|e| {
+            ClientError::Config(format!(
+                "Failed to read baseline directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            match RunRecord::load(&path) {
+                Ok(record)
+                    if record.complete
+                        && record.server == server
+                        && (since..before).contains(&record.written_at_unix_ms) =>
+                {
+                    records.push(record)
+                }
+                Ok(_) => {}
+                Err(e) => warn!(path = %path.display(), error = %e, "Skipping baseline file"),
+            }
+        }
+        debug!(dir = %dir.display(), runs = records.len(), "Baseline runs loaded");
+        Self::merge(&records)
+    }
+
+    /// Merge the histograms of `records`; `None` if they answered no probe
+    pub fn merge(records: &[RunRecord]) -> Result<Option<Self>> {
+        let values: Vec<(u64, u64)> = records
+            .iter()
+            .flat_map(|record| record.histogram.iter().copied())
+            .collect();
+        let min_ns = records.iter().map(|record| record.min_ns).min();
+        let max_ns = records.iter().map(|record| record.max_ns).max();
+        let clamped = records.iter().map(|record| record.clamped_count).sum();
+        let stats = Statistics::from_recorded(
+            &values,
+            min_ns.unwrap_or_default(),
+            max_ns.unwrap_or_default(),
+            clamped,
+        )?;
+        if stats.count() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            runs: records.len(),
+            samples: stats.count(),
+            p50_ns: stats.percentile(0.5),
+            p99_ns: stats.percentile(0.99),
+        }))
+    }
+}
+
+/// P99 of a run against the baseline of the runs before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaselineOutcome {
+    pub baseline: Baseline,
+    /// P99 RTT of the run in nanoseconds
+    pub p99_ns: u64,
+    /// Deviation from the baseline P99 allowed, in percent
+    pub max_change_pct: f64,
+}
+
+impl BaselineOutcome {
+    /// Change of the P99 over the baseline P99, in percent
+    pub fn change_pct(&self) -> f64 {
+        (self.p99_ns as f64 - self.baseline.p99_ns as f64) / self.baseline.p99_ns.max(1) as f64
+            * 100.0
+    }
+
+    /// Whether the P99 stayed within the allowed deviation
+    pub fn within(&self) -> bool {
+        self.change_pct().abs() <= self.max_change_pct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(server: &str, latencies: &[u64], written_at: SystemTime) -> Result<RunRecord> {
+        let stats = Statistics::new(latencies)?;
+        let count = latencies.len();
+        let mut record = RunRecord::new(
+            server,
+            &stats,
+            count,
+            count,
+            0,
+            Duration::from_secs(1),
+            true,
+        );
+        record.written_at_unix_ms =
+            written_at.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        Ok(record)
+    }
+
+    #[test]
+    fn test_baseline_load() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("synapse-{}-baseline", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let now = SystemTime::now();
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        let fast = vec![40_000; 100];
+        let slow = vec![400_000; 100];
+
+        // Two runs of the last day, one too old, one of another server and the run itself
+        record("10.0.0.2:8080", &fast, now - hours(1))?.save(&dir.join("a.json"))?;
+        record("10.0.0.2:8080", &fast, now - hours(20))?.save(&dir.join("b.json"))?;
+        record("10.0.0.2:8080", &slow, now - hours(30))?.save(&dir.join("c.json"))?;
+        record("10.0.0.3:8080", &slow, now - hours(2))?.save(&dir.join("d.json"))?;
+        record("10.0.0.2:8080", &slow, now)?.save(&dir.join("e.json"))?;
+        fs::write(dir.join("notes.json"), "not a run record")?;
+
+        let baseline = Baseline::load(&dir, "10.0.0.2:8080", hours(24), now)?.unwrap();
+        assert_eq!((baseline.runs, baseline.samples), (2, 200));
+        assert!(
+            baseline.p99_ns.abs_diff(40_000) < 100,
+            "{}",
+            baseline.p99_ns
+        );
+        assert_eq!(Baseline::load(&dir, "10.0.0.9:8080", hours(24), now)?, None);
+        fs::remove_dir_all(&dir)?;
+
+        assert!(Baseline::load(&dir, "10.0.0.2:8080", hours(24), now).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_baseline_outcome() {
+        let baseline = Baseline {
+            runs: 12,
+            samples: 120_000,
+            p50_ns: 30_000,
+            p99_ns: 80_000,
+        };
+        let outcome = |p99_ns| BaselineOutcome {
+            baseline,
+            p99_ns,
+            max_change_pct: 20.0,
+        };
+        assert!((outcome(92_000).change_pct() - 15.0).abs() < 1e-9);
+        assert!(outcome(92_000).within());
+        assert!(!outcome(100_000).within());
+        // Faster by more than the limit deviates as well
+        assert!(!outcome(60_000).within());
+    }
+}
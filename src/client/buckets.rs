@@ -0,0 +1,293 @@
+//! Bucket boundaries of the latency distribution
+//!
+//! The report counts probes into ranges of RTT. The default ranges suit a LAN: 20 µs
+//! steps up to 100 µs, then coarser up to 10 ms, beyond which a probe is an outlier. Over
+//! a WAN or the internet nearly every probe lands in the last range, so `--buckets`
+//! picks others:
+//!
+//! - a list of upper edges, such as `100us,250us,1ms,5ms,20ms,100ms`: the ranges run
+//!   from 0 to the first edge, between consecutive edges, and beyond the last one for the
+//!   outliers
+//! - `auto`: edges on a log scale in 1-2-5 steps spanning the fastest to the slowest
+//!   probe, in decades instead when that takes more than [`AUTO_MAX_BUCKETS`] ranges
+//!
+//! The example packets of each range follow the same edges.
+
+use crate::client::slo;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Upper edges of the default ranges in microseconds; probes from the last on are
+/// outliers
+pub const DEFAULT_EDGES_US: [u64; 9] = [20, 40, 60, 80, 100, 200, 500, 1_000, 10_000];
+
+/// Empty default ranges from this one on (200 µs and up) are left out of the report
+const DEFAULT_HIDE_EMPTY_FROM: usize = 6;
+
+/// Most edges --buckets accepts
+pub const MAX_EDGES: usize = 32;
+
+/// Most ranges `--buckets auto` spreads 1-2-5 steps over before it falls back to decades
+pub const AUTO_MAX_BUCKETS: usize = 12;
+
+/// Steps of a decade `--buckets auto` places edges at
+const AUTO_STEPS: [u64; 3] = [1, 2, 5];
+
+/// Edges of the distribution, written on the command line as a list of latencies or
+/// `auto`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketEdges {
+    /// These upper edges, in nanoseconds, ascending
+    Custom(Vec<u64>),
+    /// Log-scale edges spanning the run's latencies
+    Auto,
+}
+
+impl BucketEdges {
+    /// Ranges for a run whose latencies span `min_ns` to `max_ns`
+    pub fn buckets(&self, min_ns: u64, max_ns: u64) -> Buckets {
+        match self {
+            BucketEdges::Custom(edges) => Buckets::from_edges(edges),
+            BucketEdges::Auto => Buckets::from_edges(&auto_edges(min_ns, max_ns)),
+        }
+    }
+}
+
+impl fmt::Display for BucketEdges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BucketEdges::Custom(edges) => {
+                let edges: Vec<String> = edges
+                    .iter()
+                    .map(|&ns| slo::format_threshold(Duration::from_nanos(ns)))
+                    .collect();
+                write!(f, "{}", edges.join(","))
+            }
+            BucketEdges::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for BucketEdges {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(BucketEdges::Auto);
+        }
+        let edges = s
+            .split(',')
+            .map(|edge| slo::parse_threshold(edge.trim()).map(|d| d.as_nanos() as u64))
+            .collect::<std::result::Result<Vec<u64>, String>>()?;
+        if edges.len() > MAX_EDGES {
+            return Err(format!(
+                "{} bucket edges are too many (at most {})",
+                edges.len(),
+                MAX_EDGES
+            ));
+        }
+        if edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(format!("bucket edges '{}' must be in ascending order", s));
+        }
+        Ok(BucketEdges::Custom(edges))
+    }
+}
+
+/// A range of RTT the distribution counts probes into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bucket {
+    /// Lowest RTT of the range in nanoseconds
+    pub low_ns: u64,
+    /// RTT the range stops short of in nanoseconds
+    pub high_ns: u64,
+    pub label: String,
+}
+
+/// Ranges of the distribution, followed by the outliers beyond the last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buckets {
+    pub buckets: Vec<Bucket>,
+    /// Label of the outliers, e.g. `>10 ms`
+    pub outlier_label: String,
+    /// Empty ranges from this one on are left out of the report
+    pub hide_empty_from: usize,
+}
+
+impl Default for Buckets {
+    /// The LAN ranges, up to 10 ms
+    fn default() -> Self {
+        let edges = DEFAULT_EDGES_US.map(|us| us * 1_000);
+        Self {
+            hide_empty_from: DEFAULT_HIDE_EMPTY_FROM,
+            ..Self::from_edges(&edges)
+        }
+    }
+}
+
+impl Buckets {
+    /// Ranges up to each of `edges` in nanoseconds, ascending; every range is reported
+    pub fn from_edges(edges: &[u64]) -> Self {
+        let buckets: Vec<Bucket> = edges
+            .iter()
+            .scan(0, |low_ns, &high_ns| {
+                let bucket = Bucket {
+                    low_ns: *low_ns,
+                    high_ns,
+                    label: range_label(*low_ns, high_ns),
+                };
+                *low_ns = high_ns;
+                Some(bucket)
+            })
+            .collect();
+        let (value, unit) = edge_parts(edges.last().copied().unwrap_or_default());
+        Self {
+            hide_empty_from: buckets.len(),
+            buckets,
+            outlier_label: format!(">{} {}", value, unit),
+        }
+    }
+
+    /// Number of ranges, outliers aside
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Whether there is no range, so every probe is an outlier
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Range `latency_ns` falls in; `None` for an outlier
+    pub fn index(&self, latency_ns: u64) -> Option<usize> {
+        self.buckets
+            .iter()
+            .position(|bucket| latency_ns >= bucket.low_ns && latency_ns < bucket.high_ns)
+    }
+}
+
+/// 1-2-5 edges from just above `min_ns` to just above `max_ns`, or decades if those are
+/// too many
+fn auto_edges(min_ns: u64, max_ns: u64) -> Vec<u64> {
+    let steps = |multipliers: &[u64]| -> Vec<u64> {
+        let mut edges = Vec::new();
+        let mut decade = 1u64;
+        'decades: while decade <= max_ns.max(1).saturating_mul(10) {
+            for &multiplier in multipliers {
+                let edge = decade.saturating_mul(multiplier);
+                if edge > min_ns {
+                    edges.push(edge);
+                }
+                if edge > max_ns {
+                    break 'decades;
+                }
+            }
+            decade = decade.saturating_mul(10);
+        }
+        edges
+    };
+    let edges = steps(&AUTO_STEPS);
+    if edges.len() <= AUTO_MAX_BUCKETS {
+        edges
+    } else {
+        steps(&[1])
+    }
+}
+
+/// An edge as a number and the unit it is written in, e.g. `("500", "µs")`
+fn edge_parts(ns: u64) -> (String, &'static str) {
+    let (scale, unit) = match ns {
+        1_000_000_000.. => (1e9, "s"),
+        1_000_000.. => (1e6, "ms"),
+        1_000.. => (1e3, "µs"),
+        _ => (1.0, "ns"),
+    };
+    ((ns as f64 / scale).to_string(), unit)
+}
+
+/// Label of the range from `low_ns` up to `high_ns`, e.g. `0-20 µs` or `500µs-1ms`
+fn range_label(low_ns: u64, high_ns: u64) -> String {
+    let (high, high_unit) = edge_parts(high_ns);
+    if low_ns == 0 {
+        return format!("0-{} {}", high, high_unit);
+    }
+    let (low, low_unit) = edge_parts(low_ns);
+    if low_unit == high_unit {
+        format!("{}-{} {}", low, high, high_unit)
+    } else {
+        format!("{}{}-{}{}", low, low_unit, high, high_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bucket_edges() {
+        assert_eq!("auto".parse(), Ok(BucketEdges::Auto));
+        let edges: BucketEdges = "100us, 250us,1ms,5ms,20ms,100ms".parse().unwrap();
+        assert_eq!(
+            edges,
+            BucketEdges::Custom(vec![
+                100_000,
+                250_000,
+                1_000_000,
+                5_000_000,
+                20_000_000,
+                100_000_000
+            ])
+        );
+        assert_eq!(edges.to_string(), "100µs,250µs,1ms,5ms,20ms,100ms");
+        assert!("1ms,500us".parse::<BucketEdges>().is_err());
+        assert!("1ms,1ms".parse::<BucketEdges>().is_err());
+        assert!("1,2".parse::<BucketEdges>().is_err());
+    }
+
+    #[test]
+    fn test_buckets() {
+        let lan = Buckets::default();
+        let labels: Vec<&str> = lan.buckets.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "0-20 µs",
+                "20-40 µs",
+                "40-60 µs",
+                "60-80 µs",
+                "80-100 µs",
+                "100-200 µs",
+                "200-500 µs",
+                "500µs-1ms",
+                "1-10 ms"
+            ]
+        );
+        assert_eq!(lan.outlier_label, ">10 ms");
+        assert_eq!(lan.index(19_999), Some(0));
+        assert_eq!(lan.index(20_000), Some(1));
+        assert_eq!(lan.index(10_000_000), None);
+
+        let wan = Buckets::from_edges(&[100_000, 1_500_000, 20_000_000]);
+        let labels: Vec<&str> = wan.buckets.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, ["0-100 µs", "100µs-1.5ms", "1.5-20 ms"]);
+        assert_eq!(wan.hide_empty_from, 3);
+        assert_eq!(wan.index(5_000_000), Some(2));
+    }
+
+    #[test]
+    fn test_auto_edges() {
+        // An internet path between 12 and 180 ms
+        let edges = auto_edges(12_000_000, 180_000_000);
+        assert_eq!(edges, [20_000_000, 50_000_000, 100_000_000, 200_000_000]);
+        let buckets = BucketEdges::Auto.buckets(12_000_000, 180_000_000);
+        assert_eq!(buckets.index(180_000_000), Some(3));
+
+        // From 3 µs to 2 s takes too many 1-2-5 steps; decades instead
+        let edges = auto_edges(3_000, 2_000_000_000);
+        assert_eq!(edges.first(), Some(&10_000));
+        assert_eq!(edges.last(), Some(&10_000_000_000));
+        assert!(edges.len() <= AUTO_MAX_BUCKETS);
+
+        assert_eq!(auto_edges(0, 0), [1]);
+    }
+}
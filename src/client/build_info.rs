@@ -0,0 +1,136 @@
+//! Build information and user tags of a run
+//!
+//! Comparing results across releases needs to know which code produced them. Every
+//! export (run record, report bundle, Pushgateway push, OTLP export) carries the
+//! [`BuildInfo`] of the client binary: its version, the git commit it was built from,
+//! the build profile and the rustc version, all embedded at compile time by `build.rs`.
+//! `--tag KEY=VALUE` adds free-form tags on top, such as `--tag build=$CI_COMMIT` for
+//! the commit of the system under test.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Tag names taken by the build information and the Pushgateway grouping labels
+pub const RESERVED_TAG_KEYS: [&str; 6] =
+    ["version", "git_sha", "profile", "rustc", "job", "instance"];
+
+/// Version and build of the synapse client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: String,
+    /// Git commit the binary was built from (`unknown` outside a git checkout)
+    pub git_sha: String,
+    /// Cargo build profile (`debug` or `release`)
+    pub profile: String,
+    /// Output of `rustc --version` for the compiler that built the binary
+    pub rustc: String,
+}
+
+impl BuildInfo {
+    /// Build information of the running binary
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: env!("SYNAPSE_GIT_SHA").to_string(),
+            profile: env!("SYNAPSE_BUILD_PROFILE").to_string(),
+            rustc: env!("SYNAPSE_RUSTC_VERSION").to_string(),
+        }
+    }
+
+    /// The git commit shortened to 12 characters, for display
+    pub fn short_sha(&self) -> &str {
+        self.git_sha.get(..12).unwrap_or(&self.git_sha)
+    }
+
+    /// The fields as `(name, value)` pairs
+    pub fn fields(&self) -> [(&'static str, &str); 4] {
+        [
+            ("version", &self.version),
+            ("git_sha", &self.git_sha),
+            ("profile", &self.profile),
+            ("rustc", &self.rustc),
+        ]
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {}, {})",
+            self.version,
+            self.short_sha(),
+            self.profile,
+            self.rustc
+        )
+    }
+}
+
+/// User tag of a run, given as `KEY=VALUE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    /// Tag name: letters, digits and underscores, not starting with a digit
+    pub key: String,
+    /// Tag value (may be empty)
+    pub value: String,
+}
+
+impl FromStr for Tag {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid tag '{}' (expected KEY=VALUE)", s))?;
+        let valid = key.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "invalid tag name '{}' (letters, digits and underscores, not starting with a digit)",
+                key
+            ));
+        }
+        if RESERVED_TAG_KEYS.contains(&key) {
+            return Err(format!("tag name '{}' is reserved", key));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let build = BuildInfo::current();
+        assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+        assert!(!build.git_sha.is_empty());
+        assert!(build.rustc.starts_with("rustc") || build.rustc == "unknown");
+        assert!(build.short_sha().len() <= 12);
+        for (name, _) in build.fields() {
+            assert!(RESERVED_TAG_KEYS.contains(&name));
+        }
+    }
+
+    #[test]
+    fn test_tag_parsing() {
+        let tag: Tag = "build=4f2a9c1".parse().unwrap();
+        assert_eq!(tag.key, "build");
+        assert_eq!(tag.value, "4f2a9c1");
+        assert_eq!("branch=a=b".parse::<Tag>().unwrap().value, "a=b");
+        assert_eq!("empty=".parse::<Tag>().unwrap().value, "");
+
+        assert!("build".parse::<Tag>().is_err());
+        assert!("=x".parse::<Tag>().is_err());
+        assert!("1st=x".parse::<Tag>().is_err());
+        assert!("ci-job=x".parse::<Tag>().is_err());
+        assert!("version=2".parse::<Tag>().is_err());
+        assert!("instance=x".parse::<Tag>().is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! Report bundle: every export of a run in one directory
+//!
+//! `--report-bundle DIR` writes the results of a run in all formats at once, so sharing
+//! a complete result set takes one flag rather than remembering which exports exist:
+//!
+//! | File              | Contents                                                   |
+//! |-------------------|------------------------------------------------------------|
+//! | `summary.json`    | Key figures of the run ([`RunSummary`])                    |
+//! | `report.html`     | Self-contained report with percentiles and a histogram     |
+//! | `packets.csv`     | Send time and RTT of every packet, lost ones included      |
+//! | `latency.hlog`    | RTT and component histograms as an HdrHistogram log        |
+//! | `run-record.json` | Run record, as written by `--checkpoint` (for `analyze`)   |
+//! | `manifest.json`   | Build, tags, server and seed, and a list of the files      |
+
+use crate::client::build_info::BuildInfo;
+use crate::client::constants::HGRM_UNIT_RATIO;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::MeasurementResult;
+use crate::client::output::RunSummary;
+use crate::client::record::RunRecord;
+use crate::client::statistics::Statistics;
+use crate::client::units::{NumberFormat, UnitSystem};
+use hdrhistogram::serialization::interval_log::{IntervalLogWriterBuilder, Tag};
+use hdrhistogram::serialization::V2DeflateSerializer;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// Name of the manifest within the bundle directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bars of the histogram in the HTML report
+const HISTOGRAM_BARS: usize = 24;
+
+/// Percentiles listed in the HTML report
+const REPORT_PERCENTILES: [(&str, f64); 5] = [
+    ("P50", 0.5),
+    ("P90", 0.9),
+    ("P99", 0.99),
+    ("P99.9", 0.999),
+    ("P99.99", 0.9999),
+];
+
+/// One file of a bundle, as listed in its manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BundleFile {
+    /// File name within the bundle directory
+    pub name: String,
+    /// Format of the file (e.g. `json`, `csv`, `hlog`)
+    pub format: String,
+    /// What the file holds
+    pub description: String,
+    /// Size of the file in bytes
+    pub bytes: u64,
+}
+
+/// Contents of `manifest.json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BundleManifest {
+    /// Version of the synapse client that wrote the bundle
+    pub synapse_version: String,
+    /// Build of the synapse client that wrote the bundle
+    pub build: BuildInfo,
+    /// User tags of the run (`--tag`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Wall-clock time the bundle was written (milliseconds since the UNIX epoch)
+    pub created_at_unix_ms: u64,
+    /// Server address the run was measured against
+    pub server: String,
+    /// Seed the run's randomized choices were derived from
+    pub seed: u64,
+    /// Files of the bundle, the manifest excluded
+    pub files: Vec<BundleFile>,
+}
+
+/// Write every export of a finished run into `dir`, creating it if needed
+pub fn write_bundle(
+    dir: &Path,
+    record: &RunRecord,
+    summary: &RunSummary,
+    result: &MeasurementResult,
+) -> Result<BundleManifest> {
+    fs::create_dir_all(dir)?;
+    let stats = record.statistics()?;
+    let mut files = Vec::new();
+    let mut add = |name: &str, format: &str, description: &str| -> Result<()> {
+        files.push(BundleFile {
+            name: name.to_string(),
+            format: format.to_string(),
+            description: description.to_string(),
+            bytes: fs::metadata(dir.join(name))?.len(),
+        });
+        Ok(())
+    };
+
+    write_json(&dir.join("summary.json"), summary)?;
+    add("summary.json", "json", "Key figures of the run")?;
+    fs::write(
+        dir.join("report.html"),
+        html_report(record, summary, &stats),
+    )?;
+    add(
+        "report.html",
+        "html",
+        "Self-contained report with percentiles and a latency histogram",
+    )?;
+    write_packets_csv(&dir.join("packets.csv"), result)?;
+    add(
+        "packets.csv",
+        "csv",
+        "Send time and RTT of every packet, lost ones included",
+    )?;
+    write_hlog(&dir.join("latency.hlog"), record, result)?;
+    add(
+        "latency.hlog",
+        "hlog",
+        "RTT, send-complete and receive-wait histograms as an HdrHistogram log",
+    )?;
+    write_hgrm(&dir.join("latency.hgrm"), &stats)?;
+    add(
+        "latency.hgrm",
+        "hgrm",
+        "RTT percentile distribution in microseconds, for HdrHistogram plotters",
+    )?;
+    record.save(&dir.join("run-record.json"))?;
+    add(
+        "run-record.json",
+        "json",
+        "Run record, readable with `client analyze`",
+    )?;
+
+    let manifest = BundleManifest {
+        synapse_version: env!("CARGO_PKG_VERSION").to_string(),
+        build: BuildInfo::current(),
+        tags: record.tags.clone(),
+        created_at_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        server: record.server.clone(),
+        seed: result.seed,
+        files,
+    };
+    write_json(&dir.join(MANIFEST_FILE), &manifest)?;
+    debug!(dir = %dir.display(), files = manifest.files.len(), "Report bundle written");
+    Ok(manifest)
+}
+
+/// Write `value` as pretty-printed JSON
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let json = serde_json::to_vec_pretty(value).map_err(|e| {
+        ClientError::Measurement(format!("Failed to serialize {}: {}", path.display(), e))
+    })?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Write one row per sent packet, in sequence order; lost packets have no RTT
+fn write_packets_csv(path: &Path, result: &MeasurementResult) -> Result<()> {
+    let mut rows: Vec<(u64, Option<u64>, Option<u64>)> = result
+        .sequences
+        .iter()
+        .zip(&result.send_offsets)
+        .zip(&result.latencies)
+        .map(|((&sequence, &offset), &rtt)| (sequence, Some(offset), Some(rtt)))
+        .chain(
+            result
+                .lost_sequences
+                .iter()
+                .map(|&sequence| (sequence, None, None)),
+        )
+        .collect();
+    rows.sort_unstable_by_key(|&(sequence, _, _)| sequence);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "sequence,send_offset_ns,rtt_ns,lost")?;
+    let field = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    for (sequence, offset, rtt) in rows {
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            sequence,
+            field(offset),
+            field(rtt),
+            rtt.is_none()
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the RTT histogram (untagged) and its components (tagged) as one interval each
+fn write_hlog(path: &Path, record: &RunRecord, result: &MeasurementResult) -> Result<()> {
+    let histograms = [
+        (None, record.statistics()?),
+        (Some("send-complete"), record.send_statistics()?),
+        (Some("receive-wait"), record.recv_wait_statistics()?),
+    ];
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut serializer = V2DeflateSerializer::new();
+    let mut log = IntervalLogWriterBuilder::new()
+        .add_comment(&format!(
+            "synapse {} against {}; values in nanoseconds",
+            env!("CARGO_PKG_VERSION"),
+            record.server
+        ))
+        .with_start_time(result.started_at)
+        .with_base_time(result.started_at)
+        .with_max_value_divisor(1e6)
+        .begin_log_with(&mut writer, &mut serializer)?;
+    for (tag, stats) in &histograms {
+        if stats.count() == 0 {
+            continue;
+        }
+        log.write_histogram(
+            stats.histogram(),
+            std::time::Duration::ZERO,
+            result.elapsed,
+            (*tag).and_then(Tag::new),
+        )
+        .map_err(|e| ClientError::Measurement(format!("Failed to write histogram log: {}", e)))?;
+    }
+    drop(log);
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the percentile distribution of `stats` to `path` in the `.hgrm` format
+pub fn write_hgrm(path: &Path, stats: &Statistics) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    stats.write_hgrm(&mut writer, HGRM_UNIT_RATIO)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Render the self-contained HTML report
+fn html_report(record: &RunRecord, summary: &RunSummary, stats: &Statistics) -> String {
+    let us = |ns: f64| format!("{:.1} µs", ns / 1000.0);
+    let exact = NumberFormat::default();
+    let compact = NumberFormat {
+        units: UnitSystem::Si,
+        ..NumberFormat::default()
+    };
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Synapse report: {server}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 48em; margin: 2em auto; color: #222; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1.5em; }}\n\
+         td, th {{ padding: 0.2em 0.8em; text-align: right; }}\n\
+         th:first-child, td:first-child {{ text-align: left; }}\n\
+         .bar {{ background: #3b82c4; height: 0.9em; }}\n\
+         .pass {{ color: #1a7f37; }} .fail {{ color: #c62828; }}\n\
+         </style>\n</head>\n<body>\n<h1>Synapse report</h1>\n",
+        server = escape_html(&record.server)
+    );
+    let verdict = if summary.passed() {
+        "<span class=\"pass\">PASS</span>"
+    } else {
+        "<span class=\"fail\">FAIL</span>"
+    };
+    let _ = writeln!(
+        html,
+        "<table>\n<tr><td>Server</td><td>{}</td></tr>\n\
+         <tr><td>Packets</td><td>{} sent, {} lost ({:.2}%)</td></tr>\n\
+         <tr><td>Duration</td><td>{:.2} s</td></tr>\n\
+         <tr><td>Seed</td><td>{}</td></tr>\n\
+         <tr><td>Verdict</td><td>{}</td></tr>\n</table>",
+        escape_html(&record.server),
+        exact.count(summary.total_packets as u64),
+        exact.count(summary.lost_packets as u64),
+        summary.loss_pct(),
+        record.elapsed_secs,
+        record.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+        verdict
+    );
+
+    html.push_str("<h2>Round-trip latency</h2>\n<table>\n");
+    let _ = writeln!(
+        html,
+        "<tr><td>Mean</td><td>{}</td></tr>",
+        us(summary.mean_ns)
+    );
+    let _ = writeln!(
+        html,
+        "<tr><td>Min</td><td>{}</td></tr>",
+        us(stats.min() as f64)
+    );
+    for (label, quantile) in REPORT_PERCENTILES {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            label,
+            us(stats.percentile(quantile).min(stats.max()) as f64)
+        );
+    }
+    let _ = writeln!(
+        html,
+        "<tr><td>Max</td><td>{}</td></tr>\n</table>",
+        us(stats.max() as f64)
+    );
+
+    let bars = histogram_bars(&record.histogram);
+    if let Some(peak) = bars.iter().map(|&(_, _, count)| count).max() {
+        html.push_str(
+            "<h2>Histogram</h2>\n<table>\n<tr><th>RTT</th><th>Packets</th><th></th></tr>\n",
+        );
+        for (low, high, count) in bars {
+            let _ = writeln!(
+                html,
+                "<tr title=\"{} packets\"><td>{:.1}–{:.1} µs</td><td>{}</td>\
+                 <td style=\"width: 20em\"><div class=\"bar\" style=\"width: {:.1}%\"></div></td></tr>",
+                exact.count(count),
+                low as f64 / 1000.0,
+                high as f64 / 1000.0,
+                compact.count(count),
+                count as f64 / peak.max(1) as f64 * 100.0
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    let tags: String = record
+        .tags
+        .iter()
+        .map(|(key, value)| format!(" {}={}", escape_html(key), escape_html(value)))
+        .collect();
+    let _ = writeln!(
+        html,
+        "<p>Written by synapse {}.{}</p>\n</body>\n</html>",
+        escape_html(&BuildInfo::current().to_string()),
+        if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" Tags:{}.", tags)
+        }
+    );
+    html
+}
+
+/// Group `(value, count)` pairs into log-spaced bars as `(low, high, count)`
+fn histogram_bars(histogram: &[(u64, u64)]) -> Vec<(u64, u64, u64)> {
+    let (Some(&(min, _)), Some(&(max, _))) = (histogram.first(), histogram.last()) else {
+        return Vec::new();
+    };
+    let min = min.max(1);
+    if max <= min {
+        return vec![(min, max, histogram.iter().map(|&(_, count)| count).sum())];
+    }
+    let ratio = (max as f64 / min as f64).powf(1.0 / HISTOGRAM_BARS as f64);
+    let edge = |i: usize| (min as f64 * ratio.powi(i as i32)).round() as u64;
+    let mut bars: Vec<(u64, u64, u64)> = (0..HISTOGRAM_BARS)
+        .map(|i| (edge(i), edge(i + 1), 0))
+        .collect();
+    for &(value, count) in histogram {
+        let index = ((value.max(min) as f64 / min as f64).ln() / ratio.ln()) as usize;
+        bars[index.min(HISTOGRAM_BARS - 1)].2 += count;
+    }
+    bars
+}
+
+/// Escape text for an HTML element or attribute
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn result() -> MeasurementResult {
+        MeasurementResult {
+            latencies: vec![20_000, 30_000, 45_000],
+            send_durations: vec![2_000; 4],
+            recv_wait_durations: vec![18_000, 28_000, 43_000],
+            lost_packets: 1,
+            total_packets: 4,
+            elapsed: Duration::from_millis(5),
+            seed: 7,
+            system_timeline: None,
+            sequences: vec![0, 1, 3],
+            send_offsets: vec![0, 1_000_000, 3_000_000],
+            idle_gaps: vec![0, 980_000, 1_970_000],
+            started_at: SystemTime::now(),
+            warmup: None,
+            lost_sequences: vec![2],
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: None,
+            reply_dscp: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_write_bundle() -> Result<()> {
+        let result = result();
+        let stats = Statistics::new(&result.latencies)?;
+        let record = RunRecord::new("10.0.0.2:8080", &stats, 4, 4, 1, result.elapsed, true)
+            .with_components(
+                &Statistics::new(&result.send_durations)?,
+                &Statistics::new(&result.recv_wait_durations)?,
+            )
+            .with_seed(result.seed)
+            .with_tags(BTreeMap::from([(
+                "build".to_string(),
+                "4f2a9c1".to_string(),
+            )]));
+        let summary = RunSummary::new(&stats, 1, 4, &result.send_durations);
+        let dir = std::env::temp_dir().join(format!("synapse-{}-bundle", std::process::id()));
+
+        let manifest = write_bundle(&dir, &record, &summary, &result)?;
+        let names: Vec<&str> = manifest.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "summary.json",
+                "report.html",
+                "packets.csv",
+                "latency.hlog",
+                "latency.hgrm",
+                "run-record.json"
+            ]
+        );
+        assert!(manifest.files.iter().all(|f| f.bytes > 0));
+        assert!(dir.join(MANIFEST_FILE).exists());
+        assert_eq!(manifest.build, BuildInfo::current());
+        assert_eq!(manifest.tags, record.tags);
+
+        let csv = fs::read_to_string(dir.join("packets.csv"))?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "sequence,send_offset_ns,rtt_ns,lost");
+        assert_eq!(lines[3], "2,,,true");
+        assert_eq!(lines[4], "3,3000000,45000,false");
+
+        let hlog = fs::read_to_string(dir.join("latency.hlog"))?;
+        assert!(hlog.contains("#[StartTime: "));
+        assert!(hlog
+            .lines()
+            .any(|line| line.starts_with("Tag=receive-wait,")));
+        let html = fs::read_to_string(dir.join("report.html"))?;
+        assert!(html.contains("10.0.0.2:8080"));
+        assert!(html.contains("<tr title=\"1 packets\">"));
+        assert!(html.contains("Tags: build=4f2a9c1."));
+        assert_eq!(RunRecord::load(&dir.join("run-record.json"))?, record);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_histogram_bars() {
+        let bars = histogram_bars(&[(1_000, 5), (10_000, 3), (100_000, 1)]);
+        assert_eq!(bars.len(), HISTOGRAM_BARS);
+        assert_eq!(bars.iter().map(|&(_, _, count)| count).sum::<u64>(), 9);
+        assert_eq!(bars[0].2, 5);
+        assert_eq!(bars[HISTOGRAM_BARS - 1].2, 1);
+        assert_eq!(histogram_bars(&[(5_000, 2)]), vec![(5_000, 5_000, 2)]);
+        assert!(histogram_bars(&[]).is_empty());
+        assert_eq!(escape_html("<a&b>"), "&lt;a&amp;b&gt;");
+    }
+}
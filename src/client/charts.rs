@@ -0,0 +1,331 @@
+//! SVG and PNG charts of a run
+//!
+//! The terminal report does not paste into a wiki page or a dashboard panel.
+//! `--chart-dir <DIR>` renders two charts of the run into the directory, each as SVG for
+//! the web and as PNG for everything else:
+//!
+//! - `latency-cdf`: the share of answered probes at or below each RTT, on a log scale,
+//!   with the P50, P99 and P99.9 marked
+//! - `latency-over-time`: the P50 and P99 of the probes sent in each slice of the run
+//!   (see [`crate::client::trend`]), gaps left where no probe was answered
+//!
+//! Labels are drawn with a system sans-serif font, so the PNG charts need one installed.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::MeasurementResult;
+use crate::client::trend::{self, TrendBucket};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Width and height of every chart, in pixels
+pub const CHART_SIZE: (u32, u32) = (960, 540);
+
+/// Most points drawn of the CDF
+pub const CDF_POINTS: usize = 1_000;
+
+/// Most slices of the run charted over time
+pub const TIME_SLICES: usize = 200;
+
+/// Color of the P50 line over time and of the CDF
+const P50_COLOR: RGBColor = RGBColor(59, 130, 196);
+
+/// Color of the P99 line over time
+const P99_COLOR: RGBColor = RGBColor(198, 40, 40);
+
+/// RTT of a slice charted over time, in nanoseconds
+type RttOf = fn(&TrendBucket) -> Option<u64>;
+
+/// One of the charts of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chart {
+    Cdf,
+    OverTime,
+}
+
+impl Chart {
+    /// File name of the chart, without its extension
+    fn stem(self) -> &'static str {
+        match self {
+            Chart::Cdf => "latency-cdf",
+            Chart::OverTime => "latency-over-time",
+        }
+    }
+}
+
+/// Points of the CDF of `latencies`: up to `points` (RTT in µs, share of probes at or
+/// below it in percent), evenly spread over the ranks and ending at the slowest
+pub fn cdf_points(latencies: &[u64], points: usize) -> Vec<(f64, f64)> {
+    let mut sorted = latencies.to_vec();
+    sorted.sort_unstable();
+    let count = sorted.len();
+    if count == 0 || points == 0 {
+        return Vec::new();
+    }
+    let step = (count as f64 / points as f64).max(1.0);
+    let mut ranks: Vec<usize> = (0..points.min(count))
+        .map(|i| ((i as f64 + 1.0) * step).ceil() as usize - 1)
+        .map(|rank| rank.min(count - 1))
+        .collect();
+    ranks.dedup();
+    ranks
+        .into_iter()
+        .map(|rank| {
+            (
+                sorted[rank] as f64 / 1000.0,
+                (rank + 1) as f64 / count as f64 * 100.0,
+            )
+        })
+        .collect()
+}
+
+/// Render the charts of `result` into `dir`, created if need be, and return the files
+/// written; none if no probe was answered
+pub fn write_charts(dir: &Path, result: &MeasurementResult) -> Result<Vec<PathBuf>> {
+    if result.latencies.is_empty() {
+        return Ok(Vec::new());
+    }
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for chart in [Chart::Cdf, Chart::OverTime] {
+        let svg = dir.join(format!("{}.svg", chart.stem()));
+        let root = SVGBackend::new(&svg, CHART_SIZE).into_drawing_area();
+        draw(chart, &root, result)?;
+        root.present().map_err(chart_error)?;
+        drop(root);
+        written.push(svg);
+
+        let png = dir.join(format!("{}.png", chart.stem()));
+        let root = BitMapBackend::new(&png, CHART_SIZE).into_drawing_area();
+        draw(chart, &root, result)?;
+        root.present().map_err(chart_error)?;
+        drop(root);
+        written.push(png);
+    }
+    debug!(dir = %dir.display(), files = written.len(), "Charts written");
+    Ok(written)
+}
+
+fn chart_error(e: impl std::fmt::Display) -> ClientError {
+    ClientError::Export(format!("Failed to draw chart: {}", e))
+}
+
+/// Draw `chart` of `result` on `root`
+fn draw<DB: DrawingBackend>(
+    chart: Chart,
+    root: &DrawingArea<DB, Shift>,
+    result: &MeasurementResult,
+) -> Result<()> {
+    root.fill(&WHITE).map_err(chart_error)?;
+    match chart {
+        Chart::Cdf => draw_cdf(root, &result.latencies),
+        Chart::OverTime => draw_over_time(
+            root,
+            &trend::latency_over_time(
+                &result.latencies,
+                &result.send_offsets,
+                result.elapsed,
+                TIME_SLICES,
+            ),
+        ),
+    }
+}
+
+/// Range of a log-scale RTT axis in µs covering `lowest` to `highest` nanoseconds
+fn rtt_axis(lowest: u64, highest: u64) -> std::ops::Range<f64> {
+    let lowest = (lowest as f64 / 1000.0).max(0.001);
+    let highest = (highest as f64 / 1000.0).max(lowest * 1.5);
+    lowest / 1.2..highest * 1.2
+}
+
+fn draw_cdf<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, latencies: &[u64]) -> Result<()> {
+    let mut sorted = latencies.to_vec();
+    sorted.sort_unstable();
+    let (Some(&lowest), Some(&highest)) = (sorted.first(), sorted.last()) else {
+        return Ok(());
+    };
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(
+            format!("Latency CDF ({} answered probes)", sorted.len()),
+            ("sans-serif", 22),
+        )
+        .margin(16)
+        .x_label_area_size(44)
+        .y_label_area_size(56)
+        .build_cartesian_2d(rtt_axis(lowest, highest).log_scale(), 0f64..100f64)
+        .map_err(chart_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("RTT (µs)")
+        .max_light_lines(4)
+        .y_desc("Probes at or below (%)")
+        .x_label_formatter(&|us| format!("{:.1}", us))
+        .draw()
+        .map_err(chart_error)?;
+    chart
+        .draw_series(LineSeries::new(
+            cdf_points(&sorted, CDF_POINTS),
+            P50_COLOR.stroke_width(2),
+        ))
+        .map_err(chart_error)?;
+
+    // Nearest-rank percentiles, as the report gives them
+    let markers = [("P50", 0.5), ("P99", 0.99), ("P99.9", 0.999)].map(|(label, p)| {
+        let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+        let us = sorted[rank - 1] as f64 / 1000.0;
+        EmptyElement::at((us, p * 100.0))
+            + Circle::new((0, 0), 4, P99_COLOR.filled())
+            + Text::new(
+                format!("{} {:.1} µs", label, us),
+                (8, 6),
+                ("sans-serif", 14),
+            )
+    });
+    chart.draw_series(markers).map_err(chart_error)?;
+    Ok(())
+}
+
+fn draw_over_time<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    buckets: &[TrendBucket],
+) -> Result<()> {
+    let lowest = buckets.iter().filter_map(|bucket| bucket.p50_ns).min();
+    let highest = buckets.iter().filter_map(|bucket| bucket.p99_ns).max();
+    let (Some(lowest), Some(highest), Some(last)) = (lowest, highest, buckets.last()) else {
+        return Ok(());
+    };
+    let width = buckets.get(1).map_or(last.start, |second| second.start);
+    let end = (last.start + width).as_secs_f64();
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(
+            "Latency over time (P50 and P99 per slice)",
+            ("sans-serif", 22),
+        )
+        .margin(16)
+        .x_label_area_size(44)
+        .y_label_area_size(56)
+        .build_cartesian_2d(
+            0f64..end.max(f64::EPSILON),
+            rtt_axis(lowest, highest).log_scale(),
+        )
+        .map_err(chart_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("Time since the start of the run (s)")
+        .max_light_lines(4)
+        .y_desc("RTT (µs)")
+        .y_label_formatter(&|us| format!("{:.1}", us))
+        .draw()
+        .map_err(chart_error)?;
+
+    let percentiles: [(&str, RGBColor, RttOf); 2] = [
+        ("P50", P50_COLOR, |bucket| bucket.p50_ns),
+        ("P99", P99_COLOR, |bucket| bucket.p99_ns),
+    ];
+    for (label, color, rtt_ns) in percentiles {
+        // One line per run of answered slices, so a silent stretch shows as a gap
+        for (index, segment) in buckets
+            .split(|bucket| rtt_ns(bucket).is_none())
+            .filter(|segment| !segment.is_empty())
+            .enumerate()
+        {
+            let points = segment.iter().map(|bucket| {
+                (
+                    bucket.start.as_secs_f64(),
+                    rtt_ns(bucket).unwrap_or_default() as f64 / 1000.0,
+                )
+            });
+            let series = chart
+                .draw_series(LineSeries::new(points, color.stroke_width(2)))
+                .map_err(chart_error)?;
+            if index == 0 {
+                series.label(label).legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                });
+            }
+        }
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(chart_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_cdf_points() {
+        let latencies: Vec<u64> = (1..=10).rev().map(|i| i * 1_000).collect();
+        let points = cdf_points(&latencies, 100);
+        assert_eq!(points.len(), 10);
+        assert_eq!(points[0], (1.0, 10.0));
+        assert_eq!(points[9], (10.0, 100.0));
+
+        // Thinned out, the slowest probe is still the last point
+        let points = cdf_points(&latencies, 4);
+        assert_eq!(points.len(), 4);
+        assert_eq!(points.last(), Some(&(10.0, 100.0)));
+        assert!(cdf_points(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_write_charts() -> Result<()> {
+        let latencies: Vec<u64> = (0..500).map(|i| 20_000 + (i % 50) * 1_000).collect();
+        let mut result = MeasurementResult {
+            send_offsets: (0..500).map(|i| i * 1_000_000).collect(),
+            sequences: (0..500).collect(),
+            send_durations: vec![1_000; 500],
+            recv_wait_durations: vec![19_000; 500],
+            idle_gaps: vec![0; 500],
+            latencies,
+            lost_packets: 0,
+            total_packets: 500,
+            elapsed: Duration::from_millis(500),
+            seed: 7,
+            system_timeline: None,
+            started_at: SystemTime::now(),
+            warmup: None,
+            lost_sequences: Vec::new(),
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: None,
+            reply_dscp: Default::default(),
+        };
+        let dir = std::env::temp_dir().join(format!("synapse-{}-charts", std::process::id()));
+
+        let written = write_charts(&dir, &result)?;
+        let names: Vec<String> = written
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "latency-cdf.svg",
+                "latency-cdf.png",
+                "latency-over-time.svg",
+                "latency-over-time.png"
+            ]
+        );
+        assert!(fs::read_to_string(dir.join("latency-cdf.svg"))?.contains("P99.9"));
+        assert!(fs::metadata(dir.join("latency-over-time.png"))?.len() > 0);
+        fs::remove_dir_all(&dir)?;
+
+        result.latencies.clear();
+        assert!(write_charts(&dir, &result)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Crash-safe periodic checkpointing of measurement results
+//!
+//! Long soak tests can run for hours; a crash or OOM near the end should not lose
+//! everything measured so far. The [`Checkpointer`] keeps its own incremental histogram
+//! and periodically flushes it as a [`RunRecord`] that `client analyze` can read.
+
+use crate::client::error::Result;
+use crate::client::measurement::ProbeTiming;
+use crate::client::record::{PacketTimestamps, RunRecord};
+use crate::client::statistics::Statistics;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, info};
+
+/// Periodically writes the running results of a measurement phase to a checkpoint file
+pub struct Checkpointer {
+    path: PathBuf,
+    interval: Duration,
+    server: String,
+    planned_packets: usize,
+    stats: Statistics,
+    send_stats: Statistics,
+    recv_wait_stats: Statistics,
+    sent_packets: usize,
+    lost_packets: usize,
+    seed: Option<u64>,
+    packets: Option<PacketTimestamps>,
+    tags: BTreeMap<String, String>,
+    last_write: Instant,
+}
+
+impl Checkpointer {
+    /// Create a checkpointer and write an initial (empty) checkpoint
+    ///
+    /// Writing up front surfaces an unwritable path before the run starts rather
+    /// than at the first checkpoint interval.
+    pub fn new(
+        path: &Path,
+        interval: Duration,
+        server: &str,
+        planned_packets: usize,
+    ) -> Result<Self> {
+        let mut checkpointer = Self {
+            path: path.to_path_buf(),
+            interval,
+            server: server.to_string(),
+            planned_packets,
+            stats: Statistics::empty()?,
+            send_stats: Statistics::empty()?,
+            recv_wait_stats: Statistics::empty()?,
+            sent_packets: 0,
+            lost_packets: 0,
+            seed: None,
+            packets: None,
+            tags: BTreeMap::new(),
+            last_write: Instant::now(),
+        };
+        checkpointer.write(Duration::ZERO, false)?;
+        info!(
+            path = %path.display(),
+            interval_secs = interval.as_secs_f64(),
+            "Checkpointing enabled"
+        );
+        Ok(checkpointer)
+    }
+
+    /// Record the run's seed in every following checkpoint
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Also store the application timestamps of every `packet_size`-byte packet, for
+    /// `analyze --pcap`
+    pub fn with_packet_timestamps(mut self, packet_size: usize) -> Self {
+        self.packets = Some(PacketTimestamps::new(SystemTime::UNIX_EPOCH, packet_size));
+        self
+    }
+
+    /// Record the user tags of the run in every following checkpoint
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Mark the start of the measurement phase; packet send times are relative to it
+    pub fn set_started_at(&mut self, started_at: SystemTime) {
+        if let Some(packets) = &mut self.packets {
+            *packets = PacketTimestamps::new(started_at, packets.packet_size);
+        }
+    }
+
+    /// Record the application timestamps of one probe (if packet timestamps are stored)
+    pub fn record_timestamps(&mut self, sequence: u64, send_offset_ns: u64, probe: &ProbeTiming) {
+        if let Some(packets) = &mut self.packets {
+            packets.push(sequence, send_offset_ns, probe.rtt_ns);
+        }
+    }
+
+    /// Record the outcome of one probe
+    pub fn record(&mut self, probe: &ProbeTiming) -> Result<()> {
+        self.sent_packets += 1;
+        self.send_stats.record(probe.send_ns)?;
+        if let Some(recv_wait) = probe.recv_wait_ns {
+            self.recv_wait_stats.record(recv_wait)?;
+        }
+        match probe.rtt_ns {
+            Some(latency) => self.stats.record(latency),
+            None => {
+                self.lost_packets += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Write a checkpoint if the checkpoint interval has elapsed since the last write
+    ///
+    /// Returns true if a checkpoint was written.
+    pub fn maybe_write(&mut self, elapsed: Duration) -> Result<bool> {
+        if self.last_write.elapsed() < self.interval {
+            return Ok(false);
+        }
+        self.write(elapsed, false)?;
+        Ok(true)
+    }
+
+    /// Write a checkpoint now
+    ///
+    /// `complete` marks the final checkpoint of a measurement phase that ran to the end.
+    pub fn write(&mut self, elapsed: Duration, complete: bool) -> Result<()> {
+        let record = RunRecord::new(
+            &self.server,
+            &self.stats,
+            self.planned_packets,
+            self.sent_packets,
+            self.lost_packets,
+            elapsed,
+            complete,
+        )
+        .with_components(&self.send_stats, &self.recv_wait_stats)
+        .with_tags(self.tags.clone());
+        let record = match self.seed {
+            Some(seed) => record.with_seed(seed),
+            None => record,
+        };
+        let record = match &self.packets {
+            Some(packets) => record.with_packets(packets.clone()),
+            None => record,
+        };
+        record.save(&self.path)?;
+        self.last_write = Instant::now();
+        debug!(
+            sent_packets = self.sent_packets,
+            lost_packets = self.lost_packets,
+            complete = complete,
+            "Checkpoint written"
+        );
+        Ok(())
+    }
+
+    /// Path of the checkpoint file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("synapse-{}-{}", std::process::id(), name))
+    }
+
+    fn probe(rtt_ns: Option<u64>) -> ProbeTiming {
+        ProbeTiming {
+            send_ns: 500,
+            rtt_ns,
+            recv_wait_ns: rtt_ns.map(|rtt| rtt - 500),
+            path: None,
+            kernel: None,
+        }
+    }
+
+    #[test]
+    fn test_checkpointer_writes_initial_checkpoint() -> Result<()> {
+        let path = temp_file("checkpoint-initial.json");
+        let _checkpointer = Checkpointer::new(&path, Duration::from_secs(60), "host:1", 100)?;
+
+        let record = RunRecord::load(&path)?;
+        fs::remove_file(&path)?;
+
+        assert!(!record.complete);
+        assert_eq!(record.planned_packets, 100);
+        assert_eq!(record.sent_packets, 0);
+        assert!(record.histogram.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpointer_records_and_respects_interval() -> Result<()> {
+        let path = temp_file("checkpoint-interval.json");
+        let mut checkpointer = Checkpointer::new(&path, Duration::from_secs(3600), "host:1", 10)?;
+
+        checkpointer.record(&probe(Some(1000)))?;
+        checkpointer.record(&probe(None))?;
+        checkpointer.record(&probe(Some(3000)))?;
+
+        // Interval has not elapsed yet
+        assert!(!checkpointer.maybe_write(Duration::from_millis(5))?);
+        assert_eq!(RunRecord::load(&path)?.sent_packets, 0);
+
+        checkpointer.write(Duration::from_millis(5), true)?;
+        let record = RunRecord::load(&path)?;
+        fs::remove_file(&path)?;
+
+        assert!(record.complete);
+        assert_eq!(record.sent_packets, 3);
+        assert_eq!(record.lost_packets, 1);
+        assert_eq!(record.statistics()?.count(), 2);
+        assert_eq!(record.send_statistics()?.count(), 3);
+        assert_eq!(record.recv_wait_statistics()?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpointer_zero_interval_always_writes() -> Result<()> {
+        let path = temp_file("checkpoint-zero.json");
+        let mut checkpointer = Checkpointer::new(&path, Duration::ZERO, "host:1", 10)?;
+        checkpointer.record(&probe(Some(1000)))?;
+
+        assert!(checkpointer.maybe_write(Duration::from_millis(1))?);
+        assert_eq!(RunRecord::load(&path)?.sent_packets, 1);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpointer_stores_packet_timestamps() -> Result<()> {
+        let path = temp_file("checkpoint-packets.json");
+        let mut checkpointer =
+            Checkpointer::new(&path, Duration::ZERO, "host:1", 10)?.with_packet_timestamps(64);
+        checkpointer.set_started_at(SystemTime::UNIX_EPOCH + Duration::from_secs(5));
+        checkpointer.record_timestamps(0, 100, &probe(Some(1000)));
+        checkpointer.record_timestamps(1, 2_000, &probe(None));
+        checkpointer.write(Duration::from_millis(1), true)?;
+
+        let packets = RunRecord::load(&path)?.packets;
+        fs::remove_file(&path)?;
+        let packets = packets.expect("packet timestamps stored");
+        assert_eq!(packets.started_at_unix_ns, 5_000_000_000);
+        assert_eq!(packets.packet_size, 64);
+        assert_eq!(packets.sequences, vec![0, 1]);
+        assert_eq!(packets.send_offsets_ns, vec![100, 2_000]);
+        assert_eq!(packets.rtts_ns, vec![Some(1000), None]);
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Clock the round trips are timed with
+//!
+//! Probes are timed with the monotonic clock by default. It never steps, but NTP may
+//! slew its rate by up to 500 ppm to pull the wall clock back in line, and in a VM it is
+//! only as good as the hypervisor's clock source. `--clock` picks another one:
+//!
+//! - `monotonic`: `CLOCK_MONOTONIC` (the default, what `std::time::Instant` reads)
+//! - `monotonic_raw`: `CLOCK_MONOTONIC_RAW`, the hardware clock without NTP's rate
+//!   adjustments (Linux only)
+//! - `tsc`: the CPU's time stamp counter, read with `rdtsc` and calibrated against the
+//!   monotonic clock when the run starts (x86_64 CPUs with an invariant TSC only)
+//!
+//! The clock times the RTT and its send and receive-wait components; send offsets and
+//! wall-clock times are unaffected.
+
+use crate::client::error::{ClientError, Result};
+use clap::ValueEnum;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Time the TSC is counted against the monotonic clock for its rate
+pub const TSC_CALIBRATION: Duration = Duration::from_millis(20);
+
+/// Clock source selectable with `--clock`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClockSource {
+    /// CLOCK_MONOTONIC, subject to NTP slewing
+    #[default]
+    Monotonic,
+    /// CLOCK_MONOTONIC_RAW, without NTP's rate adjustments (Linux only)
+    #[value(name = "monotonic_raw", alias = "monotonic-raw")]
+    MonotonicRaw,
+    /// The CPU's time stamp counter (x86_64 with an invariant TSC only)
+    Tsc,
+}
+
+impl ClockSource {
+    /// Whether the clock can be read on this machine
+    pub fn is_available(self) -> bool {
+        match self {
+            ClockSource::Monotonic => true,
+            ClockSource::MonotonicRaw => monotonic_raw_ns().is_some(),
+            ClockSource::Tsc => has_invariant_tsc(),
+        }
+    }
+}
+
+impl fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockSource::Monotonic => write!(f, "monotonic"),
+            ClockSource::MonotonicRaw => write!(f, "monotonic_raw"),
+            ClockSource::Tsc => write!(f, "tsc"),
+        }
+    }
+}
+
+/// A clock source ready to read, in nanoseconds since an arbitrary origin
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    source: ClockSource,
+    /// Origin of the monotonic clock readings
+    origin: Instant,
+    /// TSC reading at the origin and ticks per nanosecond, for the TSC
+    tsc: Option<(u64, f64)>,
+}
+
+impl Default for Clock {
+    /// The monotonic clock
+    fn default() -> Self {
+        Self {
+            source: ClockSource::Monotonic,
+            origin: Instant::now(),
+            tsc: None,
+        }
+    }
+}
+
+impl Clock {
+    /// Set up `source`, calibrating the TSC (which takes [`TSC_CALIBRATION`])
+    pub fn new(source: ClockSource) -> Result<Self> {
+        if !source.is_available() {
+            return Err(ClientError::Config(format!(
+                "the {} clock is not available on this machine",
+                source
+            )));
+        }
+        let mut clock = Self {
+            source,
+            ..Self::default()
+        };
+        if source == ClockSource::Tsc {
+            let start_ticks = read_tsc().unwrap_or_default();
+            let start = Instant::now();
+            std::thread::sleep(TSC_CALIBRATION);
+            let ticks = read_tsc().unwrap_or_default().saturating_sub(start_ticks);
+            let ticks_per_ns = ticks as f64 / start.elapsed().as_nanos() as f64;
+            debug!(ghz = ticks_per_ns, "TSC calibrated");
+            clock.tsc = Some((start_ticks, ticks_per_ns));
+        }
+        Ok(clock)
+    }
+
+    /// Clock source read
+    pub fn source(&self) -> ClockSource {
+        self.source
+    }
+
+    /// Calibrated TSC rate in GHz, for the TSC
+    pub fn tsc_ghz(&self) -> Option<f64> {
+        self.tsc.map(|(_, ticks_per_ns)| ticks_per_ns)
+    }
+
+    /// Current reading in nanoseconds; only differences between readings are meaningful
+    pub fn now_ns(&self) -> u64 {
+        match (self.source, self.tsc) {
+            (ClockSource::MonotonicRaw, _) => monotonic_raw_ns().unwrap_or_default(),
+            (ClockSource::Tsc, Some((origin_ticks, ticks_per_ns))) => {
+                let ticks = read_tsc().unwrap_or(origin_ticks);
+                (ticks.saturating_sub(origin_ticks) as f64 / ticks_per_ns) as u64
+            }
+            _ => self.origin.elapsed().as_nanos() as u64,
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tsc_ghz() {
+            Some(ghz) => write!(f, "{} ({:.3} GHz)", self.source, ghz),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn monotonic_raw_ns() -> Option<u64> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: clock_gettime writes the time into the timespec it is handed
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC_RAW, &mut ts) };
+    (rc == 0).then(|| ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn monotonic_raw_ns() -> Option<u64> {
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+fn has_invariant_tsc() -> bool {
+    use std::arch::x86_64::__cpuid;
+    // Leaf 0x80000007 (advanced power management), EDX bit 8: the TSC ticks at a
+    // constant rate in every P-, C- and T-state
+    #[allow(unused_unsafe)]
+    // SAFETY: cpuid is available on every x86_64 CPU
+    let (max_leaf, edx) = unsafe {
+        let max_leaf = __cpuid(0x8000_0000).eax;
+        (max_leaf, __cpuid(0x8000_0007).edx)
+    };
+    max_leaf >= 0x8000_0007 && edx & (1 << 8) != 0
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn has_invariant_tsc() -> bool {
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_tsc() -> Option<u64> {
+    #[allow(unused_unsafe)]
+    // SAFETY: rdtsc is available on every x86_64 CPU
+    Some(unsafe { std::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn read_tsc() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_sources() -> Result<()> {
+        for source in [
+            ClockSource::Monotonic,
+            ClockSource::MonotonicRaw,
+            ClockSource::Tsc,
+        ] {
+            if !source.is_available() {
+                assert!(Clock::new(source).is_err());
+                continue;
+            }
+            let clock = Clock::new(source)?;
+            let start = clock.now_ns();
+            std::thread::sleep(Duration::from_millis(5));
+            let elapsed_ns = clock.now_ns() - start;
+            assert!(
+                (4_000_000..500_000_000).contains(&elapsed_ns),
+                "{}: {}",
+                clock,
+                elapsed_ns
+            );
+        }
+        assert!(ClockSource::Monotonic.is_available());
+        assert_eq!(
+            ClockSource::from_str("monotonic_raw", false),
+            Ok(ClockSource::MonotonicRaw)
+        );
+        assert_eq!(Clock::default().to_string(), "monotonic");
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+//! Comparison of two saved runs
+//!
+//! `client compare BASELINE CURRENT` reloads two run records (from `--checkpoint`, a
+//! matrix or a report bundle) and lines their figures up percentile by percentile, so a
+//! change between them (a kernel upgrade, a new NIC driver, a config change) shows as
+//! "P99 went from 80 µs to 140 µs". A figure that grew by more than the tolerance is a
+//! regression; latency figures are compared relative to the baseline, loss in
+//! percentage points. Each percentile and the distribution as a whole also get a
+//! significance test (see [`crate::client::analysis`]), telling a real regression from
+//! run-to-run noise.
+//!
+//! Percentiles miss changes of shape, such as a new second mode or a fatter tail
+//! between two of them, so the latency distributions are lined up too: the share of
+//! each run's probes in every range of the report's distribution (see `--buckets`), and
+//! how many percentage points it moved.
+
+use crate::client::analysis::{self, ShiftTest, TestResult};
+use crate::client::buckets::{BucketEdges, Buckets};
+use crate::client::error::Result;
+use crate::client::record::RunRecord;
+use crate::client::statistics::Statistics;
+
+/// Percentage points of loss above the baseline's from which loss has regressed
+pub const LOSS_REGRESSION_PCT: f64 = 0.1;
+
+/// Percentage points a range's share of the probes must move by to stand out
+pub const BUCKET_SHIFT_PCT: f64 = 1.0;
+
+/// One latency figure of both runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct FigureChange {
+    /// Name of the figure, e.g. "P99"
+    pub label: String,
+    /// Value in the baseline, in nanoseconds
+    pub baseline_ns: f64,
+    /// Value in the current run, in nanoseconds
+    pub current_ns: f64,
+    /// Test of the change, for a percentile; `None` for the mean and max, or if the
+    /// runs cannot tell it
+    pub significance: Option<TestResult>,
+}
+
+impl FigureChange {
+    /// Change from the baseline, in percent of the baseline (positive is slower)
+    pub fn change_pct(&self) -> f64 {
+        if self.baseline_ns == 0.0 {
+            return 0.0;
+        }
+        (self.current_ns - self.baseline_ns) / self.baseline_ns * 100.0
+    }
+
+    /// Whether the figure grew by more than `tolerance_pct`
+    pub fn regressed(&self, tolerance_pct: f64) -> bool {
+        self.change_pct() > tolerance_pct
+    }
+
+    /// Whether the figure shrank by more than `tolerance_pct`
+    pub fn improved(&self, tolerance_pct: f64) -> bool {
+        self.change_pct() < -tolerance_pct
+    }
+}
+
+/// Share of the probes of both runs in one range of the latency distribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketChange {
+    /// Range, e.g. "40-60 µs", or the outliers' label
+    pub label: String,
+    /// Share of the baseline's probes in the range, in percent
+    pub baseline_pct: f64,
+    /// Share of the current run's probes in the range, in percent
+    pub current_pct: f64,
+}
+
+impl BucketChange {
+    /// Percentage points the range's share moved by from the baseline
+    pub fn delta_pct(&self) -> f64 {
+        self.current_pct - self.baseline_pct
+    }
+
+    /// Whether the share moved by [`BUCKET_SHIFT_PCT`] or more
+    pub fn shifted(&self) -> bool {
+        self.delta_pct().abs() >= BUCKET_SHIFT_PCT
+    }
+}
+
+/// Figures of a current run next to those of its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// Mean, the requested percentiles and max, in that order
+    pub figures: Vec<FigureChange>,
+    /// Packet loss of the baseline, in percent of packets sent
+    pub baseline_loss_pct: f64,
+    /// Packet loss of the current run, in percent of packets sent
+    pub current_loss_pct: f64,
+    /// Test of a shift of the whole latency distribution; `None` if the runs cannot tell
+    pub shift: Option<ShiftTest>,
+    /// Both distributions range by range, the outliers last; empty slow ranges are left
+    /// out, as in the report
+    pub distribution: Vec<BucketChange>,
+}
+
+impl Comparison {
+    /// Compare the latency of `current` with `baseline` at `percentiles` (e.g. 99.9), and
+    /// their distributions over the ranges of `edges` (the default ranges for `None`)
+    pub fn new(
+        baseline: &RunRecord,
+        current: &RunRecord,
+        percentiles: &[f64],
+        edges: Option<&BucketEdges>,
+    ) -> Result<Self> {
+        let (base, cur) = (baseline.statistics()?, current.statistics()?);
+        let change = |label: String, of: &dyn Fn(&Statistics) -> f64| FigureChange {
+            label,
+            baseline_ns: of(&base),
+            current_ns: of(&cur),
+            significance: None,
+        };
+
+        let mut figures = vec![change("Mean".into(), &|s| s.mean())];
+        for &p in percentiles {
+            let mut figure = change(format!("P{}", p), &|s| s.percentile(p / 100.0) as f64);
+            // A percentile moved if more of the current run exceeds its baseline value
+            figure.significance = analysis::exceedance(
+                &baseline.histogram,
+                &current.histogram,
+                figure.baseline_ns as u64,
+            );
+            figures.push(figure);
+        }
+        // Records keep the unclamped max next to the histogram
+        figures.push(FigureChange {
+            label: "Max".into(),
+            baseline_ns: baseline.max_ns as f64,
+            current_ns: current.max_ns as f64,
+            significance: None,
+        });
+        Ok(Self {
+            figures,
+            baseline_loss_pct: loss_pct(baseline),
+            current_loss_pct: loss_pct(current),
+            shift: analysis::mann_whitney(&baseline.histogram, &current.histogram),
+            distribution: distribution(baseline, current, edges),
+        })
+    }
+
+    /// Whether loss grew by more than [`LOSS_REGRESSION_PCT`]
+    pub fn loss_regressed(&self) -> bool {
+        self.current_loss_pct - self.baseline_loss_pct > LOSS_REGRESSION_PCT
+    }
+
+    /// Labels of the figures that regressed beyond `tolerance_pct`, loss included
+    pub fn regressions(&self, tolerance_pct: f64) -> Vec<&str> {
+        let mut regressions: Vec<&str> = self
+            .figures
+            .iter()
+            .filter(|figure| figure.regressed(tolerance_pct))
+            .map(|figure| figure.label.as_str())
+            .collect();
+        if self.loss_regressed() {
+            regressions.push("Loss");
+        }
+        regressions
+    }
+}
+
+/// Shares of the probes of both runs in every range of `edges`, spanning both runs
+fn distribution(
+    baseline: &RunRecord,
+    current: &RunRecord,
+    edges: Option<&BucketEdges>,
+) -> Vec<BucketChange> {
+    let buckets = edges.map_or_else(Buckets::default, |edges| {
+        edges.buckets(
+            baseline.min_ns.min(current.min_ns),
+            baseline.max_ns.max(current.max_ns),
+        )
+    });
+    let (base, cur) = (shares(baseline, &buckets), shares(current, &buckets));
+    let labels = buckets
+        .buckets
+        .iter()
+        .map(|bucket| bucket.label.clone())
+        .chain([buckets.outlier_label.clone()]);
+    labels
+        .zip(base.into_iter().zip(cur))
+        .enumerate()
+        .filter(|&(i, (_, (base, cur)))| {
+            let hidden = i >= buckets.hide_empty_from || i == buckets.len();
+            !hidden || base > 0.0 || cur > 0.0
+        })
+        .map(|(_, (label, (baseline_pct, current_pct)))| BucketChange {
+            label,
+            baseline_pct,
+            current_pct,
+        })
+        .collect()
+}
+
+/// Share of the recorded probes of `record` in every range of `buckets` and beyond the
+/// last, in percent
+fn shares(record: &RunRecord, buckets: &Buckets) -> Vec<f64> {
+    let mut counts = vec![0u64; buckets.len() + 1];
+    for &(latency_ns, count) in &record.histogram {
+        counts[buckets.index(latency_ns).unwrap_or(buckets.len())] += count;
+    }
+    let total = counts.iter().sum::<u64>().max(1) as f64;
+    counts
+        .into_iter()
+        .map(|count| count as f64 / total * 100.0)
+        .collect()
+}
+
+/// Packet loss of `record`, in percent of the packets it sent
+fn loss_pct(record: &RunRecord) -> f64 {
+    if record.sent_packets == 0 {
+        return 0.0;
+    }
+    record.lost_packets as f64 / record.sent_packets as f64 * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(latencies: &[u64], lost_packets: usize) -> Result<RunRecord> {
+        let stats = Statistics::new(latencies)?;
+        let sent = latencies.len() + lost_packets;
+        Ok(RunRecord::new(
+            "127.0.0.1:8080",
+            &stats,
+            sent,
+            sent,
+            lost_packets,
+            Duration::from_secs(1),
+            true,
+        ))
+    }
+
+    #[test]
+    fn test_comparison() -> Result<()> {
+        // Same median, slower tail after the "upgrade"
+        let mut before = vec![50_000; 990];
+        before.extend([80_000; 10]);
+        let mut after = vec![50_000; 990];
+        after.extend([140_000; 10]);
+        let comparison = Comparison::new(
+            &record(&before, 0)?,
+            &record(&after, 5)?,
+            &[50.0, 99.9],
+            None,
+        )?;
+
+        let labels: Vec<&str> = comparison
+            .figures
+            .iter()
+            .map(|f| f.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["Mean", "P50", "P99.9", "Max"]);
+        let p50 = &comparison.figures[1];
+        assert!(!p50.regressed(10.0) && !p50.improved(10.0));
+        let p999 = &comparison.figures[2];
+        assert!(
+            (p999.change_pct() - 75.0).abs() < 1.0,
+            "{}",
+            p999.change_pct()
+        );
+        assert!(p999.regressed(10.0));
+        assert!(!p999.regressed(100.0));
+        assert!(p999.significance.unwrap().is_significantly_slower());
+        assert!(comparison.figures[0].significance.is_none());
+        assert!(!p50.significance.unwrap().is_significant());
+        // Ten slow probes of a thousand: a shift of the tail, not of the distribution
+        assert!(!comparison.shift.unwrap().test.is_significant());
+
+        assert!(comparison.loss_regressed());
+        assert_eq!(comparison.regressions(10.0), vec!["P99.9", "Max", "Loss"]);
+
+        // The other way round, the tail improved
+        let reverse = Comparison::new(&record(&after, 0)?, &record(&before, 0)?, &[99.9], None)?;
+        assert!(reverse.figures[1].improved(10.0));
+        assert!(reverse.regressions(10.0).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_distribution_changes() -> Result<()> {
+        // A second mode appears around 150 µs, taking a fifth of the probes
+        let before = vec![30_000; 1000];
+        let mut after = vec![30_000; 800];
+        after.extend([150_000; 200]);
+        let comparison = Comparison::new(&record(&before, 0)?, &record(&after, 0)?, &[50.0], None)?;
+
+        // The LAN ranges up to 200 µs are always shown, slower ones only when used
+        let labels: Vec<&str> = comparison
+            .distribution
+            .iter()
+            .map(|b| b.label.as_str())
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "0-20 µs",
+                "20-40 µs",
+                "40-60 µs",
+                "60-80 µs",
+                "80-100 µs",
+                "100-200 µs"
+            ]
+        );
+        let mode = &comparison.distribution[1];
+        assert_eq!((mode.baseline_pct, mode.current_pct), (100.0, 80.0));
+        assert!((mode.delta_pct() + 20.0).abs() < 1e-9 && mode.shifted());
+        let new_mode = &comparison.distribution[5];
+        assert!((new_mode.delta_pct() - 20.0).abs() < 1e-9);
+        assert!(!comparison.distribution[0].shifted());
+        // The median did not move, the distribution did
+        assert!(!comparison.figures[1].regressed(10.0));
+
+        // Custom edges, with a tail beyond the last one
+        let edges: BucketEdges = "100us".parse().unwrap();
+        let comparison = Comparison::new(
+            &record(&before, 0)?,
+            &record(&after, 0)?,
+            &[50.0],
+            Some(&edges),
+        )?;
+        let shares: Vec<(&str, f64, f64)> = comparison
+            .distribution
+            .iter()
+            .map(|b| (b.label.as_str(), b.baseline_pct, b.current_pct))
+            .collect();
+        assert_eq!(shares, [("0-100 µs", 100.0, 80.0), (">100 µs", 0.0, 20.0)]);
+        Ok(())
+    }
+}
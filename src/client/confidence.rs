@@ -0,0 +1,215 @@
+//! Stopping a run once its result is precise enough
+//!
+//! A fixed `--packets` count is a guess at how many probes a result needs: too few on a
+//! noisy path, far too many on a quiet one. `--until-ci 1%` keeps measuring until the
+//! 95% confidence interval of the `--ci-metric` (the mean, or the P99) is within ±1% of
+//! its estimate, with `--packets` as the most probes sent. The interval is checked
+//! every [`CI_CHECK_INTERVAL`] answered probes, from [`MIN_CI_SAMPLES`] on:
+//!
+//! - of the mean, from the standard error: mean ± 1.96 σ / √n
+//! - of the P99, distribution-free, from the order statistics whose ranks lie 1.96
+//!   standard deviations of the binomial count of probes below the P99 either side of
+//!   it; it needs enough probes above the P99 to have an upper bound at all
+//!
+//! Both assume independent probes, which consecutive probes over a congested path are
+//! not quite; the interval is then somewhat narrower than it should be.
+
+use crate::client::error::Result;
+use crate::client::statistics::Statistics;
+use clap::ValueEnum;
+use std::fmt;
+
+/// Standard score of the two-sided 95% confidence level
+pub const CI_Z: f64 = 1.96;
+
+/// Answered probes before the interval is first checked
+pub const MIN_CI_SAMPLES: u64 = 1_000;
+
+/// Answered probes between two checks of the interval
+pub const CI_CHECK_INTERVAL: u64 = 100;
+
+/// Figure whose confidence interval `--until-ci` waits on
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CiMetric {
+    /// Mean RTT
+    #[default]
+    Mean,
+    /// 99th percentile RTT
+    P99,
+}
+
+impl fmt::Display for CiMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CiMetric::Mean => write!(f, "mean"),
+            CiMetric::P99 => write!(f, "P99"),
+        }
+    }
+}
+
+/// 95% confidence interval of a figure of a run, in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub estimate_ns: f64,
+    pub low_ns: f64,
+    pub high_ns: f64,
+}
+
+impl ConfidenceInterval {
+    /// Interval of `metric` over the latencies in `stats`; `None` without enough of them
+    /// to bound it
+    pub fn of(stats: &Statistics, metric: CiMetric) -> Option<Self> {
+        let n = stats.count();
+        if n < 2 {
+            return None;
+        }
+        match metric {
+            CiMetric::Mean => {
+                let half_width = CI_Z * stats.stddev() / (n as f64).sqrt();
+                Some(Self {
+                    estimate_ns: stats.mean(),
+                    low_ns: stats.mean() - half_width,
+                    high_ns: stats.mean() + half_width,
+                })
+            }
+            CiMetric::P99 => {
+                let q = 0.99;
+                let spread = CI_Z * (n as f64 * q * (1.0 - q)).sqrt();
+                let low_rank = (n as f64 * q - spread).floor().max(1.0);
+                let high_rank = (n as f64 * q + spread).ceil();
+                if high_rank > n as f64 {
+                    return None;
+                }
+                Some(Self {
+                    estimate_ns: stats.percentile(q) as f64,
+                    low_ns: stats.percentile(low_rank / n as f64) as f64,
+                    high_ns: stats.percentile(high_rank / n as f64) as f64,
+                })
+            }
+        }
+    }
+
+    /// Widest distance from the estimate to either bound, as a percentage of the
+    /// estimate
+    pub fn half_width_pct(&self) -> f64 {
+        let half_width = (self.estimate_ns - self.low_ns).max(self.high_ns - self.estimate_ns);
+        half_width / self.estimate_ns.max(1.0) * 100.0
+    }
+}
+
+/// Precision `--until-ci` waits for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CiTarget {
+    pub metric: CiMetric,
+    /// Half-width of the interval, in percent of the estimate
+    pub half_width_pct: f64,
+}
+
+/// Tells the measurement loop when the interval of a [`CiTarget`] is narrow enough
+pub struct CiStop {
+    target: CiTarget,
+    stats: Statistics,
+}
+
+impl CiStop {
+    pub fn new(target: CiTarget) -> Result<Self> {
+        Ok(Self {
+            target,
+            stats: Statistics::empty()?,
+        })
+    }
+
+    /// Record the RTT of a probe, `None` if it was lost, and return whether the run can
+    /// stop
+    pub fn record(&mut self, rtt_ns: Option<u64>) -> Result<bool> {
+        let Some(rtt_ns) = rtt_ns else {
+            return Ok(false);
+        };
+        self.stats.record(rtt_ns)?;
+        let n = self.stats.count();
+        if n < MIN_CI_SAMPLES || !n.is_multiple_of(CI_CHECK_INTERVAL) {
+            return Ok(false);
+        }
+        Ok(ConfidenceInterval::of(&self.stats, self.target.metric)
+            .is_some_and(|ci| ci.half_width_pct() <= self.target.half_width_pct))
+    }
+}
+
+/// Parse a half-width such as `1%` or `0.5`, in percent
+pub fn parse_half_width(s: &str) -> std::result::Result<f64, String> {
+    let pct: f64 = s.trim().trim_end_matches('%').parse().map_err(|_| {
+        format!(
+            "invalid confidence interval width '{}' (expected e.g. 1%)",
+            s
+        )
+    })?;
+    if !(pct > 0.0 && pct < 100.0) {
+        return Err(format!(
+            "confidence interval width {}% is out of range (expected above 0, below 100)",
+            pct
+        ));
+    }
+    Ok(pct)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence_interval() -> Result<()> {
+        // 10,000 latencies spread evenly over 10-110 µs
+        let latencies: Vec<u64> = (0..10_000)
+            .map(|i| 10_000 + (i * 7_919) % 100_000)
+            .collect();
+        let stats = Statistics::new(&latencies)?;
+
+        let mean = ConfidenceInterval::of(&stats, CiMetric::Mean).unwrap();
+        assert!((mean.estimate_ns - 60_000.0).abs() < 200.0, "{:?}", mean);
+        // σ ≈ 28.9 µs, so ± 1.96 · 28.9 / 100 µs
+        assert!((mean.half_width_pct() - 0.94).abs() < 0.05, "{:?}", mean);
+
+        let p99 = ConfidenceInterval::of(&stats, CiMetric::P99).unwrap();
+        assert!(p99.low_ns < p99.estimate_ns && p99.estimate_ns < p99.high_ns);
+        assert!(p99.half_width_pct() < 1.0, "{:?}", p99);
+
+        // Too few probes above the P99 to bound it
+        let stats = Statistics::new(&latencies[..100])?;
+        assert_eq!(ConfidenceInterval::of(&stats, CiMetric::P99), None);
+        assert_eq!(
+            ConfidenceInterval::of(&Statistics::empty()?, CiMetric::Mean),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ci_stop() -> Result<()> {
+        let target = CiTarget {
+            metric: CiMetric::Mean,
+            half_width_pct: 1.0,
+        };
+        // A steady latency is precise enough at the first check
+        let mut stop = CiStop::new(target)?;
+        let stopped_at = (1..=5_000).find(|_| stop.record(Some(50_000)).unwrap());
+        assert_eq!(stopped_at, Some(MIN_CI_SAMPLES as usize));
+
+        // Lost probes do not count; a noisy latency needs more probes
+        let mut stop = CiStop::new(target)?;
+        assert!(!stop.record(None)?);
+        let stopped_at = (0..100_000u64)
+            .find(|i| stop.record(Some(10_000 + (i * 7_919) % 100_000)).unwrap())
+            .unwrap();
+        assert!((8_000..10_000).contains(&stopped_at), "{}", stopped_at);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_half_width() {
+        assert_eq!(parse_half_width("1%"), Ok(1.0));
+        assert_eq!(parse_half_width("0.5"), Ok(0.5));
+        assert!(parse_half_width("0%").is_err());
+        assert!(parse_half_width("100%").is_err());
+        assert!(parse_half_width("tight").is_err());
+    }
+}
@@ -1,23 +1,170 @@
+use crate::client::baseline::{DEFAULT_BASELINE_HOURS, DEFAULT_MAX_P99_CHANGE_PCT};
+use crate::client::buckets::BucketEdges;
+use crate::client::build_info::Tag;
+use crate::client::clock::ClockSource;
+use crate::client::confidence::{self, CiMetric, CiTarget};
+use crate::client::constants::{DEFAULT_PERCENTILES, HISTOGRAM_HIGH_BOUND_NS};
+#[cfg(feature = "dashboard")]
+use crate::client::dashboard::UiMode;
+use crate::client::drilldown::DEFAULT_SLOWEST;
+use crate::client::dscp::Dscp;
 use crate::client::error::{ClientError, Result};
-use clap::Parser;
+use crate::client::exporter::{HttpUrl, Pushgateway};
+use crate::client::impair::NetemProfile;
+use crate::client::matrix::MatrixParameter;
+#[cfg(feature = "otlp")]
+use crate::client::otlp::OtlpExporter;
+use crate::client::output::OutputFormat;
+use crate::client::pacing::{self, Bandwidth, BandwidthBudget, TrafficModel};
+use crate::client::pipeline::MAX_IN_FLIGHT_BYTES;
+use crate::client::port_sweep::{self, PortRange};
+use crate::client::progress_stream::ProgressTarget;
+use crate::client::redact::Redactor;
+use crate::client::retry::RetryPolicy;
+use crate::client::sla::Sla;
+use crate::client::slo::{self, Slo};
+use crate::client::template::PayloadTemplate;
+use crate::client::theme::Theme;
+use crate::client::traffic::TrafficEstimate;
+use crate::client::transaction::Transaction;
+use crate::client::units::{self, NumberFormat, UnitSystem};
+use crate::client::verdict::{FailMetric, Verdict};
+use crate::client::warmup::{Warmup, DEFAULT_WARMUP_TOLERANCE_PCT};
+use crate::config_file::expand_args;
+use crate::protocol::{Transport, MAX_PACKET_SIZE, PACKET_SIZE};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 use std::time::Duration;
 use tracing::debug;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "synapse-client")]
 #[command(about = "Bare-metal application latency diagnostic tool")]
+#[command(args_override_self = true)]
 pub struct Config {
+    /// Read flags from this TOML file (keys are flag names); flags given on the command
+    /// line take precedence
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// Server address to connect to
     #[arg(long, default_value = "127.0.0.1:8080")]
     pub server: String,
 
+    /// Transport to probe over; the server must listen on the same one
+    #[arg(long, value_enum, default_value_t = Transport::Tcp)]
+    pub transport: Transport,
+
+    /// Size of every probe in bytes: the 64-byte header plus zero padding
+    #[arg(long, value_name = "BYTES", default_value_t = PACKET_SIZE)]
+    pub payload_size: usize,
+
+    /// Shape the padding of every probe like an application message: http:BYTES (an
+    /// HTTP/1.1 POST with a JSON body) or protobuf:BYTES (a protobuf message), where BYTES
+    /// is the probe size, header included
+    #[arg(long, value_name = "KIND:BYTES", conflicts_with = "payload_size")]
+    pub payload_template: Option<PayloadTemplate>,
+
     /// Number of packets to send during the test
     #[arg(long, default_value_t = 10000)]
     pub packets: usize,
 
-    /// Number of warmup packets before the test
-    #[arg(long, default_value_t = 100000)]
-    pub warmup: usize,
+    /// Stop once the 95% confidence interval of the --ci-metric is within this share of
+    /// its estimate (e.g. 1%), checked every 100 answered probes; --packets is the cap
+    #[arg(long, value_name = "PCT", value_parser = confidence::parse_half_width)]
+    pub until_ci: Option<f64>,
+
+    /// Figure whose confidence interval --until-ci waits on: mean or p99
+    #[arg(long, value_enum, default_value_t = CiMetric::Mean, requires = "until_ci")]
+    pub ci_metric: CiMetric,
+
+    /// Measure this many connections at once, each sending --packets probes from a
+    /// thread of its own; the report merges them and breaks them down per connection
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub connections: usize,
+
+    /// Measure every destination port of this range (e.g. 8080-8090) on the --server host
+    /// in turn and compare them, to expose policies applied per port
+    #[arg(long, value_name = "FIRST-LAST")]
+    pub port_sweep: Option<PortRange>,
+
+    /// Keep up to this many probes in flight, sending the next as soon as a slot frees up
+    /// and matching replies by sequence number, to measure latency under sustained load
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub window: usize,
+
+    /// Make every probe a transaction of REQUESTS packets answered by RESPONSES packets
+    /// (e.g. 3:2, at most 16 each) and measure the completion time of each, to model
+    /// protocols that take several round trips per operation
+    #[arg(long, value_name = "REQUESTS:RESPONSES")]
+    pub transaction: Option<Transaction>,
+
+    /// Send the probes from this many UDP source ports in turn and break latency down per
+    /// port, so paths that ECMP hashes flows onto show up as per-flow differences
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub flows: usize,
+
+    /// Also probe this reflector, in turn with --server and the other reflectors, failing
+    /// over from any that stops answering; repeat for each reflector
+    #[arg(long = "reflector", value_name = "HOST:PORT")]
+    pub reflectors: Vec<String>,
+
+    /// Weight of --server and of each --reflector, in that order, in the weighted score of
+    /// the pool (e.g. their share of the traffic); all equal by default
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    pub weights: Vec<f64>,
+
+    /// Measure the first IPv4 and the first IPv6 address of the --server host in turn and
+    /// compare them, since dual-stack paths often differ
+    #[arg(long)]
+    pub compare_families: bool,
+
+    /// Measure every address the --server host resolves to in turn and compare them,
+    /// rather than whichever address the connection picks
+    #[arg(long)]
+    pub each_address: bool,
+
+    /// Pace measurement probes at this many per second instead of sending each as soon
+    /// as the previous one completes
+    #[arg(long, value_name = "PPS")]
+    pub rate: Option<u64>,
+
+    /// Spacing of paced probes: evenly (fixed) or with exponential gaps around the
+    /// rate (poisson)
+    #[arg(long, value_enum, default_value_t = TrafficModel::Fixed, requires = "rate")]
+    pub traffic: TrafficModel,
+
+    /// Idle time in milliseconds before a paced probe from which it counts as cold in
+    /// the warm/cold split (default: the mean gap)
+    #[arg(long, value_name = "MS", requires = "rate")]
+    pub cold_gap_ms: Option<u64>,
+
+    /// Cap the probe traffic at this bit rate (e.g. 1Mbps or 500kbps), headers included,
+    /// across every connection, flow and probe size of the run
+    #[arg(long, value_name = "RATE")]
+    pub max_bandwidth: Option<Bandwidth>,
+
+    /// Refuse to start a run whose probes and echoes would put more than this many bytes
+    /// on the wire (e.g. 500MB or 2GiB)
+    #[arg(long, value_name = "BYTES", value_parser = units::parse_bytes)]
+    pub max_bytes: Option<u64>,
+
+    /// Number of warmup packets before the test, or `auto` to warm up until the latency
+    /// settles
+    #[arg(long, value_name = "N|auto", default_value_t = Warmup::Count(100000))]
+    pub warmup: Warmup,
+
+    /// Change of the mean and P99 between two windows of --warmup auto, in percent, below
+    /// which the latency counts as settled [default: 10]
+    #[arg(long, value_name = "PCT")]
+    pub warmup_tolerance: Option<f64>,
+
+    /// Exchanges after warmup to estimate the server clock offset from, which corrects
+    /// the one-way times of the path breakdown (0 disables)
+    #[arg(long, value_name = "EXCHANGES", default_value_t = 32)]
+    pub clock_sync: usize,
 
     /// Dashboard update interval (packets)
     #[arg(long, default_value_t = 100)]
@@ -31,21 +178,634 @@ pub struct Config {
     #[arg(long)]
     pub quiet: bool,
 
+    /// Live view of the measurement: a progress bar, or a full-screen dashboard
+    #[cfg(feature = "dashboard")]
+    #[arg(long, value_enum, default_value_t = UiMode::Live)]
+    pub ui: UiMode,
+
+    /// Without the live view (--quiet, or stdout not a terminal), log packets done, recent P99
+    /// and loss every this many seconds (0 = never)
+    #[arg(long, default_value_t = 10)]
+    pub heartbeat_secs: u64,
+
+    /// Write progress as one JSON line every --update packets to TARGET: - (stdout, with
+    /// --quiet), tcp:HOST:PORT or unix:PATH
+    #[arg(long, value_name = "TARGET")]
+    pub progress_json: Option<ProgressTarget>,
+
     /// Log level (trace, debug, info, warn, error)
-    #[arg(long, default_value = "info")]
+    #[arg(long, default_value = "info", global = true)]
     pub log_level: String,
 
     /// Log format (text or json)
-    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    #[arg(long, default_value = "text", value_parser = ["text", "json"], global = true)]
     pub log_format: String,
+
+    /// Periodically write the running results to this checkpoint file
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Checkpoint interval in seconds
+    #[arg(long, default_value_t = 30)]
+    pub checkpoint_interval: u64,
+
+    /// Also store every packet's send time and RTT in the checkpoint, for `analyze --pcap`
+    #[arg(long, requires = "checkpoint")]
+    pub record_packets: bool,
+
+    /// Abort a phase if a send or receive does not return (with a reply or a timeout)
+    /// within this many seconds; waits between probes, as of --rate, do not count
+    #[arg(long)]
+    pub watchdog_secs: Option<u64>,
+
+    /// Watch load average, context switches and CPU migrations of the measuring thread (Linux)
+    #[arg(long)]
+    pub watch_system: bool,
+
+    /// Measure the energy the CPU packages and their DRAM use over the run with the RAPL
+    /// counters (Linux, usually as root)
+    #[arg(long)]
+    pub energy: bool,
+
+    /// Carbon intensity of the host's electricity in grams of CO2 equivalent per kWh, to
+    /// turn the --energy figure into emissions
+    #[arg(long, value_name = "G_PER_KWH", requires = "energy")]
+    pub carbon_intensity: Option<f64>,
+
+    /// Pin the measuring thread to this NUMA node and prefer its memory (Linux)
+    #[arg(long)]
+    pub numa_node: Option<usize>,
+
+    /// Fail the run at the first lost packet, with diagnostics
+    #[arg(long)]
+    pub strict_loss: bool,
+
+    /// Back the per-packet sample buffers with huge pages to reduce TLB misses (Linux)
+    #[arg(long)]
+    pub huge_pages: bool,
+
+    /// Clock the round trips are timed with: monotonic, monotonic_raw (no NTP slewing,
+    /// Linux) or tsc (the CPU's time stamp counter, x86_64 with an invariant TSC)
+    #[arg(long, value_enum, default_value_t = ClockSource::Monotonic)]
+    pub clock: ClockSource,
+
+    /// Also time every probe with kernel send/receive timestamps (SO_TIMESTAMPING) and
+    /// report application vs kernel time (Linux)
+    #[arg(long)]
+    pub kernel_timestamps: bool,
+
+    /// Mark every probe with this DSCP (0-63 or a name such as EF or AF41) and, over UDP,
+    /// report whether replies kept it (Linux)
+    #[arg(long)]
+    pub dscp: Option<Dscp>,
+
+    /// Capture the probe flow during the measurement into this pcap file (Linux, CAP_NET_RAW)
+    #[arg(long, value_name = "PATH")]
+    pub pcap: Option<PathBuf>,
+
+    /// Seed for every randomized choice of the run (random when unset; printed in the report)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Latency objective to grade the run against, as TARGET%<THRESHOLD (e.g.
+    /// 99.95%<500us); repeat for several
+    #[arg(long, value_name = "SPEC")]
+    pub slo: Vec<Slo>,
+
+    /// Conditions the run must all meet, comma-separated (e.g.
+    /// "p99<500us,p50<100us,loss<0.01%")
+    #[arg(long, value_name = "POLICY")]
+    pub sla: Option<Sla>,
+
+    /// Replay the probes through a client that retries with backoff and report the
+    /// completion time of its requests, as KEY=VALUE pairs of timeout, attempts,
+    /// backoff, multiplier, max-backoff and jitter (none, full or equal), e.g.
+    /// "timeout=200ms,attempts=4,backoff=50ms,jitter=full"
+    #[arg(long, value_name = "POLICY")]
+    pub retry_policy: Option<RetryPolicy>,
+
+    /// Directory of the run records of earlier runs (e.g. --checkpoint files of a cron
+    /// job); the run fails when its P99 deviates from theirs by more than --max-p99-change
+    #[arg(long, value_name = "DIR")]
+    pub baseline_dir: Option<PathBuf>,
+
+    /// Hours of earlier runs in --baseline-dir the baseline covers
+    #[arg(long, value_name = "HOURS", default_value_t = DEFAULT_BASELINE_HOURS, requires = "baseline_dir")]
+    pub baseline_hours: u64,
+
+    /// Deviation of the P99 from the --baseline-dir baseline, in percent either way, from
+    /// which the run fails
+    #[arg(long, value_name = "PCT", default_value_t = DEFAULT_MAX_P99_CHANGE_PCT, requires = "baseline_dir")]
+    pub max_p99_change: f64,
+
+    /// Fail the run when --fail-metric reaches this latency, with a unit of ns, us, ms or
+    /// s (e.g. 500us)
+    #[arg(long, value_name = "LATENCY", default_value = "1ms", value_parser = slo::parse_threshold)]
+    pub fail_above: Duration,
+
+    /// RTT figure the pass/fail verdict grades against --fail-above
+    #[arg(long, value_enum, default_value_t = FailMetric::Mean)]
+    pub fail_metric: FailMetric,
+
+    /// Exit with status 4 when more than this percentage of probes is lost (a run with
+    /// no reply always does)
+    #[arg(long, value_name = "PCT")]
+    pub max_loss: Option<f64>,
+
+    /// Stream every measured packet (sequence, send timestamp, latency, status) to this
+    /// CSV file
+    #[arg(long, value_name = "PATH")]
+    pub raw_output: Option<PathBuf>,
+
+    /// Write the RTT percentile distribution in microseconds to this file in HdrHistogram's
+    /// .hgrm format, for the standard HdrHistogram plotting tools
+    #[arg(long, value_name = "PATH")]
+    pub hgrm: Option<PathBuf>,
+
+    /// Render the latency CDF and the latency over time of the run into this directory, as
+    /// SVG and PNG, for wikis and dashboards
+    #[cfg(feature = "charts")]
+    #[arg(long, value_name = "DIR")]
+    pub chart_dir: Option<PathBuf>,
+
+    /// Write JSON, HTML, CSV, hlog and run-record exports of the run into this directory,
+    /// with a manifest
+    #[arg(long, value_name = "DIR")]
+    pub report_bundle: Option<PathBuf>,
+
+    /// Tag every export of the run with KEY=VALUE (e.g. build=$CI_COMMIT); repeat for
+    /// several
+    #[arg(long, value_name = "KEY=VALUE")]
+    pub tag: Vec<Tag>,
+
+    /// Push the run summary and RTT percentiles to this Prometheus Pushgateway after the
+    /// run (e.g. http://pushgateway:9091)
+    #[arg(long, value_name = "URL")]
+    pub pushgateway: Option<HttpUrl>,
+
+    /// Job label of the pushed metrics
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = "synapse",
+        requires = "pushgateway"
+    )]
+    pub push_job: String,
+
+    /// Instance label of the pushed metrics (default: the server address, redacted with
+    /// --redact)
+    #[arg(long, value_name = "NAME", requires = "pushgateway")]
+    pub push_instance: Option<String>,
+
+    /// Export each run as an OpenTelemetry trace and metrics to this OTLP/HTTP collector
+    /// (e.g. http://collector:4318)
+    #[cfg(feature = "otlp")]
+    #[arg(long, value_name = "URL")]
+    pub otlp_endpoint: Option<HttpUrl>,
+
+    /// service.name of the exported telemetry
+    #[cfg(feature = "otlp")]
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = "synapse",
+        requires = "otlp_endpoint"
+    )]
+    pub otlp_service_name: String,
+
+    /// Replace server host names and IP addresses in every export (checkpoint, run
+    /// record, report bundle, matrix files) by stable hashed tokens
+    #[arg(long)]
+    pub redact: bool,
+
+    /// Secret mixed into the --redact tokens so they cannot be reversed by hashing
+    /// candidate addresses
+    #[arg(long, value_name = "KEY", requires = "redact")]
+    pub redact_key: Option<String>,
+
+    /// Print counts and sizes in the report scaled by SI prefixes (k, M, G; powers of 1000)
+    #[arg(long, conflicts_with = "iec")]
+    pub si: bool,
+
+    /// Print counts and sizes in the report scaled by IEC prefixes (Ki, Mi, Gi; powers of
+    /// 1024)
+    #[arg(long)]
+    pub iec: bool,
+
+    /// Separator between groups of three digits in exact counts ("" for none)
+    #[arg(long, value_name = "SEP", default_value = ",")]
+    pub thousands_separator: String,
+
+    /// Color mapping of the report, live view and OSI visualization
+    #[arg(long, value_enum, default_value_t = Theme::Default)]
+    pub theme: Theme,
+
+    /// Print the report in ASCII: no check marks, box drawing or block bars
+    #[arg(long)]
+    pub plain_verdict: bool,
+
+    /// RTT percentiles listed in the report, comma-separated (e.g. 50,99,99.99,99.999)
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        default_values_t = DEFAULT_PERCENTILES
+    )]
+    pub percentiles: Vec<f64>,
+
+    /// Upper edges of the latency distribution's ranges, comma-separated with a unit of
+    /// ns, us, ms or s (e.g. 100us,1ms,20ms,100ms), or `auto` for log-scale edges over the
+    /// run's latencies [default: 20 µs steps up to 100 µs, then up to 10 ms]
+    #[arg(long, value_name = "EDGES|auto")]
+    pub buckets: Option<BucketEdges>,
+
+    /// Slowest probes listed in the report with their send time and context (0 = none)
+    #[arg(long, value_name = "K", default_value_t = DEFAULT_SLOWEST)]
+    pub slowest: usize,
+
+    /// Additional machine-readable output printed after the report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands that replace the default measurement run
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Print the results stored in a checkpoint file
+    Analyze(AnalyzeArgs),
+    /// Compare two saved runs percentile by percentile and flag regressions
+    Compare(CompareArgs),
+    /// Run the cartesian product of varied client flags and emit a combined table/CSV
+    Matrix(MatrixArgs),
+    /// Apply a series of tc/netem profiles and compare measured with injected values (Linux, root)
+    ImpairSweep(ImpairSweepArgs),
+    /// Emit a Wireshark Lua dissector for the current protocol version
+    Dissector(DissectorArgs),
+    /// Measure how latency grows with the number of probes in flight on one connection (TCP)
+    DepthSweep(DepthSweepArgs),
+}
+
+/// Arguments for the `analyze` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct AnalyzeArgs {
+    /// Checkpoint file written with --checkpoint
+    pub path: PathBuf,
+
+    /// Capture of the run (e.g. from --pcap or tcpdump) to match against its packets;
+    /// needs a checkpoint written with --record-packets
+    #[arg(long, value_name = "PCAP")]
+    pub pcap: Option<PathBuf>,
+
+    /// Write the per-packet wire and application timestamps matched with --pcap to this CSV file
+    #[arg(long, value_name = "PATH", requires = "pcap")]
+    pub packets_csv: Option<PathBuf>,
+
+    /// Slowest probes listed with their send time and context (0 = none); needs a
+    /// checkpoint written with --record-packets
+    #[arg(long, value_name = "K", default_value_t = DEFAULT_SLOWEST)]
+    pub slowest: usize,
+}
+
+/// Arguments for the `compare` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct CompareArgs {
+    /// Run record of the reference run (--checkpoint file, matrix run or run-record.json
+    /// of a report bundle)
+    pub baseline: PathBuf,
+
+    /// Run record of the run to check against the baseline
+    pub current: PathBuf,
+
+    /// Growth of a latency figure over the baseline, in percent, from which it counts as
+    /// a regression
+    #[arg(long, value_name = "PCT", default_value_t = 10.0)]
+    pub tolerance: f64,
+}
+
+impl CompareArgs {
+    /// Validates the comparison arguments
+    pub fn validate(&self) -> Result<()> {
+        if self.tolerance.is_nan() || self.tolerance < 0.0 {
+            return Err(ClientError::Config("tolerance must be >= 0".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Arguments for the `dissector` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct DissectorArgs {
+    /// Server port to decode traffic on (default: the port of --server)
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Write the dissector to this file instead of stdout
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+}
+
+/// Arguments for the `matrix` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct MatrixArgs {
+    /// Client flag to vary as NAME=V1,V2,... (e.g. packets=1k,10k); repeat for more flags
+    #[arg(long, required = true)]
+    pub vary: Vec<MatrixParameter>,
+
+    /// Directory receiving one run record per run and the combined matrix.csv
+    #[arg(long, default_value = "synapse-matrix")]
+    pub out_dir: PathBuf,
+}
+
+/// Arguments for the `impair-sweep` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct ImpairSweepArgs {
+    /// Network interface to impair (e.g. lo, eth0)
+    #[arg(long)]
+    pub interface: String,
+
+    /// Impairment profile as DELAY_MS[:LOSS_PCT]; repeat for several profiles
+    #[arg(long = "profile", default_values = ["1", "5", "10", "5:1"])]
+    pub profiles: Vec<NetemProfile>,
+
+    /// Packets measured per profile
+    #[arg(long, default_value_t = 2000)]
+    pub packets: usize,
+
+    /// Warmup packets before each profile
+    #[arg(long, default_value_t = 200)]
+    pub warmup: usize,
+
+    /// Times each probe crosses the interface (default: 2 on lo, 1 otherwise)
+    #[arg(long)]
+    pub passes: Option<u32>,
+}
+
+/// Arguments for the `depth-sweep` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct DepthSweepArgs {
+    /// Largest number of probes in flight; depths double from 1 up to it
+    #[arg(long, default_value_t = 32)]
+    pub max_depth: usize,
+
+    /// Packets measured per depth
+    #[arg(long, default_value_t = 2000)]
+    pub packets: usize,
+
+    /// Warmup packets before the first depth
+    #[arg(long, default_value_t = 200)]
+    pub warmup: usize,
+}
+
+impl DepthSweepArgs {
+    /// Validates the sweep arguments against the client's transport and probe size
+    pub fn validate(&self, transport: Transport, payload_size: usize) -> Result<()> {
+        if transport != Transport::Tcp {
+            return Err(ClientError::Config(
+                "depth-sweep requires --transport tcp, which returns replies in order".into(),
+            ));
+        }
+        if self.packets == 0 {
+            return Err(ClientError::Config("packets must be > 0".into()));
+        }
+        if self.max_depth == 0 {
+            return Err(ClientError::Config("max_depth must be > 0".into()));
+        }
+        if self.max_depth.saturating_mul(payload_size) > MAX_IN_FLIGHT_BYTES {
+            return Err(ClientError::Config(format!(
+                "--max-depth {} of {}-byte probes keeps {} bytes in flight; the most is {}",
+                self.max_depth,
+                payload_size,
+                self.max_depth.saturating_mul(payload_size),
+                MAX_IN_FLIGHT_BYTES
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ImpairSweepArgs {
+    /// Returns how many times each probe crosses the impaired interface
+    ///
+    /// On loopback the request and the reply both traverse the qdisc.
+    pub fn passes(&self) -> u32 {
+        self.passes
+            .unwrap_or(if self.interface == "lo" { 2 } else { 1 })
+    }
+
+    /// Validates the sweep arguments
+    pub fn validate(&self) -> Result<()> {
+        if self.packets == 0 {
+            return Err(ClientError::Config("packets must be > 0".into()));
+        }
+        if self.passes() == 0 {
+            return Err(ClientError::Config("passes must be > 0".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    /// Returns the configuration obtained when no CLI flags are given
+    fn default() -> Self {
+        Config::parse_from(["synapse-client"])
+    }
 }
 
 impl Config {
+    /// Parse the process arguments, with the flags of a `--config` file beneath them
+    ///
+    /// Exits with a usage error, as [`Parser::parse`] does, if they are invalid.
+    pub fn load() -> Self {
+        Self::try_load_from(std::env::args()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args` (program name first), with the flags of a `--config` file beneath them
+    pub fn try_load_from<I, T>(args: I) -> std::result::Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let args = expand_args(&Self::command(), args.into_iter().map(Into::into).collect())?;
+        Self::try_parse_from(args)
+    }
+
     /// Returns the configured timeout as a Duration
     pub fn timeout(&self) -> Duration {
         Duration::from_millis(self.timeout_ms)
     }
 
+    /// Returns the size of every probe: the size of the payload template, if one is set
+    pub fn probe_size(&self) -> usize {
+        self.payload_template
+            .map_or(self.payload_size, |template| template.size)
+    }
+
+    /// Returns a fresh budget of the probe traffic cap, if configured, for the sockets of
+    /// one run to share
+    pub fn bandwidth_budget(&self) -> Option<BandwidthBudget> {
+        self.max_bandwidth.map(BandwidthBudget::new)
+    }
+
+    /// Returns the traffic the probing run described by this configuration puts on the
+    /// wire, over every port of --port-sweep and both families of --compare-families
+    /// (--each-address: per address)
+    pub fn traffic_estimate(&self) -> TrafficEstimate {
+        let runs = match self.port_sweep {
+            Some(range) => range.count(),
+            None if self.compare_families => 2,
+            None => 1,
+        };
+        // A transaction puts up to this many packets on the wire each way
+        let measured = (self.packets as u64).saturating_mul(
+            self.transaction
+                .map_or(1, |transaction| transaction.packets() as u64),
+        );
+        let probes = (self.warmup.max_probes() as u64 + self.clock_sync as u64 + measured)
+            .saturating_mul(self.connections as u64)
+            .saturating_mul(runs as u64);
+        TrafficEstimate {
+            probes,
+            probe_bytes: (self.probe_size() + pacing::header_bytes(self.transport)) as u64,
+            paced: self.rate.map(|rate| (self.packets as u64, rate)),
+        }
+    }
+
+    /// Returns the weight of --server and of each --reflector, in that order: --weights,
+    /// or 1 each
+    pub fn reflector_weights(&self) -> Vec<f64> {
+        if self.weights.is_empty() {
+            vec![1.0; self.reflectors.len() + 1]
+        } else {
+            self.weights.clone()
+        }
+    }
+
+    /// Returns the change between windows below which --warmup auto counts the latency
+    /// as settled, in percent
+    pub fn warmup_tolerance_pct(&self) -> f64 {
+        self.warmup_tolerance
+            .unwrap_or(DEFAULT_WARMUP_TOLERANCE_PCT)
+    }
+
+    /// Returns the precision --until-ci stops the run at, if it is set
+    pub fn until_ci(&self) -> Option<CiTarget> {
+        self.until_ci.map(|half_width_pct| CiTarget {
+            metric: self.ci_metric,
+            half_width_pct,
+        })
+    }
+
+    /// Returns the trailing window of earlier runs the --baseline-dir baseline covers
+    pub fn baseline_window(&self) -> Duration {
+        Duration::from_secs(self.baseline_hours.saturating_mul(3600))
+    }
+
+    /// Returns the idle time from which a paced probe counts as cold, if configured
+    pub fn cold_gap(&self) -> Option<Duration> {
+        self.cold_gap_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the configured checkpoint interval as a Duration
+    pub fn checkpoint_interval(&self) -> Duration {
+        Duration::from_secs(self.checkpoint_interval)
+    }
+
+    /// Returns true if the run shows the live view (warmup spinner, progress bar)
+    ///
+    /// Not with --quiet, nor when stdout is not a terminal: in CI logs and pipes, every
+    /// redraw of the view would pile up as carriage returns and escape codes.
+    pub fn live_view(&self) -> bool {
+        !self.quiet && io::stdout().is_terminal()
+    }
+
+    /// Returns true if the live view is the full-screen dashboard
+    #[cfg(feature = "dashboard")]
+    pub fn dashboard(&self) -> bool {
+        self.live_view() && self.ui == UiMode::Dashboard
+    }
+
+    /// Returns the interval of progress log lines, in runs without the live view
+    pub fn heartbeat(&self) -> Option<Duration> {
+        (!self.live_view() && self.heartbeat_secs > 0)
+            .then(|| Duration::from_secs(self.heartbeat_secs))
+    }
+
+    /// Returns the watchdog stall timeout, if the watchdog is enabled
+    pub fn watchdog(&self) -> Option<Duration> {
+        self.watchdog_secs.map(Duration::from_secs)
+    }
+
+    /// Returns the redactor for exports, if --redact is set
+    pub fn redactor(&self) -> Option<Redactor> {
+        self.redact
+            .then(|| Redactor::new(self.redact_key.as_deref()))
+    }
+
+    /// Returns the server address as it may appear in exports
+    pub fn exported_server(&self) -> String {
+        match self.redactor() {
+            Some(redactor) => redactor.address(&self.server),
+            None => self.server.clone(),
+        }
+    }
+
+    /// Returns the user tags of the run; a repeated key keeps its last value
+    pub fn tags(&self) -> BTreeMap<String, String> {
+        self.tag
+            .iter()
+            .map(|tag| (tag.key.clone(), tag.value.clone()))
+            .collect()
+    }
+
+    /// Returns the Pushgateway group to push results to, if --pushgateway is set
+    pub fn pushgateway(&self) -> Option<Pushgateway> {
+        let instance = self
+            .push_instance
+            .clone()
+            .unwrap_or_else(|| self.exported_server());
+        self.pushgateway
+            .clone()
+            .map(|url| Pushgateway::new(url, &self.push_job, &instance).with_tags(self.tags()))
+    }
+
+    /// Returns the OpenTelemetry exporter, if --otlp-endpoint is set
+    #[cfg(feature = "otlp")]
+    pub fn otlp_exporter(&self) -> Option<OtlpExporter> {
+        let endpoint = self.otlp_endpoint.clone()?;
+        let exporter = OtlpExporter::new(endpoint, &self.otlp_service_name)
+            .with_attribute("server.address", self.exported_server())
+            .with_attribute("network.transport", self.transport.to_string())
+            .with_attribute("synapse.payload_size", self.probe_size())
+            .with_tags(self.tags());
+        Some(exporter)
+    }
+
+    /// Returns the criterion of the pass/fail verdict
+    pub fn verdict(&self) -> Verdict {
+        Verdict {
+            metric: self.fail_metric,
+            threshold_ns: self.fail_above.as_nanos() as u64,
+        }
+    }
+
+    /// Returns how the report prints counts and sizes
+    pub fn number_format(&self) -> NumberFormat {
+        let units = if self.si {
+            UnitSystem::Si
+        } else if self.iec {
+            UnitSystem::Iec
+        } else {
+            UnitSystem::Exact
+        };
+        NumberFormat {
+            units,
+            thousands_separator: self.thousands_separator.clone(),
+        }
+    }
+
     /// Returns true if JSON format logging is enabled
     pub fn is_json_format(&self) -> bool {
         self.log_format.to_lowercase() == "json"
@@ -60,6 +820,298 @@ impl Config {
         if self.timeout_ms == 0 {
             return Err(ClientError::Config("timeout must be > 0".into()));
         }
+        // Percentiles and the mean saturate at the histogram's upper bound; the max does not
+        if self.fail_metric != FailMetric::Max
+            && self.fail_above.as_nanos() > HISTOGRAM_HIGH_BOUND_NS as u128
+        {
+            return Err(ClientError::Config(format!(
+                "--fail-above cannot exceed {} ms unless --fail-metric is max",
+                HISTOGRAM_HIGH_BOUND_NS / 1_000_000
+            )));
+        }
+        if let Some(max_loss) = self.max_loss {
+            if !(0.0..=100.0).contains(&max_loss) {
+                return Err(ClientError::Config(
+                    "max_loss must be between 0 and 100".into(),
+                ));
+            }
+        }
+        if !self.clock.is_available() {
+            return Err(ClientError::Config(format!(
+                "--clock {} is not available on this machine",
+                self.clock
+            )));
+        }
+        if let Some(tolerance) = self.warmup_tolerance {
+            if self.warmup != Warmup::Auto {
+                return Err(ClientError::Config(
+                    "--warmup-tolerance requires --warmup auto".into(),
+                ));
+            }
+            if !(tolerance > 0.0 && tolerance.is_finite()) {
+                return Err(ClientError::Config("warmup_tolerance must be > 0".into()));
+            }
+        }
+        if self.until_ci.is_some() {
+            // Both send on without looking at the probes answered so far
+            let open = [
+                ("--connections", self.connections > 1),
+                ("--window", self.window > 1),
+            ];
+            if let Some((flag, _)) = open.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "--until-ci stops a single closed-loop run and cannot be combined with {}",
+                    flag
+                )));
+            }
+        }
+        if self.transaction.is_some() {
+            // The one waits for each transaction, the other pairs one send with one reply
+            let single = [
+                ("--window", self.window > 1),
+                ("--kernel-timestamps", self.kernel_timestamps),
+            ];
+            if let Some((flag, _)) = single.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "{} cannot be combined with --transaction",
+                    flag
+                )));
+            }
+        }
+        if let Some(intensity) = self.carbon_intensity {
+            if !(intensity > 0.0 && intensity.is_finite()) {
+                return Err(ClientError::Config("carbon_intensity must be > 0".into()));
+            }
+        }
+        if self.baseline_hours == 0 {
+            return Err(ClientError::Config("baseline_hours must be > 0".into()));
+        }
+        if self.max_p99_change.is_nan() || self.max_p99_change < 0.0 {
+            return Err(ClientError::Config("max_p99_change must be >= 0".into()));
+        }
+        if let Some(percentile) = self.percentiles.iter().find(|&&p| !(p > 0.0 && p <= 100.0)) {
+            return Err(ClientError::Config(format!(
+                "percentile {} is out of range (expected above 0, up to 100)",
+                percentile
+            )));
+        }
+        if self.connections == 0 {
+            return Err(ClientError::Config("connections must be > 0".into()));
+        }
+        if self.connections > 1 {
+            // Each of these follows the probes or the thread of a single connection
+            let single = [
+                ("--checkpoint", self.checkpoint.is_some()),
+                ("--raw-output", self.raw_output.is_some()),
+                ("--pcap", self.pcap.is_some()),
+                ("--watch-system", self.watch_system),
+                ("--progress-json", self.progress_json.is_some()),
+                #[cfg(feature = "dashboard")]
+                ("--ui dashboard", self.ui == UiMode::Dashboard),
+            ];
+            if let Some((flag, _)) = single.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "{} follows a single connection and cannot be combined with connections > 1",
+                    flag
+                )));
+            }
+        }
+        let modes = [
+            ("--port-sweep", self.port_sweep.is_some()),
+            ("--compare-families", self.compare_families),
+            ("--each-address", self.each_address),
+        ];
+        let mut set_modes = modes.iter().filter(|(_, set)| *set).map(|(mode, _)| *mode);
+        let several_runs = set_modes.next();
+        if let (Some(first), Some(second)) = (several_runs, set_modes.next()) {
+            return Err(ClientError::Config(format!(
+                "{} cannot be combined with {}",
+                second, first
+            )));
+        }
+        if let (Some(mode), true) = (several_runs, self.baseline_dir.is_some()) {
+            return Err(ClientError::Config(format!(
+                "--baseline-dir grades a single run and cannot be combined with {}",
+                mode
+            )));
+        }
+        if let Some(mode) = several_runs {
+            // Every run would overwrite the files of the one before
+            let single = [
+                ("--checkpoint", self.checkpoint.is_some()),
+                ("--raw-output", self.raw_output.is_some()),
+                ("--pcap", self.pcap.is_some()),
+                ("--hgrm", self.hgrm.is_some()),
+                ("--report-bundle", self.report_bundle.is_some()),
+                #[cfg(feature = "charts")]
+                ("--chart-dir", self.chart_dir.is_some()),
+            ];
+            if let Some((flag, _)) = single.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "{} writes the files of a single run and cannot be combined with {}",
+                    flag, mode
+                )));
+            }
+        }
+        if self.port_sweep.is_some() {
+            port_sweep::with_port(&self.server, 0)?;
+        }
+        if self.window == 0 {
+            return Err(ClientError::Config("window must be > 0".into()));
+        }
+        if self.window > 1 {
+            if self.rate.is_some() {
+                return Err(ClientError::Config(
+                    "--window sends as replies free up slots and cannot be combined with --rate"
+                        .into(),
+                ));
+            }
+            if self.kernel_timestamps {
+                return Err(ClientError::Config(
+                    "--kernel-timestamps pairs timestamps with one probe at a time and cannot be \
+                     combined with --window"
+                        .into(),
+                ));
+            }
+            // The whole window is written before a reply is read
+            let in_flight = self.window.saturating_mul(self.probe_size());
+            if self.transport == Transport::Tcp && in_flight > MAX_IN_FLIGHT_BYTES {
+                return Err(ClientError::Config(format!(
+                    "--window {} of {}-byte probes keeps {} bytes in flight over TCP; the most is {}",
+                    self.window,
+                    self.probe_size(),
+                    in_flight,
+                    MAX_IN_FLIGHT_BYTES
+                )));
+            }
+        }
+        if self.flows == 0 {
+            return Err(ClientError::Config("flows must be > 0".into()));
+        }
+        if self.flows > 1 {
+            if self.transport != Transport::Udp {
+                return Err(ClientError::Config(
+                    "--flows requires --transport udp".into(),
+                ));
+            }
+            let single = [
+                ("--connections", self.connections > 1),
+                ("--window", self.window > 1),
+                ("--pcap", self.pcap.is_some()),
+            ];
+            if let Some((flag, _)) = single.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "{} cannot be combined with --flows",
+                    flag
+                )));
+            }
+        }
+        if !self.reflectors.is_empty() {
+            if self.transport != Transport::Udp {
+                return Err(ClientError::Config(
+                    "--reflector requires --transport udp".into(),
+                ));
+            }
+            let single = [
+                ("--connections", self.connections > 1),
+                ("--window", self.window > 1),
+                ("--flows", self.flows > 1),
+                ("--pcap", self.pcap.is_some()),
+                ("--port-sweep", self.port_sweep.is_some()),
+                ("--compare-families", self.compare_families),
+                ("--each-address", self.each_address),
+            ];
+            if let Some((flag, _)) = single.iter().find(|(_, set)| *set) {
+                return Err(ClientError::Config(format!(
+                    "{} cannot be combined with --reflector",
+                    flag
+                )));
+            }
+        }
+        if !self.weights.is_empty() {
+            if self.reflectors.is_empty() {
+                return Err(ClientError::Config(
+                    "--weights weighs the reflectors of --reflector".into(),
+                ));
+            }
+            if self.weights.len() != self.reflectors.len() + 1 {
+                return Err(ClientError::Config(format!(
+                    "--weights has {} weights for {} reflectors (--server and {} --reflector)",
+                    self.weights.len(),
+                    self.reflectors.len() + 1,
+                    self.reflectors.len()
+                )));
+            }
+            if let Some(weight) = self.weights.iter().find(|&&w| !(w > 0.0 && w.is_finite())) {
+                return Err(ClientError::Config(format!(
+                    "weight {} is out of range (expected above 0)",
+                    weight
+                )));
+            }
+        }
+        if self.progress_json == Some(ProgressTarget::Stdout) && !self.quiet {
+            return Err(ClientError::Config(
+                "--progress-json - writes to stdout, which the live view draws on; add --quiet"
+                    .into(),
+            ));
+        }
+        if self.rate == Some(0) {
+            return Err(ClientError::Config("rate must be > 0".into()));
+        }
+        let caps = [
+            ("--max-bandwidth", self.max_bandwidth.is_some()),
+            ("--max-bytes", self.max_bytes.is_some()),
+        ];
+        if let Some((cap, _)) = caps.iter().find(|(_, set)| *set) {
+            let own_sockets = match self.command {
+                Some(Command::DepthSweep(_)) => Some("depth-sweep"),
+                Some(Command::ImpairSweep(_)) => Some("impair-sweep"),
+                _ => None,
+            };
+            if let Some(command) = own_sockets {
+                return Err(ClientError::Config(format!(
+                    "{} cannot be combined with {}, which probes over sockets of its own",
+                    cap, command
+                )));
+            }
+        }
+        // Subcommands that do not probe send nothing; matrix runs are checked one by one
+        if let (Some(max_bytes), None) = (self.max_bytes, &self.command) {
+            let estimate = self.traffic_estimate();
+            if estimate.total_bytes() > max_bytes {
+                return Err(ClientError::Config(format!(
+                    "the run would put an estimated {} bytes on the wire ({} probes of {} bytes \
+                     and their echoes), above --max-bytes {}; lower --packets, --warmup or \
+                     --payload-size",
+                    estimate.total_bytes(),
+                    estimate.probes,
+                    estimate.probe_bytes,
+                    max_bytes
+                )));
+            }
+        }
+        if self.cold_gap_ms == Some(0) {
+            return Err(ClientError::Config("cold_gap_ms must be > 0".into()));
+        }
+        if !(PACKET_SIZE..=MAX_PACKET_SIZE).contains(&self.payload_size) {
+            return Err(ClientError::Config(format!(
+                "payload_size must be between {} and {} bytes",
+                PACKET_SIZE, MAX_PACKET_SIZE
+            )));
+        }
+        if self.checkpoint_interval == 0 {
+            return Err(ClientError::Config(
+                "checkpoint_interval must be > 0".into(),
+            ));
+        }
+        if let Some(watchdog) = self.watchdog() {
+            // A watchdog shorter than the timeout would fire on ordinary packet loss
+            if watchdog <= self.timeout() {
+                return Err(ClientError::Config(
+                    "watchdog_secs must be longer than the socket timeout".into(),
+                ));
+            }
+        }
 
         // Validate log level
         let valid_levels = ["trace", "debug", "info", "warn", "error"];
@@ -84,12 +1136,13 @@ mod tests {
         let config = Config {
             server: "127.0.0.1:8080".to_string(),
             packets: 10000,
-            warmup: 100000,
+            warmup: Warmup::Count(100000),
             update: 100,
             timeout_ms: 100,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            ..Config::default()
         };
 
         assert_eq!(config.server, "127.0.0.1:8080");
@@ -103,12 +1156,13 @@ mod tests {
         let config = Config {
             server: "192.168.1.1:9000".to_string(),
             packets: 50000,
-            warmup: 10000,
+            warmup: Warmup::Count(10000),
             update: 50,
             timeout_ms: 200,
             quiet: true,
             log_level: "debug".to_string(),
             log_format: "json".to_string(),
+            ..Config::default()
         };
 
         assert_eq!(config.server, "192.168.1.1:9000");
@@ -122,12 +1176,13 @@ mod tests {
         let config = Config {
             server: "127.0.0.1:8080".to_string(),
             packets: 0,
-            warmup: 100000,
+            warmup: Warmup::Count(100000),
             update: 100,
             timeout_ms: 100,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            ..Config::default()
         };
 
         assert!(config.validate().is_err());
@@ -138,12 +1193,13 @@ mod tests {
         let config = Config {
             server: "127.0.0.1:8080".to_string(),
             packets: 10000,
-            warmup: 100000,
+            warmup: Warmup::Count(100000),
             update: 100,
             timeout_ms: 0,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            ..Config::default()
         };
 
         assert!(config.validate().is_err());
@@ -154,12 +1210,13 @@ mod tests {
         let config = Config {
             server: "127.0.0.1:8080".to_string(),
             packets: 10000,
-            warmup: 100000,
+            warmup: Warmup::Count(100000),
             update: 100,
             timeout_ms: 100,
             quiet: false,
             log_level: "invalid".to_string(),
             log_format: "text".to_string(),
+            ..Config::default()
         };
 
         assert!(config.validate().is_err());
@@ -170,12 +1227,13 @@ mod tests {
         let mut config = Config {
             server: "127.0.0.1:8080".to_string(),
             packets: 10000,
-            warmup: 100000,
+            warmup: Warmup::Count(100000),
             update: 100,
             timeout_ms: 100,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            ..Config::default()
         };
 
         assert!(!config.is_json_format());
@@ -186,4 +1244,849 @@ mod tests {
         config.log_format = "JSON".to_string(); // Case insensitive
         assert!(config.is_json_format());
     }
+
+    #[test]
+    fn test_checkpoint_defaults() {
+        let config = Config::default();
+        assert!(config.checkpoint.is_none());
+        assert_eq!(config.checkpoint_interval(), Duration::from_secs(30));
+        assert!(config.command.is_none());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_checkpoint_interval() {
+        let config = Config {
+            checkpoint: Some(PathBuf::from("run.json")),
+            checkpoint_interval: 0,
+            ..Config::default()
+        };
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rate_validation() {
+        assert_eq!(Config::default().rate, None);
+        let config = Config::parse_from(["synapse-client", "--rate", "1000"]);
+        assert_eq!(config.rate, Some(1000));
+        assert!(config.validate().is_ok());
+
+        let config =
+            Config::parse_from(["synapse-client", "--rate", "1000", "--traffic", "poisson"]);
+        assert_eq!(config.traffic, TrafficModel::Poisson);
+        assert!(Config::try_parse_from(["synapse-client", "--traffic", "poisson"]).is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--cold-gap-ms", "5"]).is_err());
+        let config = Config::parse_from(["synapse-client", "--rate", "10", "--cold-gap-ms", "0"]);
+        assert!(config.validate().is_err());
+
+        let config = Config {
+            rate: Some(0),
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_progress_json() {
+        assert_eq!(Config::default().progress_json, None);
+        let config =
+            Config::parse_from(["synapse-client", "--progress-json", "tcp:127.0.0.1:9000"]);
+        assert_eq!(
+            config.progress_json,
+            Some(ProgressTarget::Tcp("127.0.0.1:9000".into()))
+        );
+        assert!(config.validate().is_ok());
+        assert!(Config::try_parse_from(["synapse-client", "--progress-json", "udp:x"]).is_err());
+
+        // On stdout the lines would interleave with the live view
+        let config = Config::parse_from(["synapse-client", "--progress-json", "-"]);
+        assert!(config.validate().is_err());
+        let config = Config::parse_from(["synapse-client", "--progress-json", "-", "--quiet"]);
+        assert!(config.validate().is_ok());
+
+        let config = Config::parse_from([
+            "synapse-client",
+            "--progress-json",
+            "-",
+            "--quiet",
+            "--connections",
+            "2",
+        ]);
+        assert!(config.validate().is_err());
+    }
+
+    #[cfg(feature = "dashboard")]
+    #[test]
+    fn test_ui_dashboard() {
+        assert_eq!(Config::default().ui, UiMode::Live);
+        let config = Config::parse_from(["synapse-client", "--ui", "dashboard"]);
+        assert_eq!(config.ui, UiMode::Dashboard);
+        assert!(config.validate().is_ok());
+        let config = Config::parse_from(["synapse-client", "--ui", "dashboard", "--quiet"]);
+        assert!(!config.dashboard());
+
+        let config =
+            Config::parse_from(["synapse-client", "--ui", "dashboard", "--connections", "2"]);
+        assert!(config.validate().is_err());
+    }
+
+    #[cfg(feature = "charts")]
+    #[test]
+    fn test_chart_dir() {
+        assert_eq!(Config::default().chart_dir, None);
+        let mut config = Config::parse_from(["synapse-client", "--chart-dir", "charts"]);
+        assert_eq!(config.chart_dir, Some(PathBuf::from("charts")));
+        assert!(config.validate().is_ok());
+
+        config.each_address = true;
+        assert!(config.validate().is_err(), "one run's charts only");
+    }
+
+    #[test]
+    fn test_baseline_dir() {
+        let config = Config::default();
+        assert_eq!(config.baseline_dir, None);
+        assert_eq!(config.baseline_window(), Duration::from_secs(24 * 3600));
+        let mut config = Config::parse_from([
+            "synapse-client",
+            "--baseline-dir",
+            "runs",
+            "--baseline-hours",
+            "6",
+            "--max-p99-change",
+            "35",
+        ]);
+        assert_eq!(config.baseline_window(), Duration::from_secs(6 * 3600));
+        assert_eq!(config.max_p99_change, 35.0);
+        assert!(config.validate().is_ok());
+
+        config.max_p99_change = -1.0;
+        assert!(config.validate().is_err());
+        config.max_p99_change = 35.0;
+        config.port_sweep = "8080-8081".parse().ok();
+        assert!(config.validate().is_err(), "one run to grade");
+        assert!(
+            Config::try_parse_from(["synapse-client", "--max-p99-change", "35"]).is_err(),
+            "nothing to deviate from"
+        );
+    }
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(Config::default().clock, ClockSource::Monotonic);
+        let config = Config::parse_from(["synapse-client", "--clock", "monotonic_raw"]);
+        assert_eq!(config.clock, ClockSource::MonotonicRaw);
+        assert_eq!(config.validate().is_ok(), config.clock.is_available());
+        let config = Config::parse_from(["synapse-client", "--clock", "tsc"]);
+        assert_eq!(config.validate().is_ok(), config.clock.is_available());
+        assert!(Config::try_parse_from(["synapse-client", "--clock", "hpet"]).is_err());
+    }
+
+    #[test]
+    fn test_until_ci() {
+        assert_eq!(Config::default().until_ci(), None);
+        let config = Config::parse_from([
+            "synapse-client",
+            "--until-ci",
+            "1%",
+            "--ci-metric",
+            "p99",
+            "--packets",
+            "1000000",
+        ]);
+        assert_eq!(
+            config.until_ci(),
+            Some(CiTarget {
+                metric: CiMetric::P99,
+                half_width_pct: 1.0,
+            })
+        );
+        assert!(config.validate().is_ok());
+        let config = Config::parse_from(["synapse-client", "--until-ci", "0.5", "--window", "8"]);
+        assert!(config.validate().is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--until-ci", "0%"]).is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--ci-metric", "p99"]).is_err());
+    }
+
+    #[test]
+    fn test_transaction() {
+        assert_eq!(Config::default().transaction, None);
+        let config = Config::parse_from(["synapse-client", "--transaction", "3:2"]);
+        assert_eq!(
+            config.transaction,
+            Some(Transaction {
+                requests: 3,
+                responses: 2
+            })
+        );
+        assert!(config.validate().is_ok());
+        // 10,000 transactions of up to 3 packets each way
+        assert_eq!(config.traffic_estimate().probes, 100_000 + 32 + 30_000);
+
+        let config =
+            Config::parse_from(["synapse-client", "--transaction", "3:2", "--window", "8"]);
+        assert!(config.validate().is_err());
+        let config = Config::parse_from([
+            "synapse-client",
+            "--transaction",
+            "1:4",
+            "--kernel-timestamps",
+        ]);
+        assert!(config.validate().is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--transaction", "3:0"]).is_err());
+    }
+
+    #[test]
+    fn test_energy() {
+        let config = Config::default();
+        assert!(!config.energy);
+        let mut config =
+            Config::parse_from(["synapse-client", "--energy", "--carbon-intensity", "400"]);
+        assert_eq!(config.carbon_intensity, Some(400.0));
+        assert!(config.validate().is_ok());
+        config.carbon_intensity = Some(0.0);
+        assert!(config.validate().is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--carbon-intensity", "400"]).is_err());
+    }
+
+    #[test]
+    fn test_warmup_auto() {
+        let config = Config::default();
+        assert_eq!(config.warmup, Warmup::Count(100000));
+        assert_eq!(config.warmup_tolerance_pct(), DEFAULT_WARMUP_TOLERANCE_PCT);
+        let mut config = Config::parse_from([
+            "synapse-client",
+            "--warmup",
+            "auto",
+            "--warmup-tolerance",
+            "5",
+        ]);
+        assert_eq!(config.warmup, Warmup::Auto);
+        assert_eq!(config.warmup_tolerance_pct(), 5.0);
+        assert!(config.validate().is_ok());
+        // The estimate covers the longest warmup
+        assert_eq!(config.traffic_estimate().probes, 100_000 + 32 + 10_000);
+
+        config.warmup_tolerance = Some(0.0);
+        assert!(config.validate().is_err());
+        config.warmup_tolerance = Some(5.0);
+        config.warmup = Warmup::Count(500);
+        assert!(config.validate().is_err(), "nothing to settle");
+        assert!(Config::try_parse_from(["synapse-client", "--warmup", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        assert_eq!(Config::default().max_bytes, None);
+        // 100,000 warmup, 32 clock sync and 10,000 measurement probes of 64 + 60 bytes
+        let estimate = Config::default().traffic_estimate();
+        assert_eq!(estimate.probes, 110_032);
+        assert_eq!(estimate.probe_bytes, 124);
+        assert_eq!(estimate.total_bytes(), 110_032 * 124 * 2);
+        assert_eq!(estimate.paced, None);
+
+        let config = Config::parse_from(["synapse-client", "--max-bytes", "50MB"]);
+        assert_eq!(config.max_bytes, Some(50_000_000));
+        assert!(config.validate().is_ok());
+        let config = Config::parse_from(["synapse-client", "--max-bytes", "20MB"]);
+        assert!(config.validate().is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--max-bytes", "lots"]).is_err());
+
+        // Every connection and swept port sends its own probes
+        let config = Config::parse_from([
+            "synapse-client",
+            "--warmup",
+            "0",
+            "--clock-sync",
+            "0",
+            "--packets",
+            "100",
+            "--connections",
+            "2",
+            "--rate",
+            "50",
+            "--port-sweep",
+            "8080-8084",
+        ]);
+        let estimate = config.traffic_estimate();
+        assert_eq!(estimate.probes, 1_000);
+        assert_eq!(estimate.paced, Some((100, 50)));
+
+        // Subcommands that send nothing are not held to the cap
+        let config = Config::parse_from([
+            "synapse-client",
+            "--max-bytes",
+            "1kB",
+            "analyze",
+            "run.json",
+        ]);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_max_bandwidth() {
+        assert_eq!(Config::default().max_bandwidth, None);
+        assert!(Config::default().bandwidth_budget().is_none());
+        let config = Config::parse_from(["synapse-client", "--max-bandwidth", "1Mbps"]);
+        assert_eq!(
+            config.max_bandwidth,
+            Some(Bandwidth::from_bits_per_sec(1_000_000))
+        );
+        assert!(config.bandwidth_budget().is_some());
+        assert!(config.validate().is_ok());
+        assert!(Config::try_parse_from(["synapse-client", "--max-bandwidth", "fast"]).is_err());
+
+        // The sweeps probe over sockets the cap does not cover
+        let config =
+            Config::parse_from(["synapse-client", "--max-bandwidth", "1Mbps", "depth-sweep"]);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_dscp_flag() {
+        assert_eq!(Config::default().dscp, None);
+        let config = Config::parse_from(["synapse-client", "--dscp", "af41"]);
+        assert_eq!(config.dscp, Dscp::new(34));
+        assert!(Config::try_parse_from(["synapse-client", "--dscp", "64"]).is_err());
+    }
+
+    #[test]
+    fn test_payload_size_validation() {
+        assert_eq!(Config::default().payload_size, PACKET_SIZE);
+        let config = Config::parse_from(["synapse-client", "--payload-size", "1400"]);
+        assert_eq!(config.payload_size, 1400);
+        assert!(config.validate().is_ok());
+
+        let too_small = Config::parse_from(["synapse-client", "--payload-size", "4"]);
+        assert!(too_small.validate().is_err());
+        let too_large = Config::parse_from(["synapse-client", "--payload-size", "65508"]);
+        assert!(too_large.validate().is_err());
+    }
+
+    #[test]
+    fn test_slo_parsing() {
+        assert!(Config::default().slo.is_empty());
+        let config = Config::parse_from([
+            "synapse-client",
+            "--slo",
+            "99.95%<500us",
+            "--slo",
+            "99%<1ms",
+        ]);
+        assert_eq!(config.slo.len(), 2);
+        assert_eq!(config.slo[1].threshold, Duration::from_millis(1));
+        assert!(Config::try_parse_from(["synapse-client", "--slo", "p99<1ms"]).is_err());
+
+        let config =
+            Config::parse_from(["synapse-client", "--sla", "p99<500us,p50<100us,loss<0.01%"]);
+        assert_eq!(config.sla.unwrap().conditions.len(), 3);
+        assert!(
+            Config::try_parse_from(["synapse-client", "--sla", "p99<500us,jitter<1ms"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_transport_parsing() {
+        assert_eq!(Config::default().transport, Transport::Tcp);
+        let config = Config::parse_from(["synapse-client", "--transport", "udp"]);
+        assert_eq!(config.transport, Transport::Udp);
+        assert!(Config::try_parse_from(["synapse-client", "--transport", "sctp"]).is_err());
+    }
+
+    #[test]
+    fn test_record_packets_requires_checkpoint() {
+        assert!(Config::try_parse_from(["synapse-client", "--record-packets"]).is_err());
+        let config = Config::parse_from([
+            "synapse-client",
+            "--checkpoint",
+            "run.json",
+            "--record-packets",
+        ]);
+        assert!(config.record_packets);
+    }
+
+    #[test]
+    fn test_dissector_subcommand_parsing() {
+        let config = Config::parse_from(["synapse-client", "dissector", "--port", "9301"]);
+        match config.command {
+            Some(Command::Dissector(args)) => {
+                assert_eq!(args.port, Some(9301));
+                assert_eq!(args.out, None);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_analyze_subcommand_parsing() {
+        let config = Config::parse_from(["synapse-client", "analyze", "run.json"]);
+        match config.command {
+            Some(Command::Analyze(args)) => assert_eq!(args.path, PathBuf::from("run.json")),
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        assert_eq!(Config::default().slowest, DEFAULT_SLOWEST);
+        let config =
+            Config::parse_from(["synapse-client", "analyze", "run.json", "--slowest", "5"]);
+        match config.command {
+            Some(Command::Analyze(args)) => assert_eq!(args.slowest, 5),
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compare_subcommand_parsing() {
+        let config = Config::parse_from([
+            "synapse-client",
+            "compare",
+            "baseline.json",
+            "current.json",
+            "--tolerance",
+            "5",
+        ]);
+        match config.command {
+            Some(Command::Compare(args)) => {
+                assert_eq!(args.baseline, PathBuf::from("baseline.json"));
+                assert_eq!(args.current, PathBuf::from("current.json"));
+                assert_eq!(args.tolerance, 5.0);
+                assert!(args.validate().is_ok());
+                let negative = CompareArgs {
+                    tolerance: -1.0,
+                    ..args
+                };
+                assert!(negative.validate().is_err());
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(Config::try_parse_from(["synapse-client", "compare", "baseline.json"]).is_err());
+    }
+
+    #[test]
+    fn test_watchdog_validation() {
+        let mut config = Config {
+            watchdog_secs: Some(5),
+            ..Config::default()
+        };
+        assert_eq!(config.watchdog(), Some(Duration::from_secs(5)));
+        assert!(config.validate().is_ok());
+
+        config.watchdog_secs = Some(0);
+        assert!(config.validate().is_err());
+
+        config.watchdog_secs = Some(1);
+        config.timeout_ms = 1000;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let mut config = Config::parse_from(["synapse-client", "--heartbeat-secs", "30"]);
+        config.quiet = true;
+        assert!(!config.live_view());
+        assert_eq!(config.heartbeat(), Some(Duration::from_secs(30)));
+        config.heartbeat_secs = 0;
+        assert_eq!(config.heartbeat(), None);
+    }
+
+    #[test]
+    fn test_impair_sweep_args() {
+        let config = Config::parse_from([
+            "synapse-client",
+            "impair-sweep",
+            "--interface",
+            "lo",
+            "--profile",
+            "2:0.5",
+        ]);
+        let Some(Command::ImpairSweep(args)) = config.command else {
+            panic!("expected impair-sweep subcommand");
+        };
+        assert_eq!(args.profiles.len(), 1);
+        assert_eq!(args.profiles[0].loss_pct, 0.5);
+        assert_eq!(args.passes(), 2);
+        assert!(args.validate().is_ok());
+
+        let args = ImpairSweepArgs {
+            interface: "eth0".into(),
+            passes: None,
+            ..args
+        };
+        assert_eq!(args.passes(), 1);
+    }
+
+    #[test]
+    fn test_connections() {
+        assert_eq!(Config::default().connections, 1);
+        let mut config = Config::parse_from(["synapse-client", "--connections", "8"]);
+        assert_eq!(config.connections, 8);
+        assert!(config.validate().is_ok());
+
+        config.pcap = Some(PathBuf::from("run.pcap"));
+        assert!(config.validate().is_err());
+        config.connections = 1;
+        assert!(config.validate().is_ok());
+        config.connections = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_window() {
+        assert_eq!(Config::default().window, 1);
+        let mut config = Config::parse_from(["synapse-client", "--window", "16"]);
+        assert_eq!(config.window, 16);
+        assert!(config.validate().is_ok());
+
+        config.rate = Some(1000);
+        assert!(config.validate().is_err());
+        config.rate = None;
+        config.kernel_timestamps = true;
+        assert!(config.validate().is_err());
+        config.kernel_timestamps = false;
+        config.window = 10_000;
+        assert!(
+            config.validate().is_err(),
+            "too many bytes in flight over TCP"
+        );
+        config.transport = Transport::Udp;
+        assert!(config.validate().is_ok());
+        config.window = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_flows() {
+        assert_eq!(Config::default().flows, 1);
+        let mut config =
+            Config::parse_from(["synapse-client", "--transport", "udp", "--flows", "8"]);
+        assert_eq!(config.flows, 8);
+        assert!(config.validate().is_ok());
+
+        config.window = 4;
+        assert!(config.validate().is_err());
+        config.window = 1;
+        config.transport = Transport::Tcp;
+        assert!(config.validate().is_err(), "UDP only");
+        config.flows = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_reflectors() {
+        assert!(Config::default().reflectors.is_empty());
+        let mut config = Config::parse_from([
+            "synapse-client",
+            "--transport",
+            "udp",
+            "--reflector",
+            "10.0.1.1:8080",
+            "--reflector",
+            "10.0.2.1:8080",
+        ]);
+        assert_eq!(config.reflectors, vec!["10.0.1.1:8080", "10.0.2.1:8080"]);
+        assert!(config.validate().is_ok());
+
+        config.flows = 2;
+        assert!(config.validate().is_err());
+        config.flows = 1;
+        config.each_address = true;
+        assert!(config.validate().is_err());
+        config.each_address = false;
+        config.transport = Transport::Tcp;
+        assert!(config.validate().is_err(), "UDP only");
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut config = Config::parse_from([
+            "synapse-client",
+            "--transport",
+            "udp",
+            "--reflector",
+            "10.0.1.1:8080",
+        ]);
+        assert_eq!(config.reflector_weights(), vec![1.0, 1.0]);
+        config.weights = vec![3.0, 0.5];
+        assert!(config.validate().is_ok());
+        assert_eq!(config.reflector_weights(), vec![3.0, 0.5]);
+
+        config.weights = vec![3.0];
+        assert!(config.validate().is_err(), "one weight per reflector");
+        config.weights = vec![3.0, 0.0];
+        assert!(config.validate().is_err());
+        config.weights = vec![3.0, 1.0];
+        config.reflectors.clear();
+        assert!(config.validate().is_err(), "no pool to weigh");
+        assert!(Config::try_parse_from(["synapse-client", "--weights", "3,x"]).is_err());
+    }
+
+    #[test]
+    fn test_port_sweep() {
+        assert_eq!(Config::default().port_sweep, None);
+        let mut config = Config::parse_from(["synapse-client", "--port-sweep", "8080-8090"]);
+        assert_eq!(config.port_sweep.map(|range| range.count()), Some(11));
+        assert!(config.validate().is_ok());
+
+        config.report_bundle = Some(PathBuf::from("bundle"));
+        assert!(config.validate().is_err());
+        config.report_bundle = None;
+        config.server = "localhost".into();
+        assert!(config.validate().is_err(), "no port to replace");
+        assert!(Config::try_parse_from(["synapse-client", "--port-sweep", "9000-8000"]).is_err());
+    }
+
+    #[test]
+    fn test_compare_families() {
+        assert!(!Config::default().compare_families);
+        let mut config = Config::parse_from(["synapse-client", "--compare-families"]);
+        assert!(config.compare_families);
+        assert!(config.validate().is_ok());
+
+        config.hgrm = Some(PathBuf::from("run.hgrm"));
+        assert!(config.validate().is_err());
+        config.hgrm = None;
+        config.port_sweep = "8080-8081".parse().ok();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_each_address() {
+        assert!(!Config::default().each_address);
+        let mut config = Config::parse_from(["synapse-client", "--each-address"]);
+        assert!(config.validate().is_ok());
+
+        config.pcap = Some(PathBuf::from("run.pcap"));
+        assert!(config.validate().is_err());
+        config.pcap = None;
+        config.compare_families = true;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert_eq!(Config::default().percentiles, DEFAULT_PERCENTILES);
+        let mut config = Config::parse_from(["synapse-client", "--percentiles", "50,99.99,99.999"]);
+        assert_eq!(config.percentiles, vec![50.0, 99.99, 99.999]);
+        assert!(config.validate().is_ok());
+
+        config.percentiles = vec![99.0, 100.5];
+        assert!(config.validate().is_err());
+        config.percentiles = vec![0.0];
+        assert!(config.validate().is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--percentiles", "p99"]).is_err());
+    }
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(Config::default().buckets, None);
+        let config = Config::parse_from(["synapse-client", "--buckets", "1ms,10ms,100ms"]);
+        assert_eq!(
+            config.buckets,
+            Some(BucketEdges::Custom(vec![
+                1_000_000,
+                10_000_000,
+                100_000_000
+            ]))
+        );
+        let config = Config::parse_from(["synapse-client", "--buckets", "auto"]);
+        assert_eq!(config.buckets, Some(BucketEdges::Auto));
+        assert!(Config::try_parse_from(["synapse-client", "--buckets", "10ms,1ms"]).is_err());
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        assert_eq!(Config::default().retry_policy, None);
+        let config = Config::parse_from([
+            "synapse-client",
+            "--retry-policy",
+            "timeout=50ms,attempts=5",
+        ]);
+        let policy = config.retry_policy.unwrap();
+        assert_eq!(policy.timeout, Duration::from_millis(50));
+        assert_eq!(policy.attempts, 5);
+        assert_eq!(policy.backoff, RetryPolicy::default().backoff);
+        assert!(
+            Config::try_parse_from(["synapse-client", "--retry-policy", "attempts=none"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_fail_threshold() {
+        assert_eq!(Config::default().verdict(), Verdict::default());
+        let mut config = Config::parse_from([
+            "synapse-client",
+            "--fail-above",
+            "500us",
+            "--fail-metric",
+            "p999",
+        ]);
+        assert_eq!(
+            config.verdict(),
+            Verdict {
+                metric: FailMetric::P999,
+                threshold_ns: 500_000,
+            }
+        );
+        assert!(config.validate().is_ok());
+
+        // Beyond the histogram, only the unclamped max can be graded
+        config.fail_above = Duration::from_secs(1);
+        assert!(config.validate().is_err());
+        config.fail_metric = FailMetric::Max;
+        assert!(config.validate().is_ok());
+        assert!(Config::try_parse_from(["synapse-client", "--fail-above", "5"]).is_err());
+
+        config.max_loss = Some(0.5);
+        assert!(config.validate().is_ok());
+        config.max_loss = Some(101.0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_payload_template() {
+        assert_eq!(Config::default().probe_size(), PACKET_SIZE);
+        let config = Config::parse_from(["synapse-client", "--payload-template", "http:512"]);
+        assert_eq!(
+            config.payload_template.map(|t| t.to_string()).as_deref(),
+            Some("http:512")
+        );
+        assert_eq!(config.probe_size(), 512);
+        assert!(config.validate().is_ok());
+
+        // The template sets the probe size, so --payload-size cannot also be given
+        assert!(Config::try_parse_from([
+            "synapse-client",
+            "--payload-template",
+            "protobuf:200",
+            "--payload-size",
+            "100",
+        ])
+        .is_err());
+        assert!(Config::try_parse_from(["synapse-client", "--payload-template", "http"]).is_err());
+    }
+
+    #[test]
+    fn test_depth_sweep_args() {
+        let config = Config::parse_from(["synapse-client", "depth-sweep", "--max-depth", "64"]);
+        let Some(Command::DepthSweep(args)) = config.command else {
+            panic!("expected depth-sweep subcommand");
+        };
+        assert_eq!(args.max_depth, 64);
+        assert!(args.validate(Transport::Tcp, PACKET_SIZE).is_ok());
+        assert!(args.validate(Transport::Udp, PACKET_SIZE).is_err());
+        // 64 probes of 8 KiB would overrun the socket buffers
+        assert!(args.validate(Transport::Tcp, 8192).is_err());
+
+        let args = DepthSweepArgs {
+            max_depth: 0,
+            ..args
+        };
+        assert!(args.validate(Transport::Tcp, PACKET_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_exported_server() {
+        let config = Config {
+            server: "10.0.0.2:8080".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(config.exported_server(), "10.0.0.2:8080");
+
+        let config =
+            Config::parse_from(["synapse-client", "--server", "10.0.0.2:8080", "--redact"]);
+        let exported = config.exported_server();
+        assert!(!exported.contains("10.0.0.2"), "{}", exported);
+        assert!(exported.ends_with(":8080"), "{}", exported);
+        assert!(Config::try_parse_from(["synapse-client", "--redact-key", "k"]).is_err());
+    }
+
+    #[test]
+    fn test_pushgateway() {
+        assert!(Config::default().pushgateway().is_none());
+
+        let config = Config::parse_from([
+            "synapse-client",
+            "--server",
+            "10.0.0.2:8080",
+            "--pushgateway",
+            "http://pushgateway:9091",
+        ]);
+        let gateway = config.pushgateway().unwrap();
+        assert_eq!(
+            gateway.group_path(),
+            "/metrics/job/synapse/instance/10.0.0.2:8080"
+        );
+        assert!(
+            Config::try_parse_from(["synapse-client", "--pushgateway", "pushgateway:9091"])
+                .is_err()
+        );
+        assert!(Config::try_parse_from(["synapse-client", "--push-job", "nightly"]).is_err());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path =
+            std::env::temp_dir().join(format!("synapse-{}-client-config.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "server = \"10.0.0.2:8080\"\npackets = 5000\nslo = [\"99%<1ms\"]\nquiet = true\n",
+        )
+        .unwrap();
+        let config = Config::try_load_from([
+            "synapse-client",
+            "--config",
+            path.to_str().unwrap(),
+            "--packets",
+            "100",
+        ]);
+        let analyze = Config::try_load_from([
+            "synapse-client",
+            "--config",
+            path.to_str().unwrap(),
+            "analyze",
+            "run.json",
+        ]);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.server, "10.0.0.2:8080");
+        assert_eq!(config.packets, 100);
+        assert_eq!(config.slo.len(), 1);
+        assert!(config.quiet);
+        assert!(matches!(
+            analyze.unwrap().command,
+            Some(Command::Analyze(_))
+        ));
+        assert!(Config::try_load_from(["synapse-client", "--config", "missing.toml"]).is_err());
+    }
+
+    #[test]
+    fn test_tags() {
+        assert!(Config::default().tags().is_empty());
+
+        let config = Config::parse_from([
+            "synapse-client",
+            "--tag",
+            "build=4f2a9c1",
+            "--tag",
+            "branch=main",
+            "--tag",
+            "build=9e0b7d3",
+        ]);
+        let tags = config.tags();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags["build"], "9e0b7d3");
+        assert_eq!(tags["branch"], "main");
+        assert!(Config::try_parse_from(["synapse-client", "--tag", "build"]).is_err());
+    }
+
+    #[test]
+    fn test_number_format() {
+        assert_eq!(Config::default().number_format(), NumberFormat::default());
+
+        let config = Config::parse_from(["synapse-client", "--si", "--thousands-separator", ""]);
+        assert_eq!(config.number_format().units, UnitSystem::Si);
+        assert_eq!(config.number_format().thousands_separator, "");
+        assert!(Config::try_parse_from(["synapse-client", "--si", "--iec"]).is_err());
+    }
 }
@@ -0,0 +1,358 @@
+//! Concurrent client connections
+//!
+//! A single connection sees the server and the path one probe at a time; a service
+//! answering many clients sees their requests contend for its threads, queues and
+//! locks. `--connections N` opens N sockets, each measured by a thread of its own with
+//! its own sequence space and the usual warmup, and starts their measurement phases
+//! together. Every connection sends `--packets` probes, at `--rate` if one is set.
+//!
+//! The report covers the samples of all connections merged in send order, so every
+//! section reads as for a single connection, followed by a breakdown per connection.
+//! Connections differ by their source port alone, so one standing out is typically on
+//! another path through an ECMP hash or another server queue.
+
+use crate::client::error::Result;
+use crate::client::measurement::MeasurementResult;
+use crate::client::pacing::PacingStats;
+use crate::client::statistics::Statistics;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// Ratio of the highest to the lowest P99 of the connections from which the report
+/// points out the slowest
+pub const P99_SPREAD_RATIO: f64 = 2.0;
+
+/// Ratio to the median P99 of the connections from which one is flagged as an outlier
+pub const P99_MEDIAN_RATIO: f64 = 2.0;
+
+/// Outcome of one connection of a concurrent run, or of one flow of a multi-flow run
+/// (see [`crate::client::flows`])
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionStats {
+    /// Position of the connection, from 0
+    pub index: usize,
+    /// Local address of the connection, if known
+    pub local: Option<SocketAddr>,
+    /// Probes sent
+    pub sent: usize,
+    /// Probes without a reply
+    pub lost: usize,
+    /// Lowest RTT in nanoseconds
+    pub min_ns: u64,
+    /// Mean RTT in nanoseconds
+    pub mean_ns: f64,
+    /// Median RTT in nanoseconds
+    pub p50_ns: u64,
+    /// 99th percentile RTT in nanoseconds
+    pub p99_ns: u64,
+}
+
+impl ConnectionStats {
+    /// Summarize the measurement of connection `index`
+    pub fn new(
+        index: usize,
+        local: Option<SocketAddr>,
+        result: &MeasurementResult,
+    ) -> Result<Self> {
+        Self::from_samples(
+            index,
+            local,
+            &result.latencies,
+            result.total_packets,
+            result.lost_packets,
+        )
+    }
+
+    /// Summarize `sent` probes, `lost` of them without a reply and the rest answered
+    /// after `latencies`
+    pub fn from_samples(
+        index: usize,
+        local: Option<SocketAddr>,
+        latencies: &[u64],
+        sent: usize,
+        lost: usize,
+    ) -> Result<Self> {
+        let stats = Statistics::new(latencies)?;
+        Ok(Self {
+            index,
+            local,
+            sent,
+            lost,
+            min_ns: stats.min(),
+            mean_ns: stats.mean(),
+            p50_ns: stats.percentile(0.5),
+            p99_ns: stats.percentile(0.99),
+        })
+    }
+}
+
+/// Dispersion of the P99 of several connections or flows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct P99Dispersion {
+    /// Median P99 in nanoseconds
+    pub median_ns: u64,
+    /// Standard deviation of the P99s over their mean, in percent
+    pub cv_pct: f64,
+}
+
+impl P99Dispersion {
+    /// Dispersion of the P99 of `streams`, or `None` without any
+    ///
+    /// The median is the lower middle value for an even count, so a single slow stream
+    /// out of two does not raise it.
+    pub fn of(streams: &[ConnectionStats]) -> Option<Self> {
+        let mut p99s: Vec<u64> = streams.iter().map(|stream| stream.p99_ns).collect();
+        p99s.sort_unstable();
+        let median_ns = *p99s.get((p99s.len().max(1) - 1) / 2)?;
+        let count = p99s.len() as f64;
+        let mean = p99s.iter().sum::<u64>() as f64 / count;
+        let variance = p99s
+            .iter()
+            .map(|&p99| (p99 as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        Some(Self {
+            median_ns,
+            cv_pct: variance.sqrt() / mean.max(1.0) * 100.0,
+        })
+    }
+
+    /// Whether `stream`'s P99 is [`P99_MEDIAN_RATIO`] times the median or more
+    pub fn is_outlier(&self, stream: &ConnectionStats) -> bool {
+        stream.p99_ns as f64 >= self.median_ns.max(1) as f64 * P99_MEDIAN_RATIO
+    }
+}
+
+/// Merge the measurements of concurrent connections into one
+///
+/// Successful samples are ordered by send time across connections, from the earliest
+/// start. Sequence numbers and lost sequences are moved into one space: connection
+/// `i`'s probes are numbered after all the probes of connections `0..i`. The per-thread
+/// CPU usage and system timeline do not add up across threads and are left out; the
+/// pacing target is the sum of the connections' rates.
+pub fn merge(results: Vec<MeasurementResult>) -> MeasurementResult {
+    let started_at = results
+        .iter()
+        .map(|result| result.started_at)
+        .min()
+        .unwrap_or_else(SystemTime::now);
+    let shift = |result: &MeasurementResult| {
+        result
+            .started_at
+            .duration_since(started_at)
+            .unwrap_or_default()
+    };
+
+    // (send offset from the merged start, connection, sample) of every success
+    let mut order: Vec<(u64, usize, usize)> = results
+        .iter()
+        .enumerate()
+        .flat_map(|(connection, result)| {
+            let shift_ns = shift(result).as_nanos() as u64;
+            result
+                .send_offsets
+                .iter()
+                .enumerate()
+                .map(move |(sample, offset)| (offset + shift_ns, connection, sample))
+        })
+        .collect();
+    order.sort_unstable();
+
+    let mut base = Vec::with_capacity(results.len());
+    let mut sequence_base = 0u64;
+    for result in &results {
+        base.push(sequence_base);
+        sequence_base += result.total_packets as u64;
+    }
+
+    let mut merged = MeasurementResult {
+        latencies: Vec::with_capacity(order.len()),
+        send_durations: Vec::new(),
+        recv_wait_durations: Vec::with_capacity(order.len()),
+        lost_packets: results.iter().map(|r| r.lost_packets).sum(),
+        total_packets: results.iter().map(|r| r.total_packets).sum(),
+        elapsed: results
+            .iter()
+            .map(|r| shift(r) + r.elapsed)
+            .max()
+            .unwrap_or(Duration::ZERO),
+        seed: results.first().map_or(0, |r| r.seed),
+        system_timeline: None,
+        sequences: Vec::with_capacity(order.len()),
+        send_offsets: Vec::with_capacity(order.len()),
+        idle_gaps: Vec::with_capacity(order.len()),
+        started_at,
+        warmup: None,
+        lost_sequences: Vec::new(),
+        thread_usage: None,
+        sample_backing: results.iter().filter_map(|r| r.sample_backing).min(),
+        path_timings: Vec::new(),
+        clock_offset: results.first().and_then(|r| r.clock_offset),
+        kernel_timings: Vec::new(),
+        pacing: results
+            .first()
+            .and_then(|r| r.pacing)
+            .map(|pacing| PacingStats {
+                rate: pacing.rate * results.len() as u64,
+                traffic: pacing.traffic,
+                late_sends: results
+                    .iter()
+                    .filter_map(|r| r.pacing)
+                    .map(|p| p.late_sends)
+                    .sum(),
+            }),
+        reply_dscp: Default::default(),
+    };
+    for (offset, connection, sample) in order {
+        let result = &results[connection];
+        merged.latencies.push(result.latencies[sample]);
+        merged
+            .recv_wait_durations
+            .push(result.recv_wait_durations[sample]);
+        merged
+            .sequences
+            .push(result.sequences[sample] + base[connection]);
+        merged.send_offsets.push(offset);
+        merged.idle_gaps.push(result.idle_gaps[sample]);
+    }
+    for (result, base) in results.iter().zip(base) {
+        merged
+            .send_durations
+            .extend_from_slice(&result.send_durations);
+        merged
+            .lost_sequences
+            .extend(result.lost_sequences.iter().map(|sequence| sequence + base));
+        merged.path_timings.extend_from_slice(&result.path_timings);
+        merged
+            .kernel_timings
+            .extend_from_slice(&result.kernel_timings);
+        for (dscp, count) in &result.reply_dscp {
+            *merged.reply_dscp.entry(*dscp).or_default() += count;
+        }
+    }
+
+    debug!(
+        connections = results.len(),
+        samples = merged.latencies.len(),
+        "Connection results merged"
+    );
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::pacing::TrafficModel;
+
+    fn result(started_at: SystemTime, send_offsets: Vec<u64>, lost: Vec<u64>) -> MeasurementResult {
+        let latencies: Vec<u64> = send_offsets.iter().map(|offset| 10_000 + offset).collect();
+        let total = send_offsets.len() + lost.len();
+        MeasurementResult {
+            recv_wait_durations: latencies.clone(),
+            latencies,
+            send_durations: vec![1_000; total],
+            lost_packets: lost.len(),
+            total_packets: total,
+            elapsed: Duration::from_millis(1),
+            seed: 7,
+            system_timeline: None,
+            sequences: (0..total as u64).filter(|s| !lost.contains(s)).collect(),
+            idle_gaps: vec![0; send_offsets.len()],
+            send_offsets,
+            started_at,
+            warmup: None,
+            lost_sequences: lost,
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: Some(PacingStats {
+                rate: 1_000,
+                traffic: TrafficModel::Fixed,
+                late_sends: 1,
+            }),
+            reply_dscp: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let start = SystemTime::now();
+        let first = result(start, vec![0, 200, 400], vec![]);
+        // Started 100ns later, with its second probe lost
+        let second = result(
+            start + Duration::from_nanos(100),
+            vec![0, 200, 400],
+            vec![1],
+        );
+        let merged = merge(vec![first, second]);
+
+        assert_eq!(merged.started_at, start);
+        assert_eq!(merged.send_offsets, vec![0, 100, 200, 300, 400, 500]);
+        assert_eq!(merged.sequences, vec![0, 3, 1, 5, 2, 6]);
+        assert_eq!(
+            merged.latencies[1], 10_000,
+            "second connection's first probe"
+        );
+        assert_eq!(merged.lost_sequences, vec![4]);
+        assert_eq!((merged.lost_packets, merged.total_packets), (1, 7));
+        assert_eq!(merged.send_durations.len(), 7);
+        assert_eq!(merged.elapsed, Duration::from_nanos(1_000_100));
+        let pacing = merged.pacing.unwrap();
+        assert_eq!((pacing.rate, pacing.late_sends), (2_000, 2));
+    }
+
+    #[test]
+    fn test_connection_stats() -> Result<()> {
+        let result = result(SystemTime::now(), vec![0, 0, 0, 10_000], vec![4]);
+        let stats = ConnectionStats::new(2, None, &result)?;
+        assert_eq!((stats.index, stats.sent, stats.lost), (2, 5, 1));
+        // Within the histogram's precision
+        assert!(stats.p50_ns.abs_diff(10_000) < 100, "{}", stats.p50_ns);
+        assert!(
+            (stats.mean_ns - 12_500.0).abs() < 100.0,
+            "{}",
+            stats.mean_ns
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_p99_dispersion() -> Result<()> {
+        let stream = |index, p99_ns| ConnectionStats {
+            index,
+            local: None,
+            sent: 100,
+            lost: 0,
+            min_ns: 10_000,
+            mean_ns: 15_000.0,
+            p50_ns: 14_000,
+            p99_ns,
+        };
+        assert_eq!(P99Dispersion::of(&[]), None);
+
+        // One bad path among eight
+        let mut streams: Vec<_> = (0..7).map(|index| stream(index, 30_000)).collect();
+        streams.push(stream(7, 240_000));
+        let dispersion = P99Dispersion::of(&streams).unwrap();
+        assert_eq!(dispersion.median_ns, 30_000);
+        // Mean 56,250 ns, standard deviation 69,448 ns
+        assert!(
+            (dispersion.cv_pct - 123.5).abs() < 0.1,
+            "{}",
+            dispersion.cv_pct
+        );
+        let outliers: Vec<_> = streams
+            .iter()
+            .filter(|stream| dispersion.is_outlier(stream))
+            .map(|stream| stream.index)
+            .collect();
+        assert_eq!(outliers, vec![7]);
+
+        let even = P99Dispersion::of(&[stream(0, 30_000), stream(1, 30_000)]).unwrap();
+        assert_eq!(even.cv_pct, 0.0);
+        Ok(())
+    }
+}
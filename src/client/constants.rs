@@ -1,7 +1,7 @@
 //! Constants used throughout the client application
 
-/// Sample rate for OSI layer animation (animate every Nth packet)
-pub const OSI_ANIMATION_SAMPLE_RATE: usize = 100;
+/// Sample rate of the live OSI view (split the recent RTTs again every Nth packet)
+pub const OSI_SPLIT_SAMPLE_RATE: usize = 100;
 
 /// Progress bar tick interval in milliseconds
 pub const PROGRESS_TICK_INTERVAL_MS: u64 = 100;
@@ -18,9 +18,24 @@ pub const HISTOGRAM_HIGH_BOUND_NS: u64 = 100_000_000;
 /// Histogram significant digits for precision
 pub const HISTOGRAM_SIGNIFICANT_DIGITS: u8 = 3;
 
+/// Percentile ticks per half distance to 100% in `.hgrm` exports (HdrHistogram's default)
+pub const HGRM_TICKS_PER_HALF_DISTANCE: u32 = 5;
+
+/// Nanoseconds per unit of the values in `.hgrm` exports (microseconds)
+pub const HGRM_UNIT_RATIO: f64 = 1000.0;
+
+/// RTT percentiles listed in the report unless --percentiles says otherwise
+pub const DEFAULT_PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 99.9];
+
+/// Time a UDP socket waits for the server's challenge to its hello, in milliseconds
+pub const HELLO_TIMEOUT_MS: u64 = 1000;
+
 /// Pass threshold for mean latency in milliseconds
 pub const PASS_THRESHOLD_MS: f64 = 1.0;
 
+/// Send calls taking longer than this (nanoseconds) are reported as send stalls
+pub const SEND_STALL_THRESHOLD_NS: u64 = 100_000;
+
 /// Excellent latency threshold in milliseconds
 pub const EXCELLENT_LATENCY_MS: f64 = 0.5;
 
@@ -0,0 +1,498 @@
+//! Full-screen live dashboard
+//!
+//! The default live view draws its statistics and the OSI view into the message of
+//! an indicatif progress bar, which wraps and smears on terminals narrower than its
+//! lines. `--ui dashboard` (behind the `dashboard` cargo feature) takes over the
+//! terminal's alternate screen instead and lays the measurement out in ratatui panes:
+//!
+//! - progress: probes done out of the phase, and the time elapsed
+//! - statistics: last and mean RTT, rolling P50, P90 and P99, probe rate and loss
+//! - latency: a chart of the RTTs of the most recent probes
+//! - OSI: the client and server stacks, the layers taking the largest share of the
+//!   recent probes' RTT highlighted
+//!
+//! The panes stack vertically on narrow terminals, and the OSI pane is left out of
+//! short ones. Rolling figures cover the last [`LIVE_P99_WINDOW_SIZE`] answered probes,
+//! as in the default view. The terminal is not put into raw mode, so Ctrl-C still
+//! interrupts the run. Log lines share stdout with the dashboard and land on its screen
+//! until the next redraw of their cells; `--log-level error` keeps it clean.
+
+use crate::client::constants::{
+    ACCEPTABLE_LATENCY_MS, EXCELLENT_LATENCY_MS, LIVE_P99_WINDOW_SIZE, PROGRESS_TICK_INTERVAL_MS,
+};
+use crate::client::decomposition::Decomposition;
+use crate::client::error::Result;
+use crate::client::theme::Theme;
+use crate::client::visualizer::{Host, OsiVisualizer, OSI_LAYERS};
+use clap::ValueEnum;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::crossterm::cursor::{Hide, Show};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::VecDeque;
+use std::io::{self, Stdout};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Width from which the statistics and the chart sit side by side
+const SIDE_BY_SIDE_WIDTH: u16 = 80;
+
+/// Height from which the OSI pane is shown
+const OSI_PANE_MIN_HEIGHT: u16 = 24;
+
+/// Live view of the measurement phase
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UiMode {
+    /// Statistics and OSI view above a progress bar
+    #[default]
+    Live,
+    /// Full-screen dashboard with a latency chart, in panes
+    Dashboard,
+}
+
+/// Figures the dashboard shows, updated probe by probe
+struct Panes {
+    theme: Theme,
+    visualizer: OsiVisualizer,
+    total: usize,
+    done: usize,
+    lost: usize,
+    answered: usize,
+    sum_ns: u128,
+    last_ns: Option<u64>,
+    /// Probe number and RTT of the most recent answered probes
+    recent: VecDeque<(usize, u64)>,
+    start: Instant,
+}
+
+impl Panes {
+    fn record(&mut self, rtt_ns: Option<u64>, split: impl FnOnce() -> Option<Decomposition>) {
+        self.done += 1;
+        match rtt_ns {
+            Some(rtt_ns) => {
+                self.answered += 1;
+                self.sum_ns += rtt_ns as u128;
+                self.last_ns = Some(rtt_ns);
+                if self.recent.len() == LIVE_P99_WINDOW_SIZE {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back((self.done, rtt_ns));
+            }
+            None => self.lost += 1,
+        }
+        if self.visualizer.should_update(self.done - 1) {
+            self.visualizer.update(split());
+        }
+    }
+
+    /// Rolling percentile `p` (0.0 to 1.0) of the recent probes, in nanoseconds
+    fn rolling_percentile(&self, p: f64) -> Option<u64> {
+        let mut sorted: Vec<u64> = self.recent.iter().map(|&(_, rtt_ns)| rtt_ns).collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * p) as usize).min(sorted.len().checked_sub(1)?);
+        Some(sorted[index])
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let show_osi = area.height >= OSI_PANE_MIN_HEIGHT;
+        let [progress, middle, osi] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(if show_osi { 9 } else { 0 }),
+        ])
+        .areas(area);
+        let [stats, chart] = if area.width >= SIDE_BY_SIDE_WIDTH {
+            Layout::horizontal([Constraint::Length(30), Constraint::Min(20)]).areas(middle)
+        } else {
+            Layout::vertical([Constraint::Length(8), Constraint::Min(5)]).areas(middle)
+        };
+
+        self.render_progress(frame, progress);
+        self.render_stats(frame, stats);
+        self.render_chart(frame, chart);
+        if show_osi {
+            self.render_osi(frame, osi);
+        }
+    }
+
+    fn render_progress(&self, frame: &mut Frame, area: Rect) {
+        let elapsed = self.start.elapsed().as_secs();
+        let label = format!(
+            "{}/{}  [{:02}:{:02}:{:02}]",
+            self.done,
+            self.total,
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60
+        );
+        let ratio = if self.total == 0 {
+            1.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(" Synapse "))
+            .gauge_style(accent(self.theme))
+            .ratio(ratio)
+            .label(label);
+        frame.render_widget(gauge, area);
+    }
+
+    fn render_stats(&self, frame: &mut Frame, area: Rect) {
+        let ms = |ns: u64| format!("{:.3} ms", ns as f64 / 1_000_000.0);
+        let rolling = |p: f64| self.rolling_percentile(p).map_or("-".into(), ms);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            self.done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let loss_pct = if self.done == 0 {
+            0.0
+        } else {
+            self.lost as f64 / self.done as f64 * 100.0
+        };
+        let loss_style = if self.lost > 0 {
+            bad(self.theme)
+        } else {
+            good(self.theme)
+        };
+
+        let figure = |label: &str, value: String, style: Style| {
+            Line::from(vec![
+                Span::raw(format!("{:<7}", label)),
+                Span::styled(value, style),
+            ])
+        };
+        let lines = vec![
+            figure(
+                "Last",
+                self.last_ns.map_or("-".into(), ms),
+                self.last_ns
+                    .map_or(Style::default(), |ns| latency_style(self.theme, ns as f64)),
+            ),
+            figure(
+                "Mean",
+                match self.answered {
+                    0 => "-".into(),
+                    n => ms((self.sum_ns / n as u128) as u64),
+                },
+                Style::default(),
+            ),
+            figure("P50", rolling(0.50), Style::default()),
+            figure("P90", rolling(0.90), Style::default()),
+            figure("P99", rolling(0.99), Style::default()),
+            figure(
+                "Rate",
+                format!("{:.1}k pkt/s", rate / 1000.0),
+                Style::default(),
+            ),
+            figure(
+                "Loss",
+                format!("{} ({:.2}%)", self.lost, loss_pct),
+                loss_style,
+            ),
+        ];
+        let title = format!(" Statistics (last {}) ", self.recent.len());
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn render_chart(&self, frame: &mut Frame, area: Rect) {
+        let points: Vec<(f64, f64)> = self
+            .recent
+            .iter()
+            .map(|&(probe, rtt_ns)| (probe as f64, rtt_ns as f64 / 1000.0))
+            .collect();
+        let first = points.first().map_or(0.0, |&(probe, _)| probe);
+        let last = points
+            .last()
+            .map_or(1.0, |&(probe, _)| probe)
+            .max(first + 1.0);
+        let top = points.iter().map(|&(_, rtt_us)| rtt_us).fold(1.0, f64::max) * 1.1;
+
+        let dataset = Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(accent(self.theme))
+            .data(&points);
+        let chart = Chart::new(vec![dataset])
+            .block(Block::bordered().title(" Latency (µs) "))
+            .x_axis(
+                Axis::default()
+                    .bounds([first, last])
+                    .labels([format!("{:.0}", first), format!("{:.0}", last)]),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, top])
+                    .labels(["0".to_string(), format!("{:.0}", top)]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn render_osi(&self, frame: &mut Frame, area: Rect) {
+        let colors = self.theme.layer_colors();
+        let layer = |host: Host, index: usize| {
+            let (label, detail) = OSI_LAYERS[index];
+            let text = format!(" {:<20} ", format!("{}: {}", label, detail));
+            let active = self.visualizer.is_active(host, index);
+            let style = match colors.map(|colors| colors[index]) {
+                Some((r, g, b)) if active => Style::default()
+                    .bg(Color::Rgb(r, g, b))
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                // Inactive layers dimmed to ~30% of their color, as in the default view
+                Some((r, g, b)) => Style::default()
+                    .bg(Color::Rgb(
+                        (r as f32 * 0.3) as u8,
+                        (g as f32 * 0.3) as u8,
+                        (b as f32 * 0.3) as u8,
+                    ))
+                    .fg(Color::Rgb(100, 100, 100)),
+                None if active => {
+                    Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+                }
+                None => Style::default(),
+            };
+            Span::styled(text, style)
+        };
+
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let mut lines = vec![Line::from(vec![
+            Span::styled(format!("{:<24}", "  CLIENT"), bold),
+            Span::styled("  SERVER", bold),
+        ])];
+        lines.extend((0..OSI_LAYERS.len()).map(|index| {
+            Line::from(vec![
+                layer(Host::Client, index),
+                Span::raw("  "),
+                layer(Host::Server, index),
+            ])
+        }));
+        lines.push(Line::from(format!(" {}", self.visualizer.summary())));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" OSI ")),
+            area,
+        );
+    }
+}
+
+/// Full-screen dashboard of a measurement phase
+pub struct Dashboard<B: Backend> {
+    terminal: Terminal<B>,
+    panes: Panes,
+    last_draw: Option<Instant>,
+    /// Restores the terminal when the dashboard is dropped, if it took it over
+    screen: Option<AlternateScreen>,
+}
+
+impl Dashboard<CrosstermBackend<Stdout>> {
+    /// Take over the terminal for a phase of `total` probes
+    pub fn open(total: usize, theme: Theme) -> Result<Self> {
+        let screen = AlternateScreen::enter()?;
+        let mut dashboard = Self::new(CrosstermBackend::new(io::stdout()), total, theme)?;
+        dashboard.terminal.clear()?;
+        dashboard.screen = Some(screen);
+        debug!(total = total, "Opened dashboard");
+        Ok(dashboard)
+    }
+}
+
+impl<B: Backend> Dashboard<B> {
+    /// Draw the dashboard of a phase of `total` probes on `backend`
+    pub fn new(backend: B, total: usize, theme: Theme) -> Result<Self> {
+        Ok(Self {
+            terminal: Terminal::new(backend)?,
+            panes: Panes {
+                theme,
+                visualizer: OsiVisualizer::new().with_theme(theme),
+                total,
+                done: 0,
+                lost: 0,
+                answered: 0,
+                sum_ns: 0,
+                last_ns: None,
+                recent: VecDeque::with_capacity(LIVE_P99_WINDOW_SIZE),
+                start: Instant::now(),
+            },
+            last_draw: None,
+            screen: None,
+        })
+    }
+
+    /// Count a completed probe, answered after `rtt_ns` or lost, and redraw if a frame
+    /// is due; `split` splits the recent RTTs for the OSI pane, called on sampled
+    /// probes only
+    pub fn record(
+        &mut self,
+        rtt_ns: Option<u64>,
+        split: impl FnOnce() -> Option<Decomposition>,
+    ) -> Result<()> {
+        self.panes.record(rtt_ns, split);
+        let due = self
+            .last_draw
+            .is_none_or(|last| last.elapsed() >= Duration::from_millis(PROGRESS_TICK_INTERVAL_MS));
+        if due {
+            self.draw()?;
+        }
+        Ok(())
+    }
+
+    /// Draw the final frame and give the terminal back
+    pub fn finish(mut self) -> Result<()> {
+        self.draw()?;
+        debug!(done = self.panes.done, "Closed dashboard");
+        Ok(())
+    }
+
+    /// Backend the dashboard draws on
+    pub fn backend(&self) -> &B {
+        self.terminal.backend()
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        self.terminal.draw(|frame| self.panes.render(frame))?;
+        self.last_draw = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// The terminal's alternate screen, left when dropped
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter() -> Result<Self> {
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        if let Err(e) = execute!(io::stdout(), Show, LeaveAlternateScreen) {
+            warn!(error = %e, "Failed to restore the terminal");
+        }
+    }
+}
+
+/// Style of a good result
+fn good(theme: Theme) -> Style {
+    match theme {
+        Theme::Default => Style::default().fg(Color::Green),
+        Theme::Colorblind => Style::default().fg(Color::Rgb(0, 114, 178)),
+        Theme::Mono => Style::default(),
+    }
+}
+
+/// Style of a bad result
+fn bad(theme: Theme) -> Style {
+    match theme {
+        Theme::Default => Style::default().fg(Color::Red),
+        Theme::Colorblind => Style::default().fg(Color::Rgb(213, 94, 0)),
+        Theme::Mono => Style::default().add_modifier(Modifier::BOLD),
+    }
+}
+
+/// Style of a result between good and bad
+fn warning(theme: Theme) -> Style {
+    match theme {
+        Theme::Default => Style::default().fg(Color::Yellow),
+        Theme::Colorblind => Style::default().fg(Color::Rgb(240, 228, 66)),
+        Theme::Mono => Style::default(),
+    }
+}
+
+/// Style of text set apart without judging it
+fn accent(theme: Theme) -> Style {
+    match theme {
+        Theme::Default => Style::default().fg(Color::Cyan),
+        Theme::Colorblind => Style::default().fg(Color::Rgb(86, 180, 233)),
+        Theme::Mono => Style::default(),
+    }
+}
+
+/// Style of an RTT of `ns` nanoseconds, by the thresholds of the default view
+fn latency_style(theme: Theme, ns: f64) -> Style {
+    let ms = ns / 1_000_000.0;
+    if ms < EXCELLENT_LATENCY_MS {
+        good(theme)
+    } else if ms < ACCEPTABLE_LATENCY_MS {
+        warning(theme)
+    } else {
+        bad(theme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::measurement::PathTiming;
+    use ratatui::backend::TestBackend;
+
+    /// Text of the dashboard's last frame, row by row
+    fn screen(dashboard: &Dashboard<TestBackend>) -> String {
+        let buffer = dashboard.backend().buffer();
+        let width = buffer.area.width as usize;
+        buffer
+            .content()
+            .chunks(width)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_dashboard() -> Result<()> {
+        let mut dashboard = Dashboard::new(TestBackend::new(100, 30), 200, Theme::Default)?;
+        // Server timestamps alone: 4 µs of the 45 µs in the server app
+        let path = [PathTiming {
+            outbound_ns: 20_000,
+            server_ns: 5_000,
+            return_ns: 20_000,
+            server_processing_ns: Some(4_000),
+        }];
+        let split = || Decomposition::new(45_000.0, &[3_000], &[], &path);
+        for i in 0..100u64 {
+            dashboard.record((i % 10 != 9).then_some(40_000 + i * 1000), split)?;
+        }
+        dashboard.draw()?;
+        let text = screen(&dashboard);
+        assert!(text.contains("100/200"), "{}", text);
+        assert!(text.contains("Loss   10 (10.00%)"), "{}", text);
+        assert!(text.contains("Latency (µs)"), "{}", text);
+        assert!(text.contains("L7: APPLICATION"), "{}", text);
+        // The 100th probe split the RTT; everything but the server app is one segment
+        assert!(
+            text.contains("network + server kernel 41.0 µs (91%)"),
+            "{}",
+            text
+        );
+        assert!(dashboard.panes.visualizer.is_active(Host::Client, 0));
+        assert!(!dashboard.panes.visualizer.is_active(Host::Server, 0));
+        assert_eq!(dashboard.panes.rolling_percentile(0.5), Some(90_000));
+        dashboard.finish()
+    }
+
+    #[test]
+    fn test_dashboard_narrow() -> Result<()> {
+        // Panes stack, and the OSI pane is left out of a short terminal
+        let mut dashboard = Dashboard::new(TestBackend::new(40, 20), 10, Theme::Mono)?;
+        dashboard.record(Some(2_000_000), || None)?;
+        let text = screen(&dashboard);
+        assert!(text.contains("Last   2.000 ms"), "{}", text);
+        assert!(text.contains("Latency"), "{}", text);
+        assert!(!text.contains("OSI"), "{}", text);
+        assert_eq!(dashboard.panes.rolling_percentile(0.99), Some(2_000_000));
+        Ok(())
+    }
+}
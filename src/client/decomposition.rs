@@ -0,0 +1,234 @@
+//! Where the round trip goes: client app, client kernel, network, server kernel and
+//! server app
+//!
+//! The report puts measured numbers on the journey of a probe down the client's stack,
+//! over the wire and up the server's, and the live view highlights the layers taking
+//! the largest share of the recent probes' RTT, by combining the timestamps a run has:
+//!
+//! - the application RTT, timed by the client around its send and receive calls
+//! - the time inside the send call: the client kernel's send path
+//! - with `--kernel-timestamps`, the kernel RTT between the probe leaving the client's
+//!   stack and the reply entering it; the rest of the application RTT is the client's,
+//!   less the send call its application (its receive path's kernel time, which no
+//!   timestamp separates from the wakeup of the client, included)
+//! - the server's processing time (see [`crate::protocol::Packet::server_processing_ns`]),
+//!   or the time between its receive and transmit stamps from an older server: the
+//!   server app
+//!
+//! Whatever is left is the network and the server kernel, which no timestamp tells
+//! apart. Means are used throughout, as unlike percentiles they add up to the mean RTT.
+//! A split the run has no timestamps for is not guessed: its layers are reported
+//! together.
+
+use crate::client::measurement::{KernelTiming, PathTiming};
+use std::fmt;
+
+/// A layer of the round trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    ClientApp,
+    ClientKernel,
+    Network,
+    ServerKernel,
+    ServerApp,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::ClientApp => write!(f, "client app"),
+            Layer::ClientKernel => write!(f, "client kernel"),
+            Layer::Network => write!(f, "network"),
+            Layer::ServerKernel => write!(f, "server kernel"),
+            Layer::ServerApp => write!(f, "server app"),
+        }
+    }
+}
+
+/// Part of the round trip spent in one layer, or in several no timestamp tells apart
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub layers: Vec<Layer>,
+    /// Mean time per probe, in nanoseconds
+    pub mean_ns: f64,
+}
+
+impl Segment {
+    /// Names of the layers, e.g. `network + server kernel`
+    pub fn label(&self) -> String {
+        let names: Vec<String> = self.layers.iter().map(ToString::to_string).collect();
+        names.join(" + ")
+    }
+}
+
+/// The mean RTT of a run split into the layers it was spent in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decomposition {
+    /// Mean application RTT, in nanoseconds; the segments add up to it
+    pub rtt_ns: f64,
+    /// Segments in the order the probe passes them
+    pub segments: Vec<Segment>,
+}
+
+impl Decomposition {
+    /// Split `rtt_ns`, the mean application RTT, by the timestamps of a run; `None`
+    /// without kernel or server timestamps, which leave nothing to split
+    pub fn new(
+        rtt_ns: f64,
+        send_durations: &[u64],
+        kernel: &[KernelTiming],
+        path: &[PathTiming],
+    ) -> Option<Self> {
+        if kernel.is_empty() && path.is_empty() {
+            return None;
+        }
+        let mean = |values: &mut dyn Iterator<Item = u64>| {
+            let (sum, count) = values.fold((0.0, 0u64), |(sum, count), value| {
+                (sum + value as f64, count + 1)
+            });
+            (count > 0).then(|| sum / count as f64)
+        };
+        // Of an echo without its processing time (an older server), the time between
+        // its stamps
+        let server_app = mean(&mut path.iter().filter_map(|t| t.server_processing_ns))
+            .or_else(|| mean(&mut path.iter().map(|t| t.server_ns)));
+        let client = mean(&mut kernel.iter().map(KernelTiming::application_ns));
+
+        let mut segments = Vec::new();
+        let mut middle = Vec::new();
+        let mut remaining_ns = rtt_ns;
+        match client {
+            Some(client_ns) => {
+                let kernel_ns = mean(&mut send_durations.iter().copied())
+                    .unwrap_or_default()
+                    .min(client_ns);
+                segments.push(Segment {
+                    layers: vec![Layer::ClientApp],
+                    mean_ns: client_ns - kernel_ns,
+                });
+                segments.push(Segment {
+                    layers: vec![Layer::ClientKernel],
+                    mean_ns: kernel_ns,
+                });
+                remaining_ns -= client_ns;
+            }
+            None => middle.extend([Layer::ClientApp, Layer::ClientKernel]),
+        }
+        middle.extend([Layer::Network, Layer::ServerKernel]);
+        let server_app_ns = server_app.map(|ns| ns.min(remaining_ns.max(0.0)));
+        if server_app_ns.is_none() {
+            middle.push(Layer::ServerApp);
+        }
+        segments.push(Segment {
+            layers: middle,
+            mean_ns: (remaining_ns - server_app_ns.unwrap_or_default()).max(0.0),
+        });
+        if let Some(server_app_ns) = server_app_ns {
+            segments.push(Segment {
+                layers: vec![Layer::ServerApp],
+                mean_ns: server_app_ns,
+            });
+        }
+        Some(Self { rtt_ns, segments })
+    }
+
+    /// Split the mean RTT of the last `window` answered probes of a run in progress,
+    /// for the live view; `None` before the first answer, or without timestamps
+    ///
+    /// Lost probes are in the send times but not the others, so the windows only
+    /// roughly line up; near enough for a live figure.
+    pub fn recent(
+        window: usize,
+        latencies: &[u64],
+        send_durations: &[u64],
+        kernel: &[KernelTiming],
+        path: &[PathTiming],
+    ) -> Option<Self> {
+        fn tail<T>(values: &[T], window: usize) -> &[T] {
+            &values[values.len().saturating_sub(window)..]
+        }
+        let latencies = tail(latencies, window);
+        if latencies.is_empty() {
+            return None;
+        }
+        let rtt_ns = latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+        Self::new(
+            rtt_ns,
+            tail(send_durations, window),
+            tail(kernel, window),
+            tail(path, window),
+        )
+    }
+
+    /// Whether the client app and kernel are split out, which takes kernel timestamps
+    pub fn splits_client(&self) -> bool {
+        self.segments
+            .first()
+            .is_some_and(|segment| segment.layers == [Layer::ClientApp])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decomposition() {
+        let kernel = [
+            KernelTiming {
+                rtt_ns: 40_000,
+                kernel_rtt_ns: 30_000,
+            },
+            KernelTiming {
+                rtt_ns: 50_000,
+                kernel_rtt_ns: 38_000,
+            },
+        ];
+        let path = [PathTiming {
+            outbound_ns: 15_000,
+            server_ns: 1_000,
+            return_ns: 15_000,
+            server_processing_ns: Some(4_000),
+        }];
+        let split = Decomposition::new(45_000.0, &[3_000, 5_000], &kernel, &path).unwrap();
+        let segments: Vec<(String, f64)> = split
+            .segments
+            .iter()
+            .map(|segment| (segment.label(), segment.mean_ns))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("client app".to_string(), 7_000.0),
+                ("client kernel".to_string(), 4_000.0),
+                ("network + server kernel".to_string(), 30_000.0),
+                ("server app".to_string(), 4_000.0),
+            ]
+        );
+        assert!(split.splits_client());
+        let total: f64 = split.segments.iter().map(|segment| segment.mean_ns).sum();
+        assert_eq!(total, split.rtt_ns);
+
+        // Server timestamps alone, from an older server that does not report its
+        // processing time
+        let path = [PathTiming {
+            server_processing_ns: None,
+            ..path[0]
+        }];
+        let split = Decomposition::new(45_000.0, &[3_000], &[], &path).unwrap();
+        assert!(!split.splits_client());
+        assert_eq!(
+            split.segments[0].label(),
+            "client app + client kernel + network + server kernel"
+        );
+        assert_eq!(split.segments[0].mean_ns, 44_000.0);
+        assert_eq!(split.segments[1].mean_ns, 1_000.0);
+
+        assert_eq!(Decomposition::new(45_000.0, &[3_000], &[], &[]), None);
+
+        // Only the most recent probes count toward a live split
+        let recent = Decomposition::recent(1, &[90_000, 45_000], &[3_000], &[], &path).unwrap();
+        assert_eq!(recent.rtt_ns, 45_000.0);
+        assert_eq!(Decomposition::recent(1, &[], &[], &[], &path), None);
+    }
+}
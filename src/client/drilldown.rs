@@ -0,0 +1,106 @@
+//! Drill-down into the slowest probes of a run
+//!
+//! A P99.9 says how slow the tail is, not when it happened. The report (and `analyze`,
+//! for checkpoints recorded with `--record-packets`) lists the `--slowest` K probes with
+//! what surrounded each one: its sequence, its wall-clock send time to line it up with
+//! server logs, cron jobs or GC pauses, the gap since the previous probe was sent (a long
+//! gap points at a stalled client or a path that went cold) and how far into the run it
+//! was sent.
+
+use std::time::{Duration, SystemTime};
+
+/// Probes listed unless --slowest says otherwise
+pub const DEFAULT_SLOWEST: usize = 10;
+
+/// One of the slowest probes of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlowProbe {
+    pub sequence: u64,
+    pub latency_ns: u64,
+    /// Wall-clock time the probe was sent
+    pub sent_at: SystemTime,
+    /// Send time since the start of the measurement phase
+    pub offset: Duration,
+    /// Time since the previous probe (sequence - 1) was sent; `None` for the first probe,
+    /// or if the send time of the previous one is unknown
+    pub send_gap: Option<Duration>,
+}
+
+/// The `k` slowest answered probes, slowest first
+///
+/// `probes` holds the `(sequence, send offset, RTT)` of the probes of a phase started at
+/// `started_at`, in any order, the RTT `None` for a lost probe. Lost probes count for
+/// the send gap of the next one only; a run result that keeps answered probes alone
+/// leaves the gap after a lost probe unknown.
+pub fn slowest(
+    probes: impl IntoIterator<Item = (u64, u64, Option<u64>)>,
+    started_at: SystemTime,
+    k: usize,
+) -> Vec<SlowProbe> {
+    let mut probes: Vec<(u64, u64, Option<u64>)> = probes.into_iter().collect();
+    probes.sort_unstable_by_key(|&(sequence, _, _)| sequence);
+    let send_offset = |sequence: u64| {
+        probes
+            .binary_search_by_key(&sequence, |&(sequence, _, _)| sequence)
+            .ok()
+            .map(|index| probes[index].1)
+    };
+
+    let mut answered: Vec<(u64, u64, u64)> = probes
+        .iter()
+        .filter_map(|&(sequence, offset_ns, rtt_ns)| Some((sequence, offset_ns, rtt_ns?)))
+        .collect();
+    // Slowest first; of equal RTTs, the earliest
+    answered.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+    answered
+        .into_iter()
+        .take(k)
+        .map(|(sequence, offset_ns, latency_ns)| SlowProbe {
+            sequence,
+            latency_ns,
+            sent_at: started_at + Duration::from_nanos(offset_ns),
+            offset: Duration::from_nanos(offset_ns),
+            send_gap: sequence
+                .checked_sub(1)
+                .and_then(send_offset)
+                .map(|previous_ns| Duration::from_nanos(offset_ns.saturating_sub(previous_ns))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowest() {
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        // Probe 2 was lost; probe 3 waited 5 ms after it
+        let probes = [
+            (0, 0, Some(40_000)),
+            (1, 100_000, Some(900_000)),
+            (2, 1_100_000, None),
+            (4, 7_000_000, Some(40_000)),
+            (3, 6_100_000, Some(900_000)),
+        ];
+        let slowest = slowest(probes, started_at, 3);
+
+        let sequences: Vec<u64> = slowest.iter().map(|p| p.sequence).collect();
+        assert_eq!(sequences, vec![1, 3, 0]);
+        assert_eq!(slowest[0].send_gap, Some(Duration::from_micros(100)));
+        assert_eq!(slowest[1].send_gap, Some(Duration::from_millis(5)));
+        assert_eq!(slowest[1].offset, Duration::from_micros(6_100));
+        assert_eq!(
+            slowest[1].sent_at,
+            started_at + Duration::from_micros(6_100)
+        );
+        assert_eq!(slowest[2].send_gap, None);
+
+        // Without the lost probe's send time, the gap after it is unknown
+        let answered = probes.into_iter().filter(|p| p.2.is_some());
+        let slowest = super::slowest(answered, started_at, 2);
+        assert_eq!(slowest[1].sequence, 3);
+        assert_eq!(slowest[1].send_gap, None);
+        assert!(super::slowest([], started_at, 10).is_empty());
+    }
+}
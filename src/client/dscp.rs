@@ -0,0 +1,320 @@
+//! DSCP marking of probes and verification on the replies
+//!
+//! Traffic meant for a priority queue carries a DSCP (the upper six bits of the IPv4 TOS
+//! byte or IPv6 traffic class), and a router that rewrites it sends the traffic through
+//! the default queue without any other sign. `--dscp EF` marks every probe; over UDP
+//! the client also reads the marking of every reply (`IP_RECVTOS`/`IPV6_RECVTCLASS`)
+//! and reports how many came back with it.
+//!
+//! The synapse server answers each datagram with the marking it arrived with, so a
+//! reply carrying another DSCP was rewritten on the way out or on the way back; which
+//! leg did it cannot be told from the client. Over TCP probes are marked but replies
+//! cannot be checked, as a stream hides the headers of the segments it is read from.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(target_os = "linux")]
+pub(crate) use imp::tos_of;
+pub use imp::{mark, recv_from, report_markings};
+
+/// Named code points (RFC 2474, 2597, 3246, 5865 and 8622)
+const NAMES: [(&str, u8); 22] = [
+    ("CS0", 0),
+    ("LE", 1),
+    ("CS1", 8),
+    ("AF11", 10),
+    ("AF12", 12),
+    ("AF13", 14),
+    ("CS2", 16),
+    ("AF21", 18),
+    ("AF22", 20),
+    ("AF23", 22),
+    ("CS3", 24),
+    ("AF31", 26),
+    ("AF32", 28),
+    ("AF33", 30),
+    ("CS4", 32),
+    ("AF41", 34),
+    ("AF42", 36),
+    ("AF43", 38),
+    ("CS5", 40),
+    ("VA", 44),
+    ("EF", 46),
+    ("CS6", 48),
+];
+
+/// Differentiated services code point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dscp(u8);
+
+impl Dscp {
+    /// Largest code point (six bits)
+    pub const MAX: u8 = 63;
+
+    /// The code point `value`, if it fits in six bits
+    pub fn new(value: u8) -> Option<Self> {
+        (value <= Self::MAX).then_some(Self(value))
+    }
+
+    /// The code point of a TOS or traffic class byte (the ECN bits are dropped)
+    pub fn from_tos(tos: u8) -> Self {
+        Self(tos >> 2)
+    }
+
+    /// Numeric value
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// TOS or traffic class byte carrying the code point, with ECN bits clear
+    pub fn tos(self) -> u8 {
+        self.0 << 2
+    }
+
+    /// Standard name of the code point, if it has one
+    pub fn name(self) -> Option<&'static str> {
+        NAMES
+            .iter()
+            .find(|(_, value)| *value == self.0)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl fmt::Display for Dscp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} ({})", name, self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl FromStr for Dscp {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(value) = s.parse::<u8>() {
+            return Self::new(value)
+                .ok_or_else(|| format!("DSCP {} is out of range (0-{})", value, Self::MAX));
+        }
+        NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, value)| Self(*value))
+            .ok_or_else(|| {
+                format!(
+                    "invalid DSCP '{}' (expected 0-{} or a name such as EF, AF41 or CS5)",
+                    s,
+                    Self::MAX
+                )
+            })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::Dscp;
+    use std::io;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+    use std::os::fd::AsRawFd;
+
+    /// Mark every packet `socket` sends with `dscp`
+    pub fn mark<S: AsRawFd>(socket: &S, ipv6: bool, dscp: Dscp) -> io::Result<()> {
+        let tos = libc::c_int::from(dscp.tos());
+        if ipv6 {
+            set_option(socket, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos)?;
+            // A dual-stack socket sends to IPv4-mapped peers with the IPv4 option
+            let _ = set_option(socket, libc::IPPROTO_IP, libc::IP_TOS, tos);
+            Ok(())
+        } else {
+            set_option(socket, libc::IPPROTO_IP, libc::IP_TOS, tos)
+        }
+    }
+
+    /// Have every read from `socket` report the TOS or traffic class of the packet
+    pub fn report_markings<S: AsRawFd>(socket: &S, ipv6: bool) -> io::Result<()> {
+        if ipv6 {
+            set_option(socket, libc::IPPROTO_IPV6, libc::IPV6_RECVTCLASS, 1)?;
+            let _ = set_option(socket, libc::IPPROTO_IP, libc::IP_RECVTOS, 1);
+            Ok(())
+        } else {
+            set_option(socket, libc::IPPROTO_IP, libc::IP_RECVTOS, 1)
+        }
+    }
+
+    /// Receive a datagram into `buf`, returning its length, sender and TOS byte (if
+    /// [`report_markings`] was called on `socket`)
+    pub fn recv_from(
+        socket: &UdpSocket,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+        // SAFETY: sockaddr_storage and msghdr are plain data; all-zero is valid for both
+        let mut source: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        // Room for one TOS or traffic class message, as u64 words for alignment
+        let mut control = [0u64; 8];
+        let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+        message.msg_name = (&mut source as *mut libc::sockaddr_storage).cast();
+        message.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as _;
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = std::mem::size_of_val(&control) as _;
+
+        // SAFETY: every buffer referenced by `message` outlives the call
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: recvmsg filled `message`, whose control buffer is still alive
+        let tos = unsafe { tos_of(&message) };
+        Ok((len as usize, socket_addr(&source)?, tos))
+    }
+
+    /// TOS or traffic class carried by the control messages of `message`
+    ///
+    /// # Safety
+    ///
+    /// `message` must have been filled by `recvmsg`, with its control buffer still alive.
+    pub(crate) unsafe fn tos_of(message: &libc::msghdr) -> Option<u8> {
+        let mut tos = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(message);
+        while !cmsg.is_null() {
+            let data = libc::CMSG_DATA(cmsg);
+            match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                // IPv4 delivers the TOS as a single byte, IPv6 the traffic class as an int
+                (libc::IPPROTO_IP, libc::IP_TOS) => tos = Some(*data),
+                (libc::IPPROTO_IPV6, libc::IPV6_TCLASS) => {
+                    tos = Some(std::ptr::read_unaligned(data as *const libc::c_int) as u8)
+                }
+                _ => {}
+            }
+            cmsg = libc::CMSG_NXTHDR(message, cmsg);
+        }
+        tos
+    }
+
+    /// Address in a `sockaddr_storage` filled by the kernel
+    fn socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+        match storage.ss_family as libc::c_int {
+            libc::AF_INET => {
+                // SAFETY: the family says the storage holds a sockaddr_in
+                let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+                Ok(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                    u16::from_be(addr.sin_port),
+                )))
+            }
+            libc::AF_INET6 => {
+                // SAFETY: the family says the storage holds a sockaddr_in6
+                let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+                Ok(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )))
+            }
+            family => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("datagram from an unsupported address family ({})", family),
+            )),
+        }
+    }
+
+    /// Set the integer socket option `name` at `level`
+    fn set_option<S: AsRawFd>(
+        socket: &S,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: `value` is a valid c_int of the given size
+        let rc = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                (&value as *const libc::c_int).cast(),
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::Dscp;
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "DSCP marking requires Linux")
+    }
+
+    pub fn mark<S>(_socket: &S, _ipv6: bool, _dscp: Dscp) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn report_markings<S>(_socket: &S, _ipv6: bool) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn recv_from(
+        socket: &UdpSocket,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Option<u8>)> {
+        socket
+            .recv_from(buf)
+            .map(|(len, source)| (len, source, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dscp_parsing() {
+        assert_eq!("46".parse::<Dscp>().unwrap().value(), 46);
+        assert_eq!("ef".parse::<Dscp>().unwrap().value(), 46);
+        assert_eq!("AF41".parse::<Dscp>().unwrap().value(), 34);
+        assert!("64".parse::<Dscp>().is_err());
+        assert!("AF51".parse::<Dscp>().is_err());
+
+        let ef = Dscp::new(46).unwrap();
+        assert_eq!(ef.tos(), 0xb8);
+        assert_eq!(Dscp::from_tos(0xb9), ef, "ECN bits are ignored");
+        assert_eq!(ef.to_string(), "EF (46)");
+        assert_eq!(Dscp::new(5).unwrap().to_string(), "5");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_marking_reaches_the_receiver() -> std::io::Result<()> {
+        use std::net::UdpSocket;
+
+        let receiver = UdpSocket::bind("127.0.0.1:0")?;
+        report_markings(&receiver, false)?;
+        let sender = UdpSocket::bind("127.0.0.1:0")?;
+        let af41 = Dscp::new(34).unwrap();
+        mark(&sender, false, af41)?;
+        sender.send_to(b"probe", receiver.local_addr()?)?;
+
+        let mut buf = [0u8; 16];
+        let (len, source, tos) = recv_from(&receiver, &mut buf)?;
+        assert_eq!(&buf[..len], b"probe");
+        assert_eq!(source, sender.local_addr()?);
+        assert_eq!(tos.map(Dscp::from_tos), Some(af41));
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Every address of a round-robin DNS name
+//!
+//! A name with several A or AAAA records usually fronts several servers, and a client
+//! measures whichever address its connect picked: the first of the answer for UDP, the
+//! first that accepts for TCP. One slow backend behind the name then goes unnoticed, or
+//! shows up in one run and not the next. `--each-address` resolves the --server host
+//! once, runs the configured measurement against every address it resolved to, one
+//! after the other, and compares them.
+//!
+//! An address that cannot be reached is reported as failed and the others are still
+//! measured.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::output::RunSummary;
+use crate::client::port_sweep::{self, Deviation};
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// Outcome of the measurement of one resolved address
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointResult {
+    /// Address measured
+    pub address: SocketAddr,
+    /// Summary of the run, or the error that stopped it
+    pub outcome: std::result::Result<RunSummary, String>,
+}
+
+/// Every distinct address `server` (`HOST:PORT`) resolves to, in resolver order
+pub fn resolve_all(server: &str) -> Result<Vec<SocketAddr>> {
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    let resolved = server
+        .to_socket_addrs()
+        .map_err(|e| ClientError::Socket(format!("Failed to resolve {}: {}", server, e)))?;
+    for addr in resolved {
+        // Resolvers repeat an address once per socket type
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    if addrs.is_empty() {
+        return Err(ClientError::Socket(format!(
+            "{} resolved to no address",
+            server
+        )));
+    }
+    Ok(addrs)
+}
+
+/// Ways `result` stands out from the best addresses of `results`, by the thresholds of
+/// a port sweep
+///
+/// Failed addresses have no figures to compare and are not listed.
+pub fn deviations(result: &EndpointResult, results: &[EndpointResult]) -> Vec<Deviation> {
+    let Ok(summary) = &result.outcome else {
+        return Vec::new();
+    };
+    let measured: Vec<&RunSummary> = results
+        .iter()
+        .filter_map(|r| r.outcome.as_ref().ok())
+        .collect();
+    port_sweep::deviations_from_best(summary, &measured)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::verdict::Verdict;
+
+    #[test]
+    fn test_resolve_all() -> Result<()> {
+        assert_eq!(
+            resolve_all("127.0.0.1:8080")?,
+            vec!["127.0.0.1:8080".parse::<SocketAddr>().unwrap()]
+        );
+        assert!(resolve_all("127.0.0.1").is_err(), "no port");
+        Ok(())
+    }
+
+    #[test]
+    fn test_deviations() {
+        let summary = |p99_ns| RunSummary {
+            total_packets: 1000,
+            lost_packets: 0,
+            received: 1000,
+            mean_ns: 40_000.0,
+            p50_ns: 40_000,
+            p99_ns,
+            p999_ns: p99_ns,
+            max_ns: p99_ns,
+            clamped_count: 0,
+            send_stalls: 0,
+            stages: Vec::new(),
+            caveats: Vec::new(),
+            energy_joules: None,
+            verdict: Verdict::default(),
+        };
+        let results = vec![
+            EndpointResult {
+                address: "192.0.2.1:8080".parse().unwrap(),
+                outcome: Ok(summary(100_000)),
+            },
+            EndpointResult {
+                address: "192.0.2.2:8080".parse().unwrap(),
+                outcome: Ok(summary(400_000)),
+            },
+            EndpointResult {
+                address: "192.0.2.3:8080".parse().unwrap(),
+                outcome: Err("Connection refused".into()),
+            },
+        ];
+        assert!(deviations(&results[0], &results).is_empty());
+        assert_eq!(
+            deviations(&results[1], &results),
+            vec![Deviation::Latency(4.0)]
+        );
+        assert!(deviations(&results[2], &results).is_empty());
+    }
+}
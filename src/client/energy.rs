@@ -0,0 +1,288 @@
+//! Energy used by a run, from the RAPL counters
+//!
+//! Labs that probe continuously have to account for what it costs. With `--energy`,
+//! [`EnergyMeter`] samples the RAPL (Running Average Power Limit) energy counters the
+//! kernel exposes under `/sys/class/powercap` from a background thread for the length of
+//! the run, and the report gives the energy used, the mean power and, with
+//! `--carbon-intensity`, the emissions it stands for.
+//!
+//! The counters cover the whole CPU package (and its DRAM, where the platform reports
+//! it), not the client alone: the figure is what the host drew while the test ran. The
+//! counters wrap at `max_energy_range_uj`; sampling every [`ENERGY_SAMPLE_INTERVAL_MS`]
+//! keeps up with them. Since Linux 5.10 only root may read them. Intel and AMD CPUs on
+//! Linux only.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Interval between two reads of the energy counters
+pub const ENERGY_SAMPLE_INTERVAL_MS: u64 = 250;
+
+/// Where the kernel exposes the RAPL zones
+const POWERCAP_DIR: &str = "/sys/class/powercap";
+
+/// One RAPL zone: a CPU package, or the DRAM of one
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RaplZone {
+    /// Name the kernel gives the zone, e.g. `package-0` or `dram`
+    name: String,
+    dir: PathBuf,
+    /// Value past which the counter wraps to 0, in microjoules
+    max_range_uj: u64,
+}
+
+impl RaplZone {
+    fn read_uj(&self) -> Option<u64> {
+        fs::read_to_string(self.dir.join("energy_uj"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+/// Energy one RAPL zone used over a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneEnergy {
+    /// Name of the zone, e.g. `package-0` or `package-0/dram`
+    pub zone: String,
+    pub joules: f64,
+}
+
+/// Energy used over a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyReport {
+    /// Energy per zone, packages first
+    pub zones: Vec<ZoneEnergy>,
+    /// Time the counters were sampled over
+    pub elapsed: Duration,
+}
+
+impl EnergyReport {
+    /// Energy of all zones, in joules
+    pub fn joules(&self) -> f64 {
+        self.zones.iter().map(|zone| zone.joules).sum()
+    }
+
+    /// Energy of all zones, in watt-hours
+    pub fn watt_hours(&self) -> f64 {
+        self.joules() / 3600.0
+    }
+
+    /// Mean power drawn over the run, in watts
+    pub fn mean_watts(&self) -> f64 {
+        self.joules() / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Emissions of the energy at `grams_per_kwh` of CO2 equivalent, in grams
+    pub fn carbon_grams(&self, grams_per_kwh: f64) -> f64 {
+        self.watt_hours() / 1000.0 * grams_per_kwh
+    }
+}
+
+/// Background sampler of the RAPL energy counters
+pub struct EnergyMeter {
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<EnergyReport>>,
+}
+
+impl EnergyMeter {
+    /// Start sampling the counters of every CPU package and its DRAM
+    ///
+    /// Returns `None` (with a warning) where there is no readable RAPL zone, so the run
+    /// continues without an energy figure.
+    pub fn start() -> Option<Self> {
+        let zones = rapl_zones(Path::new(POWERCAP_DIR));
+        if zones.is_empty() {
+            warn!("Energy metering unavailable (requires Linux RAPL in /sys/class/powercap)");
+            return None;
+        }
+        let Some(mut previous) = zones
+            .iter()
+            .map(RaplZone::read_uj)
+            .collect::<Option<Vec<u64>>>()
+        else {
+            warn!("Energy metering unavailable (the RAPL counters are readable by root only)");
+            return None;
+        };
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = Arc::clone(&stopped);
+        let interval = Duration::from_millis(ENERGY_SAMPLE_INTERVAL_MS);
+        debug!(zones = zones.len(), "Energy meter started");
+
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let mut used_uj = vec![0u64; zones.len()];
+            loop {
+                let stopping = thread_stopped.load(Ordering::Relaxed);
+                for (i, zone) in zones.iter().enumerate() {
+                    if let Some(now) = zone.read_uj() {
+                        used_uj[i] += counter_delta(previous[i], now, zone.max_range_uj);
+                        previous[i] = now;
+                    }
+                }
+                if stopping {
+                    break;
+                }
+                thread::sleep(interval);
+            }
+            EnergyReport {
+                zones: zones
+                    .iter()
+                    .zip(used_uj)
+                    .map(|(zone, uj)| ZoneEnergy {
+                        zone: zone.name.clone(),
+                        joules: uj as f64 / 1e6,
+                    })
+                    .collect(),
+                elapsed: started.elapsed(),
+            }
+        });
+        Some(Self {
+            stopped,
+            handle: Some(handle),
+        })
+    }
+
+    /// Stop sampling and return the energy used since the start
+    pub fn finish(mut self) -> Option<EnergyReport> {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.handle.take().and_then(|handle| handle.join().ok())
+    }
+}
+
+impl Drop for EnergyMeter {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Increase of a counter from `before` to `now` that wraps past `max_range`
+fn counter_delta(before: u64, now: u64, max_range: u64) -> u64 {
+    if now >= before {
+        now - before
+    } else {
+        max_range.saturating_sub(before) + now
+    }
+}
+
+/// Package zones under `powercap` (`intel-rapl:N`) and their DRAM subzones
+/// (`intel-rapl:N:M` named `dram`), in the order of their paths
+///
+/// Cores and uncore subzones are left out, as the package already counts them, and so is
+/// the `psys` platform zone, which counts the packages again.
+fn rapl_zones(powercap: &Path) -> Vec<RaplZone> {
+    let Ok(entries) = fs::read_dir(powercap) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("intel-rapl:"))
+        })
+        .collect();
+    dirs.sort();
+
+    let zone = |dir: &Path| {
+        let read = |file: &str| fs::read_to_string(dir.join(file)).ok();
+        Some(RaplZone {
+            name: read("name")?.trim().to_string(),
+            dir: dir.to_path_buf(),
+            max_range_uj: read("max_energy_range_uj")?.trim().parse().ok()?,
+        })
+    };
+    let mut zones = Vec::new();
+    for dir in dirs {
+        let Some(mut found) = zone(&dir) else {
+            continue;
+        };
+        let id = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match id.rsplit_once(':') {
+            // A package: intel-rapl:N (not psys)
+            Some((prefix, _)) if !prefix.contains(':') && found.name.starts_with("package") => {
+                zones.push(found);
+            }
+            // A subzone of one: intel-rapl:N:M
+            Some((package, _)) if found.name == "dram" => {
+                let Some(package) = zone(&powercap.join(package)) else {
+                    continue;
+                };
+                found.name = format!("{}/dram", package.name);
+                zones.push(found);
+            }
+            _ => {}
+        }
+    }
+    zones
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(1_000, 4_000, 10_000), 3_000);
+        // Wrapped past the range
+        assert_eq!(counter_delta(9_000, 500, 10_000), 1_500);
+        assert_eq!(counter_delta(7, 7, 10_000), 0);
+    }
+
+    #[test]
+    fn test_rapl_zones() -> std::io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("synapse-{}-powercap", std::process::id()));
+        let zone = |path: &str, name: &str| -> std::io::Result<()> {
+            let zone = dir.join(path);
+            fs::create_dir_all(&zone)?;
+            fs::write(zone.join("name"), format!("{}\n", name))?;
+            fs::write(zone.join("max_energy_range_uj"), "262143328850\n")?;
+            fs::write(zone.join("energy_uj"), "1000000\n")
+        };
+        zone("intel-rapl:0", "package-0")?;
+        zone("intel-rapl:0:0", "core")?;
+        zone("intel-rapl:0:1", "dram")?;
+        zone("intel-rapl:1", "psys")?;
+        zone("intel-rapl-mmio:0", "package-0")?;
+
+        let zones = rapl_zones(&dir);
+        let names: Vec<&str> = zones.iter().map(|zone| zone.name.as_str()).collect();
+        assert_eq!(names, ["package-0", "package-0/dram"]);
+        assert_eq!(zones[0].max_range_uj, 262_143_328_850);
+        assert_eq!(zones[1].read_uj(), Some(1_000_000));
+        fs::remove_dir_all(&dir)?;
+        assert!(rapl_zones(&dir).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_energy_report() {
+        let report = EnergyReport {
+            zones: vec![
+                ZoneEnergy {
+                    zone: "package-0".into(),
+                    joules: 300.0,
+                },
+                ZoneEnergy {
+                    zone: "package-0/dram".into(),
+                    joules: 60.0,
+                },
+            ],
+            elapsed: Duration::from_secs(30),
+        };
+        assert_eq!(report.joules(), 360.0);
+        assert_eq!(report.watt_hours(), 0.1);
+        assert_eq!(report.mean_watts(), 12.0);
+        // 0.1 Wh at 400 g/kWh
+        assert!((report.carbon_grams(400.0) - 0.04).abs() < 1e-12);
+    }
+}
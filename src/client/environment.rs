@@ -0,0 +1,434 @@
+//! Virtual machine and container detection
+//!
+//! Latency measured inside a VM or a container is often read as if it were the
+//! network's. A hypervisor deschedules vCPUs and puts a virtual NIC between the client
+//! and the wire, a container usually reaches the network through a veth pair and a
+//! bridge, and a cgroup CPU quota throttles the client for the rest of a scheduling
+//! period once it has used its share, which shows up as a tail of the size of the period.
+//! [`Environment`] detects all three so the report can say so:
+//!
+//! - the hypervisor from the CPUID hypervisor bit and vendor leaf (x86_64 only)
+//! - the container runtime from `/.dockerenv`, `/run/.containerenv`, the Kubernetes
+//!   environment and `/proc/self/cgroup`
+//! - the CPU quota from `cpu.max` of the client's cgroup and its ancestors (cgroup v2)
+//!   or `cpu.cfs_quota_us` (cgroup v1)
+//!
+//! [`CpuThrottling`] snapshots the `cpu.stat` of the cgroup holding the quota, so the
+//! report can say how often the run was actually throttled. Containers, quotas and
+//! throttling are detected on Linux only.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// Where the cgroup hierarchy is mounted
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Bandwidth a cgroup may use of the CPUs: `quota` of CPU time every `period`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuQuota {
+    pub quota: Duration,
+    pub period: Duration,
+}
+
+impl CpuQuota {
+    /// Quota in CPU cores
+    pub fn cores(&self) -> f64 {
+        self.quota.as_secs_f64() / self.period.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// What the client runs on, as far as it affects the latency it measures
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Environment {
+    /// Hypervisor of the virtual machine, if the client runs in one
+    pub hypervisor: Option<String>,
+    /// Container runtime, if the client runs in a container
+    pub container: Option<String>,
+    /// Tightest CPU quota of the client's cgroups, if any is set
+    pub cpu_quota: Option<CpuQuota>,
+}
+
+/// A property of the environment that skews what the run measures
+#[derive(Debug, Clone, PartialEq)]
+pub enum Caveat {
+    /// The client runs in a virtual machine
+    VirtualMachine {
+        /// Name of the hypervisor
+        hypervisor: String,
+    },
+    /// The client runs in a container
+    Container {
+        /// Name of the runtime
+        runtime: String,
+    },
+    /// The client's cgroup has no more CPU than its measuring threads need
+    CpuQuota { quota: CpuQuota },
+}
+
+impl fmt::Display for Caveat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VirtualMachine { hypervisor } => write!(
+                f,
+                "Running in a virtual machine ({}): vCPU scheduling and the virtual NIC add \
+                 latency and jitter that a bare-metal host does not see",
+                hypervisor
+            ),
+            Self::Container { runtime } => write!(
+                f,
+                "Running in a container ({}): probes usually cross a veth pair and a bridge or \
+                 NAT, which the host's own traffic does not",
+                runtime
+            ),
+            Self::CpuQuota { quota } => write!(
+                f,
+                "CPU quota {:.2} cores ({} ms every {} ms): the client is throttled for the rest \
+                 of a period once it uses its share; expect scheduling-induced tail latency",
+                quota.cores(),
+                quota.quota.as_millis(),
+                quota.period.as_millis()
+            ),
+        }
+    }
+}
+
+/// CFS bandwidth throttling of the cgroup with the client's CPU quota, or the change
+/// of it between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuThrottling {
+    /// Quota the cgroup is throttled to
+    pub quota: CpuQuota,
+    /// Enforcement periods in which the cgroup ran
+    pub periods: u64,
+    /// Periods in which it used up its quota and was throttled
+    pub throttled_periods: u64,
+    /// Time its tasks spent throttled
+    pub throttled: Duration,
+}
+
+impl CpuThrottling {
+    /// Snapshot the counters of the cgroup with the tightest CPU quota over the client;
+    /// `None` without a quota, which nothing is throttled to
+    pub fn current() -> Option<Self> {
+        let (dir, quota) = quota_cgroup()?;
+        parse_cpu_stat(&fs::read_to_string(dir.join("cpu.stat")).ok()?, quota)
+    }
+
+    /// Throttling between `earlier` and this snapshot
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            quota: self.quota,
+            periods: self.periods.saturating_sub(earlier.periods),
+            throttled_periods: self
+                .throttled_periods
+                .saturating_sub(earlier.throttled_periods),
+            throttled: self.throttled.saturating_sub(earlier.throttled),
+        }
+    }
+
+    /// Share of the periods that were throttled
+    pub fn throttled_fraction(&self) -> f64 {
+        if self.periods == 0 {
+            return 0.0;
+        }
+        self.throttled_periods as f64 / self.periods as f64
+    }
+}
+
+impl Environment {
+    /// Detect the hypervisor, container runtime and CPU quota the client runs under
+    pub fn detect() -> Self {
+        let environment = Self {
+            hypervisor: hypervisor(),
+            container: container_runtime(),
+            cpu_quota: cpu_quota(),
+        };
+        debug!(environment = ?environment, "Environment detected");
+        environment
+    }
+
+    /// Properties of the environment that skew a run measuring from `threads` threads
+    ///
+    /// A CPU quota only counts if it leaves no more than a core per measuring thread; a
+    /// wider one is rarely used up by a client that mostly waits for replies.
+    pub fn caveats(&self, threads: usize) -> Vec<Caveat> {
+        let mut caveats = Vec::new();
+        if let Some(hypervisor) = &self.hypervisor {
+            caveats.push(Caveat::VirtualMachine {
+                hypervisor: hypervisor.clone(),
+            });
+        }
+        if let Some(runtime) = &self.container {
+            caveats.push(Caveat::Container {
+                runtime: runtime.clone(),
+            });
+        }
+        if let Some(quota) = self.cpu_quota {
+            if quota.cores() <= threads.max(1) as f64 {
+                caveats.push(Caveat::CpuQuota { quota });
+            }
+        }
+        caveats
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn hypervisor() -> Option<String> {
+    use std::arch::x86_64::__cpuid;
+    // Leaf 1, ECX bit 31 is set by every hypervisor; leaf 0x40000000 holds its vendor
+    // signature in EBX, ECX and EDX
+    #[allow(unused_unsafe)]
+    // SAFETY: cpuid is available on every x86_64 CPU
+    let (present, vendor) = unsafe {
+        let present = __cpuid(1).ecx & (1 << 31) != 0;
+        let leaf = __cpuid(0x4000_0000);
+        (present, [leaf.ebx, leaf.ecx, leaf.edx])
+    };
+    if !present {
+        return None;
+    }
+    let signature: Vec<u8> = vendor.iter().flat_map(|reg| reg.to_le_bytes()).collect();
+    Some(hypervisor_name(&String::from_utf8_lossy(&signature)))
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn hypervisor() -> Option<String> {
+    None
+}
+
+/// Name of the hypervisor with CPUID vendor `signature`
+fn hypervisor_name(signature: &str) -> String {
+    let signature = signature.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    let name = match signature {
+        "KVMKVMKVM" => "KVM",
+        "Microsoft Hv" => "Hyper-V",
+        "VMwareVMware" => "VMware",
+        "XenVMMXenVMM" => "Xen",
+        "VBoxVBoxVBox" => "VirtualBox",
+        "TCGTCGTCGTCG" => "QEMU",
+        "ACRNACRNACRN" => "ACRN",
+        "bhyve bhyve" => "bhyve",
+        "lrpepyh  vr" | "prl hyperv" => "Parallels",
+        "QNXQVMBSQG" => "QNX",
+        "" => "unknown hypervisor",
+        other => return other.to_string(),
+    };
+    name.to_string()
+}
+
+fn container_runtime() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".into());
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("podman".into());
+    }
+    if std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("kubernetes".into());
+    }
+    if let Some(runtime) = std::env::var("container").ok().filter(|r| !r.is_empty()) {
+        return Some(runtime);
+    }
+    let cgroup = fs::read_to_string("/proc/self/cgroup").ok()?;
+    runtime_from_cgroup(&cgroup).map(str::to_string)
+}
+
+/// Container runtime named by the cgroup paths of `/proc/self/cgroup`
+fn runtime_from_cgroup(cgroup: &str) -> Option<&'static str> {
+    let runtimes = [
+        ("kubepods", "kubernetes"),
+        ("docker", "docker"),
+        ("libpod", "podman"),
+        ("containerd", "containerd"),
+        ("lxc", "lxc"),
+    ];
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(|path| {
+            runtimes
+                .iter()
+                .find(|(marker, _)| path.contains(marker))
+                .map(|&(_, runtime)| runtime)
+        })
+}
+
+fn cpu_quota() -> Option<CpuQuota> {
+    quota_cgroup().map(|(_, quota)| quota)
+}
+
+/// Directory of the client's cgroup, or of the ancestor, with the tightest CPU quota,
+/// and that quota
+fn quota_cgroup() -> Option<(PathBuf, CpuQuota)> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let cgroup = fs::read_to_string("/proc/self/cgroup").ok()?;
+    match unified_cgroup(&cgroup) {
+        Some(path) => {
+            // A quota of any ancestor bounds the cgroup as well; take the tightest
+            let dir = Path::new(CGROUP_ROOT).join(path.trim_start_matches('/'));
+            dir.ancestors()
+                .take_while(|dir| dir.starts_with(CGROUP_ROOT))
+                .filter_map(|dir| {
+                    let max = fs::read_to_string(dir.join("cpu.max")).ok()?;
+                    Some((dir.to_path_buf(), parse_cpu_max(&max)?))
+                })
+                .min_by(|(_, a), (_, b)| a.cores().total_cmp(&b.cores()))
+        }
+        None => {
+            let dir = [
+                PathBuf::from(CGROUP_ROOT).join("cpu,cpuacct"),
+                PathBuf::from(CGROUP_ROOT).join("cpu"),
+            ]
+            .into_iter()
+            .find(|dir| dir.join("cpu.cfs_quota_us").exists())?;
+            let quota = parse_cfs_quota(
+                &fs::read_to_string(dir.join("cpu.cfs_quota_us")).ok()?,
+                &fs::read_to_string(dir.join("cpu.cfs_period_us")).ok()?,
+            )?;
+            Some((dir, quota))
+        }
+    }
+}
+
+/// Parse the throttling counters of a `cpu.stat`: `throttled_usec` on cgroup v2,
+/// `throttled_time` in nanoseconds on v1
+fn parse_cpu_stat(stat: &str, quota: CpuQuota) -> Option<CpuThrottling> {
+    let field = |name: &str| {
+        stat.lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+    };
+    let throttled = field("throttled_usec")
+        .map(Duration::from_micros)
+        .or_else(|| field("throttled_time").map(Duration::from_nanos))?;
+    Some(CpuThrottling {
+        quota,
+        periods: field("nr_periods")?,
+        throttled_periods: field("nr_throttled")?,
+        throttled,
+    })
+}
+
+/// Path of the cgroup v2 group in `/proc/self/cgroup`, if the hierarchy is unified
+fn unified_cgroup(cgroup: &str) -> Option<&str> {
+    let mut lines = cgroup.lines();
+    let path = lines.next()?.strip_prefix("0::")?;
+    lines.next().is_none().then_some(path)
+}
+
+/// Parse a cgroup v2 `cpu.max`, such as `50000 100000` or `max 100000` for no quota
+fn parse_cpu_max(max: &str) -> Option<CpuQuota> {
+    let mut fields = max.split_whitespace();
+    let quota: u64 = fields.next()?.parse().ok()?;
+    let period: u64 = fields.next().map_or(Some(100_000), |p| p.parse().ok())?;
+    Some(CpuQuota {
+        quota: Duration::from_micros(quota),
+        period: Duration::from_micros(period),
+    })
+}
+
+/// Parse the cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us`; a quota of -1 is none
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<CpuQuota> {
+    let quota: u64 = quota.trim().parse().ok()?;
+    let period: u64 = period.trim().parse().ok()?;
+    Some(CpuQuota {
+        quota: Duration::from_micros(quota),
+        period: Duration::from_micros(period),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hypervisor_name() {
+        assert_eq!(hypervisor_name("KVMKVMKVM\0\0\0"), "KVM");
+        assert_eq!(hypervisor_name("Microsoft Hv"), "Hyper-V");
+        assert_eq!(hypervisor_name("\0\0\0\0"), "unknown hypervisor");
+        assert_eq!(hypervisor_name("NewVisor"), "NewVisor");
+    }
+
+    #[test]
+    fn test_runtime_from_cgroup() {
+        let docker = "12:cpu,cpuacct:/docker/3f2a9c\n11:memory:/docker/3f2a9c\n";
+        assert_eq!(runtime_from_cgroup(docker), Some("docker"));
+        let kubernetes = "0::/kubepods.slice/kubepods-burstable.slice/cri-containerd-1a2b.scope\n";
+        assert_eq!(runtime_from_cgroup(kubernetes), Some("kubernetes"));
+        assert_eq!(
+            runtime_from_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_cpu_quota() {
+        let half = CpuQuota {
+            quota: Duration::from_millis(50),
+            period: Duration::from_millis(100),
+        };
+        assert_eq!(parse_cpu_max("50000 100000\n"), Some(half));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cfs_quota("50000\n", "100000\n"), Some(half));
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(half.cores(), 0.5);
+
+        assert_eq!(
+            unified_cgroup("0::/system.slice/app.service\n"),
+            Some("/system.slice/app.service")
+        );
+        assert_eq!(unified_cgroup("12:cpu,cpuacct:/\n0::/\n"), None);
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let quota = CpuQuota {
+            quota: Duration::from_millis(50),
+            period: Duration::from_millis(100),
+        };
+        let v2 = "usage_usec 8000000\nuser_usec 6000000\nsystem_usec 2000000\n\
+                  nr_periods 400\nnr_throttled 40\nthrottled_usec 1200000\n";
+        let earlier = parse_cpu_stat(v2, quota).unwrap();
+        assert_eq!(earlier.throttled, Duration::from_millis(1200));
+        let v2 = "nr_periods 500\nnr_throttled 65\nthrottled_usec 2700000\n";
+        let run = parse_cpu_stat(v2, quota).unwrap().since(&earlier);
+        assert_eq!((run.periods, run.throttled_periods), (100, 25));
+        assert_eq!(run.throttled, Duration::from_millis(1500));
+        assert_eq!(run.throttled_fraction(), 0.25);
+
+        let v1 = "nr_periods 10\nnr_throttled 1\nthrottled_time 30000000\n";
+        let v1 = parse_cpu_stat(v1, quota).unwrap();
+        assert_eq!(v1.throttled, Duration::from_millis(30));
+        assert_eq!(parse_cpu_stat("usage_usec 100\n", quota), None);
+    }
+
+    #[test]
+    fn test_caveats() {
+        let quota = CpuQuota {
+            quota: Duration::from_millis(150),
+            period: Duration::from_millis(100),
+        };
+        let environment = Environment {
+            hypervisor: Some("KVM".into()),
+            container: Some("docker".into()),
+            cpu_quota: Some(quota),
+        };
+        let caveats = environment.caveats(2);
+        assert_eq!(caveats.len(), 3);
+        assert!(caveats[2]
+            .to_string()
+            .starts_with("CPU quota 1.50 cores (150 ms every 100 ms)"));
+        // One measuring thread has more than a core to itself
+        assert_eq!(environment.caveats(1).len(), 2);
+        assert!(Environment::default().caveats(1).is_empty());
+    }
+}
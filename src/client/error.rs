@@ -15,8 +15,15 @@ pub enum ClientError {
     #[error("Measurement error: {0}")]
     Measurement(String),
 
+    /// A probe was lost with `--strict-loss` set, which aborts the run
+    #[error("Strict loss: {0}")]
+    StrictLoss(String),
+
     #[error("Socket error: {0}")]
     Socket(String),
+
+    #[error("Export error: {0}")]
+    Export(String),
 }
 
 impl From<ProtocolError> for ClientError {
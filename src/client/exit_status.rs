@@ -0,0 +1,99 @@
+//! Exit status of the client
+//!
+//! CI pipelines gate on the exit status alone, so a run that completed but failed its
+//! thresholds must not exit 0, and a pipeline should be able to tell a slow service from
+//! a broken test setup. Each outcome has a code of its own:
+//!
+//! | Code | Outcome                                                      |
+//! |------|--------------------------------------------------------------|
+//! | 0    | The run passed                                               |
+//! | 1    | Any other error (measurement, I/O, report)                   |
+//! | 2    | Invalid configuration                                        |
+//! | 3    | The server could not be reached                              |
+//! | 4    | Excessive loss: above `--max-loss`, no probe answered, or a `--strict-loss` abort |
+//! | 5    | Threshold breach: the verdict failed, an `--slo`/`--sla` was missed, or `compare` found a regression |
+
+use crate::client::output::RunSummary;
+
+/// Outcome of a client invocation, as the process reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The run passed
+    Pass,
+    /// The run failed with an error of no more specific status
+    Error,
+    /// The configuration is invalid
+    Config,
+    /// The server could not be reached
+    Connection,
+    /// Too many probes were lost
+    Loss,
+    /// The run missed its verdict threshold or one of its objectives
+    Breach,
+}
+
+impl ExitStatus {
+    /// Process exit code of the status
+    pub fn code(self) -> i32 {
+        match self {
+            ExitStatus::Pass => 0,
+            ExitStatus::Error => 1,
+            ExitStatus::Config => 2,
+            ExitStatus::Connection => 3,
+            ExitStatus::Loss => 4,
+            ExitStatus::Breach => 5,
+        }
+    }
+
+    /// Status of a completed run
+    ///
+    /// `objectives_met` tells whether every --slo and --sla held. Loss comes first: the
+    /// latency of a run that lost too many probes says little.
+    pub fn of_run(summary: &RunSummary, max_loss_pct: Option<f64>, objectives_met: bool) -> Self {
+        let excessive_loss =
+            summary.received == 0 || max_loss_pct.is_some_and(|max| summary.loss_pct() > max);
+        if excessive_loss {
+            ExitStatus::Loss
+        } else if !summary.passed() || !objectives_met {
+            ExitStatus::Breach
+        } else {
+            ExitStatus::Pass
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::Result;
+    use crate::client::statistics::Statistics;
+
+    #[test]
+    fn test_exit_status_of_run() -> Result<()> {
+        let fast = Statistics::new(&[100_000; 98])?;
+        let summary = RunSummary::new(&fast, 2, 100, &[]);
+        assert_eq!(ExitStatus::of_run(&summary, None, true), ExitStatus::Pass);
+        assert_eq!(
+            ExitStatus::of_run(&summary, Some(2.0), true),
+            ExitStatus::Pass
+        );
+        assert_eq!(
+            ExitStatus::of_run(&summary, Some(1.0), true),
+            ExitStatus::Loss
+        );
+        assert_eq!(
+            ExitStatus::of_run(&summary, None, false),
+            ExitStatus::Breach
+        );
+
+        let slow = Statistics::new(&[5_000_000; 100])?;
+        let summary = RunSummary::new(&slow, 0, 100, &[]);
+        assert_eq!(ExitStatus::of_run(&summary, None, true), ExitStatus::Breach);
+
+        // Nothing answered is excessive loss whatever --max-loss says
+        let summary = RunSummary::new(&Statistics::empty()?, 100, 100, &[]);
+        assert_eq!(ExitStatus::of_run(&summary, None, true), ExitStatus::Loss);
+        assert_eq!(ExitStatus::Breach.code(), 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+//! Push of run results to a Prometheus Pushgateway
+//!
+//! A client run is too short-lived to be scraped. With `--pushgateway` the summary of
+//! every run is pushed once it is reported, as gauges in the Prometheus text format,
+//! so latency regressions show up on the team's dashboards:
+//!
+//! ```text
+//! synapse_rtt_seconds{quantile="0.99"} 0.000061
+//! synapse_packets_lost 0
+//! synapse_run_passed 1
+//! ```
+//!
+//! Runs with server or kernel timestamps add the time spent in each stage of the round
+//! trip, so a dashboard charts where latency was added:
+//!
+//! ```text
+//! synapse_stage_seconds{stage="server",quantile="0.99"} 0.000004
+//! ```
+//!
+//! Metrics are grouped by `job` and `instance` labels (`--push-job`, `--push-instance`),
+//! plus the varied flags of a matrix run, and each push replaces the previous metrics
+//! of its group. The build of the client and the `--tag` tags are the labels of a
+//! constant `synapse_build_info` gauge. Only plain HTTP is spoken; Prometheus
+//! remote-write is left to a Prometheus server scraping the Pushgateway.
+
+use crate::client::build_info::BuildInfo;
+use crate::client::error::{ClientError, Result};
+use crate::client::output::RunSummary;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Time to connect to a collector and to wait for its answer
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Content type of the Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Base URL of a collector, e.g. `http://pushgateway:9091`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl fmt::Display for HttpUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+impl FromStr for HttpUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let rest = s.strip_prefix("http://").ok_or_else(|| {
+            format!(
+                "'{}' is not an http:// URL (https is not supported; use a local proxy)",
+                s
+            )
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("invalid port '{}' in '{}'", port, s))?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in '{}'", s));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Pushes run summaries to one group of a Pushgateway
+#[derive(Debug, Clone)]
+pub struct Pushgateway {
+    url: HttpUrl,
+    grouping: Vec<(String, String)>,
+    tags: BTreeMap<String, String>,
+}
+
+impl Pushgateway {
+    /// Push to the group identified by `job` and `instance`
+    pub fn new(url: HttpUrl, job: &str, instance: &str) -> Self {
+        Self {
+            url,
+            grouping: vec![
+                ("job".to_string(), job.to_string()),
+                ("instance".to_string(), instance.to_string()),
+            ],
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Narrow the group by a further label, e.g. a flag varied by the matrix
+    pub fn with_label(mut self, name: &str, value: &str) -> Self {
+        self.grouping.push((label_name(name), value.to_string()));
+        self
+    }
+
+    /// Add the user tags of the run as labels of `synapse_build_info`
+    ///
+    /// Tags are not part of the group, so a new build replaces the metrics of the last.
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Path of the group, relative to the base URL of the Pushgateway
+    ///
+    /// Values that are not safe in a URL path segment are base64-encoded, as the
+    /// Pushgateway expects for a value containing `/`.
+    pub fn group_path(&self) -> String {
+        let mut path = "/metrics".to_string();
+        for (name, value) in &self.grouping {
+            if !value.is_empty() && value.bytes().all(is_path_safe) {
+                let _ = write!(path, "/{}/{}", name, value);
+            } else {
+                let _ = write!(path, "/{}@base64/{}", name, base64url(value.as_bytes()));
+            }
+        }
+        path
+    }
+
+    /// Replace the metrics of the group by those of `summary`
+    pub fn push(&self, summary: &RunSummary) -> Result<()> {
+        let path = self.group_path();
+        http_send(
+            &self.url,
+            "PUT",
+            &path,
+            CONTENT_TYPE,
+            &render(summary, &self.tags, SystemTime::now()),
+        )?;
+        info!(path = %path, "Pushed run metrics");
+        Ok(())
+    }
+}
+
+/// Send `body` to `path` below `url` and fail unless the answer is a 2xx status
+pub(crate) fn http_send(
+    url: &HttpUrl,
+    method: &str,
+    path: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    debug!(host = %url.host, port = url.port, method = method, path = %path, "Sending export");
+    let addr = (url.host.as_str(), url.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| ClientError::Export(format!("cannot resolve host {}", url.host)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, HTTP_TIMEOUT)?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    let request = format!(
+        "{} {}{} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        url.path,
+        path,
+        url.host,
+        url.port,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(ClientError::Export(format!(
+            "{} {}{} rejected: {}",
+            method,
+            url,
+            path,
+            if status_line.is_empty() {
+                "no response"
+            } else {
+                status_line
+            }
+        ))),
+    }
+}
+
+/// Run summary as gauges in the Prometheus text exposition format
+///
+/// The build of the client and the user `tags` are the labels of a constant
+/// `synapse_build_info` gauge, to be joined onto the other metrics in queries.
+pub fn render(
+    summary: &RunSummary,
+    tags: &BTreeMap<String, String>,
+    pushed_at: SystemTime,
+) -> String {
+    let seconds = |ns: f64| format!("{}", ns / 1e9);
+    let pushed_at = pushed_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64());
+    let build = BuildInfo::current();
+    let build_labels = build
+        .fields()
+        .into_iter()
+        .chain(
+            tags.iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        )
+        .map(|(name, value)| format!("{}=\"{}\"", name, label_value(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+    let build_labels = format!("{{{}}}", build_labels);
+    let stage_quantiles: Vec<(String, String)> = summary
+        .stages
+        .iter()
+        .flat_map(|stage| {
+            [
+                ("0.5", stage.p50_ns),
+                ("0.99", stage.p99_ns),
+                ("1", stage.max_ns),
+            ]
+            .map(|(quantile, ns)| {
+                (
+                    format!("{{stage=\"{}\",quantile=\"{}\"}}", stage.stage, quantile),
+                    seconds(ns as f64),
+                )
+            })
+        })
+        .collect();
+    let stage_means: Vec<(String, String)> = summary
+        .stages
+        .iter()
+        .map(|stage| {
+            (
+                format!("{{stage=\"{}\"}}", stage.stage),
+                seconds(stage.mean_ns),
+            )
+        })
+        .collect();
+    let metrics = [
+        (
+            "synapse_rtt_seconds",
+            "Round-trip time quantiles of the last run",
+            vec![
+                ("{quantile=\"0.5\"}", seconds(summary.p50_ns as f64)),
+                ("{quantile=\"0.99\"}", seconds(summary.p99_ns as f64)),
+                ("{quantile=\"1\"}", seconds(summary.max_ns as f64)),
+            ],
+        ),
+        (
+            "synapse_rtt_mean_seconds",
+            "Mean round-trip time of the last run",
+            vec![("", seconds(summary.mean_ns))],
+        ),
+        (
+            "synapse_stage_seconds",
+            "Time spent in each stage of the round trips of the last run, by quantile",
+            stage_quantiles
+                .iter()
+                .map(|(labels, value)| (labels.as_str(), value.clone()))
+                .collect(),
+        ),
+        (
+            "synapse_stage_mean_seconds",
+            "Mean time spent in each stage of the round trips of the last run",
+            stage_means
+                .iter()
+                .map(|(labels, value)| (labels.as_str(), value.clone()))
+                .collect(),
+        ),
+        (
+            "synapse_energy_joules",
+            "Energy the CPU packages of the client host used over the last run",
+            summary
+                .energy_joules
+                .iter()
+                .map(|joules| ("", joules.to_string()))
+                .collect(),
+        ),
+        (
+            "synapse_packets_sent",
+            "Packets sent in the last run",
+            vec![("", summary.total_packets.to_string())],
+        ),
+        (
+            "synapse_packets_lost",
+            "Packets lost or timed out in the last run",
+            vec![("", summary.lost_packets.to_string())],
+        ),
+        (
+            "synapse_loss_ratio",
+            "Share of the packets of the last run that were lost",
+            vec![("", format!("{}", summary.loss_pct() / 100.0))],
+        ),
+        (
+            "synapse_send_stalls",
+            "Sends of the last run that blocked longer than the stall threshold",
+            vec![("", summary.send_stalls.to_string())],
+        ),
+        (
+            "synapse_run_passed",
+            "1 if the last run passed its latency verdict, 0 otherwise",
+            vec![("", (summary.passed() as u8).to_string())],
+        ),
+        (
+            "synapse_last_push_timestamp_seconds",
+            "Time of the last push, in seconds since the UNIX epoch",
+            vec![("", format!("{:.3}", pushed_at))],
+        ),
+        (
+            "synapse_build_info",
+            "Build of the client and tags of the last run, as labels; always 1",
+            vec![(build_labels.as_str(), "1".to_string())],
+        ),
+    ];
+
+    let mut out = String::new();
+    for (name, help, samples) in metrics {
+        // Stages are only known with server or kernel timestamps, energy with --energy
+        if samples.is_empty() {
+            continue;
+        }
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "# HELP {} {}.", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    }
+    out
+}
+
+/// `name` as a Prometheus label name (`timeout-ms` becomes `timeout_ms`)
+fn label_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// `value` escaped for a label value of the text exposition format
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Whether `byte` may appear unencoded in a label value of the group path
+fn is_path_safe(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b':')
+}
+
+/// `bytes` in URL-safe base64 without padding
+fn base64url(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    if out.is_empty() {
+        // The Pushgateway reads a lone "=" as an empty value
+        out.push('=');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::output::StageLatency;
+    use crate::client::verdict::Verdict;
+    use std::net::TcpListener;
+
+    fn summary() -> RunSummary {
+        RunSummary {
+            total_packets: 1000,
+            lost_packets: 10,
+            received: 990,
+            mean_ns: 48_000.0,
+            p50_ns: 45_000,
+            p99_ns: 61_000,
+            p999_ns: 61_000,
+            max_ns: 250_000,
+            clamped_count: 0,
+            send_stalls: 2,
+            stages: Vec::new(),
+            caveats: Vec::new(),
+            energy_joules: None,
+            verdict: Verdict::default(),
+        }
+    }
+
+    #[test]
+    fn test_pushgateway_url() {
+        let url: HttpUrl = "http://pushgateway:9091/".parse().unwrap();
+        assert_eq!(url.host, "pushgateway");
+        assert_eq!(url.port, 9091);
+        assert_eq!(url.path, "");
+        let url: HttpUrl = "http://10.0.0.5/prom".parse().unwrap();
+        assert_eq!((url.port, url.path.as_str()), (80, "/prom"));
+        assert!("https://pushgateway:9091".parse::<HttpUrl>().is_err());
+        assert!("http://:9091".parse::<HttpUrl>().is_err());
+    }
+
+    #[test]
+    fn test_pushgateway_group_path() {
+        let url: HttpUrl = "http://pushgateway:9091".parse().unwrap();
+        let gateway =
+            Pushgateway::new(url, "synapse", "10.0.0.2:8080").with_label("timeout-ms", "50");
+        assert_eq!(
+            gateway.group_path(),
+            "/metrics/job/synapse/instance/10.0.0.2:8080/timeout_ms/50"
+        );
+        let url: HttpUrl = "http://pushgateway:9091".parse().unwrap();
+        let gateway = Pushgateway::new(url, "synapse", "a/b");
+        assert_eq!(
+            gateway.group_path(),
+            "/metrics/job/synapse/instance@base64/YS9i"
+        );
+        assert_eq!(base64url(b"[fe80::1]"), "W2ZlODA6OjFd");
+    }
+
+    #[test]
+    fn test_render_push_metrics() {
+        let tags = BTreeMap::from([("build".to_string(), "4f2a\"9c1".to_string())]);
+        let text = render(
+            &summary(),
+            &tags,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+        assert!(text.contains("# TYPE synapse_rtt_seconds gauge\n"));
+        assert!(text.contains("\nsynapse_rtt_seconds{quantile=\"0.99\"} 0.000061\n"));
+        assert!(text.contains("\nsynapse_rtt_mean_seconds 0.000048\n"));
+        assert!(text.contains("\nsynapse_packets_lost 10\n"));
+        assert!(text.contains("\nsynapse_loss_ratio 0.01\n"));
+        assert!(text.contains("\nsynapse_run_passed 1\n"));
+        assert!(text.contains("\nsynapse_last_push_timestamp_seconds 1700000000.000\n"));
+        let build_info = format!(
+            "\nsynapse_build_info{{version=\"{}\",git_sha=\"{}\",",
+            env!("CARGO_PKG_VERSION"),
+            BuildInfo::current().git_sha
+        );
+        assert!(text.contains(&build_info), "{}", text);
+        assert!(text.contains(",build=\"4f2a\\\"9c1\"} 1\n"), "{}", text);
+        assert!(!text.contains("synapse_stage_seconds"));
+        assert!(!text.contains("synapse_energy_joules"));
+
+        let stages = vec![StageLatency {
+            stage: "server",
+            mean_ns: 3_500.0,
+            p50_ns: 3_000,
+            p99_ns: 4_000,
+            max_ns: 9_000,
+        }];
+        let text = render(&summary().with_stages(stages), &tags, UNIX_EPOCH);
+        assert!(text.contains("# TYPE synapse_stage_seconds gauge\n"));
+        assert!(
+            text.contains("\nsynapse_stage_seconds{stage=\"server\",quantile=\"0.99\"} 0.000004\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("\nsynapse_stage_mean_seconds{stage=\"server\"} 0.0000035\n"));
+
+        let mut summary = summary();
+        summary.energy_joules = Some(152.5);
+        let text = render(&summary, &tags, UNIX_EPOCH);
+        assert!(text.contains("\nsynapse_energy_joules 152.5\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_pushgateway_push() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url: HttpUrl = format!("http://{}", listener.local_addr()?)
+            .parse()
+            .unwrap();
+        let server = std::thread::spawn(move || -> std::io::Result<String> {
+            let (mut stream, _) = listener.accept()?;
+            let mut request = vec![0u8; 8192];
+            let n = stream.read(&mut request)?;
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")?;
+            Ok(String::from_utf8_lossy(&request[..n]).into_owned())
+        });
+
+        Pushgateway::new(url, "synapse", "lab").push(&summary())?;
+        let request = server.join().unwrap()?;
+        assert!(
+            request.starts_with("PUT /metrics/job/synapse/instance/lab HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+        Ok(())
+    }
+}
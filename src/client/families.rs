@@ -0,0 +1,198 @@
+//! IPv4 and IPv6 side by side
+//!
+//! A dual-stack host is often reached over two different paths: IPv6 may go through a
+//! tunnel, take another route through a transit provider, or cross a NAT64 the IPv4
+//! traffic never sees. `--compare-families` resolves the --server host, runs the
+//! configured measurement against its first IPv4 (A) and its first IPv6 (AAAA) address,
+//! one after the other, and compares them.
+//!
+//! A family the host has no address of, or whose address cannot be reached, is reported
+//! as failed; the other is still measured.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::output::RunSummary;
+use crate::client::port_sweep::Deviation;
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// Ratio to the other family's P99 from which a family stands out
+pub const P99_DIFFERENCE_RATIO: f64 = 1.5;
+
+/// Percentage points of loss above the other family's from which a family stands out
+pub const LOSS_DIFFERENCE_PCT: f64 = 1.0;
+
+/// Internet Protocol version of an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    /// IPv4, from A records
+    V4,
+    /// IPv6, from AAAA records
+    V6,
+}
+
+impl Family {
+    /// Both families, in the order they are measured
+    pub const ALL: [Family; 2] = [Family::V4, Family::V6];
+
+    /// Family of `addr`
+    pub fn of(addr: &SocketAddr) -> Self {
+        if addr.is_ipv4() {
+            Family::V4
+        } else {
+            Family::V6
+        }
+    }
+
+    /// DNS record type holding addresses of the family
+    pub fn record(&self) -> &'static str {
+        match self {
+            Family::V4 => "A",
+            Family::V6 => "AAAA",
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Family::V4 => write!(f, "IPv4"),
+            Family::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Outcome of the measurement of one family
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyResult {
+    /// Family measured
+    pub family: Family,
+    /// Address measured, if the host has one of the family
+    pub address: Option<SocketAddr>,
+    /// Summary of the run, or the error that stopped it
+    pub outcome: std::result::Result<RunSummary, String>,
+}
+
+/// First address of each family that `server` (`HOST:PORT`) resolves to, in
+/// [`Family::ALL`] order
+///
+/// Fails when the name does not resolve at all.
+pub fn resolve(server: &str) -> Result<Vec<(Family, Option<SocketAddr>)>> {
+    let addrs: Vec<SocketAddr> = server
+        .to_socket_addrs()
+        .map_err(|e| ClientError::Socket(format!("Failed to resolve {}: {}", server, e)))?
+        .collect();
+    Ok(first_of_each(&addrs))
+}
+
+/// First address of each family in `addrs`, in [`Family::ALL`] order
+fn first_of_each(addrs: &[SocketAddr]) -> Vec<(Family, Option<SocketAddr>)> {
+    Family::ALL
+        .iter()
+        .map(|&family| {
+            let addr = addrs.iter().find(|addr| Family::of(addr) == family);
+            (family, addr.copied())
+        })
+        .collect()
+}
+
+/// Ways `result` stands out from the other family of `results`
+///
+/// Empty unless both families were measured.
+pub fn deviations(result: &FamilyResult, results: &[FamilyResult]) -> Vec<Deviation> {
+    let Ok(summary) = &result.outcome else {
+        return Vec::new();
+    };
+    let Some(other) = results
+        .iter()
+        .filter(|r| r.family != result.family)
+        .find_map(|r| r.outcome.as_ref().ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut deviations = Vec::new();
+    let ratio = summary.p99_ns as f64 / other.p99_ns.max(1) as f64;
+    if ratio >= P99_DIFFERENCE_RATIO {
+        deviations.push(Deviation::Latency(ratio));
+    }
+    let excess = summary.loss_pct() - other.loss_pct();
+    if excess >= LOSS_DIFFERENCE_PCT {
+        deviations.push(Deviation::Loss(excess));
+    }
+    deviations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::verdict::Verdict;
+
+    fn summary(lost_packets: usize, p99_ns: u64) -> RunSummary {
+        RunSummary {
+            total_packets: 1000,
+            lost_packets,
+            received: 1000 - lost_packets as u64,
+            mean_ns: 40_000.0,
+            p50_ns: 40_000,
+            p99_ns,
+            p999_ns: p99_ns,
+            max_ns: p99_ns,
+            clamped_count: 0,
+            send_stalls: 0,
+            stages: Vec::new(),
+            caveats: Vec::new(),
+            energy_joules: None,
+            verdict: Verdict::default(),
+        }
+    }
+
+    #[test]
+    fn test_first_of_each() {
+        let addrs: Vec<SocketAddr> = ["[2001:db8::1]:8080", "192.0.2.1:8080", "192.0.2.2:8080"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        assert_eq!(
+            first_of_each(&addrs),
+            vec![(Family::V4, Some(addrs[1])), (Family::V6, Some(addrs[0]))]
+        );
+        assert_eq!(
+            first_of_each(&addrs[1..]),
+            vec![(Family::V4, Some(addrs[1])), (Family::V6, None)]
+        );
+    }
+
+    #[test]
+    fn test_resolve() -> Result<()> {
+        let families = resolve("127.0.0.1:8080")?;
+        assert_eq!(families[0].1, Some("127.0.0.1:8080".parse().unwrap()));
+        assert_eq!(families[1], (Family::V6, None));
+        assert!(resolve("no port").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deviations() {
+        let result = |family, outcome| FamilyResult {
+            family,
+            address: None,
+            outcome,
+        };
+        let results = vec![
+            result(Family::V4, Ok(summary(0, 100_000))),
+            result(Family::V6, Ok(summary(20, 200_000))),
+        ];
+        assert!(deviations(&results[0], &results).is_empty());
+        assert_eq!(
+            deviations(&results[1], &results),
+            vec![Deviation::Latency(2.0), Deviation::Loss(2.0)]
+        );
+
+        // Nothing to compare with
+        let one = vec![
+            results[1].clone(),
+            result(Family::V4, Err("no A record".into())),
+        ];
+        assert!(deviations(&one[0], &one).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! UDP source-port rotation for ECMP path exploration
+//!
+//! Routers with equal-cost multipath (ECMP) spread traffic over parallel links by a
+//! hash of each packet's addresses and ports, so all probes of one UDP socket take the
+//! same path and a run sees only one of them. `--flows N` opens N UDP sockets to the
+//! server, each on a source port of its own, and sends probe `i` from socket `i % N`.
+//! The N flows hash onto whatever paths the network has, and the report breaks the
+//! latency down per flow: flows on a longer or busier path stand out from the others.
+//!
+//! Probing stays one probe at a time, so the flows take turns rather than compete.
+
+use crate::client::connections::ConnectionStats;
+use crate::client::dscp::Dscp;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::MeasurementResult;
+use crate::client::socket::{AbortHandle, NetworkSocket, UdpNetworkSocket};
+use crate::client::timestamping::KernelTimestamps;
+use crate::protocol::Packet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tracing::debug;
+
+/// Flow that carries the probe with sequence number `sequence` in a run of `flows` flows
+pub fn flow_of(sequence: u64, flows: usize) -> usize {
+    (sequence % flows.max(1) as u64) as usize
+}
+
+/// UDP sockets to one server, each on a source port of its own, taking turns by
+/// sequence number
+pub struct FlowSocket {
+    sockets: Vec<UdpNetworkSocket>,
+    /// Flow of the last probe sent; its socket receives the reply
+    current: AtomicUsize,
+}
+
+impl FlowSocket {
+    /// Open `flows` UDP sockets to `addr`
+    pub fn connect(addr: &str, flows: usize) -> Result<Self> {
+        if flows == 0 {
+            return Err(ClientError::Config("flows must be > 0".into()));
+        }
+        let sockets = (0..flows)
+            .map(|_| UdpNetworkSocket::connect(addr))
+            .collect::<Result<Vec<_>>>()?;
+        debug!(flows = flows, "Flow sockets connected");
+        Ok(Self {
+            sockets,
+            current: AtomicUsize::new(0),
+        })
+    }
+
+    /// Sockets of the flows in order, to configure them alike
+    pub fn sockets_mut(&mut self) -> impl Iterator<Item = &mut UdpNetworkSocket> {
+        self.sockets.iter_mut()
+    }
+
+    /// Source address of every flow, in flow order
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>> {
+        self.sockets
+            .iter()
+            .map(UdpNetworkSocket::local_addr)
+            .collect()
+    }
+
+    fn current(&mut self) -> &mut UdpNetworkSocket {
+        let current = self.current.load(Ordering::Relaxed);
+        &mut self.sockets[current]
+    }
+}
+
+impl NetworkSocket for FlowSocket {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        let flow = flow_of(packet.sequence.0, self.sockets.len());
+        self.current.store(flow, Ordering::Relaxed);
+        self.sockets[flow].send_packet(packet)
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        self.current().recv_packet()
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        self.sockets
+            .iter()
+            .try_for_each(|socket| socket.set_timeout(timeout))
+    }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        let handles = self
+            .sockets
+            .iter()
+            .map(UdpNetworkSocket::abort_handle)
+            .collect::<Result<Option<Vec<_>>>>()?;
+        Ok(handles.map(|handles| {
+            Box::new(move || handles.into_iter().for_each(|abort| abort())) as AbortHandle
+        }))
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        let current = self.current.load(Ordering::Relaxed);
+        self.sockets[current].take_error()
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        self.current().kernel_timestamps()
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        self.current().reply_dscp()
+    }
+
+    fn accept_any_reply(&mut self) {
+        self.sockets
+            .iter_mut()
+            .for_each(NetworkSocket::accept_any_reply);
+    }
+}
+
+/// Break a measurement down by the flow that carried each probe
+///
+/// `sources` are the flows' source addresses, in flow order.
+pub fn flow_stats(
+    result: &MeasurementResult,
+    sources: &[SocketAddr],
+) -> Result<Vec<ConnectionStats>> {
+    let flows = sources.len();
+    let mut latencies = vec![Vec::new(); flows];
+    for (&sequence, &latency) in result.sequences.iter().zip(&result.latencies) {
+        latencies[flow_of(sequence, flows)].push(latency);
+    }
+    let mut lost = vec![0; flows];
+    for &sequence in &result.lost_sequences {
+        lost[flow_of(sequence, flows)] += 1;
+    }
+
+    latencies
+        .iter()
+        .zip(lost)
+        .zip(sources)
+        .enumerate()
+        .map(|(index, ((latencies, lost), &source))| {
+            ConnectionStats::from_samples(
+                index,
+                Some(source),
+                latencies,
+                latencies.len() + lost,
+                lost,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::socket::challenge_hellos;
+    use crate::protocol::SequenceNumber;
+    use std::collections::BTreeSet;
+    use std::net::UdpSocket;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_flows_take_turns_from_their_own_ports() -> Result<()> {
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let hellos = challenge_hellos(&server, 3, 5);
+        let mut socket = FlowSocket::connect(&server.local_addr()?.to_string(), 3)?;
+        hellos.join().unwrap();
+        socket.set_timeout(Duration::from_millis(500))?;
+        let sources = socket.local_addrs()?;
+        assert_eq!(sources.iter().collect::<BTreeSet<_>>().len(), 3);
+
+        let mut buf = [0u8; 64];
+        for sequence in 0..6 {
+            socket.send_packet(&Packet::new(SequenceNumber(sequence)))?;
+            let (len, client) = server.recv_from(&mut buf)?;
+            assert_eq!(client, sources[flow_of(sequence, 3)]);
+            server.send_to(&buf[..len], client)?;
+            assert_eq!(socket.recv_packet()?.sequence, SequenceNumber(sequence));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flow_stats() -> Result<()> {
+        let result = MeasurementResult {
+            latencies: vec![10_000, 50_000, 10_000, 50_000, 10_000],
+            send_durations: vec![1_000; 6],
+            recv_wait_durations: vec![9_000; 5],
+            lost_packets: 1,
+            total_packets: 6,
+            elapsed: Duration::from_millis(1),
+            seed: 7,
+            system_timeline: None,
+            sequences: vec![0, 1, 2, 3, 4],
+            send_offsets: vec![0, 100, 200, 300, 400],
+            idle_gaps: vec![0; 5],
+            started_at: SystemTime::now(),
+            warmup: None,
+            lost_sequences: vec![5],
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: None,
+            reply_dscp: Default::default(),
+        };
+        let sources: Vec<SocketAddr> = vec![
+            "10.0.0.1:40000".parse().unwrap(),
+            "10.0.0.1:40001".parse().unwrap(),
+        ];
+        let flows = flow_stats(&result, &sources)?;
+        assert_eq!(flows.len(), 2);
+        assert_eq!((flows[0].sent, flows[0].lost), (3, 0));
+        assert_eq!((flows[1].sent, flows[1].lost), (3, 1));
+        assert_eq!(flows[1].local, Some(sources[1]));
+        assert!(flows[1].p50_ns > 4 * flows[0].p50_ns);
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Progress heartbeat of runs without the live view
+//!
+//! `--quiet` drops the live view, as does a stdout that is not a terminal, so a run of
+//! hours under systemd, in a container or in CI prints nothing until it ends. Instead,
+//! the measurement logs one structured line
+//! every `--heartbeat-secs`: packets done, the P99 of the probes answered since the
+//! previous line, and the loss so far. Operators tailing the logs can tell a live run
+//! from a hung one, and a latency shift shows up while the run is still going.
+
+use crate::client::error::Result;
+use crate::client::statistics::Statistics;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Progress of a run at one heartbeat
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beat {
+    /// Probes completed, answered or lost
+    pub done: usize,
+    /// Probes of the whole phase
+    pub total: usize,
+    /// P99 RTT of the probes answered since the previous beat, in nanoseconds; `None`
+    /// if none were
+    pub p99_ns: Option<u64>,
+    /// Probes lost since the start of the phase
+    pub lost: usize,
+}
+
+impl Beat {
+    /// Probes lost so far, as a percentage of the probes completed
+    pub fn loss_pct(&self) -> f64 {
+        if self.done == 0 {
+            return 0.0;
+        }
+        self.lost as f64 / self.done as f64 * 100.0
+    }
+}
+
+/// Logs a [`Beat`] every interval of a measurement phase
+pub struct Heartbeat {
+    interval: Duration,
+    total: usize,
+    last_beat: Instant,
+    /// Answered probes since the last beat
+    recent: Statistics,
+    done: usize,
+    lost: usize,
+}
+
+impl Heartbeat {
+    /// Heartbeat every `interval` of a phase of `total` probes, started at `start`
+    pub fn new(interval: Duration, total: usize, start: Instant) -> Result<Self> {
+        Ok(Self {
+            interval,
+            total,
+            last_beat: start,
+            recent: Statistics::empty()?,
+            done: 0,
+            lost: 0,
+        })
+    }
+
+    /// Count a completed probe, answered after `rtt_ns` or lost
+    pub fn record(&mut self, rtt_ns: Option<u64>) -> Result<()> {
+        self.done += 1;
+        match rtt_ns {
+            Some(rtt_ns) => self.recent.record(rtt_ns)?,
+            None => self.lost += 1,
+        }
+        Ok(())
+    }
+
+    /// Log a beat if an interval has passed since the last one at `now`
+    pub fn poll(&mut self, now: Instant) -> Result<Option<Beat>> {
+        if now.duration_since(self.last_beat) < self.interval {
+            return Ok(None);
+        }
+        let beat = Beat {
+            done: self.done,
+            total: self.total,
+            p99_ns: (self.recent.count() > 0).then(|| self.recent.percentile(0.99)),
+            lost: self.lost,
+        };
+        info!(
+            packets_done = beat.done,
+            packets_total = beat.total,
+            p99_us = beat.p99_ns.map(|ns| ns as f64 / 1000.0),
+            packets_lost = beat.lost,
+            loss_pct = beat.loss_pct(),
+            "Measurement in progress"
+        );
+        self.last_beat = now;
+        self.recent = Statistics::empty()?;
+        Ok(Some(beat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() -> Result<()> {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(10), 100, start)?;
+        for rtt_ns in [Some(20_000), None, Some(90_000), Some(30_000)] {
+            heartbeat.record(rtt_ns)?;
+        }
+        assert_eq!(heartbeat.poll(start + Duration::from_secs(9))?, None);
+
+        let beat = heartbeat.poll(start + Duration::from_secs(10))?.unwrap();
+        assert_eq!((beat.done, beat.total, beat.lost), (4, 100, 1));
+        assert!(beat.p99_ns.unwrap().abs_diff(90_000) < 100);
+        assert_eq!(beat.loss_pct(), 25.0);
+
+        // The P99 covers the probes since the previous beat, the loss the whole phase
+        heartbeat.record(None)?;
+        assert_eq!(heartbeat.poll(start + Duration::from_secs(15))?, None);
+        let beat = heartbeat.poll(start + Duration::from_secs(20))?.unwrap();
+        assert_eq!((beat.done, beat.lost, beat.p99_ns), (5, 2, None));
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+//! Huge-page backed sample buffers
+//!
+//! At high packet rates the per-packet sample vectors span many megabytes, and every
+//! 4 KiB page of them costs a TLB entry and a page fault on first write. With
+//! `--huge-pages` the measurement allocates its [`SampleBuffer`]s from huge pages and
+//! pre-faults them, so neither happens inside the measurement loop.
+//!
+//! Explicit huge pages (`MAP_HUGETLB`) are tried first; they need pages reserved with
+//! `sysctl vm.nr_hugepages=N`. Without a reservation the buffer falls back to a
+//! transparent-huge-page hint (`MADV_HUGEPAGE`), and without that to regular pages. The
+//! [`PageBacking`] actually obtained is reported with the results.
+
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use tracing::{debug, warn};
+
+/// Huge page size assumed when `/proc/meminfo` does not report one
+const DEFAULT_HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Kind of pages backing a sample buffer, from least to most TLB-friendly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PageBacking {
+    /// Regular pages from the heap
+    Regular,
+    /// Regular mapping with the transparent-huge-page hint applied
+    Transparent,
+    /// Explicitly reserved huge pages (`MAP_HUGETLB`)
+    HugeTlb,
+}
+
+impl fmt::Display for PageBacking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Regular => write!(f, "regular pages"),
+            Self::Transparent => write!(f, "transparent huge pages"),
+            Self::HugeTlb => {
+                let kib = huge_page_size().unwrap_or(DEFAULT_HUGE_PAGE_SIZE) / 1024;
+                if kib.is_multiple_of(1024) {
+                    write!(f, "{} MiB huge pages", kib / 1024)
+                } else {
+                    write!(f, "{} KiB huge pages", kib)
+                }
+            }
+        }
+    }
+}
+
+/// Fixed-capacity buffer of `u64` samples
+///
+/// Dereferences to the samples pushed so far. Pushing beyond the capacity moves the
+/// samples to the heap rather than failing.
+pub struct SampleBuffer {
+    storage: Storage,
+}
+
+enum Storage {
+    Heap(Vec<u64>),
+    Mapped(Mapping),
+}
+
+/// Anonymous memory mapping holding `len` of `capacity` samples
+struct Mapping {
+    /// Start of the mapping as returned by `mmap`
+    base: *mut std::ffi::c_void,
+    /// Length of the mapping in bytes
+    map_len: usize,
+    /// First sample slot, aligned to a huge page within the mapping
+    samples: *mut u64,
+    capacity: usize,
+    len: usize,
+    backing: PageBacking,
+}
+
+impl SampleBuffer {
+    /// Buffer for `capacity` samples on regular heap pages
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            storage: Storage::Heap(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Buffer for `capacity` samples on huge pages where available
+    ///
+    /// Falls back to regular pages; check [`SampleBuffer::backing`] for the outcome.
+    pub fn huge(capacity: usize) -> Self {
+        match Mapping::new(capacity) {
+            Some(mapping) => {
+                debug!(capacity = capacity, backing = ?mapping.backing, "Sample buffer mapped");
+                Self {
+                    storage: Storage::Mapped(mapping),
+                }
+            }
+            None => Self::with_capacity(capacity),
+        }
+    }
+
+    /// Kind of pages backing the samples
+    pub fn backing(&self) -> PageBacking {
+        match &self.storage {
+            Storage::Heap(_) => PageBacking::Regular,
+            Storage::Mapped(mapping) => mapping.backing,
+        }
+    }
+
+    /// Append a sample
+    pub fn push(&mut self, value: u64) {
+        match &mut self.storage {
+            Storage::Heap(vec) => vec.push(value),
+            Storage::Mapped(mapping) if mapping.len < mapping.capacity => {
+                // SAFETY: the slot is within the mapping, which holds `capacity` samples
+                unsafe { mapping.samples.add(mapping.len).write(value) };
+                mapping.len += 1;
+            }
+            Storage::Mapped(mapping) => {
+                warn!(
+                    capacity = mapping.capacity,
+                    "Sample buffer full; moving samples to regular pages"
+                );
+                let mut vec = Vec::with_capacity(mapping.capacity * 2);
+                vec.extend_from_slice(mapping.as_slice());
+                vec.push(value);
+                self.storage = Storage::Heap(vec);
+            }
+        }
+    }
+
+    /// Move the samples into a `Vec`
+    pub fn into_vec(self) -> Vec<u64> {
+        match self.storage {
+            Storage::Heap(vec) => vec,
+            Storage::Mapped(mapping) => mapping.as_slice().to_vec(),
+        }
+    }
+}
+
+impl Deref for SampleBuffer {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        match &self.storage {
+            Storage::Heap(vec) => vec,
+            Storage::Mapped(mapping) => mapping.as_slice(),
+        }
+    }
+}
+
+impl Mapping {
+    /// Map and pre-fault room for `capacity` samples, preferring huge pages
+    #[cfg(target_os = "linux")]
+    fn new(capacity: usize) -> Option<Self> {
+        let page = huge_page_size().unwrap_or(DEFAULT_HUGE_PAGE_SIZE);
+        let bytes = capacity.max(1).checked_mul(std::mem::size_of::<u64>())?;
+        let len = bytes.div_ceil(page).checked_mul(page)?;
+        let protection = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+
+        // SAFETY: anonymous mapping without a fixed address; the result is checked
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                protection,
+                flags | libc::MAP_HUGETLB | libc::MAP_POPULATE,
+                -1,
+                0,
+            )
+        };
+        if base != libc::MAP_FAILED {
+            return Some(Self {
+                base,
+                map_len: len,
+                samples: base.cast(),
+                capacity,
+                len: 0,
+                backing: PageBacking::HugeTlb,
+            });
+        }
+        debug!(
+            error = %std::io::Error::last_os_error(),
+            "No reserved huge pages; trying transparent huge pages"
+        );
+
+        // One extra huge page leaves room to align the samples to a huge-page boundary
+        let map_len = len.checked_add(page)?;
+        // SAFETY: as above
+        let base = unsafe { libc::mmap(std::ptr::null_mut(), map_len, protection, flags, -1, 0) };
+        if base == libc::MAP_FAILED {
+            warn!(
+                error = %std::io::Error::last_os_error(),
+                "Failed to map sample buffer; using the heap"
+            );
+            return None;
+        }
+        let offset = (page - base as usize % page) % page;
+        // SAFETY: `offset` is less than one huge page, which the mapping has to spare
+        let samples = unsafe { base.cast::<u8>().add(offset) };
+        // SAFETY: the range lies within the mapping created above
+        let advised = unsafe { libc::madvise(samples.cast(), len, libc::MADV_HUGEPAGE) } == 0;
+        // Fault the pages in now rather than inside the measurement loop
+        // SAFETY: the range lies within the writable mapping created above
+        unsafe { samples.write_bytes(0, len) };
+
+        Some(Self {
+            base,
+            map_len,
+            samples: samples.cast(),
+            capacity,
+            len: 0,
+            backing: if advised && transparent_huge_pages_enabled() {
+                PageBacking::Transparent
+            } else {
+                PageBacking::Regular
+            },
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new(_capacity: usize) -> Option<Self> {
+        None
+    }
+
+    fn as_slice(&self) -> &[u64] {
+        // SAFETY: the first `len` slots have been written and lie within the mapping
+        unsafe { std::slice::from_raw_parts(self.samples, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        // SAFETY: `base` and `map_len` describe a mapping created by `mmap`, unmapped once
+        if unsafe { libc::munmap(self.base, self.map_len) } != 0 {
+            warn!(error = %std::io::Error::last_os_error(), "Failed to unmap sample buffer");
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn drop(&mut self) {}
+}
+
+/// Size of the default huge page from `/proc/meminfo`
+fn huge_page_size() -> Option<usize> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_huge_page_size(&meminfo)
+}
+
+/// Parse the `Hugepagesize:` line of `/proc/meminfo` into bytes
+fn parse_huge_page_size(meminfo: &str) -> Option<usize> {
+    let kib: usize = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("Hugepagesize:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+/// Whether transparent huge pages honor `MADV_HUGEPAGE` (mode `always` or `madvise`)
+fn transparent_huge_pages_enabled() -> bool {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+        .map(|modes| !modes.contains("[never]"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_buffer_heap() {
+        let mut buffer = SampleBuffer::with_capacity(2);
+        buffer.push(1);
+        buffer.push(2);
+        buffer.push(3);
+        assert_eq!(buffer.backing(), PageBacking::Regular);
+        assert_eq!(&buffer[..], &[1, 2, 3]);
+        assert_eq!(buffer.into_vec(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sample_buffer_huge_falls_back_and_spills() {
+        // Whatever backing the host provides, the buffer behaves like a Vec
+        let mut buffer = SampleBuffer::huge(3);
+        for value in 0..5 {
+            buffer.push(value);
+        }
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(buffer.iter().sum::<u64>(), 10);
+        assert_eq!(buffer.into_vec(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parse_huge_page_size() {
+        let meminfo = "HugePages_Total:       0\nHugepagesize:       2048 kB\n";
+        assert_eq!(parse_huge_page_size(meminfo), Some(2 * 1024 * 1024));
+        assert_eq!(parse_huge_page_size("MemTotal: 1 kB\n"), None);
+        assert!(PageBacking::Regular < PageBacking::Transparent);
+        assert!(PageBacking::Transparent < PageBacking::HugeTlb);
+    }
+}
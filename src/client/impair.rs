@@ -0,0 +1,298 @@
+//! Automated tc/netem impairment sweeps
+//!
+//! An impairment sweep applies a series of netem delay/loss profiles to a network
+//! interface, runs a short measurement under each one and compares what was measured
+//! with what was injected. It validates the whole pipeline end to end: if synapse
+//! reports 10 ms more latency when netem adds 10 ms, the numbers can be trusted.
+//!
+//! Applying a qdisc requires root (or `CAP_NET_ADMIN`) and Linux. The qdisc is removed
+//! when [`NetemQdisc`] is dropped; if the process is killed, remove it by hand with
+//! `tc qdisc del dev <interface> root`.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::{
+    measurement_phase_with_options, warmup_phase, MeasurementOptions,
+};
+use crate::client::socket::{NetworkSocket, TcpNetworkSocket, UdpNetworkSocket};
+use crate::client::statistics::Statistics;
+use crate::client::warmup::{Warmup, DEFAULT_WARMUP_TOLERANCE_PCT};
+use crate::protocol::Transport;
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Time given to the kernel to settle after changing the qdisc
+const QDISC_SETTLE_MS: u64 = 200;
+
+/// A netem delay/loss profile, written on the command line as `DELAY_MS[:LOSS_PCT]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetemProfile {
+    /// Delay added to every packet crossing the interface
+    pub delay: Duration,
+    /// Percentage of packets dropped by the interface
+    pub loss_pct: f64,
+}
+
+impl NetemProfile {
+    /// Arguments passed to `tc qdisc add dev <interface> root` for this profile
+    pub fn tc_args(&self, interface: &str) -> Vec<String> {
+        let mut args = vec![
+            "qdisc".to_string(),
+            "add".to_string(),
+            "dev".to_string(),
+            interface.to_string(),
+            "root".to_string(),
+            "netem".to_string(),
+            "delay".to_string(),
+            format!("{}us", self.delay.as_micros()),
+        ];
+        if self.loss_pct > 0.0 {
+            args.push("loss".to_string());
+            args.push(format!("{}%", self.loss_pct));
+        }
+        args
+    }
+
+    /// RTT increase expected when each probe crosses the interface `passes` times
+    pub fn expected_delay(&self, passes: u32) -> Duration {
+        self.delay * passes
+    }
+
+    /// Loss percentage expected when each probe crosses the interface `passes` times
+    pub fn expected_loss_pct(&self, passes: u32) -> f64 {
+        let delivered = (1.0 - self.loss_pct / 100.0).powi(passes as i32);
+        (1.0 - delivered) * 100.0
+    }
+}
+
+impl FromStr for NetemProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (delay, loss) = match s.split_once(':') {
+            Some((delay, loss)) => (delay, Some(loss)),
+            None => (s, None),
+        };
+        let delay_ms: f64 = delay
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid delay '{}' (expected milliseconds)", delay))?;
+        let loss_pct: f64 = match loss {
+            Some(loss) => loss
+                .trim()
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| format!("invalid loss '{}' (expected a percentage)", loss))?,
+            None => 0.0,
+        };
+
+        if !delay_ms.is_finite() || delay_ms < 0.0 {
+            return Err("delay must be >= 0".into());
+        }
+        if !(0.0..=100.0).contains(&loss_pct) {
+            return Err("loss must be between 0 and 100".into());
+        }
+        Ok(Self {
+            delay: Duration::from_secs_f64(delay_ms / 1000.0),
+            loss_pct,
+        })
+    }
+}
+
+impl fmt::Display for NetemProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "delay {:.1}ms", self.delay.as_secs_f64() * 1000.0)?;
+        if self.loss_pct > 0.0 {
+            write!(f, " loss {}%", self.loss_pct)?;
+        }
+        Ok(())
+    }
+}
+
+/// Root netem qdisc on an interface, removed again when dropped
+pub struct NetemQdisc {
+    interface: String,
+}
+
+impl NetemQdisc {
+    /// Apply `profile` as the root qdisc of `interface`
+    ///
+    /// Uses `tc qdisc add`, so an interface that already has a root qdisc is left
+    /// untouched and an error is returned instead.
+    pub fn apply(interface: &str, profile: &NetemProfile) -> Result<Self> {
+        run_tc(&profile.tc_args(interface))?;
+        info!(interface = interface, profile = %profile, "Netem qdisc applied");
+        Ok(Self {
+            interface: interface.to_string(),
+        })
+    }
+}
+
+impl Drop for NetemQdisc {
+    fn drop(&mut self) {
+        let args: Vec<String> = ["qdisc", "del", "dev", &self.interface, "root"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        match run_tc(&args) {
+            Ok(()) => debug!(interface = %self.interface, "Netem qdisc removed"),
+            Err(e) => warn!(
+                interface = %self.interface,
+                error = %e,
+                "Failed to remove netem qdisc; remove it with `tc qdisc del dev {} root`",
+                self.interface
+            ),
+        }
+    }
+}
+
+/// Run `tc` with `args`, turning a non-zero exit into an error carrying its stderr
+fn run_tc(args: &[String]) -> Result<()> {
+    debug!(args = ?args, "Running tc");
+    let output = Command::new("tc").args(args).output().map_err(|e| {
+        ClientError::Config(format!("failed to run tc (is iproute2 installed?): {}", e))
+    })?;
+    if !output.status.success() {
+        return Err(ClientError::Measurement(format!(
+            "tc {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Settings shared by every measurement of a sweep
+#[derive(Debug, Clone)]
+pub struct SweepSettings {
+    /// Server address to measure against
+    pub server: String,
+    /// Interface the profiles are applied to
+    pub interface: String,
+    /// Socket read timeout
+    pub timeout: Duration,
+    /// Warmup packets before each measurement
+    pub warmup: usize,
+    /// Packets measured per profile
+    pub packets: usize,
+    /// Transport to probe over
+    pub transport: Transport,
+    /// Size of every probe in bytes
+    pub payload_size: usize,
+}
+
+/// Outcome of one profile of a sweep
+#[derive(Debug, Clone)]
+pub struct SweepResult {
+    /// Injected profile (`None` for the unimpaired baseline)
+    pub profile: Option<NetemProfile>,
+    /// Median RTT in nanoseconds
+    pub p50_ns: u64,
+    /// 99th percentile RTT in nanoseconds
+    pub p99_ns: u64,
+    /// Measured loss percentage
+    pub loss_pct: f64,
+}
+
+/// Run a baseline measurement followed by one measurement per profile
+///
+/// Every measurement uses a fresh connection so late replies from a previous profile
+/// cannot be mistaken for replies of the next one.
+pub fn run_sweep(settings: &SweepSettings, profiles: &[NetemProfile]) -> Result<Vec<SweepResult>> {
+    if !cfg!(target_os = "linux") {
+        return Err(ClientError::Config(
+            "impairment sweeps require Linux (tc/netem)".into(),
+        ));
+    }
+
+    let mut results = Vec::with_capacity(profiles.len() + 1);
+    info!("Measuring unimpaired baseline");
+    results.push(measure_profile(settings, None)?);
+
+    for profile in profiles {
+        let _qdisc = NetemQdisc::apply(&settings.interface, profile)?;
+        thread::sleep(Duration::from_millis(QDISC_SETTLE_MS));
+        results.push(measure_profile(settings, Some(*profile))?);
+    }
+    Ok(results)
+}
+
+/// Measure the server under the currently applied profile
+fn measure_profile(settings: &SweepSettings, profile: Option<NetemProfile>) -> Result<SweepResult> {
+    let mut socket: Box<dyn NetworkSocket> = match settings.transport {
+        Transport::Tcp => Box::new(TcpNetworkSocket::connect(&settings.server)?),
+        Transport::Udp => Box::new(UdpNetworkSocket::connect(&settings.server)?),
+    };
+    socket.set_timeout(settings.timeout)?;
+    warmup_phase(
+        &mut socket,
+        Warmup::Count(settings.warmup),
+        DEFAULT_WARMUP_TOLERANCE_PCT,
+        settings.payload_size,
+        true,
+    )?;
+    let mut options = MeasurementOptions::new(settings.packets, settings.packets, true);
+    options.payload_size = settings.payload_size;
+    let result = measurement_phase_with_options(&mut socket, options)?;
+
+    let stats = Statistics::new(&result.latencies)?;
+    let sweep_result = SweepResult {
+        profile,
+        p50_ns: stats.percentile(0.5),
+        p99_ns: stats.percentile(0.99),
+        loss_pct: result.lost_packets as f64 / result.total_packets as f64 * 100.0,
+    };
+    info!(
+        profile = %profile.map(|p| p.to_string()).unwrap_or_else(|| "baseline".into()),
+        p50_ns = sweep_result.p50_ns,
+        loss_pct = sweep_result.loss_pct,
+        "Sweep profile measured"
+    );
+    Ok(sweep_result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netem_profile_parse() {
+        let profile: NetemProfile = "5".parse().unwrap();
+        assert_eq!(profile.delay, Duration::from_millis(5));
+        assert_eq!(profile.loss_pct, 0.0);
+
+        let profile: NetemProfile = "0.5:1.5%".parse().unwrap();
+        assert_eq!(profile.delay, Duration::from_micros(500));
+        assert_eq!(profile.loss_pct, 1.5);
+
+        assert!("abc".parse::<NetemProfile>().is_err());
+        assert!("-1".parse::<NetemProfile>().is_err());
+        assert!("5:150".parse::<NetemProfile>().is_err());
+    }
+
+    #[test]
+    fn test_netem_profile_tc_args() {
+        let profile: NetemProfile = "10:2".parse().unwrap();
+        assert_eq!(
+            profile.tc_args("lo").join(" "),
+            "qdisc add dev lo root netem delay 10000us loss 2%"
+        );
+
+        let profile: NetemProfile = "1".parse().unwrap();
+        assert_eq!(
+            profile.tc_args("eth0").join(" "),
+            "qdisc add dev eth0 root netem delay 1000us"
+        );
+    }
+
+    #[test]
+    fn test_netem_profile_expected_values() {
+        let profile: NetemProfile = "5:10".parse().unwrap();
+        assert_eq!(profile.expected_delay(2), Duration::from_millis(10));
+        assert!((profile.expected_loss_pct(1) - 10.0).abs() < 1e-9);
+        assert!((profile.expected_loss_pct(2) - 19.0).abs() < 1e-9);
+    }
+}
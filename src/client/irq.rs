@@ -0,0 +1,443 @@
+//! NIC interrupt affinity checks
+//!
+//! Where the receive interrupts of the NIC are handled matters for latency. A measuring
+//! thread pinned to the core that services the RX interrupts competes with the softirq
+//! processing of its own replies, which inflates and spreads the measured RTT. A thread
+//! pinned to a different NUMA node than the NIC pays a cross-node hop on every packet,
+//! which shifts the whole distribution up. [`IrqAffinity`] reads the relevant affinities
+//! from `/proc` and `/sys` and reports both setups. Linux only.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use tracing::debug;
+
+/// Interrupt affinities of the NIC carrying the connection and of the measuring thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrqAffinity {
+    /// Interface the connection leaves through
+    pub interface: String,
+    /// RX interrupt numbers of the interface
+    pub rx_irqs: Vec<u32>,
+    /// CPUs the RX interrupts are delivered to
+    pub irq_cpus: BTreeSet<usize>,
+    /// NUMA node of the NIC, if the platform reports one
+    pub nic_numa_node: Option<usize>,
+    /// CPUs the measuring thread may run on
+    pub thread_cpus: BTreeSet<usize>,
+    /// CPUs online on the host
+    pub online_cpus: BTreeSet<usize>,
+    /// NUMA node of every CPU that reports one
+    pub cpu_nodes: BTreeMap<usize, usize>,
+}
+
+/// A placement of the measuring thread that distorts results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AffinityWarning {
+    /// The thread is pinned to CPUs that also handle the NIC's RX interrupts
+    SharedCore {
+        /// Interface whose interrupts are shared
+        interface: String,
+        /// CPUs that run both the thread and the RX interrupts
+        cpus: Vec<usize>,
+    },
+    /// The thread is pinned to a different NUMA node than the NIC
+    RemoteNumaNode {
+        /// Interface of the NIC
+        interface: String,
+        /// NUMA node of the NIC
+        nic_node: usize,
+        /// NUMA nodes the thread may run on
+        thread_nodes: Vec<usize>,
+    },
+}
+
+impl fmt::Display for AffinityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SharedCore { interface, cpus } => write!(
+                f,
+                "The measuring thread is pinned to CPU {}, which also handles the RX interrupts of {}; \
+                 softirq processing of the replies competes with the client and inflates the RTT",
+                format_cpu_list(cpus),
+                interface
+            ),
+            Self::RemoteNumaNode {
+                interface,
+                nic_node,
+                thread_nodes,
+            } => write!(
+                f,
+                "The measuring thread is pinned to NUMA node {} but {} is attached to node {}; \
+                 every packet crosses the interconnect, which shifts all latencies up",
+                format_cpu_list(thread_nodes),
+                interface,
+                nic_node
+            ),
+        }
+    }
+}
+
+impl IrqAffinity {
+    /// Read the affinities for the connection bound to `local_ip`
+    ///
+    /// Returns `None` if the interface cannot be determined or has no RX interrupts of its
+    /// own (loopback, most virtual interfaces) and on non-Linux systems.
+    pub fn detect(local_ip: IpAddr) -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let interface = interface_for_ip(local_ip)?;
+        let device_dir = Path::new("/sys/class/net").join(&interface).join("device");
+
+        // Interrupt names use either the interface or the underlying device (virtio3-input.0)
+        let mut names = vec![interface.clone()];
+        if let Some(device) = fs::canonicalize(&device_dir)
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        {
+            names.push(device);
+        }
+
+        let interrupts = fs::read_to_string("/proc/interrupts").ok()?;
+        let rx_irqs = parse_rx_irqs(&interrupts, &names);
+        if rx_irqs.is_empty() {
+            debug!(interface = %interface, "No RX interrupts found for interface");
+            return None;
+        }
+
+        let irq_cpus = rx_irqs
+            .iter()
+            .filter_map(|irq| {
+                let dir = Path::new("/proc/irq").join(irq.to_string());
+                fs::read_to_string(dir.join("effective_affinity_list"))
+                    .or_else(|_| fs::read_to_string(dir.join("smp_affinity_list")))
+                    .ok()
+                    .and_then(|list| parse_cpu_list(&list))
+            })
+            .flatten()
+            .collect();
+        let nic_numa_node = fs::read_to_string(device_dir.join("numa_node"))
+            .ok()
+            .and_then(|node| node.trim().parse::<i64>().ok())
+            .and_then(|node| usize::try_from(node).ok());
+        let thread_cpus = fs::read_to_string("/proc/thread-self/status")
+            .ok()
+            .and_then(|status| parse_cpus_allowed(&status))?;
+        let online_cpus = fs::read_to_string("/sys/devices/system/cpu/online")
+            .ok()
+            .and_then(|list| parse_cpu_list(&list))?;
+        let cpu_nodes = online_cpus
+            .iter()
+            .filter_map(|&cpu| cpu_node(cpu).map(|node| (cpu, node)))
+            .collect();
+
+        let affinity = Self {
+            interface,
+            rx_irqs,
+            irq_cpus,
+            nic_numa_node,
+            thread_cpus,
+            online_cpus,
+            cpu_nodes,
+        };
+        debug!(affinity = ?affinity, "IRQ affinity detected");
+        Some(affinity)
+    }
+
+    /// Whether the measuring thread is restricted to a subset of the online CPUs
+    pub fn is_pinned(&self) -> bool {
+        !self.online_cpus.is_subset(&self.thread_cpus)
+    }
+
+    /// Placements of the pinned measuring thread that distort results
+    ///
+    /// An unpinned thread is left to the scheduler, which moves it away from busy cores,
+    /// so no warning is raised for it.
+    pub fn warnings(&self) -> Vec<AffinityWarning> {
+        let mut warnings = Vec::new();
+        if !self.is_pinned() {
+            return warnings;
+        }
+
+        let shared: Vec<usize> = self
+            .thread_cpus
+            .intersection(&self.irq_cpus)
+            .copied()
+            .collect();
+        if !shared.is_empty() {
+            warnings.push(AffinityWarning::SharedCore {
+                interface: self.interface.clone(),
+                cpus: shared,
+            });
+        }
+
+        if let Some(nic_node) = self.nic_numa_node {
+            let thread_nodes: BTreeSet<usize> = self
+                .thread_cpus
+                .iter()
+                .filter_map(|cpu| self.cpu_nodes.get(cpu).copied())
+                .collect();
+            if !thread_nodes.is_empty() && !thread_nodes.contains(&nic_node) {
+                warnings.push(AffinityWarning::RemoteNumaNode {
+                    interface: self.interface.clone(),
+                    nic_node,
+                    thread_nodes: thread_nodes.into_iter().collect(),
+                });
+            }
+        }
+        warnings
+    }
+}
+
+/// Name of the interface that has `ip` assigned
+#[cfg(target_os = "linux")]
+pub(crate) fn interface_for_ip(ip: IpAddr) -> Option<String> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates the list and stores its head in `addrs`
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return None;
+    }
+
+    let mut found = None;
+    let mut cursor = addrs;
+    while !cursor.is_null() {
+        // SAFETY: every node of the list returned by getifaddrs is valid until freeifaddrs
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null() {
+            continue;
+        }
+        // SAFETY: ifa_addr is non-null and its family tells which sockaddr type it points to
+        let address = unsafe {
+            match (*entry.ifa_addr).sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let sin = &*(entry.ifa_addr as *const libc::sockaddr_in);
+                    Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        sin.sin_addr.s_addr,
+                    ))))
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(entry.ifa_addr as *const libc::sockaddr_in6);
+                    Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+                }
+                _ => None,
+            }
+        };
+        if address == Some(ip) {
+            // SAFETY: ifa_name is a NUL-terminated string owned by the list
+            let name = unsafe { CStr::from_ptr(entry.ifa_name) };
+            found = Some(name.to_string_lossy().into_owned());
+            break;
+        }
+    }
+    // SAFETY: `addrs` was returned by getifaddrs and is freed exactly once
+    unsafe { libc::freeifaddrs(addrs) };
+    found
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn interface_for_ip(_ip: IpAddr) -> Option<String> {
+    None
+}
+
+/// NUMA node of `cpu`, from the `nodeN` entry of its sysfs directory
+fn cpu_node(cpu: usize) -> Option<usize> {
+    fs::read_dir(format!("/sys/devices/system/cpu/cpu{}", cpu))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()
+        })
+}
+
+/// RX interrupt numbers of the device known by any of `names` in `/proc/interrupts`
+///
+/// Queue interrupts are named `<name>-rx-0`, `<name>-TxRx-0` or `<name>-input.0`. If the
+/// driver names none of them as RX, every interrupt of the device except the TX, config
+/// and control ones is taken.
+fn parse_rx_irqs(interrupts: &str, names: &[String]) -> Vec<u32> {
+    let device_irqs: Vec<(u32, String)> = interrupts
+        .lines()
+        .filter_map(|line| {
+            let (irq, rest) = line.trim_start().split_once(':')?;
+            let irq: u32 = irq.parse().ok()?;
+            let action = rest.split_whitespace().last()?.to_lowercase();
+            let matches = names.iter().any(|name| {
+                let name = name.to_lowercase();
+                action == name
+                    || action
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|suffix| suffix.starts_with(['-', '@']))
+            });
+            matches.then_some((irq, action))
+        })
+        .collect();
+
+    let rx: Vec<u32> = device_irqs
+        .iter()
+        .filter(|(_, action)| action.contains("rx") || action.contains("input"))
+        .map(|(irq, _)| *irq)
+        .collect();
+    if !rx.is_empty() {
+        return rx;
+    }
+    device_irqs
+        .into_iter()
+        .filter(|(_, action)| {
+            !["tx", "output", "config", "ctrl", "event"]
+                .iter()
+                .any(|kind| action.contains(kind))
+        })
+        .map(|(irq, _)| irq)
+        .collect()
+}
+
+/// CPUs of the `Cpus_allowed_list` line of `/proc/<pid>/status`
+fn parse_cpus_allowed(status: &str) -> Option<BTreeSet<usize>> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+        .and_then(parse_cpu_list)
+}
+
+/// Parse a kernel CPU list such as `0-3,8,10-11`
+pub(crate) fn parse_cpu_list(list: &str) -> Option<BTreeSet<usize>> {
+    let mut cpus = BTreeSet::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().ok()?;
+                let last: usize = last.trim().parse().ok()?;
+                cpus.extend(first..=last);
+            }
+            None => {
+                cpus.insert(part.trim().parse().ok()?);
+            }
+        }
+    }
+    Some(cpus)
+}
+
+/// Format CPU (or node) numbers compactly (`0-3,8`)
+pub(crate) fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERRUPTS: &str = "           CPU0       CPU1
+ 39:          0          0 PCI-MSIX-0000:00:04.0   0-edge      virtio3-config
+ 40:        357         12 PCI-MSIX-0000:00:04.0   1-edge      virtio3-input.0
+ 41:        351          9 PCI-MSIX-0000:00:04.0   2-edge      virtio3-output.0
+ 43:       1627          0 PCI-MSIX-0000:00:05.0   1-edge      eth1-TxRx-0
+ 44:       5042          0 PCI-MSIX-0000:00:05.0   2-edge      eth1-TxRx-1
+ 45:          1          0 PCI-MSIX-0000:00:05.0   3-edge      eth1
+ 46:          3          0 PCI-MSIX-0000:00:06.0   1-edge      eth10-rx-0
+NMI:          0          0   Non-maskable interrupts
+";
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn affinity(thread_cpus: &[usize], nic_numa_node: Option<usize>) -> IrqAffinity {
+        IrqAffinity {
+            interface: "eth0".into(),
+            rx_irqs: vec![40],
+            irq_cpus: [2].into(),
+            nic_numa_node,
+            thread_cpus: thread_cpus.iter().copied().collect(),
+            online_cpus: (0..8).collect(),
+            cpu_nodes: (0..8).map(|cpu| (cpu, cpu / 4)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_rx_irqs() {
+        assert_eq!(
+            parse_rx_irqs(INTERRUPTS, &names(&["eth0", "virtio3"])),
+            vec![40]
+        );
+        assert_eq!(parse_rx_irqs(INTERRUPTS, &names(&["eth1"])), vec![43, 44]);
+        assert_eq!(parse_rx_irqs(INTERRUPTS, &names(&["eth10"])), vec![46]);
+        assert!(parse_rx_irqs(INTERRUPTS, &names(&["lo"])).is_empty());
+    }
+
+    #[test]
+    fn test_parse_cpu_lists() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n"),
+            Some([0, 1, 2, 3, 8, 10, 11].into())
+        );
+        assert_eq!(parse_cpu_list("x"), None);
+        assert_eq!(
+            parse_cpus_allowed("Name:\tclient\nCpus_allowed_list:\t2-3\n"),
+            Some([2, 3].into())
+        );
+        assert_eq!(format_cpu_list(&[0, 1, 2, 3, 8, 10, 11]), "0-3,8,10-11");
+    }
+
+    #[test]
+    fn test_irq_affinity_warnings() {
+        // Unpinned threads are left to the scheduler
+        assert!(affinity(&[0, 1, 2, 3, 4, 5, 6, 7], Some(1))
+            .warnings()
+            .is_empty());
+
+        // Pinned to the RX interrupt core, on the NIC's node
+        let warnings = affinity(&[2], Some(0)).warnings();
+        assert_eq!(
+            warnings,
+            vec![AffinityWarning::SharedCore {
+                interface: "eth0".into(),
+                cpus: vec![2],
+            }]
+        );
+        assert!(warnings[0].to_string().contains("CPU 2"));
+
+        // Pinned away from the interrupts but to the other node
+        let warnings = affinity(&[5, 6], Some(0)).warnings();
+        assert_eq!(
+            warnings,
+            vec![AffinityWarning::RemoteNumaNode {
+                interface: "eth0".into(),
+                nic_node: 0,
+                thread_nodes: vec![1],
+            }]
+        );
+
+        // A well-placed thread and a NIC without NUMA information
+        assert!(affinity(&[3], Some(0)).warnings().is_empty());
+        assert!(affinity(&[5], None).warnings().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::io::{self, IsTerminal};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 /// Initialize structured logging with default settings
@@ -47,10 +48,12 @@ pub fn init_logging_with_config(log_level: &str, json_format: bool) {
             )
             .init();
     } else {
-        // Human-readable text format for development
+        // Human-readable text format for development; colored only on a terminal, as
+        // escape codes would end up verbatim in log files and CI logs
         registry
             .with(
                 fmt::layer()
+                    .with_ansi(io::stdout().is_terminal())
                     .with_target(false)
                     .with_thread_ids(true)
                     .with_line_number(true)
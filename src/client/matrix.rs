@@ -0,0 +1,365 @@
+//! Multi-run experiment matrix
+//!
+//! `client matrix --vary packets=1000,10000 --vary timeout-ms=50,200` runs the cartesian
+//! product of the varied client flags, stores each run as a [`RunRecord`] and emits a
+//! combined table and CSV. Every `--vary` name is a long client flag; the remaining flags
+//! given before the subcommand apply to every run.
+//!
+//! [`RunRecord`]: crate::client::record::RunRecord
+
+use crate::client::config::Config;
+use crate::client::error::{ClientError, Result};
+use crate::client::output::RunSummary;
+use crate::client::redact::Redactor;
+use clap::CommandFactory;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::debug;
+
+/// One varied flag and the values it takes, written as `NAME=V1,V2,...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixParameter {
+    /// Long client flag without the leading dashes
+    pub name: String,
+    /// Values the flag takes, with `k`/`M` suffixes expanded
+    pub values: Vec<String>,
+}
+
+impl FromStr for MatrixParameter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, values) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=V1,V2,... but got '{}'", s))?;
+        let name = name.trim().trim_start_matches("--").to_string();
+        if name.is_empty() {
+            return Err("parameter name must not be empty".into());
+        }
+        let values: Vec<String> = values
+            .split(',')
+            .map(|v| expand_suffix(v.trim()))
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            return Err(format!("parameter '{}' has no values", name));
+        }
+        Ok(Self { name, values })
+    }
+}
+
+/// Expand a `k`/`M` suffix on a number (`10k` -> `10000`); other values are kept as-is
+fn expand_suffix(value: &str) -> String {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'k')) | Some((i, 'K')) => (&value[..i], 1_000.0),
+        Some((i, 'M')) => (&value[..i], 1_000_000.0),
+        _ => return value.to_string(),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0.0 => format!("{}", (n * multiplier).round() as u64),
+        _ => value.to_string(),
+    }
+}
+
+/// Values of every varied flag for one run, in `--vary` order
+pub type Combination = Vec<(String, String)>;
+
+/// Cartesian product of all parameter values
+pub fn combinations(parameters: &[MatrixParameter]) -> Vec<Combination> {
+    parameters.iter().fold(vec![Vec::new()], |acc, parameter| {
+        acc.iter()
+            .flat_map(|prefix| {
+                parameter.values.iter().map(move |value| {
+                    let mut combination = prefix.clone();
+                    combination.push((parameter.name.clone(), value.clone()));
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+/// Human-readable label of a combination (`packets=1000 timeout-ms=50`)
+pub fn label(combination: &Combination) -> String {
+    combination
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `combination` with a varied server address replaced by its `--redact` token
+pub fn redacted(combination: &Combination, redactor: &Redactor) -> Combination {
+    combination
+        .iter()
+        .map(|(name, value)| match name.as_str() {
+            "server" => (name.clone(), redactor.address(value)),
+            _ => (name.clone(), value.clone()),
+        })
+        .collect()
+}
+
+/// Client flags given before the `matrix` subcommand
+///
+/// `args` are the process arguments without the program name. Flag values are skipped
+/// using the client's own argument definitions, so a value that happens to read
+/// "matrix" is not mistaken for the subcommand.
+pub fn base_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let command = Config::command();
+    let mut base = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            break; // the subcommand
+        }
+        let takes_value = !arg.contains('=')
+            && command
+                .get_arguments()
+                .find(|a| a.get_long() == Some(arg.trim_start_matches("--")))
+                .is_some_and(|a| a.get_action().takes_values());
+        base.push(arg);
+        if takes_value {
+            if let Some(value) = args.next() {
+                base.push(value);
+            }
+        }
+    }
+    base
+}
+
+/// Build the configuration of one run: the base flags overridden by the combination
+///
+/// Runs are always quiet; the matrix prints its own progress and table.
+pub fn run_config(base_args: &[String], combination: &Combination) -> Result<Config> {
+    let mut argv = vec!["synapse-client".to_string()];
+    argv.extend(base_args.iter().cloned());
+    for (name, value) in combination {
+        argv.push(format!("--{}", name));
+        argv.push(value.clone());
+    }
+    debug!(argv = ?argv, "Building matrix run configuration");
+
+    let mut config = Config::try_load_from(argv).map_err(|e| {
+        ClientError::Config(format!(
+            "invalid matrix run '{}': {}",
+            label(combination),
+            e.render()
+                .to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+        ))
+    })?;
+    config.quiet = true;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Result of one run of the matrix
+#[derive(Debug, Clone)]
+pub struct MatrixRow {
+    /// Values of the varied flags
+    pub combination: Combination,
+    /// Summary of the run, or the error that stopped it
+    pub outcome: std::result::Result<RunSummary, String>,
+    /// Run record written for this run, if it completed
+    pub record_path: Option<PathBuf>,
+}
+
+/// File name of the run record of run `index` (1-based)
+pub fn record_file_name(index: usize, combination: &Combination) -> String {
+    let label: String = label(combination)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '=' || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("run-{:03}-{}.json", index, label)
+}
+
+/// Write the combined results as CSV, one row per run
+pub fn write_csv(path: &Path, parameters: &[MatrixParameter], rows: &[MatrixRow]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    let mut header: Vec<String> = parameters.iter().map(|p| csv_field(&p.name)).collect();
+    header.extend(
+        [
+            "status", "sent", "lost", "loss_pct", "mean_us", "p50_us", "p99_us", "max_us", "record",
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    writeln!(writer, "{}", header.join(","))?;
+
+    for row in rows {
+        let mut fields: Vec<String> = row.combination.iter().map(|(_, v)| csv_field(v)).collect();
+        match &row.outcome {
+            Ok(summary) => fields.extend([
+                "ok".to_string(),
+                summary.total_packets.to_string(),
+                summary.lost_packets.to_string(),
+                format!("{:.4}", summary.loss_pct()),
+                format!("{:.3}", summary.mean_ns / 1000.0),
+                format!("{:.3}", summary.p50_ns as f64 / 1000.0),
+                format!("{:.3}", summary.p99_ns as f64 / 1000.0),
+                format!("{:.3}", summary.max_ns as f64 / 1000.0),
+            ]),
+            Err(e) => {
+                fields.push(csv_field(&format!("error: {}", e)));
+                fields.extend(std::iter::repeat_n(String::new(), 7));
+            }
+        }
+        fields.push(
+            row.record_path
+                .as_ref()
+                .map(|p| csv_field(&p.display().to_string()))
+                .unwrap_or_default(),
+        );
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::warmup::Warmup;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matrix_parameter_parse() {
+        let parameter: MatrixParameter = "rate=1k,10k,2.5M".parse().unwrap();
+        assert_eq!(parameter.name, "rate");
+        assert_eq!(parameter.values, vec!["1000", "10000", "2500000"]);
+
+        let parameter: MatrixParameter = "--log-format=text,json".parse().unwrap();
+        assert_eq!(parameter.name, "log-format");
+        assert_eq!(parameter.values, vec!["text", "json"]);
+
+        assert!("packets".parse::<MatrixParameter>().is_err());
+        assert!("packets=".parse::<MatrixParameter>().is_err());
+    }
+
+    #[test]
+    fn test_matrix_combinations() {
+        let parameters = vec![
+            "packets=10,20".parse().unwrap(),
+            "timeout-ms=50,100,200".parse().unwrap(),
+        ];
+        let runs = combinations(&parameters);
+
+        assert_eq!(runs.len(), 6);
+        assert_eq!(label(&runs[0]), "packets=10 timeout-ms=50");
+        assert_eq!(label(&runs[5]), "packets=20 timeout-ms=200");
+        assert_eq!(combinations(&[]), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn test_matrix_base_args_and_run_config() -> Result<()> {
+        let base = base_args(strings(&[
+            "--server",
+            "matrix",
+            "--warmup=10",
+            "--quiet",
+            "matrix",
+            "--vary",
+            "packets=5",
+        ]));
+        assert_eq!(
+            base,
+            strings(&["--server", "matrix", "--warmup=10", "--quiet"])
+        );
+
+        let combination = vec![("packets".to_string(), "5".to_string())];
+        let config = run_config(&strings(&["--packets", "99", "--warmup=10"]), &combination)?;
+        assert_eq!(config.packets, 5);
+        assert_eq!(config.warmup, Warmup::Count(10));
+        assert!(config.quiet);
+
+        let unknown = vec![("no-such-flag".to_string(), "1".to_string())];
+        assert!(matches!(
+            run_config(&[], &unknown),
+            Err(ClientError::Config(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_matrix_write_csv() -> Result<()> {
+        use crate::client::statistics::Statistics;
+
+        let parameters: Vec<MatrixParameter> = vec!["packets=3,4".parse().unwrap()];
+        let stats = Statistics::new(&[1000, 2000, 3000])?;
+        let rows = vec![
+            MatrixRow {
+                combination: vec![("packets".into(), "3".into())],
+                outcome: Ok(RunSummary::new(&stats, 0, 3, &[])),
+                record_path: Some(PathBuf::from("run-001.json")),
+            },
+            MatrixRow {
+                combination: vec![("packets".into(), "4".into())],
+                outcome: Err("connection refused, retry".into()),
+                record_path: None,
+            },
+        ];
+
+        let path = std::env::temp_dir().join(format!("synapse-{}-matrix.csv", std::process::id()));
+        write_csv(&path, &parameters, &rows)?;
+        let csv = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("packets,status,sent"));
+        assert!(lines[1].starts_with("3,ok,3,0,"));
+        assert!(lines[1].ends_with(",run-001.json"));
+        assert!(lines[2].starts_with("4,\"error: connection refused, retry\","));
+        Ok(())
+    }
+
+    #[test]
+    fn test_matrix_record_file_name() {
+        let combination = vec![
+            ("packets".to_string(), "1000".to_string()),
+            ("server".to_string(), "10.0.0.1:80".to_string()),
+        ];
+        assert_eq!(
+            record_file_name(7, &combination),
+            "run-007-packets=1000_server=10.0.0.1_80.json"
+        );
+    }
+
+    #[test]
+    fn test_matrix_redacted() {
+        let redactor = Redactor::new(None);
+        let combination = vec![
+            ("packets".to_string(), "1000".to_string()),
+            ("server".to_string(), "10.0.0.1:80".to_string()),
+        ];
+        let redacted = redacted(&combination, &redactor);
+        assert_eq!(redacted[0], combination[0]);
+        assert_eq!(redacted[1].1, redactor.address("10.0.0.1:80"));
+        assert!(!record_file_name(1, &redacted).contains("10.0.0.1"));
+    }
+}
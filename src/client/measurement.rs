@@ -1,93 +1,398 @@
+use crate::client::checkpoint::Checkpointer;
+use crate::client::clock::Clock;
+use crate::client::confidence::{CiStop, CiTarget};
+use crate::client::constants::{LIVE_P99_WINDOW_SIZE, SEND_STALL_THRESHOLD_NS};
+#[cfg(feature = "dashboard")]
+use crate::client::dashboard::Dashboard;
+use crate::client::decomposition::Decomposition;
+use crate::client::dscp::Dscp;
 use crate::client::error::{ClientError, Result};
+use crate::client::heartbeat::Heartbeat;
+use crate::client::hugepage::{PageBacking, SampleBuffer};
+use crate::client::open_loop::ProbeWindow;
+use crate::client::pacing::{Pacer, PacingStats, TrafficModel};
 use crate::client::progress::ProgressTracker;
+use crate::client::progress_stream::ProgressStream;
+use crate::client::raw_output::RawOutput;
+use crate::client::rng::random_seed;
+use crate::client::rusage::ThreadUsage;
 use crate::client::socket::NetworkSocket;
-use crate::protocol::{Packet, SequenceNumber};
+use crate::client::sysmon::{SystemMonitor, SystemTimeline};
+use crate::client::theme::Theme;
+use crate::client::transaction::{self, Transaction};
+use crate::client::warmup::{Settling, Warmup, SETTLING_WINDOW};
+use crate::protocol::{
+    unix_time_ns, ClockOffset, ClockSample, Packet, SequenceNumber, PACKET_SIZE,
+};
+use std::collections::BTreeMap;
 use std::io::{self, Write};
-use std::time::{Duration, Instant};
-use tracing::{debug, warn};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info, warn};
 
 /// Represents a single measurement result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Measurement {
     pub sequence: SequenceNumber,
     pub latency_ns: u64,
-    pub timestamp: Instant,
+    /// Wall-clock time the probe was sent
+    pub timestamp: SystemTime,
 }
 
 /// Results from a complete measurement phase
 #[derive(Debug, Clone)]
 pub struct MeasurementResult {
     pub latencies: Vec<u64>,
+    /// Time spent inside `send_packet` for every packet sent, in nanoseconds
+    pub send_durations: Vec<u64>,
+    /// Time from send completion to reply for every successful packet, in nanoseconds
+    pub recv_wait_durations: Vec<u64>,
     pub lost_packets: usize,
     pub total_packets: usize,
     pub elapsed: Duration,
+    /// Seed the run's randomized choices were derived from
+    pub seed: u64,
+    /// Host and scheduler activity during the run, if system monitoring was enabled
+    pub system_timeline: Option<SystemTimeline>,
+    /// Sequence number of each successful measurement, parallel to `latencies`
+    pub sequences: Vec<u64>,
+    /// Send time of each successful measurement in nanoseconds since the start of the
+    /// phase, parallel to `latencies`
+    pub send_offsets: Vec<u64>,
+    /// Time the connection sat idle before each successful measurement (from the end of
+    /// the previous probe to the send), in nanoseconds, parallel to `latencies`
+    pub idle_gaps: Vec<u64>,
+    /// Wall-clock time at the start of the phase, the origin of `send_offsets`
+    pub started_at: SystemTime,
+    /// Start and duration of the warmup before the phase, set by the caller that ran it
+    pub warmup: Option<(SystemTime, Duration)>,
+    /// Indices of the lost packets, in ascending order
+    pub lost_sequences: Vec<u64>,
+    /// CPU time and context switches of the measuring thread during the phase
+    pub thread_usage: Option<ThreadUsage>,
+    /// Pages backing the sample buffers, if huge pages were requested
+    pub sample_backing: Option<PageBacking>,
+    /// Split of every successful round trip the server timestamped (empty if it did not)
+    pub path_timings: Vec<PathTiming>,
+    /// Clock offset `path_timings` were corrected by, if one was estimated
+    pub clock_offset: Option<ClockOffset>,
+    /// Application and kernel RTT of every successful round trip the kernel
+    /// timestamped (empty unless kernel timestamping is enabled)
+    pub kernel_timings: Vec<KernelTiming>,
+    /// Target rate and late sends, if sends were paced
+    pub pacing: Option<PacingStats>,
+    /// Number of successful replies by the DSCP they arrived with (empty unless the
+    /// socket reads reply markings)
+    pub reply_dscp: BTreeMap<Dscp, usize>,
+}
+
+/// Options controlling a measurement phase
+pub struct MeasurementOptions {
+    /// Number of packets to send
+    pub packet_count: usize,
+    /// Dashboard update interval (packets)
+    pub update_interval: usize,
+    /// Disable the terminal UI
+    pub quiet: bool,
+    /// Interval of progress log lines, if any (see [`crate::client::heartbeat`])
+    pub heartbeat: Option<Duration>,
+    /// NDJSON progress writer, if enabled (see [`crate::client::progress_stream`])
+    pub progress_stream: Option<ProgressStream>,
+    /// Periodic checkpoint writer, if checkpointing is enabled
+    pub checkpointer: Option<Checkpointer>,
+    /// Seed for every randomized choice of the run (see [`crate::client::rng`])
+    pub seed: u64,
+    /// Host-noise monitor of the measuring thread, if system monitoring is enabled
+    pub system_monitor: Option<SystemMonitor>,
+    /// Allocate the sample buffers from huge pages (see [`crate::client::hugepage`])
+    pub huge_pages: bool,
+    /// Fail the run at the first lost packet
+    pub strict_loss: bool,
+    /// Size of every probe in bytes, sequence header included
+    pub payload_size: usize,
+    /// Server clock offset to correct one-way times by (see [`clock_sync_phase`])
+    pub clock_offset: Option<ClockOffset>,
+    /// Per-packet CSV writer, if raw output is enabled
+    pub raw_output: Option<RawOutput>,
+    /// Color theme of the live view
+    pub theme: Theme,
+    /// Show the full-screen dashboard in place of the live view (see
+    /// [`crate::client::dashboard`])
+    #[cfg(feature = "dashboard")]
+    pub dashboard: bool,
+    /// Probes per second to pace sends at, or `None` to send each probe as soon as the
+    /// previous one completes (see [`crate::client::pacing`])
+    pub rate: Option<u64>,
+    /// Spacing of paced sends
+    pub traffic: TrafficModel,
+    /// Probes kept in flight; above 1 the phase runs open-loop (see
+    /// [`crate::client::open_loop`])
+    pub window: usize,
+    /// Clock the round trips are timed with (see [`crate::client::clock`])
+    pub clock: Clock,
+    /// Stop before `packet_count` probes once the confidence interval is this narrow (see
+    /// [`crate::client::confidence`]); closed-loop phases only
+    pub until_ci: Option<CiTarget>,
+    /// Measure each probe as a transaction of several requests and responses (see
+    /// [`crate::client::transaction`]); closed-loop phases only
+    pub transaction: Option<Transaction>,
+}
+
+impl MeasurementResult {
+    /// Time series of the successful measurements, in the order they completed
+    ///
+    /// Timestamps are `started_at` plus each send offset, so they line up with server
+    /// logs, cron schedules and GC pauses to the accuracy of the client's wall clock. The
+    /// offsets come from the monotonic clock: a step of the wall clock during the run
+    /// does not reorder them.
+    pub fn samples(&self) -> impl Iterator<Item = Measurement> + '_ {
+        self.latencies
+            .iter()
+            .zip(&self.sequences)
+            .zip(&self.send_offsets)
+            .map(|((&latency_ns, &sequence), &send_offset_ns)| Measurement {
+                sequence: SequenceNumber(sequence),
+                latency_ns,
+                timestamp: self.started_at + Duration::from_nanos(send_offset_ns),
+            })
+    }
+}
+
+impl MeasurementOptions {
+    /// Create options with every optional feature disabled and a fresh random seed
+    pub fn new(packet_count: usize, update_interval: usize, quiet: bool) -> Self {
+        Self {
+            packet_count,
+            update_interval,
+            quiet,
+            heartbeat: None,
+            progress_stream: None,
+            checkpointer: None,
+            seed: random_seed(),
+            system_monitor: None,
+            huge_pages: false,
+            strict_loss: false,
+            payload_size: PACKET_SIZE,
+            clock_offset: None,
+            raw_output: None,
+            theme: Theme::Default,
+            #[cfg(feature = "dashboard")]
+            dashboard: false,
+            rate: None,
+            traffic: TrafficModel::Fixed,
+            window: 1,
+            clock: Clock::default(),
+            until_ci: None,
+            transaction: None,
+        }
+    }
+}
+
+/// Timing of a single probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeTiming {
+    /// Time spent inside the `send_packet` call, in nanoseconds
+    pub send_ns: u64,
+    /// Round-trip latency in nanoseconds (send included), or `None` if the packet was lost
+    pub rtt_ns: Option<u64>,
+    /// Time from send completion until the reply arrived, or `None` if the packet was lost
+    pub recv_wait_ns: Option<u64>,
+    /// Split of the round trip at the server, if the reply carries server timestamps
+    pub path: Option<PathTiming>,
+    /// Round trip as timed by the kernel, if kernel timestamping is enabled
+    pub kernel: Option<KernelTiming>,
+}
+
+/// A round trip split at the server by the timestamps it stamps into the echo
+///
+/// Server time is measured on one clock and always exact. Outbound and return compare
+/// the client's clock with the server's, so they are only as accurate as the two clocks
+/// are synchronized; an offset moves time from one to the other (and can make either
+/// negative) while their sum stays exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathTiming {
+    /// Client send until the server had the probe, in nanoseconds
+    pub outbound_ns: i64,
+    /// Time the probe spent in the server, in nanoseconds
+    pub server_ns: u64,
+    /// Server transmit until the client had the reply, in nanoseconds
+    pub return_ns: i64,
+    /// Time the server took over its previous echo, from reading the probe until the
+    /// write of the echo returned, in nanoseconds; `None` for the first echo, or from a
+    /// server that does not report it
+    pub server_processing_ns: Option<u64>,
+}
+
+impl PathTiming {
+    /// Split a round trip from the client's send and receive times and the server's
+    /// timestamps in the reply, if it carries them
+    pub fn from_reply(client_send_ns: u64, client_receive_ns: u64, reply: &Packet) -> Option<Self> {
+        let receive_ns = reply.server_receive_ns?;
+        let transmit_ns = reply.server_transmit_ns?;
+        Some(Self {
+            outbound_ns: receive_ns as i64 - client_send_ns as i64,
+            server_ns: transmit_ns.saturating_sub(receive_ns),
+            return_ns: client_receive_ns as i64 - transmit_ns as i64,
+            server_processing_ns: reply.server_processing_ns,
+        })
+    }
+
+    /// Move the clock offset out of the outbound and return times
+    pub fn corrected(&self, offset: &ClockOffset) -> Self {
+        Self {
+            outbound_ns: self.outbound_ns - offset.offset_ns,
+            server_ns: self.server_ns,
+            return_ns: self.return_ns + offset.offset_ns,
+            server_processing_ns: self.server_processing_ns,
+        }
+    }
+}
+
+/// A round trip timed by both the application and the kernel
+///
+/// The kernel RTT runs from the probe being handed to the network device until the
+/// reply came off it; the rest of the application RTT was spent in the client's
+/// application, system calls and protocol stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelTiming {
+    /// Round trip measured by the application, in nanoseconds
+    pub rtt_ns: u64,
+    /// Round trip between the kernel send and receive timestamps, in nanoseconds
+    pub kernel_rtt_ns: u64,
+}
+
+impl KernelTiming {
+    /// Part of the round trip spent above the kernel's timestamps, in nanoseconds
+    pub fn application_ns(&self) -> u64 {
+        self.rtt_ns.saturating_sub(self.kernel_rtt_ns)
+    }
 }
 
 /// Measure a single packet round-trip latency
 pub fn measure_single_packet<S: NetworkSocket>(
     socket: &mut S,
-    sequence: SequenceNumber,
+    packet: &Packet,
 ) -> Result<Option<u64>> {
-    let packet = Packet::new(sequence);
-    let t1 = Instant::now();
+    measure_probe(socket, packet, &Clock::default()).map(|probe| probe.rtt_ns)
+}
+
+/// Measure a single packet, timing the send call separately from the round trip
+///
+/// Three timestamps are taken: before the send (`t1`), when the send call returns
+/// (`t_send_done`) and when the reply arrives (`t2`). The RTT splits into the
+/// send-complete component (`t_send_done - t1`) and the receive-wait component
+/// (`t2 - t_send_done`), telling whether latency lives on the outbound or inbound side.
+/// The three are read from `clock`.
+pub fn measure_probe<S: NetworkSocket>(
+    socket: &mut S,
+    packet: &Packet,
+    clock: &Clock,
+) -> Result<ProbeTiming> {
+    let sequence = packet.sequence;
+    let mut packet = packet.clone();
+    socket.wait_to_send(&packet)?;
+    let t1 = clock.now_ns();
+    let client_send_ns = unix_time_ns();
+    packet.client_send_ns = Some(client_send_ns);
 
     debug!("Sending packet");
     socket.send_packet(&packet)?;
+    let t_send_done = clock.now_ns();
+    let send_ns = t_send_done.saturating_sub(t1);
 
-    match socket.recv_packet() {
+    let (rtt_ns, recv_wait_ns, path) = match socket.recv_packet() {
         Ok(recv_packet) => {
-            let t2 = Instant::now();
+            let t2 = clock.now_ns();
+            let client_receive_ns = unix_time_ns();
 
             if recv_packet.sequence == sequence {
-                let latency_ns = (t2 - t1).as_nanos() as u64;
-                debug!(latency_ns = latency_ns, "Packet received successfully");
-                Ok(Some(latency_ns))
+                let latency_ns = t2.saturating_sub(t1);
+                let recv_wait_ns = t2.saturating_sub(t_send_done);
+                debug!(
+                    latency_ns = latency_ns,
+                    send_ns = send_ns,
+                    recv_wait_ns = recv_wait_ns,
+                    "Packet received successfully"
+                );
+                let path = PathTiming::from_reply(client_send_ns, client_receive_ns, &recv_packet);
+                (Some(latency_ns), Some(recv_wait_ns), path)
             } else {
                 warn!(
                     expected = sequence.0,
                     received = recv_packet.sequence.0,
                     "Sequence mismatch"
                 );
-                Ok(None) // Sequence mismatch
+                (None, None, None) // Sequence mismatch
             }
         }
-        Err(ClientError::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut => {
+        // Unix reports an expired SO_RCVTIMEO as EAGAIN (WouldBlock), Windows as TimedOut
+        Err(ClientError::Io(e))
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) =>
+        {
             debug!("Packet receive timeout");
-            Ok(None) // Timeout
+            (None, None, None) // Timeout
         }
         Err(e) => {
             warn!(error = %e, "Error receiving packet");
-            Err(e)
+            return Err(e);
         }
-    }
+    };
+
+    // Taken for every probe, so timestamps of a lost reply never pair with a later one
+    let kernel = socket.kernel_timestamps()?;
+    let kernel = rtt_ns.zip(kernel).map(|(rtt_ns, kernel)| KernelTiming {
+        rtt_ns,
+        kernel_rtt_ns: kernel.rtt_ns(),
+    });
+
+    Ok(ProbeTiming {
+        send_ns,
+        rtt_ns,
+        recv_wait_ns,
+        path,
+        kernel,
+    })
 }
 
 /// Perform warmup phase to stabilize system conditions
 ///
 /// This phase populates ARP tables, warms CPU/OS caches, and establishes
-/// baseline network paths before measurement begins.
+/// baseline network paths before measurement begins. Probes are `payload_size` bytes,
+/// like those of the measurement that follows. With [`Warmup::Auto`] it ends once the
+/// latency settles within `tolerance_pct` percent (see [`crate::client::warmup`]).
+/// Returns the number of probes sent.
 pub fn warmup_phase<S: NetworkSocket>(
     socket: &mut S,
-    warmup_count: usize,
+    warmup: Warmup,
+    tolerance_pct: f64,
+    payload_size: usize,
     quiet: bool,
-) -> Result<()> {
+) -> Result<usize> {
     let spinner_chars = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
     let mut spinner_idx = 0;
     let mut successful_packets = 0usize;
     let mut lost_packets = 0usize;
+    let warmup_count = warmup.max_probes();
+    let mut settling =
+        (warmup == Warmup::Auto).then(|| Settling::new(SETTLING_WINDOW, tolerance_pct));
+    let mut settled = false;
 
     for seq in 0..warmup_count {
-        let sequence = SequenceNumber(seq as u64);
+        let packet = Packet::with_size(SequenceNumber(seq as u64), payload_size);
 
         // Send and receive, but discard results
-        match measure_single_packet(socket, sequence) {
-            Ok(Some(_)) => {
+        let rtt_ns = match measure_single_packet(socket, &packet) {
+            Ok(Some(rtt_ns)) => {
                 successful_packets += 1;
                 debug!(packet_num = seq + 1, "Warmup packet completed");
+                Some(rtt_ns)
             }
             Ok(None) => {
                 lost_packets += 1;
                 warn!(packet_num = seq + 1, "Warmup packet lost or timed out");
+                None
             }
             Err(e) => {
                 // Error occurred - return with context about how many packets were processed
@@ -97,26 +402,120 @@ pub fn warmup_phase<S: NetworkSocket>(
                     actual_packets, successful_packets, lost_packets, e
                 )));
             }
+        };
+        if let Some(settling) = &mut settling {
+            if settling.record(rtt_ns) {
+                settled = true;
+                break;
+            }
         }
 
         // Update spinner every 10 packets for smooth animation (only if not in quiet mode)
         if !quiet && seq % 10 == 0 {
-            print!(
-                "\rWarming up {} ({}/{})",
-                spinner_chars[spinner_idx],
-                seq + 1,
-                warmup_count
-            );
+            match warmup {
+                Warmup::Count(_) => print!(
+                    "\rWarming up {} ({}/{})",
+                    spinner_chars[spinner_idx],
+                    seq + 1,
+                    warmup_count
+                ),
+                Warmup::Auto => print!(
+                    "\rWarming up {} ({} probes, waiting for the latency to settle)",
+                    spinner_chars[spinner_idx],
+                    seq + 1
+                ),
+            }
             io::stdout().flush().map_err(ClientError::Io)?;
             spinner_idx = (spinner_idx + 1) % spinner_chars.len();
         }
     }
 
+    let sent = successful_packets + lost_packets;
+    if settling.is_some() {
+        if settled {
+            info!(probes = sent, "Warmup latency settled");
+        } else {
+            warn!(
+                probes = sent,
+                "Warmup latency did not settle; measuring anyway"
+            );
+        }
+    }
     if !quiet {
-        println!("\rWarming up ✓ ({}/{})", warmup_count, warmup_count);
+        match warmup {
+            Warmup::Count(_) => println!("\rWarming up ✓ ({}/{})", warmup_count, warmup_count),
+            // Padded to clear the longer progress line
+            Warmup::Auto if settled => println!(
+                "\rWarming up ✓ (latency settled after {} probes){:24}",
+                sent, ""
+            ),
+            Warmup::Auto => println!(
+                "\rWarming up ✓ (latency did not settle within {} probes){:16}",
+                sent, ""
+            ),
+        }
         println!();
     }
-    Ok(())
+    Ok(sent)
+}
+
+/// Estimate the offset of the server clock over `exchanges` probes
+///
+/// Each probe is an NTP-style exchange (see [`crate::protocol::clocksync`]); the one
+/// with the lowest delay gives the estimate. Returns `None` if no reply carried server
+/// timestamps.
+pub fn clock_sync_phase<S: NetworkSocket>(
+    socket: &mut S,
+    exchanges: usize,
+    payload_size: usize,
+) -> Result<Option<ClockOffset>> {
+    let mut samples = Vec::with_capacity(exchanges);
+    for seq in 0..exchanges {
+        let mut packet = Packet::with_size(SequenceNumber(seq as u64), payload_size);
+        socket.wait_to_send(&packet)?;
+        let client_send_ns = unix_time_ns();
+        packet.client_send_ns = Some(client_send_ns);
+        socket.send_packet(&packet)?;
+
+        match socket.recv_packet() {
+            Ok(reply) if reply.sequence == packet.sequence => {
+                let client_receive_ns = unix_time_ns();
+                samples.extend(ClockSample::from_reply(
+                    client_send_ns,
+                    &reply,
+                    client_receive_ns,
+                ));
+            }
+            Ok(reply) => {
+                warn!(
+                    expected = seq,
+                    received = reply.sequence.0,
+                    "Sequence mismatch during clock sync"
+                );
+            }
+            Err(ClientError::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) =>
+            {
+                debug!(exchange = seq, "Clock sync exchange timed out");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let offset = ClockOffset::estimate(&samples);
+    match &offset {
+        Some(offset) => info!(
+            offset_ns = offset.offset_ns,
+            uncertainty_ns = offset.uncertainty_ns,
+            samples = offset.samples,
+            "Clock offset estimated"
+        ),
+        None => warn!("Server replies carry no timestamps; clock offset unknown"),
+    }
+    Ok(offset)
 }
 
 /// Perform measurement phase and collect latency statistics
@@ -126,36 +525,154 @@ pub fn measurement_phase<S: NetworkSocket>(
     update_interval: usize,
     quiet: bool,
 ) -> Result<MeasurementResult> {
-    // Pre-allocate vectors
-    let mut latencies = Vec::with_capacity(packet_count);
+    measurement_phase_with_options(
+        socket,
+        MeasurementOptions::new(packet_count, update_interval, quiet),
+    )
+}
+
+/// Perform measurement phase with the given options and collect latency statistics
+pub fn measurement_phase_with_options<S: NetworkSocket>(
+    socket: &mut S,
+    options: MeasurementOptions,
+) -> Result<MeasurementResult> {
+    let MeasurementOptions {
+        packet_count,
+        update_interval,
+        quiet,
+        heartbeat,
+        mut progress_stream,
+        mut checkpointer,
+        seed,
+        system_monitor,
+        huge_pages,
+        strict_loss,
+        payload_size,
+        clock_offset,
+        mut raw_output,
+        theme,
+        #[cfg(feature = "dashboard")]
+        dashboard,
+        rate,
+        traffic,
+        window,
+        clock,
+        until_ci,
+        transaction,
+    } = options;
+
+    if let Some(ref mut c) = checkpointer {
+        c.set_seed(seed);
+    }
+
+    // Pre-allocate buffers
+    let allocate = if huge_pages {
+        SampleBuffer::huge
+    } else {
+        SampleBuffer::with_capacity
+    };
+    let mut latencies = allocate(packet_count);
+    let mut sequences = allocate(packet_count);
+    let mut send_offsets = allocate(packet_count);
+    let mut idle_gaps = allocate(packet_count);
+    let mut send_durations = allocate(packet_count);
+    let mut recv_wait_durations = allocate(packet_count);
+    // Buffers may end up on different backings; report the least favorable one
+    let sample_backing = huge_pages.then(|| {
+        [
+            &latencies,
+            &sequences,
+            &send_offsets,
+            &idle_gaps,
+            &send_durations,
+            &recv_wait_durations,
+        ]
+        .iter()
+        .map(|buffer| buffer.backing())
+        .min()
+        .unwrap_or(PageBacking::Regular)
+    });
+    if let Some(backing) = sample_backing {
+        info!(backing = %backing, "Sample buffers allocated");
+    }
+    let mut path_timings = Vec::with_capacity(packet_count);
+    let mut kernel_timings = Vec::new();
     let mut lost_packets = 0usize;
+    let mut last_success: Option<u64> = None;
+    let mut lost_sequences = Vec::new();
+    let mut pacer = rate.map(|rate| Pacer::new(rate, traffic, seed));
+    let mut reply_dscp = BTreeMap::new();
 
     let start_time = Instant::now();
+    let started_at = SystemTime::now();
+    // End of the last probe, reply or timeout; the connection is idle from then on
+    let mut idle_since = start_time;
+    let mut probe_window = (window > 1).then(|| {
+        socket.accept_any_reply();
+        ProbeWindow::new(window, packet_count, payload_size, start_time).with_clock(clock)
+    });
+    let usage_at_start = ThreadUsage::current();
+    if let Some(ref mut c) = checkpointer {
+        c.set_started_at(started_at);
+    }
+    if let Some(ref mut r) = raw_output {
+        r.set_started_at(started_at);
+    }
 
-    // Create progress tracker only if not in quiet mode
-    let mut progress = if !quiet {
-        Some(ProgressTracker::new(packet_count, update_interval)?)
+    #[cfg(feature = "dashboard")]
+    let mut dashboard = (!quiet && dashboard)
+        .then(|| Dashboard::open(packet_count, theme))
+        .transpose()?;
+    #[cfg(feature = "dashboard")]
+    let live_view = !quiet && dashboard.is_none();
+    #[cfg(not(feature = "dashboard"))]
+    let live_view = !quiet;
+
+    // Create progress tracker only if not in quiet mode, and the dashboard is not shown
+    let mut progress = if live_view {
+        Some(ProgressTracker::new(packet_count, update_interval)?.with_theme(theme)?)
     } else {
         None
     };
+    let mut heartbeat = heartbeat
+        .map(|interval| Heartbeat::new(interval, packet_count, start_time))
+        .transpose()?;
+    let mut ci_stop = until_ci.map(CiStop::new).transpose()?;
 
     for i in 0..packet_count {
-        let sequence = SequenceNumber(i as u64);
-
-        match measure_single_packet(socket, sequence) {
-            Ok(Some(latency_ns)) => {
-                latencies.push(latency_ns);
-                debug!(
-                    packet_num = i + 1,
-                    latency_ns = latency_ns,
-                    "Measurement packet completed"
-                );
-            }
-            Ok(None) => {
-                lost_packets += 1;
-                warn!(packet_num = i + 1, "Measurement packet lost or timed out");
+        // An open-loop window hands out probes as they complete, in any order; with
+        // probes in flight the connection is never idle
+        let measured = match probe_window {
+            Some(ref mut w) => w
+                .next_completion(socket)
+                .map(|c| (c.sequence, c.send_offset_ns, 0, c.probe)),
+            None => {
+                let sequence = SequenceNumber(i as u64);
+                if let Some(ref mut p) = pacer {
+                    p.wait();
+                }
+                let send_offset_ns = start_time.elapsed().as_nanos() as u64;
+                let idle_ns = idle_since.elapsed().as_nanos() as u64;
+                let packet = Packet::with_size(sequence, payload_size);
+                match transaction {
+                    Some(transaction) => {
+                        transaction::measure_transaction(socket, transaction, &packet, &clock)
+                    }
+                    None => measure_probe(socket, &packet, &clock),
+                }
+                .map(|probe| (sequence, send_offset_ns, idle_ns, probe))
             }
+        };
+        let (sequence, send_offset_ns, idle_ns, probe) = match measured {
+            Ok(measured) => measured,
             Err(e) => {
+                // Preserve what was measured so far before bailing out
+                if let Some(ref mut c) = checkpointer {
+                    if let Err(write_err) = c.write(start_time.elapsed(), false) {
+                        warn!(error = %write_err, "Failed to write final checkpoint");
+                    }
+                }
+
                 // Error occurred - return with context about how many packets were processed
                 let actual_packets = latencies.len() + lost_packets;
                 return Err(ClientError::Measurement(format!(
@@ -166,11 +683,135 @@ pub fn measurement_phase<S: NetworkSocket>(
                     e
                 )));
             }
+        };
+        idle_since = Instant::now();
+
+        // Taken for every probe, so the marking of a lost reply is never counted
+        if let Some(dscp) = socket.reply_dscp().filter(|_| probe.rtt_ns.is_some()) {
+            *reply_dscp.entry(dscp).or_insert(0) += 1;
+        }
+        if let Some(ref mut c) = checkpointer {
+            c.record_timestamps(sequence.0, send_offset_ns, &probe);
+        }
+        if let Some(ref mut r) = raw_output {
+            r.record(sequence.0, send_offset_ns, probe.rtt_ns)?;
+        }
+        send_durations.push(probe.send_ns);
+        if let Some(recv_wait_ns) = probe.recv_wait_ns {
+            recv_wait_durations.push(recv_wait_ns);
+        }
+        if let Some(path) = probe.path {
+            path_timings.push(match &clock_offset {
+                Some(offset) => path.corrected(offset),
+                None => path,
+            });
+        }
+        if let Some(kernel) = probe.kernel {
+            kernel_timings.push(kernel);
+        }
+        if probe.send_ns > SEND_STALL_THRESHOLD_NS {
+            debug!(
+                packet_num = i + 1,
+                send_ns = probe.send_ns,
+                "Send call stalled"
+            );
+        }
+
+        match probe.rtt_ns {
+            Some(latency_ns) => {
+                latencies.push(latency_ns);
+                sequences.push(sequence.0);
+                send_offsets.push(send_offset_ns);
+                idle_gaps.push(idle_ns);
+                last_success = Some(sequence.0);
+                if let Some(ref m) = system_monitor {
+                    m.observe(latency_ns);
+                }
+                debug!(
+                    packet_num = i + 1,
+                    latency_ns = latency_ns,
+                    "Measurement packet completed"
+                );
+            }
+            None => {
+                lost_packets += 1;
+                lost_sequences.push(sequence.0);
+                warn!(packet_num = i + 1, "Measurement packet lost or timed out");
+            }
         }
 
+        if strict_loss && probe.rtt_ns.is_none() {
+            let socket_error = match socket.take_error() {
+                Ok(Some(e)) => e.to_string(),
+                Ok(None) => "none".to_string(),
+                Err(e) => format!("unavailable ({})", e),
+            };
+            let elapsed = start_time.elapsed();
+            if let Some(ref mut c) = checkpointer {
+                if let Err(e) = c.record(&probe).and_then(|_| c.write(elapsed, false)) {
+                    warn!(error = %e, "Failed to write final checkpoint");
+                }
+            }
+            error!(
+                packet_num = i + 1,
+                sequence = sequence.0,
+                last_successful_sequence = ?last_success,
+                socket_error = %socket_error,
+                "Strict loss: packet lost"
+            );
+            return Err(ClientError::StrictLoss(format!(
+                "packet {} of {} (sequence {}) was lost or timed out after {:.3}s; \
+                 last successful sequence: {}; socket error: {}",
+                i + 1,
+                packet_count,
+                sequence.0,
+                elapsed.as_secs_f64(),
+                last_success.map_or("none".to_string(), |s| s.to_string()),
+                socket_error
+            )));
+        }
+
+        // A failed periodic checkpoint must not abort a long-running measurement
+        if let Some(ref mut c) = checkpointer {
+            c.record(&probe)?;
+            if let Err(e) = c.maybe_write(start_time.elapsed()) {
+                warn!(error = %e, path = %c.path().display(), "Failed to write checkpoint");
+            }
+        }
+
+        // Split of the recent RTTs for the live OSI view, made only when it is due
+        let split = || {
+            Decomposition::recent(
+                LIVE_P99_WINDOW_SIZE,
+                &latencies,
+                &send_durations,
+                &kernel_timings,
+                &path_timings,
+            )
+        };
         // Update progress only if not in quiet mode
         if let Some(ref mut p) = progress {
-            p.update(&latencies, start_time, i)?;
+            p.update(&latencies, start_time, i, split)?;
+        }
+        #[cfg(feature = "dashboard")]
+        if let Some(ref mut d) = dashboard {
+            d.record(probe.rtt_ns, split)?;
+        }
+        if let Some(ref mut h) = heartbeat {
+            h.record(probe.rtt_ns)?;
+            h.poll(Instant::now())?;
+        }
+        if let Some(ref mut s) = progress_stream {
+            s.record(probe.rtt_ns, start_time.elapsed())?;
+        }
+        if let Some(ref mut c) = ci_stop {
+            if c.record(probe.rtt_ns)? {
+                info!(
+                    packets = i + 1,
+                    "Confidence interval narrow enough; stopping early"
+                );
+                break;
+            }
         }
     }
 
@@ -186,13 +827,51 @@ pub fn measurement_phase<S: NetworkSocket>(
         p.finish();
         println!(); // Add blank line for separation
     }
+    #[cfg(feature = "dashboard")]
+    if let Some(d) = dashboard {
+        d.finish()?;
+    }
 
     let elapsed = start_time.elapsed();
+    if let Some(s) = progress_stream {
+        s.finish(elapsed);
+    }
+    let system_timeline = system_monitor.map(SystemMonitor::finish);
+    let thread_usage = ThreadUsage::current()
+        .zip(usage_at_start)
+        .map(|(end, start)| end.since(&start));
+
+    if let Some(ref mut c) = checkpointer {
+        c.write(elapsed, true)?;
+    }
+    if let Some(r) = raw_output {
+        r.finish()?;
+    }
+
+    // Fewer than planned if the confidence interval stopped the phase early
+    let total_packets = latencies.len() + lost_packets;
     Ok(MeasurementResult {
-        latencies,
+        latencies: latencies.into_vec(),
+        sequences: sequences.into_vec(),
+        send_offsets: send_offsets.into_vec(),
+        idle_gaps: idle_gaps.into_vec(),
+        started_at,
+        warmup: None,
+        send_durations: send_durations.into_vec(),
+        recv_wait_durations: recv_wait_durations.into_vec(),
         lost_packets,
-        total_packets: packet_count,
+        total_packets,
         elapsed,
+        seed,
+        system_timeline,
+        lost_sequences,
+        thread_usage,
+        sample_backing,
+        path_timings,
+        clock_offset,
+        kernel_timings,
+        pacing: pacer.map(|p| p.stats()),
+        reply_dscp,
     })
 }
 
@@ -217,7 +896,7 @@ mod tests {
             .times(1)
             .returning(move || Ok(Packet::new(seq)));
 
-        let result = measure_single_packet(&mut mock_socket, seq)?;
+        let result = measure_single_packet(&mut mock_socket, &Packet::new(seq))?;
         assert!(result.is_some());
         assert!(result.unwrap() > 0); // Some latency measured
         Ok(())
@@ -239,7 +918,7 @@ mod tests {
             .times(1)
             .returning(move || Ok(Packet::new(wrong_seq)));
 
-        let result = measure_single_packet(&mut mock_socket, seq)?;
+        let result = measure_single_packet(&mut mock_socket, &Packet::new(seq))?;
         assert!(result.is_none()); // Sequence mismatch
         Ok(())
     }
@@ -259,7 +938,7 @@ mod tests {
             .times(1)
             .returning(|| Err(ClientError::Io(std::io::Error::from(ErrorKind::TimedOut))));
 
-        let result = measure_single_packet(&mut mock_socket, seq)?;
+        let result = measure_single_packet(&mut mock_socket, &Packet::new(seq))?;
         assert!(result.is_none()); // Timeout
         Ok(())
     }
@@ -275,7 +954,245 @@ mod tests {
             )))
         });
 
-        let result = measure_single_packet(&mut mock_socket, seq);
+        let result = measure_single_packet(&mut mock_socket, &Packet::new(seq));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_measurement_phase_writes_final_checkpoint() -> Result<()> {
+        use crate::client::record::RunRecord;
+
+        let mut mock_socket = MockNetworkSocket::new();
+        mock_socket
+            .expect_send_packet()
+            .times(3)
+            .returning(|_| Ok(8));
+        let mut next = 0u64;
+        mock_socket
+            .expect_recv_packet()
+            .times(3)
+            .returning(move || {
+                let packet = Packet::new(SequenceNumber(next));
+                next += 1;
+                Ok(packet)
+            });
+
+        let path = std::env::temp_dir().join(format!(
+            "synapse-{}-measurement-checkpoint.json",
+            std::process::id()
+        ));
+        let mut options = MeasurementOptions::new(3, 1, true);
+        options.checkpointer = Some(Checkpointer::new(
+            &path,
+            Duration::from_secs(3600),
+            "mock",
+            3,
+        )?);
+
+        let result = measurement_phase_with_options(&mut mock_socket, options)?;
+        let record = RunRecord::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(result.latencies.len(), 3);
+        assert!(record.complete);
+        assert_eq!(record.sent_packets, 3);
+        assert_eq!(record.lost_packets, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure_probe_times_send_separately() -> Result<()> {
+        let mut mock_socket = MockNetworkSocket::new();
+        let seq = SequenceNumber(7);
+
+        mock_socket.expect_send_packet().times(1).returning(|_| {
+            std::thread::sleep(Duration::from_millis(2));
+            Ok(8)
+        });
+        mock_socket
+            .expect_recv_packet()
+            .times(1)
+            .returning(move || Ok(Packet::new(seq)));
+
+        let probe = measure_probe(&mut mock_socket, &Packet::new(seq), &Clock::default())?;
+        assert!(probe.send_ns >= 2_000_000);
+        assert!(probe.rtt_ns.unwrap() >= probe.send_ns);
+        assert_eq!(
+            probe.rtt_ns.unwrap(),
+            probe.send_ns + probe.recv_wait_ns.unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure_probe_splits_path_at_server() -> Result<()> {
+        let mut mock_socket = MockNetworkSocket::new();
+        let seq = SequenceNumber(3);
+
+        mock_socket
+            .expect_send_packet()
+            .times(1)
+            .returning(|_| Ok(40));
+        mock_socket
+            .expect_recv_packet()
+            .times(1)
+            .returning(move || {
+                // A server stamping times 1 ms apart on a clock 1 s ahead of the client's
+                let receive_ns = unix_time_ns() + 1_000_000_000;
+                let mut reply = Packet::new(seq);
+                reply.server_receive_ns = Some(receive_ns);
+                reply.server_transmit_ns = Some(receive_ns + 1_000_000);
+                Ok(reply)
+            });
+
+        let probe = measure_probe(&mut mock_socket, &Packet::new(seq), &Clock::default())?;
+        let path = probe.path.expect("server timestamps in the reply");
+        assert_eq!(path.server_ns, 1_000_000);
+        assert!(path.outbound_ns >= 1_000_000_000);
+        assert!(path.return_ns < 0);
+
+        // Replies from a server that does not stamp carry no split
+        assert_eq!(PathTiming::from_reply(1, 2, &Packet::new(seq)), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock_sync_phase_estimates_offset() -> Result<()> {
+        let mut mock_socket = MockNetworkSocket::new();
+        mock_socket.expect_send_packet().returning(|_| Ok(40));
+        let mut next = 0u64;
+        mock_socket.expect_recv_packet().returning(move || {
+            next += 1;
+            if next == 2 {
+                return Err(ClientError::Io(std::io::Error::from(
+                    std::io::ErrorKind::WouldBlock,
+                )));
+            }
+            // A server clock 1 s ahead of the client's, answering instantly
+            let server_ns = unix_time_ns() + 1_000_000_000;
+            let mut reply = Packet::new(SequenceNumber(next - 1));
+            reply.server_receive_ns = Some(server_ns);
+            reply.server_transmit_ns = Some(server_ns);
+            Ok(reply)
+        });
+
+        let offset = clock_sync_phase(&mut mock_socket, 4, PACKET_SIZE)?.expect("an estimate");
+        assert_eq!(offset.samples, 3);
+        assert!(offset.offset_ns.abs_diff(1_000_000_000) <= offset.uncertainty_ns + 1);
+
+        let path = PathTiming {
+            outbound_ns: 1_000_010_000,
+            server_ns: 1_000,
+            return_ns: -999_990_000,
+            server_processing_ns: Some(3_000),
+        };
+        let corrected = path.corrected(&ClockOffset {
+            offset_ns: 1_000_000_000,
+            uncertainty_ns: 0,
+            samples: 1,
+        });
+        assert_eq!(corrected.outbound_ns, 10_000);
+        assert_eq!(corrected.return_ns, 10_000);
+        assert_eq!(corrected.server_processing_ns, Some(3_000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_measurement_result_samples() -> Result<()> {
+        // Packet 2 of 3 times out
+        let mut mock_socket = MockNetworkSocket::new();
+        mock_socket.expect_send_packet().returning(|_| Ok(8));
+        let mut next = 0u64;
+        mock_socket.expect_recv_packet().returning(move || {
+            next += 1;
+            match next {
+                2 => Err(ClientError::Io(std::io::Error::from(ErrorKind::TimedOut))),
+                n => Ok(Packet::new(SequenceNumber(n - 1))),
+            }
+        });
+
+        let before = SystemTime::now();
+        let result =
+            measurement_phase_with_options(&mut mock_socket, MeasurementOptions::new(3, 1, true))?;
+        let after = SystemTime::now();
+
+        let samples: Vec<Measurement> = result.samples().collect();
+        let sequences: Vec<u64> = samples.iter().map(|m| m.sequence.0).collect();
+        assert_eq!(sequences, vec![0, 2]);
+        assert_eq!(samples[1].latency_ns, result.latencies[1]);
+        assert!(samples[0].timestamp >= before);
+        assert!(samples[0].timestamp <= samples[1].timestamp);
+        assert!(samples[1].timestamp <= after);
+        Ok(())
+    }
+
+    #[test]
+    fn test_measurement_phase_strict_loss() -> Result<()> {
+        // Packet 1 is answered, packet 2 times out
+        let mock_socket = || {
+            let mut mock_socket = MockNetworkSocket::new();
+            mock_socket.expect_send_packet().returning(|_| Ok(8));
+            let mut next = 0u64;
+            mock_socket.expect_recv_packet().returning(move || {
+                next += 1;
+                match next {
+                    2 => Err(ClientError::Io(std::io::Error::from(ErrorKind::TimedOut))),
+                    n => Ok(Packet::new(SequenceNumber(n - 1))),
+                }
+            });
+            mock_socket
+        };
+
+        let result = measurement_phase_with_options(
+            &mut mock_socket(),
+            MeasurementOptions::new(3, 1, true),
+        )?;
+        assert_eq!(result.lost_packets, 1);
+
+        let mut options = MeasurementOptions::new(3, 1, true);
+        options.strict_loss = true;
+        let err = measurement_phase_with_options(&mut mock_socket(), options).unwrap_err();
+        assert!(matches!(err, ClientError::StrictLoss(_)), "{}", err);
+        let message = err.to_string();
+        assert!(
+            message.contains("packet 2 of 3 (sequence 1)"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("last successful sequence: 0"),
+            "{}",
+            message
+        );
+        assert!(message.contains("socket error: none"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn test_measurement_phase_until_ci() -> Result<()> {
+        use crate::client::confidence::{CiMetric, CI_CHECK_INTERVAL, MIN_CI_SAMPLES};
+
+        let mut mock_socket = MockNetworkSocket::new();
+        mock_socket.expect_send_packet().returning(|_| Ok(8));
+        let mut next = 0u64;
+        mock_socket.expect_recv_packet().returning(move || {
+            next += 1;
+            Ok(Packet::new(SequenceNumber(next - 1)))
+        });
+
+        // A mock's latency is precise to ±50% long before 50,000 probes
+        let mut options = MeasurementOptions::new(50_000, 1, true);
+        options.until_ci = Some(CiTarget {
+            metric: CiMetric::Mean,
+            half_width_pct: 50.0,
+        });
+        let result = measurement_phase_with_options(&mut mock_socket, options)?;
+        assert!(result.total_packets >= MIN_CI_SAMPLES as usize);
+        assert!(result.total_packets < 50_000, "{}", result.total_packets);
+        assert!(result
+            .total_packets
+            .is_multiple_of(CI_CHECK_INTERVAL as usize));
+        assert_eq!(result.latencies.len(), result.total_packets);
+        Ok(())
+    }
 }
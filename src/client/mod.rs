@@ -1,25 +1,159 @@
 //! Client module for Synapse latency measurement tool
 
+pub mod analysis;
+#[cfg(feature = "tokio")]
+pub mod async_client;
+pub mod baseline;
+pub mod buckets;
+pub mod build_info;
+pub mod bundle;
+#[cfg(feature = "charts")]
+pub mod charts;
+pub mod checkpoint;
+pub mod clock;
+pub mod compare;
+pub mod confidence;
 pub mod config;
+pub mod connections;
 pub mod constants;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod decomposition;
+pub mod drilldown;
+pub mod dscp;
+pub mod endpoints;
+pub mod energy;
+pub mod environment;
 pub mod error;
+pub mod exit_status;
+pub mod exporter;
+pub mod families;
+pub mod flows;
+pub mod heartbeat;
+pub mod hugepage;
+pub mod impair;
+pub mod irq;
 pub mod logging;
+pub mod matrix;
 pub mod measurement;
+pub mod numa;
+pub mod open_loop;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod output;
+pub mod pacing;
+pub mod pcap;
+pub mod periodicity;
+pub mod pipeline;
+pub mod plain;
+pub mod port_sweep;
 pub mod progress;
+pub mod progress_stream;
+pub mod raw_output;
+pub mod record;
+pub mod redact;
+pub mod reflectors;
 pub mod reporter;
+pub mod retry;
+pub mod rng;
+pub mod rusage;
+pub mod sla;
+pub mod slo;
 pub mod socket;
 pub mod statistics;
+pub mod sysmon;
+pub mod template;
+pub mod theme;
+pub mod timestamping;
+pub mod traffic;
+pub mod transaction;
+pub mod trend;
+pub mod units;
+pub mod verdict;
 pub mod visualizer;
+pub mod warmup;
+pub mod watchdog;
 
-pub use config::Config;
+pub use analysis::{ShiftTest, TestResult};
+#[cfg(feature = "tokio")]
+pub use async_client::{
+    measurement_phase_async, AsyncMeasurementOptions, AsyncNetworkSocket, AsyncTcpNetworkSocket,
+    AsyncUdpNetworkSocket,
+};
+pub use baseline::{Baseline, BaselineOutcome};
+pub use buckets::{Bucket, BucketEdges, Buckets};
+pub use build_info::{BuildInfo, Tag};
+pub use bundle::{BundleFile, BundleManifest};
+pub use checkpoint::Checkpointer;
+pub use clock::{Clock, ClockSource};
+pub use compare::Comparison;
+pub use confidence::{CiMetric, CiTarget, ConfidenceInterval};
+pub use config::{
+    AnalyzeArgs, Command, CompareArgs, Config, DepthSweepArgs, DissectorArgs, ImpairSweepArgs,
+    MatrixArgs,
+};
+pub use connections::{ConnectionStats, P99Dispersion};
 pub use constants::*;
+#[cfg(feature = "dashboard")]
+pub use dashboard::{Dashboard, UiMode};
+pub use decomposition::{Decomposition, Layer, Segment};
+pub use drilldown::SlowProbe;
+pub use dscp::Dscp;
+pub use endpoints::EndpointResult;
+pub use energy::{EnergyMeter, EnergyReport, ZoneEnergy};
+pub use environment::{Caveat, CpuQuota, CpuThrottling, Environment};
 pub use error::{ClientError, Result};
+pub use exit_status::ExitStatus;
+pub use exporter::{HttpUrl, Pushgateway};
+pub use families::{Family, FamilyResult};
+pub use flows::FlowSocket;
+pub use heartbeat::{Beat, Heartbeat};
+pub use hugepage::{PageBacking, SampleBuffer};
+pub use impair::{NetemProfile, NetemQdisc, SweepResult, SweepSettings};
+pub use irq::{AffinityWarning, IrqAffinity};
 pub use logging::{init_logging, init_logging_with_config};
+pub use matrix::{MatrixParameter, MatrixRow};
 pub use measurement::{
-    measure_single_packet, measurement_phase, warmup_phase, Measurement, MeasurementResult,
+    clock_sync_phase, measure_probe, measure_single_packet, measurement_phase,
+    measurement_phase_with_options, warmup_phase, KernelTiming, Measurement, MeasurementOptions,
+    MeasurementResult, PathTiming, ProbeTiming,
+};
+pub use numa::NumaPlacement;
+pub use open_loop::ProbeWindow;
+#[cfg(feature = "otlp")]
+pub use otlp::OtlpExporter;
+pub use output::{OutputFormat, RunSummary, StageLatency};
+pub use pacing::{
+    Bandwidth, BandwidthBudget, BudgetStats, BudgetedSocket, IdleSplit, Pacer, PacingStats,
+    TrafficModel,
 };
+pub use pcap::{CaptureStats, Flow, PcapCapture};
+pub use periodicity::{LossPeriod, SpikePeriod};
+pub use pipeline::{DepthResult, DepthSweepSettings};
+pub use port_sweep::{PortRange, PortResult};
 pub use progress::ProgressTracker;
+pub use progress_stream::{ProgressLine, ProgressStream, ProgressTarget};
+pub use raw_output::RawOutput;
+pub use record::{HistogramRecord, PacketTimestamps, RunRecord};
+pub use redact::Redactor;
+pub use reflectors::{ReflectorPool, ReflectorStats, WeightedScore};
 pub use reporter::Reporter;
-pub use socket::{NetworkSocket, TcpNetworkSocket};
+pub use retry::{BackoffJitter, RetryOutcome, RetryPolicy};
+pub use rng::SeededRng;
+pub use rusage::ThreadUsage;
+pub use sla::{ConditionOutcome, Sla};
+pub use slo::{Slo, SloOutcome};
+pub use socket::{AbortHandle, NetworkSocket, TcpNetworkSocket, UdpNetworkSocket};
 pub use statistics::Statistics;
+pub use sysmon::{SystemEvent, SystemMonitor, SystemTimeline};
+pub use template::{PayloadTemplate, TemplateKind};
+pub use theme::Theme;
+pub use timestamping::KernelTimestamps;
+pub use traffic::TrafficEstimate;
+pub use transaction::Transaction;
+pub use trend::TrendBucket;
+pub use units::{NumberFormat, UnitSystem};
+pub use verdict::{FailMetric, Verdict};
 pub use visualizer::OsiVisualizer;
+pub use warmup::Warmup;
+pub use watchdog::{Watchdog, WatchdogSocket};
@@ -0,0 +1,266 @@
+//! NUMA placement of the measuring thread
+//!
+//! `--numa-node N` pins the measuring thread to the CPUs of node N and makes it prefer
+//! memory of that node, so the packet buffers and result vectors it allocates live on the
+//! same node as the NIC when N is the NIC's node. Helper threads started while the
+//! placement is active (watchdog, system monitor) inherit it. The previous CPU affinity
+//! and memory policy are restored when [`NumaPlacement`] is dropped. Linux only.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::irq::parse_cpu_list;
+use std::collections::BTreeSet;
+use std::fs;
+use tracing::{debug, info, warn};
+
+/// The measuring thread bound to one NUMA node, restored when dropped
+#[derive(Debug)]
+pub struct NumaPlacement {
+    /// Node the thread is bound to
+    pub node: usize,
+    /// CPUs of the node the thread may run on
+    pub cpus: BTreeSet<usize>,
+    /// Whether the thread's allocations prefer the node's memory
+    ///
+    /// False if the kernel refused the memory policy (e.g. under a seccomp filter); the
+    /// kernel's first-touch placement then still favors the node the thread runs on.
+    pub memory_preferred: bool,
+    #[cfg(target_os = "linux")]
+    previous_cpus: libc::cpu_set_t,
+    /// Memory policy before the placement, if the kernel reported it
+    #[cfg(target_os = "linux")]
+    previous_policy: Option<MemoryPolicy>,
+}
+
+impl NumaPlacement {
+    /// Bind the calling thread to the CPUs and memory of `node`
+    pub fn apply(node: usize) -> Result<Self> {
+        let cpus = node_cpus(node)?;
+        apply_placement(node, cpus)
+    }
+}
+
+/// CPUs of NUMA node `node`
+fn node_cpus(node: usize) -> Result<BTreeSet<usize>> {
+    if !cfg!(target_os = "linux") {
+        return Err(ClientError::Config("--numa-node requires Linux".into()));
+    }
+    let cpulist = fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node))
+        .map_err(|_| {
+            let available = fs::read_to_string("/sys/devices/system/node/online")
+                .unwrap_or_else(|_| "none".into());
+            ClientError::Config(format!(
+                "NUMA node {} does not exist (online nodes: {})",
+                node,
+                available.trim()
+            ))
+        })?;
+    let cpus = parse_cpu_list(&cpulist).unwrap_or_default();
+    if cpus.is_empty() {
+        return Err(ClientError::Config(format!(
+            "NUMA node {} has no CPUs to run on",
+            node
+        )));
+    }
+    Ok(cpus)
+}
+
+/// `MPOL_DEFAULT` and `MPOL_PREFERRED` from `<linux/mempolicy.h>`
+#[cfg(target_os = "linux")]
+const MPOL_DEFAULT: libc::c_int = 0;
+#[cfg(target_os = "linux")]
+const MPOL_PREFERRED: libc::c_int = 1;
+
+/// Words of a node mask, enough for the 1024 nodes the kernel supports at most
+#[cfg(target_os = "linux")]
+const NODEMASK_WORDS: usize = 1024 / libc::c_ulong::BITS as usize;
+
+/// A memory policy of the calling thread: its mode, flags included, and node mask
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MemoryPolicy {
+    mode: libc::c_int,
+    nodemask: Vec<libc::c_ulong>,
+}
+
+#[cfg(target_os = "linux")]
+impl MemoryPolicy {
+    /// Policy of the calling thread (`get_mempolicy(2)`), if the kernel reports it
+    fn current() -> Option<Self> {
+        let mut mode: libc::c_int = 0;
+        let mut nodemask: Vec<libc::c_ulong> = vec![0; NODEMASK_WORDS];
+        let maxnode = (NODEMASK_WORDS * libc::c_ulong::BITS as usize) as libc::c_ulong;
+        // SAFETY: the mask holds `maxnode` bits, and both outputs outlive the call; a
+        // null address with no flags asks for the thread's policy
+        let read = unsafe {
+            libc::syscall(
+                libc::SYS_get_mempolicy,
+                &mut mode,
+                nodemask.as_mut_ptr(),
+                maxnode,
+                std::ptr::null::<libc::c_void>(),
+                0 as libc::c_ulong,
+            )
+        } == 0;
+        read.then_some(Self { mode, nodemask })
+    }
+
+    /// Prefer the memory of `node`
+    fn preferring(node: usize) -> Self {
+        let bits_per_word = libc::c_ulong::BITS as usize;
+        let mut nodemask: Vec<libc::c_ulong> = vec![0; node / bits_per_word + 1];
+        nodemask[node / bits_per_word] |= 1 << (node % bits_per_word);
+        Self {
+            mode: MPOL_PREFERRED,
+            nodemask,
+        }
+    }
+
+    /// The default policy, allocating on the node the thread runs on
+    fn default_policy() -> Self {
+        Self {
+            mode: MPOL_DEFAULT,
+            nodemask: vec![0],
+        }
+    }
+
+    /// Make this the policy of the calling thread (`set_mempolicy(2)`)
+    fn set(&self) -> bool {
+        // The kernel reads one bit less than `maxnode`
+        let maxnode = (self.nodemask.len() * libc::c_ulong::BITS as usize + 1) as libc::c_ulong;
+        // SAFETY: the mask holds at least `maxnode - 1` bits and outlives the call
+        unsafe {
+            libc::syscall(
+                libc::SYS_set_mempolicy,
+                self.mode,
+                self.nodemask.as_ptr(),
+                maxnode,
+            ) == 0
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn apply_placement(node: usize, cpus: BTreeSet<usize>) -> Result<NumaPlacement> {
+    let cpu_set_size = std::mem::size_of::<libc::cpu_set_t>();
+
+    // SAFETY: cpu_set_t is plain data; the all-zero value is the empty set
+    let mut previous_cpus: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    // SAFETY: the set is valid for writes of its full size; pid 0 is the calling thread
+    if unsafe { libc::sched_getaffinity(0, cpu_set_size, &mut previous_cpus) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    // SAFETY: as above, the all-zero value is the empty set
+    let mut node_set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in &cpus {
+        if cpu < cpu_set_size * 8 {
+            // SAFETY: `cpu` is within the bounds of the set
+            unsafe { libc::CPU_SET(cpu, &mut node_set) };
+        }
+    }
+    // SAFETY: the set is initialized and valid for reads of its full size
+    if unsafe { libc::sched_setaffinity(0, cpu_set_size, &node_set) } != 0 {
+        let e = std::io::Error::last_os_error();
+        return Err(ClientError::Config(format!(
+            "failed to pin the measuring thread to NUMA node {}: {}",
+            node, e
+        )));
+    }
+
+    let previous_policy = MemoryPolicy::current();
+    let memory_preferred = MemoryPolicy::preferring(node).set();
+    if !memory_preferred {
+        warn!(
+            node = node,
+            error = %std::io::Error::last_os_error(),
+            "Failed to prefer NUMA-local memory; relying on first-touch placement"
+        );
+    }
+    info!(node = node, cpus = ?cpus, memory_preferred = memory_preferred, "Pinned to NUMA node");
+
+    Ok(NumaPlacement {
+        node,
+        cpus,
+        memory_preferred,
+        previous_cpus,
+        previous_policy,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_placement(_node: usize, _cpus: BTreeSet<usize>) -> Result<NumaPlacement> {
+    Err(ClientError::Config("--numa-node requires Linux".into()))
+}
+
+impl Drop for NumaPlacement {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        {
+            let cpu_set_size = std::mem::size_of::<libc::cpu_set_t>();
+            // SAFETY: the saved set is initialized and valid for reads of its full size
+            if unsafe { libc::sched_setaffinity(0, cpu_set_size, &self.previous_cpus) } != 0 {
+                warn!(
+                    error = %std::io::Error::last_os_error(),
+                    "Failed to restore the CPU affinity of the measuring thread"
+                );
+            }
+            // Where the kernel did not report the previous policy, the default is the
+            // best guess
+            if self.memory_preferred {
+                let previous = self
+                    .previous_policy
+                    .clone()
+                    .unwrap_or_else(MemoryPolicy::default_policy);
+                if !previous.set() {
+                    warn!(
+                        error = %std::io::Error::last_os_error(),
+                        "Failed to restore the memory policy of the measuring thread"
+                    );
+                }
+            }
+        }
+        debug!(node = self.node, "NUMA placement released");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numa_placement_rejects_unknown_node() {
+        assert!(matches!(
+            NumaPlacement::apply(usize::MAX),
+            Err(ClientError::Config(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_numa_placement_pins_and_restores() -> Result<()> {
+        // Every Linux system has node 0; skip where sysfs is not mounted
+        if !std::path::Path::new("/sys/devices/system/node/node0").exists() {
+            return Ok(());
+        }
+        let allowed = || {
+            fs::read_to_string("/proc/thread-self/status")
+                .ok()
+                .and_then(|status| {
+                    status
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Cpus_allowed_list:"))
+                        .and_then(parse_cpu_list)
+                })
+        };
+
+        let before = allowed();
+        let policy_before = MemoryPolicy::current();
+        let placement = NumaPlacement::apply(0)?;
+        // A cpuset restricting the process may narrow the node's CPUs further
+        assert!(allowed().is_some_and(|cpus| cpus.is_subset(&placement.cpus)));
+        drop(placement);
+        assert_eq!(allowed(), before);
+        assert_eq!(MemoryPolicy::current(), policy_before);
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! Open-loop measurement with a window of probes in flight
+//!
+//! The default measurement is lockstep: a probe is sent, its reply awaited, and only
+//! then is the next one sent, so the server and path never hold more than one probe of
+//! the run. That measures latency at rest. `--window N` keeps up to N probes in flight
+//! instead: the next probe goes out as soon as a slot frees up, without waiting for the
+//! replies of the others, and every reply is matched to its probe by sequence number,
+//! in whatever order it arrives. The run then measures latency under the sustained
+//! load the window puts on the server and path.
+//!
+//! A probe counts as lost when the socket receives nothing for a full reply timeout:
+//! every probe still in flight has then waited at least that long. A reply that turns
+//! up after its probe was given up is ignored.
+
+use crate::client::clock::Clock;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::{PathTiming, ProbeTiming};
+use crate::client::socket::NetworkSocket;
+use crate::protocol::{unix_time_ns, Packet, SequenceNumber};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+use tracing::debug;
+
+/// A probe sent and not yet answered or given up
+#[derive(Debug, Clone, Copy)]
+struct InFlight {
+    /// Before the send call, read from the window's clock
+    sent_at: u64,
+    /// After the send call returned, read from the window's clock
+    send_done: u64,
+    /// Wall-clock send time, stamped into the probe
+    client_send_ns: u64,
+    /// Send time since the start of the phase, in nanoseconds
+    send_offset_ns: u64,
+}
+
+/// A probe whose outcome is known, answered or lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Completion {
+    /// Sequence number of the probe
+    pub sequence: SequenceNumber,
+    /// Send time since the start of the phase, in nanoseconds
+    pub send_offset_ns: u64,
+    /// Timing of the probe; without an RTT if it was lost
+    pub probe: ProbeTiming,
+}
+
+/// Sender and reply matcher of an open-loop measurement
+pub struct ProbeWindow {
+    /// Most probes in flight at once
+    depth: usize,
+    /// Probes of the whole phase
+    packet_count: usize,
+    /// Size of every probe in bytes
+    payload_size: usize,
+    /// Origin of the send offsets
+    start_time: Instant,
+    /// Clock the round trips are timed with
+    clock: Clock,
+    /// Sequence number of the next probe to send
+    next_sequence: u64,
+    /// Probes in flight by sequence number
+    in_flight: BTreeMap<u64, InFlight>,
+    /// Probes given up and not yet handed out
+    expired: VecDeque<Completion>,
+}
+
+impl ProbeWindow {
+    /// Window of `depth` probes over a phase of `packet_count` probes of
+    /// `payload_size` bytes, started at `start_time`
+    pub fn new(
+        depth: usize,
+        packet_count: usize,
+        payload_size: usize,
+        start_time: Instant,
+    ) -> Self {
+        Self {
+            depth: depth.max(1),
+            packet_count,
+            payload_size,
+            start_time,
+            clock: Clock::default(),
+            next_sequence: 0,
+            in_flight: BTreeMap::new(),
+            expired: VecDeque::new(),
+        }
+    }
+
+    /// Time the round trips with `clock` rather than the monotonic clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Probes sent and not yet answered or given up
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Top up the window, then wait for the next probe to complete
+    ///
+    /// Every probe of the phase completes exactly once, so calling this `packet_count`
+    /// times completes the phase. Lost probes complete in send order.
+    pub fn next_completion<S: NetworkSocket>(&mut self, socket: &mut S) -> Result<Completion> {
+        loop {
+            if let Some(completion) = self.expired.pop_front() {
+                return Ok(completion);
+            }
+            while self.in_flight.len() < self.depth && self.next_sequence < self.packet_count as u64
+            {
+                self.send(socket)?;
+            }
+            if self.in_flight.is_empty() {
+                return Err(ClientError::Measurement(format!(
+                    "all {} probes of the phase have completed",
+                    self.packet_count
+                )));
+            }
+
+            match socket.recv_packet() {
+                Ok(reply) => {
+                    let t2 = self.clock.now_ns();
+                    let client_receive_ns = unix_time_ns();
+                    let Some(probe) = self.in_flight.remove(&reply.sequence.0) else {
+                        debug!(
+                            sequence = reply.sequence.0,
+                            "Reply to a probe no longer in flight"
+                        );
+                        continue;
+                    };
+                    return Ok(Completion {
+                        sequence: reply.sequence,
+                        send_offset_ns: probe.send_offset_ns,
+                        probe: ProbeTiming {
+                            send_ns: probe.send_done.saturating_sub(probe.sent_at),
+                            rtt_ns: Some(t2.saturating_sub(probe.sent_at)),
+                            recv_wait_ns: Some(t2.saturating_sub(probe.send_done)),
+                            path: PathTiming::from_reply(
+                                probe.client_send_ns,
+                                client_receive_ns,
+                                &reply,
+                            ),
+                            kernel: None,
+                        },
+                    });
+                }
+                // Unix reports an expired SO_RCVTIMEO as EAGAIN (WouldBlock), Windows as TimedOut
+                Err(ClientError::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    // Everything in flight was sent before the receive began
+                    debug!(
+                        probes = self.in_flight.len(),
+                        "Reply timeout; giving up the probes in flight"
+                    );
+                    let in_flight = std::mem::take(&mut self.in_flight);
+                    self.expired
+                        .extend(in_flight.into_iter().map(|(sequence, probe)| Completion {
+                            sequence: SequenceNumber(sequence),
+                            send_offset_ns: probe.send_offset_ns,
+                            probe: ProbeTiming {
+                                send_ns: probe.send_done.saturating_sub(probe.sent_at),
+                                rtt_ns: None,
+                                recv_wait_ns: None,
+                                path: None,
+                                kernel: None,
+                            },
+                        }));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send the next probe of the phase
+    fn send<S: NetworkSocket>(&mut self, socket: &mut S) -> Result<()> {
+        let sequence = self.next_sequence;
+        let mut packet = Packet::with_size(SequenceNumber(sequence), self.payload_size);
+        socket.wait_to_send(&packet)?;
+        let send_offset_ns = self.start_time.elapsed().as_nanos() as u64;
+        let sent_at = self.clock.now_ns();
+        let client_send_ns = unix_time_ns();
+        packet.client_send_ns = Some(client_send_ns);
+        socket.send_packet(&packet)?;
+        let send_done = self.clock.now_ns();
+
+        self.in_flight.insert(
+            sequence,
+            InFlight {
+                sent_at,
+                send_done,
+                client_send_ns,
+                send_offset_ns,
+            },
+        );
+        self.next_sequence += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::socket::MockNetworkSocket;
+    use std::io::ErrorKind;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_window_matches_replies_out_of_order() -> Result<()> {
+        // Answer the probes in flight newest first
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut socket = MockNetworkSocket::new();
+        let queue = Arc::clone(&sent);
+        socket.expect_send_packet().returning(move |packet| {
+            queue.lock().unwrap().push(packet.sequence);
+            Ok(packet.size)
+        });
+        let queue = Arc::clone(&sent);
+        socket
+            .expect_recv_packet()
+            .returning(move || Ok(Packet::new(queue.lock().unwrap().pop().unwrap())));
+
+        let mut window = ProbeWindow::new(3, 5, 40, Instant::now());
+        let mut order = Vec::new();
+        for _ in 0..5 {
+            let completion = window.next_completion(&mut socket)?;
+            assert!(completion.probe.rtt_ns.is_some());
+            order.push(completion.sequence.0);
+        }
+        assert_eq!(order, vec![2, 3, 4, 1, 0]);
+        assert_eq!(window.in_flight(), 0);
+        assert!(window.next_completion(&mut socket).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_gives_up_probes_after_a_timeout() -> Result<()> {
+        let mut socket = MockNetworkSocket::new();
+        socket
+            .expect_send_packet()
+            .returning(|packet| Ok(packet.size));
+        let mut replies = vec![
+            Ok(Packet::new(SequenceNumber(1))),
+            Err(ClientError::Io(std::io::Error::from(ErrorKind::WouldBlock))),
+            // Probe 0 answers after it was given up
+            Ok(Packet::new(SequenceNumber(0))),
+            Ok(Packet::new(SequenceNumber(3))),
+        ]
+        .into_iter();
+        socket
+            .expect_recv_packet()
+            .returning(move || replies.next().unwrap());
+
+        let mut window = ProbeWindow::new(2, 4, 40, Instant::now());
+        let outcomes: Vec<(u64, bool)> = (0..4)
+            .map(|_| {
+                window
+                    .next_completion(&mut socket)
+                    .map(|c| (c.sequence.0, c.probe.rtt_ns.is_some()))
+            })
+            .collect::<Result<_>>()?;
+        assert_eq!(outcomes, vec![(1, true), (0, false), (2, false), (3, true)]);
+        Ok(())
+    }
+}
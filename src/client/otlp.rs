@@ -0,0 +1,402 @@
+//! OpenTelemetry export of measurement runs (feature `otlp`)
+//!
+//! With `--otlp-endpoint` every run is sent to an OpenTelemetry collector over
+//! OTLP/HTTP with JSON encoding, so synapse results land in Tempo or Mimir next to the
+//! application's own telemetry:
+//!
+//! - a trace with a `synapse.run` span and its `warmup` and `measurement` children,
+//!   carrying the server address, transport, packet counts and seed as attributes
+//! - a `synapse.rtt` histogram (seconds, with the bucket bounds of the report's
+//!   distribution) and `synapse.packets.sent` / `synapse.packets.lost` gauges
+//!
+//! The resource carries the client's version (`service.version`), git commit, build
+//! profile and rustc version, and the `--tag` tags as `synapse.tag.<KEY>`.
+//!
+//! Both go to the standard paths below the endpoint (`/v1/traces`, `/v1/metrics`), the
+//! default OTLP/HTTP port being 4318. The feature adds no dependencies; the encoding is
+//! built with `serde_json` and sent with the exporter's HTTP client.
+
+use crate::client::build_info::BuildInfo;
+use crate::client::error::Result;
+use crate::client::exporter::{http_send, HttpUrl};
+use crate::client::measurement::MeasurementResult;
+use crate::client::rng::{random_seed, SeededRng};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Upper bounds of the `synapse.rtt` histogram buckets in nanoseconds, matching the
+/// latency distribution of the report
+const RTT_BUCKET_BOUNDS_NS: [u64; 9] = [
+    20_000, 40_000, 60_000, 80_000, 100_000, 200_000, 500_000, 1_000_000, 10_000_000,
+];
+
+/// OTLP span kind `SPAN_KIND_INTERNAL`
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// OTLP aggregation temporality `AGGREGATION_TEMPORALITY_DELTA`; a run is one interval
+const AGGREGATION_TEMPORALITY_DELTA: u8 = 1;
+
+/// Content type of OTLP/HTTP with JSON encoding
+const CONTENT_TYPE: &str = "application/json";
+
+/// Sends runs to an OpenTelemetry collector
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    endpoint: HttpUrl,
+    service_name: String,
+    attributes: Vec<(String, Value)>,
+    tags: BTreeMap<String, String>,
+}
+
+impl OtlpExporter {
+    /// Export to the collector at `endpoint` as the service `service_name`
+    pub fn new(endpoint: HttpUrl, service_name: &str) -> Self {
+        Self {
+            endpoint,
+            service_name: service_name.to_string(),
+            attributes: Vec::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Attach an attribute to the spans and data points of every run
+    pub fn with_attribute(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.attributes.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Describe the client by the user tags of the run as well, as `synapse.tag.<KEY>`
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Send the trace and the metrics of `result`
+    pub fn export(&self, result: &MeasurementResult) -> Result<()> {
+        let mut rng = SeededRng::stream(random_seed(), "otlp");
+        let trace = self.trace(result, &mut rng);
+        http_send(
+            &self.endpoint,
+            "POST",
+            "/v1/traces",
+            CONTENT_TYPE,
+            &trace.to_string(),
+        )?;
+        http_send(
+            &self.endpoint,
+            "POST",
+            "/v1/metrics",
+            CONTENT_TYPE,
+            &self.metrics(result).to_string(),
+        )?;
+        info!(endpoint = %self.endpoint, "Exported run telemetry");
+        Ok(())
+    }
+
+    /// `ExportTraceServiceRequest` of the run, with IDs drawn from `rng`
+    pub fn trace(&self, result: &MeasurementResult, rng: &mut SeededRng) -> Value {
+        let trace_id = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+        let run_id = format!("{:016x}", rng.next_u64());
+        let measurement_end = result.started_at + result.elapsed;
+
+        let mut run_attributes = self.attributes.clone();
+        run_attributes.extend([
+            (
+                "synapse.packets.sent".to_string(),
+                result.total_packets.into(),
+            ),
+            (
+                "synapse.packets.lost".to_string(),
+                result.lost_packets.into(),
+            ),
+            ("synapse.seed".to_string(), result.seed.to_string().into()),
+        ]);
+        let mut spans = vec![span(
+            &trace_id,
+            &run_id,
+            None,
+            "synapse.run",
+            result.warmup.map_or(result.started_at, |(start, _)| start),
+            measurement_end,
+            &run_attributes,
+        )];
+        if let Some((start, elapsed)) = result.warmup {
+            spans.push(span(
+                &trace_id,
+                &format!("{:016x}", rng.next_u64()),
+                Some(&run_id),
+                "warmup",
+                start,
+                start + elapsed,
+                &[],
+            ));
+        }
+        spans.push(span(
+            &trace_id,
+            &format!("{:016x}", rng.next_u64()),
+            Some(&run_id),
+            "measurement",
+            result.started_at,
+            measurement_end,
+            &[],
+        ));
+
+        json!({
+            "resourceSpans": [{
+                "resource": self.resource(),
+                "scopeSpans": [{ "scope": scope(), "spans": spans }],
+            }]
+        })
+    }
+
+    /// `ExportMetricsServiceRequest` of the run
+    pub fn metrics(&self, result: &MeasurementResult) -> Value {
+        let start = unix_nanos(result.started_at);
+        let end = unix_nanos(result.started_at + result.elapsed);
+        let attributes = key_values(&self.attributes);
+
+        let mut bucket_counts = vec![0u64; RTT_BUCKET_BOUNDS_NS.len() + 1];
+        for &latency in &result.latencies {
+            // Bucket i holds (bound[i-1], bound[i]]; the last one everything above
+            let bucket = RTT_BUCKET_BOUNDS_NS.partition_point(|&bound| bound < latency);
+            bucket_counts[bucket] += 1;
+        }
+        let seconds = |ns: u64| ns as f64 / 1e9;
+        let mut rtt = json!({
+            "attributes": attributes,
+            "startTimeUnixNano": start,
+            "timeUnixNano": end,
+            "count": result.latencies.len().to_string(),
+            "sum": seconds(result.latencies.iter().sum()),
+            "bucketCounts": bucket_counts.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "explicitBounds": RTT_BUCKET_BOUNDS_NS.map(seconds),
+        });
+        if let (Some(&min), Some(&max)) =
+            (result.latencies.iter().min(), result.latencies.iter().max())
+        {
+            rtt["min"] = seconds(min).into();
+            rtt["max"] = seconds(max).into();
+        }
+        let gauge = |name: &str, description: &str, value: usize| {
+            json!({
+                "name": name,
+                "description": description,
+                "unit": "{packet}",
+                "gauge": {
+                    "dataPoints": [{
+                        "attributes": attributes,
+                        "timeUnixNano": end,
+                        "asInt": value.to_string(),
+                    }]
+                },
+            })
+        };
+        let sent = gauge(
+            "synapse.packets.sent",
+            "Packets sent in a run",
+            result.total_packets,
+        );
+        let lost = gauge(
+            "synapse.packets.lost",
+            "Packets lost or timed out in a run",
+            result.lost_packets,
+        );
+
+        json!({
+            "resourceMetrics": [{
+                "resource": self.resource(),
+                "scopeMetrics": [{
+                    "scope": scope(),
+                    "metrics": [
+                        {
+                            "name": "synapse.rtt",
+                            "description": "Round-trip time of the probes of a run",
+                            "unit": "s",
+                            "histogram": {
+                                "aggregationTemporality": AGGREGATION_TEMPORALITY_DELTA,
+                                "dataPoints": [rtt],
+                            },
+                        },
+                        sent,
+                        lost,
+                    ],
+                }],
+            }]
+        })
+    }
+
+    /// Resource describing the client: service, build and user tags
+    fn resource(&self) -> Value {
+        let build = BuildInfo::current();
+        let mut attributes: Vec<(String, Value)> = vec![
+            ("service.name".into(), self.service_name.clone().into()),
+            ("service.version".into(), build.version.into()),
+            ("synapse.build.git_sha".into(), build.git_sha.into()),
+            ("synapse.build.profile".into(), build.profile.into()),
+            ("synapse.build.rustc".into(), build.rustc.into()),
+        ];
+        attributes.extend(
+            self.tags
+                .iter()
+                .map(|(key, value)| (format!("synapse.tag.{}", key), value.clone().into())),
+        );
+        json!({ "attributes": key_values(&attributes) })
+    }
+}
+
+/// OTLP span from `start` to `end`
+fn span(
+    trace_id: &str,
+    span_id: &str,
+    parent_span_id: Option<&str>,
+    name: &str,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: &[(String, Value)],
+) -> Value {
+    let mut span = json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "name": name,
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": unix_nanos(start),
+        "endTimeUnixNano": unix_nanos(end),
+        "attributes": key_values(attributes),
+    });
+    if let Some(parent) = parent_span_id {
+        span["parentSpanId"] = parent.into();
+    }
+    span
+}
+
+/// Instrumentation scope of everything synapse exports
+fn scope() -> Value {
+    json!({ "name": "synapse", "version": env!("CARGO_PKG_VERSION") })
+}
+
+/// Attributes as OTLP `KeyValue`s; integers are strings in the JSON encoding
+fn key_values(attributes: &[(String, Value)]) -> Value {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Number(n) if n.is_u64() || n.is_i64() => {
+                    json!({ "intValue": n.to_string() })
+                }
+                Value::Number(n) => json!({ "doubleValue": n }),
+                Value::Bool(b) => json!({ "boolValue": b }),
+                Value::String(s) => json!({ "stringValue": s }),
+                other => json!({ "stringValue": other.to_string() }),
+            };
+            json!({ "key": key, "value": value })
+        })
+        .collect()
+}
+
+/// `time` in nanoseconds since the UNIX epoch, as the string OTLP/JSON expects
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_nanos()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> MeasurementResult {
+        let started_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        MeasurementResult {
+            latencies: vec![15_000, 30_000, 30_000, 2_000_000],
+            send_durations: vec![2_000; 5],
+            recv_wait_durations: vec![13_000, 28_000, 28_000, 1_998_000],
+            lost_packets: 1,
+            total_packets: 5,
+            elapsed: Duration::from_millis(5),
+            seed: 7,
+            system_timeline: None,
+            sequences: vec![0, 1, 2, 4],
+            send_offsets: vec![0, 1_000_000, 2_000_000, 4_000_000],
+            idle_gaps: vec![0, 985_000, 970_000, 1_970_000],
+            started_at,
+            warmup: Some((
+                started_at - Duration::from_millis(3),
+                Duration::from_millis(2),
+            )),
+            lost_sequences: vec![3],
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: None,
+            reply_dscp: Default::default(),
+        }
+    }
+
+    fn exporter() -> OtlpExporter {
+        OtlpExporter::new("http://collector:4318".parse().unwrap(), "synapse")
+            .with_attribute("server.address", "10.0.0.2")
+            .with_attribute("server.port", 8080)
+            .with_tags(BTreeMap::from([(
+                "build".to_string(),
+                "4f2a9c1".to_string(),
+            )]))
+    }
+
+    #[test]
+    fn test_otlp_trace() {
+        let trace = exporter().trace(&result(), &mut SeededRng::new(1));
+        let spans = trace["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        let names: Vec<&str> = spans.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["synapse.run", "warmup", "measurement"]);
+
+        let (run, warmup, measurement) = (&spans[0], &spans[1], &spans[2]);
+        assert_eq!(run["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(warmup["parentSpanId"], run["spanId"]);
+        assert_eq!(measurement["parentSpanId"], run["spanId"]);
+        assert!(run.get("parentSpanId").is_none());
+        assert_eq!(run["startTimeUnixNano"], "1699999999997000000");
+        assert_eq!(run["endTimeUnixNano"], measurement["endTimeUnixNano"]);
+        assert_eq!(warmup["endTimeUnixNano"], "1699999999999000000");
+
+        let attributes = run["attributes"].as_array().unwrap();
+        assert!(
+            attributes.contains(&json!({ "key": "server.port", "value": { "intValue": "8080" } }))
+        );
+        assert!(attributes
+            .contains(&json!({ "key": "synapse.packets.lost", "value": { "intValue": "1" } })));
+        let resource = trace["resourceSpans"][0]["resource"]["attributes"]
+            .as_array()
+            .unwrap();
+        assert_eq!(resource[0]["value"]["stringValue"], "synapse");
+        assert!(resource.contains(&json!({
+            "key": "service.version",
+            "value": { "stringValue": env!("CARGO_PKG_VERSION") }
+        })));
+        assert!(resource.contains(
+            &json!({ "key": "synapse.tag.build", "value": { "stringValue": "4f2a9c1" } })
+        ));
+    }
+
+    #[test]
+    fn test_otlp_metrics() {
+        let metrics = exporter().metrics(&result());
+        let metrics = &metrics["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        let rtt = &metrics[0]["histogram"]["dataPoints"][0];
+        assert_eq!(rtt["count"], "4");
+        assert_eq!(
+            rtt["bucketCounts"],
+            json!(["1", "2", "0", "0", "0", "0", "0", "0", "1", "0"])
+        );
+        assert_eq!(rtt["explicitBounds"][0], 0.00002);
+        assert_eq!(rtt["max"], 0.002);
+        assert_eq!(metrics[2]["name"], "synapse.packets.lost");
+        assert_eq!(metrics[2]["gauge"]["dataPoints"][0]["asInt"], "1");
+    }
+}
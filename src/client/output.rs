@@ -0,0 +1,524 @@
+//! Machine-readable result output for CI systems
+//!
+//! The human-readable report is always printed. Selecting a machine format with
+//! `--output` additionally prints lines that the CI system picks up from stdout.
+
+use crate::client::constants::SEND_STALL_THRESHOLD_NS;
+use crate::client::energy::EnergyReport;
+use crate::client::environment::Caveat;
+use crate::client::measurement::{KernelTiming, PathTiming};
+use crate::client::statistics::Statistics;
+use crate::client::verdict::Verdict;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Additional output format printed after the report
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable report only
+    #[default]
+    Text,
+    /// GitHub Actions workflow annotations for failed checks
+    GhAnnotations,
+    /// TeamCity service messages (buildStatisticValue per metric, buildProblem on failure)
+    Teamcity,
+}
+
+/// Key figures of a run that machine formats are built from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    /// Packets sent
+    pub total_packets: usize,
+    /// Packets lost or timed out
+    pub lost_packets: usize,
+    /// Successful measurements
+    pub received: u64,
+    /// Mean RTT in nanoseconds
+    pub mean_ns: f64,
+    /// Median RTT in nanoseconds
+    pub p50_ns: u64,
+    /// 99th percentile RTT in nanoseconds
+    pub p99_ns: u64,
+    /// 99.9th percentile RTT in nanoseconds
+    pub p999_ns: u64,
+    /// Maximum RTT in nanoseconds
+    pub max_ns: u64,
+    /// Latencies clamped to the histogram bounds
+    pub clamped_count: usize,
+    /// Sends that blocked longer than `SEND_STALL_THRESHOLD_NS`
+    pub send_stalls: usize,
+    /// Latency added by each stage of the round trip, if the run had server or kernel
+    /// timestamps
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageLatency>,
+    /// Properties of the environment that skew the run (see
+    /// [`crate::client::environment`])
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub caveats: Vec<String>,
+    /// Energy the CPU packages used over the run in joules, with --energy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy_joules: Option<f64>,
+    /// Criterion of [`RunSummary::passed`]
+    #[serde(skip)]
+    pub verdict: Verdict,
+}
+
+impl RunSummary {
+    /// Summarize the statistics and counters of a run
+    pub fn new(
+        stats: &Statistics,
+        lost_packets: usize,
+        total_packets: usize,
+        send_durations: &[u64],
+    ) -> Self {
+        Self {
+            total_packets,
+            lost_packets,
+            received: stats.count(),
+            mean_ns: stats.mean(),
+            p50_ns: stats.percentile(0.5),
+            p99_ns: stats.percentile(0.99),
+            p999_ns: stats.percentile(0.999),
+            max_ns: stats.max(),
+            clamped_count: stats.clamped_count(),
+            send_stalls: send_durations
+                .iter()
+                .filter(|&&d| d > SEND_STALL_THRESHOLD_NS)
+                .count(),
+            stages: Vec::new(),
+            caveats: Vec::new(),
+            energy_joules: None,
+            verdict: Verdict::default(),
+        }
+    }
+
+    /// Grade the run by `verdict` rather than the default
+    pub fn with_verdict(mut self, verdict: Verdict) -> Self {
+        self.verdict = verdict;
+        self
+    }
+
+    /// Add the stage breakdown of the run (see [`stage_latencies`])
+    pub fn with_stages(mut self, stages: Vec<StageLatency>) -> Self {
+        self.stages = stages;
+        self
+    }
+
+    /// Add the caveats of the environment the run measured from
+    pub fn with_caveats(mut self, caveats: &[Caveat]) -> Self {
+        self.caveats = caveats.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Add the energy the run used (see [`crate::client::energy`])
+    pub fn with_energy(mut self, energy: Option<&EnergyReport>) -> Self {
+        self.energy_joules = energy.map(EnergyReport::joules);
+        self
+    }
+
+    /// Mean RTT in milliseconds
+    pub fn mean_ms(&self) -> f64 {
+        self.mean_ns / 1_000_000.0
+    }
+
+    /// Value of the figure the verdict grades, in nanoseconds
+    pub fn verdict_value_ns(&self) -> f64 {
+        self.verdict.metric.of_summary(self)
+    }
+
+    /// Whether the run passes its verdict
+    pub fn passed(&self) -> bool {
+        self.received > 0 && self.verdict.passes(self.verdict_value_ns())
+    }
+
+    /// Packet loss as a percentage of packets sent
+    pub fn loss_pct(&self) -> f64 {
+        if self.total_packets == 0 {
+            return 0.0;
+        }
+        self.lost_packets as f64 / self.total_packets as f64 * 100.0
+    }
+}
+
+/// Latency added by one stage of the round trip
+///
+/// Outbound and return compare the client and server clocks and can be negative when
+/// they are offset; see [`PathTiming`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageLatency {
+    /// `outbound`, `server` or `return` (server timestamps), `application` or `kernel`
+    /// (kernel timestamps)
+    pub stage: &'static str,
+    /// Mean time spent in the stage in nanoseconds
+    pub mean_ns: f64,
+    /// Median time spent in the stage in nanoseconds
+    pub p50_ns: i64,
+    /// 99th percentile time spent in the stage in nanoseconds
+    pub p99_ns: i64,
+    /// Maximum time spent in the stage in nanoseconds
+    pub max_ns: i64,
+}
+
+impl StageLatency {
+    /// Summarize the times spent in `stage`, if there are any
+    fn of(stage: &'static str, mut values: Vec<i64>) -> Option<Self> {
+        values.sort_unstable();
+        let max_ns = *values.last()?;
+        // Same ranks as the path breakdown of the report
+        let percentile =
+            |quantile: f64| values[((values.len() - 1) as f64 * quantile).round() as usize];
+        Some(Self {
+            stage,
+            mean_ns: values.iter().map(|&v| v as f64).sum::<f64>() / values.len() as f64,
+            p50_ns: percentile(0.5),
+            p99_ns: percentile(0.99),
+            max_ns,
+        })
+    }
+}
+
+/// Stages of the round trips of a run: outbound, server and return from the server
+/// timestamps, application and kernel from the kernel timestamps; none of either if the
+/// run did not have them
+pub fn stage_latencies(path: &[PathTiming], kernel: &[KernelTiming]) -> Vec<StageLatency> {
+    let path_stage = |stage, time: fn(&PathTiming) -> i64| {
+        StageLatency::of(stage, path.iter().map(time).collect())
+    };
+    let kernel_stage = |stage, time: fn(&KernelTiming) -> u64| {
+        StageLatency::of(stage, kernel.iter().map(|t| time(t) as i64).collect())
+    };
+    [
+        path_stage("outbound", |t| t.outbound_ns),
+        path_stage("server", |t| t.server_ns as i64),
+        path_stage("return", |t| t.return_ns),
+        kernel_stage("application", KernelTiming::application_ns),
+        kernel_stage("kernel", |t| t.kernel_rtt_ns),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Render the lines of `format` for `summary` (empty for the text format)
+pub fn render(format: OutputFormat, summary: &RunSummary) -> Vec<String> {
+    match format {
+        OutputFormat::Text => Vec::new(),
+        OutputFormat::GhAnnotations => gh_annotations(summary),
+        OutputFormat::Teamcity => teamcity_messages(summary),
+    }
+}
+
+/// GitHub Actions `::error`/`::warning` workflow commands for every failed check
+pub fn gh_annotations(summary: &RunSummary) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if summary.received == 0 {
+        lines.push(gh_command(
+            "error",
+            "Synapse: no data",
+            "No successful measurements recorded",
+        ));
+    } else if !summary.passed() {
+        lines.push(gh_command(
+            "error",
+            "Synapse: latency regression",
+            &format!(
+                "{} latency {:.3}ms exceeds the {}ms threshold (P50 {:.1}µs, P99 {:.1}µs, max {:.1}µs)",
+                summary.verdict.metric,
+                summary.verdict_value_ns() / 1_000_000.0,
+                summary.verdict.threshold_ms(),
+                summary.p50_ns as f64 / 1000.0,
+                summary.p99_ns as f64 / 1000.0,
+                summary.max_ns as f64 / 1000.0
+            ),
+        ));
+    }
+
+    if summary.lost_packets > 0 {
+        lines.push(gh_command(
+            "warning",
+            "Synapse: packet loss",
+            &format!(
+                "{} of {} packets lost ({:.2}%)",
+                summary.lost_packets,
+                summary.total_packets,
+                summary.loss_pct()
+            ),
+        ));
+    }
+    if summary.send_stalls > 0 {
+        lines.push(gh_command(
+            "warning",
+            "Synapse: send stalls",
+            &format!(
+                "{} send(s) blocked longer than {}µs",
+                summary.send_stalls,
+                SEND_STALL_THRESHOLD_NS / 1000
+            ),
+        ));
+    }
+    if summary.clamped_count > 0 {
+        lines.push(gh_command(
+            "warning",
+            "Synapse: clamped measurements",
+            &format!(
+                "{} measurement(s) exceeded the histogram bounds and were clamped",
+                summary.clamped_count
+            ),
+        ));
+    }
+    for caveat in &summary.caveats {
+        lines.push(gh_command("warning", "Synapse: environment", caveat));
+    }
+    lines
+}
+
+/// Format a workflow command, escaping the title property and the message
+fn gh_command(level: &str, title: &str, message: &str) -> String {
+    format!(
+        "::{} title={}::{}",
+        level,
+        escape_gh_property(title),
+        escape_gh_data(message)
+    )
+}
+
+/// Escape workflow command data as the Actions runner expects
+fn escape_gh_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a workflow command property value
+fn escape_gh_property(s: &str) -> String {
+    escape_gh_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// TeamCity `buildStatisticValue` messages for every metric, plus a `buildProblem` if
+/// the verdict fails
+///
+/// TeamCity charts statistic values across builds, so p99 over time needs no extra tooling.
+pub fn teamcity_messages(summary: &RunSummary) -> Vec<String> {
+    let metrics = [
+        ("synapse.packets.sent", summary.total_packets as f64),
+        ("synapse.packets.lost", summary.lost_packets as f64),
+        ("synapse.loss_pct", summary.loss_pct()),
+        ("synapse.latency.mean_us", summary.mean_ns / 1000.0),
+        ("synapse.latency.p50_us", summary.p50_ns as f64 / 1000.0),
+        ("synapse.latency.p99_us", summary.p99_ns as f64 / 1000.0),
+        ("synapse.latency.max_us", summary.max_ns as f64 / 1000.0),
+        ("synapse.send_stalls", summary.send_stalls as f64),
+    ];
+    let stages = summary.stages.iter().flat_map(|stage| {
+        [
+            ("mean_us", stage.mean_ns),
+            ("p50_us", stage.p50_ns as f64),
+            ("p99_us", stage.p99_ns as f64),
+        ]
+        .map(|(figure, ns)| {
+            (
+                format!("synapse.stage.{}.{}", stage.stage, figure),
+                ns / 1000.0,
+            )
+        })
+    });
+
+    let mut lines: Vec<String> = metrics
+        .iter()
+        .map(|(key, value)| (key.to_string(), *value))
+        .chain(stages)
+        .map(|(key, value)| {
+            format!(
+                "##teamcity[buildStatisticValue key='{}' value='{}']",
+                escape_teamcity(&key),
+                format_teamcity_value(value)
+            )
+        })
+        .collect();
+
+    if !summary.passed() {
+        let description = if summary.received == 0 {
+            "Synapse: no successful measurements recorded".to_string()
+        } else {
+            format!(
+                "Synapse: {} latency {:.3}ms exceeds the {}ms threshold",
+                summary.verdict.metric,
+                summary.verdict_value_ns() / 1_000_000.0,
+                summary.verdict.threshold_ms()
+            )
+        };
+        lines.push(format!(
+            "##teamcity[buildProblem description='{}' identity='synapse-latency']",
+            escape_teamcity(&description)
+        ));
+    }
+    lines
+}
+
+/// Format a statistic value with at most three decimals and no trailing zeros
+fn format_teamcity_value(value: f64) -> String {
+    let formatted = format!("{:.3}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Escape a service message attribute value as TeamCity expects
+fn escape_teamcity(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::Result;
+
+    #[test]
+    fn test_gh_annotations_passing_run_is_silent() -> Result<()> {
+        let stats = Statistics::new(&[10_000, 20_000, 30_000])?;
+        let summary = RunSummary::new(&stats, 0, 3, &[1_000, 1_000, 1_000]);
+
+        assert!(summary.passed());
+        assert!(render(OutputFormat::Text, &summary).is_empty());
+        assert!(gh_annotations(&summary).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_gh_annotations_failed_checks() -> Result<()> {
+        let stats = Statistics::new(&[5_000_000, 6_000_000])?;
+        let summary = RunSummary::new(&stats, 1, 3, &[1_000, SEND_STALL_THRESHOLD_NS + 1]);
+        let lines = gh_annotations(&summary);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("::error title=Synapse%3A latency regression::Mean latency"));
+        assert!(lines[1]
+            .starts_with("::warning title=Synapse%3A packet loss::1 of 3 packets lost (33.33%25)"));
+        assert!(lines[2].starts_with("::warning title=Synapse%3A send stalls::1 send(s)"));
+
+        let summary = summary.with_caveats(&[Caveat::Container {
+            runtime: "docker".into(),
+        }]);
+        let lines = gh_annotations(&summary);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].starts_with(
+            "::warning title=Synapse%3A environment::Running in a container (docker)"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gh_annotations_no_data() -> Result<()> {
+        let summary = RunSummary::new(&Statistics::empty()?, 5, 5, &[]);
+        let lines = gh_annotations(&summary);
+
+        assert!(!summary.passed());
+        assert!(lines[0].starts_with("::error title=Synapse%3A no data::"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_teamcity_messages() -> Result<()> {
+        let stats = Statistics::new(&[10_000, 20_000, 30_000])?;
+        let summary = RunSummary::new(&stats, 0, 3, &[]);
+        let lines = render(OutputFormat::Teamcity, &summary);
+
+        assert_eq!(lines.len(), 8);
+        assert!(lines.contains(
+            &"##teamcity[buildStatisticValue key='synapse.packets.sent' value='3']".to_string()
+        ));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("##teamcity[buildStatisticValue key='synapse.latency.p99_us'")));
+        assert!(!lines.iter().any(|l| l.contains("buildProblem")));
+
+        let failed = RunSummary::new(&Statistics::new(&[5_000_000])?, 0, 1, &[]);
+        let lines = teamcity_messages(&failed);
+        assert!(lines.last().unwrap().starts_with("##teamcity[buildProblem"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_verdict() -> Result<()> {
+        use crate::client::verdict::FailMetric;
+
+        // A mean well below 1 ms with a slow tail
+        let mut latencies = vec![100_000; 99];
+        latencies.push(20_000_000);
+        let stats = Statistics::new(&latencies)?;
+        let summary = RunSummary::new(&stats, 0, 100, &[]);
+        assert!(summary.passed());
+
+        let summary = summary.with_verdict(Verdict {
+            metric: FailMetric::Max,
+            threshold_ns: 10_000_000,
+        });
+        assert!(!summary.passed());
+        let lines = gh_annotations(&summary);
+        assert!(lines[0].contains("::Max latency 20.000ms exceeds the 10ms threshold"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stage_latencies() -> Result<()> {
+        let path: Vec<PathTiming> = (1..=100)
+            .map(|i| PathTiming {
+                outbound_ns: 20_000 - i,
+                server_ns: 5_000,
+                return_ns: 15_000 + i * 100,
+                server_processing_ns: None,
+            })
+            .collect();
+        let stages = stage_latencies(&path, &[]);
+        let names: Vec<&str> = stages.iter().map(|stage| stage.stage).collect();
+        assert_eq!(names, ["outbound", "server", "return"]);
+        assert_eq!((stages[0].p50_ns, stages[0].max_ns), (19_950, 19_999));
+        assert_eq!(stages[1].mean_ns, 5_000.0);
+        assert_eq!(stages[2].p99_ns, 24_900);
+
+        let kernel = [KernelTiming {
+            rtt_ns: 30_000,
+            kernel_rtt_ns: 22_000,
+        }];
+        let stages = stage_latencies(&[], &kernel);
+        assert_eq!(stages.len(), 2);
+        assert_eq!((stages[0].stage, stages[0].p50_ns), ("application", 8_000));
+        assert!(stage_latencies(&[], &[]).is_empty());
+
+        let stats = Statistics::new(&[30_000])?;
+        let summary = RunSummary::new(&stats, 0, 1, &[]);
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(json.get("stages").is_none());
+        let summary = summary.with_stages(stages);
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["stages"][1]["stage"], "kernel");
+        assert_eq!(json["stages"][1]["p99_ns"], 22_000);
+        assert!(teamcity_messages(&summary).contains(
+            &"##teamcity[buildStatisticValue key='synapse.stage.kernel.p50_us' value='22']"
+                .to_string()
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_teamcity_escaping() {
+        assert_eq!(escape_teamcity("a'b|c[d]\n"), "a|'b||c|[d|]|n");
+        assert_eq!(format_teamcity_value(12.5), "12.5");
+        assert_eq!(format_teamcity_value(3.0), "3");
+        assert_eq!(format_teamcity_value(0.0), "0");
+    }
+}
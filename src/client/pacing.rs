@@ -0,0 +1,530 @@
+//! Fixed-rate pacing of the measurement phase
+//!
+//! By default a probe is sent as soon as the previous reply arrives, so the send rate is
+//! whatever the round trip allows and the queues along the path mostly hold the client's
+//! own back-to-back probes. `--rate <PPS>` spaces the sends at a fixed interval instead,
+//! closer to the arrival pattern of real traffic. The schedule is anchored to the first
+//! send, so sleep overshoot does not add up to drift; the last stretch before each send
+//! is spun rather than slept, as a sleep can overshoot by tens of microseconds.
+//!
+//! Probing stays lockstep: a probe is only sent once the previous one was answered or
+//! timed out. A round trip longer than the interval delays the next send, which counts
+//! as late, and the schedule restarts from the late send instead of bursting to catch up.
+//!
+//! With `--traffic poisson` the gaps between sends are drawn from an exponential
+//! distribution with the same mean, making the sends a Poisson process. Evenly spaced
+//! probes can phase-lock with periodic activity on the host or path (a timer tick, a
+//! flush every few milliseconds) and then always or never hit it, which skews the tail;
+//! Poisson sends sample such activity in proportion to the time it takes up. The gaps
+//! come from the run's seed, so `--seed` reproduces them.
+//!
+//! At low rates the connection sits idle between probes, long enough for state along
+//! the path to expire or power down: CPU caches and idle states, NIC and switch flow
+//! tables, ARP or neighbour entries. Paced runs split their samples by the idle time
+//! before each probe (from the end of the previous probe to the send) into a warm and a
+//! cold distribution, at `--cold-gap-ms` or the mean gap; a cold path noticeably slower
+//! than the warm one shows such expiry. Fixed gaps put almost every probe on one side,
+//! so the split needs Poisson traffic or a threshold between the shortest and longest
+//! gaps.
+//!
+//! `--max-bandwidth` caps the probe traffic itself, so probing a constrained WAN link
+//! cannot cause the congestion it measures. Every socket of a run sends through one
+//! [`BandwidthBudget`], whatever the connection, flow or probe size, and a send waits
+//! until the previous ones have drained at the cap. The budget keeps no credit while
+//! idle, so probes never burst above the cap; the echoes are as large as the probes, so
+//! the cap holds in both directions.
+
+use crate::client::dscp::Dscp;
+use crate::client::error::{ClientError, Result};
+use crate::client::rng::SeededRng;
+use crate::client::socket::{AbortHandle, NetworkSocket};
+use crate::client::timestamping::KernelTimestamps;
+use crate::protocol::{Packet, Transport};
+use clap::ValueEnum;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time before a send that is busy-waited rather than slept
+const SPIN_THRESHOLD: Duration = Duration::from_micros(200);
+
+/// Name of the random stream the Poisson gaps are drawn from
+const GAP_STREAM: &str = "send gaps";
+
+/// Probes needed on each side of an idle split before the two are compared
+pub const MIN_SPLIT_SAMPLES: usize = 10;
+
+/// Ratio of cold to warm median RTT from which the cold path is reported as slower
+pub const COLD_PENALTY_RATIO: f64 = 1.2;
+
+/// How the gaps between paced sends are spaced
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrafficModel {
+    /// Every gap equal to the interval
+    #[default]
+    Fixed,
+    /// Exponentially distributed gaps around the interval (a Poisson process)
+    Poisson,
+}
+
+impl fmt::Display for TrafficModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrafficModel::Fixed => write!(f, "fixed"),
+            TrafficModel::Poisson => write!(f, "poisson"),
+        }
+    }
+}
+
+/// Outcome of a paced measurement phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacingStats {
+    /// Target send rate in probes per second
+    pub rate: u64,
+    /// Spacing of the sends
+    pub traffic: TrafficModel,
+    /// Probes sent after their scheduled time, because the previous round trip took
+    /// longer than the interval
+    pub late_sends: usize,
+}
+
+impl PacingStats {
+    /// Mean scheduled time between two sends
+    pub fn interval(&self) -> Duration {
+        Duration::from_nanos(1_000_000_000 / self.rate.max(1))
+    }
+}
+
+/// Spaces probe sends at a fixed rate
+#[derive(Debug)]
+pub struct Pacer {
+    interval: Duration,
+    /// Source of the gaps of Poisson traffic
+    gaps: Option<SeededRng>,
+    /// Time the next probe is due, unset until the first send
+    next: Option<Instant>,
+    stats: PacingStats,
+}
+
+impl Pacer {
+    /// Pace sends at `rate` probes per second, spaced by `traffic`; random gaps are
+    /// derived from `seed`
+    pub fn new(rate: u64, traffic: TrafficModel, seed: u64) -> Self {
+        let stats = PacingStats {
+            rate: rate.max(1),
+            traffic,
+            late_sends: 0,
+        };
+        Self {
+            interval: stats.interval(),
+            gaps: (traffic == TrafficModel::Poisson).then(|| SeededRng::stream(seed, GAP_STREAM)),
+            next: None,
+            stats,
+        }
+    }
+
+    /// Time from one send to the next
+    fn next_gap(&mut self) -> Duration {
+        match &mut self.gaps {
+            // Inverse transform sampling; 1 - u is in (0, 1], so the logarithm is finite
+            Some(rng) => self.interval.mul_f64(-(1.0 - rng.next_f64()).ln()),
+            None => self.interval,
+        }
+    }
+
+    /// Block until the next probe is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let due = *self.next.get_or_insert(now);
+        if now > due {
+            self.stats.late_sends += 1;
+            self.next = Some(now + self.next_gap());
+            return;
+        }
+
+        sleep_until(due);
+        self.next = Some(due + self.next_gap());
+    }
+
+    /// Target rate and late sends so far
+    pub fn stats(&self) -> PacingStats {
+        self.stats
+    }
+}
+
+/// Sleep until `due`, spinning through the last stretch
+fn sleep_until(due: Instant) {
+    let remaining = due.saturating_duration_since(Instant::now());
+    if remaining > SPIN_THRESHOLD {
+        thread::sleep(remaining - SPIN_THRESHOLD);
+    }
+    while Instant::now() < due {
+        std::hint::spin_loop();
+    }
+}
+
+/// Bytes of IP and transport headers on every probe, for IPv6 (the larger) so the cap
+/// holds on either family
+pub fn header_bytes(transport: Transport) -> usize {
+    match transport {
+        Transport::Tcp => 40 + 20,
+        Transport::Udp => 40 + 8,
+    }
+}
+
+/// Bit rate, written like `1Mbps`, `500kbps` or `2G`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bandwidth {
+    bits_per_sec: u64,
+}
+
+impl Bandwidth {
+    /// Bandwidth of `bits_per_sec`
+    pub fn from_bits_per_sec(bits_per_sec: u64) -> Self {
+        Self { bits_per_sec }
+    }
+
+    /// Bits per second
+    pub fn bits_per_sec(&self) -> u64 {
+        self.bits_per_sec
+    }
+
+    /// Time `bytes` take on a link of this bandwidth
+    pub fn transmit_time(&self, bytes: usize) -> Duration {
+        let bits = bytes as u128 * 8;
+        Duration::from_nanos((bits * 1_000_000_000 / self.bits_per_sec.max(1) as u128) as u64)
+    }
+
+    /// Probes of `bytes` per second (headers included) that fit in the bandwidth
+    pub fn packet_rate(&self, bytes: usize) -> f64 {
+        self.bits_per_sec as f64 / (bytes.max(1) * 8) as f64
+    }
+}
+
+impl FromStr for Bandwidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid bandwidth '{}' (expected a rate such as 1Mbps, 500kbps or 2Gbps)",
+                s
+            )
+        };
+        let trimmed = s.trim();
+        let number = trimmed
+            .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+            .trim_end();
+        let unit = trimmed[number.len()..].trim_start();
+        let prefix = unit.strip_suffix("bps").unwrap_or(unit);
+        let multiplier = match prefix {
+            "" => 1.0,
+            "k" | "K" => 1e3,
+            "M" | "m" => 1e6,
+            "G" | "g" => 1e9,
+            _ => return Err(invalid()),
+        };
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        let bits_per_sec = value * multiplier;
+        if !(bits_per_sec >= 1.0 && bits_per_sec < u64::MAX as f64) {
+            return Err("bandwidth must be at least 1 bps".into());
+        }
+        Ok(Self::from_bits_per_sec(bits_per_sec as u64))
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bps = self.bits_per_sec as f64;
+        match bps {
+            b if b >= 1e9 => write!(f, "{} Gbps", b / 1e9),
+            b if b >= 1e6 => write!(f, "{} Mbps", b / 1e6),
+            b if b >= 1e3 => write!(f, "{} kbps", b / 1e3),
+            b => write!(f, "{} bps", b),
+        }
+    }
+}
+
+/// How much a [`BandwidthBudget`] held probing back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetStats {
+    /// The cap
+    pub bandwidth: Bandwidth,
+    /// Bytes sent, headers included
+    pub bytes: u64,
+    /// Sends that waited for the budget
+    pub held_sends: usize,
+    /// Total time sends waited for the budget
+    pub held: Duration,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    /// Time the bytes sent so far have drained at the cap, unset until the first send
+    drained_at: Option<Instant>,
+    stats: BudgetStats,
+}
+
+/// Cap on the traffic of every socket that shares it (clones share the budget)
+#[derive(Debug, Clone)]
+pub struct BandwidthBudget {
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl BandwidthBudget {
+    /// Budget of `bandwidth`
+    pub fn new(bandwidth: Bandwidth) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BudgetState {
+                drained_at: None,
+                stats: BudgetStats {
+                    bandwidth,
+                    bytes: 0,
+                    held_sends: 0,
+                    held: Duration::ZERO,
+                },
+            })),
+        }
+    }
+
+    /// Block until `bytes` can be sent without exceeding the cap, and spend them
+    pub fn spend(&self, bytes: usize) -> Result<()> {
+        let due = {
+            let mut state = self.state.lock().map_err(|e| {
+                ClientError::Measurement(format!("Failed to lock bandwidth budget: {}", e))
+            })?;
+            let now = Instant::now();
+            let due = state.drained_at.map_or(now, |drained| drained.max(now));
+            state.drained_at = Some(due + state.stats.bandwidth.transmit_time(bytes));
+            state.stats.bytes += bytes as u64;
+            if due > now {
+                state.stats.held_sends += 1;
+                state.stats.held += due - now;
+            }
+            due
+        };
+        sleep_until(due);
+        Ok(())
+    }
+
+    /// The cap and how much it held probing back so far
+    pub fn stats(&self) -> Result<BudgetStats> {
+        let state = self.state.lock().map_err(|e| {
+            ClientError::Measurement(format!("Failed to lock bandwidth budget: {}", e))
+        })?;
+        Ok(state.stats)
+    }
+}
+
+/// Socket decorator that spends every probe it sends from a [`BandwidthBudget`]
+///
+/// Like [`crate::client::watchdog::WatchdogSocket`], wrapping the socket covers warmup,
+/// clock sync and measurement, paced or open-loop, without threading the budget
+/// through every phase. The phases wait for the budget in
+/// [`NetworkSocket::wait_to_send`], before they time the send.
+pub struct BudgetedSocket<S: NetworkSocket> {
+    inner: S,
+    budget: BandwidthBudget,
+    header_bytes: usize,
+}
+
+impl<S: NetworkSocket> BudgetedSocket<S> {
+    /// Wrap `inner`, charging every probe its size plus `header_bytes` (see
+    /// [`header_bytes`])
+    pub fn new(inner: S, budget: BandwidthBudget, header_bytes: usize) -> Self {
+        Self {
+            inner,
+            budget,
+            header_bytes,
+        }
+    }
+}
+
+impl<S: NetworkSocket> NetworkSocket for BudgetedSocket<S> {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        self.inner.send_packet(packet)
+    }
+
+    fn wait_to_send(&self, packet: &Packet) -> Result<()> {
+        self.budget.spend(packet.size + self.header_bytes)?;
+        self.inner.wait_to_send(packet)
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        self.inner.recv_packet()
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        self.inner.abort_handle()
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        self.inner.take_error()
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        self.inner.kernel_timestamps()
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        self.inner.reply_dscp()
+    }
+
+    fn accept_any_reply(&mut self) {
+        self.inner.accept_any_reply()
+    }
+}
+
+/// Latencies of a run split by the idle time before each probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleSplit {
+    /// Idle time from which a probe counts as cold
+    pub threshold: Duration,
+    /// Latencies of probes sent after a shorter idle time, in nanoseconds
+    pub warm: Vec<u64>,
+    /// Latencies of probes sent after at least `threshold` of idle time, in nanoseconds
+    pub cold: Vec<u64>,
+}
+
+impl IdleSplit {
+    /// Split `latencies` by the parallel `idle_gaps`, both in nanoseconds
+    pub fn new(latencies: &[u64], idle_gaps: &[u64], threshold: Duration) -> Self {
+        let threshold_ns = threshold.as_nanos() as u64;
+        let (cold, warm): (Vec<_>, Vec<_>) = latencies
+            .iter()
+            .zip(idle_gaps)
+            .partition(|(_, idle_ns)| **idle_ns >= threshold_ns);
+        let latencies = |pairs: Vec<(&u64, &u64)>| pairs.into_iter().map(|(l, _)| *l).collect();
+        Self {
+            threshold,
+            warm: latencies(warm),
+            cold: latencies(cold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pacer() {
+        let mut pacer = Pacer::new(1000, TrafficModel::Fixed, 0);
+        assert_eq!(pacer.stats().interval(), Duration::from_millis(1));
+
+        // The first send is due at once, the following ones an interval apart
+        let start = Instant::now();
+        for _ in 0..5 {
+            pacer.wait();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(4));
+        assert_eq!(pacer.stats().late_sends, 0);
+
+        // A slow round trip makes the next send late, and the schedule restarts from it
+        thread::sleep(Duration::from_millis(5));
+        pacer.wait();
+        let late = Instant::now();
+        pacer.wait();
+        assert!(late.elapsed() >= Duration::from_micros(900));
+        assert_eq!(pacer.stats().late_sends, 1);
+    }
+
+    #[test]
+    fn test_poisson_gaps() {
+        let gaps = |seed| {
+            let mut pacer = Pacer::new(1000, TrafficModel::Poisson, seed);
+            (0..10_000).map(|_| pacer.next_gap()).collect::<Vec<_>>()
+        };
+        let a = gaps(42);
+        assert_eq!(a, gaps(42), "the seed reproduces the gaps");
+        assert_ne!(a, gaps(43));
+
+        // Exponential: the mean is the interval, and about 1/e of the gaps exceed it
+        let mean = a.iter().sum::<Duration>() / a.len() as u32;
+        assert!(
+            (950..1050).contains(&mean.as_micros()),
+            "mean gap {:?}",
+            mean
+        );
+        let longer = a
+            .iter()
+            .filter(|gap| **gap > Duration::from_millis(1))
+            .count();
+        assert!(
+            (3400..3950).contains(&longer),
+            "{} gaps above the mean",
+            longer
+        );
+
+        let mut fixed = Pacer::new(1000, TrafficModel::Fixed, 42);
+        assert_eq!(fixed.next_gap(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_bandwidth_parse() {
+        for (input, bits_per_sec) in [
+            ("1Mbps", 1_000_000),
+            ("500kbps", 500_000),
+            ("2.5 Gbps", 2_500_000_000),
+            ("64k", 64_000),
+            ("9600", 9_600),
+        ] {
+            let bandwidth: Bandwidth = input.parse().unwrap();
+            assert_eq!(bandwidth.bits_per_sec(), bits_per_sec, "{}", input);
+        }
+        assert_eq!(
+            Bandwidth::from_bits_per_sec(1_500_000).to_string(),
+            "1.5 Mbps"
+        );
+        for invalid in ["", "Mbps", "1Tbps", "0bps", "-1Mbps", "fast"] {
+            assert!(invalid.parse::<Bandwidth>().is_err(), "{}", invalid);
+        }
+
+        // 1 Mbps carries 125 bytes per millisecond
+        let bandwidth = Bandwidth::from_bits_per_sec(1_000_000);
+        assert_eq!(bandwidth.transmit_time(125), Duration::from_millis(1));
+        assert_eq!(bandwidth.packet_rate(125), 1000.0);
+    }
+
+    #[test]
+    fn test_bandwidth_budget() -> Result<()> {
+        // 1000 bytes take 8 ms at 1 Mbps
+        let budget = BandwidthBudget::new(Bandwidth::from_bits_per_sec(1_000_000));
+        let start = Instant::now();
+        budget.spend(1000)?;
+        assert!(start.elapsed() < Duration::from_millis(8));
+
+        // A clone shares the budget: the next send waits for the first to drain
+        let shared = budget.clone();
+        let handle = thread::spawn(move || shared.spend(1000));
+        handle.join().unwrap()?;
+        budget.spend(1000)?;
+        assert!(start.elapsed() >= Duration::from_millis(16));
+
+        let stats = budget.stats()?;
+        assert_eq!((stats.bytes, stats.held_sends), (3000, 2));
+        assert!(stats.held >= Duration::from_millis(8));
+        assert_eq!(header_bytes(Transport::Udp), 48);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_split() {
+        let latencies = [10_000, 50_000, 11_000, 52_000, 12_000];
+        let idle_gaps = [0, 5_000_000, 400_000, 1_000_000, 999_999];
+        let split = IdleSplit::new(&latencies, &idle_gaps, Duration::from_millis(1));
+        assert_eq!(split.warm, vec![10_000, 11_000, 12_000]);
+        // The threshold itself counts as cold
+        assert_eq!(split.cold, vec![50_000, 52_000]);
+
+        let split = IdleSplit::new(&latencies, &idle_gaps, Duration::from_secs(1));
+        assert_eq!(split.warm.len(), 5);
+        assert!(split.cold.is_empty());
+    }
+}
@@ -0,0 +1,952 @@
+//! Packet capture of the probe flow
+//!
+//! `--pcap out.pcap` records the TCP segments or UDP datagrams of the measurement flow
+//! while the measurement phase runs, so anomalous probes can be inspected in Wireshark:
+//! every probe starts with its little-endian sequence number.
+//!
+//! Frames are read from an `AF_PACKET` socket with kernel receive timestamps and written
+//! as network-layer packets (`LINKTYPE_RAW`) in the nanosecond pcap format. Only frames
+//! between the connection's two endpoints are kept. Capturing needs `CAP_NET_RAW` (or
+//! root) and Linux.
+//!
+//! `analyze --pcap` goes the other way: it reads a capture of a run, from `--pcap` or
+//! from tcpdump on either host, and [`correlate`]s its frames with the application
+//! timestamps stored by `--record-packets`, separating the time a probe spent in the
+//! client's stack from the time it spent on the wire.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::irq::interface_for_ip;
+use crate::client::record::PacketTimestamps;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Magic number of the pcap format with nanosecond timestamps
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+/// Link type of packets that begin with an IPv4 or IPv6 header
+const LINKTYPE_RAW: u32 = 101;
+
+/// Magic number of the pcap format with microsecond timestamps
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+
+/// Magic number of a pcapng section header block, which is not supported
+const PCAPNG_MAGIC: u32 = 0x0a0d_0d0a;
+
+/// Link types whose frames can be reduced to an IP packet
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW_OPENBSD: u32 = 12;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// Largest frame stored per packet
+const SNAPLEN: u32 = 65_535;
+
+/// Bytes of the little-endian sequence number every packet starts with, in all
+/// protocol versions
+const SEQUENCE_LEN: usize = 8;
+
+/// Time given to the kernel to start timestamping frames of a new tap
+const TIMESTAMP_SETTLE: Duration = Duration::from_millis(20);
+
+/// How often the capture thread checks whether it should stop
+const CAPTURE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Write the pcap file header
+pub fn write_file_header<W: Write>(writer: &mut W, linktype: u32) -> std::io::Result<()> {
+    writer.write_all(&PCAP_MAGIC_NANOS.to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?; // version major
+    writer.write_all(&4u16.to_le_bytes())?; // version minor
+    writer.write_all(&0i32.to_le_bytes())?; // timezone offset
+    writer.write_all(&0u32.to_le_bytes())?; // timestamp accuracy
+    writer.write_all(&SNAPLEN.to_le_bytes())?;
+    writer.write_all(&linktype.to_le_bytes())
+}
+
+/// Write one packet record; `timestamp` is the time since the Unix epoch
+pub fn write_record<W: Write>(
+    writer: &mut W,
+    timestamp: Duration,
+    data: &[u8],
+    original_len: usize,
+) -> std::io::Result<()> {
+    let captured = data.len().min(SNAPLEN as usize);
+    writer.write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+    writer.write_all(&timestamp.subsec_nanos().to_le_bytes())?;
+    writer.write_all(&(captured as u32).to_le_bytes())?;
+    writer.write_all(&(original_len as u32).to_le_bytes())?;
+    writer.write_all(&data[..captured])
+}
+
+/// A frame read from a capture file, reduced to its IP packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// Capture timestamp since the Unix epoch
+    pub timestamp: Duration,
+    /// The IPv4 or IPv6 packet, as far as it was captured
+    pub packet: Vec<u8>,
+}
+
+/// Read the IP packets of a classic pcap file
+///
+/// Frames that do not carry IPv4 or IPv6 are skipped. pcapng files are rejected with a
+/// hint to convert them.
+pub fn read_capture(path: &Path) -> Result<Vec<CapturedFrame>> {
+    let bytes = fs::read(path)?;
+    parse_capture(&bytes).map_err(|e| {
+        ClientError::Config(format!("{} is not a usable capture: {}", path.display(), e))
+    })
+}
+
+/// Parse the contents of a classic pcap file; the error describes what is wrong
+fn parse_capture(bytes: &[u8]) -> std::result::Result<Vec<CapturedFrame>, String> {
+    let header = bytes.get(..24).ok_or("file too short for a pcap header")?;
+    let magic = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let (big_endian, nanos) = match magic {
+        PCAP_MAGIC_MICROS => (false, false),
+        PCAP_MAGIC_NANOS => (false, true),
+        _ if magic.swap_bytes() == PCAP_MAGIC_MICROS => (true, false),
+        _ if magic.swap_bytes() == PCAP_MAGIC_NANOS => (true, true),
+        PCAPNG_MAGIC => {
+            return Err("pcapng is not supported; convert it with `editcap -F pcap`".into())
+        }
+        _ => return Err(format!("unknown magic number {:#010x}", magic)),
+    };
+    let read_u32 = |field: &[u8]| {
+        let field: [u8; 4] = field.try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(field)
+        } else {
+            u32::from_le_bytes(field)
+        }
+    };
+    // The upper bits of the link type field carry FCS information
+    let linktype = read_u32(&header[20..24]) & 0x0fff_ffff;
+
+    let mut frames = Vec::new();
+    let mut offset = 24;
+    while offset < bytes.len() {
+        let record = bytes
+            .get(offset..offset + 16)
+            .ok_or("truncated record header")?;
+        let seconds = read_u32(&record[0..4]) as u64;
+        let fraction = read_u32(&record[4..8]);
+        let captured = read_u32(&record[8..12]) as usize;
+        let data = bytes
+            .get(offset + 16..offset + 16 + captured)
+            .ok_or("truncated record")?;
+        offset += 16 + captured;
+
+        let Some(packet) = network_layer(linktype, data) else {
+            continue;
+        };
+        let nanos = if nanos { fraction } else { fraction * 1000 };
+        frames.push(CapturedFrame {
+            timestamp: Duration::new(seconds, nanos),
+            packet: packet.to_vec(),
+        });
+    }
+    Ok(frames)
+}
+
+/// The IP packet inside a frame of `linktype`, if it carries one
+fn network_layer(linktype: u32, frame: &[u8]) -> Option<&[u8]> {
+    const ETHERTYPE_IPV4: u16 = 0x0800;
+    const ETHERTYPE_IPV6: u16 = 0x86dd;
+    const ETHERTYPE_VLAN: [u16; 2] = [0x8100, 0x88a8];
+    let ethertype_at = |at: usize| {
+        frame
+            .get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+
+    let (ethertype, offset) = match linktype {
+        LINKTYPE_RAW | LINKTYPE_RAW_OPENBSD | LINKTYPE_IPV4 | LINKTYPE_IPV6 => return Some(frame),
+        // A 4-byte address family in the capturing host's byte order; check the version
+        LINKTYPE_NULL => return frame.get(4..),
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            while ethertype_at(offset).is_some_and(|t| ETHERTYPE_VLAN.contains(&t)) {
+                offset += 4;
+            }
+            (ethertype_at(offset)?, offset + 2)
+        }
+        LINKTYPE_LINUX_SLL => (ethertype_at(14)?, 16),
+        LINKTYPE_LINUX_SLL2 => (ethertype_at(0)?, 20),
+        _ => return None,
+    };
+    match ethertype {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => frame.get(offset..),
+        _ => None,
+    }
+}
+
+/// The two endpoints of the captured TCP connection or UDP flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flow {
+    /// Client side of the connection
+    pub local: SocketAddr,
+    /// Server side of the connection
+    pub peer: SocketAddr,
+}
+
+impl Flow {
+    /// Whether the IP packet `packet` is a segment of this flow, in either direction
+    pub fn matches(&self, packet: &[u8]) -> bool {
+        match parse_segment(packet) {
+            Some(segment) => {
+                (segment.source == self.local && segment.destination == self.peer)
+                    || (segment.source == self.peer && segment.destination == self.local)
+            }
+            None => false,
+        }
+    }
+}
+
+/// A TCP segment or UDP datagram parsed out of an IP packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    /// Segment payload; empty if the transport header was cut off by the snap length
+    pub payload: &'a [u8],
+}
+
+/// Parse the TCP segment or UDP datagram carried by an IPv4 or IPv6 packet
+///
+/// IPv6 extension headers are not followed; such packets are not recognized. Link-layer
+/// padding after the IP packet is not part of the payload.
+pub fn parse_segment(packet: &[u8]) -> Option<Segment<'_>> {
+    const IPPROTO_TCP: u8 = 6;
+    const IPPROTO_UDP: u8 = 17;
+    let (protocol, source, destination, transport) = match packet.first()? >> 4 {
+        4 => {
+            let header_len = (packet[0] & 0x0f) as usize * 4;
+            if packet.len() < 20 {
+                return None;
+            }
+            let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+            let source: [u8; 4] = packet[12..16].try_into().ok()?;
+            let destination: [u8; 4] = packet[16..20].try_into().ok()?;
+            (
+                packet[9],
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                packet.get(header_len..total_len.clamp(header_len, packet.len()))?,
+            )
+        }
+        6 => {
+            if packet.len() < 40 {
+                return None;
+            }
+            let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+            let source: [u8; 16] = packet[8..24].try_into().ok()?;
+            let destination: [u8; 16] = packet[24..40].try_into().ok()?;
+            (
+                packet[6],
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                &packet[40..(40 + payload_len).min(packet.len())],
+            )
+        }
+        _ => return None,
+    };
+    if transport.len() < 4 {
+        return None;
+    }
+    let source_port = u16::from_be_bytes([transport[0], transport[1]]);
+    let destination_port = u16::from_be_bytes([transport[2], transport[3]]);
+    let data_offset = match protocol {
+        IPPROTO_TCP => transport
+            .get(12)
+            .map_or(usize::MAX, |b| (b >> 4) as usize * 4),
+        IPPROTO_UDP => 8,
+        _ => return None,
+    };
+    Some(Segment {
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+        payload: transport.get(data_offset..).unwrap_or_default(),
+    })
+}
+
+/// Wire and application timestamps of one probe, in nanoseconds since the Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireTiming {
+    pub sequence: u64,
+    /// When the application handed the probe to the socket
+    pub app_sent_ns: u64,
+    /// Round trip seen by the application, `None` if the probe was lost
+    pub app_rtt_ns: Option<u64>,
+    /// Capture time of the probe on its way to the server
+    pub wire_out_ns: Option<u64>,
+    /// Capture time of the server's reply
+    pub wire_in_ns: Option<u64>,
+}
+
+impl WireTiming {
+    /// When the application received the reply
+    pub fn app_received_ns(&self) -> Option<u64> {
+        self.app_rtt_ns.map(|rtt| self.app_sent_ns + rtt)
+    }
+
+    /// Round trip seen on the wire
+    pub fn wire_rtt_ns(&self) -> Option<i64> {
+        Some(self.wire_in_ns? as i64 - self.wire_out_ns? as i64)
+    }
+
+    /// Time from the send call until the probe was on the wire
+    pub fn send_path_ns(&self) -> Option<i64> {
+        Some(self.wire_out_ns? as i64 - self.app_sent_ns as i64)
+    }
+
+    /// Time from the reply being on the wire until the application had it
+    pub fn receive_path_ns(&self) -> Option<i64> {
+        Some(self.app_received_ns()? as i64 - self.wire_in_ns? as i64)
+    }
+}
+
+/// Match the probes of a run to the frames of a capture by sequence number
+///
+/// Probes travel to `server_port` and replies come back from it; every packet of the
+/// run's packet size in a segment's payload starts with a little-endian sequence number.
+/// Segments are assumed to start at a packet boundary, which holds unless a padded
+/// packet was split across segments. Where a sequence number
+/// appears more than once (warmup probes, retransmissions, several runs in one capture),
+/// the probe frame closest to the application send time is used, and the first reply
+/// after it. Both clocks are wall clocks, so a capture taken on another host is offset
+/// by the difference between the hosts' clocks.
+pub fn correlate(
+    packets: &PacketTimestamps,
+    frames: &[CapturedFrame],
+    server_port: u16,
+) -> Vec<WireTiming> {
+    let packet_size = packets.packet_size.max(SEQUENCE_LEN);
+    let mut probes: HashMap<u64, Vec<u64>> = HashMap::new();
+    let mut replies: HashMap<u64, Vec<u64>> = HashMap::new();
+    for frame in frames {
+        let Some(segment) = parse_segment(&frame.packet) else {
+            continue;
+        };
+        let seen = if segment.destination.port() == server_port {
+            &mut probes
+        } else if segment.source.port() == server_port {
+            &mut replies
+        } else {
+            continue;
+        };
+        let timestamp = frame.timestamp.as_nanos() as u64;
+        for chunk in segment.payload.chunks_exact(packet_size) {
+            let sequence = u64::from_le_bytes(chunk[..SEQUENCE_LEN].try_into().unwrap());
+            seen.entry(sequence).or_default().push(timestamp);
+        }
+    }
+
+    packets
+        .sequences
+        .iter()
+        .zip(&packets.send_offsets_ns)
+        .zip(&packets.rtts_ns)
+        .map(|((&sequence, &send_offset_ns), &app_rtt_ns)| {
+            let app_sent_ns = packets.started_at_unix_ns + send_offset_ns;
+            let wire_out_ns = probes.get(&sequence).and_then(|times| {
+                times
+                    .iter()
+                    .copied()
+                    .min_by_key(|&t| t.abs_diff(app_sent_ns))
+            });
+            let wire_in_ns = replies.get(&sequence).and_then(|times| {
+                let after = wire_out_ns.unwrap_or(app_sent_ns);
+                times.iter().copied().filter(|&t| t >= after).min()
+            });
+            WireTiming {
+                sequence,
+                app_sent_ns,
+                app_rtt_ns,
+                wire_out_ns,
+                wire_in_ns,
+            }
+        })
+        .collect()
+}
+
+/// Write one row per probe of `timings` to a CSV file
+pub fn write_timings_csv(path: &Path, timings: &[WireTiming]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "sequence,app_sent_ns,wire_out_ns,wire_in_ns,app_received_ns,app_rtt_ns,wire_rtt_ns,send_path_ns,receive_path_ns"
+    )?;
+    let field = |value: Option<String>| value.unwrap_or_default();
+    for timing in timings {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            timing.sequence,
+            timing.app_sent_ns,
+            field(timing.wire_out_ns.map(|v| v.to_string())),
+            field(timing.wire_in_ns.map(|v| v.to_string())),
+            field(timing.app_received_ns().map(|v| v.to_string())),
+            field(timing.app_rtt_ns.map(|v| v.to_string())),
+            field(timing.wire_rtt_ns().map(|v| v.to_string())),
+            field(timing.send_path_ns().map(|v| v.to_string())),
+            field(timing.receive_path_ns().map(|v| v.to_string())),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Counters of a finished capture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Frames of the flow written to the file
+    pub written: u64,
+    /// Frames the kernel dropped because the capture fell behind
+    pub dropped: u64,
+}
+
+/// A running capture of one flow into a pcap file
+pub struct PcapCapture {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Result<CaptureStats>>,
+}
+
+impl PcapCapture {
+    /// Start capturing `flow` into `path`
+    ///
+    /// The tap is bound to the interface carrying the flow's local address. When the
+    /// server runs on this host the flow travels over loopback whatever addresses it
+    /// uses, so it is captured on all interfaces, as is a flow whose interface is unknown.
+    pub fn start(path: &Path, flow: Flow) -> Result<Self> {
+        let interface = match interface_for_ip(flow.peer.ip()) {
+            Some(_) => None,
+            None => interface_for_ip(flow.local.ip()),
+        };
+        let interface = interface.as_deref();
+        let mut socket = tap::PacketSocket::open(interface)?;
+        // The kernel switches receive timestamping on asynchronously; frames arriving
+        // before that are stamped when read, which would put them out of order
+        thread::sleep(TIMESTAMP_SETTLE);
+        let file = File::create(path).map_err(|e| {
+            ClientError::Config(format!(
+                "cannot create capture file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let mut writer = BufWriter::new(file);
+        write_file_header(&mut writer, LINKTYPE_RAW)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::Builder::new()
+            .name("synapse-pcap".into())
+            .spawn(move || {
+                let mut stats = CaptureStats::default();
+                let mut buf = vec![0u8; SNAPLEN as usize];
+                // Once stopped, frames still queued (the last replies) are drained for
+                // up to one poll interval, so the capture does not end early
+                let mut drain_until: Option<Instant> = None;
+                loop {
+                    if drain_until.is_none() && stop_flag.load(Ordering::Relaxed) {
+                        drain_until = Some(Instant::now() + CAPTURE_POLL_INTERVAL);
+                    }
+                    if drain_until.is_some_and(|until| Instant::now() >= until) {
+                        break;
+                    }
+                    let Some(frame) = socket.recv(&mut buf)? else {
+                        if drain_until.is_some() {
+                            break; // queue drained
+                        }
+                        continue; // poll interval elapsed
+                    };
+                    let data = &buf[..frame.len.min(buf.len())];
+                    if frame.skip || !flow.matches(data) {
+                        continue;
+                    }
+                    write_record(&mut writer, frame.timestamp, data, frame.len)?;
+                    stats.written += 1;
+                }
+                writer.flush()?;
+                stats.dropped = socket.dropped();
+                Ok(stats)
+            })?;
+
+        info!(path = %path.display(), flow = ?flow, interface = ?interface, "Packet capture started");
+        Ok(Self {
+            path: path.to_path_buf(),
+            stop,
+            handle,
+        })
+    }
+
+    /// Path of the capture file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop capturing and flush the file
+    pub fn finish(self) -> Result<CaptureStats> {
+        self.stop.store(true, Ordering::Relaxed);
+        let stats = self
+            .handle
+            .join()
+            .map_err(|_| ClientError::Measurement("packet capture thread panicked".into()))??;
+        if stats.dropped > 0 {
+            warn!(
+                dropped = stats.dropped,
+                "Kernel dropped frames during capture"
+            );
+        }
+        debug!(written = stats.written, path = %self.path.display(), "Packet capture finished");
+        Ok(stats)
+    }
+}
+
+/// A frame read from the tap
+struct Frame {
+    /// Length of the frame on the wire
+    len: usize,
+    /// Kernel receive timestamp since the Unix epoch
+    timestamp: Duration,
+    /// Frame is the outgoing copy of a loopback packet, which is also seen incoming
+    skip: bool,
+}
+
+#[cfg(target_os = "linux")]
+mod tap {
+    use super::{Frame, CAPTURE_POLL_INTERVAL};
+    use crate::client::error::{ClientError, Result};
+    use std::ffi::CString;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tracing::debug;
+
+    /// `AF_PACKET` datagram socket delivering network-layer frames
+    pub(super) struct PacketSocket {
+        fd: OwnedFd,
+    }
+
+    impl PacketSocket {
+        pub(super) fn open(interface: Option<&str>) -> Result<Self> {
+            let protocol = (libc::ETH_P_ALL as u16).to_be();
+            // SAFETY: plain socket(2) call; the descriptor is checked before use
+            let raw = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_DGRAM, protocol as i32) };
+            if raw < 0 {
+                let e = io::Error::last_os_error();
+                return Err(ClientError::Config(format!(
+                    "packet capture needs CAP_NET_RAW (run as root): {}",
+                    e
+                )));
+            }
+            // SAFETY: `raw` is a freshly created descriptor owned by nobody else
+            let socket = Self {
+                fd: unsafe { OwnedFd::from_raw_fd(raw) },
+            };
+
+            let enable: libc::c_int = 1;
+            socket.set_option(libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, &enable)?;
+            let timeout = libc::timeval {
+                tv_sec: 0,
+                tv_usec: CAPTURE_POLL_INTERVAL.as_micros() as libc::suseconds_t,
+            };
+            socket.set_option(libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)?;
+
+            if let Some(interface) = interface {
+                let name = CString::new(interface).map_err(|_| {
+                    ClientError::Config(format!("invalid interface '{}'", interface))
+                })?;
+                // SAFETY: `name` is a valid NUL-terminated string
+                let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+                if index == 0 {
+                    return Err(ClientError::Config(format!(
+                        "unknown interface '{}'",
+                        interface
+                    )));
+                }
+                // SAFETY: sockaddr_ll is plain data; all-zero is a valid starting value
+                let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+                address.sll_family = libc::AF_PACKET as u16;
+                address.sll_protocol = protocol;
+                address.sll_ifindex = index as i32;
+                // SAFETY: the address is a valid sockaddr_ll of the given length
+                let rc = unsafe {
+                    libc::bind(
+                        socket.fd.as_raw_fd(),
+                        (&address as *const libc::sockaddr_ll).cast(),
+                        std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                    )
+                };
+                if rc != 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+
+            // Frames queued before the bind may belong to any interface; discard them,
+            // and reset the drop count (reading it does) so earlier drops are not counted
+            socket.discard_queued();
+            socket.dropped();
+            Ok(socket)
+        }
+
+        /// Read and throw away every frame already queued on the socket
+        fn discard_queued(&self) {
+            let mut discarded = 0usize;
+            let mut byte = 0u8;
+            loop {
+                // MSG_TRUNC takes the whole frame off the queue into the one-byte buffer
+                // SAFETY: the buffer is valid for writes of one byte
+                let len = unsafe {
+                    libc::recv(
+                        self.fd.as_raw_fd(),
+                        (&mut byte as *mut u8).cast(),
+                        1,
+                        libc::MSG_DONTWAIT | libc::MSG_TRUNC,
+                    )
+                };
+                if len < 0 {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    // EAGAIN once the queue is empty
+                    break;
+                }
+                discarded += 1;
+            }
+            debug!(
+                frames = discarded,
+                "Discarded frames queued before the bind"
+            );
+        }
+
+        fn set_option<T>(&self, level: libc::c_int, name: libc::c_int, value: &T) -> Result<()> {
+            // SAFETY: `value` points to a valid T of the given size
+            let rc = unsafe {
+                libc::setsockopt(
+                    self.fd.as_raw_fd(),
+                    level,
+                    name,
+                    (value as *const T).cast(),
+                    std::mem::size_of::<T>() as libc::socklen_t,
+                )
+            };
+            if rc != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(())
+        }
+
+        /// Read the next frame into `buf`; `None` if the poll interval elapsed first
+        pub(super) fn recv(&mut self, buf: &mut [u8]) -> Result<Option<Frame>> {
+            // SAFETY: sockaddr_ll is plain data; all-zero is a valid starting value
+            let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            // Room for one timespec control message, as u64 words for alignment
+            let mut control = [0u64; 8];
+            // SAFETY: msghdr is plain data; all-zero is a valid starting value
+            let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+            message.msg_name = (&mut address as *mut libc::sockaddr_ll).cast();
+            message.msg_namelen = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            message.msg_iov = &mut iov;
+            message.msg_iovlen = 1;
+            message.msg_control = control.as_mut_ptr().cast();
+            message.msg_controllen = std::mem::size_of_val(&control) as _;
+
+            // MSG_TRUNC reports the full frame length even if the buffer is smaller
+            // SAFETY: every buffer referenced by `message` outlives the call
+            let len = unsafe { libc::recvmsg(self.fd.as_raw_fd(), &mut message, libc::MSG_TRUNC) };
+            if len < 0 {
+                let e = io::Error::last_os_error();
+                return match e.kind() {
+                    io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted => Ok(None),
+                    _ => Err(e.into()),
+                };
+            }
+
+            let mut timestamp = None;
+            // SAFETY: the control buffer was filled by recvmsg and is walked with the
+            // kernel's CMSG macros
+            unsafe {
+                let mut cmsg = libc::CMSG_FIRSTHDR(&message);
+                while !cmsg.is_null() {
+                    if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                        && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS
+                    {
+                        let ts = std::ptr::read_unaligned(
+                            libc::CMSG_DATA(cmsg) as *const libc::timespec
+                        );
+                        timestamp = Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32));
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&message, cmsg);
+                }
+            }
+            let timestamp = timestamp.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+            });
+
+            Ok(Some(Frame {
+                len: len as usize,
+                timestamp,
+                skip: address.sll_hatype == libc::ARPHRD_LOOPBACK
+                    && address.sll_pkttype == libc::PACKET_OUTGOING,
+            }))
+        }
+
+        /// Frames dropped by the kernel since the last call
+        pub(super) fn dropped(&self) -> u64 {
+            // SAFETY: tpacket_stats is plain data; all-zero is a valid starting value
+            let mut stats: libc::tpacket_stats = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of::<libc::tpacket_stats>() as libc::socklen_t;
+            // SAFETY: `stats` is valid for writes of `len` bytes
+            let rc = unsafe {
+                libc::getsockopt(
+                    self.fd.as_raw_fd(),
+                    libc::SOL_PACKET,
+                    libc::PACKET_STATISTICS,
+                    (&mut stats as *mut libc::tpacket_stats).cast(),
+                    &mut len,
+                )
+            };
+            if rc != 0 {
+                return 0;
+            }
+            stats.tp_drops as u64
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod tap {
+    use super::Frame;
+    use crate::client::error::{ClientError, Result};
+
+    pub(super) struct PacketSocket;
+
+    impl PacketSocket {
+        pub(super) fn open(_interface: Option<&str>) -> Result<Self> {
+            Err(ClientError::Config("--pcap requires Linux".into()))
+        }
+
+        pub(super) fn recv(&mut self, _buf: &mut [u8]) -> Result<Option<Frame>> {
+            Ok(None)
+        }
+
+        pub(super) fn dropped(&self) -> u64 {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Packet, SequenceNumber, PACKET_SIZE};
+    use std::time::UNIX_EPOCH;
+
+    /// IPv4/TCP packet from 10.0.0.1:40000 to 10.0.0.2:8080 carrying `payload`
+    fn ipv4_tcp(payload: &[u8]) -> Vec<u8> {
+        tcp_segment(40000, 8080, payload)
+    }
+
+    /// IPv4/TCP packet between 10.0.0.1 and 10.0.0.2 with the given ports
+    fn tcp_segment(source_port: u16, destination_port: u16, payload: &[u8]) -> Vec<u8> {
+        let (source, destination) = if source_port == 8080 {
+            ([10, 0, 0, 2], [10, 0, 0, 1])
+        } else {
+            ([10, 0, 0, 1], [10, 0, 0, 2])
+        };
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+        packet[9] = 6;
+        packet[12..16].copy_from_slice(&source);
+        packet[16..20].copy_from_slice(&destination);
+        packet[20..22].copy_from_slice(&source_port.to_be_bytes());
+        packet[22..24].copy_from_slice(&destination_port.to_be_bytes());
+        packet[32] = 5 << 4; // TCP data offset
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn test_flow_matches_both_directions() {
+        let flow = Flow {
+            local: "10.0.0.1:40000".parse().unwrap(),
+            peer: "10.0.0.2:8080".parse().unwrap(),
+        };
+        let packet = ipv4_tcp(&7u64.to_le_bytes());
+        assert!(flow.matches(&packet));
+
+        let reverse = Flow {
+            local: flow.peer,
+            peer: flow.local,
+        };
+        assert!(reverse.matches(&packet));
+
+        let other = Flow {
+            local: "10.0.0.1:40001".parse().unwrap(),
+            peer: flow.peer,
+        };
+        assert!(!other.matches(&packet));
+
+        // The same probe as a UDP datagram
+        let mut datagram = packet[..28].to_vec();
+        datagram[2..4].copy_from_slice(&36u16.to_be_bytes());
+        datagram[9] = 17;
+        datagram.extend_from_slice(&7u64.to_le_bytes());
+        assert!(flow.matches(&datagram));
+        assert_eq!(
+            parse_segment(&datagram).map(|s| s.payload),
+            Some(&7u64.to_le_bytes()[..])
+        );
+        assert!(!flow.matches(&[0x45, 0, 0]));
+        assert!(!flow.matches(&[]));
+    }
+
+    #[test]
+    fn test_pcap_file_layout() -> std::io::Result<()> {
+        let mut file = Vec::new();
+        write_file_header(&mut file, LINKTYPE_RAW)?;
+        let packet = ipv4_tcp(&[1, 2, 3]);
+        write_record(
+            &mut file,
+            Duration::new(1_700_000_000, 5),
+            &packet,
+            packet.len(),
+        )?;
+
+        assert_eq!(file.len(), 24 + 16 + packet.len());
+        assert_eq!(&file[0..4], &PCAP_MAGIC_NANOS.to_le_bytes());
+        assert_eq!(&file[20..24], &LINKTYPE_RAW.to_le_bytes());
+        assert_eq!(&file[24..28], &1_700_000_000u32.to_le_bytes());
+        assert_eq!(&file[28..32], &5u32.to_le_bytes());
+        assert_eq!(&file[32..36], &(packet.len() as u32).to_le_bytes());
+        assert_eq!(&file[40..], &packet[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_capture_ethernet_microseconds() {
+        // Big-endian microsecond file as written by some capture tools
+        let mut file = Vec::new();
+        for field in [
+            PCAP_MAGIC_MICROS,
+            0x0002_0004,
+            0,
+            0,
+            SNAPLEN,
+            LINKTYPE_ETHERNET,
+        ] {
+            file.extend_from_slice(&field.to_be_bytes());
+        }
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&ipv4_tcp(&9u64.to_le_bytes()));
+        frame.extend_from_slice(&[0; 6]); // Ethernet padding
+        for field in [1_700_000_000, 250, frame.len() as u32, frame.len() as u32] {
+            file.extend_from_slice(&field.to_be_bytes());
+        }
+        file.extend_from_slice(&frame);
+
+        let frames = parse_capture(&file).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].timestamp, Duration::new(1_700_000_000, 250_000));
+        let segment = parse_segment(&frames[0].packet).unwrap();
+        assert_eq!(segment.payload, &9u64.to_le_bytes());
+
+        assert!(parse_capture(&PCAPNG_MAGIC.to_le_bytes().repeat(6))
+            .unwrap_err()
+            .contains("pcapng"));
+        assert!(parse_capture(&file[..30]).is_err());
+    }
+
+    #[test]
+    fn test_correlate_matches_probes_and_replies() {
+        let frame = |ns: u64, packet: Vec<u8>| CapturedFrame {
+            timestamp: Duration::from_nanos(ns),
+            packet,
+        };
+        let started = 1_000_000_000;
+        let frames = vec![
+            // Warmup probe reusing sequence 0, long before the run
+            frame(
+                started - 500_000_000,
+                tcp_segment(40000, 8080, &Packet::new(SequenceNumber(0)).encode()),
+            ),
+            frame(
+                started + 3_000,
+                tcp_segment(40000, 8080, &Packet::new(SequenceNumber(0)).encode()),
+            ),
+            frame(
+                started + 40_000,
+                tcp_segment(8080, 40000, &Packet::new(SequenceNumber(0)).encode()),
+            ),
+            frame(
+                started + 100_500,
+                tcp_segment(40000, 8080, &Packet::new(SequenceNumber(1)).encode()),
+            ),
+        ];
+        let packets = PacketTimestamps {
+            started_at_unix_ns: started,
+            packet_size: PACKET_SIZE,
+            sequences: vec![0, 1, 2],
+            send_offsets_ns: vec![0, 100_000, 200_000],
+            rtts_ns: vec![Some(50_000), None, None],
+        };
+
+        let timings = correlate(&packets, &frames, 8080);
+        assert_eq!(timings.len(), 3);
+        assert_eq!(timings[0].send_path_ns(), Some(3_000));
+        assert_eq!(timings[0].wire_rtt_ns(), Some(37_000));
+        assert_eq!(timings[0].receive_path_ns(), Some(10_000));
+        assert_eq!(timings[1].wire_out_ns, Some(started + 100_500));
+        assert_eq!(timings[1].wire_in_ns, None);
+        assert_eq!(timings[2].wire_out_ns, None);
+    }
+
+    #[test]
+    fn test_correlate_padded_packets() {
+        let started = 1_000_000_000;
+        let padded = |sequence: u64| Packet::with_size(SequenceNumber(sequence), 64).encode();
+        // Two probes coalesced into one segment; their padding holds no sequence numbers
+        let mut coalesced = padded(0);
+        coalesced.extend(padded(1));
+        let frames = vec![
+            CapturedFrame {
+                timestamp: Duration::from_nanos(started + 1_000),
+                packet: tcp_segment(40000, 8080, &coalesced),
+            },
+            CapturedFrame {
+                timestamp: Duration::from_nanos(started + 9_000),
+                packet: tcp_segment(8080, 40000, &padded(1)),
+            },
+        ];
+        let mut packets = PacketTimestamps::new(UNIX_EPOCH + Duration::from_nanos(started), 64);
+        packets.push(1, 0, Some(10_000));
+
+        let timings = correlate(&packets, &frames, 8080);
+        assert_eq!(timings[0].wire_out_ns, Some(started + 1_000));
+        assert_eq!(timings[0].wire_in_ns, Some(started + 9_000));
+    }
+}
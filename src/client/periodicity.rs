@@ -0,0 +1,316 @@
+//! Periodicity analysis of packet loss and latency spikes
+//!
+//! Losses that recur at a fixed distance (a drop every ~1024 packets) usually point
+//! straight at a buffer size or polling interval somewhere in the path, and latency
+//! spikes that recur at a fixed interval (every 4 ms) at a timer tick or periodic task
+//! on a host. Random events do neither. Both analyses compute the autocorrelation of an
+//! event train and report the lags at which an event is most often followed by another:
+//! [`loss_periods`] over packet indices, [`spike_periods`] over send times.
+//!
+//! A run of adjacent events counts as one, so a buffer overflow that drops a burst every
+//! cycle is detected by the cycle length rather than the burst length.
+
+use crate::client::statistics::Statistics;
+use std::time::Duration;
+
+/// Fewest events for which periodicity is analyzed
+pub const MIN_EVENTS: usize = 4;
+
+/// At most this many events are correlated, bounding the work on very noisy runs
+const MAX_EVENTS: usize = 5_000;
+
+/// Longest lag examined, in packets or time bins
+const MAX_LAG: usize = 1 << 20;
+
+/// Width of the time bins spike send times are quantized to
+const SPIKE_BIN_NS: u64 = 10_000;
+
+/// A latency counts as a spike above the run's P99 and this multiple of its median
+const SPIKE_MEDIAN_FACTOR: f64 = 1.5;
+
+/// Timer frequencies a spike period is compared against (common `CONFIG_HZ` values)
+const TIMER_FREQUENCIES_HZ: [f64; 4] = [100.0, 250.0, 300.0, 1000.0];
+
+/// Relative deviation within which a spike frequency matches a timer frequency
+const TIMER_MATCH_TOLERANCE: f64 = 0.02;
+
+/// Relative jitter tolerated around a period (1% of the lag, at least one packet)
+const LAG_TOLERANCE: f64 = 0.01;
+
+/// Fraction of events that must recur at a lag for it to count as a period
+const MIN_PERIOD_SCORE: f64 = 0.5;
+
+/// How many times more often than chance a lag must recur to count as a period
+const MIN_SCORE_OVER_CHANCE: f64 = 5.0;
+
+/// Most periods reported
+const MAX_PERIODS: usize = 3;
+
+/// A distance at which losses recur
+#[derive(Debug, Clone, PartialEq)]
+pub struct LossPeriod {
+    /// Distance between recurring losses, in packets
+    pub packets: usize,
+    /// Fraction of loss events followed by another one about one period later
+    pub score: f64,
+}
+
+/// An interval at which latency spikes recur
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpikePeriod {
+    /// Time between recurring spikes
+    pub interval: Duration,
+    /// Fraction of spikes followed by another one about one interval later
+    pub score: f64,
+}
+
+impl SpikePeriod {
+    /// Frequency of the spikes in Hz
+    pub fn frequency_hz(&self) -> f64 {
+        1.0 / self.interval.as_secs_f64()
+    }
+
+    /// Common kernel timer frequency (`CONFIG_HZ`) the spikes match, if any
+    pub fn timer_hz(&self) -> Option<f64> {
+        let frequency = self.frequency_hz();
+        TIMER_FREQUENCIES_HZ
+            .into_iter()
+            .find(|hz| (frequency - hz).abs() <= hz * TIMER_MATCH_TOLERANCE)
+    }
+}
+
+/// Detect periods in the losses of a run of `total_packets` packets
+///
+/// `lost` holds the indices of the lost packets in ascending order. Periods are
+/// returned strongest first; multiples of a reported period are left out.
+pub fn loss_periods(lost: &[u64], total_packets: usize) -> Vec<LossPeriod> {
+    event_periods(&merge_events(lost, 1), total_packets, 1)
+        .into_iter()
+        .map(|(packets, score)| LossPeriod { packets, score })
+        .collect()
+}
+
+/// Detect intervals at which latency spikes recur
+///
+/// `send_offsets_ns` holds the send time of every sample of `latencies`, relative to the
+/// start of the run. A spike is a latency above both the run's P99 and 1.5 times its
+/// median. Periods are returned strongest first; multiples are left out.
+pub fn spike_periods(latencies: &[u64], send_offsets_ns: &[u64]) -> Vec<SpikePeriod> {
+    let Some(&last_offset) = send_offsets_ns.last() else {
+        return Vec::new();
+    };
+    let Ok(stats) = Statistics::new(latencies) else {
+        return Vec::new();
+    };
+    let threshold =
+        (stats.percentile(0.99) as f64).max(stats.percentile(0.5) as f64 * SPIKE_MEDIAN_FACTOR);
+
+    let spikes: Vec<u64> = latencies
+        .iter()
+        .zip(send_offsets_ns)
+        .filter(|&(&latency, _)| latency as f64 > threshold)
+        .map(|(_, &offset)| offset / SPIKE_BIN_NS)
+        .collect();
+
+    // Send times are only as precise as the packet spacing; never expect better
+    let span = (last_offset / SPIKE_BIN_NS) as usize + 1;
+    let packet_spacing = (span as f64 / latencies.len() as f64).ceil() as usize;
+    let events = merge_events(&spikes, packet_spacing.max(1) * 2);
+    event_periods(&events, span, packet_spacing.max(1))
+        .into_iter()
+        .map(|(bins, score)| SpikePeriod {
+            interval: Duration::from_nanos(bins as u64 * SPIKE_BIN_NS),
+            score,
+        })
+        .collect()
+}
+
+/// Periods of an event train over positions `0..span`, as `(lag, score)`
+///
+/// Jitter up to 1% of the lag, and at least `min_tolerance`, is tolerated.
+fn event_periods(events: &[u64], span: usize, min_tolerance: usize) -> Vec<(usize, f64)> {
+    let max_lag = (span / 2).min(MAX_LAG);
+    if events.len() < MIN_EVENTS || max_lag < 2 {
+        return Vec::new();
+    }
+    let events = &events[..events.len().min(MAX_EVENTS)];
+    let event_rate = events.len() as f64 / span as f64;
+    let tolerance_at = |lag: usize| tolerance(lag).max(min_tolerance);
+
+    // counts[d]: events followed by another event exactly d packets later
+    let mut counts = vec![0u32; max_lag + 1];
+    for (i, &start) in events.iter().enumerate() {
+        for &later in &events[i + 1..] {
+            let distance = (later - start) as usize;
+            if distance > max_lag {
+                break;
+            }
+            counts[distance] += 1;
+        }
+    }
+    let mut prefix = vec![0u64; max_lag + 2];
+    for (d, &count) in counts.iter().enumerate() {
+        prefix[d + 1] = prefix[d] + count as u64;
+    }
+
+    // Score every lag over its tolerance window and keep the significant ones
+    let mut significant: Vec<(usize, f64)> = Vec::new();
+    for lag in 2..=max_lag {
+        let tolerance = tolerance_at(lag);
+        let low = lag.saturating_sub(tolerance).max(1);
+        let high = (lag + tolerance).min(max_lag);
+        let hits = (prefix[high + 1] - prefix[low]) as f64;
+        let score = (hits / events.len() as f64).min(1.0);
+        let chance = (event_rate * (high - low + 1) as f64).min(1.0);
+        if score >= MIN_PERIOD_SCORE && score >= chance * MIN_SCORE_OVER_CHANCE {
+            significant.push((lag, score));
+        }
+    }
+
+    // Neighbouring lags share a tolerance window: keep the best lag of each group
+    let mut peaks: Vec<(usize, f64)> = Vec::new();
+    let mut group: Vec<(usize, f64)> = Vec::new();
+    for (lag, score) in significant {
+        if group.last().is_some_and(|&(last, _)| lag > last + 1) {
+            peaks.push(best_of_group(&group));
+            group.clear();
+        }
+        group.push((lag, score));
+    }
+    if !group.is_empty() {
+        peaks.push(best_of_group(&group));
+    }
+
+    let mut periods: Vec<(usize, f64)> = Vec::new();
+    for (lag, score) in peaks {
+        let is_multiple = periods.iter().any(|&(period, _)| {
+            let multiple = (lag as f64 / period as f64).round() as usize;
+            multiple >= 2 && lag.abs_diff(multiple * period) <= tolerance_at(lag)
+        });
+        if !is_multiple {
+            periods.push((lag, score));
+        }
+    }
+    periods.sort_by(|a, b| b.1.total_cmp(&a.1));
+    periods.truncate(MAX_PERIODS);
+    periods
+}
+
+/// Start positions of runs of events at most `gap` apart
+fn merge_events(positions: &[u64], gap: usize) -> Vec<u64> {
+    let mut events = Vec::new();
+    let mut previous: Option<u64> = None;
+    for &position in positions {
+        if previous.is_none_or(|p| position > p + gap as u64) {
+            events.push(position);
+        }
+        previous = Some(position);
+    }
+    events
+}
+
+/// Jitter tolerated around `lag`
+fn tolerance(lag: usize) -> usize {
+    ((lag as f64 * LAG_TOLERANCE).round() as usize).max(1)
+}
+
+/// Lag with the highest score in a group of adjacent lags; ties go to the middle
+fn best_of_group(group: &[(usize, f64)]) -> (usize, f64) {
+    let best = group
+        .iter()
+        .map(|&(_, score)| score)
+        .fold(f64::MIN, f64::max);
+    let tied: Vec<usize> = group
+        .iter()
+        .filter(|&&(_, score)| score == best)
+        .map(|&(lag, _)| lag)
+        .collect();
+    (tied[tied.len() / 2], best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::rng::SeededRng;
+
+    #[test]
+    fn test_loss_periods_detects_jittered_period() {
+        let mut rng = SeededRng::new(1);
+        let mut lost: Vec<u64> = (1..40)
+            .map(|k| k * 1024 + (rng.next_u64() % 7) - 3)
+            .collect();
+        // A few random losses on top of the periodic ones
+        lost.extend([5_000, 17_333, 29_001]);
+        lost.sort_unstable();
+
+        let periods = loss_periods(&lost, 41_000);
+        assert_eq!(periods.len(), 1, "{:?}", periods);
+        assert!(periods[0].packets.abs_diff(1024) <= 10, "{:?}", periods);
+        assert!(periods[0].score > 0.8);
+    }
+
+    #[test]
+    fn test_loss_periods_bursts_count_once() {
+        // Bursts of three losses every 500 packets
+        let lost: Vec<u64> = (1..20)
+            .flat_map(|k| [k * 500, k * 500 + 1, k * 500 + 2])
+            .collect();
+        let periods = loss_periods(&lost, 10_500);
+        assert_eq!(periods[0].packets, 500);
+    }
+
+    #[test]
+    fn test_loss_periods_ignores_random_loss() {
+        let mut rng = SeededRng::new(2);
+        let mut lost: Vec<u64> = (0..200).map(|_| rng.next_u64() % 100_000).collect();
+        lost.sort_unstable();
+        lost.dedup();
+
+        assert!(loss_periods(&lost, 100_000).is_empty());
+        assert!(loss_periods(&[10, 20, 30], 100).is_empty());
+        assert!(loss_periods(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_spike_periods_detects_timer_tick() {
+        // One packet every 30 µs; the packet in flight at every 4 ms tick is slow
+        let mut rng = SeededRng::new(3);
+        let send_offsets: Vec<u64> = (0..40_000u64).map(|i| i * 30_000).collect();
+        let latencies: Vec<u64> = send_offsets
+            .iter()
+            .map(|&offset| {
+                let base = 20_000 + rng.next_u64() % 5_000;
+                if offset % 4_000_000 < 30_000 {
+                    base + 200_000
+                } else {
+                    base
+                }
+            })
+            .collect();
+
+        let periods = spike_periods(&latencies, &send_offsets);
+        assert_eq!(periods.len(), 1, "{:?}", periods);
+        let interval_ms = periods[0].interval.as_secs_f64() * 1000.0;
+        assert!((interval_ms - 4.0).abs() < 0.1, "{:?}", periods);
+        assert_eq!(periods[0].timer_hz(), Some(250.0));
+    }
+
+    #[test]
+    fn test_spike_periods_ignores_random_spikes() {
+        let mut rng = SeededRng::new(4);
+        let send_offsets: Vec<u64> = (0..40_000u64).map(|i| i * 30_000).collect();
+        let latencies: Vec<u64> = (0..40_000)
+            .map(|_| {
+                let base = 20_000 + rng.next_u64() % 5_000;
+                if rng.next_f64() < 0.005 {
+                    base + 200_000
+                } else {
+                    base
+                }
+            })
+            .collect();
+
+        assert!(spike_periods(&latencies, &send_offsets).is_empty());
+        assert!(spike_periods(&[], &[]).is_empty());
+    }
+}
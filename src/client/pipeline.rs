@@ -0,0 +1,206 @@
+//! Pipelining depth sweeps
+//!
+//! A normal run keeps one probe in flight, so the server only ever has one request of
+//! the connection to work on. `depth-sweep` keeps a window of probes in flight instead,
+//! sending the next one as each reply arrives, and grows the window from 1 up to
+//! `--max-depth` in doublings on the same connection. A server that answers requests
+//! one at a time queues the rest, and each additional probe in flight adds about one
+//! service time to the RTT; a server or path that overlaps requests keeps the RTT flat
+//! as throughput rises.
+//!
+//! The sweep runs over TCP, where replies come back in order and are matched to their
+//! probes by position. The whole window is written before any reply is read, so the
+//! bytes in flight are capped at [`MAX_IN_FLIGHT_BYTES`]; beyond the socket buffers the
+//! client would block writing while the server blocks writing replies nobody reads.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::warmup_phase;
+use crate::client::socket::{NetworkSocket, TcpNetworkSocket};
+use crate::client::statistics::Statistics;
+use crate::client::warmup::{Warmup, DEFAULT_WARMUP_TOLERANCE_PCT};
+use crate::protocol::{Packet, SequenceNumber};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Most probe bytes kept in flight at the largest depth
+pub const MAX_IN_FLIGHT_BYTES: usize = 256 * 1024;
+
+/// Settings shared by every depth of a sweep
+#[derive(Debug, Clone)]
+pub struct DepthSweepSettings {
+    /// Server address to measure against
+    pub server: String,
+    /// Longest wait for a reply before the sweep fails
+    pub timeout: Duration,
+    /// Warmup packets before the first depth
+    pub warmup: usize,
+    /// Packets measured per depth
+    pub packets: usize,
+    /// Size of every probe in bytes
+    pub payload_size: usize,
+}
+
+/// Outcome of one depth of a sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthResult {
+    /// Probes kept in flight
+    pub depth: usize,
+    /// Median RTT in nanoseconds
+    pub p50_ns: u64,
+    /// 99th percentile RTT in nanoseconds
+    pub p99_ns: u64,
+    /// Replies per second over the depth's measurement
+    pub throughput_pps: f64,
+}
+
+/// Depths of a sweep up to `max_depth`: 1 and its doublings, and `max_depth` itself
+pub fn depths(max_depth: usize) -> Vec<usize> {
+    let mut depths: Vec<usize> = std::iter::successors(Some(1usize), |d| d.checked_mul(2))
+        .take_while(|d| *d < max_depth)
+        .collect();
+    depths.push(max_depth.max(1));
+    depths
+}
+
+/// Warm up one connection, then measure every depth on it in turn
+pub fn run_sweep(settings: &DepthSweepSettings, depths: &[usize]) -> Result<Vec<DepthResult>> {
+    let mut socket = TcpNetworkSocket::connect(&settings.server)?;
+    // Every probe in flight but the oldest would otherwise wait for an acknowledgement
+    socket.disable_nagle()?;
+    socket.set_timeout(settings.timeout)?;
+    warmup_phase(
+        &mut socket,
+        Warmup::Count(settings.warmup),
+        DEFAULT_WARMUP_TOLERANCE_PCT,
+        settings.payload_size,
+        true,
+    )?;
+
+    let mut next_sequence = 0;
+    depths
+        .iter()
+        .map(|&depth| {
+            info!(depth = depth, "Measuring pipelining depth");
+            let start = Instant::now();
+            let latencies = measure_pipelined(
+                &mut socket,
+                depth,
+                settings.packets,
+                settings.payload_size,
+                next_sequence,
+            )?;
+            let elapsed = start.elapsed();
+            next_sequence += settings.packets as u64;
+
+            let stats = Statistics::new(&latencies)?;
+            Ok(DepthResult {
+                depth,
+                p50_ns: stats.percentile(0.5),
+                p99_ns: stats.percentile(0.99),
+                throughput_pps: latencies.len() as f64 / elapsed.as_secs_f64(),
+            })
+        })
+        .collect()
+}
+
+/// Send `packets` probes keeping `depth` of them in flight, returning the RTT of each
+///
+/// Replies must come back in order, as they do on a TCP connection; a reply for
+/// another probe than the oldest in flight, or none within the socket timeout, fails
+/// the measurement.
+pub fn measure_pipelined<S: NetworkSocket>(
+    socket: &mut S,
+    depth: usize,
+    packets: usize,
+    payload_size: usize,
+    first_sequence: u64,
+) -> Result<Vec<u64>> {
+    let mut in_flight: VecDeque<(SequenceNumber, Instant)> = VecDeque::with_capacity(depth);
+    let mut latencies = Vec::with_capacity(packets);
+    let mut sent = 0usize;
+    let mut send = |socket: &mut S, in_flight: &mut VecDeque<_>| -> Result<()> {
+        let sequence = SequenceNumber(first_sequence + sent as u64);
+        in_flight.push_back((sequence, Instant::now()));
+        socket.send_packet(&Packet::with_size(sequence, payload_size))?;
+        sent += 1;
+        Ok(())
+    };
+
+    for _ in 0..depth.max(1).min(packets) {
+        send(socket, &mut in_flight)?;
+    }
+    while let Some((expected, sent_at)) = in_flight.pop_front() {
+        let reply = socket.recv_packet().map_err(|e| {
+            ClientError::Measurement(format!(
+                "no reply to probe {} at depth {}: {}",
+                expected.0, depth, e
+            ))
+        })?;
+        latencies.push(sent_at.elapsed().as_nanos() as u64);
+        if reply.sequence != expected {
+            return Err(ClientError::Measurement(format!(
+                "reply {} arrived while probe {} was the oldest in flight",
+                reply.sequence.0, expected.0
+            )));
+        }
+        if latencies.len() + in_flight.len() < packets {
+            send(socket, &mut in_flight)?;
+        }
+    }
+
+    debug!(
+        depth = depth,
+        packets = latencies.len(),
+        "Pipelined measurement completed"
+    );
+    Ok(latencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::socket::MockNetworkSocket;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_depths() {
+        assert_eq!(depths(1), vec![1]);
+        assert_eq!(depths(8), vec![1, 2, 4, 8]);
+        assert_eq!(depths(12), vec![1, 2, 4, 8, 12]);
+    }
+
+    #[test]
+    fn test_measure_pipelined_keeps_the_window_full() -> Result<()> {
+        // Echo in order, recording how many probes were in flight at each receive
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let windows = Arc::new(Mutex::new(Vec::new()));
+        let mut socket = MockNetworkSocket::new();
+        let sent = Arc::clone(&queue);
+        socket.expect_send_packet().returning(move |packet| {
+            sent.lock().unwrap().push_back(packet.sequence);
+            Ok(packet.size)
+        });
+        let (received, window) = (Arc::clone(&queue), Arc::clone(&windows));
+        socket.expect_recv_packet().returning(move || {
+            let mut queue = received.lock().unwrap();
+            window.lock().unwrap().push(queue.len());
+            Ok(Packet::new(queue.pop_front().unwrap()))
+        });
+
+        let latencies = measure_pipelined(&mut socket, 4, 10, 64, 100)?;
+        assert_eq!(latencies.len(), 10);
+        assert_eq!(*windows.lock().unwrap(), vec![4, 4, 4, 4, 4, 4, 4, 3, 2, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure_pipelined_rejects_out_of_order_replies() {
+        let mut socket = MockNetworkSocket::new();
+        socket.expect_send_packet().returning(|_| Ok(40));
+        socket
+            .expect_recv_packet()
+            .returning(|| Ok(Packet::new(SequenceNumber(1))));
+        assert!(measure_pipelined(&mut socket, 2, 4, 40, 0).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! ASCII rendering of the report
+//!
+//! The report marks verdicts with `✓` and `✗`, frames headings with box-drawing
+//! characters and draws histogram bars with Unicode blocks. Pasted into an email or a
+//! ticket, or passed through a system that mangles Unicode, these come out as question
+//! marks. `--plain-verdict` prints every line of the report in ASCII instead: `✓ PASS`
+//! becomes `PASS`, `└─` becomes `` `- ``, bars are drawn with `#` and `µs` reads `us`.
+
+/// Unicode sequences of the report and their ASCII replacements, in replacement order
+///
+/// Tree branches come before the box-drawing characters they are made of, and marks
+/// followed by a word (`✓ PASS`) before the bare marks.
+const REPLACEMENTS: [(&str, &str); 28] = [
+    ("✓ ", ""),
+    ("✗ ", ""),
+    ("✓", "OK"),
+    ("✗", "X"),
+    ("⚠", "!"),
+    ("└─ ", "`- "),
+    ("├─ ", "|- "),
+    ("─", "-"),
+    ("┌", "+"),
+    ("┐", "+"),
+    ("└", "+"),
+    ("┘", "+"),
+    ("├", "+"),
+    ("│", "|"),
+    ("█", "#"),
+    ("▉", "#"),
+    ("▊", "#"),
+    ("▋", "#"),
+    ("▌", "#"),
+    ("▍", "."),
+    ("▎", "."),
+    ("▏", "."),
+    ("░", ":"),
+    ("←", "<-"),
+    ("≈", "~"),
+    ("±", "+/-"),
+    ("Δ", "delta"),
+    ("–", "-"),
+];
+
+/// `text` with the Unicode of the report replaced by ASCII
+///
+/// `µ` is replaced as well, by `u`; any other non-ASCII character is kept.
+pub fn plain_text(text: &str) -> String {
+    let mut plain = text.replace('µ', "u");
+    for (unicode, ascii) in REPLACEMENTS {
+        if plain.contains(unicode) {
+            plain = plain.replace(unicode, ascii);
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            plain_text("✓ PASS: Mean latency (0.048ms) is below 1ms threshold"),
+            "PASS: Mean latency (0.048ms) is below 1ms threshold"
+        );
+        assert_eq!(plain_text("┌──┐\n│ab│\n└──┘"), "+--+\n|ab|\n+--+");
+        assert_eq!(
+            plain_text("  Mean:  48.2 µs  ← Average latency"),
+            "  Mean:  48.2 us  <- Average latency"
+        );
+        assert_eq!(
+            plain_text("          └─ Packet loss should be 0%"),
+            "          `- Packet loss should be 0%"
+        );
+        assert_eq!(plain_text("  0-20 µs  ███▌ 12.5%"), "  0-20 us  #### 12.5%");
+        assert!(plain_text("⚠ Note ≈ ±1 Δ ▏▉").is_ascii());
+    }
+}
@@ -0,0 +1,221 @@
+//! Per-destination-port sweeps
+//!
+//! Firewalls, load balancers and QoS policies often classify traffic by destination
+//! port: one port is shaped, another goes through a proxy, a third is rate-limited.
+//! `--port-sweep 8080-8090` runs the configured measurement against every port of the
+//! range on the same host, one after the other, and compares them. The server must
+//! listen on every port of the range, e.g. one server per port.
+//!
+//! Ports are measured in turn rather than at once, so each sees the path unloaded by
+//! the others. A port that cannot be reached (filtered, or nothing listening) is
+//! reported as failed and the sweep carries on.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::output::RunSummary;
+use std::fmt;
+use std::str::FromStr;
+
+/// Ratio to the lowest P99 of the sweep from which a port stands out
+pub const P99_OUTLIER_RATIO: f64 = 2.0;
+
+/// Percentage points of loss above the lowest loss of the sweep from which a port
+/// stands out
+pub const LOSS_OUTLIER_PCT: f64 = 1.0;
+
+/// Inclusive range of destination ports, written as `FIRST-LAST` or a single port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    /// First port measured
+    pub first: u16,
+    /// Last port measured
+    pub last: u16,
+}
+
+impl PortRange {
+    /// Ports of the range in ascending order
+    pub fn ports(&self) -> impl Iterator<Item = u16> {
+        self.first..=self.last
+    }
+
+    /// Number of ports in the range
+    pub fn count(&self) -> usize {
+        (self.last - self.first) as usize + 1
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.first, self.last)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let port = |value: &str| match value.trim().parse::<u16>() {
+            Ok(0) | Err(_) => Err(format!("invalid port '{}' (expected 1-65535)", value)),
+            Ok(port) => Ok(port),
+        };
+        let (first, last) = match s.split_once('-') {
+            Some((first, last)) => (port(first)?, port(last)?),
+            None => {
+                let port = port(s)?;
+                (port, port)
+            }
+        };
+        if first > last {
+            return Err(format!(
+                "port range '{}' ends before it starts (expected FIRST-LAST)",
+                s
+            ));
+        }
+        Ok(Self { first, last })
+    }
+}
+
+/// Outcome of the measurement of one port
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortResult {
+    /// Destination port measured
+    pub port: u16,
+    /// Summary of the run, or the error that stopped it
+    pub outcome: std::result::Result<RunSummary, String>,
+}
+
+/// Why a port stands out from the others of its sweep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deviation {
+    /// P99 this many times the lowest P99 of the sweep
+    Latency(f64),
+    /// Loss this many percentage points above the lowest loss of the sweep
+    Loss(f64),
+}
+
+/// Ways `result` stands out from the best ports of `results`
+///
+/// Failed ports have no figures to compare and are not listed.
+pub fn deviations(result: &PortResult, results: &[PortResult]) -> Vec<Deviation> {
+    let Ok(summary) = &result.outcome else {
+        return Vec::new();
+    };
+    let measured: Vec<&RunSummary> = results
+        .iter()
+        .filter_map(|r| r.outcome.as_ref().ok())
+        .collect();
+    deviations_from_best(summary, &measured)
+}
+
+/// Ways `summary` stands out from the best of `measured`, which it is one of
+pub fn deviations_from_best(summary: &RunSummary, measured: &[&RunSummary]) -> Vec<Deviation> {
+    let best_p99 = measured.iter().map(|s| s.p99_ns).min().unwrap_or(0);
+    let best_loss = measured
+        .iter()
+        .map(|s| s.loss_pct())
+        .fold(f64::INFINITY, f64::min);
+
+    let mut deviations = Vec::new();
+    let ratio = summary.p99_ns as f64 / best_p99.max(1) as f64;
+    if ratio >= P99_OUTLIER_RATIO {
+        deviations.push(Deviation::Latency(ratio));
+    }
+    let excess = summary.loss_pct() - best_loss;
+    if excess >= LOSS_OUTLIER_PCT {
+        deviations.push(Deviation::Loss(excess));
+    }
+    deviations
+}
+
+/// `server` with its port replaced by `port`
+///
+/// Takes `HOST:PORT`, including bracketed IPv6 addresses such as `[::1]:8080`.
+pub fn with_port(server: &str, port: u16) -> Result<String> {
+    let host = server
+        .rsplit_once(':')
+        .map(|(host, _)| host)
+        .filter(|host| !host.is_empty() && (!host.contains(':') || host.ends_with(']')))
+        .ok_or_else(|| {
+            ClientError::Config(format!(
+                "--port-sweep needs --server as HOST:PORT, got '{}'",
+                server
+            ))
+        })?;
+    Ok(format!("{}:{}", host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::verdict::Verdict;
+
+    fn summary(lost_packets: usize, p99_ns: u64) -> RunSummary {
+        RunSummary {
+            total_packets: 1000,
+            lost_packets,
+            received: 1000 - lost_packets as u64,
+            mean_ns: 40_000.0,
+            p50_ns: 40_000,
+            p99_ns,
+            p999_ns: p99_ns,
+            max_ns: p99_ns,
+            clamped_count: 0,
+            send_stalls: 0,
+            stages: Vec::new(),
+            caveats: Vec::new(),
+            energy_joules: None,
+            verdict: Verdict::default(),
+        }
+    }
+
+    #[test]
+    fn test_port_range_parsing() {
+        let range: PortRange = "8080-8090".parse().unwrap();
+        assert_eq!((range.first, range.last, range.count()), (8080, 8090, 11));
+        assert_eq!(range.ports().last(), Some(8090));
+        assert_eq!(range.to_string(), "8080-8090");
+        assert_eq!("443".parse::<PortRange>().unwrap().count(), 1);
+        assert!("8090-8080".parse::<PortRange>().is_err());
+        assert!("0-10".parse::<PortRange>().is_err());
+        assert!("8080-70000".parse::<PortRange>().is_err());
+        assert!("http".parse::<PortRange>().is_err());
+    }
+
+    #[test]
+    fn test_with_port() -> Result<()> {
+        assert_eq!(with_port("10.0.0.1:8080", 9000)?, "10.0.0.1:9000");
+        assert_eq!(with_port("echo.example:80", 81)?, "echo.example:81");
+        assert_eq!(with_port("[::1]:8080", 8081)?, "[::1]:8081");
+        assert!(with_port("10.0.0.1", 8081).is_err());
+        assert!(with_port("::1", 8081).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deviations() {
+        let results = vec![
+            PortResult {
+                port: 8080,
+                outcome: Ok(summary(0, 100_000)),
+            },
+            PortResult {
+                port: 8081,
+                outcome: Ok(summary(0, 150_000)),
+            },
+            PortResult {
+                port: 8082,
+                outcome: Ok(summary(50, 300_000)),
+            },
+            PortResult {
+                port: 8083,
+                outcome: Err("Connection refused".into()),
+            },
+        ];
+        assert!(deviations(&results[0], &results).is_empty());
+        assert!(deviations(&results[1], &results).is_empty());
+        assert_eq!(
+            deviations(&results[2], &results),
+            vec![Deviation::Latency(3.0), Deviation::Loss(5.0)]
+        );
+        assert!(deviations(&results[3], &results).is_empty());
+    }
+}
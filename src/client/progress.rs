@@ -1,7 +1,8 @@
 use crate::client::constants::*;
+use crate::client::decomposition::Decomposition;
 use crate::client::error::{ClientError, Result};
+use crate::client::theme::Theme;
 use crate::client::visualizer::OsiVisualizer;
-use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::time::{Duration, Instant};
 use tracing::debug;
@@ -14,6 +15,7 @@ pub struct ProgressTracker {
     update_interval: usize,
     last_stats_message: String,
     last_metrics_lines: Vec<String>,
+    theme: Theme,
 }
 
 impl ProgressTracker {
@@ -25,15 +27,7 @@ impl ProgressTracker {
             "Creating progress tracker"
         );
         let pb = ProgressBar::new(packet_count as u64);
-        pb.set_style(
-            ProgressStyle::with_template(
-                "{msg}\n{bar:40.cyan/blue} {pos:>7}/{len:7} [{elapsed_precise}]",
-            )
-            .map_err(|e| {
-                ClientError::Measurement(format!("Failed to create progress style: {}", e))
-            })?
-            .progress_chars("█░"),
-        );
+        pb.set_style(Self::style(Theme::Default)?);
         pb.enable_steady_tick(Duration::from_millis(PROGRESS_TICK_INTERVAL_MS));
 
         Ok(Self {
@@ -43,26 +37,49 @@ impl ProgressTracker {
             update_interval,
             last_stats_message: String::new(),
             last_metrics_lines: Vec::new(),
+            theme: Theme::Default,
         })
     }
 
-    /// Update progress and live statistics
+    /// Color the live view and its OSI visualization with `theme`
+    pub fn with_theme(mut self, theme: Theme) -> Result<Self> {
+        self.pb.set_style(Self::style(theme)?);
+        self.visualizer = self.visualizer.with_theme(theme);
+        self.theme = theme;
+        Ok(self)
+    }
+
+    /// Progress bar style, the live statistics above the bar
+    fn style(theme: Theme) -> Result<ProgressStyle> {
+        let template = format!(
+            "{{msg}}\n{} {{pos:>7}}/{{len:7}} [{{elapsed_precise}}]",
+            theme.progress_bar()
+        );
+        Ok(ProgressStyle::with_template(&template)
+            .map_err(|e| {
+                ClientError::Measurement(format!("Failed to create progress style: {}", e))
+            })?
+            .progress_chars("█░"))
+    }
+
+    /// Update progress and live statistics; `split` splits the recent RTTs for the OSI
+    /// view, called on sampled packets only
     pub fn update(
         &mut self,
         latencies: &[u64],
         start_time: Instant,
         packet_index: usize,
+        split: impl FnOnce() -> Option<Decomposition>,
     ) -> Result<()> {
         self.pb.inc(1);
 
-        // Advance OSI animation on sampled packets (lightweight operation)
-        let should_advance = self.visualizer.should_update(packet_index);
+        // Split the recent RTTs again on sampled packets only
+        let should_split = self.visualizer.should_update(packet_index);
         let mut should_update_display = false;
 
-        if should_advance {
-            self.visualizer.advance();
-            // When animation advances, update display to show the new state
-            // This ensures smooth animation without expensive stats calculations
+        if should_split {
+            self.visualizer.update(split());
+            // Show the new split without expensive stats calculations
             should_update_display = true;
         }
 
@@ -71,7 +88,7 @@ impl ProgressTracker {
         let should_update_stats = (packet_index + 1).is_multiple_of(self.update_interval)
             || self.last_update.elapsed().as_millis() > LIVE_STATS_UPDATE_INTERVAL_MS as u128;
 
-        // Update display when animation advances OR when full stats update is due
+        // Update display when the split changes OR when full stats update is due
         if should_update_stats {
             if !latencies.is_empty() {
                 // Full update with expensive stats calculations
@@ -80,7 +97,6 @@ impl ProgressTracker {
             }
         } else if should_update_display {
             // Lightweight update: update OSI visualization, reuse last stats
-            // This allows smooth animation without expensive recalculations
             self.update_osi_display_only()?;
         }
 
@@ -169,17 +185,17 @@ impl ProgressTracker {
         let mean_str = format!("{:.3}", mean_ms);
 
         let last_color = if last_ms < 0.5 {
-            last_str.green()
+            self.theme.good(&last_str)
         } else if last_ms < 1.0 {
-            last_str.yellow()
+            self.theme.warning(&last_str)
         } else {
-            last_str.red()
+            self.theme.bad(&last_str)
         };
 
         let mean_color = if mean_ms < 1.0 {
-            mean_str.green()
+            self.theme.good(&mean_str)
         } else {
-            mean_str.red()
+            self.theme.bad(&mean_str)
         };
 
         // Render OSI visualization
@@ -275,11 +291,19 @@ mod tests {
         let start_time = Instant::now();
 
         // Update should succeed
-        tracker.update(&latencies, start_time, 0)?;
+        tracker.update(&latencies, start_time, 0, || None)?;
         assert_eq!(tracker.pb.position(), 1);
         Ok(())
     }
 
+    #[test]
+    fn test_progress_tracker_with_theme() -> Result<()> {
+        let mut tracker = ProgressTracker::new(100, 10)?.with_theme(Theme::Mono)?;
+        tracker.update_live_stats(&[1000, 2_000_000], Instant::now())?;
+        assert!(tracker.last_stats_message.contains("L7: APPLICATION"));
+        Ok(())
+    }
+
     #[test]
     fn test_progress_tracker_final_update() -> Result<()> {
         let mut tracker = ProgressTracker::new(100, 10)?;
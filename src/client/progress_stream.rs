@@ -0,0 +1,277 @@
+//! NDJSON progress stream for orchestration tooling
+//!
+//! The live view is meant for a terminal, and the heartbeat (see
+//! [`crate::client::heartbeat`]) for a human reading the logs. `--progress-json <TARGET>`
+//! writes one JSON object per line instead, every `--update` packets and once more when
+//! the measurement ends, so a scheduler or dashboard can follow a run without scraping
+//! either:
+//!
+//! ```text
+//! {"packets_done":100,"packets_total":10000,"packets_lost":0,"loss_pct":0.0,"mean_us":41.2,"p99_us":80.1,"elapsed_secs":0.41,"done":false}
+//! ```
+//!
+//! The mean and P99 cover every probe answered so far (`null` before the first answer).
+//! The target is stdout (`-`), a TCP listener (`tcp:HOST:PORT`) or, on Unix, a Unix
+//! socket (`unix:PATH`). A consumer that goes away does not fail the run: the stream
+//! logs a warning and stops.
+
+use crate::client::error::{ClientError, Result};
+use crate::client::statistics::Statistics;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Where progress lines are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressTarget {
+    /// `-`: standard output
+    Stdout,
+    /// `tcp:HOST:PORT`: a TCP listener
+    Tcp(String),
+    /// `unix:PATH`: a Unix stream socket
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl ProgressTarget {
+    /// Open the target
+    fn open(&self) -> Result<Box<dyn Write + Send>> {
+        Ok(match self {
+            ProgressTarget::Stdout => Box::new(io::stdout()),
+            ProgressTarget::Tcp(addr) => Box::new(TcpStream::connect(addr)?),
+            #[cfg(unix)]
+            ProgressTarget::Unix(path) => Box::new(UnixStream::connect(path)?),
+        })
+    }
+}
+
+impl FromStr for ProgressTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(ProgressTarget::Stdout);
+        }
+        if let Some(addr) = s.strip_prefix("tcp:") {
+            if addr
+                .rsplit_once(':')
+                .is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())
+            {
+                return Err(format!(
+                    "invalid TCP address '{}' (expected HOST:PORT)",
+                    addr
+                ));
+            }
+            return Ok(ProgressTarget::Tcp(addr.to_string()));
+        }
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:").filter(|path| !path.is_empty()) {
+            return Ok(ProgressTarget::Unix(PathBuf::from(path)));
+        }
+        Err(format!(
+            "invalid progress target '{}' (expected -, tcp:HOST:PORT or unix:PATH)",
+            s
+        ))
+    }
+}
+
+impl fmt::Display for ProgressTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressTarget::Stdout => write!(f, "stdout"),
+            ProgressTarget::Tcp(addr) => write!(f, "tcp:{}", addr),
+            #[cfg(unix)]
+            ProgressTarget::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Progress of a run, as one line of the stream
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProgressLine {
+    /// Probes completed, answered or lost
+    pub packets_done: usize,
+    /// Probes of the whole phase
+    pub packets_total: usize,
+    /// Probes lost so far
+    pub packets_lost: usize,
+    /// Probes lost so far, as a percentage of those completed
+    pub loss_pct: f64,
+    /// Mean RTT of the probes answered so far, in microseconds
+    pub mean_us: Option<f64>,
+    /// P99 RTT of the probes answered so far, in microseconds
+    pub p99_us: Option<f64>,
+    /// Time since the start of the phase
+    pub elapsed_secs: f64,
+    /// Whether this is the last line of the phase
+    pub done: bool,
+}
+
+/// Writes a [`ProgressLine`] every interval of a measurement phase
+pub struct ProgressStream {
+    writer: Option<Box<dyn Write + Send>>,
+    interval: usize,
+    total: usize,
+    /// Answered probes so far
+    answered: Statistics,
+    done: usize,
+    lost: usize,
+}
+
+impl ProgressStream {
+    /// Open `target` for a phase of `total` probes, writing a line every `interval`
+    pub fn open(target: &ProgressTarget, interval: usize, total: usize) -> Result<Self> {
+        let writer = target.open().map_err(|e| {
+            ClientError::Measurement(format!("Failed to open progress stream {}: {}", target, e))
+        })?;
+        info!(target = %target, "Streaming progress as NDJSON");
+        Self::new(writer, interval, total)
+    }
+
+    /// Stream to `writer`
+    pub fn new(writer: Box<dyn Write + Send>, interval: usize, total: usize) -> Result<Self> {
+        Ok(Self {
+            writer: Some(writer),
+            interval: interval.max(1),
+            total,
+            answered: Statistics::empty()?,
+            done: 0,
+            lost: 0,
+        })
+    }
+
+    /// Count a completed probe, answered after `rtt_ns` or lost, `elapsed` into the
+    /// phase, and write a line if an interval is complete
+    pub fn record(&mut self, rtt_ns: Option<u64>, elapsed: Duration) -> Result<()> {
+        self.done += 1;
+        match rtt_ns {
+            Some(rtt_ns) => self.answered.record(rtt_ns)?,
+            None => self.lost += 1,
+        }
+        // The last probe is left to the final line
+        if self.done.is_multiple_of(self.interval) && self.done < self.total {
+            self.write(elapsed, false);
+        }
+        Ok(())
+    }
+
+    /// Write the last line of the phase, `elapsed` after its start
+    pub fn finish(mut self, elapsed: Duration) {
+        self.write(elapsed, true);
+    }
+
+    /// Progress so far
+    pub fn line(&self, elapsed: Duration, done: bool) -> ProgressLine {
+        let answered = self.answered.count() > 0;
+        ProgressLine {
+            packets_done: self.done,
+            packets_total: self.total,
+            packets_lost: self.lost,
+            loss_pct: if self.done == 0 {
+                0.0
+            } else {
+                self.lost as f64 / self.done as f64 * 100.0
+            },
+            mean_us: answered.then(|| self.answered.mean() / 1000.0),
+            p99_us: answered.then(|| self.answered.percentile(0.99) as f64 / 1000.0),
+            elapsed_secs: elapsed.as_secs_f64(),
+            done,
+        }
+    }
+
+    /// Write one line, dropping the stream if its consumer went away
+    fn write(&mut self, elapsed: Duration, done: bool) {
+        let line = self.line(elapsed, done);
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+        let written = serde_json::to_string(&line)
+            .map_err(io::Error::other)
+            .and_then(|json| {
+                writeln!(writer, "{}", json)?;
+                writer.flush()
+            });
+        if let Err(e) = written {
+            warn!(error = %e, "Progress stream closed; no more progress lines");
+            self.writer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_progress_target_parse() {
+        assert_eq!("-".parse(), Ok(ProgressTarget::Stdout));
+        assert_eq!(
+            "tcp:127.0.0.1:9000".parse(),
+            Ok(ProgressTarget::Tcp("127.0.0.1:9000".into()))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/run/synapse.sock".parse(),
+            Ok(ProgressTarget::Unix("/run/synapse.sock".into()))
+        );
+        for invalid in ["", "stdout", "tcp:", "tcp:localhost", "tcp::9000", "unix:"] {
+            assert!(invalid.parse::<ProgressTarget>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_progress_stream() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let target = ProgressTarget::Tcp(listener.local_addr()?.to_string());
+        let mut stream = ProgressStream::open(&target, 2, 5)?;
+        let (consumer, _) = listener.accept()?;
+
+        for rtt_ns in [Some(40_000), None, Some(60_000), Some(80_000), Some(50_000)] {
+            stream.record(rtt_ns, Duration::from_millis(10))?;
+        }
+        stream.finish(Duration::from_millis(50));
+
+        let lines: Vec<serde_json::Value> = BufReader::new(consumer)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        // A line every 2 probes, the fifth left to the final line
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["packets_done"], 2);
+        assert_eq!(lines[0]["loss_pct"], 50.0);
+        assert_eq!(lines[1]["done"], false);
+        assert_eq!(lines[2]["packets_done"], 5);
+        assert_eq!(lines[2]["packets_lost"], 1);
+        assert_eq!(lines[2]["done"], true);
+        let mean_us = lines[2]["mean_us"].as_f64().unwrap();
+        assert!((mean_us - 57.5).abs() < 0.5, "{}", mean_us);
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_stream_consumer_gone() -> Result<()> {
+        let line = ProgressStream::new(Box::new(io::sink()), 100, 10)?.line(Duration::ZERO, false);
+        assert_eq!((line.mean_us, line.p99_us), (None, None));
+
+        // A closed consumer stops the stream without failing the run
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let target = ProgressTarget::Tcp(listener.local_addr()?.to_string());
+        let mut stream = ProgressStream::open(&target, 1, 1000)?;
+        drop(listener.accept()?);
+        for _ in 0..100 {
+            stream.record(Some(10_000), Duration::ZERO)?;
+        }
+        assert!(stream.writer.is_none());
+        Ok(())
+    }
+}
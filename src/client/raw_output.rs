@@ -0,0 +1,111 @@
+//! Streaming CSV export of every measured packet
+//!
+//! The report only shows aggregates. With `--raw-output` every probe of the measurement
+//! phase is written as one CSV row while the run is in progress, for offline analysis
+//! in pandas or R:
+//!
+//! ```text
+//! sequence,timestamp_ns,latency_ns,status
+//! 0,1767225600000012345,48213,ok
+//! 1,1767225600001013872,,lost
+//! ```
+//!
+//! `timestamp_ns` is the time the probe was sent, in nanoseconds since the UNIX epoch;
+//! `latency_ns` is the round trip, empty for a lost packet. Rows are buffered and
+//! flushed when the writer is finished or dropped, so a failed run keeps the packets
+//! measured until it stopped.
+
+use crate::client::error::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// CSV header of the raw output file
+pub const RAW_OUTPUT_HEADER: &str = "sequence,timestamp_ns,latency_ns,status";
+
+/// Writes one CSV row per measured packet
+pub struct RawOutput {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started_at_ns: u64,
+}
+
+impl RawOutput {
+    /// Create the file and write the header
+    ///
+    /// Creating it up front surfaces an unwritable path before the run starts.
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", RAW_OUTPUT_HEADER)?;
+        info!(path = %path.display(), "Streaming raw samples");
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            started_at_ns: 0,
+        })
+    }
+
+    /// Mark the start of the measurement phase; send offsets are relative to it
+    pub fn set_started_at(&mut self, started_at: SystemTime) {
+        self.started_at_ns = started_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+    }
+
+    /// Path of the raw output file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the row of one packet, sent `send_offset_ns` after the start of the phase
+    pub fn record(
+        &mut self,
+        sequence: u64,
+        send_offset_ns: u64,
+        rtt_ns: Option<u64>,
+    ) -> Result<()> {
+        let timestamp_ns = self.started_at_ns + send_offset_ns;
+        match rtt_ns {
+            Some(rtt_ns) => writeln!(self.writer, "{},{},{},ok", sequence, timestamp_ns, rtt_ns)?,
+            None => writeln!(self.writer, "{},{},,lost", sequence, timestamp_ns)?,
+        }
+        Ok(())
+    }
+
+    /// Flush every buffered row to the file
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_raw_output_rows() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("synapse-raw-{}.csv", std::process::id()));
+        let mut raw = RawOutput::create(&path)?;
+        raw.set_started_at(UNIX_EPOCH + Duration::from_secs(2));
+        raw.record(0, 500, Some(48_213))?;
+        raw.record(1, 1_000_500, None)?;
+        raw.finish()?;
+
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                RAW_OUTPUT_HEADER,
+                "0,2000000500,48213,ok",
+                "1,2001000500,,lost"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+//! Serializable snapshot of a measurement run
+//!
+//! A run record stores the counters and the full latency histogram of a run so it can be
+//! reloaded later (e.g. by `client analyze`) without keeping every raw sample around.
+
+use crate::client::build_info::BuildInfo;
+use crate::client::error::{ClientError, Result};
+use crate::client::statistics::Statistics;
+use crate::protocol::PACKET_SIZE;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// Current on-disk format version of [`RunRecord`]
+pub const RUN_RECORD_FORMAT_VERSION: u32 = 1;
+
+/// Counters and latency histogram of a (possibly still running) measurement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// On-disk format version
+    pub format_version: u32,
+    /// Version of the synapse client that wrote the record
+    pub synapse_version: String,
+    /// Server address the run was measured against
+    pub server: String,
+    /// Whether the measurement phase finished (false for periodic checkpoints)
+    pub complete: bool,
+    /// Wall-clock time the record was written (milliseconds since the UNIX epoch)
+    pub written_at_unix_ms: u64,
+    /// Number of packets the run was configured to send
+    pub planned_packets: usize,
+    /// Number of packets sent so far
+    pub sent_packets: usize,
+    /// Number of packets lost or timed out so far
+    pub lost_packets: usize,
+    /// Time spent in the measurement phase so far, in seconds
+    pub elapsed_secs: f64,
+    /// Minimum observed latency in nanoseconds (unclamped)
+    pub min_ns: u64,
+    /// Maximum observed latency in nanoseconds (unclamped)
+    pub max_ns: u64,
+    /// Number of latencies clamped to the histogram bounds
+    pub clamped_count: usize,
+    /// Recorded histogram as `[value_ns, count]` pairs
+    pub histogram: Vec<(u64, u64)>,
+    /// Seed the run's randomized choices were derived from (absent in older records)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Send-complete component of the RTT (time spent inside the send call)
+    #[serde(default)]
+    pub send_complete: HistogramRecord,
+    /// Receive-wait component of the RTT (send completion until the reply arrived)
+    #[serde(default)]
+    pub recv_wait: HistogramRecord,
+    /// Application timestamps of every packet (only with `--record-packets`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packets: Option<PacketTimestamps>,
+    /// Build of the client that wrote the record (absent in older records)
+    #[serde(default)]
+    pub build: Option<BuildInfo>,
+    /// User tags of the run (`--tag`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Application-side send time and outcome of every packet of a run
+///
+/// Stored column-wise to keep large records compact; all columns have one entry per
+/// sent packet, in send order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketTimestamps {
+    /// Wall-clock start of the measurement phase (nanoseconds since the UNIX epoch)
+    pub started_at_unix_ns: u64,
+    /// Size of every packet in bytes (absent in records from before `--payload-size`)
+    #[serde(default = "default_packet_size")]
+    pub packet_size: usize,
+    /// Sequence number of each packet
+    pub sequences: Vec<u64>,
+    /// Time each packet was handed to the socket, relative to the start
+    pub send_offsets_ns: Vec<u64>,
+    /// Round-trip latency of each packet as seen by the application, `None` if lost
+    pub rtts_ns: Vec<Option<u64>>,
+}
+
+/// Records without a packet size predate `--payload-size`, when every packet was a bare
+/// 8-byte sequence number
+fn default_packet_size() -> usize {
+    8
+}
+
+impl Default for PacketTimestamps {
+    fn default() -> Self {
+        Self {
+            started_at_unix_ns: 0,
+            packet_size: PACKET_SIZE,
+            sequences: Vec::new(),
+            send_offsets_ns: Vec::new(),
+            rtts_ns: Vec::new(),
+        }
+    }
+}
+
+impl PacketTimestamps {
+    /// Empty timestamps for a measurement phase that started at `started_at`
+    pub fn new(started_at: SystemTime, packet_size: usize) -> Self {
+        Self {
+            started_at_unix_ns: started_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0),
+            packet_size,
+            ..Self::default()
+        }
+    }
+
+    /// Append one packet
+    pub fn push(&mut self, sequence: u64, send_offset_ns: u64, rtt_ns: Option<u64>) {
+        self.sequences.push(sequence);
+        self.send_offsets_ns.push(send_offset_ns);
+        self.rtts_ns.push(rtt_ns);
+    }
+
+    /// Number of packets
+    pub fn len(&self) -> usize {
+        self.sequences.len()
+    }
+
+    /// Whether no packet was recorded
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+    }
+}
+
+/// Serialized latency histogram of one RTT component
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistogramRecord {
+    /// Minimum observed value in nanoseconds (unclamped)
+    pub min_ns: u64,
+    /// Maximum observed value in nanoseconds (unclamped)
+    pub max_ns: u64,
+    /// Number of values clamped to the histogram bounds
+    pub clamped_count: usize,
+    /// Recorded histogram as `[value_ns, count]` pairs
+    pub histogram: Vec<(u64, u64)>,
+}
+
+impl HistogramRecord {
+    /// Capture the histogram of `stats`
+    pub fn from_statistics(stats: &Statistics) -> Self {
+        Self {
+            min_ns: stats.min(),
+            max_ns: stats.max(),
+            clamped_count: stats.clamped_count(),
+            histogram: stats.recorded_values(),
+        }
+    }
+
+    /// Rebuild the statistics stored in this histogram
+    pub fn statistics(&self) -> Result<Statistics> {
+        Statistics::from_recorded(
+            &self.histogram,
+            self.min_ns,
+            self.max_ns,
+            self.clamped_count,
+        )
+    }
+}
+
+impl RunRecord {
+    /// Build a record from the statistics and counters of a run
+    pub fn new(
+        server: &str,
+        stats: &Statistics,
+        planned_packets: usize,
+        sent_packets: usize,
+        lost_packets: usize,
+        elapsed: Duration,
+        complete: bool,
+    ) -> Self {
+        let written_at_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            format_version: RUN_RECORD_FORMAT_VERSION,
+            synapse_version: env!("CARGO_PKG_VERSION").to_string(),
+            server: server.to_string(),
+            complete,
+            written_at_unix_ms,
+            planned_packets,
+            sent_packets,
+            lost_packets,
+            elapsed_secs: elapsed.as_secs_f64(),
+            min_ns: stats.min(),
+            max_ns: stats.max(),
+            clamped_count: stats.clamped_count(),
+            histogram: stats.recorded_values(),
+            seed: None,
+            send_complete: HistogramRecord::default(),
+            recv_wait: HistogramRecord::default(),
+            packets: None,
+            build: Some(BuildInfo::current()),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Record the seed the run was derived from
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Attach the send-complete and receive-wait components of the RTT
+    pub fn with_components(mut self, send_complete: &Statistics, recv_wait: &Statistics) -> Self {
+        self.send_complete = HistogramRecord::from_statistics(send_complete);
+        self.recv_wait = HistogramRecord::from_statistics(recv_wait);
+        self
+    }
+
+    /// Attach the application timestamps of every packet
+    pub fn with_packets(mut self, packets: PacketTimestamps) -> Self {
+        self.packets = Some(packets);
+        self
+    }
+
+    /// Attach the user tags of the run
+    pub fn with_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Per-packet application timestamps, which only `--record-packets` runs store
+    pub fn packet_timestamps(&self) -> Result<&PacketTimestamps> {
+        self.packets.as_ref().ok_or_else(|| {
+            ClientError::Config(
+                "the run record has no per-packet timestamps; record the run with \
+                 --checkpoint <PATH> --record-packets"
+                    .into(),
+            )
+        })
+    }
+
+    /// Port of the server the run was measured against
+    pub fn server_port(&self) -> Option<u16> {
+        self.server.rsplit_once(':')?.1.parse().ok()
+    }
+
+    /// Rebuild the latency statistics stored in this record
+    pub fn statistics(&self) -> Result<Statistics> {
+        Statistics::from_recorded(
+            &self.histogram,
+            self.min_ns,
+            self.max_ns,
+            self.clamped_count,
+        )
+    }
+
+    /// Rebuild the send-complete component statistics (empty for older records)
+    pub fn send_statistics(&self) -> Result<Statistics> {
+        self.send_complete.statistics()
+    }
+
+    /// Rebuild the receive-wait component statistics (empty for older records)
+    pub fn recv_wait_statistics(&self) -> Result<Statistics> {
+        self.recv_wait.statistics()
+    }
+
+    /// Time spent in the measurement phase
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.elapsed_secs.max(0.0))
+    }
+
+    /// Atomically write the record to `path`
+    ///
+    /// The record is written to a temporary sibling file, synced to disk and then renamed
+    /// over the destination, so a crash mid-write never leaves a truncated record behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = temporary_path(path);
+        let json = serde_json::to_vec_pretty(self).map_err(|e| {
+            ClientError::Measurement(format!("Failed to serialize run record: {}", e))
+        })?;
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp_path, path)?;
+
+        debug!(
+            path = %path.display(),
+            sent_packets = self.sent_packets,
+            complete = self.complete,
+            "Run record written"
+        );
+        Ok(())
+    }
+
+    /// Load a record previously written with [`RunRecord::save`]
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let record: RunRecord = serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            ClientError::Config(format!(
+                "{} is not a valid run record: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        if record.format_version > RUN_RECORD_FORMAT_VERSION {
+            return Err(ClientError::Config(format!(
+                "{} uses run record format {}, but this client only understands up to {}",
+                path.display(),
+                record.format_version,
+                RUN_RECORD_FORMAT_VERSION
+            )));
+        }
+        Ok(record)
+    }
+}
+
+/// Returns the temporary path used while atomically writing `path`
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("synapse-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_run_record_save_load_roundtrip() -> Result<()> {
+        let stats = Statistics::new(&[1000, 2000, 3000, 50_000])?;
+        let record = RunRecord::new(
+            "127.0.0.1:8080",
+            &stats,
+            10,
+            5,
+            1,
+            Duration::from_millis(1500),
+            false,
+        )
+        .with_components(
+            &Statistics::new(&[500, 600, 700, 800])?,
+            &Statistics::new(&[500, 1400, 2300, 49_200])?,
+        )
+        .with_seed(42)
+        .with_packets(PacketTimestamps {
+            started_at_unix_ns: 1_700_000_000_000_000_000,
+            packet_size: 1400,
+            sequences: vec![0, 1, 2, 3, 4],
+            send_offsets_ns: vec![0, 10_000, 20_000, 30_000, 40_000],
+            rtts_ns: vec![Some(1000), Some(2000), None, Some(3000), Some(50_000)],
+        })
+        .with_tags(BTreeMap::from([(
+            "build".to_string(),
+            "4f2a9c1".to_string(),
+        )]));
+
+        let path = temp_file("record-roundtrip.json");
+        record.save(&path)?;
+        let loaded = RunRecord::load(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(loaded, record);
+        assert!(!temporary_path(&path).exists());
+
+        let rebuilt = loaded.statistics()?;
+        assert_eq!(rebuilt.count(), 4);
+        assert_eq!(rebuilt.max(), 50_000);
+        assert_eq!(loaded.elapsed(), Duration::from_millis(1500));
+        assert_eq!(loaded.send_statistics()?.max(), 800);
+        assert_eq!(loaded.seed, Some(42));
+        assert_eq!(loaded.recv_wait_statistics()?.count(), 4);
+        assert_eq!(loaded.packets.as_ref().map(PacketTimestamps::len), Some(5));
+        assert_eq!(loaded.build, Some(BuildInfo::current()));
+        assert_eq!(loaded.tags["build"], "4f2a9c1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_record_loads_without_components() -> Result<()> {
+        let stats = Statistics::new(&[1000])?;
+        let record = RunRecord::new("host:1", &stats, 1, 1, 0, Duration::from_secs(1), true);
+        let mut json = serde_json::to_value(&record).unwrap();
+        let object = json.as_object_mut().unwrap();
+        object.remove("send_complete");
+        object.remove("recv_wait");
+        object.remove("build");
+
+        let path = temp_file("record-no-components.json");
+        fs::write(&path, serde_json::to_vec(&json).unwrap())?;
+        let loaded = RunRecord::load(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(loaded.statistics()?.count(), 1);
+        assert_eq!(loaded.send_statistics()?.count(), 0);
+        assert!(loaded.packets.is_none());
+        assert!(loaded.build.is_none());
+        assert!(loaded.tags.is_empty());
+        assert!(matches!(
+            loaded.packet_timestamps(),
+            Err(ClientError::Config(_))
+        ));
+        assert_eq!(loaded.server_port(), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_record_load_rejects_garbage() {
+        let path = temp_file("record-garbage.json");
+        fs::write(&path, b"not json").unwrap();
+        let result = RunRecord::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(ClientError::Config(_))));
+    }
+}
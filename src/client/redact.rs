@@ -0,0 +1,108 @@
+//! Redaction of host names and addresses in exported results
+//!
+//! Results are often shared outside the team that ran them, e.g. with a network vendor.
+//! With `--redact` every host name or IP address written to an export (checkpoints, run
+//! records, report bundles, matrix records and CSV) is replaced by a token such as
+//! `host-3f9a0c1e`. Ports are kept. The same host always gets the same token, so runs
+//! against one server can still be told apart from runs against another.
+//!
+//! Tokens are a 64-bit FNV-1a hash of the host, keyed with `--redact-key` if given.
+//! Without a key anyone can hash candidate addresses and compare, which is quick for
+//! IPv4; give a key that stays private to make tokens irreversible in practice.
+
+/// FNV-1a 64-bit offset basis
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+/// FNV-1a 64-bit prime
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Replaces hosts by stable tokens
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    key: String,
+}
+
+impl Redactor {
+    /// Create a redactor whose tokens are keyed with `key`
+    pub fn new(key: Option<&str>) -> Self {
+        Self {
+            key: key.unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Token standing in for `host`
+    pub fn host(&self, host: &str) -> String {
+        let hash = self
+            .key
+            .bytes()
+            .chain([0])
+            .chain(host.to_ascii_lowercase().bytes())
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            });
+        format!("host-{:08x}", (hash >> 32) ^ (hash & 0xffff_ffff))
+    }
+
+    /// `addr` (`HOST`, `HOST:PORT` or `[IPV6]:PORT`) with the host replaced by its token
+    pub fn address(&self, addr: &str) -> String {
+        match split_host_port(addr) {
+            (host, Some(port)) => format!("{}:{}", self.host(host), port),
+            (host, None) => self.host(host),
+        }
+    }
+
+    /// `text` with every occurrence of the host of `addr` replaced by its token
+    pub fn scrub(&self, text: &str, addr: &str) -> String {
+        let (host, _) = split_host_port(addr);
+        if host.is_empty() {
+            return text.to_string();
+        }
+        text.replace(host, &self.host(host))
+    }
+}
+
+/// Split an address into host and port; IPv6 hosts lose their brackets
+fn split_host_port(addr: &str) -> (&str, Option<&str>) {
+    if let Some(rest) = addr.strip_prefix('[') {
+        if let Some((host, after)) = rest.split_once(']') {
+            return (host, after.strip_prefix(':'));
+        }
+    }
+    match addr.rsplit_once(':') {
+        // More than one colon without brackets: a bare IPv6 address
+        Some((host, _)) if host.contains(':') => (addr, None),
+        Some((host, port)) => (host, Some(port)),
+        None => (addr, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_address() {
+        let redactor = Redactor::new(None);
+        let token = redactor.host("10.0.0.2");
+        assert!(token.starts_with("host-") && token.len() == 13, "{}", token);
+        assert_eq!(redactor.address("10.0.0.2:8080"), format!("{}:8080", token));
+        assert_eq!(
+            redactor.address("[fe80::1]:9000"),
+            format!("{}:9000", redactor.host("fe80::1"))
+        );
+        assert_eq!(redactor.address("fe80::1"), redactor.host("fe80::1"));
+        assert_eq!(redactor.host("DB-1.example"), redactor.host("db-1.example"));
+        assert_ne!(redactor.host("10.0.0.2"), redactor.host("10.0.0.3"));
+
+        // A key changes every token
+        assert_ne!(Redactor::new(Some("secret")).host("10.0.0.2"), token);
+    }
+
+    #[test]
+    fn test_redact_scrub() {
+        let redactor = Redactor::new(Some("k"));
+        let text = "Failed to connect to server at 10.0.0.2:8080: refused";
+        let scrubbed = redactor.scrub(text, "10.0.0.2:8080");
+        assert!(!scrubbed.contains("10.0.0.2"), "{}", scrubbed);
+        assert!(scrubbed.contains(&redactor.address("10.0.0.2:8080")));
+    }
+}
@@ -0,0 +1,603 @@
+//! Round-robin probing of a pool of reflectors, with failover
+//!
+//! An edge footprint has a reflector at every site, and one client per site is as many
+//! runs to start and reports to read. `--reflector HOST:PORT` adds reflectors to the
+//! `--server` one: the client keeps a UDP socket to each, warm from the warmup on, and
+//! sends the probes to them in turn. A reflector that misses [`DOWN_AFTER`] probes in a
+//! row is down: its turns fail over to the next reflector up, all but every
+//! [`RETRY_EVERY`]th, which checks whether it is back. A reflector that refuses probes
+//! (ICMP port or host unreachable) loses them rather than ending the run.
+//!
+//! The report gives each reflector its availability (the share of its probes answered),
+//! its outages and the latency of its answers, and sums the pool up in one
+//! [`WeightedScore`]: `--weights` weighs each reflector, by the traffic it serves for
+//! instance, so one quiet site going down does not read like the busiest one.
+
+use crate::client::connections::ConnectionStats;
+use crate::client::dscp::Dscp;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::MeasurementResult;
+use crate::client::socket::{AbortHandle, NetworkSocket, UdpNetworkSocket};
+use crate::client::timestamping::KernelTimestamps;
+use crate::protocol::Packet;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Probes in a row a reflector misses before it counts as down
+pub const DOWN_AFTER: usize = 3;
+
+/// A reflector that is down still takes one of this many of its turns, to notice when it
+/// is back
+pub const RETRY_EVERY: usize = 10;
+
+/// Where one probe was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// Reflector the probe was sent to
+    pub target: usize,
+    /// Reflector whose turn it was; another one if the probe failed over
+    pub turn_of: usize,
+}
+
+/// Route of every probe sent through a [`ReflectorPool`], by sequence number
+///
+/// Shared with the pool, so the routes stay readable once the pool is wrapped and handed
+/// to the measurement. A sequence number sent twice (in warmup, then in measurement)
+/// keeps its last route.
+#[derive(Debug, Clone, Default)]
+pub struct Routes(Arc<Mutex<HashMap<u64, Route>>>);
+
+impl Routes {
+    fn insert(&self, sequence: u64, route: Route) -> Result<()> {
+        self.0
+            .lock()
+            .map_err(|e| ClientError::Measurement(format!("Failed to lock probe routes: {}", e)))?
+            .insert(sequence, route);
+        Ok(())
+    }
+
+    /// Route of the probe with sequence number `sequence`, if it was sent
+    pub fn get(&self, sequence: u64) -> Result<Option<Route>> {
+        Ok(self
+            .0
+            .lock()
+            .map_err(|e| ClientError::Measurement(format!("Failed to lock probe routes: {}", e)))?
+            .get(&sequence)
+            .copied())
+    }
+}
+
+/// Probes a reflector missed in a row, and turns it let go by since it went down
+#[derive(Debug, Clone, Copy, Default)]
+struct Health {
+    misses: usize,
+    skipped: usize,
+}
+
+impl Health {
+    fn is_down(&self) -> bool {
+        self.misses >= DOWN_AFTER
+    }
+}
+
+/// Turn taking of the reflectors and the probe in flight
+#[derive(Debug)]
+struct Rotation {
+    health: Vec<Health>,
+    /// Reflector whose turn is next
+    next_turn: usize,
+    /// Reflector, sequence number and refusal of the last probe sent
+    in_flight: Option<(usize, u64, bool)>,
+}
+
+impl Rotation {
+    fn new(reflectors: usize) -> Self {
+        Self {
+            health: vec![Health::default(); reflectors],
+            next_turn: 0,
+            in_flight: None,
+        }
+    }
+
+    /// Route of the next probe: the reflector whose turn it is if it is up or due a
+    /// retry, else the next one up
+    fn route(&mut self) -> Route {
+        let reflectors = self.health.len();
+        let turn_of = self.next_turn;
+        self.next_turn = (turn_of + 1) % reflectors;
+
+        let health = &mut self.health[turn_of];
+        if health.is_down() {
+            health.skipped += 1;
+            if !health.skipped.is_multiple_of(RETRY_EVERY) {
+                // With every reflector down, the probe stays with its own
+                let target = (1..reflectors)
+                    .map(|step| (turn_of + step) % reflectors)
+                    .find(|&other| !self.health[other].is_down())
+                    .unwrap_or(turn_of);
+                return Route { target, turn_of };
+            }
+        }
+        Route {
+            target: turn_of,
+            turn_of,
+        }
+    }
+
+    /// Count a probe to `target` as answered or missed; returns whether the reflector
+    /// just went down (`Some(true)`) or came back (`Some(false)`)
+    fn record(&mut self, target: usize, answered: bool) -> Option<bool> {
+        let health = &mut self.health[target];
+        let was_down = health.is_down();
+        if answered {
+            *health = Health::default();
+        } else {
+            health.misses += 1;
+        }
+        (was_down != health.is_down()).then_some(!was_down)
+    }
+}
+
+/// Whether `error` means the reflector (or its host) refused the probe
+fn is_refusal(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::Io(e) if matches!(
+            e.kind(),
+            ErrorKind::ConnectionRefused | ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable
+        )
+    )
+}
+
+/// UDP sockets to several reflectors, taking turns with failover
+pub struct ReflectorPool {
+    sockets: Vec<UdpNetworkSocket>,
+    addrs: Vec<SocketAddr>,
+    rotation: Mutex<Rotation>,
+    routes: Routes,
+}
+
+impl ReflectorPool {
+    /// Open a UDP socket to each of `reflectors`, in order
+    pub fn connect(reflectors: &[String]) -> Result<Self> {
+        if reflectors.is_empty() {
+            return Err(ClientError::Config(
+                "a reflector pool needs a reflector".into(),
+            ));
+        }
+        let sockets = reflectors
+            .iter()
+            .map(|addr| UdpNetworkSocket::connect(addr))
+            .collect::<Result<Vec<_>>>()?;
+        let addrs = sockets
+            .iter()
+            .map(UdpNetworkSocket::peer_addr)
+            .collect::<Result<Vec<_>>>()?;
+        debug!(reflectors = ?addrs, "Reflector pool connected");
+        Ok(Self {
+            rotation: Mutex::new(Rotation::new(sockets.len())),
+            sockets,
+            addrs,
+            routes: Routes::default(),
+        })
+    }
+
+    /// Sockets of the reflectors in order, to configure them alike
+    pub fn sockets_mut(&mut self) -> impl Iterator<Item = &mut UdpNetworkSocket> {
+        self.sockets.iter_mut()
+    }
+
+    /// Address of every reflector, in order
+    pub fn reflector_addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Source address of the socket to every reflector, in order
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>> {
+        self.sockets
+            .iter()
+            .map(UdpNetworkSocket::local_addr)
+            .collect()
+    }
+
+    /// Route of every probe sent so far and from now on
+    pub fn routes(&self) -> Routes {
+        self.routes.clone()
+    }
+
+    fn rotation(&self) -> Result<std::sync::MutexGuard<'_, Rotation>> {
+        self.rotation
+            .lock()
+            .map_err(|e| ClientError::Measurement(format!("Failed to lock reflector pool: {}", e)))
+    }
+
+    /// Reflector of the probe in flight, the first one before any probe
+    fn current(&self) -> Result<usize> {
+        Ok(self
+            .rotation()?
+            .in_flight
+            .map_or(0, |(target, _, _)| target))
+    }
+}
+
+impl NetworkSocket for ReflectorPool {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        let mut rotation = self.rotation()?;
+        let route = rotation.route();
+        self.routes.insert(packet.sequence.0, route)?;
+        let sent = self.sockets[route.target].send_packet(packet);
+        // A refusal is the reflector's loss; it must not end the run
+        let refused = sent.as_ref().err().is_some_and(is_refusal);
+        rotation.in_flight = Some((route.target, packet.sequence.0, refused));
+        if refused {
+            return Ok(0);
+        }
+        sent
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        let Some((target, sequence, refused)) = self.rotation()?.in_flight else {
+            return Err(ClientError::Measurement("No probe in flight".into()));
+        };
+        let reply = if refused {
+            Err(ClientError::Io(ErrorKind::ConnectionRefused.into()))
+        } else {
+            self.sockets[target].recv_packet()
+        };
+
+        let answered = matches!(&reply, Ok(packet) if packet.sequence.0 == sequence);
+        match self.rotation()?.record(target, answered) {
+            Some(true) => warn!(
+                reflector = %self.addrs[target],
+                misses = DOWN_AFTER,
+                "Reflector down; failing its turns over to the others"
+            ),
+            Some(false) => info!(reflector = %self.addrs[target], "Reflector back up"),
+            None => {}
+        }
+        match reply {
+            // Reported as a timeout, so the probe counts as lost
+            Err(e) if is_refusal(&e) => Err(ClientError::Io(ErrorKind::WouldBlock.into())),
+            reply => reply,
+        }
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        self.sockets
+            .iter()
+            .try_for_each(|socket| socket.set_timeout(timeout))
+    }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        let handles = self
+            .sockets
+            .iter()
+            .map(UdpNetworkSocket::abort_handle)
+            .collect::<Result<Option<Vec<_>>>>()?;
+        Ok(handles.map(|handles| {
+            Box::new(move || handles.into_iter().for_each(|abort| abort())) as AbortHandle
+        }))
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        self.sockets[self.current()?].take_error()
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        let current = self.current()?;
+        self.sockets[current].kernel_timestamps()
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        let current = self.current().ok()?;
+        self.sockets[current].reply_dscp()
+    }
+
+    fn accept_any_reply(&mut self) {
+        self.sockets
+            .iter_mut()
+            .for_each(NetworkSocket::accept_any_reply);
+    }
+}
+
+/// Outcome of one reflector of a pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectorStats {
+    /// Address of the reflector
+    pub reflector: SocketAddr,
+    /// Probes sent to the reflector and the latency of its answers
+    pub stats: ConnectionStats,
+    /// Times the reflector missed [`DOWN_AFTER`] probes or more in a row
+    pub outages: usize,
+    /// Turns of the reflector sent to another one while it was down
+    pub failed_over: usize,
+}
+
+impl ReflectorStats {
+    /// Share of the probes sent to the reflector it answered, in percent
+    pub fn availability_pct(&self) -> f64 {
+        if self.stats.sent == 0 {
+            return 0.0;
+        }
+        (self.stats.sent - self.stats.lost) as f64 / self.stats.sent as f64 * 100.0
+    }
+}
+
+/// Availability and latency of a pool of reflectors, each counting for its weight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedScore {
+    /// Weighted mean of the availability of the reflectors, in percent
+    pub availability_pct: f64,
+    /// Weighted mean of the median RTT of the reflectors that answered, in nanoseconds
+    pub p50_ns: Option<f64>,
+    /// Weighted mean of the P99 RTT of the reflectors that answered, in nanoseconds
+    pub p99_ns: Option<f64>,
+}
+
+/// Score of `reflectors` weighted by `weights`, in the same order; `None` without a
+/// reflector or weight
+///
+/// A reflector that answered nothing counts for the availability only; the latency is
+/// weighted over the others.
+pub fn weighted_score(reflectors: &[ReflectorStats], weights: &[f64]) -> Option<WeightedScore> {
+    let weighted: Vec<(&ReflectorStats, f64)> =
+        reflectors.iter().zip(weights.iter().copied()).collect();
+    let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let availability_pct = weighted
+        .iter()
+        .map(|(reflector, weight)| reflector.availability_pct() * weight)
+        .sum::<f64>()
+        / total;
+
+    let answered: Vec<&(&ReflectorStats, f64)> = weighted
+        .iter()
+        .filter(|(reflector, _)| reflector.stats.sent > reflector.stats.lost)
+        .collect();
+    let answered_total: f64 = answered.iter().map(|(_, weight)| weight).sum();
+    let latency = |rtt_ns: fn(&ConnectionStats) -> u64| {
+        (answered_total > 0.0).then(|| {
+            answered
+                .iter()
+                .map(|(reflector, weight)| rtt_ns(&reflector.stats) as f64 * weight)
+                .sum::<f64>()
+                / answered_total
+        })
+    };
+    Some(WeightedScore {
+        availability_pct,
+        p50_ns: latency(|stats| stats.p50_ns),
+        p99_ns: latency(|stats| stats.p99_ns),
+    })
+}
+
+/// Break a measurement over a [`ReflectorPool`] down by reflector
+///
+/// `reflectors` and `sources` are the addresses of the reflectors and of the sockets to
+/// them, in order.
+pub fn reflector_stats(
+    result: &MeasurementResult,
+    routes: &Routes,
+    reflectors: &[SocketAddr],
+    sources: &[SocketAddr],
+) -> Result<Vec<ReflectorStats>> {
+    let count = reflectors.len();
+    let mut latencies = vec![Vec::new(); count];
+    // Sequence number and answer of every probe sent to each reflector
+    let mut outcomes: Vec<Vec<(u64, bool)>> = vec![Vec::new(); count];
+    let mut failed_over = vec![0; count];
+    let answered = result.sequences.iter().zip(&result.latencies);
+    let lost = result
+        .lost_sequences
+        .iter()
+        .map(|sequence| (sequence, None));
+    for (&sequence, latency) in answered
+        .map(|(sequence, &latency)| (sequence, Some(latency)))
+        .chain(lost)
+    {
+        let Some(route) = routes.get(sequence)? else {
+            continue;
+        };
+        outcomes[route.target].push((sequence, latency.is_some()));
+        latencies[route.target].extend(latency);
+        if route.target != route.turn_of {
+            failed_over[route.turn_of] += 1;
+        }
+    }
+
+    outcomes
+        .iter_mut()
+        .zip(latencies)
+        .zip(failed_over)
+        .enumerate()
+        .map(|(index, ((outcomes, latencies), failed_over))| {
+            outcomes.sort_unstable();
+            let mut outages = 0;
+            let mut misses = 0;
+            for &(_, answered) in outcomes.iter() {
+                misses = if answered { 0 } else { misses + 1 };
+                if misses == DOWN_AFTER {
+                    outages += 1;
+                }
+            }
+            Ok(ReflectorStats {
+                reflector: reflectors[index],
+                stats: ConnectionStats::from_samples(
+                    index,
+                    sources.get(index).copied(),
+                    &latencies,
+                    outcomes.len(),
+                    outcomes.len() - latencies.len(),
+                )?,
+                outages,
+                failed_over,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::socket::challenge_hellos;
+    use crate::protocol::SequenceNumber;
+    use std::net::UdpSocket;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_rotation_fails_over() {
+        let mut rotation = Rotation::new(3);
+        let targets: Vec<usize> = (0..6).map(|_| rotation.route().target).collect();
+        assert_eq!(targets, vec![0, 1, 2, 0, 1, 2]);
+
+        // Reflector 1 misses three probes in a row and goes down
+        assert_eq!(rotation.record(1, false), None);
+        assert_eq!(rotation.record(1, false), None);
+        assert_eq!(rotation.record(1, false), Some(true));
+        let routes: Vec<Route> = (0..3).map(|_| rotation.route()).collect();
+        assert_eq!(
+            routes[1],
+            Route {
+                target: 2,
+                turn_of: 1
+            }
+        );
+        assert_eq!(routes[2].target, 2);
+
+        // Every RETRY_EVERYth of its turns goes to it anyway, and an answer brings it back
+        let failed_over = (0..RETRY_EVERY * 3)
+            .map(|_| rotation.route())
+            .filter(|route| route.turn_of == 1)
+            .take_while(|route| route.target != 1)
+            .count();
+        assert_eq!(failed_over, RETRY_EVERY - 2);
+        assert_eq!(rotation.record(1, true), Some(false));
+        assert!(!rotation.health[1].is_down());
+
+        // With every reflector down, each keeps its turns
+        let mut rotation = Rotation::new(2);
+        for target in [0, 0, 0, 1, 1, 1] {
+            rotation.record(target, false);
+        }
+        assert_eq!(rotation.route().target, 0);
+        assert_eq!(rotation.route().target, 1);
+    }
+
+    #[test]
+    fn test_pool_takes_turns_and_survives_a_refusal() -> Result<()> {
+        let up = UdpSocket::bind("127.0.0.1:0")?;
+        // Bound then dropped: probes to it are refused
+        let gone = UdpSocket::bind("127.0.0.1:0")?.local_addr()?;
+        let hellos = challenge_hellos(&up, 1, 5);
+        let mut pool = ReflectorPool::connect(&[up.local_addr()?.to_string(), gone.to_string()])?;
+        hellos.join().unwrap();
+        pool.set_timeout(Duration::from_millis(200))?;
+        assert_eq!(pool.reflector_addrs()[1], gone);
+
+        let mut buf = [0u8; 64];
+        for sequence in 0..4 {
+            pool.send_packet(&Packet::new(SequenceNumber(sequence)))?;
+            if sequence % 2 == 0 {
+                let (len, client) = up.recv_from(&mut buf)?;
+                up.send_to(&buf[..len], client)?;
+                assert_eq!(pool.recv_packet()?.sequence, SequenceNumber(sequence));
+            } else {
+                let lost = pool.recv_packet();
+                assert!(
+                    matches!(&lost, Err(ClientError::Io(e)) if e.kind() == ErrorKind::WouldBlock),
+                    "{:?}",
+                    lost
+                );
+            }
+        }
+        assert_eq!(pool.routes().get(3)?.map(|route| route.target), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_score() {
+        let reflector = |lost, p50_ns, p99_ns| ReflectorStats {
+            reflector: "10.0.0.1:9000".parse().unwrap(),
+            stats: ConnectionStats {
+                index: 0,
+                local: None,
+                sent: 100,
+                lost,
+                min_ns: p50_ns,
+                mean_ns: p50_ns as f64,
+                p50_ns,
+                p99_ns,
+            },
+            outages: 0,
+            failed_over: 0,
+        };
+        // A busy site up, a quiet one down
+        let pool = [
+            reflector(0, 20_000, 40_000),
+            reflector(0, 60_000, 80_000),
+            reflector(100, 0, 0),
+        ];
+        let score = weighted_score(&pool, &[3.0, 1.0, 1.0]).unwrap();
+        assert!((score.availability_pct - 80.0).abs() < 1e-9);
+        assert_eq!(score.p50_ns, Some(30_000.0));
+        assert_eq!(score.p99_ns, Some(50_000.0));
+
+        let down = weighted_score(&pool[2..], &[1.0]).unwrap();
+        assert_eq!((down.availability_pct, down.p99_ns), (0.0, None));
+        assert_eq!(weighted_score(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_reflector_stats() -> Result<()> {
+        // Reflector 1 lost probes 1, 3 and 5, and probes 7 and 9 failed over to 0
+        let routes = Routes::default();
+        for sequence in 0..10 {
+            let turn_of = (sequence % 2) as usize;
+            let target = if sequence >= 7 { 0 } else { turn_of };
+            routes.insert(sequence, Route { target, turn_of })?;
+        }
+        let sequences = vec![0, 2, 4, 6, 7, 8, 9];
+        let result = MeasurementResult {
+            latencies: vec![40_000; 7],
+            send_durations: vec![1_000; 10],
+            recv_wait_durations: vec![39_000; 7],
+            lost_packets: 3,
+            total_packets: 10,
+            elapsed: Duration::from_millis(1),
+            seed: 7,
+            system_timeline: None,
+            send_offsets: sequences.iter().map(|sequence| sequence * 100).collect(),
+            sequences,
+            idle_gaps: vec![0; 7],
+            started_at: SystemTime::now(),
+            warmup: None,
+            lost_sequences: vec![1, 3, 5],
+            thread_usage: None,
+            sample_backing: None,
+            path_timings: Vec::new(),
+            clock_offset: None,
+            kernel_timings: Vec::new(),
+            pacing: None,
+            reply_dscp: Default::default(),
+        };
+        let reflectors: Vec<SocketAddr> = vec![
+            "10.0.0.1:9000".parse().unwrap(),
+            "10.0.1.1:9000".parse().unwrap(),
+        ];
+        let stats = reflector_stats(&result, &routes, &reflectors, &[])?;
+        assert_eq!((stats[0].stats.sent, stats[0].stats.lost), (7, 0));
+        assert_eq!((stats[0].outages, stats[0].failed_over), (0, 0));
+        assert_eq!(stats[0].availability_pct(), 100.0);
+        assert_eq!((stats[1].stats.sent, stats[1].stats.lost), (3, 3));
+        assert_eq!((stats[1].outages, stats[1].failed_over), (1, 2));
+        assert_eq!(stats[1].availability_pct(), 0.0);
+        assert_eq!(stats[1].reflector, reflectors[1]);
+        Ok(())
+    }
+}
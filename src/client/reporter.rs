@@ -1,17 +1,97 @@
-use crate::client::constants::PASS_THRESHOLD_MS;
+use crate::client::analysis::format_p_value;
+use crate::client::baseline::BaselineOutcome;
+use crate::client::buckets::{BucketEdges, Buckets};
+use crate::client::bundle::{BundleManifest, MANIFEST_FILE};
+use crate::client::compare::{BucketChange, Comparison};
+use crate::client::confidence::{CiTarget, ConfidenceInterval, MIN_CI_SAMPLES};
+use crate::client::connections::{ConnectionStats, P99Dispersion, P99_SPREAD_RATIO};
+use crate::client::constants::{DEFAULT_PERCENTILES, SEND_STALL_THRESHOLD_NS};
+use crate::client::decomposition::Decomposition;
+use crate::client::drilldown::SlowProbe;
+use crate::client::dscp::Dscp;
+use crate::client::endpoints::{self, EndpointResult};
+use crate::client::energy::EnergyReport;
+use crate::client::environment::{Caveat, CpuThrottling};
 use crate::client::error::Result;
+use crate::client::families::{self, FamilyResult};
+use crate::client::hugepage::PageBacking;
+use crate::client::impair::SweepResult;
+use crate::client::irq::{format_cpu_list, AffinityWarning, IrqAffinity};
+use crate::client::matrix::{self, MatrixRow};
+use crate::client::measurement::{KernelTiming, PathTiming};
+use crate::client::numa::NumaPlacement;
+use crate::client::output::RunSummary;
+use crate::client::pacing::{IdleSplit, PacingStats, COLD_PENALTY_RATIO, MIN_SPLIT_SAMPLES};
+use crate::client::pcap::{CaptureStats, WireTiming};
+use crate::client::periodicity::{LossPeriod, SpikePeriod};
+use crate::client::pipeline::DepthResult;
+use crate::client::plain::plain_text;
+use crate::client::port_sweep::{self, Deviation, PortResult};
+use crate::client::reflectors::{self, ReflectorStats};
+use crate::client::retry::RetryOutcome;
+use crate::client::rusage::{ThreadUsage, CPU_BOUND_THRESHOLD, PREEMPTION_PER_PACKET_THRESHOLD};
+use crate::client::sla::{Condition, ConditionOutcome};
+use crate::client::slo::SloOutcome;
 use crate::client::statistics::Statistics;
+use crate::client::sysmon::SystemTimeline;
+use crate::client::theme::Theme;
+use crate::client::trend::TrendBucket;
+use crate::client::units::NumberFormat;
+use crate::client::verdict::Verdict;
+use crate::protocol::{ClockOffset, Transport};
 use colored::*;
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
 /// Reporter for printing measurement results
-pub struct Reporter;
+#[derive(Debug, Clone)]
+pub struct Reporter {
+    numbers: NumberFormat,
+    theme: Theme,
+    plain: bool,
+    /// RTT percentiles listed in the summary, in percent
+    percentiles: Vec<f64>,
+    /// Criterion of the pass/fail verdict
+    verdict: Verdict,
+    /// Edges of the latency distribution; the LAN ranges unless set
+    buckets: Option<BucketEdges>,
+}
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Self::new(NumberFormat::default())
+    }
+}
+
+/// `println!` through the reporter, which prints the line in ASCII with `--plain-verdict`
+macro_rules! outln {
+    ($reporter:expr) => {
+        $reporter.print_line("")
+    };
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.print_line(&format!($($arg)*))
+    };
+}
+
+/// One target of a sweep, a port or an address, as the sweep table prints it
+struct SweepTarget<'a> {
+    /// Port or address measured
+    label: String,
+    /// Summary of the run, or the error that stopped it
+    outcome: &'a std::result::Result<RunSummary, String>,
+    /// Ways the target stands out from the best of the sweep
+    deviations: Vec<Deviation>,
+}
 
 // Constants for histogram visualization
 const HISTOGRAM_BAR_WIDTH: usize = 30;
-const OUTLIER_THRESHOLD_US: f64 = 10_000.0;
-const EMPTY_BUCKET_SKIP_THRESHOLD: usize = 5;
+
+/// Width of each run's bars in the distribution of a comparison
+const COMPARISON_BAR_WIDTH: usize = 15;
+/// Rows of the latency-over-time chart
+const TREND_ROWS: usize = 8;
 
 // Percentage thresholds for color coding
 const HIGH_PERCENTAGE_THRESHOLD: f64 = 50.0;
@@ -21,10 +101,68 @@ const MEDIUM_PERCENTAGE_THRESHOLD: f64 = 10.0;
 const LOW_PERCENTAGE_THRESHOLD: f64 = 0.1;
 const MEDIUM_PRECISION_THRESHOLD: f64 = 1.0;
 
+// Heading of the round-trip latency distribution
+const LATENCY_DISTRIBUTION_TITLE: &str = "Latency Distribution (packet count by range):";
+
 // Width for histogram labels (must be consistent for alignment)
 const LABEL_WIDTH: usize = 12;
 
+// Example packets kept per distribution bucket
+const EXAMPLES_PER_BUCKET: usize = 3;
+
 impl Reporter {
+    /// Create a reporter that prints counts and sizes in `numbers`
+    pub fn new(numbers: NumberFormat) -> Self {
+        Self {
+            numbers,
+            theme: Theme::default(),
+            plain: false,
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+            verdict: Verdict::default(),
+            buckets: None,
+        }
+    }
+
+    /// Grade runs by `verdict` rather than the default mean below 1 ms
+    pub fn with_verdict(mut self, verdict: Verdict) -> Self {
+        self.verdict = verdict;
+        self
+    }
+
+    /// List these RTT percentiles (in percent, e.g. 99.99) in the summary, in this order
+    pub fn with_percentiles(mut self, percentiles: &[f64]) -> Self {
+        self.percentiles = percentiles.to_vec();
+        self
+    }
+
+    /// Count the latency distribution into the ranges of `edges` rather than the LAN
+    /// ranges
+    pub fn with_buckets(mut self, edges: Option<BucketEdges>) -> Self {
+        self.buckets = edges;
+        self
+    }
+
+    /// Color the report with `theme`
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Print the report in ASCII only, without symbols, box drawing or block bars
+    pub fn with_plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// Print one line of the report
+    fn print_line(&self, line: &str) {
+        if self.plain {
+            println!("{}", plain_text(line));
+        } else {
+            println!("{}", line);
+        }
+    }
+
     /// Renders a histogram bar character based on percentage relative to the maximum percentage.
     ///
     /// Uses Unicode block characters to visually represent relative sizes:
@@ -85,6 +223,18 @@ impl Reporter {
         }
     }
 
+    /// Formats the round width of a slice of a run ("1s", "5 ms", "200 µs")
+    fn format_slice_width(width: Duration) -> String {
+        let ns = width.as_nanos();
+        if ns >= 1_000_000_000 {
+            format!("{}s", ns / 1_000_000_000)
+        } else if ns >= 1_000_000 {
+            format!("{} ms", ns / 1_000_000)
+        } else {
+            format!("{} µs", ns / 1_000)
+        }
+    }
+
     /// Formats a percentage value with appropriate precision based on magnitude.
     ///
     /// Smaller percentages get more decimal places to show meaningful differences:
@@ -109,13 +259,9 @@ impl Reporter {
         }
     }
 
-    /// Formats a count value for display, using "k" suffix for thousands.
-    fn format_count(count: usize) -> String {
-        if count >= 1000 {
-            format!("{:>6}k", count / 1000)
-        } else {
-            format!("{:>7}", count)
-        }
+    /// Formats a count value for display, right-aligned in 7 characters.
+    fn format_count(&self, count: usize) -> String {
+        format!("{:>7}", self.numbers.count(count as u64))
     }
 
     /// Returns a color-coded label based on the percentage value, padded to a fixed width.
@@ -136,13 +282,13 @@ impl Reporter {
     /// # Returns
     ///
     /// A colorized string padded to the specified width
-    fn colorize_label(label: &str, percentage: f64) -> String {
+    fn colorize_label(&self, label: &str, percentage: f64) -> String {
         // Pad the label first to ensure consistent width, then apply colors
         let padded_label = format!("{:>width$}", label, width = LABEL_WIDTH);
         if percentage > HIGH_PERCENTAGE_THRESHOLD {
-            padded_label.green().to_string()
+            self.theme.good(&padded_label).to_string()
         } else if percentage > MEDIUM_PERCENTAGE_THRESHOLD {
-            padded_label.cyan().to_string()
+            self.theme.accent(&padded_label).to_string()
         } else {
             padded_label.to_string()
         }
@@ -164,252 +310,3778 @@ impl Reporter {
             "Printing measurement results"
         );
         if stats.count() == 0 {
-            warn!("No successful measurements recorded");
-            println!("{}\n", "No successful measurements recorded.".red());
-            println!("{}", "✗ FAIL: No data to analyze".red().bold());
+            self.print_no_data();
+            return Ok(());
+        }
+
+        self.print_summary(stats, lost_packets, total_packets, elapsed);
+
+        // Bucket distribution (pass latencies for accurate counting)
+        self.print_bucket_distribution(latencies, total_packets)?;
+        outln!(self);
+
+        self.print_verdict(stats);
+        Ok(())
+    }
+
+    /// Print the complete results summary when only the latency histogram is available
+    ///
+    /// Used for reloaded run records, where the raw latencies are no longer kept. Bucket
+    /// counts are derived from the histogram and are accurate to its precision.
+    pub fn print_results_from_histogram(
+        &self,
+        stats: &Statistics,
+        lost_packets: usize,
+        total_packets: usize,
+        elapsed: Duration,
+    ) -> Result<()> {
+        debug!(
+            packets_received = stats.count(),
+            packets_lost = lost_packets,
+            total_packets = total_packets,
+            "Printing measurement results from histogram"
+        );
+        if stats.count() == 0 {
+            self.print_no_data();
             return Ok(());
         }
 
-        let mean = stats.mean();
-        let mean_us = mean / 1000.0;
-        let mean_ms = mean / 1_000_000.0;
+        self.print_summary(stats, lost_packets, total_packets, elapsed);
+
+        self.print_histogram_distribution(stats, total_packets)?;
+        outln!(self);
+
+        self.print_verdict(stats);
+        Ok(())
+    }
+
+    /// Print the failure message shown when no measurement succeeded
+    fn print_no_data(&self) {
+        warn!("No successful measurements recorded");
+        outln!(
+            self,
+            "{}\n",
+            self.theme.bad("No successful measurements recorded.")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.bad("✗ FAIL: No data to analyze").bold()
+        );
+    }
+
+    /// Print the header, packet counters and latency statistics
+    fn print_summary(
+        &self,
+        stats: &Statistics,
+        lost_packets: usize,
+        total_packets: usize,
+        elapsed: Duration,
+    ) {
+        let mean_us = stats.mean() / 1000.0;
         let loss_pct = (lost_packets as f64 / total_packets as f64) * 100.0;
 
         // Print minimalistic summary
-        println!("\n{}", "┌─────────────────────────────┐".cyan());
-        println!("{}", "│  Synapse Results            │".cyan());
-        println!("{}", "└─────────────────────────────┘".cyan());
-        println!();
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse Results            │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
 
         // Key metrics with explanatory labels
         let elapsed_secs = elapsed.as_secs_f64();
         let throughput = total_packets as f64 / elapsed_secs;
 
-        println!(
+        outln!(
+            self,
             "Packets:  {} sent, {} lost ({:.2}%)",
-            total_packets, lost_packets, loss_pct
+            self.numbers.count(total_packets as u64),
+            self.numbers.count(lost_packets as u64),
+            loss_pct
         );
-        println!("          └─ Packet loss should be 0% for reliable measurements");
-        println!();
-        println!("Duration: {:.2}s", elapsed_secs);
-        println!(
-            "          └─ Test completed at {:.1}k packets/second",
-            throughput / 1000.0
+        outln!(
+            self,
+            "          └─ Packet loss should be 0% for reliable measurements"
         );
-        println!();
+        outln!(self);
+        outln!(self, "Duration: {:.2}s", elapsed_secs);
+        outln!(
+            self,
+            "          └─ Test completed at {} packets/second",
+            self.numbers.count(throughput.round() as u64)
+        );
+        outln!(self);
 
         // Statistics with explanatory labels
-        println!("Latency Statistics (round-trip time):");
-        println!("  Mean:      {:>8.1} µs  ← Average latency", mean_us);
-        println!(
+        outln!(self, "Latency Statistics (round-trip time):");
+        outln!(self, "  Mean:      {:>8.1} µs  ← Average latency", mean_us);
+        outln!(
+            self,
+            "  Std dev:   {:>8.1} µs  ← Typical distance from the mean",
+            stats.stddev() / 1000.0
+        );
+        outln!(
+            self,
+            "  Variance:  {:>8.1} µs² ← Square of the standard deviation",
+            stats.variance() / 1_000_000.0
+        );
+        outln!(
+            self,
+            "  CV:        {:>8.1} %   ← Std dev relative to the mean; how noisy the environment is",
+            stats.coefficient_of_variation() * 100.0
+        );
+        outln!(
+            self,
             "  Min:       {:>8.1} µs  ← Fastest packet",
             stats.min() as f64 / 1000.0
         );
-        println!(
+        outln!(
+            self,
             "  Max:       {:>8.1} µs  ← Slowest packet",
             stats.max() as f64 / 1000.0
         );
-        println!(
-            "  P50:       {:>8.1} µs  ← 50% of packets are faster than this (median)",
-            stats.percentile(0.5) as f64 / 1000.0
-        );
-        println!(
-            "  P90:       {:>8.1} µs  ← 90% of packets are faster than this",
-            stats.percentile(0.9) as f64 / 1000.0
-        );
-        println!(
-            "  P99:       {:>8.1} µs  ← 99% of packets are faster than this",
-            stats.percentile(0.99) as f64 / 1000.0
-        );
-        println!(
-            "  P99.9:     {:>8.1} µs  ← 99.9% of packets are faster than this",
-            stats.percentile(0.999) as f64 / 1000.0
-        );
+        for &percentile in &self.percentiles {
+            let median = if percentile == 50.0 { " (median)" } else { "" };
+            outln!(
+                self,
+                "  {:<11}{:>8.1} µs  ← {}% of packets are faster than this{}",
+                format!("P{}:", percentile),
+                stats.percentile(percentile / 100.0) as f64 / 1000.0,
+                percentile,
+                median
+            );
+        }
+
+        if let Some(jitter) = stats.jitter() {
+            outln!(self);
+            outln!(self, "Jitter (change between consecutive round trips):");
+            outln!(
+                self,
+                "  Mean:      {:>8.1} µs  ← Average change from one packet to the next",
+                jitter.mean_ns / 1000.0
+            );
+            outln!(
+                self,
+                "  Max:       {:>8.1} µs  ← Largest change from one packet to the next",
+                jitter.max_ns as f64 / 1000.0
+            );
+            outln!(
+                self,
+                "  RFC 3550:  {:>8.1} µs  ← Smoothed jitter, as RTP receivers report it",
+                jitter.smoothed_ns / 1000.0
+            );
+        }
 
         // Warn if values were clamped
         if stats.clamped_count() > 0 {
-            println!();
-            println!(
+            outln!(self);
+            outln!(
+                self,
                 "  ⚠ Note: {} measurement(s) exceeded histogram bounds and were clamped",
                 stats.clamped_count()
             );
         }
-        println!();
+        outln!(self);
+    }
 
-        // Bucket distribution (pass latencies for accurate counting)
-        self.print_bucket_distribution(latencies, total_packets)?;
-        println!();
+    /// Print the pass/fail verdict
+    fn print_verdict(&self, stats: &Statistics) {
+        let metric = self.verdict.metric;
+        let value_ns = metric.of_stats(stats);
+        let value_ms = value_ns / 1_000_000.0;
+        let threshold_ms = self.verdict.threshold_ms();
+        let passed = self.verdict.passes(value_ns);
 
         // Pass/Fail verdict with color
-        let verdict = if mean_ms < PASS_THRESHOLD_MS {
-            format!(
-                "✓ PASS: Mean latency ({:.3}ms) is below {}ms threshold",
-                mean_ms, PASS_THRESHOLD_MS
-            )
-            .green()
-            .bold()
+        let verdict = if passed {
+            self.theme
+                .good(&format!(
+                    "✓ PASS: {} latency ({:.3}ms) is below {}ms threshold",
+                    metric, value_ms, threshold_ms
+                ))
+                .bold()
         } else {
-            format!(
-                "✗ FAIL: Mean latency ({:.3}ms) exceeds {}ms threshold",
-                mean_ms, PASS_THRESHOLD_MS
-            )
-            .red()
-            .bold()
+            self.theme
+                .bad(&format!(
+                    "✗ FAIL: {} latency ({:.3}ms) exceeds {}ms threshold",
+                    metric, value_ms, threshold_ms
+                ))
+                .bold()
         };
 
-        println!("{}", verdict);
+        outln!(self, "{}", verdict);
 
-        let passed = mean_ms < PASS_THRESHOLD_MS;
         info!(
-            mean_latency_ms = mean_ms,
+            metric = %metric,
+            latency_ms = value_ms,
+            threshold_ms = threshold_ms,
             passed = passed,
             "Results reported"
         );
+    }
+
+    /// Print the RTT split into its send-complete and receive-wait components
+    ///
+    /// Send-complete is the time until the send call returned (outbound side of the local
+    /// stack); receive-wait is the time from then until the reply arrived (wire, server and
+    /// inbound side).
+    pub fn print_rtt_breakdown(&self, send: &Statistics, recv_wait: &Statistics) -> Result<()> {
+        if send.count() == 0 || recv_wait.count() == 0 {
+            return Ok(());
+        }
+        let row = |label: &str, send_ns: f64, recv_wait_ns: f64| {
+            outln!(
+                self,
+                "  {:<10} {:>10.1} µs {:>12.1} µs",
+                label,
+                send_ns / 1000.0,
+                recv_wait_ns / 1000.0
+            );
+        };
+
+        outln!(self, "RTT Breakdown (where the round trip was spent):");
+        outln!(
+            self,
+            "  {:<10} {:>13} {:>15}",
+            "",
+            "Send-complete",
+            "Receive-wait"
+        );
+        row("Mean:", send.mean(), recv_wait.mean());
+        for (label, quantile) in [("P50:", 0.5), ("P99:", 0.99), ("P99.9:", 0.999)] {
+            row(
+                label,
+                send.percentile(quantile) as f64,
+                recv_wait.percentile(quantile) as f64,
+            );
+        }
+        row("Max:", send.max() as f64, recv_wait.max() as f64);
+
+        let total = send.mean() + recv_wait.mean();
+        if total > 0.0 {
+            let send_share = send.mean() / total * 100.0;
+            outln!(
+                self,
+                "  └─ {:.1}% of the mean RTT is spent sending, {:.1}% waiting for the reply",
+                send_share,
+                100.0 - send_share
+            );
+        }
+        outln!(self);
 
+        debug!(
+            send_mean_ns = send.mean(),
+            recv_wait_mean_ns = recv_wait.mean(),
+            "RTT breakdown reported"
+        );
         Ok(())
     }
 
-    /// Print bucket distribution of latencies
-    pub fn print_bucket_distribution(&self, latencies: &[u64], total_packets: usize) -> Result<()> {
-        println!("Latency Distribution (packet count by range):");
-        println!();
-
-        // Define buckets in microseconds
-        let buckets: Vec<(f64, f64, &str)> = vec![
-            (0.0, 20.0, "0-20 µs"),
-            (20.0, 40.0, "20-40 µs"),
-            (40.0, 60.0, "40-60 µs"),
-            (60.0, 80.0, "60-80 µs"),
-            (80.0, 100.0, "80-100 µs"),
-            (100.0, 200.0, "100-200 µs"),
-            (200.0, 500.0, "200-500 µs"),
-            (500.0, 1000.0, "500µs-1ms"),
-            (1000.0, 10000.0, "1-10 ms"),
+    /// Print the round trip split at the server into outbound, server and return time
+    ///
+    /// Built from the timestamps the server stamps into every echo. Outbound and return
+    /// depend on the client and server clocks agreeing: with `clock_offset` they were
+    /// corrected by the estimated offset, which is shown; without it, negative values
+    /// (clocks visibly offset) are called out.
+    pub fn print_path_breakdown(
+        &self,
+        timings: &[PathTiming],
+        clock_offset: Option<&ClockOffset>,
+    ) -> Result<()> {
+        if timings.is_empty() {
+            return Ok(());
+        }
+        let column = |component: fn(&PathTiming) -> i64| {
+            let mut values: Vec<i64> = timings.iter().map(component).collect();
+            values.sort_unstable();
+            values
+        };
+        let columns = [
+            column(|t| t.outbound_ns),
+            column(|t| t.server_ns as i64),
+            column(|t| t.return_ns),
         ];
 
-        // Count packets in each bucket
-        let mut bucket_counts = vec![0usize; buckets.len()];
-        let mut outliers = 0usize;
-        let mut max_latency = 0u64;
-
-        for &latency_ns in latencies {
-            let latency_us = latency_ns as f64 / 1000.0;
-            max_latency = max_latency.max(latency_ns);
-
-            let mut found = false;
-            for (i, &(min, max, _)) in buckets.iter().enumerate() {
-                if latency_us >= min && latency_us < max {
-                    bucket_counts[i] += 1;
-                    found = true;
-                    break;
-                }
-            }
+        outln!(self, "Path Breakdown (server timestamps):");
+        outln!(
+            self,
+            "  {:<6} {:>14} {:>14} {:>14}",
+            "",
+            "Outbound",
+            "Server",
+            "Return"
+        );
+        for (label, quantile) in [("P50:", 0.5), ("P99:", 0.99), ("Max:", 1.0)] {
+            let [outbound, server, ret] = columns.each_ref().map(|values| {
+                let index = ((values.len() - 1) as f64 * quantile).round() as usize;
+                values[index] as f64 / 1000.0
+            });
+            outln!(
+                self,
+                "  {:<6} {:>11.1} µs {:>11.1} µs {:>11.1} µs",
+                label,
+                outbound,
+                server,
+                ret
+            );
+        }
+        let offset = timings.iter().any(|t| t.outbound_ns < 0 || t.return_ns < 0);
+        if let Some(clock) = clock_offset {
+            outln!(
+                self,
+                "  └─ Corrected for a server clock offset of {:+.1} µs (± {:.1} µs, best of {} exchanges)",
+                clock.offset_ns as f64 / 1000.0,
+                clock.uncertainty_ns as f64 / 1000.0,
+                clock.samples
+            );
+        } else if offset {
+            outln!(
+                self,
+                "  └─ {}",
+                self.theme.warning("Client and server clocks are offset (negative one-way times); only the server \
+                 time and outbound + return together are reliable")
+            );
+        } else {
+            outln!(
+                self,
+                "  └─ Outbound and return assume synchronized client and server clocks"
+            );
+        }
+        outln!(self);
 
-            if !found && latency_us >= OUTLIER_THRESHOLD_US {
-                outliers += 1;
-            }
+        debug!(
+            samples = timings.len(),
+            clocks_offset = offset,
+            "Path breakdown reported"
+        );
+        Ok(())
+    }
+
+    /// Print the mean RTT split into the client app, client kernel, network, server
+    /// kernel and server app, as far as the run's timestamps tell them apart
+    pub fn print_latency_decomposition(&self, decomposition: &Decomposition) -> Result<()> {
+        outln!(
+            self,
+            "Latency Decomposition (mean RTT {:.1} µs):",
+            decomposition.rtt_ns / 1000.0
+        );
+        let shares: Vec<f64> = decomposition
+            .segments
+            .iter()
+            .map(|segment| segment.mean_ns / decomposition.rtt_ns.max(1.0) * 100.0)
+            .collect();
+        let max_share = shares.iter().copied().fold(0.0, f64::max);
+        let width = decomposition
+            .segments
+            .iter()
+            .map(|segment| segment.label().chars().count())
+            .max()
+            .unwrap_or_default();
+        for (segment, share) in decomposition.segments.iter().zip(shares) {
+            outln!(
+                self,
+                "  {:<width$} {:>9.1} µs {} {}",
+                segment.label(),
+                segment.mean_ns / 1000.0,
+                Self::format_percentage(share),
+                self.theme
+                    .accent(&Self::render_bar_from_percentage(share, max_share, 24)),
+                width = width
+            );
+        }
+        if decomposition.splits_client() {
+            outln!(
+                self,
+                "  └─ Client kernel is the send path; the receive path counts as client app"
+            );
+        } else {
+            outln!(
+                self,
+                "  └─ Add --kernel-timestamps to split the client app and kernel from the network"
+            );
         }
+        outln!(self);
 
-        // Calculate percentages first to find max percentage for bar scaling
-        let mut percentages = Vec::new();
-        for count in &bucket_counts {
-            let percentage = (*count as f64 / total_packets as f64) * 100.0;
-            percentages.push(percentage);
+        debug!(
+            segments = decomposition.segments.len(),
+            "Latency decomposition reported"
+        );
+        Ok(())
+    }
+
+    /// Print the time the server application spent on each echo, from reading the probe
+    /// until the write of the echo returned, next to the RTT
+    pub fn print_server_processing(
+        &self,
+        timings: &[PathTiming],
+        stats: &Statistics,
+    ) -> Result<()> {
+        let processing: Vec<u64> = timings
+            .iter()
+            .filter_map(|t| t.server_processing_ns)
+            .collect();
+        if processing.is_empty() {
+            return Ok(());
         }
-        let max_percentage = percentages.iter().fold(0.0f64, |a, &b| a.max(b));
+        let processing = Statistics::new(&processing)?;
+        outln!(
+            self,
+            "Server Processing (read to echo written, server clock):"
+        );
+        outln!(
+            self,
+            "  P50: {:.1} µs   P99: {:.1} µs   Max: {:.1} µs",
+            processing.percentile(0.5) as f64 / 1000.0,
+            processing.percentile(0.99) as f64 / 1000.0,
+            processing.max() as f64 / 1000.0
+        );
+        let share = processing.percentile(0.5) as f64 / stats.percentile(0.5).max(1) as f64;
+        outln!(
+            self,
+            "  └─ {:.1}% of the P50 RTT is spent in the server, its echo write included; the rest is the network and the client",
+            (share * 100.0).min(100.0)
+        );
+        outln!(self);
 
-        // Print each bucket
-        for (i, &(_, _, label)) in buckets.iter().enumerate() {
-            let count = bucket_counts[i];
-            if count == 0 && i > EMPTY_BUCKET_SKIP_THRESHOLD {
-                continue; // Skip empty buckets beyond 100µs for cleaner output
-            }
+        debug!(
+            samples = processing.count(),
+            p50_ns = processing.percentile(0.5),
+            "Server processing reported"
+        );
+        Ok(())
+    }
 
-            let percentage = percentages[i];
-            // Scale bars based on percentage, not count, to match displayed percentages
-            let bar =
-                Self::render_bar_from_percentage(percentage, max_percentage, HISTOGRAM_BAR_WIDTH);
-            let label_colored = Self::colorize_label(label, percentage);
-            let pct_str = Self::format_percentage(percentage);
+    /// Print the RTT split into the time spent in the client application and the
+    /// round trip between the kernel's send and receive timestamps
+    pub fn print_kernel_breakdown(&self, timings: &[KernelTiming]) -> Result<()> {
+        if timings.is_empty() {
+            return Ok(());
+        }
+        let application: Vec<u64> = timings.iter().map(KernelTiming::application_ns).collect();
+        let kernel: Vec<u64> = timings.iter().map(|t| t.kernel_rtt_ns).collect();
+        let application = Statistics::new(&application)?;
+        let kernel = Statistics::new(&kernel)?;
+        let row = |label: &str, application_ns: f64, kernel_ns: f64| {
+            outln!(
+                self,
+                "  {:<10} {:>10.1} µs {:>12.1} µs",
+                label,
+                application_ns / 1000.0,
+                kernel_ns / 1000.0
+            );
+        };
 
-            println!(
-                "  {}:  {:30} {} ({:7} packets)",
-                label_colored,
-                bar,
-                pct_str,
-                Self::format_count(count)
+        outln!(self, "Kernel Timestamps (application vs kernel):");
+        outln!(
+            self,
+            "  {:<10} {:>13} {:>15}",
+            "",
+            "Application",
+            "Kernel RTT"
+        );
+        row("Mean:", application.mean(), kernel.mean());
+        for (label, quantile) in [("P50:", 0.5), ("P99:", 0.99), ("P99.9:", 0.999)] {
+            row(
+                label,
+                application.percentile(quantile) as f64,
+                kernel.percentile(quantile) as f64,
             );
         }
+        row("Max:", application.max() as f64, kernel.max() as f64);
 
-        // Print outliers if any
-        if outliers > 0 {
-            let percentage = (outliers as f64 / total_packets as f64) * 100.0;
-            let max_ms = max_latency as f64 / 1_000_000.0;
-            let outlier_bar =
-                Self::render_bar_from_percentage(percentage, max_percentage, HISTOGRAM_BAR_WIDTH);
-            let pct_str = Self::format_percentage(percentage);
-
-            // Pad outlier label to match bucket label width
-            let outlier_label = format!("{:>width$}", ">10 ms", width = LABEL_WIDTH);
-            let outlier_label_colored = outlier_label.red().bold();
-
-            println!(
-                "  {}:  {:30} {} ({:7} packets) ← MAX: {:.1}ms",
-                outlier_label_colored,
-                outlier_bar,
-                pct_str,
-                Self::format_count(outliers),
-                max_ms
+        let total = application.mean() + kernel.mean();
+        if total > 0.0 {
+            outln!(
+                self,
+                "  └─ {:.1}% of the mean RTT is spent in the client application and its system calls",
+                application.mean() / total * 100.0
             );
         }
+        outln!(self);
 
+        debug!(
+            samples = timings.len(),
+            application_mean_ns = application.mean(),
+            kernel_mean_ns = kernel.mean(),
+            "Kernel timestamp breakdown reported"
+        );
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Print the combined results of a matrix run, one row per combination
+    pub fn print_matrix(&self, rows: &[MatrixRow]) -> Result<()> {
+        let label_width = rows
+            .iter()
+            .map(|row| matrix::label(&row.combination).chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("Run".len());
 
-    #[test]
-    fn test_reporter_print_results_empty() -> Result<()> {
-        let reporter = Reporter;
-        let stats = Statistics::new(&[])?;
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse Matrix Results     │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
+        outln!(
+            self,
+            "  {:<width$} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "Run",
+            "Loss",
+            "Mean µs",
+            "P50 µs",
+            "P99 µs",
+            "Max µs",
+            width = label_width
+        );
 
-        // Should handle empty latencies gracefully
-        reporter.print_results(&stats, 0, 10, Duration::from_secs(1), &[])?;
+        for row in rows {
+            let run = matrix::label(&row.combination);
+            match &row.outcome {
+                Ok(summary) => outln!(
+                    self,
+                    "  {:<width$} {:>7.2}% {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+                    run,
+                    summary.loss_pct(),
+                    summary.mean_ns / 1000.0,
+                    summary.p50_ns as f64 / 1000.0,
+                    summary.p99_ns as f64 / 1000.0,
+                    summary.max_ns as f64 / 1000.0,
+                    width = label_width
+                ),
+                Err(e) => outln!(
+                    self,
+                    "  {:<width$} {}",
+                    run,
+                    self.theme.bad(&format!("✗ {}", e)),
+                    width = label_width
+                ),
+            }
+        }
+        outln!(self);
         Ok(())
     }
 
-    #[test]
-    fn test_reporter_print_results_with_data() -> Result<()> {
-        let reporter = Reporter;
-        let latencies = vec![1000, 2000, 3000, 4000, 5000];
-        let stats = Statistics::new(&latencies)?;
+    /// Print the measured vs injected values of an impairment sweep
+    ///
+    /// The first result is the unimpaired baseline; the measured delay of every profile is
+    /// its median RTT minus the baseline median.
+    pub fn print_impair_sweep(
+        &self,
+        results: &[SweepResult],
+        passes: u32,
+        transport: Transport,
+    ) -> Result<()> {
+        let Some((baseline, profiles)) = results.split_first() else {
+            self.print_no_data();
+            return Ok(());
+        };
 
-        reporter.print_results(&stats, 0, 5, Duration::from_secs(1), &latencies)?;
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse Impairment Sweep   │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
+        outln!(
+            self,
+            "Baseline: P50 {:.1} µs, P99 {:.1} µs, loss {:.2}%",
+            baseline.p50_ns as f64 / 1000.0,
+            baseline.p99_ns as f64 / 1000.0,
+            baseline.loss_pct
+        );
+        outln!(
+            self,
+            "          └─ Each probe crosses the impaired interface {} time(s)",
+            passes
+        );
+        outln!(self);
+        outln!(
+            self,
+            "  {:<22} {:>12} {:>12} {:>10} {:>10}",
+            "Profile",
+            "Expected Δ",
+            "Measured Δ",
+            "Exp. loss",
+            "Loss"
+        );
+
+        for result in profiles {
+            let Some(profile) = result.profile else {
+                continue;
+            };
+            let expected_ms = profile.expected_delay(passes).as_secs_f64() * 1000.0;
+            let measured_ms = (result.p50_ns as f64 - baseline.p50_ns as f64) / 1_000_000.0;
+            outln!(
+                self,
+                "  {:<22} {:>9.2} ms {:>9.2} ms {:>9.2}% {:>9.2}%",
+                profile.to_string(),
+                expected_ms,
+                measured_ms,
+                profile.expected_loss_pct(passes),
+                result.loss_pct
+            );
+        }
+        outln!(self);
+        match transport {
+            Transport::Tcp => {
+                outln!(
+                    self,
+                    "  └─ TCP retransmits dropped segments, so injected loss mostly shows up as"
+                );
+                outln!(self, "     tail latency (P99) rather than as lost probes");
+            }
+            Transport::Udp => {
+                outln!(
+                    self,
+                    "  └─ UDP does not retransmit, so every dropped probe or reply is a lost probe"
+                );
+            }
+        }
         Ok(())
     }
 
-    #[test]
-    fn test_reporter_print_bucket_distribution() -> Result<()> {
-        let reporter = Reporter;
-        let latencies = vec![
-            10000,  // 10 µs
-            20000,  // 20 µs
-            50000,  // 50 µs
-            100000, // 100 µs
-            500000, // 500 µs
-        ];
+    /// Print the outcome of every connection of a concurrent run
+    ///
+    /// Points out the slowest connection when its P99 is [`P99_SPREAD_RATIO`] times the
+    /// fastest's or more.
+    pub fn print_connections(&self, connections: &[ConnectionStats]) -> Result<()> {
+        self.print_streams(
+            connections,
+            &format!("Connections: {} concurrent", connections.len()),
+            ("Conn", "connection"),
+            "The connections differ only by source port: suspect an ECMP path or a per-flow queue",
+        )
+    }
 
-        reporter.print_bucket_distribution(&latencies, 5)?;
-        Ok(())
+    /// Print the outcome of every flow of a multi-flow run (see
+    /// [`crate::client::flows`])
+    ///
+    /// Points out the slowest flow when its P99 is [`P99_SPREAD_RATIO`] times the
+    /// fastest's or more.
+    pub fn print_flows(&self, flows: &[ConnectionStats]) -> Result<()> {
+        self.print_streams(
+            flows,
+            &format!("Flows: {} source ports", flows.len()),
+            ("Flow", "flow"),
+            "The flows differ only by source port: ECMP likely hashes them onto paths of their own",
+        )
     }
 
-    #[test]
-    fn test_reporter_format_count() {
-        assert_eq!(Reporter::format_count(100), "    100");
-        assert_eq!(Reporter::format_count(1000), "     1k");
-        assert_eq!(Reporter::format_count(5000), "     5k");
+    /// Table of connections or flows and the dispersion of their P99
+    ///
+    /// Rows whose P99 is [`crate::client::connections::P99_MEDIAN_RATIO`] times the median P99
+    /// or more are flagged, so one bad path stands out however many good ones the merged
+    /// results average it with.
+    /// `column` and `noun` are the column header and the noun naming one of them in the summary.
+    fn print_streams(
+        &self,
+        streams: &[ConnectionStats],
+        title: &str,
+        (column, noun): (&str, &str),
+        hint: &str,
+    ) -> Result<()> {
+        let by_p99 = |a: &&ConnectionStats, b: &&ConnectionStats| a.p99_ns.cmp(&b.p99_ns);
+        let (Some(fastest), Some(slowest), Some(dispersion)) = (
+            streams.iter().min_by(by_p99),
+            streams.iter().max_by(by_p99),
+            P99Dispersion::of(streams),
+        ) else {
+            return Ok(());
+        };
+        outln!(self, "\n{}", title.bold());
+        outln!(
+            self,
+            "  {:>4}  {:<22} {:>10} {:>8} {:>11} {:>11} {:>11} {:>11}",
+            column,
+            "Local",
+            "Sent",
+            "Lost",
+            "Min",
+            "Mean",
+            "P50",
+            "P99"
+        );
+        for stream in streams {
+            let local = stream
+                .local
+                .map_or_else(|| "-".to_string(), |local| local.to_string());
+            let flag = if dispersion.is_outlier(stream) {
+                format!(
+                    "  {}",
+                    self.theme.warning(&format!(
+                        "⚠ {:.1}x median",
+                        stream.p99_ns as f64 / dispersion.median_ns.max(1) as f64
+                    ))
+                )
+            } else {
+                String::new()
+            };
+            outln!(
+                self,
+                "  {:>4}  {:<22} {:>10} {:>8} {:>8.1} µs {:>8.1} µs {:>8.1} µs {:>8.1} µs{}",
+                stream.index,
+                local,
+                self.numbers.count(stream.sent as u64),
+                self.numbers.count(stream.lost as u64),
+                stream.min_ns as f64 / 1000.0,
+                stream.mean_ns / 1000.0,
+                stream.p50_ns as f64 / 1000.0,
+                stream.p99_ns as f64 / 1000.0,
+                flag
+            );
+        }
+
+        let spread = slowest.p99_ns as f64 / fastest.p99_ns.max(1) as f64;
+        outln!(
+            self,
+            "  └─ P99 ranges from {:.1} µs ({} {}) to {:.1} µs ({} {})",
+            fastest.p99_ns as f64 / 1000.0,
+            noun,
+            fastest.index,
+            slowest.p99_ns as f64 / 1000.0,
+            noun,
+            slowest.index
+        );
+        outln!(
+            self,
+            "  └─ P99 dispersion: median {:.1} µs, coefficient of variation {:.0}%",
+            dispersion.median_ns as f64 / 1000.0,
+            dispersion.cv_pct
+        );
+        if spread >= P99_SPREAD_RATIO {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!(
+                        "⚠ {}{} {} has {:.1}x the P99 of {} {}",
+                        noun[..1].to_uppercase(),
+                        &noun[1..],
+                        slowest.index,
+                        spread,
+                        noun,
+                        fastest.index
+                    ))
+                    .bold()
+            );
+            outln!(self, "  └─ {}", hint);
+        }
+        outln!(self);
+
+        debug!(
+            streams = streams.len(),
+            kind = noun,
+            p99_spread = spread,
+            p99_cv_pct = dispersion.cv_pct,
+            outliers = streams.iter().filter(|s| dispersion.is_outlier(s)).count(),
+            "Stream breakdown reported"
+        );
+        Ok(())
+    }
+
+    /// Print the availability and latency of every reflector of a pool (see
+    /// [`crate::client::reflectors`])
+    ///
+    /// Points out every reflector that went down during the run, with the turns it
+    /// failed over to the others, and sums the pool up in its score weighted by
+    /// `weights`, one per reflector.
+    pub fn print_reflectors(&self, reflectors: &[ReflectorStats], weights: &[f64]) -> Result<()> {
+        if reflectors.is_empty() {
+            return Ok(());
+        }
+        outln!(
+            self,
+            "\n{}",
+            format!("Reflectors: {} taking turns", reflectors.len()).bold()
+        );
+        outln!(
+            self,
+            "  {:>4}  {:<22} {:>6} {:>10} {:>8} {:>7} {:>7} {:>11} {:>11} {:>11}",
+            "#",
+            "Reflector",
+            "Weight",
+            "Sent",
+            "Lost",
+            "Avail",
+            "Outages",
+            "Min",
+            "P50",
+            "P99"
+        );
+        for (reflector, weight) in reflectors.iter().zip(weights) {
+            let stats = &reflector.stats;
+            let answered = stats.sent > stats.lost;
+            let latency = |ns: u64| {
+                if answered {
+                    format!("{:>8.1} µs", ns as f64 / 1000.0)
+                } else {
+                    format!("{:>11}", "-")
+                }
+            };
+            let availability = format!("{:>6.1}%", reflector.availability_pct());
+            let availability = if stats.lost == 0 {
+                self.theme.good(&availability)
+            } else if reflector.outages == 0 {
+                self.theme.warning(&availability)
+            } else {
+                self.theme.bad(&availability)
+            };
+            outln!(
+                self,
+                "  {:>4}  {:<22} {:>6} {:>10} {:>8} {} {:>7} {} {} {}",
+                stats.index,
+                reflector.reflector.to_string(),
+                weight,
+                self.numbers.count(stats.sent as u64),
+                self.numbers.count(stats.lost as u64),
+                availability,
+                reflector.outages,
+                latency(stats.min_ns),
+                latency(stats.p50_ns),
+                latency(stats.p99_ns)
+            );
+        }
+
+        let down: Vec<&ReflectorStats> = reflectors
+            .iter()
+            .filter(|reflector| reflector.outages > 0)
+            .collect();
+        for reflector in &down {
+            outln!(
+                self,
+                "  {}",
+                self.theme.warning(&format!(
+                    "⚠ Reflector {} ({}) went down {} time{}; {} of its turns failed over",
+                    reflector.stats.index,
+                    reflector.reflector,
+                    reflector.outages,
+                    if reflector.outages == 1 { "" } else { "s" },
+                    self.numbers.count(reflector.failed_over as u64)
+                ))
+            );
+        }
+        if down.is_empty() {
+            outln!(self, "  └─ No reflector went down");
+        }
+        if let Some(score) = reflectors::weighted_score(reflectors, weights) {
+            let latency = |ns: Option<f64>| {
+                ns.map_or_else(|| "-".to_string(), |ns| format!("{:.1} µs", ns / 1000.0))
+            };
+            outln!(
+                self,
+                "  {} {:.2}% available, P50 {}, P99 {}",
+                "Weighted score:".bold(),
+                score.availability_pct,
+                latency(score.p50_ns),
+                latency(score.p99_ns)
+            );
+        }
+
+        debug!(
+            reflectors = reflectors.len(),
+            down = down.len(),
+            failed_over = reflectors.iter().map(|r| r.failed_over).sum::<usize>(),
+            "Reflector breakdown reported"
+        );
+        Ok(())
+    }
+
+    /// Print the RTT and throughput at every depth of a pipelining sweep
+    ///
+    /// The bars scale P99 to the largest of the sweep; the slope of P50 over the depths is
+    /// the RTT each additional probe in flight costs, about one server service time for
+    /// a server that answers one request at a time.
+    pub fn print_depth_sweep(&self, results: &[DepthResult]) -> Result<()> {
+        let (Some(first), Some(last)) = (results.first(), results.last()) else {
+            self.print_no_data();
+            return Ok(());
+        };
+        let max_p99 = results.iter().map(|r| r.p99_ns).max().unwrap_or(0) as f64;
+
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse Depth Sweep        │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
+        outln!(
+            self,
+            "  {:>6} {:>12} {:>12} {:>14}  P99",
+            "Depth",
+            "P50",
+            "P99",
+            "Throughput"
+        );
+        for result in results {
+            let bar = Self::render_bar_from_percentage(
+                result.p99_ns as f64,
+                max_p99,
+                HISTOGRAM_BAR_WIDTH,
+            );
+            outln!(
+                self,
+                "  {:>6} {:>9.1} µs {:>9.1} µs {:>10} pps  {}",
+                result.depth,
+                result.p50_ns as f64 / 1000.0,
+                result.p99_ns as f64 / 1000.0,
+                self.numbers.count(result.throughput_pps.round() as u64),
+                self.theme.accent(&bar)
+            );
+        }
+        outln!(self);
+
+        if last.depth > first.depth {
+            let per_probe_ns =
+                (last.p50_ns as f64 - first.p50_ns as f64) / (last.depth - first.depth) as f64;
+            let speedup = last.throughput_pps / first.throughput_pps.max(f64::MIN_POSITIVE);
+            outln!(
+                self,
+                "  └─ Each additional probe in flight adds {:.1} µs at P50; depth {} moves {:.1}x the probes of depth {}",
+                per_probe_ns / 1000.0,
+                last.depth,
+                speedup,
+                first.depth
+            );
+            outln!(
+                self,
+                "     A server answering one request at a time adds about its service time per probe"
+            );
+        }
+
+        debug!(
+            depths = results.len(),
+            max_depth = last.depth,
+            "Depth sweep reported"
+        );
+        Ok(())
+    }
+
+    /// Print the measurement of every port of a port sweep side by side
+    ///
+    /// The bars scale P99 to the largest of the sweep. Ports whose P99 or loss stands out
+    /// from the best of the sweep are listed below the table, as the same host answering
+    /// slower on some ports points to a policy applied by port along the path.
+    pub fn print_port_sweep(&self, results: &[PortResult]) -> Result<()> {
+        let targets: Vec<SweepTarget> = results
+            .iter()
+            .map(|result| SweepTarget {
+                label: result.port.to_string(),
+                outcome: &result.outcome,
+                deviations: port_sweep::deviations(result, results),
+            })
+            .collect();
+        let (outliers, failed) = self.print_sweep(
+            "Synapse Port Sweep",
+            ("Port", "port"),
+            &targets,
+            "The ports share host and path: suspect a firewall, QoS or rate-limit policy matching on the port",
+            "All ports answered alike",
+        );
+        debug!(
+            ports = results.len(),
+            outliers = outliers,
+            failed = failed,
+            "Port sweep reported"
+        );
+        Ok(())
+    }
+
+    /// Print the measurement of every address of a DNS name side by side
+    ///
+    /// The bars scale P99 to the largest of the addresses. Addresses whose P99 or loss
+    /// stands out from the best are listed below the table: behind a round-robin name,
+    /// that is typically one backend slower or more loaded than the others.
+    pub fn print_endpoints(&self, results: &[EndpointResult]) -> Result<()> {
+        let targets: Vec<SweepTarget> = results
+            .iter()
+            .map(|result| SweepTarget {
+                label: result.address.to_string(),
+                outcome: &result.outcome,
+                deviations: endpoints::deviations(result, results),
+            })
+            .collect();
+        let (outliers, failed) = self.print_sweep(
+            "Synapse DNS Endpoints",
+            ("Address", "address"),
+            &targets,
+            "The name fronts several servers: suspect a slower backend or a longer path to it",
+            "All addresses answered alike",
+        );
+        debug!(
+            addresses = results.len(),
+            outliers = outliers,
+            failed = failed,
+            "Endpoints reported"
+        );
+        Ok(())
+    }
+
+    /// Table of the runs of a sweep, one per target, and the targets that stand out
+    ///
+    /// `column` and `noun` are the column header and the noun naming one target in the
+    /// summary; `hint` follows the targets that stand out or failed and `alike` is printed
+    /// when there are none. Returns the number of targets that stand out and that failed.
+    fn print_sweep(
+        &self,
+        title: &str,
+        (column, noun): (&str, &str),
+        targets: &[SweepTarget],
+        hint: &str,
+        alike: &str,
+    ) -> (usize, usize) {
+        if targets.is_empty() {
+            self.print_no_data();
+            return (0, 0);
+        }
+        let max_p99 = targets
+            .iter()
+            .filter_map(|t| t.outcome.as_ref().ok())
+            .map(|s| s.p99_ns)
+            .max()
+            .unwrap_or(0) as f64;
+        let width = targets
+            .iter()
+            .map(|t| t.label.len())
+            .chain([column.len(), 6])
+            .max()
+            .unwrap_or(6);
+
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(self, "{}", self.theme.accent(&format!("│  {:<27}│", title)));
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
+        outln!(
+            self,
+            "  {:>width$} {:>8} {:>12} {:>12} {:>12}  P99",
+            column,
+            "Loss",
+            "Mean",
+            "P50",
+            "P99",
+            width = width
+        );
+        for target in targets {
+            match target.outcome {
+                Ok(summary) => {
+                    let bar = Self::render_bar_from_percentage(
+                        summary.p99_ns as f64,
+                        max_p99,
+                        HISTOGRAM_BAR_WIDTH,
+                    );
+                    outln!(
+                        self,
+                        "  {:>width$} {:>7.2}% {:>9.1} µs {:>9.1} µs {:>9.1} µs  {}",
+                        target.label,
+                        summary.loss_pct(),
+                        summary.mean_ns / 1000.0,
+                        summary.p50_ns as f64 / 1000.0,
+                        summary.p99_ns as f64 / 1000.0,
+                        self.theme.accent(&bar),
+                        width = width
+                    );
+                }
+                Err(e) => outln!(
+                    self,
+                    "  {:>width$} {}",
+                    target.label,
+                    self.theme.bad(&format!("✗ {}", e)),
+                    width = width
+                ),
+            }
+        }
+        outln!(self);
+
+        let mut outliers = 0;
+        for target in targets {
+            if target.deviations.is_empty() {
+                continue;
+            }
+            outliers += 1;
+            let reasons: Vec<String> = target
+                .deviations
+                .iter()
+                .map(|deviation| match deviation {
+                    Deviation::Latency(ratio) => format!("{:.1}x the lowest P99", ratio),
+                    Deviation::Loss(excess) => {
+                        format!("{:.2} points more loss than the best {}", excess, noun)
+                    }
+                })
+                .collect();
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!(
+                        "⚠ {}{} {}: {}",
+                        noun[..1].to_uppercase(),
+                        &noun[1..],
+                        target.label,
+                        reasons.join(", ")
+                    ))
+                    .bold()
+            );
+        }
+        let failed = targets.iter().filter(|t| t.outcome.is_err()).count();
+        if outliers > 0 || failed > 0 {
+            outln!(self, "  └─ {}", hint);
+        } else {
+            outln!(self, "  {}", self.theme.good(&format!("✓ {}", alike)));
+        }
+        outln!(self);
+        (outliers, failed)
+    }
+
+    /// Print the measurements of the IPv4 and IPv6 addresses of a host side by side
+    ///
+    /// A family whose P99 or loss stands out from the other's is pointed out below the
+    /// table: the host is the same, so the difference lies on the path of that family.
+    pub fn print_family_comparison(&self, results: &[FamilyResult]) -> Result<()> {
+        if results.is_empty() {
+            self.print_no_data();
+            return Ok(());
+        }
+
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse IPv4 vs IPv6       │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self);
+
+        let row = |label: &str, cell: &dyn Fn(&FamilyResult) -> String| {
+            let cells: String = results
+                .iter()
+                .map(|result| format!(" {:>24}", cell(result)))
+                .collect();
+            outln!(self, "  {:<8}{}", label, cells);
+        };
+        // Failed families have no figures
+        let summary_row = |label: &str, value: &dyn Fn(&RunSummary) -> String| {
+            row(label, &|result| {
+                result
+                    .outcome
+                    .as_ref()
+                    .map_or_else(|_| "-".to_string(), value)
+            })
+        };
+        let micros = |ns: f64| format!("{:.1} µs", ns / 1000.0);
+        row("", &|result| result.family.to_string());
+        row("Address", &|result| {
+            result.address.map_or_else(
+                || format!("no {} record", result.family.record()),
+                |address| address.to_string(),
+            )
+        });
+        summary_row("Loss", &|s| format!("{:.2}%", s.loss_pct()));
+        summary_row("Mean", &|s| micros(s.mean_ns));
+        summary_row("P50", &|s| micros(s.p50_ns as f64));
+        summary_row("P99", &|s| micros(s.p99_ns as f64));
+        summary_row("Max", &|s| micros(s.max_ns as f64));
+        outln!(self);
+
+        let mut outliers = 0;
+        for result in results {
+            if let Err(e) = &result.outcome {
+                outln!(
+                    self,
+                    "  {}",
+                    self.theme.bad(&format!("✗ {}: {}", result.family, e))
+                );
+                continue;
+            }
+            let deviations = families::deviations(result, results);
+            if deviations.is_empty() {
+                continue;
+            }
+            outliers += 1;
+            let reasons: Vec<String> = deviations
+                .iter()
+                .map(|deviation| match deviation {
+                    Deviation::Latency(ratio) => format!("{:.1}x the P99 of the other", ratio),
+                    Deviation::Loss(excess) => {
+                        format!("{:.2} points more loss than the other", excess)
+                    }
+                })
+                .collect();
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!("⚠ {}: {}", result.family, reasons.join(", ")))
+                    .bold()
+            );
+        }
+        let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+        if outliers > 0 {
+            outln!(
+                self,
+                "  └─ Same host, another path: suspect a tunnel, a different route or a NAT64 on the slower family"
+            );
+        } else if failed == 0 {
+            outln!(
+                self,
+                "  {}",
+                self.theme.good("✓ Both families answered alike")
+            );
+        }
+        outln!(self);
+
+        debug!(
+            families = results.len(),
+            outliers = outliers,
+            failed = failed,
+            "Family comparison reported"
+        );
+        Ok(())
+    }
+
+    /// Print two saved runs side by side, flagging figures that moved beyond
+    /// `tolerance_pct`
+    ///
+    /// `names` are how the baseline and the current run are referred to, e.g. their files.
+    pub fn print_comparison(
+        &self,
+        comparison: &Comparison,
+        tolerance_pct: f64,
+        names: (&str, &str),
+    ) -> Result<()> {
+        outln!(
+            self,
+            "\n{}",
+            self.theme.accent("┌─────────────────────────────┐")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("│  Synapse Run Comparison     │")
+        );
+        outln!(
+            self,
+            "{}",
+            self.theme.accent("└─────────────────────────────┘")
+        );
+        outln!(self, "  Baseline: {}", names.0);
+        outln!(self, "  Current:  {}", names.1);
+        outln!(self);
+
+        outln!(
+            self,
+            "  {:<8} {:>12} {:>12} {:>9}",
+            "",
+            "Baseline",
+            "Current",
+            "Change"
+        );
+        for figure in &comparison.figures {
+            let change = format!("{:>+8.1}%", figure.change_pct());
+            let (change, flag) = if figure.regressed(tolerance_pct) {
+                let flag = match figure.significance {
+                    Some(test) if !test.is_significantly_slower() => self.theme.warning(&format!(
+                        "  ⚠ regression, within noise ({})",
+                        format_p_value(test.p_value)
+                    )),
+                    Some(test) => self
+                        .theme
+                        .bad(&format!(
+                            "  ⚠ significant regression ({})",
+                            format_p_value(test.p_value)
+                        ))
+                        .bold(),
+                    None => self.theme.bad("  ⚠ regression").bold(),
+                };
+                (self.theme.bad(&change), flag)
+            } else if figure.improved(tolerance_pct) {
+                let flag = match figure.significance {
+                    Some(test) => format!("  improved ({})", format_p_value(test.p_value)),
+                    None => "  improved".into(),
+                };
+                (self.theme.good(&change), self.theme.good(&flag))
+            } else {
+                (change.normal(), "".normal())
+            };
+            outln!(
+                self,
+                "  {:<8} {:>9.1} µs {:>9.1} µs {}{}",
+                figure.label,
+                figure.baseline_ns / 1000.0,
+                figure.current_ns / 1000.0,
+                change,
+                flag
+            );
+        }
+        let points = comparison.current_loss_pct - comparison.baseline_loss_pct;
+        let change = format!("{:>+6.2} pt", points);
+        let (change, flag) = if comparison.loss_regressed() {
+            (
+                self.theme.bad(&change),
+                self.theme.bad("  ⚠ regression").bold(),
+            )
+        } else {
+            (change.normal(), "".normal())
+        };
+        outln!(
+            self,
+            "  {:<8} {:>11.2}% {:>11.2}% {}{}",
+            "Loss",
+            comparison.baseline_loss_pct,
+            comparison.current_loss_pct,
+            change,
+            flag
+        );
+        outln!(self);
+
+        let regressions = comparison.regressions(tolerance_pct);
+        if regressions.is_empty() {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .good(&format!("✓ No regression beyond {}%", tolerance_pct))
+            );
+        } else {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .bad(&format!(
+                        "✗ {} regressed beyond {}%: {}",
+                        regressions.len(),
+                        tolerance_pct,
+                        regressions.join(", ")
+                    ))
+                    .bold()
+            );
+        }
+        if let Some(shift) = &comparison.shift {
+            let verdict = match (shift.test.is_significant(), shift.test.z > 0.0) {
+                (false, _) => "no significant shift".normal(),
+                (true, true) => self.theme.warning("significantly slower"),
+                (true, false) => self.theme.good("significantly faster"),
+            };
+            outln!(
+                self,
+                "  Distribution: current slower in {:.1}% of probe pairs (Mann-Whitney U, {}): {}",
+                shift.probability_slower * 100.0,
+                format_p_value(shift.test.p_value),
+                verdict
+            );
+        }
+        outln!(self);
+        self.print_distribution_changes(&comparison.distribution);
+
+        debug!(
+            figures = comparison.figures.len(),
+            regressions = regressions.len(),
+            "Run comparison reported"
+        );
+        Ok(())
+    }
+
+    /// Print the distributions of a comparison back to back, the baseline's bars growing
+    /// left and the current run's right, with the points each range's share moved by
+    fn print_distribution_changes(&self, distribution: &[BucketChange]) {
+        if distribution.is_empty() {
+            return;
+        }
+        outln!(
+            self,
+            "Latency Distribution (share of probes by range, baseline │ current):"
+        );
+        outln!(self);
+        let max_pct = distribution
+            .iter()
+            .map(|bucket| bucket.baseline_pct.max(bucket.current_pct))
+            .fold(0.0f64, f64::max);
+        for bucket in distribution {
+            let bar = |pct| Self::render_bar_from_percentage(pct, max_pct, COMPARISON_BAR_WIDTH);
+            let delta = format!("{:>+7.1} pt", bucket.delta_pct());
+            let delta = match bucket.delta_pct() {
+                _ if !bucket.shifted() => delta.normal(),
+                d if d > 0.0 => self.theme.warning(&format!("{} ▲", delta)),
+                _ => self.theme.warning(&format!("{} ▼", delta)),
+            };
+            outln!(
+                self,
+                "  {:>width$}:  {:>bars$} {} │ {} {:<bars$} {}",
+                bucket.label,
+                bar(bucket.baseline_pct),
+                Self::format_percentage(bucket.baseline_pct),
+                Self::format_percentage(bucket.current_pct),
+                bar(bucket.current_pct),
+                delta,
+                width = LABEL_WIDTH,
+                bars = COMPARISON_BAR_WIDTH
+            );
+        }
+        outln!(self);
+    }
+
+    /// Print where the client runs and where the NIC carrying the connection is attached
+    pub fn print_numa_locality(
+        &self,
+        placement: Option<&NumaPlacement>,
+        affinity: Option<&IrqAffinity>,
+    ) -> Result<()> {
+        if let Some(placement) = placement {
+            let cpus: Vec<usize> = placement.cpus.iter().copied().collect();
+            let memory = if placement.memory_preferred {
+                "memory preferred on the node"
+            } else {
+                "first-touch memory placement"
+            };
+            outln!(
+                self,
+                "NUMA:   client pinned to node {} (CPUs {}, {})",
+                placement.node,
+                format_cpu_list(&cpus),
+                memory
+            );
+        }
+        if let Some(affinity) = affinity {
+            let node = affinity
+                .nic_numa_node
+                .map_or("unknown".to_string(), |n| n.to_string());
+            let irq_cpus: Vec<usize> = affinity.irq_cpus.iter().copied().collect();
+            outln!(
+                self,
+                "NIC:    {} on NUMA node {} (RX interrupts on CPU {})",
+                affinity.interface,
+                node,
+                format_cpu_list(&irq_cpus)
+            );
+        }
+        Ok(())
+    }
+
+    /// Print placements of the measuring thread relative to the NIC that distort results
+    pub fn print_affinity_warnings(&self, warnings: &[AffinityWarning]) -> Result<()> {
+        for warning in warnings {
+            warn!(warning = %warning, "Measuring thread placement distorts results");
+            outln!(
+                self,
+                "{} {}",
+                self.theme.warning("⚠").bold(),
+                self.theme.warning(&warning.to_string())
+            );
+        }
+        if !warnings.is_empty() {
+            outln!(self);
+        }
+        Ok(())
+    }
+
+    /// Print the properties of the environment that skew the run, if any
+    pub fn print_caveats(&self, caveats: &[Caveat]) -> Result<()> {
+        if caveats.is_empty() {
+            return Ok(());
+        }
+        outln!(self, "Environment Caveats:");
+        for caveat in caveats {
+            outln!(
+                self,
+                "  {} {}",
+                self.theme.warning("⚠").bold(),
+                self.theme.warning(&caveat.to_string())
+            );
+        }
+        outln!(self);
+
+        info!(caveats = caveats.len(), "Environment caveats");
+        Ok(())
+    }
+
+    /// Print the periods at which losses recur
+    ///
+    /// `packet_interval` is the mean time between packets, used to express each period
+    /// as a duration as well.
+    pub fn print_loss_pattern(
+        &self,
+        periods: &[LossPeriod],
+        packet_interval: Duration,
+    ) -> Result<()> {
+        outln!(self, "Loss Pattern:");
+        if periods.is_empty() {
+            outln!(self, "  No periodicity detected");
+            outln!(self);
+            return Ok(());
+        }
+        for period in periods {
+            let duration_ms = packet_interval.as_secs_f64() * period.packets as f64 * 1000.0;
+            outln!(
+                self,
+                "  {} every ~{} packets (≈{:.1} ms) for {:.0}% of losses",
+                self.theme.warning("Loss recurs").bold(),
+                period.packets,
+                duration_ms,
+                period.score * 100.0
+            );
+        }
+        outln!(
+            self,
+            "  └─ A fixed period usually matches a buffer size or polling interval in the path"
+        );
+        outln!(self);
+
+        info!(periods = ?periods, "Loss periodicity detected");
+        Ok(())
+    }
+
+    /// Print the intervals at which latency spikes recur, with the likely cause
+    pub fn print_spike_periods(&self, periods: &[SpikePeriod]) -> Result<()> {
+        outln!(self, "Latency Periodicity:");
+        if periods.is_empty() {
+            outln!(self, "  No periodic latency spikes detected");
+            outln!(self);
+            return Ok(());
+        }
+        for period in periods {
+            let cause = match period.timer_hz() {
+                Some(hz) => format!("suggests a {:.0}Hz timer tick", hz),
+                None => "suggests a periodic task or polling interval".to_string(),
+            };
+            outln!(
+                self,
+                "  {} every {:.2}ms ({:.1}Hz) for {:.0}% of spikes: {}",
+                self.theme.warning("Spikes").bold(),
+                period.interval.as_secs_f64() * 1000.0,
+                period.frequency_hz(),
+                period.score * 100.0,
+                cause
+            );
+        }
+        outln!(self);
+
+        info!(periods = ?periods, "Latency spike periodicity detected");
+        Ok(())
+    }
+
+    /// Print the report card of a run against its latency SLOs
+    ///
+    /// `elapsed` is the length of the measurement phase the objectives were met over.
+    pub fn print_slo_report(&self, outcomes: &[SloOutcome], elapsed: Duration) -> Result<()> {
+        outln!(self, "SLO Report Card:");
+        let secs = elapsed.as_secs_f64();
+        let window = if secs >= 3600.0 {
+            format!("{:.1} h", secs / 3600.0)
+        } else if secs >= 60.0 {
+            format!("{:.1} min", secs / 60.0)
+        } else {
+            format!("{:.2} s", secs)
+        };
+        for outcome in outcomes {
+            let verdict = if outcome.met() {
+                self.theme.good("✓ MET   ").bold()
+            } else {
+                self.theme.bad("✗ MISSED").bold()
+            };
+            let consumed = format!("{:.1}%", outcome.budget_consumed_pct());
+            let consumed = if outcome.met() {
+                consumed.normal()
+            } else {
+                self.theme.bad(&consumed)
+            };
+            outln!(
+                self,
+                "  {} {} over {}: {:.3}% attained; error budget consumed: {}",
+                verdict,
+                outcome.slo,
+                window,
+                outcome.attained_pct(),
+                consumed
+            );
+        }
+        outln!(self);
+
+        info!(
+            met = outcomes.iter().filter(|o| o.met()).count(),
+            total = outcomes.len(),
+            "SLOs evaluated"
+        );
+        Ok(())
+    }
+
+    /// Print which conditions of an SLA policy the run met
+    pub fn print_sla_report(&self, outcomes: &[ConditionOutcome]) -> Result<()> {
+        let met = outcomes.iter().filter(|o| o.met).count();
+        let policy = if met == outcomes.len() {
+            self.theme.good("✓ MET").bold()
+        } else {
+            self.theme.bad("✗ BREACHED").bold()
+        };
+        outln!(
+            self,
+            "SLA Policy: {} ({} of {} conditions met)",
+            policy,
+            met,
+            outcomes.len()
+        );
+        for outcome in outcomes {
+            let mark = if outcome.met {
+                self.theme.good("✓")
+            } else {
+                self.theme.bad("✗")
+            };
+            let actual = match outcome.condition {
+                Condition::Latency { .. } => format!("{:.1} µs", outcome.actual / 1000.0),
+                Condition::Loss { .. } => format!("{:.3}%", outcome.actual),
+            };
+            let actual = if outcome.met {
+                actual.normal()
+            } else {
+                self.theme.bad(&actual)
+            };
+            outln!(
+                self,
+                "  {} {:<18} measured {}",
+                mark,
+                outcome.condition.to_string(),
+                actual
+            );
+        }
+        outln!(self);
+
+        info!(met, total = outcomes.len(), "SLA policy evaluated");
+        Ok(())
+    }
+
+    /// Print how the P99 of a run compares with the rolling baseline of the runs of the
+    /// `hours` before it (see [`crate::client::baseline`]), or that there were none
+    pub fn print_baseline(&self, outcome: Option<&BaselineOutcome>, hours: u64) -> Result<()> {
+        let Some(outcome) = outcome else {
+            outln!(
+                self,
+                "Rolling Baseline (last {} h): no earlier complete run of this server; nothing to compare\n",
+                hours
+            );
+            info!(hours, "No baseline runs");
+            return Ok(());
+        };
+        let baseline = &outcome.baseline;
+        outln!(
+            self,
+            "Rolling Baseline (last {} h, {} run{}, {} probes):",
+            hours,
+            baseline.runs,
+            if baseline.runs == 1 { "" } else { "s" },
+            self.numbers.count(baseline.samples)
+        );
+        let (verdict, change) = if outcome.within() {
+            (
+                self.theme.good("✓ WITHIN  ").bold(),
+                format!("{:+.1}%", outcome.change_pct()).normal(),
+            )
+        } else {
+            (
+                self.theme.bad("✗ DEVIATES").bold(),
+                self.theme.bad(&format!("{:+.1}%", outcome.change_pct())),
+            )
+        };
+        outln!(
+            self,
+            "  {} P99 {:.1} µs vs baseline {:.1} µs: {} (limit ±{}%)",
+            verdict,
+            outcome.p99_ns as f64 / 1000.0,
+            baseline.p99_ns as f64 / 1000.0,
+            change,
+            outcome.max_change_pct
+        );
+        outln!(
+            self,
+            "  └─ Baseline P50 {:.1} µs",
+            baseline.p50_ns as f64 / 1000.0
+        );
+        outln!(self);
+
+        info!(
+            runs = baseline.runs,
+            baseline_p99_ns = baseline.p99_ns,
+            p99_ns = outcome.p99_ns,
+            change_pct = outcome.change_pct(),
+            within = outcome.within(),
+            "Baseline compared"
+        );
+        Ok(())
+    }
+
+    /// Print the confidence interval --until-ci waited on and whether it narrowed before
+    /// --packets ran out
+    pub fn print_confidence(
+        &self,
+        stats: &Statistics,
+        target: CiTarget,
+        total_packets: usize,
+        planned_packets: usize,
+    ) -> Result<()> {
+        let Some(ci) = ConfidenceInterval::of(stats, target.metric)
+            .filter(|_| stats.count() >= MIN_CI_SAMPLES)
+        else {
+            outln!(
+                self,
+                "Confidence (95%): {} answered probes are too few to bound the {}; raise --packets\n",
+                self.numbers.count(stats.count()),
+                target.metric
+            );
+            info!(
+                samples = stats.count(),
+                "Too few probes for a confidence interval"
+            );
+            return Ok(());
+        };
+        outln!(
+            self,
+            "Confidence (95%, target ±{}%):",
+            target.half_width_pct
+        );
+        let reached = ci.half_width_pct() <= target.half_width_pct;
+        let verdict = if reached {
+            self.theme.good("✓ REACHED").bold()
+        } else {
+            self.theme.warning("✗ WIDER  ").bold()
+        };
+        outln!(
+            self,
+            "  {} {} {:.1} µs ± {:.2}% ({:.1} – {:.1} µs)",
+            verdict,
+            target.metric,
+            ci.estimate_ns / 1000.0,
+            ci.half_width_pct(),
+            ci.low_ns / 1000.0,
+            ci.high_ns / 1000.0
+        );
+        if reached && total_packets < planned_packets {
+            outln!(
+                self,
+                "  └─ Stopped after {} of at most {} probes",
+                self.numbers.count(total_packets as u64),
+                self.numbers.count(planned_packets as u64)
+            );
+        } else if !reached {
+            outln!(
+                self,
+                "  └─ Not reached within --packets {}; raise --packets for a tighter result",
+                self.numbers.count(planned_packets as u64)
+            );
+        }
+        outln!(self);
+
+        info!(
+            metric = %target.metric,
+            estimate_ns = ci.estimate_ns,
+            half_width_pct = ci.half_width_pct(),
+            reached,
+            packets = total_packets,
+            "Confidence interval"
+        );
+        Ok(())
+    }
+
+    /// Print where the packet capture went and whether it is complete
+    pub fn print_capture(&self, path: &Path, stats: &CaptureStats) -> Result<()> {
+        outln!(
+            self,
+            "Capture: {} frames of the probe flow written to {}",
+            self.numbers.count(stats.written),
+            path.display()
+        );
+        if stats.dropped > 0 {
+            outln!(
+                self,
+                "  └─ {}",
+                self.theme.warning(&format!(
+                    "{} frames dropped by the kernel; the capture is incomplete",
+                    self.numbers.count(stats.dropped)
+                ))
+            );
+        }
+        Ok(())
+    }
+
+    /// Print where the report bundle went and the files it holds
+    pub fn print_report_bundle(&self, dir: &Path, manifest: &BundleManifest) -> Result<()> {
+        outln!(
+            self,
+            "Report bundle: {} files written to {}",
+            manifest.files.len() + 1,
+            dir.display()
+        );
+        for file in &manifest.files {
+            outln!(
+                self,
+                "  ├─ {:<16} {:>12}  {}",
+                file.name,
+                self.numbers.bytes(file.bytes),
+                file.description
+            );
+        }
+        outln!(self, "  └─ {}", MANIFEST_FILE);
+        Ok(())
+    }
+
+    /// Print how the probes of a run line up with the frames of its capture
+    ///
+    /// Splits each matched round trip into the time spent in the client's stack on the
+    /// way out, on the wire, and in the stack on the way back, and lists the probes
+    /// whose application RTT exceeds the wire RTT the most.
+    pub fn print_wire_timings(&self, timings: &[WireTiming]) -> Result<()> {
+        let matched: Vec<&WireTiming> = timings
+            .iter()
+            .filter(|t| t.wire_rtt_ns().is_some() && t.app_rtt_ns.is_some())
+            .collect();
+        let without_probe = timings.iter().filter(|t| t.wire_out_ns.is_none()).count();
+        let without_reply = timings
+            .iter()
+            .filter(|t| t.wire_out_ns.is_some() && t.wire_in_ns.is_none())
+            .count();
+
+        outln!(self, "Wire vs Application Timestamps:");
+        outln!(
+            self,
+            "  Matched: {} of {} packets ({} without a probe frame, {} without a reply frame)",
+            self.numbers.count(matched.len() as u64),
+            self.numbers.count(timings.len() as u64),
+            self.numbers.count(without_probe as u64),
+            self.numbers.count(without_reply as u64)
+        );
+        if matched.is_empty() {
+            outln!(
+                self,
+                "  └─ {}",
+                self.theme.warning(
+                    "No probe matched; is this the capture of this run, and the server port right?"
+                )
+            );
+            outln!(self);
+            return Ok(());
+        }
+
+        let column = |component: fn(&WireTiming) -> Option<i64>| {
+            let mut values: Vec<i64> = matched.iter().filter_map(|t| component(t)).collect();
+            values.sort_unstable();
+            values
+        };
+        let columns = [
+            column(WireTiming::send_path_ns),
+            column(WireTiming::wire_rtt_ns),
+            column(WireTiming::receive_path_ns),
+        ];
+        outln!(
+            self,
+            "  {:<6} {:>14} {:>14} {:>14}",
+            "",
+            "Send path",
+            "Wire RTT",
+            "Receive path"
+        );
+        for (label, quantile) in [("P50:", 0.5), ("P99:", 0.99), ("Max:", 1.0)] {
+            let [send, wire, receive] = columns.each_ref().map(|values| {
+                let index = ((values.len() - 1) as f64 * quantile).round() as usize;
+                values[index] as f64 / 1000.0
+            });
+            outln!(
+                self,
+                "  {:<6} {:>11.1} µs {:>11.1} µs {:>11.1} µs",
+                label,
+                send,
+                wire,
+                receive
+            );
+        }
+
+        let mut overheads: Vec<(i64, &WireTiming)> = matched
+            .iter()
+            .filter_map(|t| Some((t.app_rtt_ns? as i64 - t.wire_rtt_ns()?, *t)))
+            .collect();
+        overheads.sort_by_key(|&(overhead, _)| std::cmp::Reverse(overhead));
+        outln!(
+            self,
+            "  Largest host overhead (application RTT minus wire RTT):"
+        );
+        for (overhead, timing) in overheads.iter().take(EXAMPLES_PER_BUCKET) {
+            outln!(
+                self,
+                "    seq {:<10} +{:.1} µs (send path {:.1} µs, receive path {:.1} µs)",
+                timing.sequence,
+                *overhead as f64 / 1000.0,
+                timing.send_path_ns().unwrap_or(0) as f64 / 1000.0,
+                timing.receive_path_ns().unwrap_or(0) as f64 / 1000.0
+            );
+        }
+        outln!(self);
+
+        debug!(
+            matched = matched.len(),
+            packets = timings.len(),
+            "Wire timings reported"
+        );
+        Ok(())
+    }
+
+    /// Print which pages backed the sample buffers after `--huge-pages` was requested
+    pub fn print_sample_backing(&self, backing: PageBacking) -> Result<()> {
+        match backing {
+            PageBacking::HugeTlb => {
+                outln!(self, "Sample buffers: {} (pre-faulted)", backing);
+            }
+            PageBacking::Transparent => {
+                outln!(self, "Sample buffers: {} (pre-faulted)", backing);
+                outln!(
+                    self,
+                    "  └─ No huge pages reserved; reserve them with `sysctl vm.nr_hugepages=N` for guaranteed backing"
+                );
+            }
+            PageBacking::Regular => {
+                outln!(
+                    self,
+                    "Sample buffers: {}",
+                    self.theme
+                        .warning("huge page allocation failed, regular pages used")
+                );
+                outln!(
+                    self,
+                    "  └─ Reserve huge pages with `sysctl vm.nr_hugepages=N` or enable transparent huge pages"
+                );
+            }
+        }
+        outln!(self);
+        Ok(())
+    }
+
+    /// Print whether the replies kept the DSCP the probes were marked with
+    ///
+    /// A remarked reply was queued as another class somewhere on the round trip, which
+    /// often explains why "priority" probes see default-queue latency.
+    pub fn print_dscp(
+        &self,
+        sent: Dscp,
+        received: &BTreeMap<Dscp, usize>,
+        transport: Transport,
+    ) -> Result<()> {
+        outln!(self, "DSCP: probes marked {}", sent);
+        let replies: usize = received.values().sum();
+        if replies == 0 {
+            let reason = match transport {
+                Transport::Tcp => "replies cannot be checked over TCP; use --transport udp",
+                Transport::Udp => "no reply marking could be read",
+            };
+            outln!(self, "  └─ Not verified: {}", reason);
+            outln!(self);
+            return Ok(());
+        }
+
+        for (dscp, count) in received {
+            let line = format!(
+                "  Replies:   {} {} ({:.1}%)",
+                self.numbers.count(*count as u64),
+                dscp,
+                *count as f64 / replies as f64 * 100.0
+            );
+            if *dscp == sent {
+                outln!(self, "{}", line);
+            } else {
+                outln!(self, "{}", self.theme.bad(&line));
+            }
+        }
+        let remarked = replies - received.get(&sent).copied().unwrap_or(0);
+        if remarked == 0 {
+            outln!(
+                self,
+                "  └─ {}",
+                self.theme.good("Every reply kept the marking")
+            );
+        } else {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!(
+                        "⚠ {} replies ({:.1}%) were remarked on the way out or back, so part of the path did not queue them as {}",
+                        self.numbers.count(remarked as u64),
+                        remarked as f64 / replies as f64 * 100.0,
+                        sent
+                    ))
+                    .bold()
+            );
+            outln!(
+                self,
+                "  └─ The synapse server answers with the marking each probe arrived with; other echo servers may not"
+            );
+        }
+        outln!(self);
+
+        debug!(
+            dscp = sent.value(),
+            replies = replies,
+            remarked = remarked,
+            "DSCP verification reported"
+        );
+        Ok(())
+    }
+
+    /// Print the target and achieved send rate of a paced run
+    ///
+    /// Probing is lockstep, so a round trip longer than the interval delays the next
+    /// send; many late sends mean the run did not keep the requested arrival pattern.
+    pub fn print_pacing(
+        &self,
+        pacing: &PacingStats,
+        elapsed: Duration,
+        total_packets: usize,
+    ) -> Result<()> {
+        let achieved = if elapsed.is_zero() {
+            0.0
+        } else {
+            total_packets as f64 / elapsed.as_secs_f64()
+        };
+        let interval_ms = pacing.interval().as_secs_f64() * 1000.0;
+
+        outln!(
+            self,
+            "Pacing: {} pps target ({}, {:.3}ms mean gap), {} pps achieved",
+            self.numbers.count(pacing.rate),
+            pacing.traffic,
+            interval_ms,
+            self.numbers.count(achieved.round() as u64)
+        );
+        if pacing.late_sends == 0 {
+            outln!(self, "  └─ Every probe was sent on schedule");
+        } else {
+            let late_pct = pacing.late_sends as f64 / total_packets.max(1) as f64 * 100.0;
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!(
+                        "⚠ {} probes ({:.1}%) sent late: the previous round trip outlasted the gap",
+                        self.numbers.count(pacing.late_sends as u64),
+                        late_pct
+                    ))
+                    .bold()
+            );
+            outln!(
+                self,
+                "  └─ Probes are sent one at a time; lower --rate to keep to the schedule"
+            );
+        }
+        outln!(self);
+
+        debug!(
+            rate = pacing.rate,
+            achieved_pps = achieved,
+            traffic = %pacing.traffic,
+            late_sends = pacing.late_sends,
+            "Pacing reported"
+        );
+        Ok(())
+    }
+
+    /// Print the latencies of probes sent after a short (warm) and a long (cold) idle time
+    ///
+    /// A cold path slower than the warm one means state along the path expires while the
+    /// connection is idle, so the run's rate shapes its latency.
+    pub fn print_idle_split(&self, split: &IdleSplit) -> Result<()> {
+        let warm = Statistics::new(&split.warm)?;
+        let cold = Statistics::new(&split.cold)?;
+        let threshold_ms = split.threshold.as_secs_f64() * 1000.0;
+        let row = |label: &str, warm_ns: u64, cold_ns: u64| {
+            outln!(
+                self,
+                "  {:<10} {:>10.1} µs {:>12.1} µs",
+                label,
+                warm_ns as f64 / 1000.0,
+                cold_ns as f64 / 1000.0
+            );
+        };
+
+        outln!(
+            self,
+            "Idle Gap Split (cold after {:.3}ms or more without traffic):",
+            threshold_ms
+        );
+        outln!(self, "  {:<10} {:>13} {:>15}", "", "Warm", "Cold");
+        outln!(
+            self,
+            "  {:<10} {:>13} {:>15}",
+            "Probes:",
+            self.numbers.count(split.warm.len() as u64),
+            self.numbers.count(split.cold.len() as u64)
+        );
+        for (label, quantile) in [("P50:", 0.5), ("P99:", 0.99)] {
+            row(label, warm.percentile(quantile), cold.percentile(quantile));
+        }
+        row("Max:", warm.max(), cold.max());
+
+        if split.warm.len() < MIN_SPLIT_SAMPLES || split.cold.len() < MIN_SPLIT_SAMPLES {
+            outln!(
+                self,
+                "  └─ Too few {} probes to compare; vary the gaps with --traffic poisson or move the threshold with --cold-gap-ms",
+                if split.warm.len() < MIN_SPLIT_SAMPLES {
+                    "warm"
+                } else {
+                    "cold"
+                }
+            );
+        } else {
+            let warm_p50 = warm.percentile(0.5) as f64;
+            let cold_p50 = cold.percentile(0.5) as f64;
+            if cold_p50 > warm_p50 * COLD_PENALTY_RATIO {
+                outln!(
+                    self,
+                    "  {}",
+                    self.theme
+                        .warning(&format!(
+                            "⚠ Cold probes are {:.1} µs ({:.0}%) slower at P50",
+                            (cold_p50 - warm_p50) / 1000.0,
+                            (cold_p50 / warm_p50 - 1.0) * 100.0
+                        ))
+                        .bold()
+                );
+                outln!(
+                    self,
+                    "  └─ State along the path (CPU caches and idle states, flow tables, ARP entries) expires while the connection is idle"
+                );
+            } else {
+                outln!(
+                    self,
+                    "  └─ {}",
+                    self.theme
+                        .good("No cold-path penalty: idle time does not slow the round trip")
+                );
+            }
+        }
+        outln!(self);
+
+        debug!(
+            threshold_ns = split.threshold.as_nanos() as u64,
+            warm = split.warm.len(),
+            cold = split.cold.len(),
+            "Idle gap split reported"
+        );
+        Ok(())
+    }
+
+    /// Print the CPU time and context switches of the measuring thread
+    ///
+    /// A client that was CPU-bound or frequently preempted measures its own scheduling
+    /// delays, not the network.
+    pub fn print_thread_usage(
+        &self,
+        usage: &ThreadUsage,
+        elapsed: Duration,
+        total_packets: usize,
+    ) -> Result<()> {
+        let busy_pct = usage.busy_fraction(elapsed) * 100.0;
+        let preemptions_per_packet = if total_packets == 0 {
+            0.0
+        } else {
+            usage.involuntary_switches as f64 / total_packets as f64
+        };
+
+        outln!(self, "Client CPU (measuring thread):");
+        outln!(
+            self,
+            "  CPU time:  {:.3}s user, {:.3}s system ({:.1}% of wall time)",
+            usage.user.as_secs_f64(),
+            usage.system.as_secs_f64(),
+            busy_pct
+        );
+        outln!(
+            self,
+            "  Switches:  {} voluntary, {} involuntary ({:.3} per packet)",
+            self.numbers.count(usage.voluntary_switches),
+            self.numbers.count(usage.involuntary_switches),
+            preemptions_per_packet
+        );
+
+        if busy_pct / 100.0 > CPU_BOUND_THRESHOLD {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(
+                        "⚠ The client was CPU-bound; latencies include its own processing delays"
+                    )
+                    .bold()
+            );
+        } else if preemptions_per_packet > PREEMPTION_PER_PACKET_THRESHOLD {
+            outln!(
+                self,
+                "  {}",
+                self.theme.warning("⚠ The client was frequently preempted; consider pinning it to an idle core")
+                    .bold()
+            );
+        } else {
+            outln!(self, "  └─ The client was not CPU-starved");
+        }
+        outln!(self);
+
+        debug!(
+            user_secs = usage.user.as_secs_f64(),
+            system_secs = usage.system.as_secs_f64(),
+            voluntary_switches = usage.voluntary_switches,
+            involuntary_switches = usage.involuntary_switches,
+            "Thread usage reported"
+        );
+        Ok(())
+    }
+
+    /// Print how often the cgroup's CPU quota throttled the client during the run
+    pub fn print_cpu_throttling(&self, throttling: &CpuThrottling) -> Result<()> {
+        let quota = throttling.quota;
+        outln!(
+            self,
+            "CPU Throttling (cgroup quota {:.2} cores, {} ms periods):",
+            quota.cores(),
+            quota.period.as_millis()
+        );
+        outln!(
+            self,
+            "  Throttled: {} of {} periods ({:.1}%), {:.1} ms in total",
+            self.numbers.count(throttling.throttled_periods),
+            self.numbers.count(throttling.periods),
+            throttling.throttled_fraction() * 100.0,
+            throttling.throttled.as_secs_f64() * 1000.0
+        );
+        if throttling.throttled_periods > 0 {
+            outln!(
+                self,
+                "  {}",
+                self.theme
+                    .warning(&format!(
+                        "⚠ The client was throttled; RTT spikes of up to {} ms may be the \
+                         scheduler's rather than the network's",
+                        quota.period.as_millis()
+                    ))
+                    .bold()
+            );
+        } else {
+            outln!(self, "  └─ The client was not throttled");
+        }
+        outln!(self);
+
+        info!(
+            periods = throttling.periods,
+            throttled_periods = throttling.throttled_periods,
+            throttled_ms = throttling.throttled.as_secs_f64() * 1000.0,
+            "CPU throttling"
+        );
+        Ok(())
+    }
+
+    /// Print the completion time of the run's probes replayed as requests of a client
+    /// that retries, next to the raw RTT
+    pub fn print_retry_outcome(&self, outcome: &RetryOutcome, stats: &Statistics) -> Result<()> {
+        outln!(self, "Retry Policy ({}):", outcome.policy);
+        if outcome.requests() == 0 {
+            outln!(self, "  No request completed within the run\n");
+            return Ok(());
+        }
+        outln!(
+            self,
+            "  Requests:  {} ({} retried, {} failed after {} attempts)",
+            self.numbers.count(outcome.requests()),
+            Self::format_percentage(outcome.retried_pct()).trim(),
+            Self::format_percentage(outcome.failed_pct()).trim(),
+            outcome.policy.attempts
+        );
+        outln!(
+            self,
+            "  Load:      {:.3} attempts per request",
+            outcome.amplification()
+        );
+        let quantiles = [0.5, 0.9, 0.99, 0.999, 1.0];
+        let completion = outcome.completion_percentiles(&quantiles);
+        if !completion.is_empty() {
+            outln!(self, "  Completion time (raw RTT):");
+            for ((label, quantile), completion_ns) in ["P50", "P90", "P99", "P99.9", "Max"]
+                .iter()
+                .zip(quantiles)
+                .zip(completion)
+            {
+                let rtt_ns = if quantile < 1.0 {
+                    stats.percentile(quantile)
+                } else {
+                    stats.max()
+                };
+                let line = format!(
+                    "    {:<6} {:>11.3} ms  ({:.3} ms)",
+                    label,
+                    completion_ns as f64 / 1e6,
+                    rtt_ns as f64 / 1e6
+                );
+                // A tail the retries stretched past the raw RTT is what users see
+                if completion_ns > outcome.policy.timeout.as_nanos() as u64 {
+                    outln!(self, "{}", self.theme.warning(&line));
+                } else {
+                    outln!(self, "{}", line);
+                }
+            }
+        }
+        for (attempt, &completed) in outcome.completed_by_attempt.iter().enumerate().skip(1) {
+            if completed > 0 {
+                outln!(
+                    self,
+                    "  ├─ {} completed on attempt {}",
+                    self.numbers.count(completed),
+                    attempt + 1
+                );
+            }
+        }
+        outln!(
+            self,
+            "  └─ Retries replay the run's next probes, sent at its pace rather than after the backoff"
+        );
+        outln!(self);
+
+        info!(
+            requests = outcome.requests(),
+            failed = outcome.failed,
+            amplification = outcome.amplification(),
+            "Retry policy replayed"
+        );
+        Ok(())
+    }
+
+    /// Print the energy the run used, and its emissions at `carbon_intensity` grams of
+    /// CO2 equivalent per kWh if given
+    pub fn print_energy(&self, energy: &EnergyReport, carbon_intensity: Option<f64>) -> Result<()> {
+        outln!(self, "Energy (RAPL, whole CPU packages):");
+        outln!(
+            self,
+            "  Used:    {} ({:.4} Wh) over {:.1} s",
+            self.theme.accent(&format!("{:.1} J", energy.joules())),
+            energy.watt_hours(),
+            energy.elapsed.as_secs_f64()
+        );
+        outln!(self, "  Power:   {:.1} W mean", energy.mean_watts());
+        for zone in &energy.zones {
+            outln!(self, "  ├─ {:<16} {:.1} J", zone.zone, zone.joules);
+        }
+        if let Some(intensity) = carbon_intensity {
+            outln!(
+                self,
+                "  Carbon:  {:.3} g CO2e at {} g/kWh",
+                energy.carbon_grams(intensity),
+                intensity
+            );
+        }
+        outln!(
+            self,
+            "  └─ Counts everything the host ran meanwhile, not the client alone"
+        );
+        outln!(self);
+
+        info!(
+            joules = energy.joules(),
+            mean_watts = energy.mean_watts(),
+            "Energy used"
+        );
+        Ok(())
+    }
+
+    /// Print the windows in which the host showed activity that can distort latency
+    ///
+    /// Each window lists what happened (preemptions, CPU migrations, load) and the worst
+    /// RTT seen in it, so host noise can be told apart from network behavior.
+    pub fn print_system_events(&self, timeline: &SystemTimeline, stats: &Statistics) -> Result<()> {
+        let events = timeline.events();
+        let p99_us = stats.percentile(0.99) as f64 / 1000.0;
+
+        outln!(self, "System Events (host activity during the run):");
+        let migrations = timeline
+            .total_migrations()
+            .map_or("n/a".to_string(), |m| m.to_string());
+        outln!(
+            self,
+            "  {} preemption(s), {} CPU migration(s) of the measuring thread over {} sample(s)",
+            timeline.total_involuntary_switches(),
+            migrations,
+            timeline.samples.len()
+        );
+
+        if events.is_empty() {
+            outln!(self, "  No host noise detected");
+            outln!(self);
+            return Ok(());
+        }
+
+        for event in &events {
+            let max_us = event.max_latency_ns as f64 / 1000.0;
+            let max_rtt = format!("max RTT {:>9.1} µs", max_us);
+            // Flag windows whose worst RTT is in the tail: likely caused by the event
+            let max_rtt = if max_us > p99_us {
+                self.theme.warning(&max_rtt).bold().to_string()
+            } else {
+                max_rtt
+            };
+            outln!(
+                self,
+                "  {:>7.2}s–{:<8}  {}  {}",
+                event.start.as_secs_f64(),
+                format!("{:.2}s", event.end.as_secs_f64()),
+                max_rtt,
+                event.description
+            );
+        }
+        outln!(
+            self,
+            "  └─ Highlighted windows contain RTTs above the run's P99 ({:.1} µs)",
+            p99_us
+        );
+        outln!(self);
+        Ok(())
+    }
+
+    /// Print how long the client spent inside the send call
+    ///
+    /// Sends that block longer than `SEND_STALL_THRESHOLD_NS` are counted as send stalls
+    /// and get their own distribution, so a local send-path problem is not mistaken for
+    /// network or server latency.
+    pub fn print_send_path(&self, send_durations: &[u64]) -> Result<()> {
+        if send_durations.is_empty() {
+            return Ok(());
+        }
+        let stats = Statistics::new(send_durations)?;
+        let stalls: Vec<u64> = send_durations
+            .iter()
+            .copied()
+            .filter(|&d| d > SEND_STALL_THRESHOLD_NS)
+            .collect();
+        let stall_pct = (stalls.len() as f64 / send_durations.len() as f64) * 100.0;
+        let threshold_us = SEND_STALL_THRESHOLD_NS as f64 / 1000.0;
+
+        outln!(
+            self,
+            "Send Path (time spent inside send, included in the RTT above):"
+        );
+        outln!(
+            self,
+            "  P50:       {:>8.1} µs",
+            stats.percentile(0.5) as f64 / 1000.0
+        );
+        outln!(
+            self,
+            "  P99:       {:>8.1} µs",
+            stats.percentile(0.99) as f64 / 1000.0
+        );
+        outln!(self, "  Max:       {:>8.1} µs", stats.max() as f64 / 1000.0);
+
+        if stalls.is_empty() {
+            outln!(
+                self,
+                "  No send stalls (no send took longer than {:.0} µs)",
+                threshold_us
+            );
+            outln!(self);
+            return Ok(());
+        }
+
+        warn!(
+            stalls = stalls.len(),
+            threshold_ns = SEND_STALL_THRESHOLD_NS,
+            "Send path stalls detected"
+        );
+        outln!(
+            self,
+            "  {} {} send(s) blocked longer than {:.0} µs ({:.3}%)",
+            self.theme.warning("⚠ Send stalls:").bold(),
+            self.numbers.count(stalls.len() as u64),
+            threshold_us,
+            stall_pct
+        );
+        outln!(
+            self,
+            "          └─ Usually a full socket send buffer or Nagle's algorithm"
+        );
+        outln!(self);
+        self.print_distribution(
+            "Send Stall Distribution (send count by duration):",
+            stalls.iter().map(|&d| (d, 1)),
+            send_durations.len(),
+        );
+        outln!(self);
+        Ok(())
+    }
+
+    /// Print bucket distribution of latencies
+    pub fn print_bucket_distribution(&self, latencies: &[u64], total_packets: usize) -> Result<()> {
+        self.print_distribution(
+            LATENCY_DISTRIBUTION_TITLE,
+            latencies.iter().map(|&l| (l, 1)),
+            total_packets,
+        );
+        Ok(())
+    }
+
+    /// Print the slowest example packets of every distribution bucket
+    ///
+    /// `sequences` and `send_offsets` are parallel to `latencies`; send times are printed
+    /// as Unix epoch seconds, the format of `tcpdump -tt` and Wireshark's epoch column, so
+    /// slow packets can be looked up in logs and captures directly.
+    pub fn print_bucket_examples(
+        &self,
+        latencies: &[u64],
+        sequences: &[u64],
+        send_offsets: &[u64],
+        started_at: SystemTime,
+    ) -> Result<()> {
+        let min_ns = latencies.iter().copied().min().unwrap_or_default();
+        let max_ns = latencies.iter().copied().max().unwrap_or_default();
+        let buckets = self.buckets(min_ns, max_ns);
+        let examples = Self::slowest_per_bucket(&buckets, latencies, sequences, send_offsets);
+        let epoch_start = started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        outln!(self);
+        outln!(
+            self,
+            "Example Packets (slowest per range, send time as Unix epoch):"
+        );
+        outln!(self);
+        for (bucket, slowest) in examples.iter().enumerate() {
+            let label = buckets
+                .buckets
+                .get(bucket)
+                .map_or(buckets.outlier_label.as_str(), |b| b.label.as_str());
+            for (i, &(latency_ns, sequence, offset_ns)) in slowest.iter().enumerate() {
+                let label = if i == 0 { label } else { "" };
+                let label = format!("{:>width$}", label, width = LABEL_WIDTH);
+                let label = if bucket == buckets.len() {
+                    self.theme.bad(&label).bold().to_string()
+                } else {
+                    label
+                };
+                let offset_secs = offset_ns as f64 / 1e9;
+                outln!(
+                    self,
+                    "  {}   seq {:>9}  {:>10.1} µs  sent {:.6} (+{:.6}s)",
+                    label,
+                    sequence,
+                    latency_ns as f64 / 1000.0,
+                    epoch_start + offset_secs,
+                    offset_secs
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Print the P50 and P99 of each slice of a run as a chart (see
+    /// [`crate::client::trend`])
+    ///
+    /// `█` fills each column up to the slice's P50 and `░` from there up to its P99. The
+    /// scale is logarithmic, from the lowest P50 on the bottom row to the highest P99 on
+    /// the top one, so a burst an order of magnitude above the median does not flatten
+    /// the rest. Columns of slices without an answered probe stay empty.
+    pub fn print_latency_over_time(&self, buckets: &[TrendBucket]) -> Result<()> {
+        let answered = || buckets.iter().filter(|b| b.count > 0);
+        let (Some(floor_ns), Some(top_ns)) = (
+            answered().filter_map(|b| b.p50_ns).min(),
+            answered().filter_map(|b| b.p99_ns).max(),
+        ) else {
+            return Ok(());
+        };
+        if buckets.len() < 2 {
+            return Ok(());
+        }
+        let width = buckets[1].start - buckets[0].start;
+        let (floor, top) = ((floor_ns.max(1)) as f64, top_ns.max(1) as f64);
+        // Rows filled by a latency: one for the floor, all of them for the top
+        let cells = |ns: u64| {
+            if top <= floor {
+                return TREND_ROWS;
+            }
+            let level = (ns.max(1) as f64 / floor).ln() / (top / floor).ln();
+            1 + (level.clamp(0.0, 1.0) * (TREND_ROWS - 1) as f64).round() as usize
+        };
+
+        outln!(
+            self,
+            "Latency Over Time (█ P50, ░ P99 of every {}, log scale):",
+            Self::format_slice_width(width)
+        );
+        for row in (0..TREND_ROWS).rev() {
+            let label = if row == TREND_ROWS - 1 {
+                format!("{:.1} µs", top / 1000.0)
+            } else if row == 0 {
+                format!("{:.1} µs", floor / 1000.0)
+            } else {
+                String::new()
+            };
+            let columns: String = buckets
+                .iter()
+                .map(|bucket| {
+                    let (Some(p50_ns), Some(p99_ns)) = (bucket.p50_ns, bucket.p99_ns) else {
+                        return ' ';
+                    };
+                    if row < cells(p50_ns) {
+                        '█'
+                    } else if row < cells(p99_ns) {
+                        '░'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect();
+            outln!(self, "  {:>11} │{}", label, columns.trim_end());
+        }
+        let end = buckets[buckets.len() - 1].start + width;
+        let end_label = if end >= Duration::from_secs(1) {
+            format!("{:.1}s", end.as_secs_f64())
+        } else {
+            format!("{:.1} ms", end.as_secs_f64() * 1000.0)
+        };
+        outln!(self, "  {:>11} └{}", "", "─".repeat(buckets.len()));
+        outln!(
+            self,
+            "  {:>11}  {:<start_width$}{}",
+            "",
+            "0",
+            end_label,
+            start_width = buckets.len().saturating_sub(end_label.len())
+        );
+        outln!(self);
+
+        debug!(
+            buckets = buckets.len(),
+            width_ns = width.as_nanos() as u64,
+            "Latency over time printed"
+        );
+        Ok(())
+    }
+
+    /// Print the slowest probes of a run with their context (see
+    /// [`crate::client::drilldown`])
+    ///
+    /// `elapsed` is the length of the measurement phase, to place each probe in the run.
+    pub fn print_slowest_probes(&self, probes: &[SlowProbe], elapsed: Duration) -> Result<()> {
+        if probes.is_empty() {
+            return Ok(());
+        }
+        outln!(self);
+        outln!(
+            self,
+            "Slowest Probes (send time as Unix epoch, gap since the previous send):"
+        );
+        outln!(self);
+        for (rank, probe) in probes.iter().enumerate() {
+            let sent_secs = probe
+                .sent_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let offset_secs = probe.offset.as_secs_f64();
+            let position = if elapsed.is_zero() {
+                String::new()
+            } else {
+                format!(
+                    ", {:.0}% into the run",
+                    offset_secs / elapsed.as_secs_f64() * 100.0
+                )
+            };
+            let gap = probe.send_gap.map_or("-".to_string(), |gap| {
+                format!("{:.1} µs", gap.as_nanos() as f64 / 1000.0)
+            });
+            outln!(
+                self,
+                "  {:>3}.  seq {:>9}  {:>10.1} µs  sent {:.6} (+{:.6}s{})  gap {}",
+                rank + 1,
+                probe.sequence,
+                probe.latency_ns as f64 / 1000.0,
+                sent_secs,
+                offset_secs,
+                position,
+                gap
+            );
+        }
+
+        debug!(count = probes.len(), "Slowest probes printed");
+        Ok(())
+    }
+
+    /// Ranges of the latency distribution of a run spanning `min_ns` to `max_ns`
+    fn buckets(&self, min_ns: u64, max_ns: u64) -> Buckets {
+        self.buckets
+            .as_ref()
+            .map_or_else(Buckets::default, |edges| edges.buckets(min_ns, max_ns))
+    }
+
+    /// Slowest samples of every bucket as `(latency, sequence, send offset)`, slowest first
+    ///
+    /// One entry per range of `buckets`, followed by the outliers.
+    fn slowest_per_bucket(
+        buckets: &Buckets,
+        latencies: &[u64],
+        sequences: &[u64],
+        send_offsets: &[u64],
+    ) -> Vec<Vec<(u64, u64, u64)>> {
+        let mut examples: Vec<Vec<(u64, u64, u64)>> = vec![Vec::new(); buckets.len() + 1];
+        for ((&latency_ns, &sequence), &offset_ns) in
+            latencies.iter().zip(sequences).zip(send_offsets)
+        {
+            let bucket = buckets.index(latency_ns).unwrap_or(buckets.len());
+            let slowest = &mut examples[bucket];
+            if slowest.len() < EXAMPLES_PER_BUCKET || latency_ns > slowest[slowest.len() - 1].0 {
+                let at = slowest.partition_point(|&(l, _, _)| l >= latency_ns);
+                slowest.insert(at, (latency_ns, sequence, offset_ns));
+                slowest.truncate(EXAMPLES_PER_BUCKET);
+            }
+        }
+        examples
+    }
+
+    /// Print bucket distribution derived from the latency histogram
+    pub fn print_histogram_distribution(
+        &self,
+        stats: &Statistics,
+        total_packets: usize,
+    ) -> Result<()> {
+        self.print_distribution(
+            LATENCY_DISTRIBUTION_TITLE,
+            stats.recorded_values().into_iter(),
+            total_packets,
+        );
+        Ok(())
+    }
+
+    /// Print bucket distribution of `(latency_ns, count)` pairs under `title`
+    fn print_distribution(
+        &self,
+        title: &str,
+        values: impl Iterator<Item = (u64, u64)>,
+        total_packets: usize,
+    ) {
+        outln!(self, "{}", title);
+        outln!(self);
+
+        let values: Vec<(u64, u64)> = values.collect();
+        let min_latency = values.iter().map(|&(l, _)| l).min().unwrap_or_default();
+        let max_latency = values.iter().map(|&(l, _)| l).max().unwrap_or_default();
+        let buckets = self.buckets(min_latency, max_latency);
+
+        // Count packets in each bucket
+        let mut bucket_counts = vec![0usize; buckets.len()];
+        let mut outliers = 0usize;
+
+        for (latency_ns, count) in values {
+            let count = count as usize;
+            match buckets.index(latency_ns) {
+                Some(i) => bucket_counts[i] += count,
+                None => outliers += count,
+            }
+        }
+
+        // Calculate percentages first to find max percentage for bar scaling
+        let mut percentages = Vec::new();
+        for count in &bucket_counts {
+            let percentage = (*count as f64 / total_packets as f64) * 100.0;
+            percentages.push(percentage);
+        }
+        let max_percentage = percentages.iter().fold(0.0f64, |a, &b| a.max(b));
+
+        // Print each bucket
+        for (i, bucket) in buckets.buckets.iter().enumerate() {
+            let count = bucket_counts[i];
+            if count == 0 && i >= buckets.hide_empty_from {
+                continue; // Skip empty slow buckets for cleaner output
+            }
+            let label = bucket.label.as_str();
+
+            let percentage = percentages[i];
+            // Scale bars based on percentage, not count, to match displayed percentages
+            let bar =
+                Self::render_bar_from_percentage(percentage, max_percentage, HISTOGRAM_BAR_WIDTH);
+            let label_colored = self.colorize_label(label, percentage);
+            let pct_str = Self::format_percentage(percentage);
+
+            outln!(
+                self,
+                "  {}:  {:30} {} ({:7} packets)",
+                label_colored,
+                bar,
+                pct_str,
+                self.format_count(count)
+            );
+        }
+
+        // Print outliers if any
+        if outliers > 0 {
+            let percentage = (outliers as f64 / total_packets as f64) * 100.0;
+            let max_ms = max_latency as f64 / 1_000_000.0;
+            let outlier_bar =
+                Self::render_bar_from_percentage(percentage, max_percentage, HISTOGRAM_BAR_WIDTH);
+            let pct_str = Self::format_percentage(percentage);
+
+            // Pad outlier label to match bucket label width
+            let outlier_label = format!("{:>width$}", buckets.outlier_label, width = LABEL_WIDTH);
+            let outlier_label_colored = self.theme.bad(&outlier_label).bold();
+
+            outln!(
+                self,
+                "  {}:  {:30} {} ({:7} packets) ← MAX: {:.1}ms",
+                outlier_label_colored,
+                outlier_bar,
+                pct_str,
+                self.format_count(outliers),
+                max_ms
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::confidence::CiMetric;
+    use crate::client::energy::ZoneEnergy;
+    use crate::client::environment::CpuQuota;
+    use crate::client::pacing::TrafficModel;
+    use crate::client::record::RunRecord;
+    use crate::client::retry::{self, RetryPolicy};
+    use crate::client::sla::Sla;
+    use crate::client::units::UnitSystem;
+
+    #[test]
+    fn test_reporter_print_results_empty() -> Result<()> {
+        let reporter = Reporter::default();
+        let stats = Statistics::new(&[])?;
+
+        // Should handle empty latencies gracefully
+        reporter.print_results(&stats, 0, 10, Duration::from_secs(1), &[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_results_with_data() -> Result<()> {
+        let reporter = Reporter::default();
+        let latencies = vec![1000, 2000, 3000, 4000, 5000];
+        let stats = Statistics::new(&latencies)?;
+
+        reporter.print_results(&stats, 0, 5, Duration::from_secs(1), &latencies)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_results_with_percentiles() -> Result<()> {
+        let latencies: Vec<u64> = (1..=10_000).map(|i| i * 100).collect();
+        let stats = Statistics::new(&latencies)?;
+        let elapsed = Duration::from_secs(1);
+
+        // Tail percentiles, then the median alone
+        Reporter::default()
+            .with_percentiles(&[50.0, 99.99, 99.999])
+            .print_results(&stats, 0, latencies.len(), elapsed, &latencies)?;
+        Reporter::default()
+            .with_percentiles(&[50.0])
+            .print_results(&stats, 0, latencies.len(), elapsed, &latencies)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_results_with_verdict() -> Result<()> {
+        use crate::client::verdict::FailMetric;
+
+        let latencies = vec![100_000, 100_000, 100_000, 4_000_000];
+        let stats = Statistics::new(&latencies)?;
+        let reporter = Reporter::default().with_verdict(Verdict {
+            metric: FailMetric::Max,
+            threshold_ns: 2_000_000,
+        });
+        reporter.print_results(&stats, 0, 4, Duration::from_secs(1), &latencies)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_bucket_distribution() -> Result<()> {
+        let reporter = Reporter::default();
+        let latencies = vec![
+            10000,  // 10 µs
+            20000,  // 20 µs
+            50000,  // 50 µs
+            100000, // 100 µs
+            500000, // 500 µs
+        ];
+
+        reporter.print_bucket_distribution(&latencies, 5)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_bucket_distribution_custom() -> Result<()> {
+        // An internet path, which the LAN ranges would lump into 1-10 ms and beyond
+        let latencies = vec![8_000_000, 12_000_000, 35_000_000, 90_000_000, 240_000_000];
+        let edges: BucketEdges = "5ms,10ms,20ms,50ms,100ms".parse().unwrap();
+        Reporter::default()
+            .with_buckets(Some(edges))
+            .print_bucket_distribution(&latencies, 5)?;
+        Reporter::default()
+            .with_buckets(Some(BucketEdges::Auto))
+            .print_bucket_distribution(&latencies, 5)?;
+        Reporter::default()
+            .with_buckets(Some(BucketEdges::Auto))
+            .print_bucket_distribution(&[], 0)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_results_from_histogram() -> Result<()> {
+        let reporter = Reporter::default();
+        let stats = Statistics::new(&[1000, 25_000, 150_000, 20_000_000])?;
+
+        reporter.print_results_from_histogram(&stats, 1, 5, Duration::from_secs(1))?;
+        reporter.print_results_from_histogram(&Statistics::empty()?, 0, 0, Duration::ZERO)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_format_count() {
+        let reporter = Reporter::default();
+        assert_eq!(reporter.format_count(100), "    100");
+        assert_eq!(reporter.format_count(1000), "  1,000");
+        assert_eq!(reporter.format_count(1999), "  1,999");
+
+        let reporter = Reporter::new(NumberFormat {
+            units: UnitSystem::Si,
+            ..NumberFormat::default()
+        });
+        assert_eq!(reporter.format_count(1999), "   2.0k");
+        assert_eq!(reporter.format_count(12_500), "  12.5k");
+    }
+
+    #[test]
+    fn test_reporter_print_send_path() -> Result<()> {
+        let reporter = Reporter::default();
+        reporter.print_send_path(&[])?;
+        reporter.print_send_path(&[2_000, 3_000, 4_000])?;
+        reporter.print_send_path(&[2_000, 3_000, SEND_STALL_THRESHOLD_NS * 5])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_rtt_breakdown() -> Result<()> {
+        let reporter = Reporter::default();
+        let send = Statistics::new(&[1_000, 2_000, 3_000])?;
+        let recv_wait = Statistics::new(&[10_000, 20_000, 30_000])?;
+        reporter.print_rtt_breakdown(&send, &recv_wait)?;
+        reporter.print_rtt_breakdown(&Statistics::empty()?, &recv_wait)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_impair_sweep() -> Result<()> {
+        let reporter = Reporter::default();
+        let results = vec![
+            SweepResult {
+                profile: None,
+                p50_ns: 10_000,
+                p99_ns: 20_000,
+                loss_pct: 0.0,
+            },
+            SweepResult {
+                profile: Some("5:1".parse().unwrap()),
+                p50_ns: 10_010_000,
+                p99_ns: 12_000_000,
+                loss_pct: 0.5,
+            },
+        ];
+        reporter.print_impair_sweep(&results, 2, Transport::Tcp)?;
+        reporter.print_impair_sweep(&results, 2, Transport::Udp)?;
+        reporter.print_impair_sweep(&[], 2, Transport::Tcp)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_connections() -> Result<()> {
+        let reporter = Reporter::default();
+        let connection = |index, p99_ns| ConnectionStats {
+            index,
+            local: format!("127.0.0.1:{}", 40000 + index).parse().ok(),
+            sent: 1000,
+            lost: index,
+            min_ns: 10_000,
+            mean_ns: 15_000.0,
+            p50_ns: 14_000,
+            p99_ns,
+        };
+        // Even, then one connection far behind the others
+        reporter.print_connections(&[connection(0, 30_000), connection(1, 32_000)])?;
+        reporter.print_connections(&[connection(0, 30_000), connection(1, 95_000)])?;
+        reporter.print_connections(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_flows() -> Result<()> {
+        let reporter = Reporter::default();
+        let flow = |index, p99_ns| ConnectionStats {
+            index,
+            local: format!("127.0.0.1:{}", 50000 + index).parse().ok(),
+            sent: 500,
+            lost: 0,
+            min_ns: 10_000,
+            mean_ns: 15_000.0,
+            p50_ns: 14_000,
+            p99_ns,
+        };
+        reporter.print_flows(&[flow(0, 30_000), flow(1, 31_000), flow(2, 120_000)])?;
+        // One bad path among eight
+        let eight: Vec<_> = (0..8)
+            .map(|index| flow(index, if index == 5 { 90_000 } else { 30_000 }))
+            .collect();
+        reporter.print_flows(&eight)?;
+        reporter.print_flows(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_confidence() -> Result<()> {
+        let reporter = Reporter::default();
+        let latencies: Vec<u64> = (0..5_000).map(|i| 40_000 + (i % 100) * 100).collect();
+        let stats = Statistics::new(&latencies)?;
+        let target = CiTarget {
+            metric: CiMetric::Mean,
+            half_width_pct: 1.0,
+        };
+        reporter.print_confidence(&stats, target, 5_000, 100_000)?;
+        reporter.print_confidence(&stats, target, 5_000, 5_000)?;
+        let target = CiTarget {
+            metric: CiMetric::P99,
+            half_width_pct: 0.01,
+        };
+        reporter.print_confidence(&stats, target, 5_000, 5_000)?;
+        reporter.print_confidence(&Statistics::new(&latencies[..10])?, target, 10, 10)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_cpu_throttling() -> Result<()> {
+        let quota = CpuQuota {
+            quota: Duration::from_millis(50),
+            period: Duration::from_millis(100),
+        };
+        let mut throttling = CpuThrottling {
+            quota,
+            periods: 120,
+            throttled_periods: 18,
+            throttled: Duration::from_millis(640),
+        };
+        Reporter::default().print_cpu_throttling(&throttling)?;
+        throttling.throttled_periods = 0;
+        throttling.throttled = Duration::ZERO;
+        Reporter::default().print_cpu_throttling(&throttling)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_retry_outcome() -> Result<()> {
+        let mut probes: Vec<(u64, Option<u64>)> =
+            (0..1_000u64).map(|i| (i, Some(40_000 + i * 10))).collect();
+        for lost in [10, 11, 500, 501, 502] {
+            probes[lost].1 = None;
+        }
+        let latencies: Vec<u64> = probes.iter().filter_map(|&(_, rtt)| rtt).collect();
+        let stats = Statistics::new(&latencies)?;
+        let outcome = retry::replay(RetryPolicy::default(), probes, 42);
+        assert_eq!(outcome.failed, 1);
+        Reporter::default().print_retry_outcome(&outcome, &stats)?;
+        let none = retry::replay(RetryPolicy::default(), [], 42);
+        Reporter::default().print_retry_outcome(&none, &stats)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_energy() -> Result<()> {
+        let energy = EnergyReport {
+            zones: vec![
+                ZoneEnergy {
+                    zone: "package-0".into(),
+                    joules: 412.5,
+                },
+                ZoneEnergy {
+                    zone: "package-0/dram".into(),
+                    joules: 38.2,
+                },
+            ],
+            elapsed: Duration::from_secs(30),
+        };
+        Reporter::default().print_energy(&energy, Some(400.0))?;
+        Reporter::default().print_energy(&energy, None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_caveats() -> Result<()> {
+        let quota = CpuQuota {
+            quota: Duration::from_millis(50),
+            period: Duration::from_millis(100),
+        };
+        Reporter::default().print_caveats(&[
+            Caveat::VirtualMachine {
+                hypervisor: "KVM".into(),
+            },
+            Caveat::CpuQuota { quota },
+        ])?;
+        Reporter::default().print_caveats(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_baseline() -> Result<()> {
+        let reporter = Reporter::default();
+        let outcome = |p99_ns| BaselineOutcome {
+            baseline: crate::client::baseline::Baseline {
+                runs: 12,
+                samples: 120_000,
+                p50_ns: 30_000,
+                p99_ns: 80_000,
+            },
+            p99_ns,
+            max_change_pct: 20.0,
+        };
+        reporter.print_baseline(Some(&outcome(84_000)), 24)?;
+        reporter.print_baseline(Some(&outcome(140_000)), 24)?;
+        reporter.print_baseline(None, 6)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_reflectors() -> Result<()> {
+        let reporter = Reporter::default();
+        let reflector = |index: usize, lost, outages, failed_over| ReflectorStats {
+            reflector: format!("10.0.{}.1:8080", index).parse().unwrap(),
+            stats: ConnectionStats {
+                index,
+                local: None,
+                sent: 500,
+                lost,
+                min_ns: 10_000,
+                mean_ns: 15_000.0,
+                p50_ns: 14_000,
+                p99_ns: 30_000,
+            },
+            outages,
+            failed_over,
+        };
+        reporter.print_reflectors(&[reflector(0, 0, 0, 0), reflector(1, 2, 0, 0)], &[1.0, 1.0])?;
+        reporter.print_reflectors(
+            &[
+                reflector(0, 0, 0, 0),
+                reflector(1, 40, 2, 310),
+                reflector(2, 500, 1, 4_500),
+            ],
+            &[5.0, 2.0, 0.5],
+        )?;
+        reporter.print_reflectors(&[], &[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_depth_sweep() -> Result<()> {
+        let reporter = Reporter::default();
+        let results: Vec<DepthResult> = [
+            (1, 20_000, 80_000.0),
+            (2, 35_000, 90_000.0),
+            (4, 70_000, 95_000.0),
+        ]
+        .into_iter()
+        .map(|(depth, p50_ns, throughput_pps)| DepthResult {
+            depth,
+            p50_ns,
+            p99_ns: p50_ns * 2,
+            throughput_pps,
+        })
+        .collect();
+        reporter.print_depth_sweep(&results)?;
+        reporter.print_depth_sweep(&results[..1])?;
+        reporter.print_depth_sweep(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_port_sweep() -> Result<()> {
+        use crate::client::output::RunSummary;
+
+        let reporter = Reporter::default();
+        let fast = Statistics::new(&[40_000, 42_000, 45_000])?;
+        let slow = Statistics::new(&[40_000, 90_000, 200_000])?;
+        let results = vec![
+            PortResult {
+                port: 8080,
+                outcome: Ok(RunSummary::new(&fast, 0, 3, &[])),
+            },
+            PortResult {
+                port: 8081,
+                outcome: Ok(RunSummary::new(&slow, 1, 4, &[])),
+            },
+            PortResult {
+                port: 8082,
+                outcome: Err("Connection refused".into()),
+            },
+        ];
+        reporter.print_port_sweep(&results)?;
+        reporter.print_port_sweep(&results[..1])?;
+        reporter.print_port_sweep(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_endpoints() -> Result<()> {
+        use crate::client::output::RunSummary;
+
+        let reporter = Reporter::default();
+        let fast = Statistics::new(&[40_000, 42_000, 45_000])?;
+        let slow = Statistics::new(&[40_000, 90_000, 200_000])?;
+        let results = vec![
+            EndpointResult {
+                address: "192.0.2.1:8080".parse().unwrap(),
+                outcome: Ok(RunSummary::new(&fast, 0, 3, &[])),
+            },
+            EndpointResult {
+                address: "[2001:db8::2]:8080".parse().unwrap(),
+                outcome: Ok(RunSummary::new(&slow, 0, 3, &[])),
+            },
+            EndpointResult {
+                address: "192.0.2.3:8080".parse().unwrap(),
+                outcome: Err("Connection refused".into()),
+            },
+        ];
+        reporter.print_endpoints(&results)?;
+        reporter.print_endpoints(&results[..1])?;
+        reporter.print_endpoints(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_family_comparison() -> Result<()> {
+        use crate::client::families::Family;
+        use crate::client::output::RunSummary;
+
+        let reporter = Reporter::default();
+        let fast = Statistics::new(&[40_000, 42_000, 45_000])?;
+        let slow = Statistics::new(&[40_000, 90_000, 200_000])?;
+        let mut results = vec![
+            FamilyResult {
+                family: Family::V4,
+                address: "192.0.2.1:8080".parse().ok(),
+                outcome: Ok(RunSummary::new(&fast, 0, 3, &[])),
+            },
+            FamilyResult {
+                family: Family::V6,
+                address: "[2001:db8::1]:8080".parse().ok(),
+                outcome: Ok(RunSummary::new(&slow, 1, 4, &[])),
+            },
+        ];
+        reporter.print_family_comparison(&results)?;
+        results[1].address = None;
+        results[1].outcome = Err("no AAAA record".into());
+        reporter.print_family_comparison(&results)?;
+        reporter.print_family_comparison(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_matrix() -> Result<()> {
+        use crate::client::output::RunSummary;
+
+        let stats = Statistics::new(&[1000, 2000, 3000])?;
+        let rows = vec![
+            MatrixRow {
+                combination: vec![("packets".into(), "3".into())],
+                outcome: Ok(RunSummary::new(&stats, 0, 3, &[])),
+                record_path: None,
+            },
+            MatrixRow {
+                combination: vec![("packets".into(), "4".into())],
+                outcome: Err("connection refused".into()),
+                record_path: None,
+            },
+        ];
+        Reporter::default().print_matrix(&rows)?;
+        Reporter::default().print_matrix(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_system_events() -> Result<()> {
+        use crate::client::sysmon::SystemSample;
+
+        let stats = Statistics::new(&[10_000, 20_000, 30_000])?;
+        let quiet = SystemTimeline {
+            samples: Vec::new(),
+            cpus: 4,
+        };
+        Reporter::default().print_system_events(&quiet, &stats)?;
+
+        let noisy = SystemTimeline {
+            samples: vec![SystemSample {
+                offset: Duration::from_millis(250),
+                loadavg: 0.1,
+                voluntary_switches: 10,
+                involuntary_switches: 2,
+                migrations: Some(1),
+                max_latency_ns: 900_000,
+            }],
+            cpus: 4,
+        };
+        Reporter::default().print_system_events(&noisy, &stats)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_thread_usage() -> Result<()> {
+        let usage = ThreadUsage {
+            user: Duration::from_millis(300),
+            system: Duration::from_millis(200),
+            voluntary_switches: 1000,
+            involuntary_switches: 50,
+        };
+        Reporter::default().print_thread_usage(&usage, Duration::from_secs(1), 1000)?;
+        Reporter::default().print_thread_usage(&usage, Duration::from_millis(500), 1000)?;
+        Reporter::default().print_thread_usage(&ThreadUsage::default(), Duration::ZERO, 0)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_affinity_warnings() -> Result<()> {
+        let warnings = vec![
+            AffinityWarning::SharedCore {
+                interface: "eth0".into(),
+                cpus: vec![2],
+            },
+            AffinityWarning::RemoteNumaNode {
+                interface: "eth0".into(),
+                nic_node: 0,
+                thread_nodes: vec![1],
+            },
+        ];
+        Reporter::default().print_affinity_warnings(&warnings)?;
+        Reporter::default().print_affinity_warnings(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_numa_locality() -> Result<()> {
+        let affinity = IrqAffinity {
+            interface: "eth0".into(),
+            rx_irqs: vec![40, 41],
+            irq_cpus: [0, 1].into(),
+            nic_numa_node: Some(0),
+            thread_cpus: [0, 1, 2, 3].into(),
+            online_cpus: [0, 1, 2, 3].into(),
+            cpu_nodes: Default::default(),
+        };
+        Reporter::default().print_numa_locality(None, Some(&affinity))?;
+        Reporter::default().print_numa_locality(None, None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_dscp() -> Result<()> {
+        let ef = Dscp::new(46).unwrap();
+        let kept = BTreeMap::from([(ef, 1000)]);
+        let remarked = BTreeMap::from([(ef, 900), (Dscp::new(0).unwrap(), 100)]);
+        Reporter::default().print_dscp(ef, &kept, Transport::Udp)?;
+        Reporter::default().print_dscp(ef, &remarked, Transport::Udp)?;
+        Reporter::default().print_dscp(ef, &BTreeMap::new(), Transport::Tcp)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_pacing() -> Result<()> {
+        let on_time = PacingStats {
+            rate: 1000,
+            traffic: TrafficModel::Fixed,
+            late_sends: 0,
+        };
+        let late = PacingStats {
+            rate: 100_000,
+            traffic: TrafficModel::Poisson,
+            late_sends: 800,
+        };
+        Reporter::default().print_pacing(&on_time, Duration::from_secs(1), 1000)?;
+        Reporter::default().print_pacing(&late, Duration::from_millis(50), 1000)?;
+        Reporter::default().print_pacing(&on_time, Duration::ZERO, 0)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_idle_split() -> Result<()> {
+        let split = |warm: Vec<u64>, cold: Vec<u64>| IdleSplit {
+            threshold: Duration::from_millis(2),
+            warm,
+            cold,
+        };
+        // Cold path slower, no penalty, and one side too small to compare
+        Reporter::default().print_idle_split(&split(vec![20_000; 50], vec![45_000; 20]))?;
+        Reporter::default().print_idle_split(&split(vec![20_000; 50], vec![21_000; 20]))?;
+        Reporter::default().print_idle_split(&split(vec![20_000; 50], Vec::new()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_sample_backing() -> Result<()> {
+        Reporter::default().print_sample_backing(PageBacking::HugeTlb)?;
+        Reporter::default().print_sample_backing(PageBacking::Transparent)?;
+        Reporter::default().print_sample_backing(PageBacking::Regular)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_loss_pattern() -> Result<()> {
+        let periods = vec![LossPeriod {
+            packets: 1024,
+            score: 0.9,
+        }];
+        Reporter::default().print_loss_pattern(&periods, Duration::from_micros(30))?;
+        Reporter::default().print_loss_pattern(&[], Duration::from_micros(30))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_spike_periods() -> Result<()> {
+        let periods = vec![
+            SpikePeriod {
+                interval: Duration::from_millis(4),
+                score: 0.9,
+            },
+            SpikePeriod {
+                interval: Duration::from_millis(7),
+                score: 0.6,
+            },
+        ];
+        Reporter::default().print_spike_periods(&periods)?;
+        Reporter::default().print_spike_periods(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_latency_over_time() -> Result<()> {
+        let latencies = [50_000, 60_000, 2_000_000, 55_000];
+        let send_offsets = [0, 400_000_000, 1_200_000_000, 3_100_000_000];
+        let buckets = crate::client::trend::latency_over_time(
+            &latencies,
+            &send_offsets,
+            Duration::from_secs(4),
+            4,
+        );
+        Reporter::default().print_latency_over_time(&buckets)?;
+        Reporter::default()
+            .with_plain(true)
+            .print_latency_over_time(&buckets)?;
+        Reporter::default().print_latency_over_time(&buckets[..1])?;
+        Reporter::default().print_latency_over_time(&[])?;
+
+        assert_eq!(Reporter::format_slice_width(Duration::from_secs(2)), "2s");
+        assert_eq!(
+            Reporter::format_slice_width(Duration::from_millis(5)),
+            "5 ms"
+        );
+        assert_eq!(
+            Reporter::format_slice_width(Duration::from_micros(200)),
+            "200 µs"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_slowest_probes() -> Result<()> {
+        let probes = crate::client::drilldown::slowest(
+            [
+                (0, 0, Some(40_000)),
+                (1, 100_000, Some(900_000)),
+                (3, 200_000, Some(60_000)),
+            ],
+            SystemTime::now(),
+            2,
+        );
+        Reporter::default().print_slowest_probes(&probes, Duration::from_millis(1))?;
+        Reporter::default().print_slowest_probes(&probes, Duration::ZERO)?;
+        Reporter::default().print_slowest_probes(&[], Duration::ZERO)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_bucket_examples() -> Result<()> {
+        let latencies = vec![10_000, 15_000, 12_000, 11_000, 50_000, 25_000_000];
+        let sequences: Vec<u64> = (0..latencies.len() as u64).collect();
+        let send_offsets: Vec<u64> = sequences.iter().map(|s| s * 1_000_000).collect();
+
+        let buckets = Buckets::default();
+        let examples =
+            Reporter::slowest_per_bucket(&buckets, &latencies, &sequences, &send_offsets);
+        assert_eq!(
+            examples[0],
+            vec![
+                (15_000, 1, 1_000_000),
+                (12_000, 2, 2_000_000),
+                (11_000, 3, 3_000_000)
+            ]
+        );
+        assert_eq!(examples[2], vec![(50_000, 4, 4_000_000)]);
+        assert_eq!(examples[buckets.len()], vec![(25_000_000, 5, 5_000_000)]);
+
+        Reporter::default().print_bucket_examples(
+            &latencies,
+            &sequences,
+            &send_offsets,
+            SystemTime::now(),
+        )?;
+        Reporter::default().print_bucket_examples(&[], &[], &[], UNIX_EPOCH)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_capture() -> Result<()> {
+        let stats = CaptureStats {
+            written: 2_004,
+            dropped: 3,
+        };
+        Reporter::default().print_capture(Path::new("run.pcap"), &stats)?;
+        Reporter::default().print_capture(Path::new("run.pcap"), &CaptureStats::default())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_wire_timings() -> Result<()> {
+        let timing = |sequence, wire_out_ns, wire_in_ns| WireTiming {
+            sequence,
+            app_sent_ns: sequence * 100_000,
+            app_rtt_ns: Some(50_000),
+            wire_out_ns,
+            wire_in_ns,
+        };
+        let timings = vec![
+            timing(0, Some(2_000), Some(40_000)),
+            timing(1, Some(105_000), Some(120_000)),
+            timing(2, Some(200_500), None),
+            timing(3, None, None),
+        ];
+        Reporter::default().print_wire_timings(&timings)?;
+        Reporter::default().print_wire_timings(&timings[2..])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_slo_report() -> Result<()> {
+        let latencies = vec![50_000; 999];
+        let outcomes = vec![
+            SloOutcome::evaluate("99%<100us".parse().unwrap(), &latencies, 1),
+            SloOutcome::evaluate("99.95%<10us".parse().unwrap(), &latencies, 1),
+        ];
+        Reporter::default().print_slo_report(&outcomes, Duration::from_secs(600))?;
+        Reporter::default().print_slo_report(&outcomes, Duration::from_millis(800))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_comparison() -> Result<()> {
+        let record = |latencies: &[u64], lost_packets| -> Result<RunRecord> {
+            let stats = Statistics::new(latencies)?;
+            let sent = latencies.len() + lost_packets;
+            Ok(RunRecord::new(
+                "127.0.0.1:8080",
+                &stats,
+                sent,
+                sent,
+                lost_packets,
+                Duration::from_secs(1),
+                true,
+            ))
+        };
+        let baseline = record(&[80_000; 100], 0)?;
+        let current = record(&[140_000; 100], 2)?;
+        let names = ("baseline.json", "current.json");
+        let comparison = Comparison::new(&baseline, &current, &[50.0, 99.0], None)?;
+        Reporter::default().print_comparison(&comparison, 10.0, names)?;
+        let comparison = Comparison::new(&current, &baseline, &[50.0, 99.0], None)?;
+        Reporter::default().print_comparison(&comparison, 10.0, names)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_sla_report() -> Result<()> {
+        let stats = Statistics::new(&[50_000; 1000])?;
+        let sla: Sla = "p99<100us,mean<10us,loss<0.1%".parse().unwrap();
+        Reporter::default().print_sla_report(&sla.evaluate(&stats, 0, 1000))?;
+        Reporter::default().print_sla_report(&sla.evaluate(&stats, 5, 1005))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_path_breakdown() -> Result<()> {
+        let timing = |outbound_ns, return_ns| PathTiming {
+            outbound_ns,
+            server_ns: 2_000,
+            return_ns,
+            server_processing_ns: None,
+        };
+        Reporter::default()
+            .print_path_breakdown(&[timing(10_000, 12_000), timing(11_000, 9_000)], None)?;
+        Reporter::default().print_path_breakdown(&[timing(-5_000, 30_000)], None)?;
+        Reporter::default().print_path_breakdown(&[], None)?;
+        let clock = ClockOffset {
+            offset_ns: -250_000,
+            uncertainty_ns: 4_000,
+            samples: 32,
+        };
+        Reporter::default().print_path_breakdown(&[timing(9_000, 8_000)], Some(&clock))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_latency_decomposition() -> Result<()> {
+        let kernel = [KernelTiming {
+            rtt_ns: 45_000,
+            kernel_rtt_ns: 33_000,
+        }];
+        let path = [PathTiming {
+            outbound_ns: 15_000,
+            server_ns: 1_000,
+            return_ns: 15_000,
+            server_processing_ns: Some(4_000),
+        }];
+        let decomposition = Decomposition::new(45_000.0, &[4_000], &kernel, &path).unwrap();
+        Reporter::default().print_latency_decomposition(&decomposition)?;
+        let decomposition = Decomposition::new(45_000.0, &[4_000], &[], &path).unwrap();
+        Reporter::default().print_latency_decomposition(&decomposition)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_server_processing() -> Result<()> {
+        let timings: Vec<PathTiming> = (0..100)
+            .map(|i| PathTiming {
+                outbound_ns: 10_000,
+                server_ns: 2_000,
+                return_ns: 10_000,
+                server_processing_ns: (i > 0).then_some(3_000 + i * 10),
+            })
+            .collect();
+        let stats = Statistics::new(&[25_000, 26_000, 40_000])?;
+        Reporter::default().print_server_processing(&timings, &stats)?;
+        // An older server does not report it
+        Reporter::default().print_server_processing(&timings[..1], &stats)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_kernel_breakdown() -> Result<()> {
+        let timings = [
+            KernelTiming {
+                rtt_ns: 30_000,
+                kernel_rtt_ns: 22_000,
+            },
+            KernelTiming {
+                rtt_ns: 45_000,
+                kernel_rtt_ns: 24_000,
+            },
+        ];
+        Reporter::default().print_kernel_breakdown(&timings)?;
+        Reporter::default().print_kernel_breakdown(&[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_print_report_bundle() -> Result<()> {
+        use crate::client::build_info::BuildInfo;
+        use crate::client::bundle::BundleFile;
+
+        let manifest = BundleManifest {
+            synapse_version: "0.1.0".to_string(),
+            build: BuildInfo::current(),
+            tags: Default::default(),
+            created_at_unix_ms: 0,
+            server: "127.0.0.1:8080".to_string(),
+            seed: 1,
+            files: vec![BundleFile {
+                name: "summary.json".to_string(),
+                format: "json".to_string(),
+                description: "Key figures of the run".to_string(),
+                bytes: 312,
+            }],
+        };
+        Reporter::default().print_report_bundle(Path::new("results"), &manifest)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_with_theme() -> Result<()> {
+        let stats = Statistics::new(&[1000, 25_000, 150_000, 20_000_000])?;
+        for theme in [Theme::Colorblind, Theme::Mono] {
+            let reporter = Reporter::default().with_theme(theme);
+            reporter.print_results_from_histogram(&stats, 1, 5, Duration::from_secs(1))?;
+            reporter.print_results_from_histogram(&Statistics::empty()?, 0, 0, Duration::ZERO)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reporter_with_plain() -> Result<()> {
+        let reporter = Reporter::default().with_plain(true);
+        let latencies = vec![1000, 2000, 3000, 4000, 5000];
+        let stats = Statistics::new(&latencies)?;
+
+        reporter.print_results(&stats, 0, 5, Duration::from_secs(1), &latencies)?;
+        Ok(())
     }
 }
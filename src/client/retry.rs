@@ -0,0 +1,387 @@
+//! Completion times of requests retried with backoff
+//!
+//! Users do not see raw RTT. An application that gets no answer within its timeout
+//! sends the request again after a backoff, and the user waits for the whole sequence:
+//! a 1% loss rate turns into a 1% chance of waiting a timeout plus a backoff on top of
+//! the RTT. `--retry-policy` replays the probes of a run through such a policy and
+//! reports the completion time of the requests it makes of them.
+//!
+//! The replay walks the probes in send order. Each request takes the next probe as its
+//! first attempt; an attempt answered within the timeout completes the request, while a
+//! lost or late one costs the timeout and, if attempts remain, the backoff before the
+//! next probe is taken as the retry. Bursts of loss therefore hit the retries the way
+//! they would have hit the application, although the retries were in fact sent at the
+//! run's pace rather than after the backoff.
+//!
+//! The policy is written as comma-separated `key=value` pairs, any of them left out
+//! taking its default, e.g.
+//! `timeout=200ms,attempts=4,backoff=50ms,multiplier=2,max-backoff=1s,jitter=full`.
+
+use crate::client::rng::SeededRng;
+use crate::client::slo;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Time an attempt waits for its answer unless the policy says otherwise
+pub const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Attempts a request makes, the first included, unless the policy says otherwise
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+
+/// Backoff before the first retry unless the policy says otherwise
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Growth of the backoff from one retry to the next unless the policy says otherwise
+pub const DEFAULT_RETRY_MULTIPLIER: f64 = 2.0;
+
+/// Longest backoff unless the policy says otherwise
+pub const DEFAULT_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Most attempts a policy may make
+pub const MAX_RETRY_ATTEMPTS: u32 = 16;
+
+/// Randomization of the backoff, as in the AWS exponential backoff guidance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackoffJitter {
+    /// The backoff as computed
+    #[default]
+    None,
+    /// Uniform between 0 and the backoff
+    Full,
+    /// Half the backoff plus uniform up to the other half
+    Equal,
+}
+
+impl fmt::Display for BackoffJitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackoffJitter::None => write!(f, "none"),
+            BackoffJitter::Full => write!(f, "full"),
+            BackoffJitter::Equal => write!(f, "equal"),
+        }
+    }
+}
+
+/// Timeout and backoff of the client `--retry-policy` emulates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Time an attempt waits for its answer
+    pub timeout: Duration,
+    /// Attempts a request makes before it fails, the first included
+    pub attempts: u32,
+    /// Backoff before the first retry
+    pub backoff: Duration,
+    /// Growth of the backoff from one retry to the next
+    pub multiplier: f64,
+    /// Longest backoff
+    pub max_backoff: Duration,
+    pub jitter: BackoffJitter,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_RETRY_TIMEOUT,
+            attempts: DEFAULT_RETRY_ATTEMPTS,
+            backoff: DEFAULT_RETRY_BACKOFF,
+            multiplier: DEFAULT_RETRY_MULTIPLIER,
+            max_backoff: DEFAULT_RETRY_MAX_BACKOFF,
+            jitter: BackoffJitter::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retry `retry` (1 for the first), before jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let scale = self.multiplier.powi(retry.saturating_sub(1) as i32);
+        Duration::from_secs_f64(
+            (self.backoff.as_secs_f64() * scale).min(self.max_backoff.as_secs_f64()),
+        )
+    }
+
+    /// Backoff before retry `retry`, jittered with `rng`
+    fn jittered_backoff(&self, retry: u32, rng: &mut SeededRng) -> Duration {
+        let backoff = self.backoff(retry);
+        match self.jitter {
+            BackoffJitter::None => backoff,
+            BackoffJitter::Full => backoff.mul_f64(rng.next_f64()),
+            BackoffJitter::Equal => backoff / 2 + (backoff / 2).mul_f64(rng.next_f64()),
+        }
+    }
+}
+
+impl fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timeout={},attempts={},backoff={},multiplier={},max-backoff={},jitter={}",
+            slo::format_threshold(self.timeout),
+            self.attempts,
+            slo::format_threshold(self.backoff),
+            self.multiplier,
+            slo::format_threshold(self.max_backoff),
+            self.jitter
+        )
+    }
+}
+
+impl FromStr for RetryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut policy = Self::default();
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid retry policy '{}' (expected KEY=VALUE)", pair))?;
+            let value = value.trim();
+            match key.trim() {
+                "timeout" => policy.timeout = slo::parse_threshold(value)?,
+                "attempts" => {
+                    policy.attempts = value
+                        .parse()
+                        .ok()
+                        .filter(|attempts| (1..=MAX_RETRY_ATTEMPTS).contains(attempts))
+                        .ok_or_else(|| {
+                            format!(
+                                "invalid attempts '{}' (expected 1 to {})",
+                                value, MAX_RETRY_ATTEMPTS
+                            )
+                        })?
+                }
+                "backoff" => policy.backoff = slo::parse_threshold(value)?,
+                "multiplier" => {
+                    policy.multiplier = value
+                        .parse()
+                        .ok()
+                        .filter(|multiplier: &f64| *multiplier >= 1.0 && multiplier.is_finite())
+                        .ok_or_else(|| {
+                            format!("invalid multiplier '{}' (expected 1 or more)", value)
+                        })?
+                }
+                "max-backoff" | "max_backoff" => policy.max_backoff = slo::parse_threshold(value)?,
+                "jitter" => {
+                    policy.jitter = match value {
+                        "none" => BackoffJitter::None,
+                        "full" => BackoffJitter::Full,
+                        "equal" => BackoffJitter::Equal,
+                        _ => {
+                            return Err(format!(
+                                "unknown jitter '{}' (expected none, full or equal)",
+                                value
+                            ))
+                        }
+                    }
+                }
+                key => {
+                    return Err(format!(
+                        "unknown retry policy key '{}' (expected timeout, attempts, backoff, \
+                         multiplier, max-backoff or jitter)",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// Requests of a run replayed through a [`RetryPolicy`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryOutcome {
+    pub policy: RetryPolicy,
+    /// Completion time of every request that got an answer, in nanoseconds, in the
+    /// order the requests were made
+    pub completion_ns: Vec<u64>,
+    /// Number of requests that got an answer by attempt, the first attempt first
+    pub completed_by_attempt: Vec<u64>,
+    /// Requests that used up their attempts without an answer
+    pub failed: u64,
+    /// Probes the requests took, retries included
+    pub probes_used: u64,
+}
+
+impl RetryOutcome {
+    /// Requests made, completed or failed; one cut short by the end of the run is left
+    /// out
+    pub fn requests(&self) -> u64 {
+        self.completion_ns.len() as u64 + self.failed
+    }
+
+    /// Share of the requests that failed, in percent
+    pub fn failed_pct(&self) -> f64 {
+        self.failed as f64 / self.requests().max(1) as f64 * 100.0
+    }
+
+    /// Share of the requests that needed at least one retry, in percent
+    pub fn retried_pct(&self) -> f64 {
+        let first_time = self
+            .completed_by_attempt
+            .first()
+            .copied()
+            .unwrap_or_default();
+        (self.requests() - first_time) as f64 / self.requests().max(1) as f64 * 100.0
+    }
+
+    /// Attempts per request: the load the retries add to the server
+    pub fn amplification(&self) -> f64 {
+        self.probes_used as f64 / self.requests().max(1) as f64
+    }
+
+    /// Completion time at each of `quantiles` (nearest rank), in nanoseconds; empty if
+    /// no request completed
+    ///
+    /// Exact rather than from a histogram, as timeouts and backoffs take completion times
+    /// far past the range of the RTT histograms.
+    pub fn completion_percentiles(&self, quantiles: &[f64]) -> Vec<u64> {
+        let mut sorted = self.completion_ns.clone();
+        sorted.sort_unstable();
+        if sorted.is_empty() {
+            return Vec::new();
+        }
+        quantiles
+            .iter()
+            .map(|&quantile| {
+                let rank = (quantile * sorted.len() as f64).ceil() as usize;
+                sorted[rank.clamp(1, sorted.len()) - 1]
+            })
+            .collect()
+    }
+}
+
+/// Replay `probes`, the `(sequence, RTT)` of every probe of a run in any order with
+/// the RTT `None` for a lost probe, through `policy`, jittering backoffs from `seed`
+pub fn replay(
+    policy: RetryPolicy,
+    probes: impl IntoIterator<Item = (u64, Option<u64>)>,
+    seed: u64,
+) -> RetryOutcome {
+    let mut probes: Vec<(u64, Option<u64>)> = probes.into_iter().collect();
+    probes.sort_by_key(|&(sequence, _)| sequence);
+    let mut rng = SeededRng::stream(seed, "retry-backoff");
+    let timeout_ns = policy.timeout.as_nanos() as u64;
+
+    let mut outcome = RetryOutcome {
+        policy,
+        completion_ns: Vec::new(),
+        completed_by_attempt: vec![0; policy.attempts as usize],
+        failed: 0,
+        probes_used: 0,
+    };
+    let mut probes = probes.into_iter().map(|(_, rtt_ns)| rtt_ns).peekable();
+    while probes.peek().is_some() {
+        let mut waited_ns = 0u64;
+        let mut used = 0;
+        let mut finished = false;
+        for attempt in 1..=policy.attempts {
+            let Some(rtt_ns) = probes.next() else {
+                break;
+            };
+            used += 1;
+            match rtt_ns.filter(|&rtt_ns| rtt_ns <= timeout_ns) {
+                Some(rtt_ns) => {
+                    outcome.completion_ns.push(waited_ns + rtt_ns);
+                    outcome.completed_by_attempt[attempt as usize - 1] += 1;
+                    finished = true;
+                    break;
+                }
+                None if attempt < policy.attempts => {
+                    waited_ns +=
+                        timeout_ns + policy.jittered_backoff(attempt, &mut rng).as_nanos() as u64;
+                }
+                None => {
+                    outcome.failed += 1;
+                    finished = true;
+                }
+            }
+        }
+        // A request still retrying when the probes ran out has no outcome
+        if finished {
+            outcome.probes_used += used;
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_policy() {
+        let policy: RetryPolicy =
+            "timeout=100ms, attempts=4,backoff=20ms,max-backoff=50ms,jitter=full"
+                .parse()
+                .unwrap();
+        assert_eq!(policy.timeout, Duration::from_millis(100));
+        assert_eq!(policy.attempts, 4);
+        assert_eq!(policy.jitter, BackoffJitter::Full);
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(2), Duration::from_millis(40));
+        // Capped
+        assert_eq!(policy.backoff(3), Duration::from_millis(50));
+        assert_eq!(
+            policy.to_string(),
+            "timeout=100ms,attempts=4,backoff=20ms,multiplier=2,max-backoff=50ms,jitter=full"
+        );
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+        assert_eq!("".parse(), Ok(RetryPolicy::default()));
+
+        assert!("attempts=0".parse::<RetryPolicy>().is_err());
+        assert!("multiplier=0.5".parse::<RetryPolicy>().is_err());
+        assert!("timeout=100".parse::<RetryPolicy>().is_err());
+        assert!("jitter=some".parse::<RetryPolicy>().is_err());
+        assert!("retries=3".parse::<RetryPolicy>().is_err());
+        assert!("timeout".parse::<RetryPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let policy = RetryPolicy {
+            timeout: Duration::from_millis(10),
+            attempts: 3,
+            backoff: Duration::from_millis(5),
+            ..RetryPolicy::default()
+        };
+        let ms = 1_000_000;
+        // Answered; lost then answered; late, lost, lost (fails); cut short by the end
+        let probes = [
+            Some(ms),
+            None,
+            Some(2 * ms),
+            Some(20 * ms),
+            None,
+            None,
+            None,
+        ];
+        let outcome = replay(
+            policy,
+            probes
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, &rtt)| (i as u64, rtt)),
+            7,
+        );
+        // 10 ms timeout + 5 ms backoff + 2 ms
+        assert_eq!(outcome.completion_ns, [ms, 17 * ms]);
+        assert_eq!(outcome.completed_by_attempt, [1, 1, 0]);
+        assert_eq!(outcome.failed, 1);
+        assert_eq!(outcome.requests(), 3);
+        assert_eq!(outcome.probes_used, 6);
+        assert_eq!(outcome.amplification(), 2.0);
+        assert!((outcome.retried_pct() - 200.0 / 3.0).abs() < 1e-9);
+        assert!((outcome.failed_pct() - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(outcome.completion_percentiles(&[0.5, 1.0]), [ms, 17 * ms]);
+
+        // Full jitter only ever shortens the backoff
+        let jittered = RetryPolicy {
+            jitter: BackoffJitter::Full,
+            ..policy
+        };
+        let outcome = replay(jittered, [(0, None), (1, Some(ms))], 7);
+        assert!((11 * ms..16 * ms).contains(&outcome.completion_ns[0]));
+    }
+}
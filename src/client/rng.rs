@@ -0,0 +1,118 @@
+//! Seeded random number generation for reproducible test plans
+//!
+//! Every randomized choice of a run (send gaps, payload bytes, sampling) derives from a
+//! single seed, so two runs with the same `--seed` produce byte-identical probe
+//! schedules. The generator is SplitMix64, implemented here rather than taken from a
+//! crate so its output can never change under a dependency upgrade.
+//!
+//! Each consumer draws from its own named stream ([`SeededRng::stream`]); adding a new
+//! consumer therefore does not shift the values seen by existing ones.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SplitMix64 increment (the golden ratio in 64-bit fixed point)
+const SPLITMIX64_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// FNV-1a 64-bit offset basis and prime, used to hash stream names
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Deterministic pseudo-random number generator (SplitMix64)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    /// Create a generator from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Create the generator of the stream `name` derived from `seed`
+    pub fn stream(seed: u64, name: &str) -> Self {
+        let name_hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+        Self::new(mix(seed ^ name_hash))
+    }
+
+    /// Next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(SPLITMIX64_GAMMA);
+        mix(self.state)
+    }
+
+    /// Next value uniformly distributed in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        // The top 53 bits fill the mantissa exactly
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fill `buf` with random bytes
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// SplitMix64 output function
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Pick a fresh seed for runs started without `--seed`
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    mix(nanos ^ ((std::process::id() as u64) << 32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng_matches_reference_splitmix64() {
+        // Reference values of SplitMix64 seeded with 0; a change here breaks reproducibility
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+    }
+
+    #[test]
+    fn test_seeded_rng_is_deterministic_per_stream() {
+        let mut a = SeededRng::stream(42, "gaps");
+        let mut b = SeededRng::stream(42, "gaps");
+        let mut other = SeededRng::stream(42, "payload");
+
+        let a_values: Vec<u64> = (0..4).map(|_| a.next_u64()).collect();
+        let b_values: Vec<u64> = (0..4).map(|_| b.next_u64()).collect();
+        let other_values: Vec<u64> = (0..4).map(|_| other.next_u64()).collect();
+
+        assert_eq!(a_values, b_values);
+        assert_ne!(a_values, other_values);
+    }
+
+    #[test]
+    fn test_seeded_rng_f64_and_bytes() {
+        let mut rng = SeededRng::new(7);
+        for _ in 0..1000 {
+            let value = rng.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+
+        let mut buf = [0u8; 13];
+        SeededRng::new(7).fill_bytes(&mut buf);
+        let mut again = [0u8; 13];
+        SeededRng::new(7).fill_bytes(&mut again);
+        assert_eq!(buf, again);
+        assert_ne!(buf, [0u8; 13]);
+    }
+}
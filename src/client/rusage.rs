@@ -0,0 +1,138 @@
+//! CPU time and context switches of the measuring thread
+//!
+//! If the client itself is starved of CPU, its latency numbers describe the client rather
+//! than the network. [`ThreadUsage`] snapshots `getrusage` for the calling thread so the
+//! measurement phase can report how much CPU it used and how often it was preempted.
+
+use std::time::Duration;
+
+/// Busy fraction of wall time above which the client is reported as CPU-bound
+pub const CPU_BOUND_THRESHOLD: f64 = 0.9;
+
+/// Involuntary context switches per packet above which the client is reported as starved
+pub const PREEMPTION_PER_PACKET_THRESHOLD: f64 = 0.01;
+
+/// Resource usage of one thread (or a difference of two snapshots)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadUsage {
+    /// CPU time spent in user mode
+    pub user: Duration,
+    /// CPU time spent in the kernel
+    pub system: Duration,
+    /// Context switches because the thread blocked (e.g. waiting in `recv`)
+    pub voluntary_switches: u64,
+    /// Context switches because the scheduler preempted the thread
+    pub involuntary_switches: u64,
+}
+
+impl ThreadUsage {
+    /// Snapshot the usage of the calling thread
+    ///
+    /// Uses `RUSAGE_THREAD` on Linux. Other Unix systems only offer process-wide usage,
+    /// which includes the client's helper threads. Returns `None` where unavailable.
+    pub fn current() -> Option<Self> {
+        current_usage()
+    }
+
+    /// Usage accumulated between `earlier` and this snapshot
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            user: self.user.saturating_sub(earlier.user),
+            system: self.system.saturating_sub(earlier.system),
+            voluntary_switches: self
+                .voluntary_switches
+                .saturating_sub(earlier.voluntary_switches),
+            involuntary_switches: self
+                .involuntary_switches
+                .saturating_sub(earlier.involuntary_switches),
+        }
+    }
+
+    /// Total CPU time (user + system)
+    pub fn cpu_time(&self) -> Duration {
+        self.user + self.system
+    }
+
+    /// CPU time as a fraction of `wall` time
+    pub fn busy_fraction(&self, wall: Duration) -> f64 {
+        if wall.is_zero() {
+            return 0.0;
+        }
+        self.cpu_time().as_secs_f64() / wall.as_secs_f64()
+    }
+}
+
+#[cfg(unix)]
+fn current_usage() -> Option<ThreadUsage> {
+    #[cfg(target_os = "linux")]
+    const WHO: libc::c_int = libc::RUSAGE_THREAD;
+    #[cfg(not(target_os = "linux"))]
+    const WHO: libc::c_int = libc::RUSAGE_SELF;
+
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: getrusage only writes into the provided, properly sized rusage struct
+    let rc = unsafe { libc::getrusage(WHO, usage.as_mut_ptr()) };
+    if rc != 0 {
+        return None;
+    }
+    // SAFETY: getrusage returned success, so the struct is initialized
+    let usage = unsafe { usage.assume_init() };
+
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    Some(ThreadUsage {
+        user: timeval(usage.ru_utime),
+        system: timeval(usage.ru_stime),
+        voluntary_switches: usage.ru_nvcsw as u64,
+        involuntary_switches: usage.ru_nivcsw as u64,
+    })
+}
+
+#[cfg(not(unix))]
+fn current_usage() -> Option<ThreadUsage> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_usage_since_and_busy_fraction() {
+        let earlier = ThreadUsage {
+            user: Duration::from_millis(100),
+            system: Duration::from_millis(50),
+            voluntary_switches: 10,
+            involuntary_switches: 1,
+        };
+        let later = ThreadUsage {
+            user: Duration::from_millis(400),
+            system: Duration::from_millis(250),
+            voluntary_switches: 110,
+            involuntary_switches: 4,
+        };
+        let delta = later.since(&earlier);
+
+        assert_eq!(delta.cpu_time(), Duration::from_millis(500));
+        assert_eq!(delta.voluntary_switches, 100);
+        assert_eq!(delta.involuntary_switches, 3);
+        assert!((delta.busy_fraction(Duration::from_secs(1)) - 0.5).abs() < 1e-9);
+        assert_eq!(delta.busy_fraction(Duration::ZERO), 0.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_thread_usage_current_counts_cpu_time() {
+        let before = ThreadUsage::current().unwrap();
+        // Burn CPU on this thread for longer than a scheduler tick
+        let started = std::time::Instant::now();
+        let mut x = 0u64;
+        while started.elapsed() < Duration::from_millis(50) {
+            x = std::hint::black_box(x.wrapping_add(1));
+        }
+        let after = ThreadUsage::current().unwrap();
+
+        assert!(after.since(&before).cpu_time() > Duration::ZERO);
+    }
+}
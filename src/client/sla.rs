@@ -0,0 +1,233 @@
+//! Multi-condition SLA policy
+//!
+//! A service level agreement rarely bounds a single figure: it caps the tail and the
+//! median latency and the loss rate together. `--sla "p99<500us,p50<100us,loss<0.01%"`
+//! grades a run against every condition of such a policy at once and reports which of
+//! them held; the policy is met only when all of them did.
+
+use crate::client::slo::{format_threshold, parse_threshold};
+use crate::client::statistics::Statistics;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// RTT figure a latency condition bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Figure {
+    /// Percentile of the answered probes, e.g. 99.9
+    Percentile(f64),
+    /// Mean RTT
+    Mean,
+    /// Highest RTT
+    Max,
+}
+
+impl Figure {
+    /// Value of the figure in `stats`, in nanoseconds
+    fn of(&self, stats: &Statistics) -> f64 {
+        match self {
+            Figure::Percentile(p) => stats.percentile(p / 100.0) as f64,
+            Figure::Mean => stats.mean(),
+            Figure::Max => stats.max() as f64,
+        }
+    }
+}
+
+impl fmt::Display for Figure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Figure::Percentile(p) => write!(f, "P{}", p),
+            Figure::Mean => write!(f, "Mean"),
+            Figure::Max => write!(f, "Max"),
+        }
+    }
+}
+
+/// One condition of an [`Sla`], written as `NAME<BOUND`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// `p99<500us`, `mean<100us` or `max<2ms`: the figure stays below the latency
+    Latency { figure: Figure, below: Duration },
+    /// `loss<0.01%`: lost probes stay below this percentage of those sent
+    Loss { below_pct: f64 },
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, bound) = s
+            .split_once('<')
+            .ok_or_else(|| format!("invalid SLA condition '{}' (expected NAME<BOUND)", s))?;
+        let (name, bound) = (name.trim().to_lowercase(), bound.trim());
+        if name == "loss" {
+            let below_pct: f64 = bound
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| format!("invalid loss bound '{}' (expected a percentage)", bound))?;
+            if !(below_pct > 0.0 && below_pct <= 100.0) {
+                return Err("loss bound must be > 0% and at most 100%".into());
+            }
+            return Ok(Condition::Loss { below_pct });
+        }
+        let figure = match name.as_str() {
+            "mean" => Figure::Mean,
+            "max" => Figure::Max,
+            _ => {
+                let percentile: f64 = name
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse().ok())
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown SLA figure '{}' (expected pNN, mean, max or loss)",
+                            name
+                        )
+                    })?;
+                if !(percentile > 0.0 && percentile <= 100.0) {
+                    return Err(format!("percentile of '{}' must be in (0, 100]", name));
+                }
+                Figure::Percentile(percentile)
+            }
+        };
+        Ok(Condition::Latency {
+            figure,
+            below: parse_threshold(bound)?,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Latency { figure, below } => {
+                write!(f, "{} < {}", figure, format_threshold(*below))
+            }
+            Condition::Loss { below_pct } => write!(f, "Loss < {}%", below_pct),
+        }
+    }
+}
+
+/// A policy of conditions that must all hold, written comma-separated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sla {
+    pub conditions: Vec<Condition>,
+}
+
+impl FromStr for Sla {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let conditions = s
+            .split(',')
+            .filter(|c| !c.trim().is_empty())
+            .map(str::parse)
+            .collect::<std::result::Result<Vec<Condition>, _>>()?;
+        if conditions.is_empty() {
+            return Err("SLA has no condition".into());
+        }
+        Ok(Self { conditions })
+    }
+}
+
+impl Sla {
+    /// Evaluate every condition over a run that lost `lost_packets` of `total_packets`
+    pub fn evaluate(
+        &self,
+        stats: &Statistics,
+        lost_packets: usize,
+        total_packets: usize,
+    ) -> Vec<ConditionOutcome> {
+        self.conditions
+            .iter()
+            .map(|&condition| {
+                let (actual, met) = match condition {
+                    // A run with no answer has no latency to meet the bound with
+                    Condition::Latency { figure, below } => {
+                        let value_ns = figure.of(stats);
+                        (
+                            value_ns,
+                            stats.count() > 0 && value_ns < below.as_nanos() as f64,
+                        )
+                    }
+                    Condition::Loss { below_pct } => {
+                        let loss_pct = if total_packets == 0 {
+                            0.0
+                        } else {
+                            lost_packets as f64 / total_packets as f64 * 100.0
+                        };
+                        (loss_pct, total_packets > 0 && loss_pct < below_pct)
+                    }
+                };
+                ConditionOutcome {
+                    condition,
+                    actual,
+                    met,
+                }
+            })
+            .collect()
+    }
+}
+
+/// How a run fared against one [`Condition`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConditionOutcome {
+    pub condition: Condition,
+    /// Value of the run: nanoseconds for a latency, a percentage for loss
+    pub actual: f64,
+    /// Whether the condition held
+    pub met: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::Result;
+
+    #[test]
+    fn test_sla_parse() {
+        let sla: Sla = "p99<500us, p50<100us,loss<0.01%".parse().unwrap();
+        assert_eq!(
+            sla.conditions,
+            vec![
+                Condition::Latency {
+                    figure: Figure::Percentile(99.0),
+                    below: Duration::from_micros(500)
+                },
+                Condition::Latency {
+                    figure: Figure::Percentile(50.0),
+                    below: Duration::from_micros(100)
+                },
+                Condition::Loss { below_pct: 0.01 },
+            ]
+        );
+        assert_eq!(sla.conditions[0].to_string(), "P99 < 500µs");
+        assert_eq!(sla.conditions[2].to_string(), "Loss < 0.01%");
+
+        let sla: Sla = "P99.9<1ms,mean<80us,max<2ms".parse().unwrap();
+        assert_eq!(sla.conditions[0].to_string(), "P99.9 < 1ms");
+
+        for invalid in [
+            "", "p99", "p99<500", "p0<1ms", "p101<1ms", "q99<1ms", "loss<0%",
+        ] {
+            assert!(invalid.parse::<Sla>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_sla_evaluate() -> Result<()> {
+        let mut latencies = vec![50_000; 990];
+        latencies.extend([800_000; 10]);
+        let stats = Statistics::new(&latencies)?;
+        let sla: Sla = "p50<100us,p99.9<500us,loss<1%".parse().unwrap();
+
+        let outcomes = sla.evaluate(&stats, 5, 1005);
+        let met: Vec<bool> = outcomes.iter().map(|o| o.met).collect();
+        assert_eq!(met, vec![true, false, true]);
+        assert!((outcomes[2].actual - 5.0 / 1005.0 * 100.0).abs() < 1e-9);
+
+        // Nothing answered: no latency condition can hold
+        let outcomes = sla.evaluate(&Statistics::empty()?, 10, 10);
+        assert!(outcomes.iter().all(|o| !o.met));
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! Latency SLO report card
+//!
+//! SRE teams state latency requirements as "99.95% of requests complete within 500 µs"
+//! rather than as a P99 figure. `--slo 99.95%<500us` evaluates a run against such an
+//! objective: the share of probes answered within the threshold, and how much of the
+//! error budget (the 0.05% of probes allowed to miss it) the run consumed. A lost probe
+//! was never answered and counts against the budget.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A latency objective, written on the command line as `TARGET%<THRESHOLD`
+///
+/// The threshold takes a unit of `ns`, `us` (or `µs`), `ms` or `s`, e.g. `99.9%<1ms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slo {
+    /// Percentage of probes that must be answered within the threshold
+    pub target_pct: f64,
+    /// Round-trip latency a probe must not exceed
+    pub threshold: Duration,
+}
+
+impl FromStr for Slo {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (target, threshold) = s
+            .split_once('<')
+            .ok_or_else(|| format!("invalid SLO '{}' (expected TARGET%<THRESHOLD)", s))?;
+        let target_pct: f64 = target
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| format!("invalid target '{}' (expected a percentage)", target))?;
+        if !(target_pct > 0.0 && target_pct < 100.0) {
+            return Err("target must be between 0 and 100 (exclusive)".into());
+        }
+        Ok(Self {
+            target_pct,
+            threshold: parse_threshold(threshold.trim())?,
+        })
+    }
+}
+
+/// Parse a latency such as `500us` or `1.5ms`
+pub fn parse_threshold(threshold: &str) -> std::result::Result<Duration, String> {
+    let split = threshold
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(|| format!("threshold '{}' needs a unit (ns, us, ms or s)", threshold))?;
+    let (value, unit) = threshold.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid threshold '{}'", threshold))?;
+    let scale = match unit {
+        "ns" => 1e-9,
+        "us" | "µs" => 1e-6,
+        "ms" => 1e-3,
+        "s" => 1.0,
+        _ => {
+            return Err(format!(
+                "unknown unit '{}' (expected ns, us, ms or s)",
+                unit
+            ))
+        }
+    };
+    if value <= 0.0 {
+        return Err("threshold must be > 0".into());
+    }
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+/// Write a latency in the largest unit it reaches, e.g. `500µs` or `1.5ms`
+pub fn format_threshold(threshold: Duration) -> String {
+    let ns = threshold.as_nanos();
+    if ns >= 1_000_000_000 {
+        format!("{}s", threshold.as_secs_f64())
+    } else if ns >= 1_000_000 {
+        format!("{}ms", ns as f64 / 1e6)
+    } else if ns >= 1_000 {
+        format!("{}µs", ns as f64 / 1e3)
+    } else {
+        format!("{}ns", ns)
+    }
+}
+
+impl fmt::Display for Slo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}% of probes under {}",
+            self.target_pct,
+            format_threshold(self.threshold)
+        )
+    }
+}
+
+/// How a run fared against one [`Slo`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloOutcome {
+    pub slo: Slo,
+    /// Probes answered within the threshold
+    pub good: usize,
+    /// Probes sent, lost ones included
+    pub total: usize,
+}
+
+impl SloOutcome {
+    /// Evaluate `slo` over the latencies of a run that lost `lost_packets` probes
+    pub fn evaluate(slo: Slo, latencies: &[u64], lost_packets: usize) -> Self {
+        let threshold_ns = slo.threshold.as_nanos() as u64;
+        Self {
+            slo,
+            good: latencies.iter().filter(|&&ns| ns <= threshold_ns).count(),
+            total: latencies.len() + lost_packets,
+        }
+    }
+
+    /// Percentage of probes answered within the threshold
+    pub fn attained_pct(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.good as f64 / self.total as f64 * 100.0
+    }
+
+    /// Share of the error budget the run used up, in percent (above 100 when missed)
+    pub fn budget_consumed_pct(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let allowed = self.total as f64 * (100.0 - self.slo.target_pct) / 100.0;
+        (self.total - self.good) as f64 / allowed * 100.0
+    }
+
+    /// Whether the run met the objective
+    pub fn met(&self) -> bool {
+        self.total > 0 && self.attained_pct() >= self.slo.target_pct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slo_parse() {
+        let slo: Slo = "99.95%<500us".parse().unwrap();
+        assert_eq!(slo.target_pct, 99.95);
+        assert_eq!(slo.threshold, Duration::from_micros(500));
+        assert_eq!(slo.to_string(), "99.95% of probes under 500µs");
+
+        let slo: Slo = "99.9 < 1.5ms".parse().unwrap();
+        assert_eq!(slo.threshold, Duration::from_micros(1500));
+        assert_eq!("99%<2µs".parse::<Slo>().unwrap().threshold.as_nanos(), 2000);
+
+        assert!("99.9%".parse::<Slo>().is_err());
+        assert!("100%<1ms".parse::<Slo>().is_err());
+        assert!("99%<500".parse::<Slo>().is_err());
+        assert!("99%<5min".parse::<Slo>().is_err());
+        assert!("99%<0ms".parse::<Slo>().is_err());
+    }
+
+    #[test]
+    fn test_slo_outcome_budget() {
+        let slo: Slo = "99%<100us".parse().unwrap();
+        // 1000 probes: 997 fast, 2 slow, 1 lost
+        let mut latencies = vec![50_000; 997];
+        latencies.extend([150_000, 100_001]);
+        let outcome = SloOutcome::evaluate(slo, &latencies, 1);
+
+        assert_eq!(outcome.total, 1000);
+        assert_eq!(outcome.good, 997);
+        assert!((outcome.attained_pct() - 99.7).abs() < 1e-9);
+        assert!((outcome.budget_consumed_pct() - 30.0).abs() < 1e-9);
+        assert!(outcome.met());
+
+        let missed = SloOutcome::evaluate("99.9%<100us".parse().unwrap(), &latencies, 1);
+        assert!(!missed.met());
+        assert!(missed.budget_consumed_pct() > 100.0);
+        assert!(!SloOutcome::evaluate(slo, &[], 0).met());
+    }
+}
@@ -1,26 +1,122 @@
+use crate::client::constants::HELLO_TIMEOUT_MS;
+use crate::client::dscp::{self, Dscp};
 use crate::client::error::{ClientError, Result};
+use crate::client::timestamping::{self, KernelTimestamps};
 use crate::protocol::{Packet, PACKET_SIZE};
 use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
+/// Callback that unblocks any in-progress I/O on a socket from another thread
+pub type AbortHandle = Box<dyn FnOnce() + Send>;
+
 /// Trait for network socket operations with packet abstraction
 pub trait NetworkSocket: Send + Sync {
     /// Send a packet over the network
     fn send_packet(&self, packet: &Packet) -> Result<usize>;
 
+    /// Block until `packet` may be sent, before its send is timed
+    ///
+    /// For sockets that cap their traffic (see [`crate::client::pacing::BudgetedSocket`]);
+    /// callers wait here rather than in `send_packet` so the wait does not count towards
+    /// the round trip.
+    fn wait_to_send(&self, _packet: &Packet) -> Result<()> {
+        Ok(())
+    }
+
     /// Receive a packet from the network
     fn recv_packet(&mut self) -> Result<Packet>;
 
     /// Set the read timeout for the socket
     fn set_timeout(&self, timeout: Duration) -> Result<()>;
+
+    /// Returns a handle that aborts blocked I/O on this socket, if supported
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        Ok(None)
+    }
+
+    /// Returns and clears the pending socket error (`SO_ERROR`), if supported
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        Ok(None)
+    }
+
+    /// Kernel send and receive timestamps of the last exchange, if kernel timestamping
+    /// is enabled (see [`crate::client::timestamping`])
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        Ok(None)
+    }
+
+    /// DSCP of the last reply, if the socket reads reply markings (see
+    /// [`crate::client::dscp`])
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        None
+    }
+
+    /// Accept replies to any probe in flight from now on, not only to the last one sent
+    ///
+    /// For open-loop measurement, which matches replies to probes itself (see
+    /// [`crate::client::open_loop`]); sockets that discard stale replies keep them.
+    fn accept_any_reply(&mut self) {}
+}
+
+impl<S: NetworkSocket + ?Sized> NetworkSocket for Box<S> {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        (**self).send_packet(packet)
+    }
+
+    fn wait_to_send(&self, packet: &Packet) -> Result<()> {
+        (**self).wait_to_send(packet)
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        (**self).recv_packet()
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        (**self).set_timeout(timeout)
+    }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        (**self).abort_handle()
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        (**self).take_error()
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        (**self).kernel_timestamps()
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        (**self).reply_dscp()
+    }
+
+    fn accept_any_reply(&mut self) {
+        (**self).accept_any_reply()
+    }
 }
 
 /// TCP-based implementation of NetworkSocket
+///
+/// Replies are as long as the probes they echo, so each receive reads as many bytes as
+/// the last packet sent.
 pub struct TcpNetworkSocket {
     stream: Mutex<TcpStream>,
+    /// Reused encode buffer, so header and padding go out in a single write
+    send_buffer: Mutex<Vec<u8>>,
+    /// Size of the last packet sent
+    expected_size: AtomicUsize,
+    recv_buffer: Vec<u8>,
+    /// Kernel timestamping is enabled
+    kernel_timestamps: bool,
+    /// Kernel receive timestamp of the last reply
+    last_rx_ns: Option<u64>,
+    /// Bytes sent after the header in place of zero padding
+    payload: Option<Vec<u8>>,
 }
 
 impl TcpNetworkSocket {
@@ -34,13 +130,104 @@ impl TcpNetworkSocket {
         debug!("TCP stream connected successfully");
         Ok(Self {
             stream: Mutex::new(stream),
+            send_buffer: Mutex::new(Vec::new()),
+            expected_size: AtomicUsize::new(PACKET_SIZE),
+            recv_buffer: Vec::new(),
+            kernel_timestamps: false,
+            last_rx_ns: None,
+            payload: None,
         })
     }
+
+    /// Have the kernel timestamp every probe and reply (Linux)
+    pub fn enable_kernel_timestamps(&mut self) -> Result<()> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        timestamping::enable(&*stream).map_err(|e| {
+            warn!(error = %e, "Failed to enable kernel timestamps");
+            ClientError::Socket(format!("Failed to enable kernel timestamps: {}", e))
+        })?;
+        self.kernel_timestamps = true;
+        debug!("Kernel timestamps enabled");
+        Ok(())
+    }
+
+    /// Send every probe at once rather than holding it back until the previous one is
+    /// acknowledged (`TCP_NODELAY`), for transactions and windows of probes in flight
+    pub fn disable_nagle(&mut self) -> Result<()> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        stream.set_nodelay(true).map_err(|e| {
+            warn!(error = %e, "Failed to disable Nagle");
+            ClientError::Socket(format!("Failed to set TCP_NODELAY: {}", e))
+        })?;
+        debug!("Nagle disabled");
+        Ok(())
+    }
+
+    /// Mark every probe with `dscp` (Linux); replies cannot be checked over TCP
+    pub fn set_dscp(&mut self, dscp: Dscp) -> Result<()> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        let ipv6 = stream.peer_addr()?.is_ipv6();
+        dscp::mark(&*stream, ipv6, dscp).map_err(|e| {
+            warn!(error = %e, "Failed to set DSCP");
+            ClientError::Socket(format!("Failed to set DSCP {}: {}", dscp, e))
+        })?;
+        debug!(dscp = %dscp, "Probes marked");
+        Ok(())
+    }
+
+    /// Send `payload` after the header of every probe instead of zero padding
+    ///
+    /// Applies to probes of `PACKET_SIZE + payload.len()` bytes; others stay zero-padded.
+    pub fn set_payload(&mut self, payload: Vec<u8>) {
+        debug!(bytes = payload.len(), "Probe payload set");
+        self.payload = Some(payload);
+    }
+
+    /// Local address the connection is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        stream
+            .local_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read local address: {}", e)))
+    }
+
+    /// Address of the server end of the connection
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        stream
+            .peer_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read peer address: {}", e)))
+    }
 }
 
 impl NetworkSocket for TcpNetworkSocket {
     fn send_packet(&self, packet: &Packet) -> Result<usize> {
-        let buf = packet.encode();
+        let mut buf = self.send_buffer.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock send buffer");
+            ClientError::Socket(format!("Failed to lock send buffer: {}", e))
+        })?;
+        packet.encode_into(&mut buf);
+        if let Some(payload) = &self.payload {
+            if buf.len() == PACKET_SIZE + payload.len() {
+                buf[PACKET_SIZE..].copy_from_slice(payload);
+            }
+        }
+        self.expected_size.store(buf.len(), Ordering::Relaxed);
         let mut stream = self.stream.lock().map_err(|e| {
             warn!(error = %e, "Failed to lock stream");
             ClientError::Socket(format!("Failed to lock stream: {}", e))
@@ -64,22 +251,45 @@ impl NetworkSocket for TcpNetworkSocket {
     }
 
     fn recv_packet(&mut self) -> Result<Packet> {
-        let mut buf = [0u8; PACKET_SIZE];
+        let size = self.expected_size.load(Ordering::Relaxed);
+        self.recv_buffer.resize(size, 0);
         let mut stream = self.stream.lock().map_err(|e| {
             warn!(error = %e, "Failed to lock stream");
             ClientError::Socket(format!("Failed to lock stream: {}", e))
         })?;
 
-        // TCP is stream-based, so we must use read_exact to read exactly PACKET_SIZE bytes
-        stream.read_exact(&mut buf).map_err(|e| {
-            debug!(error = %e, "Failed to receive packet");
-            ClientError::Io(e)
-        })?;
+        // TCP is stream-based, so we must read until the whole reply is in
+        if self.kernel_timestamps {
+            // The timestamp of the segment that completed the reply is the one kept
+            let mut filled = 0;
+            while filled < size {
+                match timestamping::recv(&*stream, &mut self.recv_buffer[filled..]) {
+                    Ok((0, _, _)) => {
+                        debug!("Connection closed while receiving packet");
+                        return Err(ClientError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+                    }
+                    Ok((len, rx_ns, _)) => {
+                        filled += len;
+                        self.last_rx_ns = rx_ns.or(self.last_rx_ns);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        debug!(error = %e, "Failed to receive packet");
+                        return Err(ClientError::Io(e));
+                    }
+                }
+            }
+        } else {
+            stream.read_exact(&mut self.recv_buffer).map_err(|e| {
+                debug!(error = %e, "Failed to receive packet");
+                ClientError::Io(e)
+            })?;
+        }
 
-        let packet = Packet::decode(&buf)?;
+        let packet = Packet::decode(&self.recv_buffer)?;
         debug!(
             sequence = packet.sequence.0,
-            bytes_received = PACKET_SIZE,
+            bytes_received = size,
             "Packet received"
         );
         Ok(packet)
@@ -99,14 +309,384 @@ impl NetworkSocket for TcpNetworkSocket {
         debug!("Timeout set successfully");
         Ok(())
     }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+
+        // A cloned handle refers to the same connection, so shutting it down wakes up a
+        // read or write blocked on the original stream without needing its lock
+        let clone = stream.try_clone().map_err(|e| {
+            warn!(error = %e, "Failed to clone stream");
+            ClientError::Socket(format!("Failed to clone stream: {}", e))
+        })?;
+        Ok(Some(Box::new(move || {
+            if let Err(e) = clone.shutdown(Shutdown::Both) {
+                warn!(error = %e, "Failed to shut down stream");
+            }
+        })))
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        Ok(stream.take_error()?)
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        if !self.kernel_timestamps {
+            return Ok(None);
+        }
+        let stream = self.stream.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock stream");
+            ClientError::Socket(format!("Failed to lock stream: {}", e))
+        })?;
+        let tx_ns = timestamping::latest_tx(&*stream)?;
+        Ok(tx_ns
+            .zip(self.last_rx_ns.take())
+            .map(|(tx_ns, rx_ns)| KernelTimestamps { tx_ns, rx_ns }))
+    }
+}
+
+/// UDP-based implementation of NetworkSocket
+///
+/// Every probe and reply is one datagram on a connected socket. A reply that arrives
+/// after its probe timed out would otherwise be taken for the reply to the next probe,
+/// so replies not carrying the last sent sequence number are discarded.
+///
+/// The server answers probes without the cookie of the socket's address with a
+/// challenge carrying it (see [`PACKET_SIZE`]), and every later probe carries the
+/// cookie. [`UdpNetworkSocket::connect`] sends a hello for it, so measuring starts with
+/// the cookie in hand; a probe challenged all the same, as after a server restart, is
+/// sent again once with the new cookie, its round trip then covering both.
+pub struct UdpNetworkSocket {
+    socket: UdpSocket,
+    /// Sequence number of the last probe sent
+    last_sent: AtomicU64,
+    /// Cookie of the last challenge from the server, 0 before the first
+    cookie: AtomicU64,
+    /// Sequence number of the last probe sent again after a challenge
+    resent: Option<u64>,
+    /// Size of the last probe sent; replies must match it
+    expected_size: AtomicUsize,
+    send_buffer: Mutex<Vec<u8>>,
+    recv_buffer: Vec<u8>,
+    /// Kernel timestamping is enabled
+    kernel_timestamps: bool,
+    /// Kernel receive timestamp of the last reply
+    last_rx_ns: Option<u64>,
+    /// Reply markings are read
+    watch_dscp: bool,
+    /// TOS byte of the last reply
+    last_tos: Option<u8>,
+    /// Bytes sent after the header in place of zero padding
+    payload: Option<Vec<u8>>,
+    /// Replies to earlier probes are returned rather than discarded
+    any_reply: bool,
+}
+
+impl UdpNetworkSocket {
+    /// Create a socket sending to and receiving only from `addr`, and exchange a hello
+    /// for the server's cookie
+    pub fn connect(addr: &str) -> Result<Self> {
+        debug!(addr = addr, "Connecting UDP socket");
+        let remote = addr
+            .to_socket_addrs()
+            .map_err(|e| ClientError::Socket(format!("Failed to resolve {}: {}", addr, e)))?
+            .next()
+            .ok_or_else(|| ClientError::Socket(format!("{} resolved to no address", addr)))?;
+        let local: SocketAddr = if remote.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(remote).map(|_| socket))
+            .map_err(|e| {
+                warn!(error = %e, "Failed to connect socket");
+                ClientError::Socket(format!("Failed to connect to {}: {}", addr, e))
+            })?;
+        debug!("UDP socket connected successfully");
+        let socket = Self {
+            socket,
+            last_sent: AtomicU64::new(0),
+            cookie: AtomicU64::new(0),
+            resent: None,
+            expected_size: AtomicUsize::new(PACKET_SIZE),
+            send_buffer: Mutex::new(Vec::new()),
+            recv_buffer: Vec::new(),
+            kernel_timestamps: false,
+            last_rx_ns: None,
+            watch_dscp: false,
+            last_tos: None,
+            payload: None,
+            any_reply: false,
+        };
+        socket.exchange_cookie()?;
+        Ok(socket)
+    }
+
+    /// Send a hello and keep the cookie of the server's challenge to it
+    ///
+    /// A peer that echoes the hello or stays silent for [`HELLO_TIMEOUT_MS`] does not
+    /// validate sources, or is down; neither fails the connection, and a challenge
+    /// arriving later is taken from the first probe.
+    fn exchange_cookie(&self) -> Result<()> {
+        let mut buf = [0u8; PACKET_SIZE];
+        self.socket
+            .set_read_timeout(Some(Duration::from_millis(HELLO_TIMEOUT_MS)))?;
+        let answer = self
+            .socket
+            .send(&Packet::hello().encode())
+            .and_then(|_| self.socket.recv(&mut buf));
+        self.socket.set_read_timeout(None)?;
+        match answer.map(|len| Packet::decode(&buf[..len])) {
+            Ok(Ok(challenge)) if challenge.is_challenge() => {
+                debug!("Server challenged the hello");
+                self.cookie.store(challenge.cookie, Ordering::Relaxed);
+            }
+            Ok(_) => debug!("Server answered the hello without a challenge"),
+            Err(e) => debug!(error = %e, "No challenge to the hello"),
+        }
+        Ok(())
+    }
+
+    /// Send the last probe again, with the cookie of the challenge it got
+    fn resend(&self) -> Result<()> {
+        let mut buf = self.send_buffer.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock send buffer");
+            ClientError::Socket(format!("Failed to lock send buffer: {}", e))
+        })?;
+        Packet::stamp_cookie(&mut buf, self.cookie.load(Ordering::Relaxed));
+        self.socket.send(&buf).map_err(|e| {
+            warn!(error = %e, "Failed to send packet");
+            ClientError::Io(e)
+        })?;
+        Ok(())
+    }
+
+    /// Mark every probe with `dscp` and read the marking of every reply (Linux)
+    ///
+    /// A kernel that cannot report reply markings leaves probes marked but unchecked.
+    pub fn set_dscp(&mut self, dscp: Dscp) -> Result<()> {
+        let ipv6 = self.peer_addr()?.is_ipv6();
+        dscp::mark(&self.socket, ipv6, dscp).map_err(|e| {
+            warn!(error = %e, "Failed to set DSCP");
+            ClientError::Socket(format!("Failed to set DSCP {}: {}", dscp, e))
+        })?;
+        match dscp::report_markings(&self.socket, ipv6) {
+            Ok(()) => self.watch_dscp = true,
+            Err(e) => warn!(error = %e, "Cannot read reply markings; DSCP not verified"),
+        }
+        debug!(dscp = %dscp, "Probes marked");
+        Ok(())
+    }
+
+    /// Send `payload` after the header of every probe instead of zero padding
+    ///
+    /// Applies to probes of `PACKET_SIZE + payload.len()` bytes; others stay zero-padded.
+    pub fn set_payload(&mut self, payload: Vec<u8>) {
+        debug!(bytes = payload.len(), "Probe payload set");
+        self.payload = Some(payload);
+    }
+
+    /// Have the kernel timestamp every probe and reply (Linux)
+    pub fn enable_kernel_timestamps(&mut self) -> Result<()> {
+        timestamping::enable(&self.socket).map_err(|e| {
+            warn!(error = %e, "Failed to enable kernel timestamps");
+            ClientError::Socket(format!("Failed to enable kernel timestamps: {}", e))
+        })?;
+        self.kernel_timestamps = true;
+        debug!("Kernel timestamps enabled");
+        Ok(())
+    }
+
+    /// Local address the socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket
+            .local_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read local address: {}", e)))
+    }
+
+    /// Address of the server the socket is connected to
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket
+            .peer_addr()
+            .map_err(|e| ClientError::Socket(format!("Failed to read peer address: {}", e)))
+    }
+
+    /// Receive datagrams until the reply to the last sent probe arrives, or any reply
+    /// once [`NetworkSocket::accept_any_reply`] was called
+    fn recv_expected(&mut self, deadline: Option<Instant>) -> Result<Packet> {
+        let expected = self.last_sent.load(Ordering::Relaxed);
+        let size = self.expected_size.load(Ordering::Relaxed);
+        // Room for a longer datagram, so one is rejected rather than silently truncated
+        self.recv_buffer.resize(size + 1, 0);
+        loop {
+            let received = if self.kernel_timestamps || self.watch_dscp {
+                timestamping::recv(&self.socket, &mut self.recv_buffer)
+            } else {
+                self.socket
+                    .recv(&mut self.recv_buffer)
+                    .map(|len| (len, None, None))
+            };
+            let (len, rx_ns, tos) = received.map_err(|e| {
+                debug!(error = %e, "Failed to receive packet");
+                ClientError::Io(e)
+            })?;
+            // A challenge, or a late echo of the hello, is the header alone and asks
+            // for no echo, whatever the size of the probe
+            let header = (len == PACKET_SIZE)
+                .then(|| Packet::decode(&self.recv_buffer[..len]).ok())
+                .flatten()
+                .filter(|header| header.replies == 0);
+            if let Some(header) = header {
+                if header.is_challenge() {
+                    debug!(sequence = header.sequence.0, "Server challenged the probe");
+                    self.cookie.store(header.cookie, Ordering::Relaxed);
+                    // The server dropped the probe; once more with the cookie, it answers
+                    if header.sequence.0 == expected && self.resent != Some(expected) {
+                        self.resent = Some(expected);
+                        self.resend()?;
+                    }
+                }
+            } else {
+                if len != size {
+                    return Err(ClientError::Socket(format!(
+                        "Received a {}-byte datagram, expected {}",
+                        len, size
+                    )));
+                }
+                let packet = Packet::decode(&self.recv_buffer[..len])?;
+                if self.any_reply || packet.sequence.0 == expected {
+                    self.last_rx_ns = rx_ns;
+                    self.last_tos = tos;
+                    debug!(
+                        sequence = packet.sequence.0,
+                        bytes_received = len,
+                        "Packet received"
+                    );
+                    return Ok(packet);
+                }
+
+                debug!(
+                    expected = expected,
+                    received = packet.sequence.0,
+                    "Discarding stale reply"
+                );
+            }
+            // Keep waiting, but no longer than the timeout of the original receive
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(ClientError::Io(std::io::ErrorKind::WouldBlock.into()));
+                }
+                self.socket.set_read_timeout(Some(remaining))?;
+            }
+        }
+    }
+}
+
+impl NetworkSocket for UdpNetworkSocket {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        let mut buf = self.send_buffer.lock().map_err(|e| {
+            warn!(error = %e, "Failed to lock send buffer");
+            ClientError::Socket(format!("Failed to lock send buffer: {}", e))
+        })?;
+        packet.encode_into(&mut buf);
+        if let Some(payload) = &self.payload {
+            if buf.len() == PACKET_SIZE + payload.len() {
+                buf[PACKET_SIZE..].copy_from_slice(payload);
+            }
+        }
+        Packet::stamp_cookie(&mut buf, self.cookie.load(Ordering::Relaxed));
+        self.last_sent.store(packet.sequence.0, Ordering::Relaxed);
+        self.expected_size.store(buf.len(), Ordering::Relaxed);
+        let sent = self.socket.send(&buf).map_err(|e| {
+            warn!(error = %e, "Failed to send packet");
+            ClientError::Io(e)
+        })?;
+        debug!(
+            bytes_sent = sent,
+            sequence = packet.sequence.0,
+            "Packet sent"
+        );
+        Ok(sent)
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        let timeout = self.socket.read_timeout()?;
+        let result = self.recv_expected(timeout.map(|t| Instant::now() + t));
+        // Discarding stale replies may have shortened the timeout; restore it
+        if self.socket.read_timeout()? != timeout {
+            self.socket.set_read_timeout(timeout)?;
+        }
+        result
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        debug!(timeout_ms = timeout.as_millis(), "Setting socket timeout");
+        self.socket.set_read_timeout(Some(timeout)).map_err(|e| {
+            warn!(error = %e, "Failed to set timeout");
+            ClientError::Socket(format!("Failed to set timeout: {}", e))
+        })?;
+        debug!("Timeout set successfully");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        use std::os::fd::AsRawFd;
+
+        // std has no shutdown for UDP sockets, but the call wakes a blocked receive all
+        // the same; the clone shares the socket, so the original is shut down too
+        let clone = self.socket.try_clone().map_err(|e| {
+            warn!(error = %e, "Failed to clone socket");
+            ClientError::Socket(format!("Failed to clone socket: {}", e))
+        })?;
+        Ok(Some(Box::new(move || {
+            // SAFETY: `clone` owns a valid descriptor for the duration of the call
+            if unsafe { libc::shutdown(clone.as_raw_fd(), libc::SHUT_RDWR) } != 0 {
+                warn!(error = %std::io::Error::last_os_error(), "Failed to shut down socket");
+            }
+        })))
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        Ok(self.socket.take_error()?)
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        if !self.kernel_timestamps {
+            return Ok(None);
+        }
+        let tx_ns = timestamping::latest_tx(&self.socket)?;
+        Ok(tx_ns
+            .zip(self.last_rx_ns.take())
+            .map(|(tx_ns, rx_ns)| KernelTimestamps { tx_ns, rx_ns }))
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        self.last_tos.take().map(Dscp::from_tos)
+    }
+
+    fn accept_any_reply(&mut self) {
+        debug!("Accepting replies to any probe");
+        self.any_reply = true;
+    }
 }
 
 #[cfg(test)]
-pub use tests::MockNetworkSocket;
+pub use tests::{challenge_hellos, MockNetworkSocket};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::SequenceNumber;
     use mockall::mock;
 
     mock! {
@@ -119,6 +699,25 @@ mod tests {
         }
     }
 
+    /// Answer the hellos of the next `count` sockets connecting to `server` with a
+    /// challenge for `cookie`, as the server does; join once they are connected
+    pub fn challenge_hellos(
+        server: &UdpSocket,
+        count: usize,
+        cookie: u64,
+    ) -> std::thread::JoinHandle<()> {
+        let server = server.try_clone().expect("clone server socket");
+        std::thread::spawn(move || {
+            let mut buf = [0u8; PACKET_SIZE];
+            for _ in 0..count {
+                let (len, client) = server.recv_from(&mut buf).expect("hello");
+                assert_eq!(Packet::decode(&buf[..len]).ok().map(|p| p.replies), Some(0));
+                Packet::make_challenge(&mut buf, cookie);
+                server.send_to(&buf, client).expect("challenge");
+            }
+        })
+    }
+
     #[test]
     fn test_tcp_socket_connect() {
         // This test requires a server running, skip for unit tests
@@ -131,4 +730,85 @@ mod tests {
         // It will be tested in integration tests
         Ok(())
     }
+
+    #[test]
+    fn test_payload_replaces_padding() -> Result<()> {
+        let receiver = UdpSocket::bind("127.0.0.1:0")?;
+        let hellos = challenge_hellos(&receiver, 1, 77);
+        let mut socket = UdpNetworkSocket::connect(&receiver.local_addr()?.to_string())?;
+        hellos.join().unwrap();
+        socket.set_payload(b"templated".to_vec());
+
+        let mut buf = [0u8; 128];
+        socket.send_packet(&Packet::with_size(SequenceNumber(1), PACKET_SIZE + 9))?;
+        let len = receiver.recv(&mut buf)?;
+        assert_eq!(&buf[PACKET_SIZE..len], b"templated");
+        assert_eq!(Packet::decode(&buf[..len])?.sequence, SequenceNumber(1));
+
+        // Probes of another size keep their zero padding
+        socket.send_packet(&Packet::with_size(SequenceNumber(2), 80))?;
+        let len = receiver.recv(&mut buf)?;
+        assert_eq!(len, 80);
+        assert!(buf[PACKET_SIZE..len].iter().all(|&b| b == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_accept_any_reply() -> Result<()> {
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let hellos = challenge_hellos(&server, 1, 77);
+        let mut socket = UdpNetworkSocket::connect(&server.local_addr()?.to_string())?;
+        hellos.join().unwrap();
+        socket.set_timeout(Duration::from_millis(500))?;
+        let mut buf = [0u8; 64];
+        // Echo the first of two probes, then the second
+        let mut exchange = |socket: &mut UdpNetworkSocket, first: u64| -> Result<Packet> {
+            socket.send_packet(&Packet::new(SequenceNumber(first)))?;
+            socket.send_packet(&Packet::new(SequenceNumber(first + 1)))?;
+            for _ in 0..2 {
+                let (len, client) = server.recv_from(&mut buf)?;
+                server.send_to(&buf[..len], client)?;
+            }
+            socket.recv_packet()
+        };
+
+        // The reply to the earlier probe is stale and discarded
+        assert_eq!(exchange(&mut socket, 1)?.sequence, SequenceNumber(2));
+        socket.accept_any_reply();
+        assert_eq!(exchange(&mut socket, 3)?.sequence, SequenceNumber(3));
+        assert_eq!(socket.recv_packet()?.sequence, SequenceNumber(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cookie_challenge() -> Result<()> {
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let hellos = challenge_hellos(&server, 1, 77);
+        let mut socket = UdpNetworkSocket::connect(&server.local_addr()?.to_string())?;
+        hellos.join().unwrap();
+        socket.set_timeout(Duration::from_secs(5))?;
+        let mut buf = [0u8; 200];
+
+        // The first probe carries the cookie the hello got, and gets its echo
+        socket.send_packet(&Packet::with_size(SequenceNumber(1), 200))?;
+        let (len, client) = server.recv_from(&mut buf)?;
+        assert_eq!(Packet::cookie_of(&buf[..len]), 77);
+        server.send_to(&buf[..len], client)?;
+        assert_eq!(socket.recv_packet()?.sequence, SequenceNumber(1));
+
+        // After a restart the cookie changes: the challenged probe goes out again with
+        // the new one, rather than being lost
+        let restarted = std::thread::spawn(move || -> std::io::Result<u64> {
+            let (len, client) = server.recv_from(&mut buf)?;
+            Packet::make_challenge(&mut buf[..len], 78);
+            server.send_to(&buf[..PACKET_SIZE], client)?;
+            let (len, client) = server.recv_from(&mut buf)?;
+            server.send_to(&buf[..len], client)?;
+            Ok(Packet::cookie_of(&buf[..len]))
+        });
+        socket.send_packet(&Packet::with_size(SequenceNumber(2), 200))?;
+        assert_eq!(socket.recv_packet()?.sequence, SequenceNumber(2));
+        assert_eq!(restarted.join().unwrap()?, 78);
+        Ok(())
+    }
 }
@@ -1,14 +1,44 @@
 use crate::client::constants::*;
 use crate::client::error::{ClientError, Result};
 use hdrhistogram::Histogram;
+use std::io::Write;
 use tracing::{debug, warn};
 
+/// Gain of the RFC 3550 interarrival jitter estimator
+const JITTER_GAIN: f64 = 1.0 / 16.0;
+
+/// Variation of the latency from one measurement to the next
+///
+/// Computed on round-trip times, the difference between consecutive RTTs plays the part
+/// of the transit-time difference `D(i-1, i)` of RFC 3550, section 6.4.1, so it covers the
+/// variation of both directions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jitter {
+    /// Mean absolute difference between consecutive latencies, in nanoseconds
+    pub mean_ns: f64,
+    /// Largest absolute difference between consecutive latencies, in nanoseconds
+    pub max_ns: u64,
+    /// Interarrival jitter smoothed with a gain of 1/16 as RTP receivers report it, at the
+    /// last measurement, in nanoseconds
+    pub smoothed_ns: f64,
+}
+
 /// Statistics calculator using HDR histogram
 pub struct Statistics {
     hist: Histogram<u64>,
     real_min: u64,
     real_max: u64,
     clamped_count: usize,
+    /// Last latency recorded, which the next one is compared to for jitter
+    previous: Option<u64>,
+    /// Sum of the absolute differences between consecutive latencies
+    delta_sum: u128,
+    /// Number of differences in `delta_sum`
+    delta_count: u64,
+    /// Largest absolute difference between consecutive latencies
+    delta_max: u64,
+    /// RFC 3550 jitter estimate
+    smoothed_jitter: f64,
 }
 
 impl Statistics {
@@ -18,67 +48,160 @@ impl Statistics {
             sample_count = latencies.len(),
             "Creating statistics from latency measurements"
         );
-        let mut hist = Histogram::<u64>::new_with_bounds(
-            HISTOGRAM_LOW_BOUND_NS,
-            HISTOGRAM_HIGH_BOUND_NS,
-            HISTOGRAM_SIGNIFICANT_DIGITS,
-        )
-        .map_err(|e| ClientError::Measurement(format!("Failed to create histogram: {}", e)))?;
-
-        let mut real_min = u64::MAX;
-        let mut real_max = 0;
-        let mut clamped_count = 0;
+        let mut result = Self::empty()?;
 
         for &latency in latencies {
-            real_min = real_min.min(latency);
-            real_max = real_max.max(latency);
-
-            let clamped = latency.clamp(HISTOGRAM_LOW_BOUND_NS, HISTOGRAM_HIGH_BOUND_NS);
-
-            if latency != clamped {
-                clamped_count += 1;
-            }
-
-            hist.record(clamped).map_err(|e| {
-                warn!(latency = latency, error = %e, "Failed to record latency");
-                ClientError::Measurement(format!("Failed to record latency: {}", e))
-            })?;
+            result.record(latency)?;
         }
 
-        let result = Self {
-            hist,
-            real_min: if real_min == u64::MAX { 0 } else { real_min },
-            real_max,
-            clamped_count,
-        };
-
-        if clamped_count > 0 {
+        if result.clamped_count > 0 {
             warn!(
-                clamped_count = clamped_count,
+                clamped_count = result.clamped_count,
                 total_count = latencies.len(),
                 "Some latency values were clamped to histogram bounds"
             );
         }
 
         debug!(
-            min_ns = result.real_min,
-            max_ns = result.real_max,
+            min_ns = result.min(),
+            max_ns = result.max(),
             mean_ns = result.mean(),
-            clamped_count = clamped_count,
+            clamped_count = result.clamped_count,
             "Statistics calculated successfully"
         );
 
         Ok(result)
     }
 
+    /// Create an empty Statistics instance that latencies can be recorded into incrementally
+    pub fn empty() -> Result<Self> {
+        let hist = Histogram::<u64>::new_with_bounds(
+            HISTOGRAM_LOW_BOUND_NS,
+            HISTOGRAM_HIGH_BOUND_NS,
+            HISTOGRAM_SIGNIFICANT_DIGITS,
+        )
+        .map_err(|e| ClientError::Measurement(format!("Failed to create histogram: {}", e)))?;
+
+        Ok(Self {
+            hist,
+            real_min: u64::MAX,
+            real_max: 0,
+            clamped_count: 0,
+            previous: None,
+            delta_sum: 0,
+            delta_count: 0,
+            delta_max: 0,
+            smoothed_jitter: 0.0,
+        })
+    }
+
+    /// Rebuild statistics from `(value, count)` pairs produced by [`Statistics::recorded_values`]
+    ///
+    /// The pairs do not keep the order of the measurements, so the result has no jitter.
+    pub fn from_recorded(
+        values: &[(u64, u64)],
+        real_min: u64,
+        real_max: u64,
+        clamped_count: usize,
+    ) -> Result<Self> {
+        let mut result = Self::empty()?;
+        for &(value, count) in values {
+            result
+                .hist
+                .record_n(
+                    value.clamp(HISTOGRAM_LOW_BOUND_NS, HISTOGRAM_HIGH_BOUND_NS),
+                    count,
+                )
+                .map_err(|e| {
+                    ClientError::Measurement(format!("Failed to record latency: {}", e))
+                })?;
+        }
+        if !result.hist.is_empty() {
+            result.real_min = real_min;
+            result.real_max = real_max;
+        }
+        result.clamped_count = clamped_count;
+        Ok(result)
+    }
+
+    /// Record a single latency value (in nanoseconds)
+    ///
+    /// Values outside the histogram bounds are clamped; the real min/max are tracked separately.
+    /// Latencies are taken to be recorded in the order they were measured, which the
+    /// jitter is computed from.
+    pub fn record(&mut self, latency: u64) -> Result<()> {
+        self.real_min = self.real_min.min(latency);
+        self.real_max = self.real_max.max(latency);
+        if let Some(previous) = self.previous {
+            let delta = latency.abs_diff(previous);
+            self.delta_sum += delta as u128;
+            self.delta_count += 1;
+            self.delta_max = self.delta_max.max(delta);
+            self.smoothed_jitter += (delta as f64 - self.smoothed_jitter) * JITTER_GAIN;
+        }
+        self.previous = Some(latency);
+
+        let clamped = latency.clamp(HISTOGRAM_LOW_BOUND_NS, HISTOGRAM_HIGH_BOUND_NS);
+        if latency != clamped {
+            self.clamped_count += 1;
+        }
+
+        self.hist.record(clamped).map_err(|e| {
+            warn!(latency = latency, error = %e, "Failed to record latency");
+            ClientError::Measurement(format!("Failed to record latency: {}", e))
+        })
+    }
+
+    /// Get the recorded histogram values as `(value, count)` pairs
+    ///
+    /// Each value is the highest value equivalent to its histogram bucket, so recording
+    /// the pairs into a new histogram reproduces the original distribution exactly.
+    pub fn recorded_values(&self) -> Vec<(u64, u64)> {
+        self.hist
+            .iter_recorded()
+            .map(|v| (v.value_iterated_to(), v.count_at_value()))
+            .collect()
+    }
+
     /// Get the mean latency
     pub fn mean(&self) -> f64 {
         self.hist.mean()
     }
 
+    /// Get the population standard deviation of the latency, in nanoseconds
+    ///
+    /// Like the mean, it is computed from the histogram, so within its precision and over
+    /// clamped values.
+    pub fn stddev(&self) -> f64 {
+        self.hist.stdev()
+    }
+
+    /// Get the population variance of the latency, in nanoseconds squared
+    pub fn variance(&self) -> f64 {
+        self.stddev().powi(2)
+    }
+
+    /// Get the coefficient of variation: the standard deviation over the mean, or 0
+    /// without any latency
+    ///
+    /// It compares how noisy runs are whatever their latency: 0.1 means the latency
+    /// typically strays 10% from its mean.
+    pub fn coefficient_of_variation(&self) -> f64 {
+        let mean = self.mean();
+        if mean > 0.0 {
+            self.stddev() / mean
+        } else {
+            0.0
+        }
+    }
+
     /// Get the minimum latency (unclamped)
     pub fn min(&self) -> u64 {
-        self.real_min
+        if self.real_min == u64::MAX {
+            0
+        } else {
+            self.real_min
+        }
     }
 
     /// Get the maximum latency (unclamped)
@@ -91,6 +214,16 @@ impl Statistics {
         self.hist.value_at_quantile(quantile)
     }
 
+    /// Get the variation between consecutive latencies, or `None` with fewer than two
+    /// recorded in order
+    pub fn jitter(&self) -> Option<Jitter> {
+        (self.delta_count > 0).then(|| Jitter {
+            mean_ns: self.delta_sum as f64 / self.delta_count as f64,
+            max_ns: self.delta_max,
+            smoothed_ns: self.smoothed_jitter,
+        })
+    }
+
     /// Get the number of values that were clamped
     pub fn clamped_count(&self) -> usize {
         self.clamped_count
@@ -100,6 +233,72 @@ impl Statistics {
     pub fn count(&self) -> u64 {
         self.hist.len()
     }
+
+    /// Get the underlying histogram (values clamped to the histogram bounds)
+    pub fn histogram(&self) -> &Histogram<u64> {
+        &self.hist
+    }
+
+    /// Write the percentile distribution in the classic HdrHistogram `.hgrm` format
+    ///
+    /// Values are divided by `unit_ratio` (1000.0 for microseconds). The layout matches
+    /// `outputPercentileDistribution` of the Java implementation, with five percentile
+    /// ticks per half distance, so the file loads into the standard HdrHistogram plotter.
+    pub fn write_hgrm<W: Write>(&self, writer: &mut W, unit_ratio: f64) -> Result<()> {
+        let precision = HISTOGRAM_SIGNIFICANT_DIGITS as usize;
+        writeln!(
+            writer,
+            "{:>12} {:>14} {:>10} {:>14}\n",
+            "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+        )?;
+        let mut total_count = 0u64;
+        for value in self.hist.iter_quantiles(HGRM_TICKS_PER_HALF_DISTANCE) {
+            total_count += value.count_since_last_iteration();
+            let scaled = value.value_iterated_to() as f64 / unit_ratio;
+            let quantile = value.quantile_iterated_to();
+            if quantile < 1.0 {
+                writeln!(
+                    writer,
+                    "{:12.*} {:2.12} {:10} {:14.2}",
+                    precision,
+                    scaled,
+                    quantile,
+                    total_count,
+                    1.0 / (1.0 - quantile)
+                )?;
+            } else {
+                writeln!(
+                    writer,
+                    "{:12.*} {:2.12} {:10}",
+                    precision, scaled, quantile, total_count
+                )?;
+            }
+        }
+        // Sub-buckets span the smallest power of two holding 2 * 10^digits values
+        let sub_buckets = (2 * 10u64.pow(HISTOGRAM_SIGNIFICANT_DIGITS as u32)).next_power_of_two();
+        writeln!(
+            writer,
+            "#[Mean    = {:12.*}, StdDeviation   = {:12.*}]",
+            precision,
+            self.hist.mean() / unit_ratio,
+            precision,
+            self.hist.stdev() / unit_ratio
+        )?;
+        writeln!(
+            writer,
+            "#[Max     = {:12.*}, Total count    = {:12}]",
+            precision,
+            self.hist.max() as f64 / unit_ratio,
+            self.hist.len()
+        )?;
+        writeln!(
+            writer,
+            "#[Buckets = {:12}, SubBuckets     = {:12}]",
+            self.hist.buckets(),
+            sub_buckets
+        )?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -117,4 +316,123 @@ mod tests {
         assert_eq!(stats.count(), 5);
         Ok(())
     }
+
+    #[test]
+    fn test_statistics_dispersion() -> Result<()> {
+        // Mean 3000, population variance 2,000,000
+        let stats = Statistics::new(&[1000, 2000, 3000, 4000, 5000])?;
+        assert!(
+            (stats.variance() - 2_000_000.0).abs() < 20_000.0,
+            "{}",
+            stats.variance()
+        );
+        assert!((stats.stddev() - 1414.2).abs() < 10.0, "{}", stats.stddev());
+        let cv = stats.coefficient_of_variation();
+        assert!((cv - 0.4714).abs() < 0.005, "{}", cv);
+
+        let steady = Statistics::new(&[50_000; 10])?;
+        assert_eq!(steady.stddev(), 0.0);
+        assert_eq!(steady.coefficient_of_variation(), 0.0);
+        assert_eq!(Statistics::empty()?.coefficient_of_variation(), 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics_incremental_matches_batch() -> Result<()> {
+        let latencies = vec![1500, 2500, 3500, 250_000_000];
+        let batch = Statistics::new(&latencies)?;
+
+        let mut incremental = Statistics::empty()?;
+        for &latency in &latencies {
+            incremental.record(latency)?;
+        }
+
+        assert_eq!(incremental.count(), batch.count());
+        assert_eq!(incremental.min(), batch.min());
+        assert_eq!(incremental.max(), batch.max());
+        assert_eq!(incremental.clamped_count(), 1);
+        assert_eq!(incremental.percentile(0.5), batch.percentile(0.5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics_recorded_values_roundtrip() -> Result<()> {
+        let latencies = vec![1000, 1000, 2000, 40_000, 900_000];
+        let stats = Statistics::new(&latencies)?;
+
+        let rebuilt = Statistics::from_recorded(
+            &stats.recorded_values(),
+            stats.min(),
+            stats.max(),
+            stats.clamped_count(),
+        )?;
+
+        assert_eq!(rebuilt.count(), stats.count());
+        assert_eq!(rebuilt.min(), 1000);
+        assert_eq!(rebuilt.max(), 900_000);
+        assert_eq!(rebuilt.mean(), stats.mean());
+        assert_eq!(rebuilt.percentile(0.99), stats.percentile(0.99));
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics_empty() -> Result<()> {
+        let stats = Statistics::empty()?;
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.min(), 0);
+        assert_eq!(stats.max(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics_write_hgrm() -> Result<()> {
+        let latencies: Vec<u64> = (1..=1000).map(|i| i * 1000).collect();
+        let stats = Statistics::new(&latencies)?;
+        let mut hgrm = Vec::new();
+        stats.write_hgrm(&mut hgrm, 1000.0)?;
+        let hgrm = String::from_utf8(hgrm).unwrap();
+        let lines: Vec<&str> = hgrm.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "       Value     Percentile TotalCount 1/(1-Percentile)"
+        );
+        assert_eq!(lines[1], "");
+        assert_eq!(
+            lines[2],
+            "       1.023 0.000000000000          1           1.00"
+        );
+        let last = lines[lines.len() - 4];
+        assert!(last.ends_with(" 1.000000000000       1000"), "{}", last);
+        assert!(lines[lines.len() - 3].starts_with("#[Mean    =      500."));
+        assert_eq!(
+            lines[lines.len() - 2],
+            "#[Max     =     1000.447, Total count    =         1000]"
+        );
+        assert!(lines[lines.len() - 1].ends_with("SubBuckets     =         2048]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jitter() -> Result<()> {
+        assert_eq!(Statistics::new(&[1000])?.jitter(), None);
+
+        let stats = Statistics::new(&[1000, 3000, 2000, 2000])?;
+        let jitter = stats.jitter().unwrap();
+        assert_eq!(jitter.mean_ns, 1000.0);
+        assert_eq!(jitter.max_ns, 2000);
+        // J += (|D| - J) / 16 over D = 2000, 1000, 0
+        let expected = [2000.0, 1000.0, 0.0]
+            .iter()
+            .fold(0.0, |j: f64, d| j + (d - j) / 16.0);
+        assert!((jitter.smoothed_ns - expected).abs() < 1e-9);
+
+        // A steady latency has no jitter, however high it is
+        let steady = Statistics::new(&[50_000; 10])?.jitter().unwrap();
+        assert_eq!((steady.mean_ns, steady.max_ns), (0.0, 0));
+
+        let rebuilt = Statistics::from_recorded(&stats.recorded_values(), 1000, 3000, 0)?;
+        assert_eq!(rebuilt.jitter(), None);
+        Ok(())
+    }
 }
@@ -0,0 +1,376 @@
+//! Host-noise monitoring during a measurement
+//!
+//! A latency spike can come from the network or from the host running the client. The
+//! [`SystemMonitor`] samples `/proc/loadavg` and the context-switch and CPU-migration
+//! counters of the measuring thread from a background thread, and the resulting
+//! [`SystemTimeline`] marks the windows where those counters spiked, together with the
+//! worst RTT seen in each window. Linux only.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Interval between two samples of the system counters
+pub const SYSTEM_MONITOR_INTERVAL_MS: u64 = 250;
+
+/// Load average per CPU above which a window is annotated as host overload
+const LOADAVG_PER_CPU_SPIKE: f64 = 1.0;
+
+/// A counter spikes when a window reaches this multiple of the run's median window
+///
+/// Relative rather than absolute: on small hosts every reply wake-up can preempt the
+/// client, which is steady background rather than an event worth annotating.
+const COUNTER_SPIKE_FACTOR: u64 = 2;
+
+/// Scheduler counters of one thread
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ThreadCounters {
+    voluntary_switches: u64,
+    involuntary_switches: u64,
+    migrations: Option<u64>,
+}
+
+/// System state over one sampling window
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemSample {
+    /// End of the window, relative to the start of monitoring
+    pub offset: Duration,
+    /// One-minute load average at the end of the window
+    pub loadavg: f64,
+    /// Voluntary context switches of the measuring thread during the window
+    pub voluntary_switches: u64,
+    /// Involuntary context switches (preemptions) of the measuring thread during the window
+    pub involuntary_switches: u64,
+    /// CPU migrations of the measuring thread during the window (`None` if unavailable)
+    pub migrations: Option<u64>,
+    /// Largest RTT observed during the window in nanoseconds (0 if none)
+    pub max_latency_ns: u64,
+}
+
+/// A window in which the host showed activity that can distort latency
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemEvent {
+    /// Start of the window, relative to the start of monitoring
+    pub start: Duration,
+    /// End of the window, relative to the start of monitoring
+    pub end: Duration,
+    /// What happened (e.g. "3 preemptions, 1 CPU migration")
+    pub description: String,
+    /// Largest RTT observed during the window in nanoseconds
+    pub max_latency_ns: u64,
+}
+
+/// Samples collected by a [`SystemMonitor`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemTimeline {
+    /// Samples in chronological order
+    pub samples: Vec<SystemSample>,
+    /// Number of CPUs available to the process
+    pub cpus: usize,
+}
+
+impl SystemTimeline {
+    /// Windows where preemptions or CPU migrations of the measuring thread spiked, or the
+    /// host was overloaded
+    pub fn events(&self) -> Vec<SystemEvent> {
+        let preemption_threshold =
+            spike_threshold(self.samples.iter().map(|s| s.involuntary_switches));
+        let migration_threshold = spike_threshold(self.samples.iter().filter_map(|s| s.migrations));
+        let mut start = Duration::ZERO;
+        let mut events = Vec::new();
+
+        for sample in &self.samples {
+            let mut reasons = Vec::new();
+            if sample.involuntary_switches >= preemption_threshold {
+                reasons.push(format!("{} preemption(s)", sample.involuntary_switches));
+            }
+            if let Some(migrations) = sample.migrations.filter(|&m| m >= migration_threshold) {
+                reasons.push(format!("{} CPU migration(s)", migrations));
+            }
+            if self.cpus > 0 && sample.loadavg / self.cpus as f64 > LOADAVG_PER_CPU_SPIKE {
+                reasons.push(format!(
+                    "load average {:.2} on {} CPU(s)",
+                    sample.loadavg, self.cpus
+                ));
+            }
+
+            if !reasons.is_empty() {
+                events.push(SystemEvent {
+                    start,
+                    end: sample.offset,
+                    description: reasons.join(", "),
+                    max_latency_ns: sample.max_latency_ns,
+                });
+            }
+            start = sample.offset;
+        }
+        events
+    }
+
+    /// Total involuntary context switches over the whole run
+    pub fn total_involuntary_switches(&self) -> u64 {
+        self.samples.iter().map(|s| s.involuntary_switches).sum()
+    }
+
+    /// Total CPU migrations over the whole run (`None` if unavailable)
+    pub fn total_migrations(&self) -> Option<u64> {
+        self.samples.iter().map(|s| s.migrations).sum()
+    }
+}
+
+/// Smallest per-window count considered a spike, given the counts of all windows
+fn spike_threshold(values: impl Iterator<Item = u64>) -> u64 {
+    let mut values: Vec<u64> = values.collect();
+    if values.is_empty() {
+        return 1;
+    }
+    values.sort_unstable();
+    let median = values[values.len() / 2];
+    median.saturating_mul(COUNTER_SPIKE_FACTOR).max(1)
+}
+
+/// State shared between the measuring thread and the sampling thread
+struct MonitorState {
+    stopped: AtomicBool,
+    window_max_latency_ns: AtomicU64,
+}
+
+/// Background sampler of host and measuring-thread scheduler counters
+pub struct SystemMonitor {
+    state: Arc<MonitorState>,
+    handle: Option<JoinHandle<Vec<SystemSample>>>,
+}
+
+impl SystemMonitor {
+    /// Start monitoring the calling thread, which must be the thread that measures
+    ///
+    /// Returns `None` (with a warning) where `/proc` is unavailable, e.g. on non-Linux
+    /// systems, so the run continues without annotations.
+    pub fn start() -> Option<Self> {
+        let task_dir = match fs::read_link("/proc/thread-self") {
+            Ok(link) => PathBuf::from("/proc").join(link),
+            Err(e) => {
+                warn!(error = %e, "System monitoring unavailable (requires Linux /proc)");
+                return None;
+            }
+        };
+
+        let state = Arc::new(MonitorState {
+            stopped: AtomicBool::new(false),
+            window_max_latency_ns: AtomicU64::new(0),
+        });
+        let thread_state = Arc::clone(&state);
+        let interval = Duration::from_millis(SYSTEM_MONITOR_INTERVAL_MS);
+
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let mut previous = read_thread_counters(&task_dir).unwrap_or_default();
+            let mut samples = Vec::new();
+
+            while !thread_state.stopped.load(Ordering::Relaxed) {
+                thread::sleep(interval);
+                let Some(current) = read_thread_counters(&task_dir) else {
+                    break; // the measuring thread has exited
+                };
+                samples.push(SystemSample {
+                    offset: started.elapsed(),
+                    loadavg: read_loadavg().unwrap_or(0.0),
+                    voluntary_switches: current
+                        .voluntary_switches
+                        .saturating_sub(previous.voluntary_switches),
+                    involuntary_switches: current
+                        .involuntary_switches
+                        .saturating_sub(previous.involuntary_switches),
+                    migrations: current
+                        .migrations
+                        .zip(previous.migrations)
+                        .map(|(now, before)| now.saturating_sub(before)),
+                    max_latency_ns: thread_state
+                        .window_max_latency_ns
+                        .swap(0, Ordering::Relaxed),
+                });
+                previous = current;
+            }
+            samples
+        });
+
+        debug!(
+            interval_ms = SYSTEM_MONITOR_INTERVAL_MS,
+            "System monitor started"
+        );
+        Some(Self {
+            state,
+            handle: Some(handle),
+        })
+    }
+
+    /// Report an RTT so spikes can be attributed to the current window
+    #[inline]
+    pub fn observe(&self, latency_ns: u64) {
+        self.state
+            .window_max_latency_ns
+            .fetch_max(latency_ns, Ordering::Relaxed);
+    }
+
+    /// Stop sampling and return the collected timeline
+    pub fn finish(mut self) -> SystemTimeline {
+        self.state.stopped.store(true, Ordering::Relaxed);
+        let samples = self
+            .handle
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+        SystemTimeline {
+            samples,
+            cpus: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Drop for SystemMonitor {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Read the context-switch and migration counters of the thread at `task_dir`
+fn read_thread_counters(task_dir: &std::path::Path) -> Option<ThreadCounters> {
+    let status = fs::read_to_string(task_dir.join("status")).ok()?;
+    let mut counters = parse_status(&status)?;
+    // `sched` only exists on kernels built with CONFIG_SCHED_DEBUG
+    counters.migrations = fs::read_to_string(task_dir.join("sched"))
+        .ok()
+        .and_then(|sched| parse_migrations(&sched));
+    Some(counters)
+}
+
+/// Parse the context-switch counters of a `/proc/<pid>/task/<tid>/status` file
+fn parse_status(status: &str) -> Option<ThreadCounters> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse().ok())
+    };
+    Some(ThreadCounters {
+        voluntary_switches: field("voluntary_ctxt_switches:")?,
+        involuntary_switches: field("nonvoluntary_ctxt_switches:")?,
+        migrations: None,
+    })
+}
+
+/// Parse `se.nr_migrations` from a `/proc/<pid>/task/<tid>/sched` file
+fn parse_migrations(sched: &str) -> Option<u64> {
+    sched.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == "se.nr_migrations")
+            .then(|| value.trim().parse().ok())
+            .flatten()
+    })
+}
+
+/// Read the one-minute load average
+fn read_loadavg() -> Option<f64> {
+    parse_loadavg(&fs::read_to_string("/proc/loadavg").ok()?)
+}
+
+/// Parse the one-minute load average of a `/proc/loadavg` line
+fn parse_loadavg(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(
+        offset_ms: u64,
+        involuntary: u64,
+        migrations: Option<u64>,
+        loadavg: f64,
+    ) -> SystemSample {
+        SystemSample {
+            offset: Duration::from_millis(offset_ms),
+            loadavg,
+            voluntary_switches: 100,
+            involuntary_switches: involuntary,
+            migrations,
+            max_latency_ns: offset_ms * 1000,
+        }
+    }
+
+    #[test]
+    fn test_sysmon_parse_proc_files() {
+        let status =
+            "Name:\tclient\nvoluntary_ctxt_switches:\t1234\nnonvoluntary_ctxt_switches:\t56\n";
+        let counters = parse_status(status).unwrap();
+        assert_eq!(counters.voluntary_switches, 1234);
+        assert_eq!(counters.involuntary_switches, 56);
+        assert!(parse_status("Name:\tclient\n").is_none());
+
+        let sched = "se.exec_start   :   1.0\nse.nr_migrations                             :                    7\n";
+        assert_eq!(parse_migrations(sched), Some(7));
+        assert_eq!(parse_migrations("se.exec_start : 1.0\n"), None);
+
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/123 4567\n"), Some(0.52));
+    }
+
+    #[test]
+    fn test_sysmon_timeline_events() {
+        let timeline = SystemTimeline {
+            samples: vec![
+                sample(250, 0, Some(0), 0.5),
+                sample(500, 3, Some(1), 0.5),
+                sample(750, 0, None, 5.0),
+                sample(1000, 0, Some(0), 0.5),
+            ],
+            cpus: 2,
+        };
+        let events = timeline.events();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].start, Duration::from_millis(250));
+        assert_eq!(events[0].end, Duration::from_millis(500));
+        assert_eq!(events[0].description, "3 preemption(s), 1 CPU migration(s)");
+        assert_eq!(events[0].max_latency_ns, 500_000);
+        assert!(events[1].description.starts_with("load average 5.00"));
+
+        assert_eq!(timeline.total_involuntary_switches(), 3);
+        assert_eq!(timeline.total_migrations(), None);
+    }
+
+    #[test]
+    fn test_sysmon_steady_preemptions_are_not_events() {
+        let timeline = SystemTimeline {
+            samples: vec![
+                sample(250, 100, Some(0), 0.5),
+                sample(500, 120, Some(0), 0.5),
+                sample(750, 110, Some(0), 0.5),
+                sample(1000, 400, Some(0), 0.5),
+            ],
+            cpus: 2,
+        };
+        let events = timeline.events();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].start, Duration::from_millis(750));
+        assert_eq!(events[0].description, "400 preemption(s)");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sysmon_collects_samples() {
+        let monitor = SystemMonitor::start().expect("/proc is available on Linux");
+        monitor.observe(42_000);
+        thread::sleep(Duration::from_millis(SYSTEM_MONITOR_INTERVAL_MS * 2 + 50));
+        let timeline = monitor.finish();
+
+        assert!(!timeline.samples.is_empty());
+        assert_eq!(timeline.samples[0].max_latency_ns, 42_000);
+        assert!(timeline.cpus >= 1);
+    }
+}
@@ -0,0 +1,276 @@
+//! Application-shaped probe payloads
+//!
+//! Probes are normally the Synapse header padded with zeros, which some middleboxes
+//! treat differently from real traffic: a DPI engine classifies a flow by its first
+//! bytes, a WAN optimizer compresses runs of zeros away, an IDS only inspects what
+//! parses as a protocol. `--payload-template KIND:BYTES` fills the padding with a
+//! message shaped like application traffic instead, so the probes take the path real
+//! messages take. The Synapse header stays in front, so the probes are still echoed
+//! and timed as usual, but a middlebox that matches a protocol only at the very start
+//! of each packet sees the header rather than the message.
+//!
+//! - `http`: an HTTP/1.1 POST with a JSON body whose `Content-Length` matches it
+//! - `protobuf`: a protobuf message in wire format, mostly a `bytes` field of random
+//!   (incompressible) data
+//!
+//! Filler characters and bytes are drawn from the run seed, so runs with the same
+//! `--seed` send the same payload. Every probe of a run carries the same payload.
+
+use crate::client::rng::SeededRng;
+use crate::protocol::{MAX_PACKET_SIZE, PACKET_SIZE};
+use std::fmt;
+use std::str::FromStr;
+
+/// Stream of the run seed the filler is drawn from
+const PAYLOAD_STREAM: &str = "payload";
+
+/// Characters of the HTTP body's filler string, which must not need escaping in JSON
+const HTTP_FILLER: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Name of the protobuf message type, carried in its string field
+const PROTOBUF_TYPE: &str = "synapse.Probe";
+
+/// Kind of application message a template imitates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKind {
+    /// HTTP/1.1 request with a JSON body
+    Http,
+    /// Protobuf message in wire format
+    Protobuf,
+}
+
+impl fmt::Display for TemplateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateKind::Http => write!(f, "http"),
+            TemplateKind::Protobuf => write!(f, "protobuf"),
+        }
+    }
+}
+
+/// Shape and size of the probes of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadTemplate {
+    /// Message imitated by the padding
+    pub kind: TemplateKind,
+    /// Size of every probe in bytes, Synapse header included
+    pub size: usize,
+}
+
+impl PayloadTemplate {
+    /// Smallest probe size that holds a complete message of `kind`
+    pub fn min_size(kind: TemplateKind) -> usize {
+        PACKET_SIZE
+            + match kind {
+                TemplateKind::Http => http_head(http_body_len(0), 0).len() + http_body_len(0),
+                TemplateKind::Protobuf => protobuf_head().len() + 1,
+            }
+    }
+
+    /// Bytes following the Synapse header of every probe, `size - PACKET_SIZE` long
+    pub fn payload(&self, seed: u64) -> Vec<u8> {
+        let len = self.size - PACKET_SIZE;
+        let mut rng = SeededRng::stream(seed, PAYLOAD_STREAM);
+        let payload = match self.kind {
+            TemplateKind::Http => {
+                // The head grows with the digits of Content-Length; where one more
+                // digit would overshoot, whitespace before the value takes the gap
+                let mut body_len = len - http_head(0, 0).len();
+                while http_head(body_len, 0).len() + body_len > len {
+                    body_len -= 1;
+                }
+                let gap = len - http_head(body_len, 0).len() - body_len;
+                let mut payload = http_head(body_len, gap).into_bytes();
+                payload.extend_from_slice(b"{\"data\":\"");
+                for _ in 0..body_len - http_body_len(0) {
+                    let index = rng.next_u64() as usize % HTTP_FILLER.len();
+                    payload.push(HTTP_FILLER[index]);
+                }
+                payload.extend_from_slice(b"\"}");
+                payload
+            }
+            TemplateKind::Protobuf => {
+                // The length prefix is encoded in as many bytes as the longest it could
+                // need, which decoders accept, so the data field fills the rest exactly
+                let mut payload = protobuf_head();
+                let available = len - payload.len();
+                let prefix_len = varint_len(available as u64);
+                let data_len = available - prefix_len;
+                let mut value = data_len as u64;
+                for i in 0..prefix_len {
+                    let more = if i + 1 < prefix_len { 0x80 } else { 0 };
+                    payload.push((value & 0x7f) as u8 | more);
+                    value >>= 7;
+                }
+                let start = payload.len();
+                payload.resize(start + data_len, 0);
+                rng.fill_bytes(&mut payload[start..]);
+                payload
+            }
+        };
+        debug_assert_eq!(payload.len(), len);
+        payload
+    }
+}
+
+impl fmt::Display for PayloadTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.size)
+    }
+}
+
+impl FromStr for PayloadTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, size) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("invalid payload template '{}' (expected KIND:BYTES)", s))?;
+        let kind = match kind.to_ascii_lowercase().as_str() {
+            "http" => TemplateKind::Http,
+            "protobuf" => TemplateKind::Protobuf,
+            other => {
+                return Err(format!(
+                    "unknown payload template '{}' (expected http or protobuf)",
+                    other
+                ))
+            }
+        };
+        let size: usize = size
+            .parse()
+            .map_err(|_| format!("invalid probe size '{}'", size))?;
+        let min_size = Self::min_size(kind);
+        if !(min_size..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(format!(
+                "a {} probe must be between {} and {} bytes",
+                kind, min_size, MAX_PACKET_SIZE
+            ));
+        }
+        Ok(Self { kind, size })
+    }
+}
+
+/// Request line and headers of an HTTP probe with a `body_len`-byte body, with `gap`
+/// extra spaces before the Content-Length value
+fn http_head(body_len: usize, gap: usize) -> String {
+    format!(
+        "POST /api/v1/events HTTP/1.1\r\n\
+         Host: synapse.invalid\r\n\
+         User-Agent: synapse-probe\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}{}\r\n\
+         \r\n",
+        " ".repeat(gap),
+        body_len
+    )
+}
+
+/// Length of an HTTP probe's JSON body around `filler_len` filler characters
+fn http_body_len(filler_len: usize) -> usize {
+    r#"{"data":""}"#.len() + filler_len
+}
+
+/// Fields of a protobuf probe up to the length prefix of its data field
+///
+/// Field 1 (varint) is a version, field 2 (string) the message type and field 3
+/// (bytes) the data.
+fn protobuf_head() -> Vec<u8> {
+    let mut head = vec![0x08, 0x01, 0x12, PROTOBUF_TYPE.len() as u8];
+    head.extend_from_slice(PROTOBUF_TYPE.as_bytes());
+    head.push(0x1a);
+    head
+}
+
+/// Bytes in the protobuf varint encoding of `value`
+fn varint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_parsing() {
+        let template: PayloadTemplate = "http:512".parse().unwrap();
+        assert_eq!(template.kind, TemplateKind::Http);
+        assert_eq!(template.size, 512);
+        assert_eq!(template.to_string(), "http:512");
+        assert_eq!(
+            "Protobuf:100".parse::<PayloadTemplate>().unwrap().kind,
+            TemplateKind::Protobuf
+        );
+        assert!("http".parse::<PayloadTemplate>().is_err());
+        assert!("grpc:512".parse::<PayloadTemplate>().is_err());
+        assert!("http:100".parse::<PayloadTemplate>().is_err(), "too small");
+        assert!("protobuf:65508".parse::<PayloadTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_http_payload() {
+        for size in [
+            PayloadTemplate::min_size(TemplateKind::Http),
+            256,
+            1400,
+            65_507,
+        ] {
+            let template = PayloadTemplate {
+                kind: TemplateKind::Http,
+                size,
+            };
+            let payload = template.payload(7);
+            assert_eq!(payload.len(), size - PACKET_SIZE);
+            assert_eq!(payload, template.payload(7), "same seed, same payload");
+
+            let text = String::from_utf8(payload).unwrap();
+            let (head, body) = text.split_once("\r\n\r\n").unwrap();
+            assert!(head.starts_with("POST /api/v1/events HTTP/1.1\r\n"));
+            let length = head.rsplit_once("Content-Length:").unwrap().1;
+            assert_eq!(length.trim().parse::<usize>().unwrap(), body.len());
+            assert!(body.starts_with(r#"{"data":""#) && body.ends_with(r#""}"#));
+        }
+    }
+
+    #[test]
+    fn test_protobuf_payload() {
+        for size in [
+            PayloadTemplate::min_size(TemplateKind::Protobuf),
+            100,
+            1400,
+            65_507,
+        ] {
+            let template = PayloadTemplate {
+                kind: TemplateKind::Protobuf,
+                size,
+            };
+            let payload = template.payload(7);
+            assert_eq!(payload.len(), size - PACKET_SIZE);
+
+            // Walk the fields as a decoder would: every byte belongs to one of them
+            let mut rest = &payload[..];
+            let mut fields = Vec::new();
+            while let Some((&key, tail)) = rest.split_first() {
+                rest = tail;
+                fields.push(key >> 3);
+                match key & 0x07 {
+                    0 => rest = &rest[1..],
+                    2 => {
+                        let (mut len, mut shift, mut used) = (0usize, 0, 0);
+                        for byte in rest {
+                            len |= ((byte & 0x7f) as usize) << shift;
+                            shift += 7;
+                            used += 1;
+                            if byte & 0x80 == 0 {
+                                break;
+                            }
+                        }
+                        rest = &rest[used + len..];
+                    }
+                    wire_type => panic!("unexpected wire type {}", wire_type),
+                }
+            }
+            assert_eq!(fields, [1, 2, 3]);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Color themes of the terminal output
+//!
+//! The report, the live view and the OSI visualizer color results by meaning: good,
+//! bad, warning and accent. The default theme uses green and red, which look alike
+//! to readers with red-green color vision deficiency. `--theme colorblind` maps the
+//! same meanings to the Okabe-Ito palette (blue for good, vermillion for bad), and
+//! `--theme mono` drops color altogether, keeping bold and reverse video. Verdicts
+//! always carry a symbol or word as well (`✓ PASS`, `✗ FAIL`), so no theme relies on
+//! color alone.
+
+use clap::ValueEnum;
+use colored::*;
+
+/// Color mapping of the terminal output
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Green for good, red for bad, yellow for warnings
+    #[default]
+    Default,
+    /// Blue for good, vermillion for bad, yellow for warnings (Okabe-Ito palette)
+    Colorblind,
+    /// No colors; bold and reverse video only
+    Mono,
+}
+
+impl Theme {
+    /// Text marking a good result (a pass, a met objective, a fast packet)
+    pub fn good(self, text: &str) -> ColoredString {
+        match self {
+            Theme::Default => text.green(),
+            Theme::Colorblind => text.truecolor(0, 114, 178),
+            Theme::Mono => text.normal(),
+        }
+    }
+
+    /// Text marking a bad result (a failure, a missed objective, an outlier)
+    pub fn bad(self, text: &str) -> ColoredString {
+        match self {
+            Theme::Default => text.red(),
+            Theme::Colorblind => text.truecolor(213, 94, 0),
+            Theme::Mono => text.normal(),
+        }
+    }
+
+    /// Text marking a warning or a result between good and bad
+    pub fn warning(self, text: &str) -> ColoredString {
+        match self {
+            Theme::Default => text.yellow(),
+            Theme::Colorblind => text.truecolor(240, 228, 66),
+            Theme::Mono => text.normal(),
+        }
+    }
+
+    /// Text set apart without judging it (headings, notable shares)
+    pub fn accent(self, text: &str) -> ColoredString {
+        match self {
+            Theme::Default => text.cyan(),
+            Theme::Colorblind => text.truecolor(86, 180, 233),
+            Theme::Mono => text.normal(),
+        }
+    }
+
+    /// Colors (RGB) of the OSI layers L7, L4, L3, L2 and L1, or `None` without colors
+    pub fn layer_colors(self) -> Option<[(u8, u8, u8); 5]> {
+        match self {
+            Theme::Default => Some([
+                (74, 144, 226),  // Blue
+                (72, 187, 120),  // Green
+                (236, 201, 75),  // Yellow
+                (237, 137, 54),  // Orange
+                (245, 101, 101), // Red
+            ]),
+            Theme::Colorblind => Some([
+                (0, 114, 178),   // Blue
+                (86, 180, 233),  // Sky blue
+                (0, 158, 115),   // Bluish green
+                (230, 159, 0),   // Orange
+                (204, 121, 167), // Reddish purple
+            ]),
+            Theme::Mono => None,
+        }
+    }
+
+    /// Template of the live view's progress bar
+    pub fn progress_bar(self) -> &'static str {
+        match self {
+            Theme::Mono => "{bar:40}",
+            _ => "{bar:40.cyan/blue}",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_colors() {
+        assert_eq!(Theme::Default.good("ok").fgcolor, Some(Color::Green));
+        assert_eq!(Theme::Default.bad("ok").fgcolor, Some(Color::Red));
+        let colorblind = Theme::Colorblind;
+        assert_ne!(colorblind.good("ok").fgcolor, Some(Color::Green));
+        assert_ne!(colorblind.good("ok").fgcolor, colorblind.bad("ok").fgcolor);
+        assert!(colorblind.layer_colors().is_some());
+
+        let mono = Theme::Mono;
+        for text in [
+            mono.good("ok"),
+            mono.bad("ok"),
+            mono.warning("ok"),
+            mono.accent("ok"),
+        ] {
+            assert!(text.is_plain());
+        }
+        assert!(mono.layer_colors().is_none());
+    }
+}
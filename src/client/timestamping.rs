@@ -0,0 +1,168 @@
+//! Kernel socket timestamps
+//!
+//! The RTT measured with `Instant::now()` includes everything between the application
+//! and the network on the client: the send and receive system calls, the protocol stack
+//! and the wakeup of the measuring thread. With `--kernel-timestamps` the client also
+//! asks the kernel (Linux `SO_TIMESTAMPING`) for the time each probe was handed to the
+//! network device and each reply came off it. The round trip between the two excludes
+//! the client host's software, and what remains of the application RTT is the time
+//! spent in the application and its system calls.
+//!
+//! Send timestamps are read from the socket's error queue, receive timestamps from the
+//! control message of every read. Both are software timestamps on `CLOCK_REALTIME`.
+//! The same read also picks up the TOS byte of a reply when `--dscp` has the socket
+//! report it (see [`crate::client::dscp`]).
+
+pub(crate) use imp::{enable, latest_tx, recv};
+
+/// Kernel timestamps of one exchange, in nanoseconds since the UNIX epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelTimestamps {
+    /// Probe handed to the network device
+    pub tx_ns: u64,
+    /// Reply taken off the network device
+    pub rx_ns: u64,
+}
+
+impl KernelTimestamps {
+    /// Round trip as seen by the kernel
+    pub fn rtt_ns(&self) -> u64 {
+        self.rx_ns.saturating_sub(self.tx_ns)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use crate::client::dscp;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::time::Duration;
+
+    /// `struct scm_timestamping`: software, deprecated and hardware timestamps
+    type ScmTimestamping = [libc::timespec; 3];
+
+    /// Turn on software send and receive timestamps for `socket`
+    pub(crate) fn enable<S: AsRawFd>(socket: &S) -> io::Result<()> {
+        let flags: libc::c_uint = libc::SOF_TIMESTAMPING_TX_SOFTWARE
+            | libc::SOF_TIMESTAMPING_RX_SOFTWARE
+            | libc::SOF_TIMESTAMPING_SOFTWARE
+            | libc::SOF_TIMESTAMPING_OPT_TSONLY;
+        // SAFETY: `flags` is a valid c_uint of the given size
+        let rc = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_TIMESTAMPING,
+                (&flags as *const libc::c_uint).cast(),
+                std::mem::size_of::<libc::c_uint>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Read from `socket` into `buf`, returning the length, the kernel receive
+    /// timestamp and the TOS byte of the packet
+    pub(crate) fn recv<S: AsRawFd>(
+        socket: &S,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, Option<u64>, Option<u8>)> {
+        recvmsg(socket, buf, 0)
+    }
+
+    /// Kernel send timestamp of the last packet sent on `socket`, if one was queued
+    ///
+    /// The error queue is drained, discarding the timestamps of earlier packets (whose
+    /// replies timed out).
+    pub(crate) fn latest_tx<S: AsRawFd>(socket: &S) -> io::Result<Option<u64>> {
+        let mut latest = None;
+        loop {
+            match recvmsg(socket, &mut [], libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) {
+                Ok((_, timestamp, _)) => latest = timestamp.or(latest),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(latest),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// `recvmsg` into `buf`, returning the length, the software timestamp and the TOS
+    /// byte, if any
+    fn recvmsg<S: AsRawFd>(
+        socket: &S,
+        buf: &mut [u8],
+        flags: libc::c_int,
+    ) -> io::Result<(usize, Option<u64>, Option<u8>)> {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+        // Room for the timestamping, extended-error and TOS messages, as u64 words for
+        // alignment
+        let mut control = [0u64; 16];
+        // SAFETY: msghdr is plain data; all-zero is a valid starting value
+        let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = std::mem::size_of_val(&control) as _;
+
+        // SAFETY: every buffer referenced by `message` outlives the call
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, flags) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut timestamp = None;
+        // SAFETY: the control buffer was filled by recvmsg and is walked with the
+        // kernel's CMSG macros
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&message);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                    && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPING
+                {
+                    let [software, ..] =
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const ScmTimestamping);
+                    if software.tv_sec != 0 || software.tv_nsec != 0 {
+                        let ts = Duration::new(software.tv_sec as u64, software.tv_nsec as u32);
+                        timestamp = Some(ts.as_nanos() as u64);
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&message, cmsg);
+            }
+        }
+        // SAFETY: as above
+        let tos = unsafe { dscp::tos_of(&message) };
+        Ok((len as usize, timestamp, tos))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "kernel timestamps require Linux",
+        )
+    }
+
+    pub(crate) fn enable<S>(_socket: &S) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(crate) fn recv<S>(
+        _socket: &S,
+        _buf: &mut [u8],
+    ) -> io::Result<(usize, Option<u64>, Option<u8>)> {
+        Err(unsupported())
+    }
+
+    pub(crate) fn latest_tx<S>(_socket: &S) -> io::Result<Option<u64>> {
+        Err(unsupported())
+    }
+}
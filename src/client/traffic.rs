@@ -0,0 +1,76 @@
+//! Traffic estimate of a run, for metered links
+//!
+//! Over a cellular or satellite link every byte is billed, and a default run sends over a
+//! hundred thousand probes. Every probe is sent once and echoed once at its full size, so
+//! the bytes a run puts on the wire follow from its flags before it starts: the client
+//! prints the estimate with the run's header, and `--max-bytes` refuses to start a run
+//! whose estimate is above the cap. Lost probes are never echoed, so the estimate is an
+//! upper bound.
+
+use crate::client::pacing::Bandwidth;
+use std::time::Duration;
+
+/// Bytes a run puts on the wire, in both directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrafficEstimate {
+    /// Probes sent: warmup, clock sync and measurement of every connection and run
+    pub probes: u64,
+    /// Bytes of every probe on the wire, IP and transport headers included
+    pub probe_bytes: u64,
+    /// Measurement probes of one connection and run, and the rate they are paced at, if
+    /// any
+    pub paced: Option<(u64, u64)>,
+}
+
+impl TrafficEstimate {
+    /// Bytes the client sends; the echoes add as many again
+    pub fn bytes_each_way(&self) -> u64 {
+        self.probes.saturating_mul(self.probe_bytes)
+    }
+
+    /// Bytes of the probes and their echoes
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_each_way().saturating_mul(2)
+    }
+
+    /// Duration of a paced measurement phase and the bandwidth it takes each way, with
+    /// every connection sending at the rate
+    pub fn paced_load(&self, connections: usize) -> Option<(Duration, Bandwidth)> {
+        let (packets, rate) = self.paced?;
+        let bits_per_sec = rate
+            .saturating_mul(self.probe_bytes)
+            .saturating_mul(8)
+            .saturating_mul(connections as u64);
+        Some((
+            Duration::from_secs_f64(packets as f64 / rate.max(1) as f64),
+            Bandwidth::from_bits_per_sec(bits_per_sec),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traffic_estimate() {
+        // 1000 probes of 64 bytes plus 48 bytes of IPv6 and UDP headers
+        let estimate = TrafficEstimate {
+            probes: 1000,
+            probe_bytes: 112,
+            paced: Some((800, 100)),
+        };
+        assert_eq!(estimate.bytes_each_way(), 112_000);
+        assert_eq!(estimate.total_bytes(), 224_000);
+
+        let (duration, bandwidth) = estimate.paced_load(2).unwrap();
+        assert_eq!(duration, Duration::from_secs(8));
+        assert_eq!(bandwidth.bits_per_sec(), 100 * 112 * 8 * 2);
+
+        let unpaced = TrafficEstimate {
+            paced: None,
+            ..estimate
+        };
+        assert_eq!(unpaced.paced_load(1), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Synthetic transactions: several request packets answered by several responses
+//!
+//! A probe is one packet each way, but many protocols need more for a single operation:
+//! a request split over several packets, or an answer that streams back in pieces. With
+//! `--transaction R:S` every probe of the run becomes a transaction of R request packets
+//! answered by S response packets, and its RTT is the completion time of the whole
+//! exchange, from the first request's send until the last response arrived.
+//!
+//! The requests share the transaction's sequence number and go out back to back. The
+//! server stays stateless: every request but the last asks it for no echo (see
+//! [`Packet::replies`]), and the last asks for S, which it sends back numbered by their
+//! reply index. A transaction whose responses do not all arrive in time is lost. Over
+//! UDP a lost request other than the last goes unnoticed, as nothing answers it.
+//!
+//! The server timestamps split the last request's round trip, to the last response.
+//! Kernel timestamps pair one send with one receive, so a run of transactions does not
+//! take them.
+
+use crate::client::clock::Clock;
+use crate::client::error::{ClientError, Result};
+use crate::client::measurement::{PathTiming, ProbeTiming};
+use crate::client::socket::NetworkSocket;
+use crate::protocol::{unix_time_ns, Packet, MAX_REPLIES};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+/// Shape of a transaction: request packets sent, then response packets awaited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transaction {
+    /// Request packets, from 1 to [`MAX_REPLIES`]
+    pub requests: u32,
+    /// Response packets, from 1 to [`MAX_REPLIES`]
+    pub responses: u32,
+}
+
+impl Transaction {
+    /// Packets a transaction puts on the wire each way at most, for traffic estimates
+    pub fn packets(&self) -> u32 {
+        self.requests.max(self.responses)
+    }
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.requests, self.responses)
+    }
+}
+
+impl FromStr for Transaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (requests, responses) = s
+            .split_once(':')
+            .ok_or_else(|| format!("transaction '{}' must be REQUESTS:RESPONSES", s))?;
+        let count = |value: &str, what: &str| {
+            value
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|count| (1..=MAX_REPLIES).contains(count))
+                .ok_or_else(|| {
+                    format!(
+                        "{} '{}' of transaction '{}' must be from 1 to {}",
+                        what, value, s, MAX_REPLIES
+                    )
+                })
+        };
+        Ok(Self {
+            requests: count(requests, "requests")?,
+            responses: count(responses, "responses")?,
+        })
+    }
+}
+
+/// Measure one transaction of `packet`'s sequence number and size
+///
+/// The RTT runs from before the first send until the last response arrived, and the
+/// send time adds up the send calls; the rest of the RTT counts as receive wait.
+pub fn measure_transaction<S: NetworkSocket>(
+    socket: &mut S,
+    transaction: Transaction,
+    packet: &Packet,
+    clock: &Clock,
+) -> Result<ProbeTiming> {
+    let sequence = packet.sequence;
+    let mut packet = packet.clone();
+    for _ in 0..transaction.requests {
+        socket.wait_to_send(&packet)?;
+    }
+    let t1 = clock.now_ns();
+    let mut send_ns = 0;
+    let mut client_send_ns = 0;
+    for request in 1..=transaction.requests {
+        let last = request == transaction.requests;
+        packet.replies = if last { transaction.responses } else { 0 };
+        client_send_ns = unix_time_ns();
+        packet.client_send_ns = Some(client_send_ns);
+        let t_send = clock.now_ns();
+        socket.send_packet(&packet)?;
+        send_ns += clock.now_ns().saturating_sub(t_send);
+    }
+    debug!(
+        sequence = sequence.0,
+        requests = transaction.requests,
+        "Transaction sent"
+    );
+
+    let mut last_reply = None;
+    for _ in 0..transaction.responses {
+        match socket.recv_packet() {
+            Ok(reply) if reply.sequence == sequence => last_reply = Some(reply),
+            Ok(reply) => {
+                warn!(
+                    expected = sequence.0,
+                    received = reply.sequence.0,
+                    "Sequence mismatch"
+                );
+                last_reply = None;
+                break;
+            }
+            // Unix reports an expired SO_RCVTIMEO as EAGAIN (WouldBlock), Windows as TimedOut
+            Err(ClientError::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) =>
+            {
+                debug!(sequence = sequence.0, "Transaction response timeout");
+                last_reply = None;
+                break;
+            }
+            Err(e) => {
+                warn!(error = %e, "Error receiving packet");
+                return Err(e);
+            }
+        }
+    }
+    let t2 = clock.now_ns();
+    let client_receive_ns = unix_time_ns();
+
+    let Some(reply) = last_reply else {
+        return Ok(ProbeTiming {
+            send_ns,
+            rtt_ns: None,
+            recv_wait_ns: None,
+            path: None,
+            kernel: None,
+        });
+    };
+    let rtt_ns = t2.saturating_sub(t1);
+    debug!(
+        latency_ns = rtt_ns,
+        send_ns = send_ns,
+        "Transaction completed"
+    );
+    Ok(ProbeTiming {
+        send_ns,
+        rtt_ns: Some(rtt_ns),
+        recv_wait_ns: Some(rtt_ns.saturating_sub(send_ns)),
+        path: PathTiming::from_reply(client_send_ns, client_receive_ns, &reply),
+        kernel: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::socket::MockNetworkSocket;
+    use crate::protocol::SequenceNumber;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_parse_transaction() {
+        let transaction: Transaction = "3:2".parse().unwrap();
+        assert_eq!(
+            transaction,
+            Transaction {
+                requests: 3,
+                responses: 2
+            }
+        );
+        assert_eq!(transaction.to_string(), "3:2");
+        assert_eq!(transaction.packets(), 3);
+        assert!("3".parse::<Transaction>().is_err());
+        assert!("0:2".parse::<Transaction>().is_err());
+        assert!("1:17".parse::<Transaction>().is_err());
+        assert!("a:b".parse::<Transaction>().is_err());
+    }
+
+    #[test]
+    fn test_measure_transaction() -> Result<()> {
+        let seq = SequenceNumber(5);
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut mock_socket = MockNetworkSocket::new();
+        let requests = Arc::clone(&sent);
+        mock_socket
+            .expect_send_packet()
+            .times(3)
+            .returning(move |packet| {
+                requests.lock().unwrap().push(packet.replies);
+                Ok(56)
+            });
+        let mut index = 0;
+        mock_socket
+            .expect_recv_packet()
+            .times(2)
+            .returning(move || {
+                let mut reply = Packet::new(seq);
+                reply.reply_index = index;
+                index += 1;
+                Ok(reply)
+            });
+
+        let transaction = "3:2".parse().unwrap();
+        let probe = measure_transaction(
+            &mut mock_socket,
+            transaction,
+            &Packet::new(seq),
+            &Clock::default(),
+        )?;
+        // Only the last request asks for the responses
+        assert_eq!(*sent.lock().unwrap(), [0, 0, 2]);
+        let rtt_ns = probe.rtt_ns.expect("both responses arrived");
+        assert_eq!(rtt_ns, probe.send_ns + probe.recv_wait_ns.unwrap());
+
+        // A missing response loses the transaction
+        let mut mock_socket = MockNetworkSocket::new();
+        mock_socket
+            .expect_send_packet()
+            .times(1)
+            .returning(|_| Ok(56));
+        let mut replies = 0;
+        mock_socket
+            .expect_recv_packet()
+            .times(2)
+            .returning(move || {
+                replies += 1;
+                if replies == 1 {
+                    Ok(Packet::new(seq))
+                } else {
+                    Err(ClientError::Io(std::io::ErrorKind::WouldBlock.into()))
+                }
+            });
+        let transaction = "1:3".parse().unwrap();
+        let probe = measure_transaction(
+            &mut mock_socket,
+            transaction,
+            &Packet::new(seq),
+            &Clock::default(),
+        )?;
+        assert_eq!(probe.rtt_ns, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Latency over the duration of a run
+//!
+//! The histogram of a run hides when its tail happened: a P99 of 2 ms reads the same
+//! whether every second had a few slow probes or one second had all of them. The report
+//! charts the P50 and P99 of the probes sent in each slice of the run, so a burst stands
+//! out from a uniformly slow tail. The run is cut into at most [`TREND_COLUMNS`] slices
+//! of a round width (1, 2 or 5 times a power of ten), so the chart fits a terminal
+//! whatever the length of the run.
+
+use std::time::Duration;
+
+/// Most slices a run is cut into, one column of the chart each
+pub const TREND_COLUMNS: usize = 60;
+
+/// Latency of the probes sent in one slice of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrendBucket {
+    /// Start of the slice since the start of the measurement phase
+    pub start: Duration,
+    /// Answered probes sent in the slice
+    pub count: usize,
+    /// Median RTT of the slice in nanoseconds, `None` if no probe was answered
+    pub p50_ns: Option<u64>,
+    /// P99 RTT of the slice in nanoseconds, `None` if no probe was answered
+    pub p99_ns: Option<u64>,
+}
+
+/// Width of the slices of a run lasting `elapsed`: the smallest round width that cuts
+/// it into at most `columns` slices, and at least a microsecond
+pub fn bucket_width(elapsed: Duration, columns: usize) -> Duration {
+    let min_ns = (elapsed.as_nanos() as f64 / columns.max(1) as f64).max(1_000.0);
+    let mut magnitude = 10f64.powf(min_ns.log10().floor());
+    loop {
+        for step in [1.0, 2.0, 5.0] {
+            if step * magnitude >= min_ns {
+                return Duration::from_nanos((step * magnitude) as u64);
+            }
+        }
+        magnitude *= 10.0;
+    }
+}
+
+/// P50 and P99 of `latencies` by the slice of the run they were sent in
+///
+/// `send_offsets` holds the send time of each latency since the start of a phase that
+/// lasted `elapsed`, as in [`crate::client::MeasurementResult`]. Slices without an
+/// answered probe are kept, empty, so the chart shows the gap.
+pub fn latency_over_time(
+    latencies: &[u64],
+    send_offsets: &[u64],
+    elapsed: Duration,
+    columns: usize,
+) -> Vec<TrendBucket> {
+    let width = bucket_width(elapsed, columns);
+    let width_ns = width.as_nanos() as u64;
+    let last_offset = send_offsets.iter().copied().max().unwrap_or(0);
+    let slices = (elapsed.as_nanos() as u64)
+        .max(last_offset + 1)
+        .div_ceil(width_ns) as usize;
+    let mut samples: Vec<Vec<u64>> = vec![Vec::new(); slices];
+    for (&latency_ns, &offset_ns) in latencies.iter().zip(send_offsets) {
+        samples[(offset_ns / width_ns) as usize].push(latency_ns);
+    }
+
+    samples
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut slice)| {
+            slice.sort_unstable();
+            // Nearest-rank percentile
+            let percentile = |p: f64| {
+                let rank = (p * slice.len() as f64).ceil() as usize;
+                slice.get(rank.saturating_sub(1)).copied()
+            };
+            TrendBucket {
+                start: width * index as u32,
+                count: slice.len(),
+                p50_ns: percentile(0.50),
+                p99_ns: percentile(0.99),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_width() {
+        assert_eq!(
+            bucket_width(Duration::from_secs(60), 60),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            bucket_width(Duration::from_secs(61), 60),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            bucket_width(Duration::from_millis(250), 60),
+            Duration::from_millis(5)
+        );
+        assert_eq!(
+            bucket_width(Duration::from_secs(3600), 60),
+            Duration::from_secs(100)
+        );
+        assert_eq!(bucket_width(Duration::ZERO, 60), Duration::from_micros(1));
+    }
+
+    #[test]
+    fn test_latency_over_time() {
+        // Three seconds; the second one had a burst of slow probes, the third none at all
+        let mut latencies = vec![50_000; 100];
+        let mut send_offsets: Vec<u64> = (0..100).map(|i| i * 10_000_000).collect();
+        latencies.extend([2_000_000; 10]);
+        send_offsets.extend((0..10).map(|i| 1_000_000_000 + i * 1_000_000));
+
+        let buckets = latency_over_time(&latencies, &send_offsets, Duration::from_secs(3), 3);
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[0].count, 100);
+        assert_eq!(buckets[0].p99_ns, Some(50_000));
+        assert_eq!(buckets[1].start, Duration::from_secs(1));
+        assert_eq!(buckets[1].p50_ns, Some(2_000_000));
+        assert_eq!(
+            buckets[2],
+            TrendBucket {
+                start: Duration::from_secs(2),
+                count: 0,
+                p50_ns: None,
+                p99_ns: None,
+            }
+        );
+        assert!(latency_over_time(&[], &[], Duration::ZERO, 60).len() <= 1);
+    }
+}
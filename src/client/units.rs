@@ -0,0 +1,190 @@
+//! Formatting of counts and byte sizes in the report
+//!
+//! By default counts are printed exactly, with a thousands separator (`1,999`), so a
+//! large run never reads as a rounder number than it is. `--si` prints them scaled by
+//! powers of 1000 (`1.0k`, `12.5k`, `3.2M`) and `--iec` by powers of 1024 (`2.0Ki`); both
+//! keep one decimal and round rather than truncate. `--thousands-separator`
+//! picks the separator for the local convention (`.`, `'`, a space, or none).
+//!
+//! Byte sizes given on the command line ([`parse_bytes`]) take either kind of prefix.
+
+/// Unit prefixes used for large values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnitSystem {
+    /// Exact values with thousands separators
+    #[default]
+    Exact,
+    /// Decimal prefixes: k, M, G, T (powers of 1000)
+    Si,
+    /// Binary prefixes: Ki, Mi, Gi, Ti (powers of 1024)
+    Iec,
+}
+
+/// How the reporter prints counts and byte sizes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub units: UnitSystem,
+    pub thousands_separator: String,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            units: UnitSystem::Exact,
+            thousands_separator: ",".to_string(),
+        }
+    }
+}
+
+/// Decimal prefixes, in powers of 1000
+const SI_PREFIXES: [&str; 4] = ["k", "M", "G", "T"];
+/// Binary prefixes, in powers of 1024
+const IEC_PREFIXES: [&str; 4] = ["Ki", "Mi", "Gi", "Ti"];
+
+impl NumberFormat {
+    /// Format a count (`1,999`, `2.0k` or `2.0Ki`)
+    pub fn count(&self, value: u64) -> String {
+        match self.scaled(value) {
+            Some((number, prefix)) => format!("{}{}", number, prefix),
+            None => self.separated(value),
+        }
+    }
+
+    /// Format a size in bytes (`1,999 B`, `2.0 kB` or `2.0 KiB`)
+    pub fn bytes(&self, value: u64) -> String {
+        match self.scaled(value) {
+            Some((number, prefix)) => format!("{} {}B", number, prefix),
+            None => format!("{} B", self.separated(value)),
+        }
+    }
+
+    /// `value` scaled to the prefixes of the unit system, if it has any
+    fn scaled(&self, value: u64) -> Option<(String, &'static str)> {
+        match self.units {
+            UnitSystem::Exact => None,
+            UnitSystem::Si => Some(scaled(value, 1000.0, &SI_PREFIXES)),
+            UnitSystem::Iec => Some(scaled(value, 1024.0, &IEC_PREFIXES)),
+        }
+    }
+
+    /// `value` in full, digits grouped by three with the thousands separator
+    fn separated(&self, value: u64) -> String {
+        let digits = value.to_string();
+        let mut grouped = String::with_capacity(digits.len() * 2);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(&self.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+/// Parse a byte size such as `500MB`, `2 GiB` or `65536`
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid size '{}' (expected bytes with an optional unit such as 500MB or 2GiB)",
+            s
+        )
+    };
+    let trimmed = s.trim();
+    let number = trimmed
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .trim_end();
+    let unit = trimmed[number.len()..].trim_start();
+    let multiplier = match unit {
+        "" | "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(invalid()),
+    };
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    let bytes = value * multiplier;
+    if !(bytes >= 1.0 && bytes < u64::MAX as f64) {
+        return Err("size must be at least 1 byte".into());
+    }
+    Ok(bytes as u64)
+}
+
+/// `value` divided by the largest power of `base` that keeps it at least 1, rounded to one
+/// decimal, and the matching prefix
+fn scaled(value: u64, base: f64, prefixes: &[&'static str]) -> (String, &'static str) {
+    let mut scaled = value as f64;
+    let mut prefix = "";
+    for candidate in prefixes {
+        // Moving up only once the rounded value would reach the next unit keeps 999,999
+        // from reading as "1000.0k"
+        if (scaled * 10.0).round() / 10.0 < base {
+            break;
+        }
+        scaled /= base;
+        prefix = candidate;
+    }
+    if prefix.is_empty() {
+        return (value.to_string(), prefix);
+    }
+    (format!("{:.1}", scaled), prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_format_exact() {
+        let format = NumberFormat::default();
+        assert_eq!(format.count(999), "999");
+        assert_eq!(format.count(1_999), "1,999");
+        assert_eq!(format.count(1_234_567), "1,234,567");
+        assert_eq!(format.bytes(65_536), "65,536 B");
+
+        let format = NumberFormat {
+            thousands_separator: "'".to_string(),
+            ..NumberFormat::default()
+        };
+        assert_eq!(format.count(1_234_567), "1'234'567");
+    }
+
+    #[test]
+    fn test_number_format_scaled() {
+        let si = NumberFormat {
+            units: UnitSystem::Si,
+            ..NumberFormat::default()
+        };
+        assert_eq!(si.count(999), "999");
+        assert_eq!(si.count(1_000), "1.0k");
+        assert_eq!(si.count(1_999), "2.0k");
+        assert_eq!(si.count(12_500), "12.5k");
+        assert_eq!(si.count(3_210_000), "3.2M");
+        assert_eq!(si.count(999_999), "1.0M");
+        assert_eq!(si.bytes(1_500), "1.5 kB");
+        assert_eq!(si.bytes(12), "12 B");
+
+        let iec = NumberFormat {
+            units: UnitSystem::Iec,
+            ..NumberFormat::default()
+        };
+        assert_eq!(iec.count(1_999), "2.0Ki");
+        assert_eq!(iec.bytes(1 << 20), "1.0 MiB");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("65536"), Ok(65_536));
+        assert_eq!(parse_bytes("500MB"), Ok(500_000_000));
+        assert_eq!(parse_bytes("1.5 kB"), Ok(1_500));
+        assert_eq!(parse_bytes("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_bytes("10 B"), Ok(10));
+        for invalid in ["", "MB", "0", "-1MB", "5mb", "1PB"] {
+            assert!(parse_bytes(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
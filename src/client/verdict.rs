@@ -0,0 +1,133 @@
+//! Pass/fail verdict of a run
+//!
+//! Every report ends with a verdict, which the CI output formats, the Pushgateway and the
+//! report bundle repeat. By default a run passes when its mean RTT is below 1 ms;
+//! `--fail-metric p99 --fail-above 500us` grades it against the figure and threshold an
+//! SLO actually states instead.
+
+use crate::client::constants::PASS_THRESHOLD_MS;
+use crate::client::output::RunSummary;
+use crate::client::statistics::Statistics;
+use clap::ValueEnum;
+use std::fmt;
+
+/// RTT figure the verdict is based on
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailMetric {
+    /// Mean RTT
+    #[default]
+    Mean,
+    /// Median RTT
+    P50,
+    /// 99th percentile RTT
+    P99,
+    /// 99.9th percentile RTT
+    P999,
+    /// Highest RTT
+    Max,
+}
+
+impl FailMetric {
+    /// Value of the figure in `stats`, in nanoseconds
+    pub fn of_stats(&self, stats: &Statistics) -> f64 {
+        match self {
+            FailMetric::Mean => stats.mean(),
+            FailMetric::P50 => stats.percentile(0.5) as f64,
+            FailMetric::P99 => stats.percentile(0.99) as f64,
+            FailMetric::P999 => stats.percentile(0.999) as f64,
+            FailMetric::Max => stats.max() as f64,
+        }
+    }
+
+    /// Value of the figure in `summary`, in nanoseconds
+    pub fn of_summary(&self, summary: &RunSummary) -> f64 {
+        match self {
+            FailMetric::Mean => summary.mean_ns,
+            FailMetric::P50 => summary.p50_ns as f64,
+            FailMetric::P99 => summary.p99_ns as f64,
+            FailMetric::P999 => summary.p999_ns as f64,
+            FailMetric::Max => summary.max_ns as f64,
+        }
+    }
+}
+
+impl fmt::Display for FailMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailMetric::Mean => write!(f, "Mean"),
+            FailMetric::P50 => write!(f, "P50"),
+            FailMetric::P99 => write!(f, "P99"),
+            FailMetric::P999 => write!(f, "P99.9"),
+            FailMetric::Max => write!(f, "Max"),
+        }
+    }
+}
+
+/// A run passes when `metric` is below `threshold_ns`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verdict {
+    /// RTT figure graded
+    pub metric: FailMetric,
+    /// Value the figure must stay below, in nanoseconds
+    pub threshold_ns: u64,
+}
+
+impl Default for Verdict {
+    fn default() -> Self {
+        Self {
+            metric: FailMetric::Mean,
+            threshold_ns: (PASS_THRESHOLD_MS * 1_000_000.0) as u64,
+        }
+    }
+}
+
+impl Verdict {
+    /// Whether a figure of `value_ns` passes
+    pub fn passes(&self, value_ns: f64) -> bool {
+        value_ns < self.threshold_ns as f64
+    }
+
+    /// Threshold in milliseconds
+    pub fn threshold_ms(&self) -> f64 {
+        self.threshold_ns as f64 / 1_000_000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::error::Result;
+
+    #[test]
+    fn test_verdict() -> Result<()> {
+        // Mostly fast, with a slow tail
+        let mut latencies = vec![100_000; 990];
+        latencies.extend([5_000_000; 10]);
+        let stats = Statistics::new(&latencies)?;
+
+        let default = Verdict::default();
+        assert_eq!(
+            (default.metric, default.threshold_ms()),
+            (FailMetric::Mean, 1.0)
+        );
+        assert!(default.passes(default.metric.of_stats(&stats)));
+
+        let p99 = Verdict {
+            metric: FailMetric::P99,
+            threshold_ns: 1_000_000,
+        };
+        assert!(p99.passes(p99.metric.of_stats(&stats)));
+        let max = Verdict {
+            metric: FailMetric::Max,
+            ..p99
+        };
+        assert!(!max.passes(max.metric.of_stats(&stats)));
+
+        let summary = RunSummary::new(&stats, 0, latencies.len(), &[]);
+        for metric in [FailMetric::Mean, FailMetric::P999, FailMetric::Max] {
+            assert_eq!(metric.of_summary(&summary), metric.of_stats(&stats));
+        }
+        assert_eq!(FailMetric::P999.to_string(), "P99.9");
+        Ok(())
+    }
+}
@@ -1,77 +1,61 @@
-use crate::client::constants::OSI_ANIMATION_SAMPLE_RATE;
+//! Live OSI view: where the round trip of recent probes goes
+//!
+//! The client and server stacks are drawn side by side, and the layers of the round
+//! trip that took the largest share of the recent probes' mean RTT are highlighted, by
+//! the split of the report's *Latency Decomposition* (see
+//! [`crate::client::decomposition`]). Layers no timestamp tells apart are highlighted
+//! together, and nothing is highlighted before the first split.
+
+use crate::client::constants::OSI_SPLIT_SAMPLE_RATE;
+use crate::client::decomposition::{Decomposition, Layer, Segment};
+use crate::client::theme::Theme;
 use colored::*;
 
-/// Represents the position of a packet in the OSI model visualization
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum PacketPosition {
-    ClientL7,
-    ClientL4,
-    ClientL3,
-    ClientL2,
-    ClientL1,
-    ServerL1,
-    ServerL2,
-    ServerL3,
-    ServerL4,
-    ServerL7,
-    ReturnServerL7,
-    ReturnServerL4,
-    ReturnServerL3,
-    ReturnServerL2,
-    ReturnServerL1,
-    ReturnClientL1,
-    ReturnClientL2,
-    ReturnClientL3,
-    ReturnClientL4,
-    ReturnClientL7,
+/// Side of the round trip an OSI stack belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Host {
+    Client,
+    Server,
 }
 
-impl PacketPosition {
-    fn next(self) -> Self {
-        match self {
-            PacketPosition::ClientL7 => PacketPosition::ClientL4,
-            PacketPosition::ClientL4 => PacketPosition::ClientL3,
-            PacketPosition::ClientL3 => PacketPosition::ClientL2,
-            PacketPosition::ClientL2 => PacketPosition::ClientL1,
-            PacketPosition::ClientL1 => PacketPosition::ServerL1,
-            PacketPosition::ServerL1 => PacketPosition::ServerL2,
-            PacketPosition::ServerL2 => PacketPosition::ServerL3,
-            PacketPosition::ServerL3 => PacketPosition::ServerL4,
-            PacketPosition::ServerL4 => PacketPosition::ServerL7,
-            PacketPosition::ServerL7 => PacketPosition::ReturnServerL7,
-            PacketPosition::ReturnServerL7 => PacketPosition::ReturnServerL4,
-            PacketPosition::ReturnServerL4 => PacketPosition::ReturnServerL3,
-            PacketPosition::ReturnServerL3 => PacketPosition::ReturnServerL2,
-            PacketPosition::ReturnServerL2 => PacketPosition::ReturnServerL1,
-            PacketPosition::ReturnServerL1 => PacketPosition::ReturnClientL1,
-            PacketPosition::ReturnClientL1 => PacketPosition::ReturnClientL2,
-            PacketPosition::ReturnClientL2 => PacketPosition::ReturnClientL3,
-            PacketPosition::ReturnClientL3 => PacketPosition::ReturnClientL4,
-            PacketPosition::ReturnClientL4 => PacketPosition::ReturnClientL7,
-            PacketPosition::ReturnClientL7 => PacketPosition::ClientL7,
-        }
-    }
-}
-
-struct OsiState {
-    position: PacketPosition,
-}
-
-impl OsiState {
-    fn new() -> Self {
-        Self {
-            position: PacketPosition::ClientL7,
-        }
-    }
-
-    fn advance(&mut self) {
-        self.position = self.position.next();
+/// Labels of the layers of each stack, top to bottom
+pub const OSI_LAYERS: [(&str, &str); 5] = [
+    ("L7", "APPLICATION"),
+    ("L4", "TRANSPORT"),
+    ("L3", "NETWORK"),
+    ("L2", "DATA LINK"),
+    ("L1", "PHYSICAL"),
+];
+
+/// Layer of the round trip an OSI layer (an index into [`OSI_LAYERS`]) is timed in
+///
+/// The application is the app, transport to data link its host's kernel, and the
+/// physical layers of both hosts the network between them.
+pub fn round_trip_layer(host: Host, index: usize) -> Layer {
+    match (host, index) {
+        (_, 4) => Layer::Network,
+        (Host::Client, 0) => Layer::ClientApp,
+        (Host::Client, _) => Layer::ClientKernel,
+        (Host::Server, 0) => Layer::ServerApp,
+        (Host::Server, _) => Layer::ServerKernel,
     }
 }
 
-fn render_layer(label: &str, detail: &str, is_active: bool, layer_color: (u8, u8, u8)) -> String {
-    let (r, g, b) = layer_color;
+fn render_layer(
+    label: &str,
+    detail: &str,
+    is_active: bool,
+    layer_color: Option<(u8, u8, u8)>,
+) -> String {
     let text = format!("{}: {}", label, detail);
+    let Some((r, g, b)) = layer_color else {
+        // Without colors the active layer stands out in reverse video
+        return if is_active {
+            format!("{}", format!(" {:<20} ", text).reversed().bold())
+        } else {
+            format!(" {:<20} ", text)
+        };
+    };
 
     if is_active {
         // Bright background color when active with white text
@@ -96,142 +80,94 @@ fn render_layer(label: &str, detail: &str, is_active: bool, layer_color: (u8, u8
     }
 }
 
-fn render_osi_stack(osi_state: &OsiState) -> String {
-    let pos = osi_state.position;
-
-    // Check which layers are active on each side
-    let client_l7_active = matches!(
-        pos,
-        PacketPosition::ClientL7 | PacketPosition::ReturnClientL7
-    );
-    let client_l4_active = matches!(
-        pos,
-        PacketPosition::ClientL4 | PacketPosition::ReturnClientL4
-    );
-    let client_l3_active = matches!(
-        pos,
-        PacketPosition::ClientL3 | PacketPosition::ReturnClientL3
-    );
-    let client_l2_active = matches!(
-        pos,
-        PacketPosition::ClientL2 | PacketPosition::ReturnClientL2
-    );
-    let client_l1_active = matches!(
-        pos,
-        PacketPosition::ClientL1 | PacketPosition::ReturnClientL1
-    );
-
-    let server_l7_active = matches!(
-        pos,
-        PacketPosition::ServerL7 | PacketPosition::ReturnServerL7
-    );
-    let server_l4_active = matches!(
-        pos,
-        PacketPosition::ServerL4 | PacketPosition::ReturnServerL4
-    );
-    let server_l3_active = matches!(
-        pos,
-        PacketPosition::ServerL3 | PacketPosition::ReturnServerL3
-    );
-    let server_l2_active = matches!(
-        pos,
-        PacketPosition::ServerL2 | PacketPosition::ReturnServerL2
-    );
-    let server_l1_active = matches!(
-        pos,
-        PacketPosition::ServerL1 | PacketPosition::ReturnServerL1
-    );
-
-    // Layer colors (RGB): Blue, Green, Yellow, Orange, Red
-    let l7_color = (74, 144, 226); // Blue
-    let l4_color = (72, 187, 120); // Green
-    let l3_color = (236, 201, 75); // Yellow
-    let l2_color = (237, 137, 54); // Orange
-    let l1_color = (245, 101, 101); // Red
-
-    let mut lines = Vec::new();
-
-    // Header - centered above stacks
-    lines.push(format!(
-        "          {}                  {}",
-        "CLIENT".bold(),
-        "SERVER".bold()
-    ));
-
-    // Layer 7
-    lines.push(format!(
-        "{}  {}",
-        render_layer("L7", "APPLICATION", client_l7_active, l7_color),
-        render_layer("L7", "APPLICATION", server_l7_active, l7_color)
-    ));
-
-    // Layer 4
-    lines.push(format!(
-        "{}  {}",
-        render_layer("L4", "TRANSPORT", client_l4_active, l4_color),
-        render_layer("L4", "TRANSPORT", server_l4_active, l4_color)
-    ));
-
-    // Layer 3
-    lines.push(format!(
-        "{}  {}",
-        render_layer("L3", "NETWORK", client_l3_active, l3_color),
-        render_layer("L3", "NETWORK", server_l3_active, l3_color)
-    ));
-
-    // Layer 2
-    lines.push(format!(
-        "{}  {}",
-        render_layer("L2", "DATA LINK", client_l2_active, l2_color),
-        render_layer("L2", "DATA LINK", server_l2_active, l2_color)
-    ));
-
-    // Layer 1
-    lines.push(format!(
-        "{}  {}",
-        render_layer("L1", "PHYSICAL", client_l1_active, l1_color),
-        render_layer("L1", "PHYSICAL", server_l1_active, l1_color)
-    ));
-
-    // Break line
-    lines.push("                      ".to_string());
-    lines.join("\n")
-}
-
 /// OSI layer visualization manager
 pub struct OsiVisualizer {
-    state: OsiState,
+    /// Split of the recent probes' mean RTT, once one was made
+    split: Option<Decomposition>,
     sample_rate: usize,
+    theme: Theme,
 }
 
 impl OsiVisualizer {
     /// Create a new OSI visualizer
     pub fn new() -> Self {
         Self {
-            state: OsiState::new(),
-            sample_rate: OSI_ANIMATION_SAMPLE_RATE,
+            split: None,
+            sample_rate: OSI_SPLIT_SAMPLE_RATE,
+            theme: Theme::Default,
         }
     }
 
+    /// Color the layers with `theme`
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
     /// Check if the visualizer should update based on packet index
     pub fn should_update(&self, packet_index: usize) -> bool {
         (packet_index + 1).is_multiple_of(self.sample_rate)
     }
 
-    /// Advance the visualization state
-    pub fn advance(&mut self) {
-        self.state.advance();
+    /// Show `split`, the latest split of the recent probes' mean RTT; `None` keeps the
+    /// one shown
+    pub fn update(&mut self, split: Option<Decomposition>) {
+        if split.is_some() {
+            self.split = split;
+        }
+    }
+
+    /// Segment of the split that took the largest share of the RTT
+    fn largest(&self) -> Option<&Segment> {
+        self.split.as_ref()?.segments.iter().max_by(|a, b| {
+            a.mean_ns
+                .partial_cmp(&b.mean_ns)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
     }
 
-    /// Render the OSI stack visualization
-    pub fn render(&self) -> String {
-        render_osi_stack(&self.state)
+    /// Whether a layer (an index into [`OSI_LAYERS`]) of `host`'s stack is in the
+    /// largest segment of the split, for views that draw the stacks themselves
+    pub fn is_active(&self, host: Host, index: usize) -> bool {
+        self.largest()
+            .is_some_and(|segment| segment.layers.contains(&round_trip_layer(host, index)))
+    }
+
+    /// One-line account of the largest segment, e.g.
+    /// `network + server kernel 30.0 µs (67%)`
+    pub fn summary(&self) -> String {
+        match (&self.split, self.largest()) {
+            (Some(split), Some(segment)) => format!(
+                "{} {:.1} µs ({:.0}%)",
+                segment.label(),
+                segment.mean_ns / 1000.0,
+                segment.mean_ns / split.rtt_ns.max(1.0) * 100.0
+            ),
+            _ => "no timestamps to split the RTT yet".to_string(),
+        }
     }
 
-    /// Get the current packet position (for testing/debugging)
-    #[cfg(test)]
-    fn current_position(&self) -> PacketPosition {
-        self.state.position
+    /// Render the OSI stack visualization
+    pub fn render(&self) -> String {
+        let colors = self.theme.layer_colors();
+        let mut lines = Vec::new();
+
+        // Header - centered above stacks
+        lines.push(format!(
+            "          {}                  {}",
+            "CLIENT".bold(),
+            "SERVER".bold()
+        ));
+        for (index, (label, detail)) in OSI_LAYERS.iter().enumerate() {
+            let color = colors.map(|colors| colors[index]);
+            lines.push(format!(
+                "{}  {}",
+                render_layer(label, detail, self.is_active(Host::Client, index), color),
+                render_layer(label, detail, self.is_active(Host::Server, index), color)
+            ));
+        }
+        lines.push(format!(" {}", self.summary()));
+        lines.join("\n")
     }
 }
 
@@ -244,49 +180,80 @@ impl Default for OsiVisualizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::measurement::{KernelTiming, PathTiming};
+
+    /// Split of a 45 µs RTT with 30 µs in the network and the server kernel
+    fn split() -> Option<Decomposition> {
+        let kernel = [KernelTiming {
+            rtt_ns: 45_000,
+            kernel_rtt_ns: 34_000,
+        }];
+        let path = [PathTiming {
+            outbound_ns: 15_000,
+            server_ns: 1_000,
+            return_ns: 15_000,
+            server_processing_ns: Some(4_000),
+        }];
+        Decomposition::new(45_000.0, &[4_000], &kernel, &path)
+    }
 
     #[test]
     fn test_visualizer_new() {
         let viz = OsiVisualizer::new();
-        assert!(!viz.render().is_empty());
+        let render = viz.render();
+        assert!(render.contains("CLIENT"));
+        assert!(render.contains("SERVER"));
+        assert!(render.contains("no timestamps to split the RTT yet"));
+        assert!(!viz.is_active(Host::Client, 0));
     }
 
     #[test]
     fn test_visualizer_should_update() {
         let viz = OsiVisualizer::new();
-        // Should update every 100th packet (OSI_ANIMATION_SAMPLE_RATE)
+        // Should update every 100th packet (OSI_SPLIT_SAMPLE_RATE)
         assert!(viz.should_update(99)); // packet_index 99 = packet 100
         assert!(!viz.should_update(98));
         assert!(viz.should_update(199)); // packet_index 199 = packet 200
     }
 
     #[test]
-    fn test_visualizer_advance() {
+    fn test_visualizer_split() {
         let mut viz = OsiVisualizer::new();
-        assert_eq!(viz.current_position(), PacketPosition::ClientL7);
-
-        // Advance through positions
-        // From ClientL7 -> ClientL4 -> ClientL3 -> ClientL2 -> ClientL1 -> ServerL1
-        viz.advance();
-        assert_eq!(viz.current_position(), PacketPosition::ClientL4);
-
-        viz.advance();
-        assert_eq!(viz.current_position(), PacketPosition::ClientL3);
-
-        viz.advance();
-        assert_eq!(viz.current_position(), PacketPosition::ClientL2);
-
-        viz.advance();
-        assert_eq!(viz.current_position(), PacketPosition::ClientL1);
-
-        viz.advance();
-        assert_eq!(viz.current_position(), PacketPosition::ServerL1);
+        viz.update(split());
+        assert!(viz
+            .render()
+            .contains("network + server kernel 30.0 µs (67%)"));
+
+        // Both physical layers and the server's kernel are highlighted, nothing else
+        let active: Vec<(Host, usize)> = [Host::Client, Host::Server]
+            .into_iter()
+            .flat_map(|host| (0..OSI_LAYERS.len()).map(move |index| (host, index)))
+            .filter(|&(host, index)| viz.is_active(host, index))
+            .collect();
+        assert_eq!(
+            active,
+            [
+                (Host::Client, 4),
+                (Host::Server, 1),
+                (Host::Server, 2),
+                (Host::Server, 3),
+                (Host::Server, 4)
+            ]
+        );
+
+        // A probe window without timestamps keeps the last split
+        viz.update(None);
+        assert!(viz.is_active(Host::Server, 4));
+    }
 
-        // Verify that render() produces output after advancing
-        let render = viz.render();
-        assert!(!render.is_empty());
-        assert!(render.contains("CLIENT"));
-        assert!(render.contains("SERVER"));
+    #[test]
+    fn test_round_trip_layer() {
+        assert_eq!(round_trip_layer(Host::Client, 0), Layer::ClientApp);
+        assert_eq!(round_trip_layer(Host::Client, 2), Layer::ClientKernel);
+        assert_eq!(round_trip_layer(Host::Client, 4), Layer::Network);
+        assert_eq!(round_trip_layer(Host::Server, 4), Layer::Network);
+        assert_eq!(round_trip_layer(Host::Server, 1), Layer::ServerKernel);
+        assert_eq!(round_trip_layer(Host::Server, 0), Layer::ServerApp);
     }
 
     #[test]
@@ -297,22 +264,11 @@ mod tests {
     }
 
     #[test]
-    fn test_packet_position_cycle() {
-        let mut pos = PacketPosition::ClientL7;
-        let start = pos;
-
-        // Count positions: There are 20 positions in the cycle
-        // ClientL7 -> ClientL4 -> ClientL3 -> ClientL2 -> ClientL1 ->
-        // ServerL1 -> ServerL2 -> ServerL3 -> ServerL4 -> ServerL7 ->
-        // ReturnServerL7 -> ReturnServerL4 -> ReturnServerL3 -> ReturnServerL2 -> ReturnServerL1 ->
-        // ReturnClientL1 -> ReturnClientL2 -> ReturnClientL3 -> ReturnClientL4 -> ReturnClientL7 -> ClientL7
-
-        // Advance through all positions in the cycle
-        for _ in 0..20 {
-            pos = pos.next();
+    fn test_visualizer_themes() {
+        for theme in [Theme::Colorblind, Theme::Mono] {
+            let render = OsiVisualizer::new().with_theme(theme).render();
+            assert!(render.contains("L7: APPLICATION"));
+            assert!(render.contains("L1: PHYSICAL"));
         }
-
-        // Should cycle back to start
-        assert_eq!(pos, start);
     }
 }
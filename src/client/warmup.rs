@@ -0,0 +1,163 @@
+//! Warmup until the latency settles
+//!
+//! A fixed `--warmup` count is a guess: the default of 100,000 probes is far more than
+//! a warm LAN path needs, and a cold path behind a NAT, a tunnel or a CPU coming out of
+//! a deep sleep state can still be settling after it. `--warmup auto` warms up in
+//! windows of [`SETTLING_WINDOW`] answered probes instead, and stops once the mean and
+//! the P99 of a window both come within `--warmup-tolerance` percent of the window
+//! before. A path that never settles stops after [`AUTO_WARMUP_MAX_PROBES`] probes, no
+//! longer than the fixed default.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Answered probes the mean and P99 of a settling window are taken over
+pub const SETTLING_WINDOW: usize = 1_000;
+
+/// Change of the mean and P99 between two windows, in percent, that counts as settled
+/// unless --warmup-tolerance says otherwise
+pub const DEFAULT_WARMUP_TOLERANCE_PCT: f64 = 10.0;
+
+/// Most probes `--warmup auto` sends before giving up on the latency settling
+pub const AUTO_WARMUP_MAX_PROBES: usize = 100_000;
+
+/// Length of the warmup, written on the command line as a probe count or `auto`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warmup {
+    /// This many probes
+    Count(usize),
+    /// Until the latency settles
+    Auto,
+}
+
+impl Warmup {
+    /// Most probes the warmup sends
+    pub fn max_probes(self) -> usize {
+        match self {
+            Warmup::Count(count) => count,
+            Warmup::Auto => AUTO_WARMUP_MAX_PROBES,
+        }
+    }
+}
+
+impl fmt::Display for Warmup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warmup::Count(count) => write!(f, "{}", count),
+            Warmup::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for Warmup {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Warmup::Auto);
+        }
+        s.parse().map(Warmup::Count).map_err(|_| {
+            format!(
+                "invalid warmup '{}' (expected a number of probes or 'auto')",
+                s
+            )
+        })
+    }
+}
+
+/// Whether the latency of a warmup has settled, fed one probe at a time
+#[derive(Debug, Clone)]
+pub struct Settling {
+    window: usize,
+    tolerance_pct: f64,
+    current: Vec<u64>,
+    /// Mean and P99 of the last full window, in nanoseconds
+    previous: Option<(f64, u64)>,
+}
+
+impl Settling {
+    /// Settle once two consecutive windows of `window` answered probes have a mean and
+    /// a P99 within `tolerance_pct` percent of each other
+    pub fn new(window: usize, tolerance_pct: f64) -> Self {
+        Self {
+            window: window.max(1),
+            tolerance_pct,
+            current: Vec::with_capacity(window),
+            previous: None,
+        }
+    }
+
+    /// Record the RTT of a probe, `None` if it was lost, and return whether the latency
+    /// has settled; lost probes do not count towards a window
+    pub fn record(&mut self, rtt_ns: Option<u64>) -> bool {
+        let Some(rtt_ns) = rtt_ns else {
+            return false;
+        };
+        self.current.push(rtt_ns);
+        if self.current.len() < self.window {
+            return false;
+        }
+
+        self.current.sort_unstable();
+        let mean = self.current.iter().sum::<u64>() as f64 / self.current.len() as f64;
+        // Nearest-rank percentile
+        let rank = (0.99 * self.current.len() as f64).ceil() as usize;
+        let p99 = self.current[rank - 1];
+        self.current.clear();
+
+        let within = |now: f64, before: f64| {
+            (now - before).abs() <= before.max(1.0) * self.tolerance_pct / 100.0
+        };
+        let settled = self.previous.is_some_and(|(previous_mean, previous_p99)| {
+            within(mean, previous_mean) && within(p99 as f64, previous_p99 as f64)
+        });
+        self.previous = Some((mean, p99));
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_warmup() {
+        assert_eq!("auto".parse(), Ok(Warmup::Auto));
+        assert_eq!("AUTO".parse(), Ok(Warmup::Auto));
+        assert_eq!("500".parse(), Ok(Warmup::Count(500)));
+        assert!("soon".parse::<Warmup>().is_err());
+        assert!("-1".parse::<Warmup>().is_err());
+        assert_eq!(Warmup::Count(500).to_string(), "500");
+        assert_eq!(Warmup::Auto.max_probes(), AUTO_WARMUP_MAX_PROBES);
+    }
+
+    #[test]
+    fn test_settling() {
+        let mut settling = Settling::new(100, 10.0);
+        // A cold path: the first window is slow, the second still settling
+        for window in [400_000, 120_000, 100_000] {
+            for i in 0..100 {
+                assert!(!settling.record(Some(window + i * 100)));
+            }
+            // Lost probes do not fill a window
+            assert!(!settling.record(None));
+        }
+        for i in 0..99 {
+            assert!(!settling.record(Some(95_000 + i * 100)));
+        }
+        assert!(settling.record(Some(105_000)));
+
+        // A P99 that keeps jumping does not settle, however steady the mean
+        let mut settling = Settling::new(100, 10.0);
+        for window in 0..10 {
+            for i in 0..100 {
+                let rtt_ns = match (window % 2, i) {
+                    (0, _) => 60_000,
+                    (_, 98..) => 550_000,
+                    _ => 50_000,
+                };
+                assert!(!settling.record(Some(rtt_ns)));
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Watchdog that aborts a hung measurement
+//!
+//! Every packet normally completes within the socket timeout, either with a reply or with
+//! a timeout error. A blocking call that ignores the timeout (e.g. a `write` stuck on a full
+//! send buffer) would otherwise hang the client forever. The watchdog notices when a socket
+//! call has not returned for too long, logs a diagnostic and shuts the socket down so the
+//! blocked call returns and the phase fails with an explanation.
+//!
+//! Only time spent inside socket calls counts: the watchdog is armed on entering a send
+//! or receive and disarmed when it returns, so the waits of `--rate`, Poisson gaps and
+//! `--max-bandwidth` between probes never trip it, however long they are.
+
+use crate::client::dscp::Dscp;
+use crate::client::error::{ClientError, Result};
+use crate::client::socket::{AbortHandle, NetworkSocket};
+use crate::client::timestamping::KernelTimestamps;
+use crate::protocol::Packet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, error};
+
+/// Upper bound on how often the watchdog thread checks for progress
+const WATCHDOG_MAX_POLL_INTERVAL_MS: u64 = 100;
+
+/// Sentinel for "no packet has completed yet"
+const NO_SEQUENCE: u64 = u64::MAX;
+
+/// Sentinel for "no socket call in progress"
+const IDLE: u64 = u64::MAX;
+
+/// Progress state shared between the measuring thread and the watchdog thread
+struct WatchdogState {
+    started: Instant,
+    last_progress_ns: AtomicU64,
+    /// Time the socket call in progress started, or [`IDLE`] between calls
+    call_started_ns: AtomicU64,
+    completed: AtomicU64,
+    last_sequence: AtomicU64,
+    fired: AtomicBool,
+    stopped: AtomicBool,
+    diagnostic: Mutex<Option<String>>,
+}
+
+impl WatchdogState {
+    fn elapsed_ns(&self) -> u64 {
+        self.started.elapsed().as_nanos() as u64
+    }
+}
+
+/// Background thread that fires when a socket call does not return within `timeout`
+pub struct Watchdog {
+    state: Arc<WatchdogState>,
+    timeout: Duration,
+}
+
+impl Watchdog {
+    /// Start a watchdog that calls `abort` if a call it was armed for does not return
+    /// within `timeout`
+    pub fn start(timeout: Duration, abort: AbortHandle) -> Self {
+        let state = Arc::new(WatchdogState {
+            started: Instant::now(),
+            last_progress_ns: AtomicU64::new(0),
+            call_started_ns: AtomicU64::new(IDLE),
+            completed: AtomicU64::new(0),
+            last_sequence: AtomicU64::new(NO_SEQUENCE),
+            fired: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            diagnostic: Mutex::new(None),
+        });
+
+        let poll_interval = (timeout / 4).min(Duration::from_millis(WATCHDOG_MAX_POLL_INTERVAL_MS));
+        let thread_state = Arc::clone(&state);
+
+        thread::spawn(move || {
+            let timeout_ns = timeout.as_nanos() as u64;
+            loop {
+                thread::sleep(poll_interval);
+                if thread_state.stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let call_started_ns = thread_state.call_started_ns.load(Ordering::SeqCst);
+                if call_started_ns == IDLE {
+                    continue;
+                }
+                let blocked_ns = thread_state.elapsed_ns().saturating_sub(call_started_ns);
+                if blocked_ns < timeout_ns {
+                    continue;
+                }
+
+                let diagnostic = Self::diagnostic(&thread_state, blocked_ns);
+                error!(
+                    blocked_secs = blocked_ns as f64 / 1e9,
+                    completed = thread_state.completed.load(Ordering::Relaxed),
+                    "Watchdog fired: {}",
+                    diagnostic
+                );
+                if let Ok(mut slot) = thread_state.diagnostic.lock() {
+                    *slot = Some(diagnostic);
+                }
+                thread_state.fired.store(true, Ordering::SeqCst);
+                abort();
+                return;
+            }
+        });
+
+        debug!(timeout_secs = timeout.as_secs_f64(), "Watchdog started");
+        Self { state, timeout }
+    }
+
+    /// Builds the human-readable explanation of why the watchdog fired
+    fn diagnostic(state: &WatchdogState, blocked_ns: u64) -> String {
+        let completed = state.completed.load(Ordering::Relaxed);
+        let last = match state.last_sequence.load(Ordering::Relaxed) {
+            NO_SEQUENCE => "no packet completed yet".to_string(),
+            seq => format!(
+                "last completed sequence {} at {:.1}s",
+                seq,
+                state.last_progress_ns.load(Ordering::Relaxed) as f64 / 1e9
+            ),
+        };
+        format!(
+            "watchdog: a socket call did not return for {:.1}s ({} completed, {}); it \
+             ignored its timeout, so the socket was shut down to abort the phase",
+            blocked_ns as f64 / 1e9,
+            completed,
+            last
+        )
+    }
+
+    /// Start timing a socket call, which must return within the timeout
+    #[inline]
+    pub fn arm(&self) {
+        self.state
+            .call_started_ns
+            .store(self.state.elapsed_ns(), Ordering::SeqCst);
+    }
+
+    /// Stop timing: the socket call returned
+    #[inline]
+    pub fn disarm(&self) {
+        self.state.call_started_ns.store(IDLE, Ordering::SeqCst);
+    }
+
+    /// Report that the packet with `sequence` completed (successfully or by timing out)
+    #[inline]
+    pub fn pet(&self, sequence: u64) {
+        self.state
+            .last_progress_ns
+            .store(self.state.elapsed_ns(), Ordering::Relaxed);
+        self.state.last_sequence.store(sequence, Ordering::Relaxed);
+        self.state.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns true if the watchdog has fired
+    pub fn has_fired(&self) -> bool {
+        self.state.fired.load(Ordering::SeqCst)
+    }
+
+    /// Returns the diagnostic message if the watchdog has fired
+    pub fn fired_diagnostic(&self) -> Option<String> {
+        if !self.has_fired() {
+            return None;
+        }
+        self.state.diagnostic.lock().ok().and_then(|d| d.clone())
+    }
+
+    /// Configured stall timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Socket decorator that arms a [`Watchdog`] for every send and receive, and feeds it
+/// packet completions for its diagnostic
+///
+/// Wrapping the socket (rather than the phases) means warmup and measurement are both
+/// covered without threading the watchdog through every phase function.
+pub struct WatchdogSocket<S: NetworkSocket> {
+    inner: S,
+    watchdog: Watchdog,
+    last_sent: AtomicU64,
+}
+
+impl<S: NetworkSocket> WatchdogSocket<S> {
+    /// Wrap `inner` with a watchdog that fires after `timeout` without progress
+    pub fn new(inner: S, timeout: Duration) -> Result<Self> {
+        let abort = inner.abort_handle()?.ok_or_else(|| {
+            ClientError::Config("the selected socket does not support the watchdog".into())
+        })?;
+        Ok(Self {
+            inner,
+            watchdog: Watchdog::start(timeout, abort),
+            last_sent: AtomicU64::new(NO_SEQUENCE),
+        })
+    }
+
+    /// Access the underlying watchdog
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    /// Replace an I/O error caused by the watchdog's abort with its diagnostic
+    fn explain(&self, err: ClientError) -> ClientError {
+        match self.watchdog.fired_diagnostic() {
+            Some(diagnostic) => ClientError::Measurement(diagnostic),
+            None => err,
+        }
+    }
+}
+
+impl<S: NetworkSocket> NetworkSocket for WatchdogSocket<S> {
+    fn send_packet(&self, packet: &Packet) -> Result<usize> {
+        // Remembered to attribute timeouts, which carry no sequence number of their own
+        self.last_sent.store(packet.sequence.0, Ordering::Relaxed);
+        self.watchdog.arm();
+        let sent = self.inner.send_packet(packet);
+        self.watchdog.disarm();
+        sent.map_err(|e| self.explain(e))
+    }
+
+    fn wait_to_send(&self, packet: &Packet) -> Result<()> {
+        self.inner.wait_to_send(packet)
+    }
+
+    fn recv_packet(&mut self) -> Result<Packet> {
+        self.watchdog.arm();
+        let received = self.inner.recv_packet();
+        self.watchdog.disarm();
+        match received {
+            Ok(packet) => {
+                self.watchdog.pet(packet.sequence.0);
+                Ok(packet)
+            }
+            Err(ClientError::Io(e)) if is_timeout(&e) && !self.watchdog.has_fired() => {
+                // A timeout is still a completed packet: the socket honoured its deadline
+                self.watchdog.pet(self.last_sent.load(Ordering::Relaxed));
+                Err(ClientError::Io(e))
+            }
+            Err(e) => Err(self.explain(e)),
+        }
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn abort_handle(&self) -> Result<Option<AbortHandle>> {
+        self.inner.abort_handle()
+    }
+
+    fn take_error(&self) -> Result<Option<std::io::Error>> {
+        self.inner.take_error()
+    }
+
+    fn kernel_timestamps(&mut self) -> Result<Option<KernelTimestamps>> {
+        self.inner.kernel_timestamps()
+    }
+
+    fn reply_dscp(&mut self) -> Option<Dscp> {
+        self.inner.reply_dscp()
+    }
+
+    fn accept_any_reply(&mut self) {
+        self.inner.accept_any_reply()
+    }
+}
+
+/// Returns true if the I/O error is a read timeout
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counting_abort() -> (AbortHandle, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let abort: AbortHandle = Box::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        (abort, calls)
+    }
+
+    #[test]
+    fn test_watchdog_fires_on_blocked_call() {
+        let (abort, calls) = counting_abort();
+        let watchdog = Watchdog::start(Duration::from_millis(50), abort);
+
+        watchdog.arm();
+        thread::sleep(Duration::from_millis(300));
+
+        assert!(watchdog.has_fired());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let diagnostic = watchdog.fired_diagnostic().unwrap();
+        assert!(diagnostic.contains("no packet completed yet"));
+    }
+
+    #[test]
+    fn test_watchdog_stays_quiet_while_petted() {
+        let (abort, calls) = counting_abort();
+        let watchdog = Watchdog::start(Duration::from_millis(200), abort);
+
+        for seq in 0..10 {
+            watchdog.arm();
+            thread::sleep(Duration::from_millis(20));
+            watchdog.pet(seq);
+            watchdog.disarm();
+        }
+
+        assert!(!watchdog.has_fired());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert!(watchdog.fired_diagnostic().is_none());
+    }
+
+    #[test]
+    fn test_watchdog_ignores_time_between_calls() {
+        let (abort, calls) = counting_abort();
+        let watchdog = Watchdog::start(Duration::from_millis(50), abort);
+
+        // A pacing wait far longer than the timeout, between two calls that return
+        watchdog.arm();
+        watchdog.disarm();
+        thread::sleep(Duration::from_millis(300));
+        watchdog.arm();
+        watchdog.disarm();
+
+        assert!(!watchdog.has_fired());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_watchdog_stops_when_dropped() {
+        let (abort, calls) = counting_abort();
+        let watchdog = Watchdog::start(Duration::from_millis(50), abort);
+        watchdog.arm();
+        drop(watchdog);
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,199 @@
+//! TOML configuration files for the client and the server
+//!
+//! `--config synapse.toml` reads flags from a file, so a complex test setup can be
+//! versioned with the code it tests instead of typed as a long command line. Keys are
+//! the long flag names, with dashes or underscores:
+//!
+//! ```toml
+//! server = "10.0.0.2:8080"
+//! transport = "udp"
+//! packets = 100000
+//! payload_size = 1400
+//! slo = ["99.9%<500us", "99.99%<2ms"]
+//! pushgateway = "http://pushgateway:9091"
+//! quiet = true
+//! ```
+//!
+//! The file is turned into command-line flags placed before those actually given, so
+//! values are checked exactly as on the command line. A flag given on the command line
+//! replaces the file's value, lists included. Paths are relative to the working
+//! directory, not to the file.
+
+use clap::error::ErrorKind;
+use clap::{Command, Error};
+use std::fs;
+use toml::{Table, Value};
+
+/// Flag naming the configuration file
+const CONFIG_FLAG: &str = "config";
+
+/// Flags that cannot be set from a file
+const RESERVED_KEYS: [&str; 3] = [CONFIG_FLAG, "help", "version"];
+
+/// `args` (program name first) with the flags of their `--config` file, if any, inserted
+/// after the program name
+///
+/// Errors are reported like those of the command line, with `command`'s usage.
+pub fn expand_args(command: &Command, args: Vec<String>) -> Result<Vec<String>, Error> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+    let text = fs::read_to_string(&path).map_err(|e| {
+        command
+            .clone()
+            .error(ErrorKind::Io, format!("cannot read {}: {}", path, e))
+    })?;
+    let file_args = file_args(command, &text, &args).map_err(|e| {
+        command
+            .clone()
+            .error(ErrorKind::InvalidValue, format!("{}: {}", path, e))
+    })?;
+
+    let mut args = args.into_iter();
+    let mut expanded: Vec<String> = args.next().into_iter().collect();
+    expanded.extend(file_args);
+    expanded.extend(args);
+    Ok(expanded)
+}
+
+/// Value of the last `--config` flag of `args`
+fn config_path(args: &[String]) -> Option<String> {
+    let flag = format!("--{}", CONFIG_FLAG);
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == flag {
+            path = args.next().cloned();
+        } else if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+            path = Some(value.to_string());
+        }
+    }
+    path
+}
+
+/// Flags of the configuration file `text`, leaving out those `cli_args` give
+fn file_args(command: &Command, text: &str, cli_args: &[String]) -> Result<Vec<String>, String> {
+    let table: Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| format!("invalid TOML: {}", e.message()))?;
+
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        if RESERVED_KEYS.contains(&name.as_str()) {
+            return Err(format!("'{}' cannot be set in a configuration file", key));
+        }
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()))
+            .ok_or_else(|| format!("unknown key '{}' (expected a long flag name)", key))?;
+        let flag = format!("--{}", name);
+        let given = cli_args
+            .iter()
+            .any(|arg| *arg == flag || arg.starts_with(&format!("{}=", flag)));
+        if given {
+            continue;
+        }
+
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Integer(n) => n.to_string(),
+                Value::Float(x) => x.to_string(),
+                Value::Boolean(b) if !arg.get_action().takes_values() => {
+                    // A switch: present when true
+                    if b {
+                        args.push(flag.clone());
+                    }
+                    continue;
+                }
+                Value::Boolean(b) => b.to_string(),
+                _ => return Err(format!("unsupported value of '{}'", key)),
+            };
+            args.push(flag.clone());
+            args.push(value);
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn command() -> Command {
+        Command::new("synapse")
+            .arg(Arg::new("config").long("config"))
+            .arg(Arg::new("payload_size").long("payload-size"))
+            .arg(Arg::new("slo").long("slo").action(clap::ArgAction::Append))
+            .arg(
+                Arg::new("quiet")
+                    .long("quiet")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    }
+
+    #[test]
+    fn test_file_args() {
+        let text = "payload_size = 1400\nslo = [\"99%<1ms\", \"99.9%<2ms\"]\nquiet = true\n";
+        assert_eq!(
+            file_args(&command(), text, &[]).unwrap(),
+            [
+                "--payload-size",
+                "1400",
+                "--quiet",
+                "--slo",
+                "99%<1ms",
+                "--slo",
+                "99.9%<2ms"
+            ]
+        );
+        assert!(file_args(&command(), "quiet = false", &[])
+            .unwrap()
+            .is_empty());
+
+        // The command line wins, for lists as well
+        let cli = ["synapse".to_string(), "--slo=90%<1ms".to_string()];
+        assert_eq!(
+            file_args(&command(), text, &cli).unwrap(),
+            ["--payload-size", "1400", "--quiet"]
+        );
+
+        assert!(file_args(&command(), "packets = 10", &[]).is_err());
+        assert!(file_args(&command(), "config = \"other.toml\"", &[]).is_err());
+        assert!(file_args(&command(), "[payload_size]\nx = 1", &[]).is_err());
+        assert!(file_args(&command(), "payload_size = ", &[]).is_err());
+    }
+
+    #[test]
+    fn test_expand_args() {
+        let path = std::env::temp_dir().join(format!("synapse-{}-config.toml", std::process::id()));
+        fs::write(&path, "payload-size = 64\n").unwrap();
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let expanded = expand_args(
+            &command(),
+            args(&["synapse", "--config", path.to_str().unwrap(), "--quiet"]),
+        )
+        .unwrap();
+        assert_eq!(&expanded[..3], ["synapse", "--payload-size", "64"]);
+        assert_eq!(expanded.last().map(String::as_str), Some("--quiet"));
+
+        let unchanged = args(&["synapse", "--quiet"]);
+        assert_eq!(
+            expand_args(&command(), unchanged.clone()).unwrap(),
+            unchanged
+        );
+        fs::remove_file(&path).unwrap();
+        assert!(expand_args(
+            &command(),
+            args(&["synapse", &format!("--config={}", path.display())])
+        )
+        .is_err());
+    }
+}
@@ -5,5 +5,6 @@
 //! (network transmission, kernel processing, and application overhead).
 
 pub mod client;
+pub mod config_file;
 pub mod protocol;
 pub mod server;
@@ -0,0 +1,125 @@
+//! Clock offset estimation from timestamped exchanges
+//!
+//! One-way latencies compare a time taken on the client with one taken on the server,
+//! so any offset between the two clocks shows up in them. Every echo carries four
+//! timestamps, as in NTP: client send (`t0`), server receive (`t1`), server transmit
+//! (`t2`) and client receive (`t3`). An exchange estimates the offset of the server
+//! clock as `((t1 - t0) + (t2 - t3)) / 2`, which is exact when the outbound and return
+//! trips take equally long and off by at most half the network delay
+//! `(t3 - t0) - (t2 - t1)` otherwise. Of a series of exchanges, the one with the
+//! lowest delay, least disturbed by queueing, gives the estimate.
+
+use crate::protocol::message::Packet;
+
+/// The four timestamps of one exchange, in nanoseconds since the UNIX epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    pub client_send_ns: u64,
+    pub server_receive_ns: u64,
+    pub server_transmit_ns: u64,
+    pub client_receive_ns: u64,
+}
+
+impl ClockSample {
+    /// Build a sample from the client's send and receive times and the server's
+    /// timestamps in the reply, if it carries them
+    pub fn from_reply(client_send_ns: u64, reply: &Packet, client_receive_ns: u64) -> Option<Self> {
+        Some(Self {
+            client_send_ns,
+            server_receive_ns: reply.server_receive_ns?,
+            server_transmit_ns: reply.server_transmit_ns?,
+            client_receive_ns,
+        })
+    }
+
+    /// Offset of the server clock from the client clock (positive when it is ahead)
+    pub fn offset_ns(&self) -> i64 {
+        let outbound = self.server_receive_ns as i64 - self.client_send_ns as i64;
+        let inbound = self.server_transmit_ns as i64 - self.client_receive_ns as i64;
+        (outbound + inbound) / 2
+    }
+
+    /// Round trip minus the time spent in the server
+    pub fn delay_ns(&self) -> u64 {
+        let round_trip = self.client_receive_ns.saturating_sub(self.client_send_ns);
+        let server = self
+            .server_transmit_ns
+            .saturating_sub(self.server_receive_ns);
+        round_trip.saturating_sub(server)
+    }
+}
+
+/// Estimated offset between the client and server clocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockOffset {
+    /// Server clock minus client clock, in nanoseconds
+    pub offset_ns: i64,
+    /// Largest error of the estimate if the path is asymmetric (half the best delay)
+    pub uncertainty_ns: u64,
+    /// Exchanges the estimate was chosen from
+    pub samples: usize,
+}
+
+impl ClockOffset {
+    /// Estimate the offset from the exchange with the lowest delay, if there is any
+    pub fn estimate(samples: &[ClockSample]) -> Option<Self> {
+        let best = samples.iter().min_by_key(|sample| sample.delay_ns())?;
+        Some(Self {
+            offset_ns: best.offset_ns(),
+            uncertainty_ns: best.delay_ns() / 2,
+            samples: samples.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::SequenceNumber;
+
+    /// An exchange with the server clock `offset` ahead and the given one-way times
+    fn sample(start: u64, offset: i64, outbound: u64, server: u64, inbound: u64) -> ClockSample {
+        let server_receive = (start + outbound) as i64 + offset;
+        ClockSample {
+            client_send_ns: start,
+            server_receive_ns: server_receive as u64,
+            server_transmit_ns: server_receive as u64 + server,
+            client_receive_ns: start + outbound + server + inbound,
+        }
+    }
+
+    #[test]
+    fn test_clock_sample_offset_and_delay() {
+        let symmetric = sample(1_000_000, 250_000, 10_000, 3_000, 10_000);
+        assert_eq!(symmetric.offset_ns(), 250_000);
+        assert_eq!(symmetric.delay_ns(), 20_000);
+
+        // An asymmetric path skews the estimate by half the difference
+        let asymmetric = sample(1_000_000, -40_000, 30_000, 3_000, 10_000);
+        assert_eq!(asymmetric.offset_ns(), -40_000 + 10_000);
+        assert_eq!(asymmetric.delay_ns(), 40_000);
+
+        let mut reply = Packet::new(SequenceNumber(1));
+        assert_eq!(ClockSample::from_reply(1, &reply, 2), None);
+        reply.server_receive_ns = Some(5);
+        reply.server_transmit_ns = Some(6);
+        assert_eq!(
+            ClockSample::from_reply(1, &reply, 9).map(|s| s.delay_ns()),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn test_clock_offset_prefers_lowest_delay() {
+        let samples = [
+            sample(0, 500_000, 80_000, 2_000, 10_000),
+            sample(1_000_000, 500_000, 10_000, 2_000, 10_000),
+            sample(2_000_000, 500_000, 10_000, 2_000, 90_000),
+        ];
+        let offset = ClockOffset::estimate(&samples).unwrap();
+        assert_eq!(offset.offset_ns, 500_000);
+        assert_eq!(offset.uncertainty_ns, 10_000);
+        assert_eq!(offset.samples, 3);
+        assert_eq!(ClockOffset::estimate(&[]), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Wireshark dissector for the Synapse protocol
+//!
+//! The dissector is generated from the constants of [`crate::protocol::message`], so a
+//! capture decoded with the output of `synapse-client dissector` always matches the
+//! protocol version of the client that generated it.
+
+use crate::protocol::message::{PACKET_SIZE, PROTOCOL_VERSION};
+
+/// Lua source of a Wireshark dissector for probes to and from `server_port`
+///
+/// Registered for both TCP and UDP on that port. Packets sent to the port are shown as
+/// probes, packets from it as replies. Each packet's length field splits TCP segments
+/// into packets, so one dissector serves every `--payload-size`.
+pub fn lua_dissector(server_port: u16) -> String {
+    format!(
+        r#"-- Wireshark dissector for the Synapse latency probe protocol, version {version}
+-- Generated by synapse-client {client_version}. Load it with
+--   wireshark -X lua_script:synapse.lua capture.pcap
+-- or copy it into the personal Lua plugins folder (Help > About Wireshark > Folders).
+
+local synapse = Proto("synapse", "Synapse Latency Probe (v{version})")
+
+local f_sequence = ProtoField.uint64("synapse.sequence", "Sequence", base.DEC)
+local f_length = ProtoField.uint32("synapse.length", "Length", base.DEC)
+local f_version = ProtoField.uint32("synapse.version", "Version", base.DEC)
+local f_client_send = ProtoField.uint64("synapse.client_send_ns", "Client send (ns)", base.DEC)
+local f_server_receive = ProtoField.uint64("synapse.server_receive_ns", "Server receive (ns)", base.DEC)
+local f_server_transmit = ProtoField.uint64("synapse.server_transmit_ns", "Server transmit (ns)", base.DEC)
+local f_server_time = ProtoField.uint64("synapse.server_time_ns", "Server time (ns)", base.DEC)
+local f_server_processing = ProtoField.uint64("synapse.server_processing_ns", "Server processing of the previous echo (ns)", base.DEC)
+local f_replies = ProtoField.uint32("synapse.replies", "Replies requested", base.DEC)
+local f_reply_index = ProtoField.uint32("synapse.reply_index", "Reply index", base.DEC)
+local f_cookie = ProtoField.uint64("synapse.cookie", "Cookie", base.HEX)
+local f_direction = ProtoField.string("synapse.direction", "Direction")
+local f_padding = ProtoField.bytes("synapse.padding", "Padding")
+synapse.fields = {{
+    f_sequence, f_length, f_version, f_client_send, f_server_receive, f_server_transmit,
+    f_server_time, f_server_processing, f_replies, f_reply_index, f_cookie, f_direction,
+    f_padding
+}}
+
+local HEADER_SIZE = {header_size}
+local SERVER_PORT = {server_port}
+
+function synapse.dissector(buffer, pinfo, tree)
+    local length = buffer:len()
+    local direction = "Reply"
+    if pinfo.dst_port == SERVER_PORT then
+        direction = "Probe"
+    end
+    pinfo.cols.protocol = "SYNAPSE"
+
+    -- A TCP segment may carry several packets, or only part of one
+    local sequences = {{}}
+    local offset = 0
+    while offset < length do
+        if length - offset < HEADER_SIZE then
+            pinfo.desegment_offset = offset
+            pinfo.desegment_len = DESEGMENT_ONE_MORE_SEGMENT
+            break
+        end
+        local size = buffer(offset + 8, 4):le_uint()
+        if size < HEADER_SIZE then
+            -- Not a packet of this protocol version
+            return 0
+        end
+        if offset + size > length then
+            pinfo.desegment_offset = offset
+            pinfo.desegment_len = offset + size - length
+            break
+        end
+
+        local packet = buffer(offset, size)
+        local subtree = tree:add(synapse, packet)
+        subtree:add_le(f_sequence, packet(0, 8))
+        subtree:add_le(f_length, packet(8, 4))
+        subtree:add_le(f_version, packet(12, 4))
+        subtree:add_le(f_client_send, packet(16, 8))
+        subtree:add_le(f_server_receive, packet(24, 8))
+        subtree:add_le(f_server_transmit, packet(32, 8))
+        local received = packet(24, 8):le_uint64()
+        local transmitted = packet(32, 8):le_uint64()
+        if received > 0 and transmitted >= received then
+            subtree:add(f_server_time, transmitted - received):set_generated()
+        end
+        subtree:add_le(f_server_processing, packet(40, 8))
+        subtree:add_le(f_replies, packet(48, 4))
+        subtree:add_le(f_reply_index, packet(52, 4))
+        subtree:add_le(f_cookie, packet(56, 8))
+        local kind = direction
+        if kind == "Reply" and packet(48, 4):le_uint() == 0 then
+            -- A UDP server's answer to a probe without the cookie of its source
+            kind = "Challenge"
+        end
+        subtree:add(f_direction, kind)
+        if size > HEADER_SIZE then
+            subtree:add(f_padding, packet(HEADER_SIZE, size - HEADER_SIZE))
+        end
+        table.insert(sequences, tostring(packet(0, 8):le_uint64()))
+        offset = offset + size
+    end
+
+    pinfo.cols.info = direction .. " seq=" .. table.concat(sequences, ",")
+    return offset
+end
+
+DissectorTable.get("tcp.port"):add(SERVER_PORT, synapse)
+DissectorTable.get("udp.port"):add(SERVER_PORT, synapse)
+"#,
+        version = PROTOCOL_VERSION,
+        client_version = env!("CARGO_PKG_VERSION"),
+        header_size = PACKET_SIZE,
+        server_port = server_port,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lua_dissector_matches_protocol() {
+        let lua = lua_dissector(9301);
+        assert!(lua.contains(&format!("version {}", PROTOCOL_VERSION)));
+        assert!(lua.contains(&format!("local HEADER_SIZE = {}", PACKET_SIZE)));
+        assert!(lua.contains("local SERVER_PORT = 9301"));
+        assert!(lua.contains("DissectorTable.get(\"udp.port\")"));
+        // Every block opened is closed again
+        let opened = lua.matches("function ").count()
+            + lua.matches(" then\n").count()
+            + lua.matches(" do\n").count();
+        assert_eq!(opened, lua.matches("end\n").count());
+    }
+}
@@ -5,6 +5,12 @@ use thiserror::Error;
 pub enum ProtocolError {
     #[error("Invalid packet size: expected {expected}, got {actual}")]
     InvalidPacketSize { expected: usize, actual: usize },
+
+    #[error("Unsupported protocol version: expected {expected}, got {actual}")]
+    UnsupportedVersion { expected: u32, actual: u32 },
+
+    #[error("Invalid reply count: at most {max}, got {actual}")]
+    InvalidReplyCount { max: u32, actual: u32 },
 }
 
 pub type Result<T> = std::result::Result<T, ProtocolError>;
@@ -1,8 +1,62 @@
 use crate::protocol::error::{ProtocolError, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::debug;
 
-/// Size of a packet in bytes (8 bytes for u64 sequence number)
-pub const PACKET_SIZE: usize = 8;
+/// Version of the wire format below; bump it whenever the packet layout changes
+pub const PROTOCOL_VERSION: u32 = 5;
+
+/// Size of the packet header in bytes, and the default packet size
+///
+/// Every packet starts with this header (all fields little-endian); larger packets pad
+/// it with zeros:
+///
+/// | Offset | Field                                     |
+/// |--------|-------------------------------------------|
+/// | 0      | sequence number (u64)                     |
+/// | 8      | packet length including padding (u32)    |
+/// | 12     | protocol version (u32)                    |
+/// | 16     | client send time (u64)                    |
+/// | 24     | server receive time (u64)                 |
+/// | 32     | server transmit time (u64)                |
+/// | 40     | server processing time (u64)              |
+/// | 48     | reply count (u32)                         |
+/// | 52     | reply index (u32)                         |
+/// | 56     | cookie (u64)                              |
+///
+/// Times are nanoseconds since the UNIX epoch on the clock of the host that took them,
+/// or 0 if not taken. The processing time is a duration in nanoseconds, or 0 if not
+/// known: how long the server took over the echo it sent before this one from the same
+/// socket or connection, from reading the probe until the write of its echo returned. An
+/// echo cannot carry the time of its own write, hence the previous one.
+///
+/// The reply count is the number of echoes the server sends back for the packet, at most
+/// [`MAX_REPLIES`]: 1 for a probe, 0 for the requests of a transaction before its last,
+/// which carries the number of responses (see [`crate::client::transaction`]). The
+/// server numbers its echoes of a packet from 0 in the reply index.
+///
+/// The cookie proves to a UDP server that the client receives at the address it sends
+/// from, or is 0 if it has none yet. A UDP server answers a packet without the right
+/// cookie with a challenge instead of its echoes: the header alone, with a reply count
+/// of 0 and the cookie to send from then on. An echo always has a reply count of 1 or
+/// more, so a challenge cannot be mistaken for one.
+pub const PACKET_SIZE: usize = 64;
+
+/// Most echoes a packet may ask the server for, which bounds the traffic a single
+/// (possibly spoofed) datagram can make it send
+pub const MAX_REPLIES: u32 = 16;
+
+/// Largest packet size, the most a UDP datagram over IPv4 can carry
+pub const MAX_PACKET_SIZE: usize = 65_507;
+
+const LENGTH_OFFSET: usize = 8;
+const VERSION_OFFSET: usize = 12;
+const CLIENT_SEND_OFFSET: usize = 16;
+const SERVER_RECEIVE_OFFSET: usize = 24;
+const SERVER_TRANSMIT_OFFSET: usize = 32;
+const SERVER_PROCESSING_OFFSET: usize = 40;
+const REPLY_COUNT_OFFSET: usize = 48;
+const REPLY_INDEX_OFFSET: usize = 52;
+const COOKIE_OFFSET: usize = 56;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SequenceNumber(pub u64);
@@ -10,18 +64,111 @@ pub struct SequenceNumber(pub u64);
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
     pub sequence: SequenceNumber,
+    /// Size on the wire in bytes: the header plus zero padding
+    pub size: usize,
+    /// Wall-clock time the client sent the packet (nanoseconds since the UNIX epoch)
+    pub client_send_ns: Option<u64>,
+    /// Wall-clock time the server had received the packet, stamped on the echo
+    pub server_receive_ns: Option<u64>,
+    /// Wall-clock time the server sent the echo back
+    pub server_transmit_ns: Option<u64>,
+    /// Time the server took over its previous echo, from reading the probe until the
+    /// write of the echo returned, on its monotonic clock (nanoseconds)
+    pub server_processing_ns: Option<u64>,
+    /// Echoes the server is to send back for the packet
+    pub replies: u32,
+    /// Which of the server's echoes of the packet this is, from 0
+    pub reply_index: u32,
+    /// Cookie a UDP server gave the client, 0 for none
+    pub cookie: u64,
 }
 
 impl Packet {
     pub fn new(sequence: SequenceNumber) -> Self {
-        Self { sequence }
+        Self::with_size(sequence, PACKET_SIZE)
+    }
+
+    /// Packet padded to `size` bytes; sizes below [`PACKET_SIZE`] are rounded up to it
+    pub fn with_size(sequence: SequenceNumber, size: usize) -> Self {
+        Self {
+            sequence,
+            size: size.max(PACKET_SIZE),
+            client_send_ns: None,
+            server_receive_ns: None,
+            server_transmit_ns: None,
+            server_processing_ns: None,
+            replies: 1,
+            reply_index: 0,
+            cookie: 0,
+        }
+    }
+
+    /// Packet asking for no echo, which a UDP client sends for the server's challenge
+    /// before it measures (see [`Packet::is_challenge`])
+    pub fn hello() -> Self {
+        Self {
+            replies: 0,
+            ..Self::new(SequenceNumber(0))
+        }
     }
 
-    pub fn encode(&self) -> [u8; PACKET_SIZE] {
-        self.sequence.0.to_le_bytes()
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size);
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    /// Encode into `buf`, replacing its contents, so a send loop can reuse one buffer
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.extend_from_slice(&self.sequence.0.to_le_bytes());
+        buf.extend_from_slice(&(self.size as u32).to_le_bytes());
+        buf.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        for time in [
+            self.client_send_ns,
+            self.server_receive_ns,
+            self.server_transmit_ns,
+            self.server_processing_ns,
+        ] {
+            buf.extend_from_slice(&time.unwrap_or(0).to_le_bytes());
+        }
+        buf.extend_from_slice(&self.replies.to_le_bytes());
+        buf.extend_from_slice(&self.reply_index.to_le_bytes());
+        buf.extend_from_slice(&self.cookie.to_le_bytes());
+        buf.resize(self.size, 0);
     }
 
     pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let size = Self::wire_size(bytes)?;
+        if bytes.len() != size {
+            debug!(expected = size, actual = bytes.len(), "Invalid packet size");
+            return Err(ProtocolError::InvalidPacketSize {
+                expected: size,
+                actual: bytes.len(),
+            });
+        }
+
+        let seq = read_u64(bytes, 0);
+        debug!(sequence = seq, "Packet decoded successfully");
+
+        let time = |offset| Some(read_u64(bytes, offset)).filter(|&ns| ns != 0);
+        Ok(Packet {
+            sequence: SequenceNumber(seq),
+            size,
+            client_send_ns: time(CLIENT_SEND_OFFSET),
+            server_receive_ns: time(SERVER_RECEIVE_OFFSET),
+            server_transmit_ns: time(SERVER_TRANSMIT_OFFSET),
+            server_processing_ns: time(SERVER_PROCESSING_OFFSET),
+            replies: read_u32(bytes, REPLY_COUNT_OFFSET),
+            reply_index: read_u32(bytes, REPLY_INDEX_OFFSET),
+            cookie: read_u64(bytes, COOKIE_OFFSET),
+        })
+    }
+
+    /// Length of the packet whose header starts `bytes`, padding included
+    ///
+    /// Lets a stream reader find the end of a packet after reading only the header.
+    pub fn wire_size(bytes: &[u8]) -> Result<usize> {
         if bytes.len() < PACKET_SIZE {
             debug!(
                 expected = PACKET_SIZE,
@@ -33,19 +180,106 @@ impl Packet {
                 actual: bytes.len(),
             });
         }
+        let version = read_u32(bytes, VERSION_OFFSET);
+        if version != PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion {
+                expected: PROTOCOL_VERSION,
+                actual: version,
+            });
+        }
+        let size = read_u32(bytes, LENGTH_OFFSET) as usize;
+        if !(PACKET_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(ProtocolError::InvalidPacketSize {
+                expected: PACKET_SIZE,
+                actual: size,
+            });
+        }
+        let replies = read_u32(bytes, REPLY_COUNT_OFFSET);
+        if replies > MAX_REPLIES {
+            return Err(ProtocolError::InvalidReplyCount {
+                max: MAX_REPLIES,
+                actual: replies,
+            });
+        }
+        Ok(size)
+    }
 
-        let mut buf = [0u8; PACKET_SIZE];
-        buf.copy_from_slice(&bytes[..PACKET_SIZE]);
-        let seq = u64::from_le_bytes(buf);
+    /// Stamp the server's receive and transmit times into an encoded packet
+    ///
+    /// Works on the raw bytes so the server can echo the padding without decoding it.
+    pub fn stamp_server_times(bytes: &mut [u8], receive_ns: u64, transmit_ns: u64) {
+        bytes[SERVER_RECEIVE_OFFSET..SERVER_RECEIVE_OFFSET + 8]
+            .copy_from_slice(&receive_ns.to_le_bytes());
+        bytes[SERVER_TRANSMIT_OFFSET..SERVER_TRANSMIT_OFFSET + 8]
+            .copy_from_slice(&transmit_ns.to_le_bytes());
+    }
 
-        debug!(sequence = seq, "Packet decoded successfully");
+    /// Number of echoes the encoded packet asks the server for
+    ///
+    /// Only meaningful for a packet [`Packet::wire_size`] accepted, which bounds it.
+    pub fn replies_requested(bytes: &[u8]) -> u32 {
+        read_u32(bytes, REPLY_COUNT_OFFSET)
+    }
 
-        Ok(Packet {
-            sequence: SequenceNumber(seq),
-        })
+    /// Number an echo of an encoded packet, from 0
+    pub fn stamp_reply_index(bytes: &mut [u8], index: u32) {
+        bytes[REPLY_INDEX_OFFSET..REPLY_INDEX_OFFSET + 4].copy_from_slice(&index.to_le_bytes());
+    }
+
+    /// Cookie the encoded packet carries, 0 for none
+    pub fn cookie_of(bytes: &[u8]) -> u64 {
+        read_u64(bytes, COOKIE_OFFSET)
+    }
+
+    /// Set the cookie of an encoded packet
+    pub fn stamp_cookie(bytes: &mut [u8], cookie: u64) {
+        bytes[COOKIE_OFFSET..COOKIE_OFFSET + 8].copy_from_slice(&cookie.to_le_bytes());
+    }
+
+    /// Turn the header of an encoded packet into a challenge carrying `cookie`
+    ///
+    /// The challenge is the header alone, so it is never larger than the packet.
+    pub fn make_challenge(bytes: &mut [u8], cookie: u64) {
+        bytes[LENGTH_OFFSET..LENGTH_OFFSET + 4]
+            .copy_from_slice(&(PACKET_SIZE as u32).to_le_bytes());
+        bytes[REPLY_COUNT_OFFSET..REPLY_COUNT_OFFSET + 4].copy_from_slice(&0u32.to_le_bytes());
+        Self::stamp_cookie(bytes, cookie);
+    }
+
+    /// Whether this is a UDP server's challenge rather than an echo
+    pub fn is_challenge(&self) -> bool {
+        self.replies == 0 && self.cookie != 0
+    }
+
+    /// Stamp the processing time of the server's previous echo into an encoded packet,
+    /// `None` for the first echo of a socket or connection
+    pub fn stamp_server_processing(bytes: &mut [u8], previous: Option<Duration>) {
+        let processing_ns = previous.map_or(0, |d| (d.as_nanos() as u64).max(1));
+        bytes[SERVER_PROCESSING_OFFSET..SERVER_PROCESSING_OFFSET + 8]
+            .copy_from_slice(&processing_ns.to_le_bytes());
     }
 }
 
+/// Current wall-clock time in nanoseconds since the UNIX epoch, as stamped into packets
+pub fn unix_time_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,6 +298,78 @@ mod tests {
         assert!(Packet::decode(&buf).is_err());
     }
 
+    #[test]
+    fn test_packet_padding() {
+        let packet = Packet::with_size(SequenceNumber(7), 80);
+        let encoded = packet.encode();
+        assert_eq!(encoded.len(), 80);
+        assert!(encoded[PACKET_SIZE..].iter().all(|&b| b == 0));
+        assert_eq!(Packet::decode(&encoded).unwrap(), packet);
+
+        // The header never shrinks
+        assert_eq!(Packet::with_size(SequenceNumber(7), 2).size, PACKET_SIZE);
+    }
+
+    #[test]
+    fn test_packet_timestamps() {
+        let mut packet = Packet::with_size(SequenceNumber(3), 100);
+        packet.client_send_ns = Some(1_000);
+        let mut encoded = packet.encode();
+        assert_eq!(Packet::wire_size(&encoded[..PACKET_SIZE]).unwrap(), 100);
+
+        Packet::stamp_server_times(&mut encoded, 1_500, 1_700);
+        let decoded = Packet::decode(&encoded).unwrap();
+        assert_eq!(decoded.client_send_ns, Some(1_000));
+        assert_eq!(decoded.server_receive_ns, Some(1_500));
+        assert_eq!(decoded.server_transmit_ns, Some(1_700));
+        assert_eq!(decoded.server_processing_ns, None);
+        Packet::stamp_server_processing(&mut encoded, Some(Duration::from_nanos(2_300)));
+        let decoded = Packet::decode(&encoded).unwrap();
+        assert_eq!(decoded.server_processing_ns, Some(2_300));
+        assert_eq!(decoded.server_transmit_ns, Some(1_700));
+        assert_eq!(decoded.size, 100);
+
+        assert_eq!(Packet::replies_requested(&encoded), 1);
+        Packet::stamp_reply_index(&mut encoded, 2);
+        assert_eq!(Packet::decode(&encoded).unwrap().reply_index, 2);
+
+        // Truncated packets and other protocol versions are rejected
+        assert!(Packet::decode(&encoded[..70]).is_err());
+        encoded[VERSION_OFFSET] = 1;
+        assert!(matches!(
+            Packet::decode(&encoded),
+            Err(ProtocolError::UnsupportedVersion { actual: 1, .. })
+        ));
+
+        // So are packets asking for more echoes than a server sends
+        let mut packet = Packet::new(SequenceNumber(4));
+        packet.replies = MAX_REPLIES + 1;
+        assert!(matches!(
+            Packet::decode(&packet.encode()),
+            Err(ProtocolError::InvalidReplyCount { actual: 17, .. })
+        ));
+    }
+
+    #[test]
+    fn test_packet_challenge() {
+        let mut packet = Packet::with_size(SequenceNumber(9), 200);
+        packet.cookie = 7;
+        let mut encoded = packet.encode();
+        assert_eq!(Packet::cookie_of(&encoded), 7);
+        assert!(!Packet::decode(&encoded).unwrap().is_challenge());
+
+        Packet::make_challenge(&mut encoded, 42);
+        let challenge = Packet::decode(&encoded[..PACKET_SIZE]).unwrap();
+        assert!(challenge.is_challenge());
+        assert_eq!(challenge.sequence, SequenceNumber(9));
+        assert_eq!(challenge.cookie, 42);
+
+        // A hello asks for no echo, and without a cookie it is not a challenge either
+        let hello = Packet::decode(&Packet::hello().encode()).unwrap();
+        assert_eq!(hello.replies, 0);
+        assert!(!hello.is_challenge());
+    }
+
     #[test]
     fn test_packet_roundtrip() {
         let seq = SequenceNumber(12345);
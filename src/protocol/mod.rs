@@ -1,7 +1,16 @@
 //! Protocol module for Synapse
 
+pub mod clocksync;
+pub mod dissector;
 pub mod error;
 pub mod message;
+pub mod transport;
 
+pub use clocksync::{ClockOffset, ClockSample};
+pub use dissector::lua_dissector;
 pub use error::{ProtocolError, Result as ProtocolResult};
-pub use message::{Packet, SequenceNumber, PACKET_SIZE};
+pub use message::{
+    unix_time_ns, Packet, SequenceNumber, MAX_PACKET_SIZE, MAX_REPLIES, PACKET_SIZE,
+    PROTOCOL_VERSION,
+};
+pub use transport::Transport;
@@ -0,0 +1,21 @@
+use clap::ValueEnum;
+use std::fmt;
+
+/// Transport the probes travel over; client and server must use the same one
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// One TCP connection; a late reply stalls the stream until it arrives
+    #[default]
+    Tcp,
+    /// One datagram per probe and reply; late and duplicate replies are discarded
+    Udp,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+        }
+    }
+}
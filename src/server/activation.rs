@@ -0,0 +1,192 @@
+//! systemd socket activation
+//!
+//! Under a systemd `.socket` unit, systemd binds the echo port itself and starts the
+//! server on the first client, handing the socket over as file descriptor 3 with
+//! `LISTEN_PID` and `LISTEN_FDS` set. The socket outlives the server, so a restart (or
+//! an exit after `POST /drain`) does not drop the port: clients arriving in between
+//! queue in the backlog instead of being refused.
+//!
+//! Only the echo socket is taken over: a `.socket` unit with `Accept=no` and a single
+//! `ListenStream=` (TCP) or `ListenDatagram=` (UDP). The metrics endpoint is bound as
+//! usual.
+
+use crate::protocol::Transport;
+use std::env;
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+const LISTEN_FDS_START: i32 = 3;
+
+/// Socket passed by systemd
+#[derive(Debug)]
+pub enum ActivatedSocket {
+    /// Listening TCP socket (`ListenStream=`)
+    Tcp(TcpListener),
+    /// UDP socket (`ListenDatagram=`)
+    Udp(UdpSocket),
+}
+
+impl ActivatedSocket {
+    /// Transport the socket carries
+    pub fn transport(&self) -> Transport {
+        match self {
+            ActivatedSocket::Tcp(_) => Transport::Tcp,
+            ActivatedSocket::Udp(_) => Transport::Udp,
+        }
+    }
+}
+
+/// The echo socket passed by systemd, if the server was socket-activated
+///
+/// The `LISTEN_*` variables are removed, so they do not leak into child processes.
+pub fn activated_socket() -> io::Result<Option<ActivatedSocket>> {
+    let count = listen_fds(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    match count {
+        0 => Ok(None),
+        1 => imp::take(LISTEN_FDS_START).map(Some),
+        n => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("systemd passed {} sockets; expected one echo socket", n),
+        )),
+    }
+}
+
+/// Number of sockets passed to process `pid`, from the values of `LISTEN_PID` and
+/// `LISTEN_FDS`
+///
+/// Variables meant for another process (inherited from a parent that did not remove
+/// them) pass nothing.
+fn listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    pid: u32,
+) -> Result<usize, String> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(0);
+    };
+    if listen_pid.trim().parse::<u32>().ok() != Some(pid) {
+        return Ok(0);
+    }
+    listen_fds
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid LISTEN_FDS '{}'", listen_fds))
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::ActivatedSocket;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+    /// Take ownership of the passed descriptor `fd`, checking it is a usable socket
+    pub(super) fn take(fd: RawFd) -> io::Result<ActivatedSocket> {
+        // SAFETY: systemd hands descriptors from LISTEN_FDS_START on to this process, and
+        // nothing else in it claims them
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        // systemd passes the descriptor without close-on-exec
+        // SAFETY: plain fcntl(2) call on the descriptor owned above
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        match socket_option(&fd, libc::SO_TYPE)? {
+            libc::SOCK_STREAM => {
+                if socket_option(&fd, libc::SO_ACCEPTCONN)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "systemd passed a connection, not a listening socket (set Accept=no)",
+                    ));
+                }
+                Ok(ActivatedSocket::Tcp(fd.into()))
+            }
+            libc::SOCK_DGRAM => Ok(ActivatedSocket::Udp(fd.into())),
+            kind => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("systemd passed an unsupported socket (type {})", kind),
+            )),
+        }
+    }
+
+    /// Integer `SOL_SOCKET` option `name` of `fd`
+    fn socket_option(fd: &OwnedFd, name: libc::c_int) -> io::Result<libc::c_int> {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: `value` and `len` are valid for writes of the given size
+        let rc = unsafe {
+            libc::getsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                name,
+                (&mut value as *mut libc::c_int).cast(),
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::ActivatedSocket;
+    use std::io;
+
+    pub(super) fn take(_fd: i32) -> io::Result<ActivatedSocket> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "socket activation is only supported on Linux",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fds() {
+        assert_eq!(listen_fds(None, None, 42), Ok(0));
+        assert_eq!(listen_fds(Some("42"), Some("1"), 42), Ok(1));
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), Ok(2));
+        // Meant for another process
+        assert_eq!(listen_fds(Some("41"), Some("1"), 42), Ok(0));
+        assert_eq!(listen_fds(Some("42"), None, 42), Ok(0));
+        assert!(listen_fds(Some("42"), Some("x"), 42).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_take_checks_socket_type() -> io::Result<()> {
+        use std::os::fd::IntoRawFd;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let taken = imp::take(listener.into_raw_fd())?;
+        assert_eq!(taken.transport(), Transport::Tcp);
+        match taken {
+            ActivatedSocket::Tcp(listener) => assert_eq!(listener.local_addr()?, addr),
+            ActivatedSocket::Udp(_) => unreachable!(),
+        }
+
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        assert_eq!(imp::take(socket.into_raw_fd())?.transport(), Transport::Udp);
+
+        // A connected stream is what systemd passes with Accept=yes
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = std::net::TcpStream::connect(listener.local_addr()?)?;
+        assert!(imp::take(stream.into_raw_fd()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! Async engine of the TCP echo server
+//!
+//! The default engine serves every TCP connection from its own OS thread, blocked in a
+//! read until the next probe arrives. That is the shortest path from socket to echo,
+//! but each client costs a thread and its stack, which limits a server shared by many
+//! clients. `--engine async` serves connections as tasks on a multi-threaded tokio
+//! runtime instead, so thousands of clients cost a few kilobytes each. A probe then
+//! waits for the runtime to poll its task, which adds a little latency to the echo; use
+//! the default engine when measuring a handful of clients.
+//!
+//! Frames are checked, counted and stamped exactly as by the threaded engine, and a
+//! drain request closes the listener and waits for the open connections likewise. UDP
+//! is served from a single socket by either engine.
+
+use crate::protocol::{unix_time_ns, Packet, PACKET_SIZE};
+use crate::server::frame::FrameLimits;
+use crate::server::monitor::ServerCounters;
+use clap::ValueEnum;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+/// How the server runs TCP connections
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// One OS thread per connection
+    #[default]
+    Threads,
+    /// Tasks on a tokio runtime
+    Async,
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Threads => write!(f, "threads"),
+            Engine::Async => write!(f, "async"),
+        }
+    }
+}
+
+/// Echo packets on every connection accepted by `listener`, until the server drains
+///
+/// Blocks the calling thread on a runtime of its own. As with the threaded engine, a
+/// drain request connects to `listener` to wake the accept loop up;
+/// `drain_poll_interval` is how often the drain then checks for open connections.
+pub fn serve_tcp(
+    listener: std::net::TcpListener,
+    counters: Arc<ServerCounters>,
+    limits: FrameLimits,
+    drain_poll_interval: Duration,
+) -> io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("synapse-echo")
+        .build()?;
+    runtime.block_on(async move {
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        info!(
+            engine = "async",
+            "Ready to accept connections and echo packets..."
+        );
+
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    counters.increment_error();
+                    error!(error = %e, "Failed to accept connection");
+                    continue;
+                }
+            };
+            if counters.is_draining() {
+                break;
+            }
+            info!(peer = %peer_addr, "New client connected");
+            // The echoes of a transaction or of pipelined probes go out back to back,
+            // which Nagle would hold back
+            if let Err(e) = stream.set_nodelay(true) {
+                counters.increment_error();
+                error!(error = %e, peer = %peer_addr, "Failed to set up connection");
+                continue;
+            }
+            tokio::spawn(serve_connection(
+                stream,
+                peer_addr,
+                Arc::clone(&counters),
+                limits,
+            ));
+        }
+
+        // As with the threaded engine, a socket passed by systemd stays open there
+        drop(listener);
+        info!(
+            active_connections = counters.active_connections(),
+            "Draining: waiting for open connections to close"
+        );
+        while counters.active_connections() > 0 {
+            tokio::time::sleep(drain_poll_interval).await;
+        }
+        info!("Drained; exiting");
+        Ok(())
+    })
+}
+
+/// Echo the packets of one TCP connection until the client disconnects
+async fn serve_connection(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    counters: Arc<ServerCounters>,
+    limits: FrameLimits,
+) {
+    counters.connection_opened();
+
+    // Sized to the connection's probes once the first one arrives, not to the largest
+    // accepted frame, to keep idle connections cheap
+    let mut buf = vec![0u8; PACKET_SIZE];
+    let mut payload_size = None;
+    // Read to written of the last echo, sent back with the next one
+    let mut previous_processing = None;
+
+    'connection: loop {
+        if let Err(e) = stream.read_exact(&mut buf[..PACKET_SIZE]).await {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                debug!(peer = %peer_addr, "Client disconnected");
+            } else {
+                counters.increment_error();
+                error!(error = %e, peer = %peer_addr, "Failed to receive packet");
+            }
+            break;
+        }
+        let size = match limits.check(&buf[..PACKET_SIZE], payload_size) {
+            Ok(size) => size,
+            Err(violation) => {
+                // The stream cannot be resynchronized after a frame it does not accept
+                counters.reject_frame(violation);
+                warn!(
+                    reason = %violation,
+                    peer = %peer_addr,
+                    "Rejected frame; closing connection"
+                );
+                break;
+            }
+        };
+        if payload_size.is_none() {
+            buf.resize(size, 0);
+            payload_size = Some(size);
+        }
+        if let Err(e) = stream.read_exact(&mut buf[PACKET_SIZE..size]).await {
+            counters.increment_error();
+            error!(error = %e, peer = %peer_addr, "Failed to receive packet");
+            break;
+        }
+        let received_ns = unix_time_ns();
+        let received = Instant::now();
+        counters.increment_received();
+
+        // Echo back the same packet with the server's timestamps filled in, as many
+        // times as it asks for
+        for index in 0..Packet::replies_requested(&buf[..size]) {
+            Packet::stamp_reply_index(&mut buf[..size], index);
+            Packet::stamp_server_processing(&mut buf[..size], previous_processing);
+            Packet::stamp_server_times(&mut buf[..size], received_ns, unix_time_ns());
+            match stream.write_all(&buf[..size]).await {
+                Ok(()) => {
+                    previous_processing = Some(received.elapsed());
+                    counters.increment_sent();
+                    counters.add_bytes_sent(size as u64);
+                }
+                Err(e) => {
+                    counters.increment_error();
+                    error!(error = %e, peer = %peer_addr, "Failed to send packet");
+                    break 'connection;
+                }
+            }
+        }
+    }
+
+    counters.connection_closed();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::SequenceNumber;
+    use crate::server::monitor::ServerMonitor;
+    use std::io::{Read, Write};
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn test_serve_tcp_many_clients_and_drain() -> io::Result<()> {
+        let monitor = ServerMonitor::new(100);
+        let counters = Arc::new(monitor.counters());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let limits = FrameLimits::new(1024);
+        let server =
+            thread::spawn(move || serve_tcp(listener, counters, limits, Duration::from_millis(10)));
+
+        // Every client stays connected, so all are served at once
+        let mut clients = Vec::new();
+        for i in 0..200 {
+            let mut stream = std::net::TcpStream::connect(addr)?;
+            let mut probe = Vec::new();
+            Packet::with_size(SequenceNumber(i), 100).encode_into(&mut probe);
+            stream.write_all(&probe)?;
+            let mut reply = vec![0u8; probe.len()];
+            stream.read_exact(&mut reply)?;
+            let reply = Packet::decode(&reply).expect("reply decodes");
+            assert_eq!(reply.sequence, SequenceNumber(i));
+            assert!(reply.server_receive_ns.is_some());
+            clients.push(stream);
+        }
+        // The last reply can reach its client before the server counts it
+        let counted = Instant::now();
+        while monitor.stats().packets_sent < 200 && counted.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(monitor.stats().active_connections, 200);
+        assert_eq!(monitor.stats().packets_sent, 200);
+
+        // An oversized frame closes its connection
+        let mut stream = std::net::TcpStream::connect(addr)?;
+        let mut probe = Vec::new();
+        Packet::with_size(SequenceNumber(0), 2048).encode_into(&mut probe);
+        stream.write_all(&probe)?;
+        // Closed with the body unread, which the kernel answers with a reset
+        let closed = stream.read(&mut [0u8; 16]);
+        assert!(matches!(closed, Ok(0)) || closed.is_err(), "{:?}", closed);
+        assert_eq!(monitor.stats().rejected_frames, [0, 1, 0]);
+
+        // Draining waits for the open connections, once a connection wakes the accept
+        // loop up, as the admin endpoint's does
+        monitor.start_draining();
+        std::net::TcpStream::connect(addr)?;
+        thread::sleep(Duration::from_millis(50));
+        assert!(!server.is_finished());
+        drop(clients);
+        server.join().expect("server thread")?;
+        assert_eq!(monitor.stats().active_connections, 0);
+        Ok(())
+    }
+}
@@ -2,13 +2,24 @@
 //!
 //! Provides CLI argument parsing and validation for the Synapse server.
 
-use clap::Parser;
+use crate::config_file::expand_args;
+use crate::protocol::{Transport, MAX_PACKET_SIZE, PACKET_SIZE};
+#[cfg(feature = "tokio")]
+use crate::server::async_engine::Engine;
+use clap::{CommandFactory, Parser};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 use tracing::debug;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "synapse-server")]
-#[command(about = "High-performance TCP echo server for application diagnostics")]
+#[command(about = "High-performance TCP/UDP echo server for application diagnostics")]
 pub struct ServerConfig {
+    /// Read flags from this TOML file (keys are flag names); flags given on the command
+    /// line take precedence
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// Bind address
     #[arg(long, default_value = "0.0.0.0")]
     pub bind: String,
@@ -17,6 +28,10 @@ pub struct ServerConfig {
     #[arg(long, default_value_t = 8080)]
     pub port: u16,
 
+    /// Transport to echo probes on; clients must use the same one
+    #[arg(long, value_enum, default_value_t = Transport::Tcp)]
+    pub transport: Transport,
+
     /// Monitor update interval in milliseconds
     #[arg(long, default_value_t = 100)]
     pub update_interval: u64,
@@ -32,14 +47,77 @@ pub struct ServerConfig {
     /// Log format (text or json)
     #[arg(long, default_value = "text", value_parser = ["text", "json"])]
     pub log_format: String,
+
+    /// Serve Prometheus metrics over HTTP at /metrics on this port (same bind address)
+    #[arg(long)]
+    pub metrics_port: Option<u16>,
+
+    /// Take admin commands over HTTP on this port (POST /drain), bound to --admin-bind
+    #[arg(long, value_name = "PORT")]
+    pub admin_port: Option<u16>,
+
+    /// Address of the --admin-port listener; anyone who can reach it can drain the server
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1")]
+    pub admin_bind: String,
+
+    /// Largest probe to echo in bytes, header included; clients sending larger frames
+    /// are disconnected
+    #[arg(long, value_name = "BYTES", default_value_t = MAX_PACKET_SIZE)]
+    pub max_payload_size: usize,
+
+    /// Send at most this many replies per second to each source address that passed the
+    /// cookie exchange, and as many challenges to each that has not (UDP only); the
+    /// datagrams beyond are dropped
+    #[arg(long, value_name = "PPS")]
+    pub udp_rate_limit: Option<u32>,
+
+    /// Serve UDP from this many sockets sharing the port (SO_REUSEPORT), each on a thread
+    /// of its own, so the echo scales across cores with the number of client flows
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub udp_sockets: usize,
+
+    /// Serve TCP connections from one thread each (threads), or as tasks on a tokio
+    /// runtime (async) to handle thousands of concurrent clients
+    #[cfg(feature = "tokio")]
+    #[arg(long, value_enum, default_value_t = Engine::Threads)]
+    pub engine: Engine,
 }
 
 impl ServerConfig {
+    /// Parse the process arguments, with the flags of a `--config` file beneath them
+    ///
+    /// Exits with a usage error, as [`Parser::parse`] does, if they are invalid.
+    pub fn load() -> Self {
+        Self::try_load_from(std::env::args()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args` (program name first), with the flags of a `--config` file beneath them
+    pub fn try_load_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let args = expand_args(&Self::command(), args.into_iter().map(Into::into).collect())?;
+        Self::try_parse_from(args)
+    }
+
     /// Returns the full bind address as a string (bind:port)
     pub fn address(&self) -> String {
         format!("{}:{}", self.bind, self.port)
     }
 
+    /// Returns the address of the metrics endpoint, if enabled
+    pub fn metrics_address(&self) -> Option<String> {
+        self.metrics_port
+            .map(|port| format!("{}:{}", self.bind, port))
+    }
+
+    /// Returns the address of the admin endpoint, if enabled
+    pub fn admin_address(&self) -> Option<String> {
+        self.admin_port
+            .map(|port| format!("{}:{}", self.admin_bind, port))
+    }
+
     /// Validates the configuration values
     pub fn validate(&self) -> Result<(), String> {
         debug!("Validating server configuration");
@@ -48,6 +126,55 @@ impl ServerConfig {
             return Err("port must be > 0".into());
         }
 
+        if let Some(metrics_port) = self.metrics_port {
+            if metrics_port == 0 {
+                return Err("metrics_port must be > 0".into());
+            }
+            if metrics_port == self.port && self.transport == Transport::Tcp {
+                return Err("metrics_port must differ from port".into());
+            }
+        }
+
+        if let Some(admin_port) = self.admin_port {
+            if admin_port == 0 {
+                return Err("admin_port must be > 0".into());
+            }
+            if self.metrics_port == Some(admin_port)
+                || (admin_port == self.port && self.transport == Transport::Tcp)
+            {
+                return Err("admin_port must differ from port and metrics_port".into());
+            }
+        }
+
+        if !(PACKET_SIZE..=MAX_PACKET_SIZE).contains(&self.max_payload_size) {
+            return Err(format!(
+                "max_payload_size must be between {} and {}",
+                PACKET_SIZE, MAX_PACKET_SIZE
+            ));
+        }
+
+        match self.udp_rate_limit {
+            Some(0) => return Err("udp_rate_limit must be > 0".into()),
+            Some(_) if self.transport != Transport::Udp => {
+                return Err("udp_rate_limit requires --transport udp".into())
+            }
+            _ => {}
+        }
+
+        match self.udp_sockets {
+            0 => return Err("udp_sockets must be > 0".into()),
+            1 => {}
+            _ if self.transport != Transport::Udp => {
+                return Err("udp_sockets requires --transport udp".into())
+            }
+            _ => {}
+        }
+
+        #[cfg(feature = "tokio")]
+        if self.engine == Engine::Async && self.transport != Transport::Tcp {
+            return Err("engine async requires --transport tcp".into());
+        }
+
         if self.update_interval == 0 {
             return Err("update_interval must be > 0".into());
         }
@@ -69,6 +196,14 @@ impl ServerConfig {
     pub fn is_json_format(&self) -> bool {
         self.log_format.to_lowercase() == "json"
     }
+
+    /// Returns true if the server redraws its live status line
+    ///
+    /// Not with --quiet, nor when stdout is not a terminal, where every redraw would pile
+    /// up in the log as a carriage return and escape codes.
+    pub fn live_view(&self) -> bool {
+        !self.quiet && io::stdout().is_terminal()
+    }
 }
 
 #[cfg(test)]
@@ -78,12 +213,22 @@ mod tests {
     #[test]
     fn test_default_config() {
         let config = ServerConfig {
+            config: None,
             bind: "0.0.0.0".to_string(),
             port: 8080,
+            transport: Transport::Tcp,
             update_interval: 100,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            metrics_port: None,
+            admin_port: None,
+            admin_bind: "127.0.0.1".to_string(),
+            max_payload_size: MAX_PACKET_SIZE,
+            udp_rate_limit: None,
+            udp_sockets: 1,
+            #[cfg(feature = "tokio")]
+            engine: Engine::Threads,
         };
 
         assert_eq!(config.address(), "0.0.0.0:8080");
@@ -94,15 +239,26 @@ mod tests {
     #[test]
     fn test_custom_config() {
         let config = ServerConfig {
+            config: None,
             bind: "127.0.0.1".to_string(),
             port: 9000,
+            transport: Transport::Udp,
             update_interval: 50,
             quiet: true,
             log_level: "debug".to_string(),
             log_format: "json".to_string(),
+            metrics_port: Some(9100),
+            admin_port: None,
+            admin_bind: "127.0.0.1".to_string(),
+            max_payload_size: 1500,
+            udp_rate_limit: Some(10_000),
+            udp_sockets: 1,
+            #[cfg(feature = "tokio")]
+            engine: Engine::Threads,
         };
 
         assert_eq!(config.address(), "127.0.0.1:9000");
+        assert_eq!(config.metrics_address().as_deref(), Some("127.0.0.1:9100"));
         assert!(config.is_json_format());
         assert!(config.validate().is_ok());
     }
@@ -110,12 +266,22 @@ mod tests {
     #[test]
     fn test_invalid_port() {
         let config = ServerConfig {
+            config: None,
             bind: "0.0.0.0".to_string(),
             port: 0,
+            transport: Transport::Tcp,
             update_interval: 100,
             quiet: false,
             log_level: "info".to_string(),
             log_format: "text".to_string(),
+            metrics_port: None,
+            admin_port: None,
+            admin_bind: "127.0.0.1".to_string(),
+            max_payload_size: MAX_PACKET_SIZE,
+            udp_rate_limit: None,
+            udp_sockets: 1,
+            #[cfg(feature = "tokio")]
+            engine: Engine::Threads,
         };
 
         assert!(config.validate().is_err());
@@ -124,14 +290,146 @@ mod tests {
     #[test]
     fn test_invalid_log_level() {
         let config = ServerConfig {
+            config: None,
             bind: "0.0.0.0".to_string(),
             port: 8080,
+            transport: Transport::Tcp,
             update_interval: 100,
             quiet: false,
             log_level: "invalid".to_string(),
             log_format: "text".to_string(),
+            metrics_port: None,
+            admin_port: None,
+            admin_bind: "127.0.0.1".to_string(),
+            max_payload_size: MAX_PACKET_SIZE,
+            udp_rate_limit: None,
+            udp_sockets: 1,
+            #[cfg(feature = "tokio")]
+            engine: Engine::Threads,
         };
 
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_invalid_metrics_port() {
+        let mut config = ServerConfig {
+            config: None,
+            bind: "0.0.0.0".to_string(),
+            port: 8080,
+            transport: Transport::Tcp,
+            update_interval: 100,
+            quiet: false,
+            log_level: "info".to_string(),
+            log_format: "text".to_string(),
+            metrics_port: Some(8080),
+            admin_port: None,
+            admin_bind: "127.0.0.1".to_string(),
+            max_payload_size: MAX_PACKET_SIZE,
+            udp_rate_limit: None,
+            udp_sockets: 1,
+            #[cfg(feature = "tokio")]
+            engine: Engine::Threads,
+        };
+        assert!(config.validate().is_err());
+
+        // A UDP server leaves the TCP port free
+        config.transport = Transport::Udp;
+        assert!(config.validate().is_ok());
+
+        config.metrics_port = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_admin_port() {
+        let config = ServerConfig::try_load_from(["synapse-server"]).unwrap();
+        assert_eq!(config.admin_address(), None);
+        let mut config =
+            ServerConfig::try_load_from(["synapse-server", "--admin-port", "9101"]).unwrap();
+        assert_eq!(config.admin_address().as_deref(), Some("127.0.0.1:9101"));
+        assert!(config.validate().is_ok());
+
+        config.metrics_port = Some(9101);
+        assert!(config.validate().is_err());
+        config.metrics_port = None;
+        config.admin_port = Some(config.port);
+        assert!(config.validate().is_err());
+        config.admin_port = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_max_payload_size() {
+        let mut config = ServerConfig::try_load_from(["synapse-server"]).unwrap();
+        assert_eq!(config.max_payload_size, MAX_PACKET_SIZE);
+        assert!(config.validate().is_ok());
+
+        config.max_payload_size = PACKET_SIZE - 1;
+        assert!(config.validate().is_err());
+        config.max_payload_size = MAX_PACKET_SIZE + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_udp_rate_limit() {
+        let mut config =
+            ServerConfig::try_load_from(["synapse-server", "--udp-rate-limit", "1000"]).unwrap();
+        assert!(config.validate().is_err(), "TCP has no rate limit");
+
+        config.transport = Transport::Udp;
+        assert!(config.validate().is_ok());
+        config.udp_rate_limit = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_udp_sockets() {
+        let mut config =
+            ServerConfig::try_load_from(["synapse-server", "--udp-sockets", "4"]).unwrap();
+        assert_eq!(config.udp_sockets, 4);
+        assert!(
+            config.validate().is_err(),
+            "TCP has a socket per connection"
+        );
+
+        config.transport = Transport::Udp;
+        assert!(config.validate().is_ok());
+        config.udp_sockets = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_engine() {
+        let config = ServerConfig::try_load_from(["synapse-server"]).unwrap();
+        assert_eq!(config.engine, Engine::Threads);
+
+        let mut config =
+            ServerConfig::try_load_from(["synapse-server", "--engine", "async"]).unwrap();
+        assert_eq!(config.engine, Engine::Async);
+        assert!(config.validate().is_ok());
+        config.transport = Transport::Udp;
+        assert!(config.validate().is_err(), "UDP has one socket either way");
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path =
+            std::env::temp_dir().join(format!("synapse-{}-server-config.toml", std::process::id()));
+        std::fs::write(&path, "port = 9000\ntransport = \"udp\"\nquiet = true\n").unwrap();
+        let config = ServerConfig::try_load_from([
+            "synapse-server",
+            "--config",
+            path.to_str().unwrap(),
+            "--port",
+            "9001",
+        ]);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.port, 9001);
+        assert_eq!(config.transport, Transport::Udp);
+        assert!(config.quiet);
+    }
 }
@@ -0,0 +1,66 @@
+//! Source validation of the UDP server
+//!
+//! A UDP echo server answers whatever address a datagram claims to come from, so a
+//! spoofed source could aim its echoes at a third party. Before echoing, the server
+//! therefore checks that the sender receives at its address: a datagram whose cookie is
+//! not the one of its source address and port is answered with a challenge carrying
+//! that cookie (see [`PACKET_SIZE`](crate::protocol::PACKET_SIZE)), and only datagrams
+//! sending it back get their echoes. A spoofed source never sees its challenges, so it
+//! gets nothing but them.
+//!
+//! The cookie is a keyed hash of the address, so the server keeps no state per client;
+//! the key is random and lives as long as the server, so clients validate again after a
+//! restart. A challenge is the header alone, one per datagram and never larger than it,
+//! so spoofed traffic is still reflected, but not amplified; with `--udp-rate-limit`,
+//! challenges count against the limit too (see [`crate::server::ratelimit`]).
+
+use std::hash::{BuildHasher, RandomState};
+use std::net::SocketAddr;
+
+/// Issues and checks the cookies of source addresses
+#[derive(Debug, Clone, Default)]
+pub struct SourceCookies {
+    key: RandomState,
+}
+
+impl SourceCookies {
+    /// Cookies under a new random key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cookie of `source`, never 0 (which stands for no cookie)
+    pub fn cookie(&self, source: SocketAddr) -> u64 {
+        self.key.hash_one(source) | 1
+    }
+
+    /// Whether a datagram from `source` carries its cookie
+    pub fn is_valid(&self, source: SocketAddr, cookie: u64) -> bool {
+        cookie == self.cookie(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_cookies() {
+        let cookies = SourceCookies::new();
+        let a: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let b: SocketAddr = "10.0.0.1:4001".parse().unwrap();
+
+        let cookie = cookies.cookie(a);
+        assert_ne!(cookie, 0);
+        assert!(cookies.is_valid(a, cookie));
+        assert!(!cookies.is_valid(a, 0));
+        assert!(
+            !cookies.is_valid(b, cookie),
+            "the port is part of the address"
+        );
+
+        // Every socket of a group shares the key
+        assert!(cookies.clone().is_valid(a, cookie));
+        assert!(!SourceCookies::new().is_valid(a, cookie));
+    }
+}
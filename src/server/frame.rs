@@ -0,0 +1,136 @@
+//! Frame validation of the echo server
+//!
+//! The server echoes whatever length a probe header announces, so without limits a
+//! misconfigured or hostile client could make it read and echo 64 KiB frames or stream
+//! garbage at it. Every frame is checked before its body is read:
+//!
+//! - a header that does not decode (wrong protocol version, a length below the
+//!   header size) is **invalid**
+//! - a frame longer than `--max-payload-size` is **oversized**
+//! - on a TCP connection, the first frame sets the payload size of the connection (a
+//!   client sends every probe at the same `--payload-size`); a later frame of another
+//!   size is a **size mismatch**
+//!
+//! A TCP client whose frame is rejected is disconnected, as the stream cannot be
+//! resynchronized; a rejected UDP datagram is dropped. Each kind of rejection has its
+//! own counter.
+
+use crate::protocol::{Packet, ProtocolError, MAX_PACKET_SIZE, PACKET_SIZE};
+use std::fmt;
+
+/// Reason a frame was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// Header that does not decode
+    Invalid,
+    /// Frame longer than the maximum payload size
+    Oversized,
+    /// Frame of another size than the connection's earlier frames
+    SizeMismatch,
+}
+
+impl Violation {
+    /// Every kind of violation, in counter order
+    pub const ALL: [Violation; 3] = [
+        Violation::Invalid,
+        Violation::Oversized,
+        Violation::SizeMismatch,
+    ];
+
+    /// Label of the violation in logs and metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Violation::Invalid => "invalid",
+            Violation::Oversized => "oversized",
+            Violation::SizeMismatch => "size_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Size limits frames are checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameLimits {
+    max_size: usize,
+}
+
+impl FrameLimits {
+    /// Accept frames of up to `max_size` bytes, clamped to the protocol's range
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size: max_size.clamp(PACKET_SIZE, MAX_PACKET_SIZE),
+        }
+    }
+
+    /// Largest accepted frame, and so the most a single read needs to hold
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Size of the frame whose header starts `bytes`, if it is accepted
+    ///
+    /// `payload_size` is the size set by the first frame of a connection, if any.
+    pub fn check(&self, bytes: &[u8], payload_size: Option<usize>) -> Result<usize, Violation> {
+        let size = match Packet::wire_size(bytes) {
+            Ok(size) => size,
+            // Only the length field can announce more than the protocol allows
+            Err(ProtocolError::InvalidPacketSize { actual, .. }) if actual > MAX_PACKET_SIZE => {
+                return Err(Violation::Oversized)
+            }
+            Err(_) => return Err(Violation::Invalid),
+        };
+        if size > self.max_size {
+            return Err(Violation::Oversized);
+        }
+        match payload_size {
+            Some(expected) if size != expected => Err(Violation::SizeMismatch),
+            _ => Ok(size),
+        }
+    }
+}
+
+impl Default for FrameLimits {
+    fn default() -> Self {
+        Self::new(MAX_PACKET_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::SequenceNumber;
+
+    fn header(size: usize) -> Vec<u8> {
+        Packet::with_size(SequenceNumber(1), size).encode()[..PACKET_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_frame_limits() {
+        let limits = FrameLimits::new(1500);
+        assert_eq!(limits.check(&header(64), None), Ok(64));
+        assert_eq!(limits.check(&header(64), Some(64)), Ok(64));
+        assert_eq!(
+            limits.check(&header(128), Some(64)),
+            Err(Violation::SizeMismatch)
+        );
+        assert_eq!(limits.check(&header(9000), None), Err(Violation::Oversized));
+
+        let mut garbage = header(64);
+        garbage[12] = 0xff;
+        assert_eq!(limits.check(&garbage, None), Err(Violation::Invalid));
+        assert_eq!(limits.check(&[0u8; 8], None), Err(Violation::Invalid));
+
+        // A length field beyond the protocol's maximum
+        let mut huge = header(64);
+        huge[8..12].copy_from_slice(&(1u32 << 20).to_le_bytes());
+        assert_eq!(limits.check(&huge, None), Err(Violation::Oversized));
+
+        assert_eq!(FrameLimits::new(1).max_size(), PACKET_SIZE);
+        assert_eq!(FrameLimits::default().max_size(), MAX_PACKET_SIZE);
+    }
+}
@@ -0,0 +1,348 @@
+//! Prometheus metrics and admin endpoints
+//!
+//! With `--metrics-port` the server answers `GET /metrics` on that port with its
+//! counters in the Prometheus text exposition format, so a long-running echo server
+//! can be scraped instead of watched on the terminal status line. Throughput is left
+//! to the query, e.g. `rate(synapse_echo_bytes_total[1m])`. The metrics port is
+//! read-only: it listens on the echo's bind address, often reachable by anyone.
+//!
+//! With `--admin-port` a second listener, on `--admin-bind` (loopback by default), takes
+//! one admin command: `POST /drain` puts the server in maintenance mode. A TCP server
+//! stops accepting connections, lets those open finish their runs and exits once the
+//! last one closes; a UDP server, which cannot tell a new client from an old one, keeps
+//! echoing and exits once no datagram has arrived for a while. `synapse_draining`
+//! reports the mode, and `synapse_active_connections` what is left.
+
+use crate::server::frame::Violation;
+use crate::server::monitor::{ServerMonitor, ServerStats};
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Time a scraper gets to send its request before the connection is dropped.
+///
+/// Scrapes are answered one at a time, so a stalled client must not hold up the next.
+const REQUEST_TIMEOUT_MS: u64 = 1000;
+
+/// Largest request read; only the request line is looked at.
+const MAX_REQUEST_SIZE: usize = 4096;
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Requests an HTTP listener of the server answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    /// `GET /metrics`
+    Metrics,
+    /// `POST /drain`, waking the accept loop of the echo listener at this address
+    Admin(Option<SocketAddr>),
+}
+
+/// Start answering scrapes on `listener` in a background thread.
+///
+/// The listener is bound by the caller, so a port already in use fails the server
+/// before it starts echoing.
+pub fn start_metrics(listener: TcpListener, monitor: Arc<ServerMonitor>) {
+    if let Ok(addr) = listener.local_addr() {
+        info!(address = %addr, "Metrics endpoint listening on /metrics");
+    }
+    serve(listener, monitor, Endpoint::Metrics);
+}
+
+/// Start taking admin commands on `listener` in a background thread.
+///
+/// As with [`start_metrics`], the listener is bound by the caller. `echo_listener` is the
+/// address of a TCP server's listener, which blocks in accept: the drain request connects
+/// to it, so the accept loop wakes up and sees the server draining.
+pub fn start_admin(
+    listener: TcpListener,
+    monitor: Arc<ServerMonitor>,
+    echo_listener: Option<SocketAddr>,
+) {
+    if let Ok(addr) = listener.local_addr() {
+        info!(address = %addr, "Admin endpoint listening on /drain");
+    }
+    serve(listener, monitor, Endpoint::Admin(echo_listener));
+}
+
+/// Answer the requests of `listener` one at a time in a background thread.
+fn serve(listener: TcpListener, monitor: Arc<ServerMonitor>, endpoint: Endpoint) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = answer(stream, &monitor, endpoint) {
+                        debug!(error = %e, endpoint = ?endpoint, "Failed to answer request");
+                    }
+                }
+                Err(e) => debug!(error = %e, endpoint = ?endpoint, "Failed to accept connection"),
+            }
+        }
+    });
+}
+
+/// Read one HTTP request and answer it.
+fn answer(mut stream: TcpStream, monitor: &ServerMonitor, endpoint: Endpoint) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_millis(REQUEST_TIMEOUT_MS)))?;
+
+    // Read until the end of the headers; the request has no body worth reading
+    let mut request = Vec::new();
+    let mut buf = [0u8; 512];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, body) = match (endpoint, method, path) {
+        (Endpoint::Metrics, "GET", "/metrics") => ("200 OK", render(&monitor.stats())),
+        (Endpoint::Admin(echo_listener), "POST", "/drain") => {
+            if !monitor.start_draining() {
+                warn!("Draining: no longer accepting new clients");
+                if let Some(addr) = echo_listener {
+                    wake_accept(addr);
+                }
+            }
+            let body = format!(
+                "Draining; {} connections active\n",
+                monitor.stats().active_connections
+            );
+            ("200 OK", body)
+        }
+        (Endpoint::Metrics, "GET" | "POST", _) => (
+            "404 Not Found",
+            "Not found; metrics are at GET /metrics\n".into(),
+        ),
+        (Endpoint::Admin(_), "GET" | "POST", _) => (
+            "404 Not Found",
+            "Not found; draining is at POST /drain\n".into(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "Only GET and POST are supported\n".into(),
+        ),
+    };
+    // One write, rather than one per formatted piece
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes())
+}
+
+/// Connect to the echo listener at `addr`, so its blocked accept returns.
+///
+/// A listener on all interfaces is reached on loopback; the connection is dropped
+/// at once, and the accept loop drops it unserved.
+fn wake_accept(addr: SocketAddr) {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    let addr = SocketAddr::new(ip, addr.port());
+    if let Err(e) = TcpStream::connect_timeout(&addr, Duration::from_millis(REQUEST_TIMEOUT_MS)) {
+        warn!(error = %e, address = %addr, "Failed to wake the accept loop for draining");
+    }
+}
+
+/// Server statistics in the Prometheus text exposition format.
+pub fn render(stats: &ServerStats) -> String {
+    let metrics: [(&str, &str, &str, String); 9] = [
+        (
+            "synapse_packets_received_total",
+            "counter",
+            "Probes received",
+            stats.packets_received.to_string(),
+        ),
+        (
+            "synapse_packets_sent_total",
+            "counter",
+            "Probes echoed back",
+            stats.packets_sent.to_string(),
+        ),
+        (
+            "synapse_errors_total",
+            "counter",
+            "Failed accepts, receives and sends, and rejected frames",
+            stats.errors.to_string(),
+        ),
+        (
+            "synapse_active_connections",
+            "gauge",
+            "TCP connections currently open (always 0 over UDP)",
+            stats.active_connections.to_string(),
+        ),
+        (
+            "synapse_draining",
+            "gauge",
+            "1 while the server drains for maintenance (POST /drain), else 0",
+            u8::from(stats.draining).to_string(),
+        ),
+        (
+            "synapse_rate_limited_total",
+            "counter",
+            "Datagrams dropped unanswered by the per-source rate limit",
+            stats.rate_limited.to_string(),
+        ),
+        (
+            "synapse_challenges_total",
+            "counter",
+            "Datagrams answered with a cookie challenge instead of their echoes",
+            stats.challenges.to_string(),
+        ),
+        (
+            "synapse_echo_bytes_total",
+            "counter",
+            "Bytes of echoed probes",
+            stats.bytes_sent.to_string(),
+        ),
+        (
+            "synapse_uptime_seconds",
+            "gauge",
+            "Seconds since the server started",
+            format!("{:.3}", stats.elapsed.as_secs_f64()),
+        ),
+    ];
+
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "# HELP {} {}.", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    let name = "synapse_rejected_frames_total";
+    let _ = writeln!(
+        out,
+        "# HELP {} Frames rejected by the frame limits, which closes a TCP connection.",
+        name
+    );
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (violation, count) in Violation::ALL.iter().zip(stats.rejected_frames) {
+        let _ = writeln!(out, "{}{{reason=\"{}\"}} {}", name, violation, count);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let stats = ServerStats {
+            packets_received: 10,
+            packets_sent: 9,
+            errors: 1,
+            bytes_sent: 576,
+            active_connections: 2,
+            rejected_frames: [3, 0, 1],
+            rate_limited: 4,
+            challenges: 5,
+            draining: true,
+            elapsed: Duration::from_millis(1500),
+        };
+        let text = render(&stats);
+        assert!(text.contains("# TYPE synapse_packets_received_total counter\n"));
+        assert!(text.contains("\nsynapse_packets_received_total 10\n"));
+        assert!(text.contains("\nsynapse_packets_sent_total 9\n"));
+        assert!(text.contains("\nsynapse_errors_total 1\n"));
+        assert!(text.contains("# TYPE synapse_active_connections gauge\n"));
+        assert!(text.contains("\nsynapse_active_connections 2\n"));
+        assert!(text.contains("\nsynapse_echo_bytes_total 576\n"));
+        assert!(text.contains("\nsynapse_rate_limited_total 4\n"));
+        assert!(text.contains("\nsynapse_challenges_total 5\n"));
+        assert!(text.contains("\nsynapse_draining 1\n"));
+        assert!(text.contains("\nsynapse_uptime_seconds 1.500\n"));
+        assert!(text.contains("\nsynapse_rejected_frames_total{reason=\"invalid\"} 3\n"));
+        assert!(text.contains("\nsynapse_rejected_frames_total{reason=\"size_mismatch\"} 1\n"));
+    }
+
+    #[test]
+    fn test_metrics_endpoint() -> io::Result<()> {
+        let monitor = Arc::new(ServerMonitor::new(100));
+        monitor.counters().increment_received();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        start_metrics(listener, Arc::clone(&monitor));
+
+        let request = |method: &str, path: &str| -> io::Result<String> {
+            let mut stream = TcpStream::connect(addr)?;
+            write!(
+                stream,
+                "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                method, path
+            )?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+            Ok(response)
+        };
+
+        let response = request("GET", "/metrics")?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("\nsynapse_packets_received_total 1\n"));
+        assert!(response.contains("\nsynapse_draining 0\n"));
+        assert!(request("GET", "/")?.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(request("DELETE", "/drain")?.starts_with("HTTP/1.1 405 "));
+        // The metrics port takes no admin command
+        assert!(request("POST", "/drain")?.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(!monitor.stats().draining);
+        Ok(())
+    }
+
+    #[test]
+    fn test_admin_endpoint() -> io::Result<()> {
+        let monitor = Arc::new(ServerMonitor::new(100));
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        // An echo listener on all interfaces, woken on loopback
+        let echo_listener = TcpListener::bind("0.0.0.0:0")?;
+        start_admin(
+            listener,
+            Arc::clone(&monitor),
+            Some(echo_listener.local_addr()?),
+        );
+
+        let request = |method: &str, path: &str| -> io::Result<String> {
+            let mut stream = TcpStream::connect(addr)?;
+            write!(
+                stream,
+                "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                method, path
+            )?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+            Ok(response)
+        };
+
+        assert!(request("GET", "/metrics")?.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(!monitor.stats().draining);
+        let response = request("POST", "/drain")?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(monitor.stats().draining);
+        let (_, peer_addr) = echo_listener.accept()?;
+        assert!(peer_addr.ip().is_loopback());
+        Ok(())
+    }
+}
@@ -1,7 +1,23 @@
 //! Server module for Synapse echo server
 
+pub mod activation;
+#[cfg(feature = "tokio")]
+pub mod async_engine;
 pub mod config;
+pub mod cookie;
+pub mod frame;
+pub mod metrics;
 pub mod monitor;
+pub mod ratelimit;
+pub mod reuseport;
 
+pub use activation::{activated_socket, ActivatedSocket};
+#[cfg(feature = "tokio")]
+pub use async_engine::Engine;
 pub use config::ServerConfig;
-pub use monitor::ServerMonitor;
+pub use cookie::SourceCookies;
+pub use frame::{FrameLimits, Violation};
+pub use metrics::{start_admin, start_metrics};
+pub use monitor::{ServerCounters, ServerMonitor, STATUS_LOG_INTERVAL_SECS};
+pub use ratelimit::SourceLimiter;
+pub use reuseport::bind_udp_group;
@@ -1,10 +1,12 @@
 //! Server monitoring and statistics display
 
+use crate::server::frame::Violation;
 use colored::*;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
+use tracing::info;
 
 /// Idle detection threshold - time without packets before marking as idle (milliseconds).
 ///
@@ -18,6 +20,9 @@ const IDLE_THRESHOLD_MS: u64 = 150;
 /// A value of 200ms provides visible feedback without being distracting.
 const BLINK_INTERVAL_MS: u64 = 200;
 
+/// Interval of the status log lines that replace the status line off a terminal (seconds).
+pub const STATUS_LOG_INTERVAL_SECS: u64 = 10;
+
 /// Monitor for tracking server packet statistics with minimal performance impact.
 ///
 /// Uses atomic counters for lock-free updates and updates the display
@@ -26,6 +31,12 @@ pub struct ServerMonitor {
     packets_received: Arc<AtomicU64>,
     packets_sent: Arc<AtomicU64>,
     errors: Arc<AtomicU64>,
+    bytes_sent: Arc<AtomicU64>,
+    active_connections: Arc<AtomicU64>,
+    rejected_frames: Arc<[AtomicU64; 3]>,
+    rate_limited: Arc<AtomicU64>,
+    challenges: Arc<AtomicU64>,
+    draining: Arc<AtomicBool>,
     start_time: Instant,
     update_interval: Duration,
 }
@@ -41,6 +52,12 @@ impl ServerMonitor {
             packets_received: Arc::new(AtomicU64::new(0)),
             packets_sent: Arc::new(AtomicU64::new(0)),
             errors: Arc::new(AtomicU64::new(0)),
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            active_connections: Arc::new(AtomicU64::new(0)),
+            rejected_frames: Arc::new(Default::default()),
+            rate_limited: Arc::new(AtomicU64::new(0)),
+            challenges: Arc::new(AtomicU64::new(0)),
+            draining: Arc::new(AtomicBool::new(false)),
             start_time: Instant::now(),
             update_interval: Duration::from_millis(update_interval_ms),
         }
@@ -55,9 +72,23 @@ impl ServerMonitor {
             packets_received: Arc::clone(&self.packets_received),
             packets_sent: Arc::clone(&self.packets_sent),
             errors: Arc::clone(&self.errors),
+            bytes_sent: Arc::clone(&self.bytes_sent),
+            active_connections: Arc::clone(&self.active_connections),
+            rejected_frames: Arc::clone(&self.rejected_frames),
+            rate_limited: Arc::clone(&self.rate_limited),
+            challenges: Arc::clone(&self.challenges),
+            draining: Arc::clone(&self.draining),
         }
     }
 
+    /// Put the server in maintenance mode: stop accepting new clients and exit once
+    /// the current ones are done.
+    ///
+    /// Returns whether the server was already draining.
+    pub fn start_draining(&self) -> bool {
+        self.draining.swap(true, Ordering::Relaxed)
+    }
+
     /// Start the background display thread.
     ///
     /// This spawns a separate thread that periodically updates the display
@@ -71,6 +102,7 @@ impl ServerMonitor {
         let packets_received = Arc::clone(&self.packets_received);
         let packets_sent = Arc::clone(&self.packets_sent);
         let errors = Arc::clone(&self.errors);
+        let draining = Arc::clone(&self.draining);
         let update_interval = self.update_interval;
 
         thread::spawn(move || {
@@ -120,13 +152,56 @@ impl ServerMonitor {
                 let indicator = Self::render_indicator(is_idle, blink_state);
 
                 // Format and display status line
-                Self::display_status_line(&indicator, is_idle, received, sent, error_count);
+                Self::display_status_line(
+                    &indicator,
+                    is_idle,
+                    draining.load(Ordering::Relaxed),
+                    received,
+                    sent,
+                    error_count,
+                );
 
                 last_received = received;
             }
         });
     }
 
+    /// Start the background status log thread, for when stdout is not a terminal.
+    ///
+    /// Instead of redrawing the status line, this logs the counters every `interval`
+    /// in which packets arrived or errors occurred; an idle server logs nothing.
+    pub fn start_log(&self, interval: Duration) {
+        let packets_received = Arc::clone(&self.packets_received);
+        let packets_sent = Arc::clone(&self.packets_sent);
+        let errors = Arc::clone(&self.errors);
+        let active_connections = Arc::clone(&self.active_connections);
+        let draining = Arc::clone(&self.draining);
+
+        thread::spawn(move || {
+            let mut last = (0u64, 0u64);
+            loop {
+                thread::sleep(interval);
+
+                let received = packets_received.load(Ordering::Relaxed);
+                let error_count = errors.load(Ordering::Relaxed);
+                if (received, error_count) == last {
+                    continue;
+                }
+                info!(
+                    received,
+                    sent = packets_sent.load(Ordering::Relaxed),
+                    errors = error_count,
+                    received_per_sec =
+                        received.saturating_sub(last.0) as f64 / interval.as_secs_f64(),
+                    active_connections = active_connections.load(Ordering::Relaxed),
+                    draining = draining.load(Ordering::Relaxed),
+                    "Server status"
+                );
+                last = (received, error_count);
+            }
+        });
+    }
+
     /// Renders the activity indicator based on current state.
     ///
     /// # Arguments
@@ -156,17 +231,23 @@ impl ServerMonitor {
     ///
     /// * `indicator` - The activity indicator string
     /// * `is_idle` - Whether the server is currently idle
+    /// * `draining` - Whether the server is draining for maintenance
     /// * `received` - Total packets received
     /// * `sent` - Total packets sent
     /// * `error_count` - Total errors encountered
     fn display_status_line(
         indicator: &str,
         is_idle: bool,
+        draining: bool,
         received: u64,
         sent: u64,
         error_count: u64,
     ) {
-        let status = if is_idle { "IDLE" } else { "ACTIVE" };
+        let status = match (draining, is_idle) {
+            (true, _) => "DRAINING",
+            (false, true) => "IDLE",
+            (false, false) => "ACTIVE",
+        };
         print!(
             "\r{} [{}] Received: {} | Sent: {} | Errors: {}",
             indicator, status, received, sent, error_count
@@ -185,6 +266,13 @@ impl ServerMonitor {
             packets_received: received,
             packets_sent: sent,
             errors,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            rejected_frames: Violation::ALL
+                .map(|violation| self.rejected_frames[violation as usize].load(Ordering::Relaxed)),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            challenges: self.challenges.load(Ordering::Relaxed),
+            draining: self.draining.load(Ordering::Relaxed),
             elapsed,
         }
     }
@@ -197,6 +285,12 @@ pub struct ServerCounters {
     packets_received: Arc<AtomicU64>,
     packets_sent: Arc<AtomicU64>,
     errors: Arc<AtomicU64>,
+    bytes_sent: Arc<AtomicU64>,
+    active_connections: Arc<AtomicU64>,
+    rejected_frames: Arc<[AtomicU64; 3]>,
+    rate_limited: Arc<AtomicU64>,
+    challenges: Arc<AtomicU64>,
+    draining: Arc<AtomicBool>,
 }
 
 impl ServerCounters {
@@ -220,6 +314,55 @@ impl ServerCounters {
     pub fn increment_error(&self) {
         self.errors.fetch_add(1, Ordering::Relaxed);
     }
+
+    /// Add the size of an echoed packet to the bytes sent counter.
+    #[inline]
+    pub fn add_bytes_sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count a newly accepted connection as active.
+    #[inline]
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stop counting a closed connection as active.
+    #[inline]
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Count a rejected frame, as an error and by the reason it was rejected.
+    #[inline]
+    pub fn reject_frame(&self, violation: Violation) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.rejected_frames[violation as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a datagram dropped unanswered by the per-source rate limit.
+    #[inline]
+    pub fn increment_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a datagram answered with a cookie challenge instead of its echoes.
+    #[inline]
+    pub fn increment_challenges(&self) {
+        self.challenges.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of TCP connections currently open.
+    #[inline]
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// Whether the server is draining, see [`ServerMonitor::start_draining`].
+    #[inline]
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
 }
 
 /// Final server statistics.
@@ -227,6 +370,18 @@ pub struct ServerStats {
     pub packets_received: u64,
     pub packets_sent: u64,
     pub errors: u64,
+    /// Bytes of echoed packets
+    pub bytes_sent: u64,
+    /// TCP connections currently open (always 0 over UDP)
+    pub active_connections: u64,
+    /// Rejected frames by reason, in the order of [`Violation::ALL`]
+    pub rejected_frames: [u64; 3],
+    /// Datagrams dropped by the per-source rate limit (always 0 over TCP)
+    pub rate_limited: u64,
+    /// Datagrams answered with a cookie challenge (always 0 over TCP)
+    pub challenges: u64,
+    /// Whether the server is draining for maintenance
+    pub draining: bool,
     pub elapsed: Duration,
 }
 
@@ -249,11 +404,29 @@ mod tests {
         counters.increment_received();
         counters.increment_sent();
         counters.increment_error();
+        counters.add_bytes_sent(64);
+        counters.connection_opened();
+        counters.connection_opened();
+        counters.connection_closed();
+        counters.reject_frame(Violation::Oversized);
+        counters.increment_rate_limited();
+        counters.increment_challenges();
 
         let stats = monitor.stats();
         assert_eq!(stats.packets_received, 1);
         assert_eq!(stats.packets_sent, 1);
-        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.errors, 2);
+        assert_eq!(stats.bytes_sent, 64);
+        assert_eq!(stats.active_connections, 1);
+        assert_eq!(stats.rejected_frames, [0, 1, 0]);
+        assert_eq!(stats.rate_limited, 1);
+        assert_eq!(stats.challenges, 1);
+        assert!(!stats.draining);
+
+        assert!(!monitor.start_draining());
+        assert!(monitor.start_draining());
+        assert!(counters.is_draining());
+        assert_eq!(counters.active_connections(), 1);
     }
 
     #[test]
@@ -0,0 +1,128 @@
+//! Per-source reply rate cap of the UDP server
+//!
+//! The cookie exchange (see [`crate::server::cookie`]) keeps spoofed sources from
+//! getting echoes, but a client that passed it may still ask for as many as it likes, up
+//! to [`MAX_REPLIES`](crate::protocol::MAX_REPLIES) per datagram (see `--transaction`).
+//! With `--udp-rate-limit` every source IP address gets a token bucket of that many
+//! replies per second (with a burst of one second's worth); a datagram costs one token
+//! per reply it asks for, and those beyond the bucket are dropped unanswered.
+//!
+//! Challenges are capped the same way, at one token each, so a spoofed flood is not
+//! reflected faster than the limit either. They are charged to a limiter of their own:
+//! only datagrams carrying their source's cookie reach the limiter of echoes, so a
+//! spoofed flood cannot fill its table of sources. Both tables are bounded all the same:
+//! sources idle long enough to have refilled their bucket are forgotten, and while a
+//! table is full, datagrams from new sources are dropped.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Most sources tracked at once
+const MAX_TRACKED_SOURCES: usize = 65_536;
+
+/// Time an empty bucket takes to refill, and the interval between sweeps of idle
+/// sources out of the table
+const REFILL_TIME: Duration = Duration::from_secs(1);
+
+/// Token bucket of one source
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Caps the reply rate of every source address
+#[derive(Debug)]
+pub struct SourceLimiter {
+    /// Replies per second per source, and the size of a burst
+    rate: f64,
+    sources: HashMap<IpAddr, Bucket>,
+    last_purge: Instant,
+}
+
+impl SourceLimiter {
+    /// Allow each source `rate` replies per second, in bursts of up to `rate`
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.max(1) as f64,
+            sources: HashMap::new(),
+            last_purge: Instant::now(),
+        }
+    }
+
+    /// Whether a datagram from `source` arriving at `now` may be answered with `replies`
+    /// replies
+    ///
+    /// A datagram asking for more replies than the rate is never answered.
+    pub fn allow(&mut self, source: IpAddr, replies: u32, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last_purge) >= REFILL_TIME {
+            self.purge(now);
+        }
+        if !self.sources.contains_key(&source) && self.sources.len() >= MAX_TRACKED_SOURCES {
+            return false;
+        }
+
+        let rate = self.rate;
+        let bucket = self.sources.entry(source).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.updated = now;
+        let cost = replies as f64;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of sources currently tracked
+    pub fn tracked_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Forget the sources whose bucket has refilled, which a new bucket would equal
+    fn purge(&mut self, now: Instant) {
+        self.sources
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < REFILL_TIME);
+        self.last_purge = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_source_limiter() {
+        let mut limiter = SourceLimiter::new(10);
+        let start = Instant::now();
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        // A burst of one second's worth, then nothing until tokens refill
+        let allowed = (0..15).filter(|_| limiter.allow(a, 1, start)).count();
+        assert_eq!(allowed, 10);
+        assert!(limiter.allow(b, 1, start), "sources are limited separately");
+        assert!(!limiter.allow(a, 1, start + Duration::from_millis(50)));
+        assert!(limiter.allow(a, 1, start + Duration::from_millis(150)));
+
+        // Every reply a datagram asks for costs a token
+        assert!(limiter.allow(b, 8, start));
+        assert!(!limiter.allow(b, 2, start));
+        assert!(limiter.allow(b, 1, start));
+
+        // Idle sources are forgotten once their bucket is full again
+        assert_eq!(limiter.tracked_sources(), 2);
+        limiter.allow(a, 1, start + Duration::from_secs(3));
+        assert_eq!(limiter.tracked_sources(), 1);
+
+        // More replies than the rate are never sent, however long the source waited
+        assert!(!limiter.allow(b, 11, start + Duration::from_secs(10)));
+    }
+}
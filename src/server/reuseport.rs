@@ -0,0 +1,159 @@
+//! UDP echo sockets sharing one port
+//!
+//! One UDP socket is served by one thread, which tops out at a few hundred thousand
+//! datagrams per second. With `--udp-sockets N` the server binds N sockets to the same
+//! address with `SO_REUSEPORT` and serves each from a thread of its own. The kernel
+//! spreads incoming datagrams over the sockets by a hash of their addresses and ports,
+//! so the probes of one client flow all reach the same socket, while many flows spread
+//! over the cores.
+//!
+//! The group only scales with the number of flows: a single client is served by a
+//! single socket whatever N is.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+/// Bind `count` UDP sockets to `addr`, all sharing its port with `SO_REUSEPORT`
+///
+/// With port 0 the first socket picks the port and the others join it.
+pub fn bind_udp_group<A: ToSocketAddrs>(addr: A, count: usize) -> io::Result<Vec<UdpSocket>> {
+    let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")
+    })?;
+    let first = imp::bind_reuseport(addr)?;
+    let addr = first.local_addr()?;
+    let mut sockets = vec![first];
+    for _ in 1..count {
+        sockets.push(imp::bind_reuseport(addr)?);
+    }
+    Ok(sockets)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    /// Bind a UDP socket to `addr` with `SO_REUSEPORT` set, so others can join it
+    pub(super) fn bind_reuseport(addr: SocketAddr) -> io::Result<UdpSocket> {
+        let family = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        // SAFETY: plain socket(2) call; the descriptor is owned right below
+        let fd = unsafe { libc::socket(family, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just created and nothing else owns it
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let one: libc::c_int = 1;
+        // SAFETY: `one` is a valid c_int of the given size
+        let rc = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_REUSEPORT,
+                (&one as *const libc::c_int).cast(),
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let rc = match addr {
+            SocketAddr::V4(addr) => {
+                // SAFETY: sockaddr_in is plain data; all-zero is valid for it
+                let mut sin: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                // SAFETY: `sin` is a sockaddr_in of the given size
+                unsafe {
+                    libc::bind(
+                        fd.as_raw_fd(),
+                        (&sin as *const libc::sockaddr_in).cast(),
+                        std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    )
+                }
+            }
+            SocketAddr::V6(addr) => {
+                // SAFETY: sockaddr_in6 is plain data; all-zero is valid for it
+                let mut sin6: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                // SAFETY: `sin6` is a sockaddr_in6 of the given size
+                unsafe {
+                    libc::bind(
+                        fd.as_raw_fd(),
+                        (&sin6 as *const libc::sockaddr_in6).cast(),
+                        std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    )
+                }
+            }
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd.into())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+
+    pub(super) fn bind_reuseport(_addr: SocketAddr) -> io::Result<UdpSocket> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "several UDP sockets on one port require Linux",
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_group_shares_the_port_and_the_load() -> io::Result<()> {
+        let group = bind_udp_group("127.0.0.1:0", 4)?;
+        let addr = group[0].local_addr()?;
+        for socket in &group {
+            assert_eq!(socket.local_addr()?, addr);
+            socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+        }
+
+        // Each sender is a flow of its own, hashed to one socket of the group
+        let senders = (0..32)
+            .map(|_| UdpSocket::bind("127.0.0.1:0"))
+            .collect::<io::Result<Vec<_>>>()?;
+        for sender in &senders {
+            sender.send_to(b"probe", addr)?;
+        }
+        let mut received = Vec::new();
+        for socket in &group {
+            let mut count = 0;
+            let mut buf = [0u8; 16];
+            while socket.recv_from(&mut buf).is_ok() {
+                count += 1;
+            }
+            received.push(count);
+        }
+        assert_eq!(received.iter().sum::<usize>(), senders.len());
+        assert!(
+            received.iter().filter(|count| **count > 0).count() > 1,
+            "{:?}",
+            received
+        );
+        Ok(())
+    }
+}
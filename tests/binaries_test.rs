@@ -0,0 +1,119 @@
+use std::net::UdpSocket;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use synapse::protocol::{Packet, PACKET_SIZE};
+
+/// Test helper: the server binary echoing UDP on a free loopback port, killed on drop
+struct UdpServer {
+    child: Child,
+    addr: String,
+}
+
+impl UdpServer {
+    fn start(args: &[&str]) -> Self {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .expect("Failed to find a free port")
+            .port();
+        let child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .args(["--bind", "127.0.0.1", "--port", &port.to_string()])
+            .args(["--transport", "udp", "--quiet", "--log-level", "error"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start the server");
+        let server = Self {
+            child,
+            addr: format!("127.0.0.1:{}", port),
+        };
+        server.wait_ready();
+        server
+    }
+
+    /// Send hellos until the server challenges one
+    fn wait_ready(&self) {
+        let socket = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind probe socket");
+        socket
+            .connect(&self.addr)
+            .expect("Failed to connect probe socket");
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut buf = [0u8; PACKET_SIZE];
+        while Instant::now() < deadline {
+            let answer = socket
+                .send(&Packet::hello().encode())
+                .and_then(|_| socket.recv(&mut buf));
+            if let Ok(len) = answer {
+                if Packet::decode(&buf[..len]).is_ok_and(|packet| packet.is_challenge()) {
+                    return;
+                }
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Server at {} did not come up", self.addr);
+    }
+
+    /// Run the client binary against the server, returning its exit code
+    fn run_client(&self, args: &[&str]) -> i32 {
+        let status = Command::new(env!("CARGO_BIN_EXE_client"))
+            .args(["--server", &self.addr, "--transport", "udp"])
+            .args(["--quiet", "--log-level", "error"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("Failed to run the client");
+        status.code().expect("Client killed by a signal")
+    }
+}
+
+impl Drop for UdpServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_udp_cookie_exchange_loses_no_probe() {
+    let server = UdpServer::start(&[]);
+
+    // Without warmup the very first probe is measured: the hello on connecting got the
+    // cookie, so the server echoes it rather than challenging it
+    let strict = ["--warmup", "0", "--clock-sync", "0", "--strict-loss"];
+    assert_eq!(
+        server.run_client(&[&strict[..], &["--packets", "50"]].concat()),
+        0
+    );
+
+    // Every flow is a socket of its own, each with its own cookie
+    let flows = ["--flows", "4", "--packets", "200", "--max-loss", "0"];
+    assert_eq!(server.run_client(&[&strict[..], &flows[..]].concat()), 0);
+}
+
+#[test]
+fn test_udp_rate_limit_caps_challenges() {
+    let server = UdpServer::start(&["--udp-rate-limit", "20"]);
+
+    // A source without the cookie, as a spoofed one, gets no more challenges than the
+    // limit, even though it never reaches the limit of echoes
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(&server.addr).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    for _ in 0..100 {
+        socket.send(&Packet::hello().encode()).unwrap();
+    }
+    let mut buf = [0u8; PACKET_SIZE];
+    let mut challenges = 0;
+    while socket.recv(&mut buf).is_ok() {
+        challenges += 1;
+    }
+    // Its bucket refills while the datagrams go out, and readiness took a challenge
+    assert!((10..=25).contains(&challenges), "{} challenges", challenges);
+}
@@ -1,33 +1,35 @@
 use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::net::{TcpListener, UdpSocket};
 use std::thread;
 use std::time::Duration;
+use synapse::client::warmup::{
+    AUTO_WARMUP_MAX_PROBES, DEFAULT_WARMUP_TOLERANCE_PCT, SETTLING_WINDOW,
+};
 use synapse::client::Result;
 use synapse::client::{
-    measurement_phase, warmup_phase, Config, NetworkSocket, Statistics, TcpNetworkSocket,
+    measurement_phase, measurement_phase_with_options, warmup_phase, Config, MeasurementOptions,
+    NetworkSocket, Statistics, TcpNetworkSocket, UdpNetworkSocket, Warmup,
 };
+use synapse::protocol::{Packet, PACKET_SIZE};
 
 /// Test helper: Start a simple echo server
 fn start_test_server(port: u16) -> TcpListener {
     let addr = format!("127.0.0.1:{}", port);
-    let listener = TcpListener::bind(&addr).expect("Failed to bind test server");
-    listener
+    TcpListener::bind(&addr).expect("Failed to bind test server")
 }
 
 /// Test helper: Echo server that responds to packets
 fn run_echo_server(listener: TcpListener) {
-    for stream in listener.incoming() {
-        if let Ok(mut stream) = stream {
-            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
-            let mut buf = [0u8; 64];
-            loop {
-                match stream.read(&mut buf) {
-                    Ok(0) => break, // Connection closed
-                    Ok(len) => {
-                        let _ = stream.write_all(&buf[..len]);
-                    }
-                    Err(_) => break,
+    for mut stream in listener.incoming().flatten() {
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+        let mut buf = [0u8; 64];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break, // Connection closed
+                Ok(len) => {
+                    let _ = stream.write_all(&buf[..len]);
                 }
+                Err(_) => break,
             }
         }
     }
@@ -38,12 +40,13 @@ fn test_config_validation() {
     let mut config = Config {
         server: "127.0.0.1:8080".to_string(),
         packets: 0,
-        warmup: 10,
+        warmup: Warmup::Count(10),
         update: 10,
         timeout_ms: 100,
         quiet: false,
         log_level: "info".to_string(),
         log_format: "text".to_string(),
+        ..Config::default()
     };
 
     // Should fail validation
@@ -59,12 +62,13 @@ fn test_config_timeout() {
     let config = Config {
         server: "127.0.0.1:8080".to_string(),
         packets: 10,
-        warmup: 5,
+        warmup: Warmup::Count(5),
         update: 5,
         timeout_ms: 500,
         quiet: false,
         log_level: "info".to_string(),
         log_format: "text".to_string(),
+        ..Config::default()
     };
 
     let timeout = config.timeout();
@@ -91,7 +95,13 @@ fn test_end_to_end_measurement() -> Result<()> {
     client_socket.set_timeout(Duration::from_millis(1000))?;
 
     // Run warmup phase (quiet mode for tests)
-    warmup_phase(&mut client_socket, 5, true)?;
+    warmup_phase(
+        &mut client_socket,
+        Warmup::Count(5),
+        DEFAULT_WARMUP_TOLERANCE_PCT,
+        PACKET_SIZE,
+        true,
+    )?;
 
     // Run measurement phase with small packet count (quiet mode for tests)
     let result = measurement_phase(&mut client_socket, 10, 5, true)?;
@@ -114,6 +124,103 @@ fn test_end_to_end_measurement() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_end_to_end_auto_warmup() -> Result<()> {
+    let server_listener = start_test_server(0);
+    let server_addr = server_listener.local_addr().unwrap();
+    thread::spawn(move || run_echo_server(server_listener));
+
+    // Loopback settles as soon as two windows compare, given a wide enough tolerance
+    let mut client_socket = TcpNetworkSocket::connect(&server_addr.to_string())?;
+    client_socket.set_timeout(Duration::from_millis(1000))?;
+    let sent = warmup_phase(&mut client_socket, Warmup::Auto, 100.0, PACKET_SIZE, true)?;
+    assert!(sent >= 2 * SETTLING_WINDOW, "{}", sent);
+    assert!(sent < AUTO_WARMUP_MAX_PROBES, "{}", sent);
+
+    let result = measurement_phase(&mut client_socket, 10, 5, true)?;
+    assert_eq!(result.latencies.len(), 10);
+    Ok(())
+}
+
+#[test]
+fn test_end_to_end_padded_packets() -> Result<()> {
+    let server_listener = start_test_server(0);
+    let server_addr = server_listener.local_addr().unwrap();
+    thread::spawn(move || run_echo_server(server_listener));
+
+    // The echo server returns a 1400-byte probe in 64-byte pieces
+    let mut client_socket = TcpNetworkSocket::connect(&server_addr.to_string())?;
+    client_socket.set_timeout(Duration::from_millis(1000))?;
+    warmup_phase(
+        &mut client_socket,
+        Warmup::Count(5),
+        DEFAULT_WARMUP_TOLERANCE_PCT,
+        1400,
+        true,
+    )?;
+    let mut options = MeasurementOptions::new(10, 5, true);
+    options.payload_size = 1400;
+    let result = measurement_phase_with_options(&mut client_socket, options)?;
+
+    assert_eq!(result.lost_packets, 0);
+    assert_eq!(result.sequences, (0..10).collect::<Vec<u64>>());
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_end_to_end_kernel_timestamps() -> Result<()> {
+    let server_listener = start_test_server(0);
+    let server_addr = server_listener.local_addr().unwrap();
+    thread::spawn(move || run_echo_server(server_listener));
+
+    // Replies arrive in several segments; the reply still gets one receive timestamp
+    let mut client_socket = TcpNetworkSocket::connect(&server_addr.to_string())?;
+    client_socket.enable_kernel_timestamps()?;
+    client_socket.set_timeout(Duration::from_millis(1000))?;
+    let mut options = MeasurementOptions::new(10, 5, true);
+    options.payload_size = 1400;
+    let result = measurement_phase_with_options(&mut client_socket, options)?;
+
+    assert_eq!(result.kernel_timings.len(), 10);
+    for timing in &result.kernel_timings {
+        assert!(timing.kernel_rtt_ns > 0);
+        assert!(timing.kernel_rtt_ns <= timing.rtt_ns, "{:?}", timing);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_udp_end_to_end_discards_stale_replies() -> Result<()> {
+    let server = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind test server");
+    let server_addr = server.local_addr().unwrap();
+
+    // Echo server whose reply to the first probe arrives after the client gave up on it;
+    // the hello before it asks for no reply
+    thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let mut first = true;
+        while let Ok((len, peer)) = server.recv_from(&mut buf) {
+            if first && Packet::replies_requested(&buf[..len]) > 0 {
+                thread::sleep(Duration::from_millis(150));
+                first = false;
+            }
+            let _ = server.send_to(&buf[..len], peer);
+        }
+    });
+
+    let mut client_socket = UdpNetworkSocket::connect(&server_addr.to_string())?;
+    client_socket.set_timeout(Duration::from_millis(100))?;
+    let result = measurement_phase(&mut client_socket, 10, 5, true)?;
+
+    // Only the delayed probe is lost; its late reply is not taken for the next one
+    assert_eq!(result.total_packets, 10);
+    assert_eq!(result.lost_packets, 1);
+    assert_eq!(result.lost_sequences, vec![0]);
+    assert_eq!(result.latencies.len(), 9);
+    Ok(())
+}
+
 #[test]
 fn test_measurement_with_lost_packets() -> Result<()> {
     // This test verifies that lost packets are handled correctly